@@ -0,0 +1,138 @@
+//! Affected-cell tracking so embedders can repaint only what an edit changed.
+//!
+//! An edit can change the display of the edited cells, every formula that
+//! (transitively) depends on them, and every cell those formulas spill into,
+//! both the old spill range (now cleared) and the new one.
+
+use super::{Document, UndoEntry};
+use crate::error::Result;
+use gridline_engine::engine::{CellRef, CellType};
+use std::collections::{HashSet, VecDeque};
+
+impl Document {
+    /// Set a cell from input like [`Document::set_cell_from_input`] and return
+    /// every cell whose displayed value may have changed, sorted row-major.
+    pub fn apply_edit(&mut self, cell_ref: CellRef, input: &str) -> Result<Vec<CellRef>> {
+        let changed = [cell_ref.clone()];
+        let ((), affected) =
+            self.affected_by(&changed, |doc| doc.set_cell_from_input(cell_ref, input))?;
+        Ok(affected)
+    }
+
+    /// Run `edit`, which writes to the `changed` cells (a paste, undo, ...), and
+    /// return its result with every cell whose displayed value may have changed.
+    ///
+    /// Affected formulas are evaluated afterwards so their new spill ranges are
+    /// known; cells a previous spill covered are included too.
+    pub fn affected_by<T>(
+        &mut self,
+        changed: &[CellRef],
+        edit: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<(T, Vec<CellRef>)> {
+        let mut affected = self.collect_affected(changed);
+        let value = edit(self)?;
+
+        for cell_ref in self.collect_affected(changed) {
+            let is_formula = self
+                .grid
+                .get(&cell_ref)
+                .is_some_and(|cell| matches!(cell.contents, CellType::Script(_)));
+            if is_formula {
+                self.get_cell_display(&cell_ref);
+            }
+        }
+        affected.extend(self.collect_affected(changed));
+
+        let mut affected: Vec<CellRef> = affected.into_iter().collect();
+        affected.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+        Ok((value, affected))
+    }
+
+    /// Cells the next [`Document::undo`] would write, for use with [`Document::affected_by`].
+    pub fn undo_cells(&self) -> Vec<CellRef> {
+        self.undo_stack.last().map(entry_cells).unwrap_or_default()
+    }
+
+    /// Cells the next [`Document::redo`] would write, for use with [`Document::affected_by`].
+    pub fn redo_cells(&self) -> Vec<CellRef> {
+        self.redo_stack.last().map(entry_cells).unwrap_or_default()
+    }
+
+    /// `changed` plus their transitive dependents and current spill outputs.
+    fn collect_affected(&self, changed: &[CellRef]) -> HashSet<CellRef> {
+        let mut affected = HashSet::new();
+        let mut queue: VecDeque<CellRef> = changed.iter().cloned().collect();
+        // Writing into a spill output invalidates the formula that spilled there.
+        queue.extend(
+            changed
+                .iter()
+                .filter_map(|cell_ref| self.spill_sources.get(cell_ref).map(|src| src.clone())),
+        );
+        while let Some(cell_ref) = queue.pop_front() {
+            if !affected.insert(cell_ref.clone()) {
+                continue;
+            }
+            if let Some(deps) = self.dependents.get(&cell_ref) {
+                queue.extend(deps.iter().cloned());
+            }
+        }
+
+        let spilled: Vec<CellRef> = self
+            .spill_sources
+            .iter()
+            .filter(|entry| affected.contains(entry.value()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        affected.extend(spilled);
+        affected
+    }
+}
+
+fn entry_cells(entry: &UndoEntry) -> Vec<CellRef> {
+    match entry {
+        UndoEntry::Single(action) => vec![action.cell_ref.clone()],
+        UndoEntry::Batch(actions) | UndoEntry::Layout { actions, .. } => {
+            actions.iter().map(|a| a.cell_ref.clone()).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_edit_reports_dependents_and_spills() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "1").unwrap();
+        doc.set_cell_from_input(CellRef::new(1, 0), "=A1 + 1")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(2, 0), "=B1 * 2")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(3, 0), "=SPILL(0..A1.to_int())")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(4, 4), "99").unwrap();
+        let _ = doc.get_cell_display(&CellRef::new(3, 0));
+
+        // D1 spills 0..1 (just D1); after A1 = 3 it spills into D2 and D3.
+        let affected = doc.apply_edit(CellRef::new(0, 0), "3").unwrap();
+        assert_eq!(
+            affected,
+            vec![
+                CellRef::new(0, 0),
+                CellRef::new(1, 0),
+                CellRef::new(2, 0),
+                CellRef::new(3, 0),
+                CellRef::new(3, 1),
+                CellRef::new(3, 2),
+            ]
+        );
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "8");
+
+        // Undo shrinks the spill again; the cleared cells are still reported.
+        let changed = doc.undo_cells();
+        let ((), affected) = doc.affected_by(&changed, |doc| doc.undo()).unwrap();
+        assert!(affected.contains(&CellRef::new(3, 2)));
+        assert!(!affected.contains(&CellRef::new(4, 4)));
+    }
+}
@@ -0,0 +1,203 @@
+//! Alignment overrides, stored as rectangles so aligning a whole column
+//! costs one entry. Later rectangles take precedence; a cleared one (`None`)
+//! hides those beneath it. Cells without one keep the front end's default
+//! placement.
+
+use super::Document;
+use super::merge::shift_span;
+use gridline_engine::engine::{CellRef, ShiftOperation};
+
+/// An alignment (or, with `None`, a cleared one) over the rectangle from
+/// top-left to bottom-right.
+pub type AlignedRange = (CellRef, CellRef, Option<Align>);
+
+fn covers(outer: &AlignedRange, inner: &AlignedRange) -> bool {
+    outer.0.col <= inner.0.col
+        && outer.0.row <= inner.0.row
+        && inner.1.col <= outer.1.col
+        && inner.1.row <= outer.1.row
+}
+
+fn overlaps(a: &AlignedRange, b: &AlignedRange) -> bool {
+    a.0.col <= b.1.col && b.0.col <= a.1.col && a.0.row <= b.1.row && b.0.row <= a.1.row
+}
+
+/// Horizontal placement of a cell's value within its column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    /// Parse `left`, `center` (or `centre`) or `right`, case-insensitively.
+    pub fn parse(name: &str) -> Option<Align> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "left" => Some(Align::Left),
+            "center" | "centre" => Some(Align::Center),
+            "right" => Some(Align::Right),
+            _ => None,
+        }
+    }
+
+    /// Name as written in `.grd` files and accepted by [`Align::parse`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Right => "right",
+        }
+    }
+
+    /// Pad `text` with spaces to `width` characters; text at least that wide
+    /// is returned unchanged. Centering puts any odd space on the right.
+    pub fn pad(self, text: &str, width: usize) -> String {
+        let len = text.chars().count();
+        let Some(space) = width.checked_sub(len).filter(|&space| space > 0) else {
+            return text.to_string();
+        };
+        let left = match self {
+            Align::Left => 0,
+            Align::Center => space / 2,
+            Align::Right => space,
+        };
+        format!("{}{}{}", " ".repeat(left), text, " ".repeat(space - left))
+    }
+}
+
+impl Document {
+    /// Set (or with `None`, clear) the alignment of every cell in the
+    /// rectangle between two corners. Returns the number of cells covered.
+    pub fn set_alignment(&mut self, a: &CellRef, b: &CellRef, align: Option<Align>) -> usize {
+        let (min_col, max_col) = (a.col.min(b.col), a.col.max(b.col));
+        let (min_row, max_row) = (a.row.min(b.row), a.row.max(b.row));
+        let range = (
+            CellRef::new(min_col, min_row),
+            CellRef::new(max_col, max_row),
+            align,
+        );
+        let old_layout = self.layout();
+        // Rectangles the new one hides entirely are no longer needed
+        self.cell_alignments
+            .retain(|existing| !covers(&range, existing));
+        if align.is_some() || self.cell_alignments.iter().any(|r| overlaps(r, &range)) {
+            self.cell_alignments.push(range);
+        }
+        self.push_undo_with_layout(Vec::new(), old_layout);
+        self.modified = true;
+        (max_col - min_col + 1) * (max_row - min_row + 1)
+    }
+
+    /// The alignment override of `cell`, if any.
+    pub fn alignment(&self, cell: &CellRef) -> Option<Align> {
+        self.cell_alignments
+            .iter()
+            .rev()
+            .find(|(top_left, bottom_right, _)| {
+                (top_left.col..=bottom_right.col).contains(&cell.col)
+                    && (top_left.row..=bottom_right.row).contains(&cell.row)
+            })
+            .and_then(|(_, _, align)| *align)
+    }
+
+    /// Keep alignments attached to their cells across a row/column insert or
+    /// delete. A rectangle grows when a row or column is inserted inside it
+    /// and is dropped once all of its cells are deleted.
+    pub(crate) fn shift_alignments(&mut self, op: ShiftOperation) {
+        let alignments = std::mem::take(&mut self.cell_alignments);
+        self.cell_alignments = alignments
+            .into_iter()
+            .filter_map(|(top_left, bottom_right, align)| {
+                let (rows, cols) = match op {
+                    ShiftOperation::InsertRow(at) | ShiftOperation::DeleteRow(at) => (
+                        shift_span(
+                            top_left.row,
+                            bottom_right.row,
+                            at,
+                            matches!(op, ShiftOperation::InsertRow(_)),
+                        )?,
+                        (top_left.col, bottom_right.col),
+                    ),
+                    ShiftOperation::InsertColumn(at) | ShiftOperation::DeleteColumn(at) => (
+                        (top_left.row, bottom_right.row),
+                        shift_span(
+                            top_left.col,
+                            bottom_right.col,
+                            at,
+                            matches!(op, ShiftOperation::InsertColumn(_)),
+                        )?,
+                    ),
+                };
+                Some((
+                    CellRef::new(cols.0, rows.0),
+                    CellRef::new(cols.1, rows.1),
+                    align,
+                ))
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pad_places_narrow_value_in_column() {
+        assert_eq!(Align::Left.pad("42", 6), "42    ");
+        assert_eq!(Align::Center.pad("42", 6), "  42  ");
+        assert_eq!(Align::Center.pad("42", 7), "  42   ");
+        assert_eq!(Align::Right.pad("42", 6), "    42");
+        // Values as wide as the column are untouched
+        assert_eq!(Align::Right.pad("123456", 6), "123456");
+        assert_eq!(Align::Center.pad("1234567", 6), "1234567");
+    }
+
+    #[test]
+    fn test_alignment_follows_row_and_column_shifts() {
+        let mut doc = Document::new();
+        doc.set_alignment(&CellRef::new(1, 1), &CellRef::new(1, 2), Some(Align::Right));
+        assert_eq!(doc.alignment(&CellRef::new(1, 2)), Some(Align::Right));
+
+        doc.insert_row(0);
+        assert_eq!(doc.alignment(&CellRef::new(1, 1)), None);
+        assert_eq!(doc.alignment(&CellRef::new(1, 2)), Some(Align::Right));
+        assert_eq!(doc.alignment(&CellRef::new(1, 3)), Some(Align::Right));
+
+        doc.delete_column(1);
+        assert!(doc.cell_alignments.is_empty());
+
+        doc.set_alignment(
+            &CellRef::new(0, 0),
+            &CellRef::new(0, 0),
+            Some(Align::Center),
+        );
+        doc.set_alignment(&CellRef::new(0, 0), &CellRef::new(0, 0), None);
+        assert_eq!(doc.alignment(&CellRef::new(0, 0)), None);
+    }
+
+    #[test]
+    fn test_column_alignment_is_one_undoable_range() {
+        let mut doc = Document::new();
+        let count = doc.set_alignment(
+            &CellRef::new(1, 0),
+            &CellRef::new(1, 1_048_575),
+            Some(Align::Right),
+        );
+        assert_eq!(count, 1_048_576);
+        assert_eq!(doc.cell_alignments.len(), 1);
+
+        // Clearing part of it hides the alignment there only
+        doc.set_alignment(&CellRef::new(1, 4), &CellRef::new(1, 5), None);
+        assert_eq!(doc.alignment(&CellRef::new(1, 5)), None);
+        assert_eq!(doc.alignment(&CellRef::new(1, 6)), Some(Align::Right));
+
+        doc.undo().unwrap();
+        assert_eq!(doc.alignment(&CellRef::new(1, 5)), Some(Align::Right));
+        doc.undo().unwrap();
+        assert!(doc.cell_alignments.is_empty());
+        doc.redo().unwrap();
+        assert_eq!(doc.alignment(&CellRef::new(1, 900_000)), Some(Align::Right));
+    }
+}
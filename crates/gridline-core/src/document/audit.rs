@@ -0,0 +1,198 @@
+//! Formula audit: warnings about formulas that are likely mistakes, such as
+//! references to blank cells or `#REF!` left behind by a deleted row/column.
+
+use super::Document;
+use gridline_engine::engine::{CellRef, CellType, expand_row_references, extract_references};
+use std::fmt;
+
+/// What an [`AuditWarning`] found in a formula.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditIssue {
+    /// The formula references a cell with nothing in it
+    EmptyReference(CellRef),
+    /// The formula references a range (by its corners) with nothing in it
+    EmptyRange(CellRef, CellRef),
+    /// The formula references its own cell
+    SelfReference,
+    /// The formula contains `#REF!` from a deleted row or column
+    BrokenReference,
+}
+
+/// A suspicious formula found by [`Document::audit`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditWarning {
+    /// The formula cell
+    pub cell: CellRef,
+    pub issue: AuditIssue,
+}
+
+impl fmt::Display for AuditWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.issue {
+            AuditIssue::EmptyReference(target) => {
+                write!(f, "{}: references empty cell {}", self.cell, target)
+            }
+            AuditIssue::EmptyRange(start, end) => {
+                write!(f, "{}: references empty range {}:{}", self.cell, start, end)
+            }
+            AuditIssue::SelfReference => write!(f, "{}: references itself", self.cell),
+            AuditIssue::BrokenReference => {
+                write!(f, "{}: contains #REF! (deleted reference)", self.cell)
+            }
+        }
+    }
+}
+
+impl Document {
+    /// Check every formula for references to empty cells, ranges with
+    /// nothing in them, references to itself and `#REF!` remnants. Blanks
+    /// inside a range that holds data are not reported. Warnings are ordered
+    /// by formula cell (row by row), then by referenced cell.
+    pub fn audit(&self) -> Vec<AuditWarning> {
+        // Deleting a referenced row or column turns the formula into text
+        // such as `=A1 + #REF!`, so broken formulas are looked for there too.
+        let mut formulas: Vec<(CellRef, String, bool)> = self
+            .grid
+            .iter()
+            .filter_map(|entry| match &entry.contents {
+                CellType::Script(script) => Some((entry.key().clone(), script.clone(), true)),
+                CellType::Text(text) if text.starts_with('=') && text.contains("#REF!") => {
+                    Some((entry.key().clone(), text.clone(), false))
+                }
+                _ => None,
+            })
+            .collect();
+        formulas.sort_by(|(a, ..), (b, ..)| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+
+        let mut warnings = Vec::new();
+        for (cell, script, is_formula) in formulas {
+            if script.contains("#REF!") {
+                warnings.push(AuditWarning {
+                    cell: cell.clone(),
+                    issue: AuditIssue::BrokenReference,
+                });
+            }
+            if !is_formula {
+                continue;
+            }
+            let script = self.expand_column_ranges(&expand_row_references(&script, cell.row));
+            let (refs, ranges) = extract_references(&script);
+            let mut found: Vec<(CellRef, AuditIssue)> = Vec::new();
+            for dep in refs {
+                if dep == cell {
+                    found.push((dep, AuditIssue::SelfReference));
+                } else if self.is_blank(&dep) {
+                    found.push((dep.clone(), AuditIssue::EmptyReference(dep)));
+                }
+            }
+            for (a, b) in ranges {
+                let start = CellRef::new(a.col.min(b.col), a.row.min(b.row));
+                let end = CellRef::new(a.col.max(b.col), a.row.max(b.row));
+                if in_range(&cell, &start, &end) {
+                    found.push((cell.clone(), AuditIssue::SelfReference));
+                } else if self.is_blank_range(&start, &end) {
+                    found.push((start.clone(), AuditIssue::EmptyRange(start, end)));
+                }
+            }
+            found.sort_by(|(a, _), (b, _)| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+            found.dedup();
+            let mut self_reported = false;
+            for (_, issue) in found {
+                if issue == AuditIssue::SelfReference {
+                    if self_reported {
+                        continue;
+                    }
+                    self_reported = true;
+                }
+                warnings.push(AuditWarning {
+                    cell: cell.clone(),
+                    issue,
+                });
+            }
+        }
+        warnings
+    }
+
+    /// Whether nothing is stored or spilled at `cell`.
+    fn is_blank(&self, cell: &CellRef) -> bool {
+        let stored = self
+            .grid
+            .get(cell)
+            .is_some_and(|c| !matches!(c.contents, CellType::Empty));
+        !stored && !self.spill_sources.contains_key(cell)
+    }
+
+    /// Whether nothing is stored or spilled anywhere from `start` to `end`.
+    fn is_blank_range(&self, start: &CellRef, end: &CellRef) -> bool {
+        let stored = self.grid.iter().any(|entry| {
+            in_range(entry.key(), start, end) && !matches!(entry.contents, CellType::Empty)
+        });
+        let spilled = self
+            .spill_sources
+            .iter()
+            .any(|entry| in_range(entry.key(), start, end));
+        !stored && !spilled
+    }
+}
+
+fn in_range(cell: &CellRef, start: &CellRef, end: &CellRef) -> bool {
+    (start.col..=end.col).contains(&cell.col) && (start.row..=end.row).contains(&cell.row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_flags_empty_and_broken_references() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "1").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 0), "=A1 + C1")
+            .unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(0, 1), "=A1 * 2")
+            .unwrap(); // A2
+        doc.set_cell_from_input(CellRef::new(0, 2), "=A1 + B5")
+            .unwrap(); // A3
+        doc.delete_row(4); // A3's reference to row 5 becomes #REF!
+
+        let warnings = doc.audit();
+        assert_eq!(
+            warnings,
+            vec![
+                AuditWarning {
+                    cell: CellRef::new(1, 0),
+                    issue: AuditIssue::EmptyReference(CellRef::new(2, 0)),
+                },
+                AuditWarning {
+                    cell: CellRef::new(0, 2),
+                    issue: AuditIssue::BrokenReference,
+                },
+            ]
+        );
+        assert_eq!(warnings[0].to_string(), "B1: references empty cell C1");
+        assert_eq!(
+            warnings[1].to_string(),
+            "A3: contains #REF! (deleted reference)"
+        );
+    }
+
+    #[test]
+    fn test_audit_ignores_blanks_inside_ranges_with_data() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "1").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 0), "=SUM(A1:A500)")
+            .unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(2, 0), "=SUM(D1:D9) + A1")
+            .unwrap(); // C1
+
+        let warnings = doc.audit();
+        assert_eq!(
+            warnings,
+            vec![AuditWarning {
+                cell: CellRef::new(2, 0),
+                issue: AuditIssue::EmptyRange(CellRef::new(3, 0), CellRef::new(3, 8)),
+            }]
+        );
+        assert_eq!(warnings[0].to_string(), "C1: references empty range D1:D9");
+    }
+}
@@ -0,0 +1,132 @@
+//! Column formulas: one row-relative template filled down a whole column.
+//!
+//! `set_column_formula(2, "=A1 + B1")` gives every data row `=A{row} + B{row}`.
+//! The template is remembered so rows inserted inside the data get the formula
+//! too, and it follows row/column inserts and deletes like any other formula.
+
+use super::Document;
+use crate::error::Result;
+use gridline_engine::engine::{
+    Cell, CellRef, ShiftOperation, offset_formula_references, shift_formula_references,
+};
+
+/// A column's template formula, written relative to `anchor_row`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ColumnFormula {
+    /// Row the template's references are relative to (the first data row)
+    pub(crate) anchor_row: usize,
+    /// Formula without the leading `=`
+    pub(crate) template: String,
+}
+
+impl ColumnFormula {
+    /// The template offset to `row`.
+    fn formula_for_row(&self, row: usize) -> String {
+        offset_formula_references(&self.template, 0, row as isize - self.anchor_row as isize)
+    }
+}
+
+impl Document {
+    /// Fill column `col` with `template` (e.g. `=A1 + B1`), written for the
+    /// first data row (the row after any header rows), offsetting references
+    /// for each row down to the last row used by other columns.
+    ///
+    /// The fill is one undo step. The template is kept, so rows later inserted
+    /// inside the data get the formula as well. Returns the number of cells
+    /// filled.
+    pub fn set_column_formula(&mut self, col: usize, template: &str) -> Result<usize> {
+        let template = template.trim();
+        let template = template.strip_prefix('=').unwrap_or(template).trim();
+        let anchor_row = self.header_rows;
+        let last_row = self
+            .grid
+            .iter()
+            .map(|entry| entry.key().clone())
+            .chain(self.spill_sources.iter().map(|entry| entry.key().clone()))
+            .filter(|cell| cell.col != col)
+            .map(|cell| cell.row)
+            .max()
+            .unwrap_or(anchor_row)
+            .max(anchor_row);
+
+        let filled = self.fill_range_from_input(
+            &CellRef::new(col, anchor_row),
+            &CellRef::new(col, last_row),
+            &format!("={}", template),
+        )?;
+        self.column_formulas.insert(
+            col,
+            ColumnFormula {
+                anchor_row,
+                template: template.to_string(),
+            },
+        );
+        Ok(filled)
+    }
+
+    /// The template formula (with `=`) registered for column `col`, written
+    /// relative to the first data row.
+    pub fn column_formula(&self, col: usize) -> Option<String> {
+        self.column_formulas
+            .get(&col)
+            .map(|cf| format!("={}", cf.template))
+    }
+
+    /// Stop applying a column formula to inserted rows. Existing cells keep
+    /// their formulas. Returns whether one was registered.
+    pub fn clear_column_formula(&mut self, col: usize) -> bool {
+        self.column_formulas.remove(&col).is_some()
+    }
+
+    /// Keep column formulas in step with a row/column insert or delete. For a
+    /// row insert (already applied to the grid) inside the data, returns the
+    /// cells that should receive each column's formula.
+    pub(crate) fn shift_column_formulas(&mut self, op: ShiftOperation) -> Vec<(CellRef, Cell)> {
+        let mut fills = Vec::new();
+        let inserted_row_has_data_below = matches!(
+            op,
+            ShiftOperation::InsertRow(at) if self.grid.iter().any(|e| e.key().row > at)
+        );
+
+        let formulas = std::mem::take(&mut self.column_formulas);
+        for (col, mut cf) in formulas {
+            let col = match op {
+                ShiftOperation::InsertRow(at) => {
+                    if at <= cf.anchor_row {
+                        cf.anchor_row += 1;
+                    }
+                    col
+                }
+                ShiftOperation::DeleteRow(at) => {
+                    if at < cf.anchor_row {
+                        cf.anchor_row -= 1;
+                    } else if at == cf.anchor_row {
+                        // Re-anchor on the row that moves up into its place.
+                        cf.template = cf.formula_for_row(at + 1);
+                    }
+                    col
+                }
+                ShiftOperation::InsertColumn(at) if col >= at => col + 1,
+                ShiftOperation::InsertColumn(_) => col,
+                ShiftOperation::DeleteColumn(at) if col == at => continue,
+                ShiftOperation::DeleteColumn(at) if col > at => col - 1,
+                ShiftOperation::DeleteColumn(_) => col,
+            };
+            cf.template = shift_formula_references(&cf.template, op);
+            if cf.template.contains("#REF!") {
+                continue;
+            }
+            if let ShiftOperation::InsertRow(at) = op
+                && at > cf.anchor_row
+                && inserted_row_has_data_below
+            {
+                fills.push((
+                    CellRef::new(col, at),
+                    Cell::new_script(&cf.formula_for_row(at)),
+                ));
+            }
+            self.column_formulas.insert(col, cf);
+        }
+        fills
+    }
+}
@@ -0,0 +1,136 @@
+//! Go-to-definition: resolve what a formula refers to.
+//!
+//! For a formula cell, the first custom function call or cell reference in the
+//! formula (whichever comes first) is resolved: a function to the file and line
+//! defining it, a reference to the cell itself.
+
+use super::Document;
+use gridline_engine::engine::{CellRef, CellType, blank_string_literals};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where a name used in a formula is defined.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Definition {
+    /// A referenced cell (the first cell of a range)
+    Cell(CellRef),
+    /// A custom function, with the file and 1-based line declaring it
+    Function {
+        name: String,
+        path: PathBuf,
+        line: usize,
+    },
+}
+
+fn fn_decl_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*(?:private\s+)?fn\s+([A-Za-z_][A-Za-z0-9_]*)\s*\(")
+            .expect("fn declaration regex must compile")
+    })
+}
+
+fn formula_token_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"\b(?:([A-Za-z_][A-Za-z0-9_]*)\s*\(|([A-Za-z]+[0-9]+)\b)")
+            .expect("formula token regex must compile")
+    })
+}
+
+/// Functions declared in a functions file, by name, with their 1-based line.
+pub(crate) fn scan_function_sources(
+    path: &Path,
+    content: &str,
+) -> HashMap<String, (PathBuf, usize)> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let caps = fn_decl_re().captures(line)?;
+            Some((caps[1].to_string(), (path.to_path_buf(), idx + 1)))
+        })
+        .collect()
+}
+
+impl Document {
+    /// Resolve the first custom function call or cell reference in the
+    /// formula at `cell_ref`. Returns `None` for non-formula cells and
+    /// formulas using neither.
+    pub fn definition_at(&self, cell_ref: &CellRef) -> Option<Definition> {
+        let formula = match self.grid.get(cell_ref).map(|c| c.contents.clone()) {
+            Some(CellType::Script(formula)) => formula,
+            _ => return None,
+        };
+        let formula = blank_string_literals(&formula);
+        formula_token_re().captures_iter(&formula).find_map(|caps| {
+            if let Some(name) = caps.get(1) {
+                let (path, line) = self.function_sources.get(name.as_str())?;
+                return Some(Definition::Function {
+                    name: name.as_str().to_string(),
+                    path: path.clone(),
+                    line: *line,
+                });
+            }
+            CellRef::from_str(caps.get(2)?.as_str()).map(Definition::Cell)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definition_at_resolves_functions_and_cells() {
+        let path = std::env::temp_dir().join(format!(
+            "gridline_definition_funcs_{}_{:?}.rhai",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(path.clone());
+        std::fs::write(
+            &path,
+            "// helpers\nfn double(x) {\n    x * 2\n}\n\nprivate fn triple(x) { x * 3 }\n",
+        )
+        .unwrap();
+        let mut doc = Document::new();
+        let path = doc.load_functions(&path).unwrap();
+
+        doc.set_cell_from_input(CellRef::new(0, 0), "=double(B2) + 1")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 1), "=abs(C3) + triple(1)")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 2), "=len(\"A1\") + SUM(D4:D9)")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 3), "42").unwrap();
+
+        assert_eq!(
+            doc.definition_at(&CellRef::new(0, 0)),
+            Some(Definition::Function {
+                name: "double".to_string(),
+                path,
+                line: 2
+            })
+        );
+        // Builtins have no source, so the first cell reference wins.
+        assert_eq!(
+            doc.definition_at(&CellRef::new(0, 1)),
+            Some(Definition::Cell(CellRef::new(2, 2)))
+        );
+        // References inside strings are ignored.
+        assert_eq!(
+            doc.definition_at(&CellRef::new(0, 2)),
+            Some(Definition::Cell(CellRef::new(3, 3)))
+        );
+        assert_eq!(doc.definition_at(&CellRef::new(0, 3)), None);
+    }
+}
@@ -1,6 +1,9 @@
 use super::Document;
+use super::state::EvalPolicy;
+use crate::error::Result;
 use gridline_engine::engine::{
-    CellRef, CellType, detect_cycle, eval_with_functions_script, format_dynamic, format_number,
+    CellFormat, CellRef, CellType, DIV_ZERO, detect_cycle, error_type_code,
+    eval_with_functions_script, format_dynamic, format_number, format_percent, is_recursion_limit,
     preprocess_script_with_context,
 };
 use rhai::Dynamic;
@@ -8,6 +11,55 @@ use rhai::Dynamic;
 impl Document {
     /// Get the display value for a cell
     pub fn get_cell_display(&mut self, cell_ref: &CellRef) -> String {
+        let display = self.compute_cell_display(cell_ref);
+        // Record errors so ERRORTYPE can classify them.
+        if error_type_code(&display).is_some() {
+            self.error_cells.insert(cell_ref.clone(), display.clone());
+        } else {
+            self.error_cells.remove(cell_ref);
+        }
+        display
+    }
+
+    /// The circular reference chain that makes a formula cell show `#CYCLE!`,
+    /// from the cell itself to the first cell seen twice, e.g. `[A1, B1, C1, A1]`.
+    /// `None` for cells that aren't caught in a cycle.
+    pub fn cycle_path_at(&self, cell_ref: &CellRef) -> Option<Vec<CellRef>> {
+        let is_formula = self
+            .grid
+            .get(cell_ref)
+            .is_some_and(|cell| matches!(cell.contents, CellType::Script(_)));
+        if !is_formula {
+            return None;
+        }
+        detect_cycle(cell_ref, &self.grid)
+    }
+
+    /// Status line for a cell caught in a cycle, e.g. `#CYCLE! A1 → B1 → A1`.
+    pub fn cycle_status_at(&self, cell_ref: &CellRef) -> Option<String> {
+        let path: Vec<String> = self
+            .cycle_path_at(cell_ref)?
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        Some(format!("#CYCLE! {}", path.join(" → ")))
+    }
+
+    /// Evaluate `formula` (with or without a leading `=`) as if it were
+    /// entered at `context`, without storing it: `ROW()`, `COL()` and
+    /// `@row.B` resolve against `context`. Useful for previews; an array
+    /// result is formatted rather than spilled.
+    pub fn evaluate_formula_at(&self, formula: &str, context: &CellRef) -> Result<String> {
+        let formula = formula.trim();
+        let formula = formula.strip_prefix('=').unwrap_or(formula);
+        let processed =
+            preprocess_script_with_context(&self.expand_column_ranges(formula), Some(context));
+        let result =
+            eval_with_functions_script(&self.engine, &processed, self.custom_functions.as_deref())?;
+        Ok(format_dynamic(&result))
+    }
+
+    fn compute_cell_display(&mut self, cell_ref: &CellRef) -> String {
         // Check if this is a spill cell (value is in shared value_cache)
         if self.spill_sources.contains_key(cell_ref) {
             if let Some(val) = self.value_cache.get(cell_ref) {
@@ -28,6 +80,7 @@ impl Document {
         match &cell.contents {
             CellType::Empty => String::new(),
             CellType::Text(s) => s.clone(),
+            CellType::Number(n) if cell.format == CellFormat::Percent => format_percent(*n),
             CellType::Number(n) => format_number(*n),
             CellType::Script(s) => {
                 // Return cached value if not dirty
@@ -42,9 +95,16 @@ impl Document {
                     return "#CYCLE!".to_string();
                 }
 
-                let processed = preprocess_script_with_context(s, Some(cell_ref));
+                let processed =
+                    preprocess_script_with_context(&self.expand_column_ranges(s), Some(cell_ref));
+                let error_targets = s.contains("ERRORTYPE").then(|| cell.depends_on.clone());
                 drop(cell);
 
+                // ERRORTYPE reads recorded errors, so evaluate its targets first.
+                for target in error_targets.unwrap_or_default() {
+                    self.get_cell_display(&target);
+                }
+
                 match eval_with_functions_script(
                     &self.engine,
                     &processed,
@@ -65,8 +125,15 @@ impl Document {
                             display
                         }
                     }
+                    Err(e) if is_recursion_limit(&e) => format!(
+                        "#ERR: recursion limit ({} calls) exceeded",
+                        self.max_call_depth
+                    ),
                     Err(e) => {
                         let e = e.to_string();
+                        if e.contains(DIV_ZERO) {
+                            return DIV_ZERO.to_string();
+                        }
                         // Show first 50 chars of error for debugging (UTF-8 safe)
                         let mut chars = e.chars();
                         let prefix: String = chars.by_ref().take(50).collect();
@@ -81,27 +148,29 @@ impl Document {
         }
     }
 
-    /// Handle array result - check conflicts and set up spill
+    /// Handle array result - check conflicts and set up spill.
+    ///
+    /// A flat array spills down from the source. An array whose elements are
+    /// all arrays is treated as rows and spills down and to the right.
     fn handle_array_spill(&mut self, source: &CellRef, result: Dynamic) -> String {
         let array: Vec<Dynamic> = result.into_array().unwrap();
         if array.is_empty() {
             return String::new();
         }
+        let values = spill_positions(source, array);
 
         // Check for conflicts in spill range
-        for i in 1..array.len() {
-            let spill_ref = CellRef::new(source.col, source.row + i);
-
+        for (spill_ref, _) in values.iter().filter(|(cell, _)| cell != source) {
             // Compute conflicts in a narrow scope so we can mutate after.
             let (has_cell_conflict, has_spill_conflict) = {
                 let cell_conflict = self
                     .grid
-                    .get(&spill_ref)
+                    .get(spill_ref)
                     .is_some_and(|cell| !matches!(cell.contents, CellType::Empty));
                 let spill_conflict = self
                     .spill_sources
-                    .get(&spill_ref)
-                    .is_some_and(|other_source| other_source != source);
+                    .get(spill_ref)
+                    .is_some_and(|other_source| *other_source != *source);
                 (cell_conflict, spill_conflict)
             };
 
@@ -116,20 +185,17 @@ impl Document {
 
         // Store all array values in the shared value_cache
         // This makes them accessible to the engine for chained VEC calls
-        for (i, val) in array.iter().enumerate() {
-            let cell_ref = CellRef::new(source.col, source.row + i);
-
-            self.value_cache.insert(cell_ref.clone(), val.clone());
-
-            // Register spill cells (skip index 0, that's the source cell)
-            if i > 0 {
-                self.spill_sources.insert(cell_ref, source.clone());
+        let mut first = String::new();
+        for (cell_ref, val) in values {
+            if cell_ref == *source {
+                first = format_dynamic(&val);
+            } else {
+                // Register spill cells (the source keeps its own entry)
+                self.spill_sources.insert(cell_ref.clone(), source.clone());
             }
+            self.value_cache.insert(cell_ref, val);
         }
 
-        // Format first value for display and cache
-        let first = format_dynamic(&array[0]);
-
         // Cache the first value in the source cell
         if let Some(mut cell) = self.grid.get_mut(source) {
             cell.cached_value = Some(first.clone());
@@ -148,8 +214,8 @@ impl Document {
         let to_remove: Vec<CellRef> = self
             .spill_sources
             .iter()
-            .filter(|(_, src)| *src == source)
-            .map(|(cell, _)| cell.clone())
+            .filter(|entry| entry.value() == source)
+            .map(|entry| entry.key().clone())
             .collect();
 
         for cell in to_remove {
@@ -158,9 +224,33 @@ impl Document {
         }
     }
 
+    /// Called at the end of every edit: under [`EvalPolicy::Eager`], or while
+    /// a change subscriber is listening, evaluate whatever the edit left dirty.
+    pub(crate) fn apply_eval_policy(&mut self) {
+        if self.eval_policy == EvalPolicy::Eager || self.change_subscriber.is_some() {
+            self.evaluate_all_cells();
+        }
+    }
+
+    /// Number of formula cells marked dirty and not yet re-evaluated. Under
+    /// [`EvalPolicy::Lazy`] these are computed when next displayed.
+    pub fn pending_recompute_count(&self) -> usize {
+        self.grid
+            .iter()
+            .filter(|entry| entry.dirty && matches!(entry.contents, CellType::Script(_)))
+            .count()
+    }
+
+    /// Evaluate all script cells in dependency order, then notify any change
+    /// subscriber.
+    pub(crate) fn evaluate_all_cells(&mut self) {
+        self.evaluate_script_cells();
+        self.publish_changes();
+    }
+
     /// Evaluate all script cells in dependency order.
     /// This ensures that cells are computed before cells that depend on them.
-    pub(crate) fn evaluate_all_cells(&mut self) {
+    fn evaluate_script_cells(&mut self) {
         // Collect all script cells as a set for quick lookup
         let script_cells: std::collections::HashSet<CellRef> = self
             .grid
@@ -235,3 +325,25 @@ impl Document {
         }
     }
 }
+
+/// Target cell for each element of a spilled array (see `handle_array_spill`).
+fn spill_positions(source: &CellRef, array: Vec<Dynamic>) -> Vec<(CellRef, Dynamic)> {
+    if array.iter().all(|row| row.is_array()) {
+        array
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, row)| {
+                let row: Vec<Dynamic> = row.into_array().unwrap_or_default();
+                row.into_iter()
+                    .enumerate()
+                    .map(move |(j, val)| (CellRef::new(source.col + j, source.row + i), val))
+            })
+            .collect()
+    } else {
+        array
+            .into_iter()
+            .enumerate()
+            .map(|(i, val)| (CellRef::new(source.col, source.row + i), val))
+            .collect()
+    }
+}
@@ -1,8 +1,15 @@
-use super::Document;
+use super::definition::scan_function_sources;
+use super::lambda::{lambdas_script, with_lambdas};
+use super::{Document, UndoAction};
 use crate::error::{GridlineError, Result};
-use crate::storage::{parse_csv, parse_grd, write_csv, write_grd};
-use gridline_engine::engine::CellType;
+use crate::storage::{
+    CsvExportOptions, CsvImportOptions, SheetSettings, parse_csv_with_options,
+    parse_grd_with_progress, write_csv, write_csv_with_options, write_dependency_dot,
+    write_grd_with_settings, write_markdown_range,
+};
 use gridline_engine::engine::create_engine_with_functions_and_cache;
+use gridline_engine::engine::{Cell, CellRef, CellType};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 const MAX_FUNCTION_FILE_BYTES: u64 = 1_048_576; // 1 MiB
@@ -57,14 +64,24 @@ fn checked_combined_script_size(
 }
 
 impl Document {
+    /// Refuse filesystem access for a headless document (see
+    /// [`Document::new_headless`]) before any path is touched.
+    fn require_filesystem(&self) -> Result<()> {
+        if self.headless {
+            return Err(GridlineError::Headless);
+        }
+        Ok(())
+    }
+
     /// Recompute formula state after custom functions change.
     ///
     /// Custom function changes can affect the value of any script cell. Clear
     /// all formula/spill caches, mark scripts dirty, and eagerly re-evaluate so
     /// dependent cells do not keep stale `#ERR`/`#NAN!` results.
-    fn refresh_after_functions_change(&mut self) {
+    pub(crate) fn refresh_after_functions_change(&mut self) {
         self.value_cache.clear();
         self.spill_sources.clear();
+        self.error_cells.clear();
         self.invalidate_script_cache();
         self.evaluate_all_cells();
     }
@@ -72,6 +89,7 @@ impl Document {
     /// Load custom Rhai functions from a file (appends to existing functions).
     /// Returns the path loaded, or an error.
     pub fn load_functions(&mut self, path: &Path) -> Result<PathBuf> {
+        self.require_filesystem()?;
         let path_buf = std::fs::canonicalize(path)?;
         let content = read_functions_file(&path_buf)?;
         ensure_total_functions_script_size(content.len())?;
@@ -83,13 +101,16 @@ impl Document {
 
         let mut new_functions_files = self.functions_files.clone();
         new_functions_files.push(path_buf.clone());
+        let sources = scan_function_sources(&path_buf, &content);
 
-        let new_custom_functions = if let Some(existing) = &self.custom_functions {
+        let new_custom_functions = if let Some(existing) = self.file_functions() {
             checked_combined_script_size(existing.len(), content.len(), true)?;
             format!("{}\n\n{}", existing, content)
         } else {
             content
         };
+        let new_custom_functions =
+            with_lambdas(Some(&new_custom_functions), &self.lambda_script).unwrap_or_default();
 
         // Compile in a temporary engine first so failures don't mutate state.
         let (engine, custom_ast, compile_error) = create_engine_with_functions_and_cache(
@@ -104,7 +125,8 @@ impl Document {
         // Commit only after successful compilation.
         self.functions_files = new_functions_files;
         self.custom_functions = Some(new_custom_functions);
-        self.engine = engine;
+        self.function_sources.extend(sources);
+        self.install_engine(engine);
         self.custom_ast = custom_ast;
         self.refresh_after_functions_change();
 
@@ -114,14 +136,17 @@ impl Document {
     /// Reload all custom functions from the loaded files.
     /// Returns the number of files reloaded.
     pub fn reload_functions(&mut self) -> Result<usize> {
+        self.require_filesystem()?;
         if self.functions_files.is_empty() {
             return Err(GridlineError::NoFunctionsLoaded);
         }
 
         let paths = self.functions_files.clone();
         let mut merged = String::new();
+        let mut sources = HashMap::new();
         for (idx, path) in paths.iter().enumerate() {
             let content = read_functions_file(path)?;
+            sources.extend(scan_function_sources(path, &content));
             checked_combined_script_size(merged.len(), content.len(), idx > 0)?;
             if idx > 0 {
                 merged.push_str("\n\n");
@@ -129,17 +154,19 @@ impl Document {
             merged.push_str(&content);
         }
 
+        let merged = with_lambdas(Some(&merged), &self.lambda_script);
         let (engine, custom_ast, compile_error) = create_engine_with_functions_and_cache(
             self.grid.clone(),
             self.value_cache.clone(),
-            Some(&merged),
+            merged.as_deref(),
         );
         if let Some(err) = compile_error {
             return Err(GridlineError::RhaiCompile(err));
         }
 
-        self.custom_functions = Some(merged);
-        self.engine = engine;
+        self.custom_functions = merged;
+        self.function_sources = sources;
+        self.install_engine(engine);
         self.custom_ast = custom_ast;
         self.refresh_after_functions_change();
 
@@ -149,36 +176,77 @@ impl Document {
     /// Save to current file path.
     /// Returns the path saved to.
     pub fn save_file(&mut self) -> Result<PathBuf> {
+        self.require_filesystem()?;
         let Some(path) = &self.file_path else {
             return Err(GridlineError::NoFilePath);
         };
 
-        write_grd(path, &self.grid)?;
+        let settings = SheetSettings {
+            metadata: self.metadata.clone(),
+            hidden_cols: self.hidden_cols.clone(),
+            merged_regions: self.merged_regions.clone(),
+            row_groups: self.row_groups.clone(),
+            collapsed_row_groups: self.collapsed_row_groups.clone(),
+            alignments: self.cell_alignments.clone(),
+            lambdas: self.lambdas.clone(),
+        };
+        write_grd_with_settings(path, &self.grid, &settings)?;
         self.modified = false;
         Ok(path.clone())
     }
 
     /// Load from file
     pub fn load_file(&mut self, path: &Path) -> Result<()> {
-        let grid = parse_grd(path)?;
+        self.load_file_with_progress(path, |_| {})
+    }
+
+    /// Load from file, reporting the number of lines parsed so far to `progress`
+    /// (e.g. to drive a loading indicator for large sheets).
+    pub fn load_file_with_progress(
+        &mut self,
+        path: &Path,
+        progress: impl FnMut(usize),
+    ) -> Result<()> {
+        self.require_filesystem()?;
+        let (grid, settings) = parse_grd_with_progress(path, progress)?;
+        for entry in grid.iter() {
+            self.check_formula_limits(entry.value())?;
+        }
 
         // Build engine for the new grid first so load is transactional.
+        // The sheet's lambdas replace any defined before.
+        let lambda_script = lambdas_script(&settings.lambdas)?;
+        let script = with_lambdas(self.file_functions(), &lambda_script);
         let (engine, custom_ast, compile_error) = create_engine_with_functions_and_cache(
             grid.clone(),
             self.value_cache.clone(),
-            self.custom_functions.as_deref(),
+            script.as_deref(),
         );
         if let Some(err) = compile_error {
             return Err(GridlineError::RhaiCompile(err));
         }
 
         self.grid = grid;
-        self.engine = engine;
+        self.custom_functions = script;
+        self.lambdas = settings.lambdas;
+        self.lambda_script = lambda_script;
+        self.install_engine(engine);
         self.custom_ast = custom_ast;
+        self.hidden_cols = settings.hidden_cols;
+        self.merged_regions = settings.merged_regions;
+        self.row_groups = settings.row_groups;
+        self.collapsed_row_groups = settings.collapsed_row_groups;
+        self.cell_alignments = settings.alignments;
+        self.metadata = settings.metadata;
+        // Not saved in the file, so none of the previous sheet's carry over
+        self.column_formulas.clear();
+        self.volatile_cells.clear();
+        self.header_rows = 0;
 
         // Clear caches since we're loading a new grid
         self.value_cache.clear();
         self.spill_sources.clear();
+        self.error_cells.clear();
 
         // Mark all script cells as dirty so they're re-evaluated with current custom functions
         for mut entry in self.grid.iter_mut() {
@@ -204,21 +272,52 @@ impl Document {
     /// Import CSV data starting at a column/row.
     /// Returns the number of cells imported.
     pub fn import_csv(&mut self, path: &str, start_col: usize, start_row: usize) -> Result<usize> {
-        let cells = parse_csv(Path::new(path), start_col, start_row)?;
+        self.import_csv_with_options(path, start_col, start_row, &CsvImportOptions::default())
+    }
+
+    /// Import the rows and columns of a CSV file selected by `options`,
+    /// starting at a column/row. Returns the number of cells imported.
+    pub fn import_csv_with_options(
+        &mut self,
+        path: &str,
+        start_col: usize,
+        start_row: usize,
+        options: &CsvImportOptions,
+    ) -> Result<usize> {
+        self.require_filesystem()?;
+        let cells = parse_csv_with_options(Path::new(path), start_col, start_row, options)?;
+        self.apply_imported_cells(cells)
+    }
+
+    /// Write imported cells over the grid as a single undo step, so one undo
+    /// restores every overwritten cell. Returns the number of cells imported.
+    fn apply_imported_cells(&mut self, cells: Vec<(CellRef, Cell)>) -> Result<usize> {
         let count = cells.len();
         if count == 0 {
             return Err(GridlineError::EmptyCsv);
         }
+        for (_, cell) in &cells {
+            self.check_formula_limits(cell)?;
+        }
+        let mut actions = Vec::with_capacity(count);
         for (cell_ref, cell) in cells {
-            self.grid.insert(cell_ref, cell);
+            let old_cell = self.grid.insert(cell_ref.clone(), cell.clone());
+            actions.push(UndoAction {
+                cell_ref,
+                old_cell,
+                new_cell: Some(cell),
+            });
         }
+        self.push_undo_batch(actions);
         self.modified = true;
         // Clear caches/spills and mark scripts dirty so dependent formulas re-evaluate
         self.value_cache.clear();
         self.spill_sources.clear();
+        self.error_cells.clear();
         self.invalidate_script_cache();
         // Rebuild dependencies (DashMap shares data, so builtins already see updates)
         self.rebuild_dependents();
+        self.apply_eval_policy();
         Ok(count)
     }
 
@@ -229,7 +328,7 @@ impl Document {
         start_col: usize,
         start_row: usize,
     ) -> Result<usize> {
-        let mut count = 0;
+        let mut cells = Vec::new();
         for (row_idx, line) in csv_content.lines().enumerate() {
             let fields = crate::storage::csv::parse_csv_line(line).map_err(|message| {
                 GridlineError::Parse {
@@ -253,22 +352,11 @@ impl Document {
                         line: row_idx + 1,
                         message: "CSV column index overflow from import offset".to_string(),
                     })?;
-                let cell_ref = gridline_engine::engine::CellRef::new(col, row);
-                let cell = crate::storage::csv::parse_csv_field(&field);
-                self.grid.insert(cell_ref, cell);
-                count += 1;
+                let cell_ref = CellRef::new(col, row);
+                cells.push((cell_ref, crate::storage::csv::parse_csv_field(&field)));
             }
         }
-        if count == 0 {
-            return Err(GridlineError::EmptyCsv);
-        }
-        self.modified = true;
-        self.value_cache.clear();
-        self.spill_sources.clear();
-        self.invalidate_script_cache();
-        // Rebuild dependencies (DashMap shares data, so builtins already see updates)
-        self.rebuild_dependents();
-        Ok(count)
+        self.apply_imported_cells(cells)
     }
 
     /// Export grid to CSV file
@@ -277,16 +365,73 @@ impl Document {
         path: &str,
         range: Option<((usize, usize), (usize, usize))>,
     ) -> Result<()> {
+        self.require_filesystem()?;
         write_csv(Path::new(path), self, range)?;
         Ok(())
     }
+
+    /// Export the cells between two corners (in any order) to CSV, or the
+    /// whole used area when `range` is `None`.
+    pub fn export_csv_range(
+        &mut self,
+        path: &Path,
+        range: Option<(CellRef, CellRef)>,
+    ) -> Result<()> {
+        self.export_csv_range_with_options(path, range, &CsvExportOptions::default())
+    }
+
+    /// Like [`Document::export_csv_range`], with the byte order mark and line
+    /// endings chosen by `options` (see [`CsvExportOptions::excel`]).
+    pub fn export_csv_range_with_options(
+        &mut self,
+        path: &Path,
+        range: Option<(CellRef, CellRef)>,
+        options: &CsvExportOptions,
+    ) -> Result<()> {
+        self.require_filesystem()?;
+        write_csv_with_options(
+            path,
+            self,
+            range.map(|(a, b)| export_bounds(&a, &b)),
+            options,
+        )?;
+        Ok(())
+    }
+
+    /// Export the cells between two corners (in any order) to a markdown
+    /// table, or the whole used area when `range` is `None`.
+    pub fn export_markdown_range(
+        &mut self,
+        path: &Path,
+        range: Option<(CellRef, CellRef)>,
+    ) -> Result<()> {
+        self.require_filesystem()?;
+        write_markdown_range(path, self, range.map(|(a, b)| export_bounds(&a, &b)))?;
+        Ok(())
+    }
+
+    /// Write the cell dependency graph to a Graphviz DOT file, with an edge
+    /// from each referenced cell to each formula referencing it.
+    pub fn write_dependency_dot(&mut self, path: &Path) -> Result<()> {
+        self.require_filesystem()?;
+        write_dependency_dot(path, self)?;
+        Ok(())
+    }
+}
+
+/// Normalize two corners into `((min_col, min_row), (max_col, max_row))`.
+fn export_bounds(a: &CellRef, b: &CellRef) -> ((usize, usize), (usize, usize)) {
+    (
+        (a.col.min(b.col), a.row.min(b.row)),
+        (a.col.max(b.col), a.row.max(b.row)),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Document, MAX_FUNCTION_FILE_BYTES};
     use crate::error::GridlineError;
-    use gridline_engine::engine::CellRef;
+    use gridline_engine::engine::{Cell, CellRef};
     use std::io::Write;
 
     #[test]
@@ -729,4 +874,249 @@ mod tests {
             other => panic!("expected parse error, got {other:?}"),
         }
     }
+
+    #[test]
+    fn test_hidden_columns_persist_through_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "gridline_hidden_cols_{}_{}_{:?}.grd",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(std::path::PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(path.clone());
+
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(1, 0), "7").unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 0), "=B1 * 2")
+            .unwrap();
+        assert!(doc.hide_column(1));
+        doc.file_path = Some(path.clone());
+        doc.save_file().unwrap();
+
+        let mut loaded = Document::new();
+        loaded.load_file(&path).unwrap();
+        assert!(loaded.is_column_hidden(1));
+        assert!(!loaded.is_column_hidden(0));
+        assert_eq!(loaded.get_cell_display(&CellRef::new(0, 0)), "14");
+    }
+
+    #[test]
+    fn test_metadata_persists_through_save_and_load() {
+        let path = std::env::temp_dir().join(format!(
+            "gridline_metadata_{}_{}_{:?}.grd",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(std::path::PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(path.clone());
+
+        let mut doc = Document::new();
+        doc.set_metadata("title", "Q3 \"Budget\"").unwrap();
+        doc.set_metadata("author", "Ada").unwrap();
+        doc.set_metadata("x-review", "pending").unwrap();
+        assert!(doc.set_metadata("bad key", "x").is_err());
+        doc.set_cell_from_input(CellRef::new(0, 0), "1").unwrap();
+        doc.file_path = Some(path.clone());
+        doc.save_file().unwrap();
+
+        let mut loaded = Document::new();
+        loaded.load_file(&path).unwrap();
+        let meta = loaded.metadata();
+        assert_eq!(meta.get("title").map(String::as_str), Some("Q3 \"Budget\""));
+        assert_eq!(meta.get("author").map(String::as_str), Some("Ada"));
+        assert_eq!(meta.get("x-review").map(String::as_str), Some("pending"));
+        assert_eq!(loaded.get_cell_display(&CellRef::new(0, 0)), "1");
+    }
+
+    #[test]
+    fn test_export_range_writes_only_sub_rectangle() {
+        let mut doc = Document::new();
+        for row in 0..4 {
+            for col in 0..4 {
+                let value = format!("{}", row * 10 + col);
+                doc.set_cell_from_input(CellRef::new(col, row), &value)
+                    .unwrap();
+            }
+        }
+        let base = std::env::temp_dir().join(format!(
+            "gridline_export_range_{}_{:?}",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        let csv_path = base.with_extension("csv");
+        let md_path = base.with_extension("md");
+
+        // Corners given bottom-right first: C3 back to B2.
+        let range = Some((CellRef::new(2, 2), CellRef::new(1, 1)));
+        doc.export_csv_range(&csv_path, range.clone()).unwrap();
+        doc.export_markdown_range(&md_path, range).unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        let md = std::fs::read_to_string(&md_path).unwrap();
+        let _ = std::fs::remove_file(&csv_path);
+        let _ = std::fs::remove_file(&md_path);
+
+        assert_eq!(csv.replace("\r\n", "\n"), "11,12\n21,22\n");
+        assert!(md.contains("| B | C |"));
+        assert!(md.contains("| 2 | 11 | 12 |"));
+        assert!(md.contains("| 3 | 21 | 22 |"));
+        assert!(!md.contains("| A |"));
+        assert!(!md.contains(" 33 "));
+        assert!(!md.contains("| 1 |"));
+    }
+
+    #[test]
+    fn test_import_is_undone_in_one_step() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "10").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 1), "=A1 + 1")
+            .unwrap(); // B2
+        let before: std::collections::HashMap<CellRef, String> = doc
+            .grid
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.to_input_string()))
+            .collect();
+
+        assert_eq!(doc.import_csv_raw("1,2\n3,4\n5,6", 0, 0).unwrap(), 6);
+        assert_eq!(doc.get_cell_display(&CellRef::new(1, 1)), "4");
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 2)), "5");
+
+        doc.undo().unwrap();
+        let after: std::collections::HashMap<CellRef, String> = doc
+            .grid
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.to_input_string()))
+            .collect();
+        assert_eq!(after, before);
+        assert_eq!(doc.get_cell_display(&CellRef::new(1, 1)), "11");
+
+        doc.redo().unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(1, 1)), "4");
+    }
+
+    #[test]
+    fn test_headless_document_never_touches_the_filesystem() {
+        let base = std::env::temp_dir().join(format!(
+            "gridline_headless_{}_{:?}",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        let (grd, csv, module) = (
+            base.with_extension("grd"),
+            base.with_extension("csv"),
+            base.with_extension("rhai"),
+        );
+        struct Cleanup(Vec<std::path::PathBuf>);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                for path in &self.0 {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+        }
+        let _cleanup = Cleanup(vec![grd.clone(), csv.clone(), module.clone()]);
+        std::fs::write(&grd, "A1: 1\n").unwrap();
+        std::fs::write(&csv, "1,2\n").unwrap();
+        std::fs::write(&module, "fn answer() { 42 }\n").unwrap();
+
+        // A regular document can import the module from disk
+        let import = format!("import \"{}\" as m; m::answer()", base.display());
+        let ctx = crate::document::ScriptContext::new(0, 0);
+        let result = Document::new().execute_script(&import, &ctx).unwrap();
+        assert_eq!(result.return_value.as_deref(), Some("42"));
+
+        let mut doc = Document::new_headless();
+        assert!(doc.is_headless());
+        assert!(doc.custom_functions.is_none());
+        assert!(doc.execute_script(&import, &ctx).is_err());
+        assert!(matches!(doc.load_file(&grd), Err(GridlineError::Headless)));
+        assert!(matches!(
+            doc.load_functions(&module),
+            Err(GridlineError::Headless)
+        ));
+        assert!(matches!(
+            doc.import_csv(csv.to_str().unwrap(), 0, 0),
+            Err(GridlineError::Headless)
+        ));
+
+        doc.set_cell_from_input(CellRef::new(0, 0), "=1 + 2")
+            .unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "3");
+        doc.file_path = Some(grd.clone());
+        assert!(matches!(doc.save_file(), Err(GridlineError::Headless)));
+        assert!(matches!(
+            doc.export_csv(csv.to_str().unwrap(), None),
+            Err(GridlineError::Headless)
+        ));
+        assert_eq!(std::fs::read_to_string(&grd).unwrap(), "A1: 1\n");
+        assert_eq!(std::fs::read_to_string(&csv).unwrap(), "1,2\n");
+    }
+
+    #[test]
+    fn test_formula_limits_apply_to_paste_and_load() {
+        let mut doc = Document::new();
+        doc.formula_limits.max_len = 5;
+
+        let long = Cell::new_script("1+2+3+4");
+        assert!(matches!(
+            doc.paste_cells(0, 0, 0, 0, &[(0, 0, long)]),
+            Err(GridlineError::FormulaTooLong { len: 7, max: 5 })
+        ));
+        assert!(doc.grid.get(&CellRef::new(0, 0)).is_none());
+
+        let path = std::env::temp_dir().join(format!(
+            "gridline_formula_limits_{}_{:?}.grd",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        std::fs::write(&path, "A1: =1+2+3+4\n").unwrap();
+        let result = doc.load_file(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(GridlineError::FormulaTooLong { .. })));
+        assert!(doc.grid.is_empty());
+    }
+
+    #[test]
+    fn test_load_drops_previous_sheet_column_formulas_and_marks() {
+        let mut doc = Document::new();
+        for row in 0..3 {
+            doc.set_cell_from_input(CellRef::new(0, row), "1").unwrap();
+        }
+        doc.set_column_formula(1, "=A1*100").unwrap();
+        doc.set_volatile(&CellRef::new(1, 0), true);
+        doc.set_header_rows(1);
+
+        let path = std::env::temp_dir().join(format!(
+            "gridline_load_resets_{}_{:?}.grd",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        std::fs::write(&path, "A1: 1\nA2: 2\n").unwrap();
+        let result = doc.load_file(&path);
+        let _ = std::fs::remove_file(&path);
+        result.unwrap();
+
+        assert!(doc.column_formulas.is_empty());
+        assert!(doc.volatile_cells.is_empty());
+        assert_eq!(doc.header_rows(), 0);
+        doc.insert_row(1);
+        assert!(doc.grid.get(&CellRef::new(1, 1)).is_none());
+    }
 }
@@ -0,0 +1,189 @@
+//! Named lambdas: reusable formulas defined in the sheet itself, e.g.
+//! `PROFIT = |rev, cost| rev - cost`, then called as `=PROFIT(A1, B1)`.
+//!
+//! Each lambda becomes a Rhai function appended to the custom functions
+//! script, so it is callable anywhere a function loaded from a file is.
+//! Lambdas are saved with the sheet in `@lambda NAME: |args| body` lines.
+
+use super::Document;
+use crate::error::{GridlineError, Result};
+use gridline_engine::builtins::is_builtin_function;
+use gridline_engine::engine::create_engine_with_functions_and_cache;
+use std::collections::BTreeMap;
+
+/// Whether `name` is a plain identifier usable as a function or parameter.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Rhai function definition for lambda `name`, e.g.
+/// `fn PROFIT(rev, cost) { rev - cost }` for `|rev, cost| rev - cost`.
+pub(crate) fn lambda_function(name: &str, lambda: &str) -> Result<String> {
+    if !is_identifier(name) {
+        return Err(GridlineError::InvalidFunctionName(name.to_string()));
+    }
+    if is_builtin_function(name) {
+        return Err(GridlineError::BuiltinFunctionName(name.to_string()));
+    }
+    let not_a_closure = || GridlineError::NotAClosure(lambda.to_string());
+    let (params, body) = lambda
+        .trim()
+        .strip_prefix('|')
+        .and_then(|rest| rest.split_once('|'))
+        .ok_or_else(not_a_closure)?;
+    let params: Vec<&str> = params
+        .split(',')
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .collect();
+    let body = body.trim();
+    if body.is_empty() || body.contains('\n') || !params.iter().all(|p| is_identifier(p)) {
+        return Err(not_a_closure());
+    }
+    Ok(format!("fn {}({}) {{ {} }}", name, params.join(", "), body))
+}
+
+/// The full custom functions script: functions from files, then lambdas.
+pub(crate) fn with_lambdas(file_functions: Option<&str>, lambda_script: &str) -> Option<String> {
+    let script = format!("{}{}", file_functions.unwrap_or_default(), lambda_script);
+    (!script.is_empty()).then_some(script)
+}
+
+/// The custom functions script section defining `lambdas`.
+pub(crate) fn lambdas_script(lambdas: &BTreeMap<String, String>) -> Result<String> {
+    let mut script = String::new();
+    for (name, lambda) in lambdas {
+        script.push_str("\n\n");
+        script.push_str(&lambda_function(name, lambda)?);
+    }
+    Ok(script)
+}
+
+impl Document {
+    /// Define (or redefine) lambda `name` as `lambda`, a closure such as
+    /// `|rev, cost| rev - cost`. The name is upper-cased and must not be a
+    /// built-in function. Returns the name to call it by.
+    pub fn define_lambda(&mut self, name: &str, lambda: &str) -> Result<String> {
+        let name = name.trim().to_ascii_uppercase();
+        let mut lambdas = self.lambdas.clone();
+        lambdas.insert(name.clone(), lambda.trim().to_string());
+        self.set_lambdas(lambdas)?;
+        Ok(name)
+    }
+
+    /// Remove lambda `name`, returning whether it was defined.
+    pub fn remove_lambda(&mut self, name: &str) -> Result<bool> {
+        let mut lambdas = self.lambdas.clone();
+        if lambdas.remove(&name.trim().to_ascii_uppercase()).is_none() {
+            return Ok(false);
+        }
+        self.set_lambdas(lambdas)?;
+        Ok(true)
+    }
+
+    /// Lambdas defined in the sheet, by name.
+    pub fn lambdas(&self) -> &BTreeMap<String, String> {
+        &self.lambdas
+    }
+
+    /// Custom functions loaded from files, without the lambda section.
+    pub(crate) fn file_functions(&self) -> Option<&str> {
+        self.custom_functions
+            .as_deref()
+            .map(|script| script.strip_suffix(&self.lambda_script).unwrap_or(script))
+            .filter(|script| !script.is_empty())
+    }
+
+    /// Replace every lambda, recompiling the custom functions. Nothing
+    /// changes if a lambda is invalid or fails to compile.
+    fn set_lambdas(&mut self, lambdas: BTreeMap<String, String>) -> Result<()> {
+        let lambda_script = lambdas_script(&lambdas)?;
+        let script = with_lambdas(self.file_functions(), &lambda_script);
+        let (engine, custom_ast, compile_error) = create_engine_with_functions_and_cache(
+            self.grid.clone(),
+            self.value_cache.clone(),
+            script.as_deref(),
+        );
+        if let Some(err) = compile_error {
+            return Err(GridlineError::RhaiCompile(err));
+        }
+
+        self.lambdas = lambdas;
+        self.lambda_script = lambda_script;
+        self.custom_functions = script;
+        self.install_engine(engine);
+        self.custom_ast = custom_ast;
+        self.modified = true;
+        self.refresh_after_functions_change();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gridline_engine::engine::CellRef;
+
+    #[test]
+    fn test_lambda_evaluates_and_survives_save_load() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "100").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 0), "60").unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(2, 0), "=PROFIT(A1, B1)")
+            .unwrap(); // C1
+        assert_eq!(
+            doc.define_lambda("profit", "|rev, cost| rev - cost")
+                .unwrap(),
+            "PROFIT"
+        );
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "40");
+
+        // Redefining replaces the old body
+        doc.define_lambda("PROFIT", "|rev, cost| (rev - cost) / rev")
+            .unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "0.40");
+
+        assert!(matches!(
+            doc.define_lambda("sum", "|a| a"),
+            Err(GridlineError::BuiltinFunctionName(_))
+        ));
+        assert!(matches!(
+            doc.define_lambda("half", "x / 2"),
+            Err(GridlineError::NotAClosure(_))
+        ));
+        assert!(doc.define_lambda("broken", "|x| x +").is_err());
+        assert_eq!(doc.lambdas().len(), 1);
+
+        let path = std::env::temp_dir().join(format!(
+            "gridline_lambda_{}_{:?}.grd",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(std::path::PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(path.clone());
+        doc.file_path = Some(path.clone());
+        doc.save_file().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("@lambda PROFIT: |rev, cost| (rev - cost) / rev"));
+
+        let mut loaded = Document::new();
+        loaded.load_file(&path).unwrap();
+        assert_eq!(loaded.get_cell_display(&CellRef::new(2, 0)), "0.40");
+        assert!(loaded.remove_lambda("profit").unwrap());
+        assert!(loaded.custom_functions.is_none());
+        assert!(
+            loaded
+                .get_cell_display(&CellRef::new(2, 0))
+                .starts_with("#ERR")
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Merged cells: a rectangle displayed as one cell showing the value of its
+//! top-left (anchor) cell. The other cells keep their data but render blank,
+//! and edits aimed at them land on the anchor.
+
+use super::Document;
+use crate::error::{GridlineError, Result};
+use gridline_engine::engine::{CellRef, ShiftOperation};
+
+/// Shift the span `lo..=hi` of one axis for an insert or delete at `at`.
+/// Returns `None` when a delete removes the whole span.
+pub(super) fn shift_span(lo: usize, hi: usize, at: usize, insert: bool) -> Option<(usize, usize)> {
+    if insert {
+        if at <= lo {
+            Some((lo + 1, hi + 1))
+        } else if at <= hi {
+            Some((lo, hi + 1))
+        } else {
+            Some((lo, hi))
+        }
+    } else if at < lo {
+        Some((lo - 1, hi - 1))
+    } else if at <= hi {
+        (lo < hi).then(|| (lo, hi - 1))
+    } else {
+        Some((lo, hi))
+    }
+}
+
+fn contains(region: &(CellRef, CellRef), cell: &CellRef) -> bool {
+    let (top_left, bottom_right) = region;
+    (top_left.col..=bottom_right.col).contains(&cell.col)
+        && (top_left.row..=bottom_right.row).contains(&cell.row)
+}
+
+fn overlaps(a: &(CellRef, CellRef), b: &(CellRef, CellRef)) -> bool {
+    a.0.col <= b.1.col && b.0.col <= a.1.col && a.0.row <= b.1.row && b.0.row <= a.1.row
+}
+
+impl Document {
+    /// Merge the rectangle between two corners into one display cell showing
+    /// the top-left cell's value. Merging a single cell does nothing; a
+    /// rectangle overlapping an existing merge is an error.
+    pub fn merge_cells(&mut self, a: &CellRef, b: &CellRef) -> Result<()> {
+        let region = (
+            CellRef::new(a.col.min(b.col), a.row.min(b.row)),
+            CellRef::new(a.col.max(b.col), a.row.max(b.row)),
+        );
+        if region.0 == region.1 {
+            return Ok(());
+        }
+        if let Some(existing) = self.merged_regions.iter().find(|r| overlaps(r, &region)) {
+            return Err(GridlineError::MergeOverlap(format!(
+                "{}:{}",
+                existing.0, existing.1
+            )));
+        }
+        let old_layout = self.layout();
+        self.merged_regions.push(region);
+        self.push_undo_with_layout(Vec::new(), old_layout);
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Remove the merge containing `cell`. Returns whether there was one.
+    pub fn unmerge_cells(&mut self, cell: &CellRef) -> bool {
+        let old_layout = self.layout();
+        self.merged_regions.retain(|region| !contains(region, cell));
+        let removed = self.merged_regions != old_layout.merged_regions;
+        if removed {
+            self.push_undo_with_layout(Vec::new(), old_layout);
+            self.modified = true;
+        }
+        removed
+    }
+
+    /// The merged region (top-left, bottom-right) containing `cell`, if any.
+    pub fn merged_region_at(&self, cell: &CellRef) -> Option<(CellRef, CellRef)> {
+        self.merged_regions
+            .iter()
+            .find(|region| contains(region, cell))
+            .cloned()
+    }
+
+    /// The cell edits of `cell` should go to: the anchor of its merged
+    /// region, or `cell` itself when it isn't merged.
+    pub fn merge_anchor(&self, cell: &CellRef) -> CellRef {
+        self.merged_region_at(cell)
+            .map(|(anchor, _)| anchor)
+            .unwrap_or_else(|| cell.clone())
+    }
+
+    /// Whether `cell` is a merged cell other than the anchor (drawn blank).
+    pub fn is_merged_hidden(&self, cell: &CellRef) -> bool {
+        self.merged_region_at(cell)
+            .is_some_and(|(anchor, _)| anchor != *cell)
+    }
+
+    /// Keep merged regions in step with a row/column insert or delete. A
+    /// region grows or shrinks when the change falls inside it, and is
+    /// dropped once it no longer spans more than one cell.
+    pub(crate) fn shift_merged_regions(&mut self, op: ShiftOperation) {
+        let regions = std::mem::take(&mut self.merged_regions);
+        self.merged_regions = regions
+            .into_iter()
+            .filter_map(|(top_left, bottom_right)| {
+                let (rows, cols) = match op {
+                    ShiftOperation::InsertRow(at) | ShiftOperation::DeleteRow(at) => (
+                        shift_span(
+                            top_left.row,
+                            bottom_right.row,
+                            at,
+                            matches!(op, ShiftOperation::InsertRow(_)),
+                        )?,
+                        (top_left.col, bottom_right.col),
+                    ),
+                    ShiftOperation::InsertColumn(at) | ShiftOperation::DeleteColumn(at) => (
+                        (top_left.row, bottom_right.row),
+                        shift_span(
+                            top_left.col,
+                            bottom_right.col,
+                            at,
+                            matches!(op, ShiftOperation::InsertColumn(_)),
+                        )?,
+                    ),
+                };
+                let region = (CellRef::new(cols.0, rows.0), CellRef::new(cols.1, rows.1));
+                (region.0 != region.1).then_some(region)
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merged_edit_redirects_to_anchor() {
+        let mut doc = Document::new();
+        doc.merge_cells(&CellRef::new(2, 1), &CellRef::new(0, 0))
+            .unwrap();
+        assert_eq!(
+            doc.merged_region_at(&CellRef::new(1, 1)),
+            Some((CellRef::new(0, 0), CellRef::new(2, 1)))
+        );
+        assert_eq!(doc.merge_anchor(&CellRef::new(2, 1)), CellRef::new(0, 0));
+        assert_eq!(doc.merge_anchor(&CellRef::new(3, 0)), CellRef::new(3, 0));
+        assert!(doc.is_merged_hidden(&CellRef::new(1, 0)));
+        assert!(!doc.is_merged_hidden(&CellRef::new(0, 0)));
+
+        // Overlapping merges are rejected
+        assert!(
+            doc.merge_cells(&CellRef::new(2, 1), &CellRef::new(3, 3))
+                .is_err()
+        );
+
+        assert!(doc.unmerge_cells(&CellRef::new(1, 1)));
+        assert_eq!(doc.merge_anchor(&CellRef::new(2, 1)), CellRef::new(2, 1));
+        assert!(!doc.unmerge_cells(&CellRef::new(1, 1)));
+    }
+
+    #[test]
+    fn test_merged_regions_follow_inserts_and_deletes() {
+        let mut doc = Document::new();
+        // B2:C3
+        doc.merge_cells(&CellRef::new(1, 1), &CellRef::new(2, 2))
+            .unwrap();
+
+        // Insert above shifts; insert inside grows
+        doc.insert_row(0);
+        assert_eq!(
+            doc.merged_regions,
+            vec![(CellRef::new(1, 2), CellRef::new(2, 3))]
+        );
+        doc.insert_column(2);
+        assert_eq!(
+            doc.merged_regions,
+            vec![(CellRef::new(1, 2), CellRef::new(3, 3))]
+        );
+
+        // Delete inside shrinks; delete before shifts back
+        doc.delete_column(1);
+        assert_eq!(
+            doc.merged_regions,
+            vec![(CellRef::new(1, 2), CellRef::new(2, 3))]
+        );
+        doc.delete_row(0);
+        assert_eq!(
+            doc.merged_regions,
+            vec![(CellRef::new(1, 1), CellRef::new(2, 2))]
+        );
+
+        // Shrinking to a single cell drops the merge
+        doc.delete_row(2);
+        doc.delete_column(2);
+        assert!(doc.merged_regions.is_empty());
+    }
+
+    #[test]
+    fn test_merges_and_their_shifts_are_undoable() {
+        let mut doc = Document::new();
+        let b2_c3 = (CellRef::new(1, 1), CellRef::new(2, 2));
+        doc.merge_cells(&b2_c3.0, &b2_c3.1).unwrap();
+        doc.undo().unwrap();
+        assert!(doc.merged_regions.is_empty());
+        doc.redo().unwrap();
+        assert_eq!(doc.merged_regions, vec![b2_c3.clone()]);
+
+        // An insert's undo puts the shifted region back with the cells
+        doc.set_cell_from_input(CellRef::new(1, 1), "x").unwrap();
+        doc.insert_row(0);
+        doc.undo().unwrap();
+        assert_eq!(doc.merged_regions, vec![b2_c3.clone()]);
+        assert_eq!(doc.get_cell_display(&CellRef::new(1, 1)), "x");
+
+        assert!(doc.unmerge_cells(&CellRef::new(1, 1)));
+        doc.undo().unwrap();
+        assert_eq!(doc.merged_regions, vec![b2_c3]);
+    }
+}
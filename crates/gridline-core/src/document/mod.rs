@@ -1,10 +1,27 @@
 //! Document state and logic (UI-agnostic).
 
+mod affected;
+mod align;
+mod audit;
+mod column_formula;
+mod definition;
 mod eval;
 mod io;
+mod lambda;
+mod merge;
+mod notify;
 mod ops;
+mod outline;
+mod profile;
 mod script;
 mod state;
+mod volatile;
 
+pub use align::{Align, AlignedRange};
+pub use audit::{AuditIssue, AuditWarning};
+pub use definition::Definition;
+pub use notify::CellChange;
+pub use ops::normalize_formula_input;
+pub use profile::{ColumnProfile, SelectionStats};
 pub use script::ScriptContext;
-pub use state::{Document, UndoAction, UndoEntry};
+pub use state::{Document, EvalPolicy, FormulaLimits, Layout, UndoAction, UndoEntry};
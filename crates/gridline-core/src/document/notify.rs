@@ -0,0 +1,140 @@
+//! Change notifications for embedders: after each edit, cells whose displayed
+//! value changed are sent to a subscriber, so external UIs can react without
+//! polling. Changes are found by diffing against a snapshot of the last
+//! published displays, which is only kept while someone is subscribed.
+
+use super::Document;
+use gridline_engine::engine::CellRef;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Sender;
+
+/// A cell whose displayed value changed. An empty display means the cell is
+/// (or was) blank.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellChange {
+    pub cell: CellRef,
+    pub old_display: String,
+    pub new_display: String,
+}
+
+impl Document {
+    /// Send a [`CellChange`] to `sender` after each edit that changes a
+    /// displayed value, replacing any previous subscriber. Formulas are then
+    /// evaluated after every edit, as under [`super::EvalPolicy::Eager`].
+    /// Values displayed now are the baseline and are not sent. Dropping the
+    /// receiver unsubscribes.
+    pub fn subscribe(&mut self, sender: Sender<CellChange>) {
+        self.change_subscriber = None;
+        self.evaluate_all_cells();
+        self.published_displays = self.current_displays();
+        self.change_subscriber = Some(sender);
+    }
+
+    /// Stop sending change events.
+    pub fn unsubscribe(&mut self) {
+        self.change_subscriber = None;
+        self.published_displays.clear();
+    }
+
+    /// Whether change events are being sent.
+    pub fn has_subscriber(&self) -> bool {
+        self.change_subscriber.is_some()
+    }
+
+    /// Send an event for every display that differs from the last published
+    /// snapshot, in row-major order. Does nothing without a subscriber.
+    pub(crate) fn publish_changes(&mut self) {
+        let Some(sender) = self.change_subscriber.clone() else {
+            return;
+        };
+        let current = self.current_displays();
+        let cells: HashSet<&CellRef> = current
+            .keys()
+            .chain(self.published_displays.keys())
+            .collect();
+        let mut changes: Vec<CellChange> = cells
+            .into_iter()
+            .filter_map(|cell| {
+                let old_display = self.published_displays.get(cell).cloned();
+                let new_display = current.get(cell).cloned();
+                (old_display != new_display).then(|| CellChange {
+                    cell: cell.clone(),
+                    old_display: old_display.unwrap_or_default(),
+                    new_display: new_display.unwrap_or_default(),
+                })
+            })
+            .collect();
+        changes.sort_by_key(|change| (change.cell.row, change.cell.col));
+        self.published_displays = current;
+        for change in changes {
+            if sender.send(change).is_err() {
+                self.unsubscribe();
+                return;
+            }
+        }
+    }
+
+    /// Display of every non-blank cell, including spilled values.
+    fn current_displays(&mut self) -> HashMap<CellRef, String> {
+        let mut cells: Vec<CellRef> = self.grid.iter().map(|entry| entry.key().clone()).collect();
+        cells.extend(self.spill_sources.iter().map(|entry| entry.key().clone()));
+        cells
+            .into_iter()
+            .filter_map(|cell| {
+                let display = self.get_cell_display(&cell);
+                (!display.is_empty()).then_some((cell, display))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_editing_precedent_notifies_dependents() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "2").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 0), "=A1 * 10")
+            .unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(0, 1), "=B1 + 1")
+            .unwrap(); // A2
+        doc.set_cell_from_input(CellRef::new(1, 1), "unrelated")
+            .unwrap(); // B2
+
+        let (sender, receiver) = mpsc::channel();
+        doc.subscribe(sender);
+        assert!(receiver.try_recv().is_err());
+
+        doc.set_cell_from_input(CellRef::new(0, 0), "3").unwrap();
+        let change = |cell: CellRef, old: &str, new: &str| CellChange {
+            cell,
+            old_display: old.to_string(),
+            new_display: new.to_string(),
+        };
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                change(CellRef::new(0, 0), "2", "3"),
+                change(CellRef::new(1, 0), "20", "30"),
+                change(CellRef::new(0, 1), "21", "31"),
+            ]
+        );
+
+        // An edit that changes nothing visible sends nothing
+        doc.set_cell_from_input(CellRef::new(0, 0), "3").unwrap();
+        assert!(receiver.try_recv().is_err());
+
+        doc.clear_cell(&CellRef::new(1, 1));
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![change(CellRef::new(1, 1), "unrelated", "")]
+        );
+
+        drop(receiver);
+        doc.set_cell_from_input(CellRef::new(0, 0), "4").unwrap();
+        assert!(!doc.has_subscriber());
+    }
+}
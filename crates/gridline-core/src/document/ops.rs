@@ -1,9 +1,10 @@
-use super::{Document, UndoAction, UndoEntry};
+use super::{Document, Layout, UndoAction, UndoEntry};
 use crate::error::{GridlineError, Result};
 use gridline_engine::engine::{
     Cell, CellRef, CellType, Dynamic, ShiftOperation, format_dynamic, offset_formula_references,
-    shift_formula_references,
+    rename_cell_reference, shift_formula_references, transpose_formula_references,
 };
+use rhai::FnPtr;
 
 /// Dimension for row/column operations
 #[derive(Copy, Clone)]
@@ -54,6 +55,39 @@ fn frozen_cell_from_dynamic(value: &Dynamic) -> Cell {
     Cell::new_text(&format_dynamic(value))
 }
 
+/// The literal cell a generated value is written as: numbers keep full
+/// precision, booleans become `TRUE`/`FALSE` text and `()` leaves the cell empty.
+fn generated_cell(value: &Dynamic) -> Cell {
+    if value.is_unit() {
+        Cell::new_empty()
+    } else if let Ok(n) = value.as_float() {
+        Cell::new_number(n)
+    } else if let Ok(n) = value.as_int() {
+        Cell::new_number(n as f64)
+    } else if let Ok(text) = value.clone().into_string() {
+        Cell::new_text(&text)
+    } else {
+        Cell::new_text(&format_dynamic(value))
+    }
+}
+
+/// Join a formula edited over several lines into a single-line expression.
+///
+/// Each line is trimmed and blank lines dropped, so
+/// `"=SUM(A1:A3)\n  + 1"` becomes `"=SUM(A1:A3) + 1"`. Non-formula input is
+/// returned unchanged.
+pub fn normalize_formula_input(input: &str) -> String {
+    if !input.trim_start().starts_with('=') || !input.contains(['\n', '\r']) {
+        return input.to_string();
+    }
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 impl Document {
     fn snapshot_grid(&self) -> std::collections::HashMap<CellRef, Cell> {
         self.grid
@@ -62,7 +96,11 @@ impl Document {
             .collect()
     }
 
-    fn push_undo_for_grid_delta(&mut self, before: std::collections::HashMap<CellRef, Cell>) {
+    fn push_undo_for_grid_delta(
+        &mut self,
+        before: std::collections::HashMap<CellRef, Cell>,
+        old_layout: Layout,
+    ) {
         let after = self.snapshot_grid();
         let mut refs = std::collections::HashSet::with_capacity(before.len() + after.len());
         refs.extend(before.keys().cloned());
@@ -72,9 +110,7 @@ impl Document {
         for cell_ref in refs {
             let old_cell = before.get(&cell_ref).cloned();
             let new_cell = after.get(&cell_ref).cloned();
-            let old_input = old_cell.as_ref().map(Cell::to_input_string);
-            let new_input = new_cell.as_ref().map(Cell::to_input_string);
-            if old_input != new_input {
+            if old_cell != new_cell {
                 actions.push(UndoAction {
                     cell_ref,
                     old_cell,
@@ -89,11 +125,11 @@ impl Document {
                 .cmp(&b.cell_ref.row)
                 .then(a.cell_ref.col.cmp(&b.cell_ref.col))
         });
-        self.push_undo_batch(actions);
+        self.push_undo_with_layout(actions, old_layout);
     }
 
     /// Mark all cells that depend (transitively) on the changed cell as dirty
-    fn mark_dependents_dirty(&mut self, changed_cell: &CellRef) {
+    pub(crate) fn mark_dependents_dirty(&mut self, changed_cell: &CellRef) {
         let mut to_process = vec![changed_cell.clone()];
         let mut visited = std::collections::HashSet::new();
         while let Some(cell_ref) = to_process.pop() {
@@ -116,6 +152,37 @@ impl Document {
         }
     }
 
+    /// Force the formulas in the rectangle `a`..`b` (and anything depending
+    /// on them) to re-evaluate when next displayed, leaving every other cached
+    /// value alone. Returns the number of formula cells in the range.
+    pub fn recalc_range(&mut self, a: &CellRef, b: &CellRef) -> usize {
+        let (min_col, max_col) = (a.col.min(b.col), a.col.max(b.col));
+        let (min_row, max_row) = (a.row.min(b.row), a.row.max(b.row));
+        let cells: Vec<CellRef> = self
+            .grid
+            .iter()
+            .filter(|entry| {
+                let cell_ref = entry.key();
+                matches!(entry.contents, CellType::Script(_))
+                    && (min_col..=max_col).contains(&cell_ref.col)
+                    && (min_row..=max_row).contains(&cell_ref.row)
+            })
+            .map(|entry| entry.key().clone())
+            .collect();
+        for cell_ref in &cells {
+            if let Some(mut cell) = self.grid.get_mut(cell_ref) {
+                cell.dirty = true;
+                cell.cached_value = None;
+            }
+            self.value_cache.remove(cell_ref);
+            self.error_cells.remove(cell_ref);
+            self.clear_spill_from(cell_ref);
+            self.mark_dependents_dirty(cell_ref);
+        }
+        self.apply_eval_policy();
+        cells.len()
+    }
+
     pub(crate) fn invalidate_script_cache(&mut self) {
         for mut entry in self.grid.iter_mut() {
             if let CellType::Script(_) = entry.contents {
@@ -129,7 +196,7 @@ impl Document {
     /// Returns any spill source that was invalidated so dependents can be dirtied.
     pub(crate) fn prepare_overwrite(&mut self, cell_ref: &CellRef) -> Option<CellRef> {
         // If writing into a spill output, invalidate the source spill and force source re-eval.
-        let spilled_from = self.spill_sources.get(cell_ref).cloned();
+        let spilled_from = self.spill_sources.get(cell_ref).map(|src| src.clone());
         if let Some(source) = &spilled_from {
             self.clear_spill_from(source);
             if let Some(mut src_cell) = self.grid.get_mut(source) {
@@ -144,6 +211,7 @@ impl Document {
         // Also remove direct stale entries at this exact position.
         self.spill_sources.remove(cell_ref);
         self.value_cache.remove(cell_ref);
+        self.error_cells.remove(cell_ref);
 
         spilled_from
     }
@@ -192,16 +260,60 @@ impl Document {
         if actions.is_empty() {
             return;
         }
-        self.undo_stack.push(UndoEntry::Batch(actions));
+        self.push_undo_entry(UndoEntry::Batch(actions));
+    }
+
+    /// Push cell actions along with the layout from before they were made,
+    /// as a plain batch when the layout did not change.
+    pub(crate) fn push_undo_with_layout(&mut self, actions: Vec<UndoAction>, old_layout: Layout) {
+        let new_layout = self.layout();
+        if new_layout == old_layout {
+            self.push_undo_batch(actions);
+        } else {
+            self.push_undo_entry(UndoEntry::Layout {
+                actions,
+                old_layout,
+                new_layout,
+            });
+        }
+    }
+
+    fn push_undo_entry(&mut self, entry: UndoEntry) {
+        self.undo_stack.push(entry);
         self.redo_stack.clear();
         if self.undo_stack.len() > super::state::MAX_UNDO_STACK {
             self.undo_stack.remove(0);
         }
     }
 
+    /// The layout undo restores alongside cells.
+    pub(crate) fn layout(&self) -> Layout {
+        Layout {
+            merged_regions: self.merged_regions.clone(),
+            cell_alignments: self.cell_alignments.clone(),
+            row_groups: self.row_groups.clone(),
+            collapsed_row_groups: self.collapsed_row_groups.clone(),
+        }
+    }
+
+    fn restore_layout(&mut self, layout: Layout) {
+        self.merged_regions = layout.merged_regions;
+        self.cell_alignments = layout.cell_alignments;
+        self.row_groups = layout.row_groups;
+        self.collapsed_row_groups = layout.collapsed_row_groups;
+    }
+
     /// Set cell contents from input string.
+    ///
+    /// Grouped numbers such as `1,234.56` are parsed according to `number_locale`.
+    /// Formulas exceeding `formula_limits` are rejected before any parsing.
+    /// Formulas typed over several lines are joined into one (see [`normalize_formula_input`]).
     pub fn set_cell_from_input(&mut self, cell_ref: CellRef, input: &str) -> Result<()> {
-        let cell = Cell::from_input(input);
+        let input = &normalize_formula_input(input);
+        if let Some(formula) = input.trim().strip_prefix('=') {
+            self.formula_limits.check(formula)?;
+        }
+        let cell = Cell::from_input_with_locale(input, self.number_locale);
         let mut invalidated_spill_sources = Vec::new();
 
         // Check for circular dependencies if it's a script
@@ -209,7 +321,8 @@ impl Document {
             // Temporarily insert to check for cycles
             let old_cell = self.grid.get(&cell_ref).map(|r| r.clone());
             self.grid.insert(cell_ref.clone(), cell.clone());
-            if gridline_engine::engine::detect_cycle(&cell_ref, &self.grid).is_some() {
+            self.refresh_contextual_dependencies();
+            if let Some(cycle) = gridline_engine::engine::detect_cycle(&cell_ref, &self.grid) {
                 // Restore old state
                 match old_cell {
                     Some(c) => {
@@ -219,7 +332,7 @@ impl Document {
                         self.grid.remove(&cell_ref);
                     }
                 }
-                return Err(GridlineError::CircularDependency);
+                return Err(GridlineError::circular(&cycle));
             }
             // Cycle check passed, now push undo (restore old state first, then re-insert)
             match old_cell {
@@ -255,6 +368,7 @@ impl Document {
                 self.mark_dependents_dirty(&source);
             }
         }
+        self.apply_eval_policy();
 
         Ok(())
     }
@@ -262,8 +376,12 @@ impl Document {
     /// Clear the specified cell
     pub fn clear_cell(&mut self, cell_ref: &CellRef) {
         if self.grid.get(cell_ref).is_some() {
+            let frozen = self.freeze_dependents_of(|cell| cell == cell_ref);
             let invalidated_spill_source = self.prepare_overwrite(cell_ref);
             self.push_undo(cell_ref.clone(), None);
+            if frozen > 0 {
+                self.merge_last_two_undo_entries();
+            }
             self.grid.remove(cell_ref);
             self.modified = true;
 
@@ -275,12 +393,122 @@ impl Document {
             {
                 self.mark_dependents_dirty(&source);
             }
+            self.apply_eval_policy();
+        }
+    }
+
+    /// Clear every cell in the rectangle spanned by two corners as a single
+    /// undo step, rebuilding dependencies once. Returns the number of cells cleared.
+    pub fn clear_range(&mut self, top_left: &CellRef, bottom_right: &CellRef) -> usize {
+        let (min_col, max_col) = (
+            top_left.col.min(bottom_right.col),
+            top_left.col.max(bottom_right.col),
+        );
+        let (min_row, max_row) = (
+            top_left.row.min(bottom_right.row),
+            top_left.row.max(bottom_right.row),
+        );
+        let mut targets: Vec<CellRef> = self
+            .grid
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|cell| {
+                (min_col..=max_col).contains(&cell.col) && (min_row..=max_row).contains(&cell.row)
+            })
+            .collect();
+        if targets.is_empty() {
+            return 0;
+        }
+        targets.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+        let frozen = self.freeze_dependents_of(|cell| {
+            (min_col..=max_col).contains(&cell.col) && (min_row..=max_row).contains(&cell.row)
+        });
+
+        let mut actions = Vec::with_capacity(targets.len());
+        let mut invalidated_spill_sources = Vec::new();
+        for cell_ref in &targets {
+            if let Some(source) = self.prepare_overwrite(cell_ref) {
+                invalidated_spill_sources.push(source);
+            }
+            if let Some((_, old_cell)) = self.grid.remove(cell_ref) {
+                actions.push(UndoAction {
+                    cell_ref: cell_ref.clone(),
+                    old_cell: Some(old_cell),
+                    new_cell: None,
+                });
+            }
+        }
+        let cleared = actions.len();
+        self.push_undo_batch(actions);
+        if frozen > 0 {
+            self.merge_last_two_undo_entries();
+        }
+        self.modified = true;
+
+        self.rebuild_dependents();
+        for cell_ref in targets.iter().chain(&invalidated_spill_sources) {
+            self.mark_dependents_dirty(cell_ref);
+        }
+        self.apply_eval_policy();
+        cleared
+    }
+
+    /// Rewrite text cells in the rectangle spanned by two corners whose
+    /// content parses as a number (per `number_locale`, `%` included) into
+    /// number cells, as a single undo step. Genuine text is left alone.
+    /// Returns the number of cells converted.
+    pub fn convert_text_to_numbers(&mut self, top_left: &CellRef, bottom_right: &CellRef) -> usize {
+        let (min_col, max_col) = (
+            top_left.col.min(bottom_right.col),
+            top_left.col.max(bottom_right.col),
+        );
+        let (min_row, max_row) = (
+            top_left.row.min(bottom_right.row),
+            top_left.row.max(bottom_right.row),
+        );
+        let mut conversions: Vec<(CellRef, Cell)> = self
+            .grid
+            .iter()
+            .filter(|entry| {
+                let cell = entry.key();
+                (min_col..=max_col).contains(&cell.col) && (min_row..=max_row).contains(&cell.row)
+            })
+            .filter_map(|entry| match &entry.contents {
+                CellType::Text(text) if !text.trim_start().starts_with(['=', '"']) => {
+                    let cell = Cell::from_input_with_locale(text, self.number_locale);
+                    matches!(cell.contents, CellType::Number(_))
+                        .then(|| (entry.key().clone(), cell))
+                }
+                _ => None,
+            })
+            .collect();
+        if conversions.is_empty() {
+            return 0;
+        }
+        conversions.sort_by(|a, b| a.0.row.cmp(&b.0.row).then(a.0.col.cmp(&b.0.col)));
+
+        let mut actions = Vec::with_capacity(conversions.len());
+        for (cell_ref, cell) in &conversions {
+            let old_cell = self.grid.insert(cell_ref.clone(), cell.clone());
+            actions.push(UndoAction {
+                cell_ref: cell_ref.clone(),
+                old_cell,
+                new_cell: Some(cell.clone()),
+            });
+        }
+        self.push_undo_batch(actions);
+        self.modified = true;
+        for (cell_ref, _) in &conversions {
+            self.mark_dependents_dirty(cell_ref);
         }
+        self.apply_eval_policy();
+        conversions.len()
     }
 
     /// Generic insert operation for row or column
     fn insert_dimension(&mut self, dim: Dimension, at: usize) {
         let before = self.snapshot_grid();
+        let old_layout = self.layout();
 
         // Collect all cells at coord >= at
         let cells_to_move: Vec<(CellRef, Cell)> = self
@@ -316,6 +544,18 @@ impl Document {
             }
         }
 
+        if let Dimension::Column = dim {
+            self.hidden_cols = self
+                .hidden_cols
+                .iter()
+                .map(|&col| if col >= at { col + 1 } else { col })
+                .collect();
+        }
+        self.shift_merged_regions(op);
+        self.shift_row_groups(op);
+        self.shift_alignments(op);
+        self.shift_volatile_cells(op);
+
         // Reinsert moved cells with coord + 1, also shifting their formulas
         for (cell_ref, cell) in cells_to_move {
             let coord = dim.get_coord(&cell_ref);
@@ -329,97 +569,146 @@ impl Document {
             self.grid.insert(new_ref, new_cell);
         }
 
+        // Rows inserted inside the data pick up their column formulas
+        for (cell_ref, cell) in self.shift_column_formulas(op) {
+            self.grid.entry(cell_ref).or_insert(cell);
+        }
+
         // Clear spill sources and value cache, then rebuild
         self.spill_sources.clear();
+        self.error_cells.clear();
         self.value_cache.clear();
         self.invalidate_script_cache();
         // Rebuild dependencies (DashMap shares data, so builtins already see updates)
         self.rebuild_dependents();
-        self.push_undo_for_grid_delta(before);
+        self.push_undo_for_grid_delta(before, old_layout);
         self.modified = true;
+        self.apply_eval_policy();
     }
 
     /// Generic delete operation for row or column
     fn delete_dimension(&mut self, dim: Dimension, at: usize) {
-        let before = self.snapshot_grid();
-
-        // Collect cells at the deleted coordinate
-        let cells_at: Vec<CellRef> = self
-            .grid
-            .iter()
-            .filter(|entry| dim.get_coord(entry.key()) == at)
-            .map(|entry| entry.key().clone())
-            .collect();
+        self.delete_dimensions(dim, &[at]);
+    }
 
-        // Remove cells at the deleted coordinate
-        for cell_ref in cells_at {
-            self.grid.remove(&cell_ref);
+    /// Delete several rows or columns in a single pass and one undo batch
+    fn delete_dimensions(&mut self, dim: Dimension, coords: &[usize]) {
+        let mut deleted = coords.to_vec();
+        deleted.sort_unstable();
+        deleted.dedup();
+        if deleted.is_empty() {
+            return;
+        }
+        let before = self.snapshot_grid();
+        let old_layout = self.layout();
+        if self.freeze_dependents_on_delete {
+            // Freeze inside the snapshot so the whole delete stays one undo step
+            let targets = self
+                .dependents_to_freeze(|cell| deleted.binary_search(&dim.get_coord(cell)).is_ok());
+            for (cell_ref, frozen_cell) in self.frozen_cells(targets) {
+                self.grid.insert(cell_ref, frozen_cell);
+            }
         }
 
-        // Collect cells after the deleted coordinate
-        let cells_to_move: Vec<(CellRef, Cell)> = self
-            .grid
+        // Shift ops applied highest-first so each index is still valid when applied
+        let ops: Vec<ShiftOperation> = deleted
             .iter()
-            .filter(|entry| dim.get_coord(entry.key()) > at)
-            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .rev()
+            .map(|&at| match dim {
+                Dimension::Row => ShiftOperation::DeleteRow(at),
+                Dimension::Column => ShiftOperation::DeleteColumn(at),
+            })
             .collect();
-
-        // Remove them from grid
-        for (cell_ref, _) in &cells_to_move {
-            self.grid.remove(cell_ref);
+        let shift = |formula: &str| {
+            ops.iter().fold(formula.to_string(), |f, &op| {
+                shift_formula_references(&f, op)
+            })
+        };
+        for &op in &ops {
+            self.shift_column_formulas(op);
+            self.shift_merged_regions(op);
+            self.shift_row_groups(op);
+            self.shift_alignments(op);
+            self.shift_volatile_cells(op);
         }
+        // Number of deleted coordinates before `coord`
+        let removed_before = |coord: usize| deleted.partition_point(|&d| d < coord);
 
-        // Update ALL formulas with shifted references
-        let op = match dim {
-            Dimension::Row => ShiftOperation::DeleteRow(at),
-            Dimension::Column => ShiftOperation::DeleteColumn(at),
-        };
+        // Take every cell out, dropping those at deleted coordinates
         let all_cells: Vec<(CellRef, Cell)> = self
             .grid
             .iter()
             .map(|entry| (entry.key().clone(), entry.value().clone()))
             .collect();
+        self.grid.clear();
 
+        // Reinsert survivors with shifted coordinates and formulas
         for (cell_ref, cell) in all_cells {
-            if let CellType::Script(formula) = &cell.contents {
-                let new_formula = shift_formula_references(formula, op);
-                if new_formula != *formula {
-                    let new_cell = if new_formula.contains("#REF!") {
+            let coord = dim.get_coord(&cell_ref);
+            if deleted.binary_search(&coord).is_ok() {
+                continue;
+            }
+            let new_ref = dim.new_cell_ref(&cell_ref, coord - removed_before(coord));
+            let new_cell = match &cell.contents {
+                CellType::Script(formula) => {
+                    let new_formula = shift(formula);
+                    if new_formula == *formula {
+                        cell
+                    } else if new_formula.contains("#REF!") {
                         // Create a text cell with #REF! error
                         Cell::new_text(&format!("={}", new_formula))
                     } else {
                         Cell::new_script(&new_formula)
-                    };
-                    self.grid.insert(cell_ref, new_cell);
-                }
-            }
-        }
-
-        // Reinsert moved cells with coord - 1, also shifting their formulas
-        for (cell_ref, cell) in cells_to_move {
-            let coord = dim.get_coord(&cell_ref);
-            let new_ref = dim.new_cell_ref(&cell_ref, coord - 1);
-            let new_cell = if let CellType::Script(formula) = &cell.contents {
-                let new_formula = shift_formula_references(formula, op);
-                if new_formula.contains("#REF!") {
-                    Cell::new_text(&format!("={}", new_formula))
-                } else {
-                    Cell::new_script(&new_formula)
+                    }
                 }
-            } else {
-                cell
+                _ => cell,
             };
             self.grid.insert(new_ref, new_cell);
         }
 
+        if let Dimension::Column = dim {
+            self.hidden_cols = self
+                .hidden_cols
+                .iter()
+                .filter(|col| deleted.binary_search(col).is_err())
+                .map(|&col| col - removed_before(col))
+                .collect();
+        }
+
         // Clear spill sources and value cache, then rebuild
         self.spill_sources.clear();
+        self.error_cells.clear();
         self.value_cache.clear();
         self.invalidate_script_cache();
         // Rebuild dependencies (DashMap shares data, so builtins already see updates)
         self.rebuild_dependents();
-        self.push_undo_for_grid_delta(before);
+        self.push_undo_for_grid_delta(before, old_layout);
         self.modified = true;
+        self.apply_eval_policy();
+    }
+
+    /// Remove fully-empty rows or columns between the first and last used ones.
+    /// Returns the removed coordinates (ascending, pre-compaction indices).
+    fn compact_dimension(&mut self, dim: Dimension) -> Vec<usize> {
+        let mut used: std::collections::BTreeSet<usize> = self
+            .grid
+            .iter()
+            .filter(|entry| !matches!(entry.value().contents, CellType::Empty))
+            .map(|entry| dim.get_coord(entry.key()))
+            .collect();
+        // Spilled values live outside the grid but still occupy their cells
+        used.extend(
+            self.spill_sources
+                .iter()
+                .map(|entry| dim.get_coord(entry.key())),
+        );
+
+        let (Some(&first), Some(&last)) = (used.first(), used.last()) else {
+            return Vec::new();
+        };
+        let empty: Vec<usize> = (first..=last).filter(|c| !used.contains(c)).collect();
+        self.delete_dimensions(dim, &empty);
+        empty
     }
 
     /// Insert a row above the specified row
@@ -442,6 +731,51 @@ impl Document {
         self.delete_dimension(Dimension::Column, at_col);
     }
 
+    /// Remove empty rows within the used range, shifting cells up.
+    /// Returns the removed row indices.
+    pub fn compact_rows(&mut self) -> Vec<usize> {
+        self.compact_dimension(Dimension::Row)
+    }
+
+    /// Remove empty columns within the used range, shifting cells left.
+    /// Returns the removed column indices.
+    pub fn compact_columns(&mut self) -> Vec<usize> {
+        self.compact_dimension(Dimension::Column)
+    }
+
+    /// Hide a column from display. Returns false if it was already hidden.
+    pub fn hide_column(&mut self, col: usize) -> bool {
+        let changed = self.hidden_cols.insert(col);
+        if changed {
+            self.modified = true;
+        }
+        changed
+    }
+
+    /// Show a previously hidden column. Returns false if it was not hidden.
+    pub fn show_column(&mut self, col: usize) -> bool {
+        let changed = self.hidden_cols.remove(&col);
+        if changed {
+            self.modified = true;
+        }
+        changed
+    }
+
+    /// Show all hidden columns. Returns the number of columns unhidden.
+    pub fn show_all_columns(&mut self) -> usize {
+        let count = self.hidden_cols.len();
+        if count > 0 {
+            self.hidden_cols.clear();
+            self.modified = true;
+        }
+        count
+    }
+
+    /// Whether a column is hidden from display
+    pub fn is_column_hidden(&self, col: usize) -> bool {
+        self.hidden_cols.contains(&col)
+    }
+
     /// Undo the last action
     pub fn undo(&mut self) -> Result<()> {
         let entry = self.undo_stack.pop().ok_or(GridlineError::NothingToUndo)?;
@@ -470,43 +804,25 @@ impl Document {
                 }
             }
             UndoEntry::Batch(actions) => {
-                // Build inverse batch for redo
-                let mut redo_actions = Vec::with_capacity(actions.len());
-                let mut affected_cells = Vec::with_capacity(actions.len());
-
-                for action in &actions {
-                    let current = self.grid.get(&action.cell_ref).map(|r| r.clone());
-                    redo_actions.push(UndoAction {
-                        cell_ref: action.cell_ref.clone(),
-                        old_cell: action.old_cell.clone(), // State after undo
-                        new_cell: current,                 // State before undo
-                    });
-                    affected_cells.push(action.cell_ref.clone());
-                }
-
+                let redo_actions = self.apply_history_batch(actions, true);
                 self.redo_stack.push(UndoEntry::Batch(redo_actions));
-
-                // Restore old states
-                let mut additionally_dirty = Vec::new();
-                for action in actions {
-                    self.apply_history_cell_state(
-                        &action.cell_ref,
-                        action.old_cell,
-                        &mut additionally_dirty,
-                    );
-                }
-
-                // Rebuild dependencies once
-                self.rebuild_dependents();
-                for cell_ref in affected_cells {
-                    self.mark_dependents_dirty(&cell_ref);
-                }
-                for spill_source in additionally_dirty {
-                    self.mark_dependents_dirty(&spill_source);
-                }
+            }
+            UndoEntry::Layout {
+                actions,
+                old_layout,
+                new_layout,
+            } => {
+                self.restore_layout(old_layout.clone());
+                let actions = self.apply_history_batch(actions, true);
+                self.redo_stack.push(UndoEntry::Layout {
+                    actions,
+                    old_layout,
+                    new_layout,
+                });
             }
         }
         self.modified = true;
+        self.apply_eval_policy();
         Ok(())
     }
 
@@ -538,47 +854,73 @@ impl Document {
                 }
             }
             UndoEntry::Batch(actions) => {
-                // Build inverse batch for undo
-                let mut undo_actions = Vec::with_capacity(actions.len());
-                let mut affected_cells = Vec::with_capacity(actions.len());
-
-                for action in &actions {
-                    let current = self.grid.get(&action.cell_ref).map(|r| r.clone());
-                    undo_actions.push(UndoAction {
-                        cell_ref: action.cell_ref.clone(),
-                        old_cell: current,
-                        new_cell: action.new_cell.clone(),
-                    });
-                    affected_cells.push(action.cell_ref.clone());
-                }
-
+                let undo_actions = self.apply_history_batch(actions, false);
                 self.undo_stack.push(UndoEntry::Batch(undo_actions));
-
-                // Apply new states
-                let mut additionally_dirty = Vec::new();
-                for action in actions {
-                    self.apply_history_cell_state(
-                        &action.cell_ref,
-                        action.new_cell,
-                        &mut additionally_dirty,
-                    );
-                }
-
-                // Rebuild dependencies once
-                self.rebuild_dependents();
-                for cell_ref in affected_cells {
-                    self.mark_dependents_dirty(&cell_ref);
-                }
-                for spill_source in additionally_dirty {
-                    self.mark_dependents_dirty(&spill_source);
-                }
+            }
+            UndoEntry::Layout {
+                actions,
+                old_layout,
+                new_layout,
+            } => {
+                self.restore_layout(new_layout.clone());
+                let actions = self.apply_history_batch(actions, false);
+                self.undo_stack.push(UndoEntry::Layout {
+                    actions,
+                    old_layout,
+                    new_layout,
+                });
             }
         }
         self.modified = true;
+        self.apply_eval_policy();
         Ok(())
     }
 
-    /// Paste cells at a base column/row, recording undo and dependencies.
+    /// Restore each cell of a history batch to its old state (`undo`) or its
+    /// new one (redo), returning the inverse batch for the opposite stack.
+    fn apply_history_batch(&mut self, actions: Vec<UndoAction>, undo: bool) -> Vec<UndoAction> {
+        // Capture every current state before changing any
+        let inverse: Vec<UndoAction> = actions
+            .iter()
+            .map(|action| {
+                let current = self.grid.get(&action.cell_ref).map(|r| r.clone());
+                let (old_cell, new_cell) = if undo {
+                    (action.old_cell.clone(), current)
+                } else {
+                    (current, action.new_cell.clone())
+                };
+                UndoAction {
+                    cell_ref: action.cell_ref.clone(),
+                    old_cell,
+                    new_cell,
+                }
+            })
+            .collect();
+
+        let mut affected_cells = Vec::with_capacity(actions.len());
+        let mut additionally_dirty = Vec::new();
+        for action in actions {
+            let target = if undo {
+                action.old_cell
+            } else {
+                action.new_cell
+            };
+            self.apply_history_cell_state(&action.cell_ref, target, &mut additionally_dirty);
+            affected_cells.push(action.cell_ref);
+        }
+
+        // Rebuild dependencies once
+        self.rebuild_dependents();
+        for cell_ref in affected_cells {
+            self.mark_dependents_dirty(&cell_ref);
+        }
+        for spill_source in additionally_dirty {
+            self.mark_dependents_dirty(&spill_source);
+        }
+        inverse
+    }
+
+    /// Paste cells at a base column/row as a single undo step, recording dependencies.
     pub fn paste_cells(
         &mut self,
         base_col: usize,
@@ -587,26 +929,91 @@ impl Document {
         source_base_row: usize,
         clipboard_cells: &[(usize, usize, Cell)],
     ) -> Result<usize> {
-        let delta_col = base_col as isize - source_base_col as isize;
-        let delta_row = base_row as isize - source_base_row as isize;
         let mut prepared = Vec::new();
+        prepare_paste(
+            &mut prepared,
+            (base_col, base_row),
+            (source_base_col, source_base_row),
+            clipboard_cells,
+        );
+        self.apply_paste(prepared)
+    }
+
+    /// Paste cells at a base column/row with rows and columns swapped, so a
+    /// copied row lands as a column. Each formula's references keep pointing
+    /// at the same neighbours under the transposed layout (see
+    /// [`transpose_formula_references`]). The paste is a single undo step.
+    pub fn paste_cells_transposed(
+        &mut self,
+        base_col: usize,
+        base_row: usize,
+        source_base_col: usize,
+        source_base_row: usize,
+        clipboard_cells: &[(usize, usize, Cell)],
+    ) -> Result<usize> {
+        let prepared = clipboard_cells
+            .iter()
+            .map(|(rel_col, rel_row, cell)| {
+                let source = CellRef::new(source_base_col + rel_col, source_base_row + rel_row);
+                let target = CellRef::new(base_col + rel_row, base_row + rel_col);
+                let pasted_cell = match &cell.contents {
+                    CellType::Script(formula) => {
+                        Cell::new_script(&transpose_formula_references(formula, &source, &target))
+                    }
+                    _ => cell.clone(),
+                };
+                (target, pasted_cell)
+            })
+            .collect();
+        self.apply_paste(prepared)
+    }
 
-        for (rel_col, rel_row, cell) in clipboard_cells {
-            let target = CellRef::new(base_col + rel_col, base_row + rel_row);
+    /// Paste a `clip_width` x `clip_height` clipboard into a selection, anchored at the
+    /// selection's top-left and tiled to fill it (spreadsheet-style).
+    ///
+    /// Only whole copies are tiled, so a selection smaller than the clipboard (or not a
+    /// multiple of it) still receives at least one complete paste. Formula references are
+    /// offset per tile. The whole paste is a single undo step.
+    pub fn paste_cells_tiled(
+        &mut self,
+        selection: ((usize, usize), (usize, usize)),
+        source_base_col: usize,
+        source_base_row: usize,
+        clip_width: usize,
+        clip_height: usize,
+        clipboard_cells: &[(usize, usize, Cell)],
+    ) -> Result<usize> {
+        let ((c1, r1), (c2, r2)) = selection;
+        let (min_col, max_col) = (c1.min(c2), c1.max(c2));
+        let (min_row, max_row) = (r1.min(r2), r1.max(r2));
+        let clip_width = clip_width.max(1);
+        let clip_height = clip_height.max(1);
+        let tiles_x = ((max_col - min_col + 1) / clip_width).max(1);
+        let tiles_y = ((max_row - min_row + 1) / clip_height).max(1);
 
-            let pasted_cell = match &cell.contents {
-                CellType::Script(formula) => {
-                    let shifted = offset_formula_references(formula, delta_col, delta_row);
-                    Cell::new_script(&shifted)
-                }
-                _ => cell.clone(),
-            };
-            prepared.push((target, pasted_cell));
+        let mut prepared = Vec::new();
+        for ty in 0..tiles_y {
+            for tx in 0..tiles_x {
+                prepare_paste(
+                    &mut prepared,
+                    (min_col + tx * clip_width, min_row + ty * clip_height),
+                    (source_base_col, source_base_row),
+                    clipboard_cells,
+                );
+            }
         }
+        self.apply_paste(prepared)
+    }
 
+    /// Write prepared paste targets as a single undo step, rejecting the whole
+    /// paste if any formula exceeds `formula_limits` or it would create a cycle.
+    fn apply_paste(&mut self, prepared: Vec<(CellRef, Cell)>) -> Result<usize> {
         if prepared.is_empty() {
             return Ok(0);
         }
+        for (_, cell) in &prepared {
+            self.check_formula_limits(cell)?;
+        }
 
         // Validate cycle safety transactionally before mutating spill/cache/undo state.
         let mut old_cells: std::collections::HashMap<CellRef, Option<Cell>> =
@@ -620,9 +1027,12 @@ impl Document {
             self.grid.insert(target.clone(), pasted_cell.clone());
         }
 
-        let has_cycle = prepared.iter().any(|(target, pasted_cell)| {
-            matches!(pasted_cell.contents, CellType::Script(_))
-                && gridline_engine::engine::detect_cycle(target, &self.grid).is_some()
+        let cycle = prepared.iter().find_map(|(target, pasted_cell)| {
+            if matches!(pasted_cell.contents, CellType::Script(_)) {
+                gridline_engine::engine::detect_cycle(target, &self.grid)
+            } else {
+                None
+            }
         });
 
         // Restore original grid state after validation pass.
@@ -637,20 +1047,26 @@ impl Document {
             }
         }
 
-        if has_cycle {
-            return Err(GridlineError::CircularDependency);
+        if let Some(cycle) = cycle {
+            return Err(GridlineError::circular(&cycle));
         }
 
         let mut pasted_cells = Vec::new();
         let mut additionally_dirty = Vec::new();
+        let mut actions = Vec::with_capacity(prepared.len());
         for (target, pasted_cell) in prepared {
             if let Some(spill_source) = self.prepare_overwrite(&target) {
                 additionally_dirty.push(spill_source);
             }
-            self.push_undo(target.clone(), Some(pasted_cell.clone()));
+            actions.push(UndoAction {
+                cell_ref: target.clone(),
+                old_cell: self.grid.get(&target).map(|r| r.clone()),
+                new_cell: Some(pasted_cell.clone()),
+            });
             self.grid.insert(target.clone(), pasted_cell);
             pasted_cells.push(target);
         }
+        self.push_undo_batch(actions);
 
         self.modified = true;
         // Rebuild dependencies (DashMap shares data, so builtins already see updates)
@@ -664,35 +1080,229 @@ impl Document {
         for spill_source in additionally_dirty {
             self.mark_dependents_dirty(&spill_source);
         }
+        self.apply_eval_policy();
 
         Ok(count)
     }
 
-    /// Freeze a formula cell or spill output at `cell_ref` to its current value.
+    /// Paste tab-separated text (e.g. from another application) at a base cell.
     ///
-    /// Returns `1` if a cell was frozen, otherwise `0`.
-    pub fn freeze_cell(&mut self, cell_ref: &CellRef) -> usize {
-        let is_formula_source = self
-            .grid
-            .get(cell_ref)
-            .is_some_and(|cell| matches!(cell.contents, CellType::Script(_)));
-        let is_spill_output = self.spill_sources.contains_key(cell_ref);
-        if !is_formula_source && !is_spill_output {
-            return 0;
+    /// Blank fields are skipped rather than clearing existing cells, so trailing
+    /// tabs and ragged rows leave neighbouring data untouched. Returns the
+    /// number of cells written.
+    pub fn paste_tsv(&mut self, base_col: usize, base_row: usize, text: &str) -> Result<usize> {
+        let mut cells = Vec::new();
+        for (dr, row) in crate::storage::parse_tsv(text).iter().enumerate() {
+            for (dc, value) in row.iter().enumerate() {
+                if value.trim().is_empty() {
+                    continue;
+                }
+                cells.push((
+                    dc,
+                    dr,
+                    Cell::from_input_with_locale(value, self.number_locale),
+                ));
+            }
         }
-        self.freeze_targets(vec![cell_ref.clone()])
+        self.paste_cells(base_col, base_row, base_col, base_row, &cells)
     }
 
-    /// Freeze all formula cells (and their spill outputs) to current values.
+    /// Cells that pasting `grid` (rows of fields, as from [`parse_tsv`]) at
+    /// `base` would overwrite: targets of non-blank fields that already hold a
+    /// value or a spilled output. Returned row-major.
     ///
-    /// Returns the number of cells that were frozen.
-    pub fn freeze_all(&mut self) -> usize {
-        let mut formula_sources: Vec<CellRef> = self
-            .grid
-            .iter()
-            .filter(|entry| matches!(entry.value().contents, CellType::Script(_)))
-            .map(|entry| entry.key().clone())
-            .collect();
+    /// [`parse_tsv`]: crate::storage::parse_tsv
+    pub fn paste_conflicts(&self, base: &CellRef, grid: &[Vec<String>]) -> Vec<CellRef> {
+        let mut conflicts = Vec::new();
+        for (dr, row) in grid.iter().enumerate() {
+            for (dc, value) in row.iter().enumerate() {
+                if value.trim().is_empty() {
+                    continue;
+                }
+                let target = CellRef::new(base.col + dc, base.row + dr);
+                let occupied = self
+                    .grid
+                    .get(&target)
+                    .is_some_and(|c| !matches!(c.contents, CellType::Empty))
+                    || self.spill_sources.contains_key(&target);
+                if occupied {
+                    conflicts.push(target);
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Enter the same input into every cell of a rectangle, like a legacy array entry.
+    ///
+    /// Formula references are offset per position relative to the top-left cell,
+    /// exactly as pasting that formula would, and the fill is a single undo step.
+    pub fn fill_range_from_input(
+        &mut self,
+        start: &CellRef,
+        end: &CellRef,
+        input: &str,
+    ) -> Result<usize> {
+        let input = &normalize_formula_input(input);
+        if let Some(formula) = input.trim().strip_prefix('=') {
+            self.formula_limits.check(formula)?;
+        }
+        let cell = Cell::from_input_with_locale(input, self.number_locale);
+        let (min_col, max_col) = (start.col.min(end.col), start.col.max(end.col));
+        let (min_row, max_row) = (start.row.min(end.row), start.row.max(end.row));
+
+        let mut cells = Vec::new();
+        for dr in 0..=max_row - min_row {
+            for dc in 0..=max_col - min_col {
+                let filled = match &cell.contents {
+                    CellType::Script(formula) => Cell::new_script(&offset_formula_references(
+                        formula,
+                        dc as isize,
+                        dr as isize,
+                    )),
+                    _ => cell.clone(),
+                };
+                cells.push((dc, dr, filled));
+            }
+        }
+        self.paste_cells(min_col, min_row, min_col, min_row, &cells)
+    }
+
+    /// Fill the rectangle between two corners, row by row, with the values a
+    /// Rhai closure returns for each cell's 0-based index (e.g. `|i| i * i`).
+    /// The closure runs on the formula engine, so it can read cells and call
+    /// custom functions but not write. Values are written as literals in one
+    /// undo step; if the closure errors, nothing is written. Returns the
+    /// number of cells filled.
+    pub fn fill_range_with_generator(
+        &mut self,
+        start: &CellRef,
+        end: &CellRef,
+        closure: &str,
+    ) -> Result<usize> {
+        let ast = self
+            .engine
+            .compile(closure)
+            .map_err(|e| GridlineError::RhaiCompile(e.to_string()))?;
+        let ast = match &self.custom_ast {
+            Some(custom_ast) => custom_ast.merge(&ast),
+            None => ast,
+        };
+        let value: Dynamic = self.engine.eval_ast(&ast)?;
+        let type_name = value.type_name().to_string();
+        let generator = value
+            .try_cast::<FnPtr>()
+            .ok_or(GridlineError::NotAClosure(type_name))?;
+
+        let (min_col, max_col) = (start.col.min(end.col), start.col.max(end.col));
+        let (min_row, max_row) = (start.row.min(end.row), start.row.max(end.row));
+        let width = max_col - min_col + 1;
+        let mut cells = Vec::new();
+        for index in 0..width * (max_row - min_row + 1) {
+            let value: Dynamic = generator
+                .call(&self.engine, &ast, (index as i64,))
+                .map_err(|e| GridlineError::GeneratorFailed {
+                    index,
+                    message: e.to_string(),
+                })?;
+            cells.push((index % width, index / width, generated_cell(&value)));
+        }
+        self.paste_cells(min_col, min_row, min_col, min_row, &cells)
+    }
+
+    /// Move one cell to an empty target, rewriting every formula that referenced
+    /// the old location to follow it. The move is a single undo step.
+    pub fn move_cell(&mut self, from: &CellRef, to: &CellRef) -> Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        let cell = self
+            .grid
+            .get(from)
+            .map(|c| c.clone())
+            .filter(|c| !matches!(c.contents, CellType::Empty))
+            .ok_or_else(|| GridlineError::EmptyCell(from.to_string()))?;
+        let target_occupied = self
+            .grid
+            .get(to)
+            .is_some_and(|c| !matches!(c.contents, CellType::Empty))
+            || self.spill_sources.contains_key(to);
+        if target_occupied {
+            return Err(GridlineError::TargetOccupied(to.to_string()));
+        }
+
+        let before = self.snapshot_grid();
+        let old_layout = self.layout();
+        self.grid.remove(from);
+        self.grid.insert(to.clone(), cell);
+
+        let rewrites: Vec<(CellRef, String)> = self
+            .grid
+            .iter()
+            .filter_map(|entry| match &entry.value().contents {
+                CellType::Script(formula) => {
+                    let renamed = rename_cell_reference(formula, from, to);
+                    (renamed != *formula).then(|| (entry.key().clone(), renamed))
+                }
+                _ => None,
+            })
+            .collect();
+        for (cell_ref, formula) in rewrites {
+            self.grid.insert(cell_ref, Cell::new_script(&formula));
+        }
+
+        self.spill_sources.clear();
+        self.error_cells.clear();
+        self.value_cache.clear();
+        self.invalidate_script_cache();
+        self.rebuild_dependents();
+        self.push_undo_for_grid_delta(before, old_layout);
+        self.modified = true;
+        self.apply_eval_policy();
+        Ok(())
+    }
+
+    /// Fold the newest two undo entries (a freeze of dependents and the edit
+    /// that caused it) into a single batch entry.
+    fn merge_last_two_undo_entries(&mut self) {
+        let split = self.undo_stack.len().saturating_sub(2);
+        let actions = self
+            .undo_stack
+            .split_off(split)
+            .into_iter()
+            .flat_map(|entry| match entry {
+                UndoEntry::Single(action) => vec![action],
+                UndoEntry::Batch(actions) | UndoEntry::Layout { actions, .. } => actions,
+            })
+            .collect();
+        self.push_undo_batch(actions);
+    }
+
+    /// Freeze a formula cell or spill output at `cell_ref` to its current value.
+    ///
+    /// Returns `1` if a cell was frozen, otherwise `0`.
+    pub fn freeze_cell(&mut self, cell_ref: &CellRef) -> usize {
+        let is_formula_source = self
+            .grid
+            .get(cell_ref)
+            .is_some_and(|cell| matches!(cell.contents, CellType::Script(_)));
+        let is_spill_output = self.spill_sources.contains_key(cell_ref);
+        if !is_formula_source && !is_spill_output {
+            return 0;
+        }
+        self.freeze_targets(vec![cell_ref.clone()])
+    }
+
+    /// Freeze all formula cells (and their spill outputs) to current values.
+    ///
+    /// Returns the number of cells that were frozen.
+    pub fn freeze_all(&mut self) -> usize {
+        let mut formula_sources: Vec<CellRef> = self
+            .grid
+            .iter()
+            .filter(|entry| matches!(entry.value().contents, CellType::Script(_)))
+            .map(|entry| entry.key().clone())
+            .collect();
 
         if formula_sources.is_empty() && self.spill_sources.is_empty() {
             return 0;
@@ -704,37 +1314,45 @@ impl Document {
         }
 
         let mut targets = formula_sources;
-        targets.extend(self.spill_sources.keys().cloned());
+        targets.extend(self.spill_sources.iter().map(|entry| entry.key().clone()));
         self.freeze_targets(targets)
     }
 
-    fn freeze_targets(&mut self, targets: Vec<CellRef>) -> usize {
-        if targets.is_empty() {
+    /// With [`Document::set_freeze_dependents_on_delete`] on, freeze the
+    /// formulas that reference a non-empty cell about to be removed (as
+    /// chosen by `removed`). Returns the number of cells frozen; they get
+    /// their own undo entry.
+    fn freeze_dependents_of(&mut self, removed: impl Fn(&CellRef) -> bool) -> usize {
+        if !self.freeze_dependents_on_delete {
             return 0;
         }
+        let targets = self.dependents_to_freeze(removed);
+        self.freeze_targets(targets)
+    }
 
-        let mut unique_targets = Vec::with_capacity(targets.len());
-        let mut seen = std::collections::HashSet::with_capacity(targets.len());
-        for cell_ref in targets {
-            if seen.insert(cell_ref.clone()) {
-                unique_targets.push(cell_ref);
-            }
-        }
-        unique_targets.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
-
-        let mut snapshots: Vec<(CellRef, Cell)> = Vec::with_capacity(unique_targets.len());
-        for cell_ref in unique_targets {
-            let display = self.get_cell_display(&cell_ref);
-            let frozen_cell = if let Some(value) = self.value_cache.get(&cell_ref) {
-                frozen_cell_from_dynamic(&value)
-            } else if display.is_empty() {
-                Cell::new_empty()
-            } else {
-                Cell::new_text(&display)
-            };
-            snapshots.push((cell_ref, frozen_cell));
-        }
+    /// Formula cells that directly reference a non-empty cell chosen by
+    /// `removed` and are not themselves removed.
+    fn dependents_to_freeze(&self, removed: impl Fn(&CellRef) -> bool) -> Vec<CellRef> {
+        let mut targets: Vec<CellRef> = self
+            .dependents
+            .iter()
+            .filter(|(precedent, _)| {
+                removed(precedent)
+                    && self
+                        .grid
+                        .get(*precedent)
+                        .is_some_and(|cell| !matches!(cell.contents, CellType::Empty))
+            })
+            .flat_map(|(_, dependents)| dependents.iter().cloned())
+            .filter(|dependent| !removed(dependent))
+            .collect();
+        targets.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+        targets.dedup();
+        targets
+    }
 
+    fn freeze_targets(&mut self, targets: Vec<CellRef>) -> usize {
+        let snapshots = self.frozen_cells(targets);
         if snapshots.is_empty() {
             return 0;
         }
@@ -767,16 +1385,66 @@ impl Document {
         for spill_source in additionally_dirty {
             self.mark_dependents_dirty(&spill_source);
         }
+        self.apply_eval_policy();
 
         affected_cells.len()
     }
+
+    /// The literal each of `targets` freezes to, evaluating formulas first.
+    fn frozen_cells(&mut self, targets: Vec<CellRef>) -> Vec<(CellRef, Cell)> {
+        let mut unique_targets = Vec::with_capacity(targets.len());
+        let mut seen = std::collections::HashSet::with_capacity(targets.len());
+        for cell_ref in targets {
+            if seen.insert(cell_ref.clone()) {
+                unique_targets.push(cell_ref);
+            }
+        }
+        unique_targets.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
+
+        let mut snapshots: Vec<(CellRef, Cell)> = Vec::with_capacity(unique_targets.len());
+        for cell_ref in unique_targets {
+            let display = self.get_cell_display(&cell_ref);
+            let frozen_cell = if let Some(value) = self.value_cache.get(&cell_ref) {
+                frozen_cell_from_dynamic(&value)
+            } else if display.is_empty() {
+                Cell::new_empty()
+            } else {
+                Cell::new_text(&display)
+            };
+            snapshots.push((cell_ref, frozen_cell));
+        }
+        snapshots
+    }
+}
+
+/// Offset clipboard cells to land at `base`, shifting formula references by the
+/// distance from where they were yanked (`source`).
+fn prepare_paste(
+    prepared: &mut Vec<(CellRef, Cell)>,
+    base: (usize, usize),
+    source: (usize, usize),
+    clipboard_cells: &[(usize, usize, Cell)],
+) {
+    let delta_col = base.0 as isize - source.0 as isize;
+    let delta_row = base.1 as isize - source.1 as isize;
+    for (rel_col, rel_row, cell) in clipboard_cells {
+        let target = CellRef::new(base.0 + rel_col, base.1 + rel_row);
+        let pasted_cell = match &cell.contents {
+            CellType::Script(formula) => {
+                let shifted = offset_formula_references(formula, delta_col, delta_row);
+                Cell::new_script(&shifted)
+            }
+            _ => cell.clone(),
+        };
+        prepared.push((target, pasted_cell));
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Document;
+    use super::{Document, normalize_formula_input};
     use crate::error::GridlineError;
-    use gridline_engine::engine::{Cell, CellRef, CellType};
+    use gridline_engine::engine::{Cell, CellRef, CellType, NumberLocale};
 
     #[test]
     fn test_delete_column_clears_spill_state() {
@@ -830,6 +1498,23 @@ mod tests {
         assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "second");
     }
 
+    #[test]
+    fn test_absolute_reference_survives_row_insert() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "5").unwrap(); // A1
+        core.set_cell_from_input(CellRef::new(0, 1), "7").unwrap(); // A2
+        core.set_cell_from_input(CellRef::new(1, 2), "=$A$1 * 10 + A2")
+            .unwrap(); // B3
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 2)), "57");
+
+        core.insert_row(0);
+        // The formula moved to B4; A2's value moved to A3 and is followed,
+        // while the pinned reference still reads A1, now blank
+        let cell = core.grid.get(&CellRef::new(1, 3)).unwrap().clone();
+        assert_eq!(cell.to_input_string(), "=$A$1 * 10 + A3");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 3)), "7");
+    }
+
     #[test]
     fn test_spill_conflict_clears_stale_spill() {
         let mut core = Document::new();
@@ -900,6 +1585,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_paste_transposed_past_undo_limit_is_one_step() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "kept")
+            .unwrap();
+        let width = super::super::state::MAX_UNDO_STACK + 50;
+        let cells: Vec<(usize, usize, Cell)> = (0..width)
+            .map(|col| (col, 0, Cell::new_number(col as f64)))
+            .collect();
+        let pasted = core.paste_cells_transposed(1, 0, 0, 5, &cells).unwrap();
+        assert_eq!(pasted, width);
+        assert_eq!(
+            core.get_cell_display(&CellRef::new(1, width - 1)),
+            (width - 1).to_string()
+        );
+
+        core.undo().unwrap();
+        assert_eq!(core.grid.len(), 1);
+        core.undo().unwrap();
+        assert!(core.grid.is_empty());
+    }
+
+    #[test]
+    fn test_paste_transposed_turns_row_into_column() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "10").unwrap(); // A1
+        core.set_cell_from_input(CellRef::new(1, 0), "=A1 * 2")
+            .unwrap(); // B1
+        core.set_cell_from_input(CellRef::new(2, 0), "=B1 + 1")
+            .unwrap(); // C1
+
+        let cells: Vec<_> = (0..3)
+            .map(|col| {
+                let cell = core.grid.get(&CellRef::new(col, 0)).unwrap().clone();
+                (col, 0, cell)
+            })
+            .collect();
+        let pasted = core.paste_cells_transposed(4, 0, 0, 0, &cells).unwrap(); // E1:E3
+        assert_eq!(pasted, 3);
+
+        let script = |core: &Document, cell: CellRef| match &core.grid.get(&cell).unwrap().contents
+        {
+            CellType::Script(s) => s.clone(),
+            _ => panic!("Expected script cell"),
+        };
+        assert_eq!(script(&core, CellRef::new(4, 1)), "E1 * 2");
+        assert_eq!(script(&core, CellRef::new(4, 2)), "E2 + 1");
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 0)), "10");
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 1)), "20");
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 2)), "21");
+
+        core.undo().unwrap();
+        assert!(core.grid.get(&CellRef::new(4, 0)).is_none());
+        assert!(core.grid.get(&CellRef::new(4, 2)).is_none());
+    }
+
     #[test]
     fn test_set_cell_over_spill_output_clears_spill_and_marks_source_dirty() {
         let mut core = Document::new();
@@ -1092,11 +1833,1094 @@ mod tests {
 
         let cycle_cell = Cell::new_script("A1");
         let result = core.paste_cells(0, 0, 0, 0, &[(0, 0, cycle_cell)]);
-        assert!(matches!(result, Err(GridlineError::CircularDependency)));
+        assert!(matches!(
+            result,
+            Err(GridlineError::CircularDependency { .. })
+        ));
 
         // Target was untouched.
         assert!(core.grid.get(&CellRef::new(0, 0)).is_none());
         // Existing unrelated cells remain unchanged.
         assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "7");
     }
+
+    #[test]
+    fn test_set_cell_from_input_uses_number_locale() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "1,234.56")
+            .unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "1,234.56");
+
+        doc.number_locale = NumberLocale::English;
+        doc.set_cell_from_input(CellRef::new(0, 1), "1,234.56")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 2), "=A2 * 2")
+            .unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 2)), "2469.12");
+
+        doc.number_locale = NumberLocale::European;
+        doc.set_cell_from_input(CellRef::new(1, 0), "1.234,5")
+            .unwrap();
+        assert!(matches!(
+            doc.grid.get(&CellRef::new(1, 0)).unwrap().contents,
+            CellType::Number(n) if n == 1234.5
+        ));
+    }
+
+    #[test]
+    fn test_compact_rows_removes_empty_middle_row_and_adjusts_refs() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "1").unwrap(); // A1
+        core.set_cell_from_input(CellRef::new(0, 2), "2").unwrap(); // A3
+        core.set_cell_from_input(CellRef::new(0, 4), "3").unwrap(); // A5
+        core.set_cell_from_input(CellRef::new(1, 4), "=SUM(A1:A5) + A3")
+            .unwrap(); // B5
+
+        let removed = core.compact_rows();
+        assert_eq!(removed, vec![1, 3]);
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "2");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 2)), "3");
+        assert_eq!(
+            core.grid
+                .get(&CellRef::new(1, 2))
+                .unwrap()
+                .to_input_string(),
+            "=SUM(A1:A3) + A2"
+        );
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 2)), "8");
+
+        // The whole compaction is a single undo step
+        core.undo().unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 2)), "2");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 4)), "8");
+        assert!(core.grid.get(&CellRef::new(0, 1)).is_none());
+    }
+
+    #[test]
+    fn test_compact_columns_without_gaps_is_noop() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(2, 0), "1").unwrap(); // C1
+        core.set_cell_from_input(CellRef::new(3, 0), "2").unwrap(); // D1
+        let undo_len = core.undo_stack.len();
+
+        assert!(core.compact_columns().is_empty());
+        assert_eq!(core.get_cell_display(&CellRef::new(2, 0)), "1");
+        assert_eq!(core.undo_stack.len(), undo_len);
+    }
+
+    #[test]
+    fn test_set_cell_from_input_enforces_formula_length_limit() {
+        let mut core = Document::new();
+        core.formula_limits.max_len = 10;
+
+        // "1+2+3+4+56" is exactly 10 characters
+        core.set_cell_from_input(CellRef::new(0, 0), "=1+2+3+4+56")
+            .unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "66");
+
+        let err = core
+            .set_cell_from_input(CellRef::new(0, 1), "=1+2+3+4+567")
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GridlineError::FormulaTooLong { len: 11, max: 10 }
+        ));
+        assert!(core.grid.get(&CellRef::new(0, 1)).is_none());
+    }
+
+    #[test]
+    fn test_set_cell_from_input_enforces_reference_limit() {
+        let mut core = Document::new();
+        core.formula_limits.max_refs = 3;
+
+        core.set_cell_from_input(CellRef::new(3, 0), "=SUM(A1:A2) + B1")
+            .unwrap();
+        let err = core
+            .set_cell_from_input(CellRef::new(3, 1), "=A1 + B1 + C1 + A2")
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GridlineError::TooManyReferences { count: 4, max: 3 }
+        ));
+        // Text that merely mentions cells is not a formula
+        core.set_cell_from_input(CellRef::new(3, 1), "A1 B1 C1 A2")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_paste_tsv_skips_blanks_without_clobbering_neighbours() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(2, 0), "\"keep\"")
+            .unwrap(); // C1
+        core.set_cell_from_input(CellRef::new(0, 1), "\"under\"")
+            .unwrap(); // A2
+
+        // Row 2 starts with a blank field and both rows have trailing tabs
+        let pasted = core.paste_tsv(0, 0, "1\t=A1*2\t\t\n\t5\t\n").unwrap();
+
+        assert_eq!(pasted, 3);
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "2");
+        assert_eq!(core.get_cell_display(&CellRef::new(2, 0)), "keep");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "under");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "5");
+    }
+
+    #[test]
+    fn test_fill_range_past_undo_limit_is_one_step() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "kept")
+            .unwrap();
+        let cells = super::super::state::MAX_UNDO_STACK + 50;
+        let filled = core
+            .fill_range_from_input(&CellRef::new(1, 0), &CellRef::new(1, cells - 1), "1")
+            .unwrap();
+        assert_eq!(filled, cells);
+
+        core.undo().unwrap();
+        assert!((0..cells).all(|row| core.grid.get(&CellRef::new(1, row)).is_none()));
+        // Earlier history survives the fill
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "kept");
+        core.undo().unwrap();
+        assert!(core.grid.get(&CellRef::new(0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_fill_range_from_input_offsets_like_paste() {
+        let mut core = Document::new();
+        for row in 0..3 {
+            core.set_cell_from_input(CellRef::new(0, row), &(row + 1).to_string())
+                .unwrap();
+        }
+
+        // Entering from the bottom-right corner still anchors at the top-left
+        let filled = core
+            .fill_range_from_input(&CellRef::new(1, 2), &CellRef::new(1, 0), "=A1*2")
+            .unwrap();
+
+        assert_eq!(filled, 3);
+        let inputs: Vec<String> = (0..3)
+            .map(|row| {
+                core.grid
+                    .get(&CellRef::new(1, row))
+                    .unwrap()
+                    .to_input_string()
+            })
+            .collect();
+        assert_eq!(inputs, ["=A1*2", "=A2*2", "=A3*2"]);
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 2)), "6");
+
+        // The whole fill undoes as one step
+        core.undo().unwrap();
+        assert!((0..3).all(|row| core.get_cell_display(&CellRef::new(1, row)).is_empty()));
+    }
+
+    #[test]
+    fn test_move_cell_rewrites_references() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "5").unwrap(); // A1
+        core.set_cell_from_input(CellRef::new(1, 0), "=A1").unwrap(); // B1
+        core.set_cell_from_input(CellRef::new(1, 1), "=SUM(A1:A2) + @A1")
+            .unwrap(); // B2
+
+        core.move_cell(&CellRef::new(0, 0), &CellRef::new(2, 2))
+            .unwrap(); // A1 -> C3
+
+        let input = |core: &Document, col, row| {
+            core.grid
+                .get(&CellRef::new(col, row))
+                .map(|c| c.to_input_string())
+                .unwrap_or_default()
+        };
+        assert_eq!(input(&core, 0, 0), "");
+        assert_eq!(input(&core, 2, 2), "5");
+        assert_eq!(input(&core, 1, 0), "=C3");
+        // Range endpoints stay put; only the single-cell reference follows
+        assert_eq!(input(&core, 1, 1), "=SUM(A1:A2) + @C3");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "5");
+
+        core.undo().unwrap();
+        assert_eq!(input(&core, 0, 0), "5");
+        assert_eq!(input(&core, 1, 0), "=A1");
+        assert_eq!(input(&core, 2, 2), "");
+    }
+
+    #[test]
+    fn test_move_cell_rejects_occupied_target() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "1").unwrap();
+        core.set_cell_from_input(CellRef::new(1, 0), "2").unwrap();
+
+        let result = core.move_cell(&CellRef::new(0, 0), &CellRef::new(1, 0));
+
+        assert!(matches!(result, Err(GridlineError::TargetOccupied(_))));
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "1");
+        assert!(matches!(
+            core.move_cell(&CellRef::new(5, 5), &CellRef::new(6, 6)),
+            Err(GridlineError::EmptyCell(_))
+        ));
+    }
+
+    #[test]
+    fn test_multiline_formula_is_normalized_on_commit() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "2").unwrap();
+
+        core.set_cell_from_input(CellRef::new(1, 0), "=A1 *\n    3\r\n\n  + 1")
+            .unwrap();
+
+        let cell = core.grid.get(&CellRef::new(1, 0)).unwrap().clone();
+        assert_eq!(cell.to_input_string(), "=A1 * 3 + 1");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "7");
+        // Plain text keeps its line breaks
+        assert_eq!(normalize_formula_input("a\nb"), "a\nb");
+    }
+
+    #[test]
+    fn test_circular_dependency_suggests_reference_to_remove() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(1, 0), "=A1 + 1")
+            .unwrap(); // B1 -> A1
+
+        let err = core
+            .set_cell_from_input(CellRef::new(0, 0), "=B1 * 2")
+            .unwrap_err();
+        match &err {
+            GridlineError::CircularDependency {
+                path,
+                cell,
+                reference,
+            } => {
+                assert_eq!(path, &["A1", "B1", "A1"]);
+                assert_eq!(cell, "A1");
+                assert_eq!(reference, "B1");
+            }
+            other => panic!("expected circular dependency, got {other}"),
+        }
+        assert_eq!(
+            err.to_string(),
+            "Circular dependency detected: A1 -> B1 -> A1 (remove reference to B1 from A1)"
+        );
+        // The rejected edit left A1 empty.
+        assert!(core.grid.get(&CellRef::new(0, 0)).is_none());
+    }
+
+    #[test]
+    fn test_paste_cells_tiled_fills_selection() {
+        let mut core = Document::new();
+        // Clipboard: 1 wide x 2 tall, yanked from A1:A2; the formula points at the cell above.
+        let cells = vec![
+            (0, 0, Cell::new_number(5.0)),
+            (0, 1, Cell::new_script("A1 + 1")),
+        ];
+
+        // Paste into C3:D6 (2 wide x 4 tall), selected bottom-right first.
+        let pasted = core
+            .paste_cells_tiled(((3, 5), (2, 2)), 0, 0, 1, 2, &cells)
+            .unwrap();
+        assert_eq!(pasted, 8);
+
+        for col in 2..=3 {
+            for top in [2, 4] {
+                let value = core.grid.get(&CellRef::new(col, top)).unwrap();
+                assert!(matches!(value.contents, CellType::Number(n) if n == 5.0));
+                let below = core.grid.get(&CellRef::new(col, top + 1)).unwrap();
+                let expected = format!("{} + 1", CellRef::new(col, top));
+                assert!(matches!(&below.contents, CellType::Script(f) if *f == expected));
+            }
+        }
+        assert_eq!(core.get_cell_display(&CellRef::new(3, 5)), "6");
+        assert!(core.grid.get(&CellRef::new(4, 2)).is_none());
+        assert!(core.grid.get(&CellRef::new(2, 6)).is_none());
+
+        // One undo removes the whole tiled paste.
+        core.undo().unwrap();
+        assert!(core.grid.is_empty());
+    }
+
+    #[test]
+    fn test_paste_cells_tiled_past_undo_limit_is_one_step() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "kept")
+            .unwrap();
+        let rows = super::super::state::MAX_UNDO_STACK + 50;
+        let cells = vec![(0, 0, Cell::new_number(1.0)), (1, 0, Cell::new_number(2.0))];
+        let pasted = core
+            .paste_cells_tiled(((1, 0), (2, rows - 1)), 1, 0, 2, 1, &cells)
+            .unwrap();
+        assert_eq!(pasted, rows * 2);
+
+        core.undo().unwrap();
+        assert_eq!(core.grid.len(), 1);
+        core.undo().unwrap();
+        assert!(core.grid.is_empty());
+    }
+
+    #[test]
+    fn test_paste_cells_tiled_small_selection_pastes_once() {
+        let mut core = Document::new();
+        let cells = vec![(0, 0, Cell::new_number(1.0)), (1, 0, Cell::new_number(2.0))];
+        let pasted = core
+            .paste_cells_tiled(((0, 0), (0, 0)), 0, 0, 2, 1, &cells)
+            .unwrap();
+        assert_eq!(pasted, 2);
+        assert!(core.grid.get(&CellRef::new(1, 0)).is_some());
+    }
+
+    #[test]
+    fn test_percent_input_is_a_fraction_for_formulas() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "50%").unwrap();
+        core.set_cell_from_input(CellRef::new(0, 1), "2.5%")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(1, 0), "=SUM(A1:A1)")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(1, 1), "=A2 * 1000")
+            .unwrap();
+
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "50%");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "0.50");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "25");
+        assert_eq!(
+            core.grid
+                .get(&CellRef::new(0, 1))
+                .unwrap()
+                .to_input_string(),
+            "2.5%"
+        );
+    }
+
+    #[test]
+    fn test_blanks_are_zero_toggle() {
+        let mut core = Document::new();
+        assert!(core.blanks_are_zero());
+        core.set_cell_from_input(CellRef::new(0, 0), "1").unwrap(); // A1
+        core.set_cell_from_input(CellRef::new(0, 2), "3").unwrap(); // A3, A2 is blank
+        core.set_cell_from_input(CellRef::new(1, 0), "=SUM(A1:A3)")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(1, 1), "=A2 + 1")
+            .unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "4");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "1");
+
+        core.set_blanks_are_zero(false);
+        let sum = core.get_cell_display(&CellRef::new(1, 0));
+        assert!(sum.starts_with("#ERR"), "{}", sum);
+        assert!(sum.contains("blank cell A2"), "{}", sum);
+        assert!(
+            core.get_cell_display(&CellRef::new(1, 1))
+                .starts_with("#ERR")
+        );
+
+        // Filling the gap makes the sum valid again.
+        core.set_cell_from_input(CellRef::new(0, 1), "2").unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "6");
+
+        core.set_cell_from_input(CellRef::new(0, 1), "").unwrap();
+        core.set_blanks_are_zero(true);
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "4");
+    }
+
+    #[test]
+    fn test_isspill_and_spillsource_report_spill_outputs() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "=SPILL(1..=3)")
+            .unwrap();
+        let _ = core.get_cell_display(&CellRef::new(0, 0));
+
+        for (row, formula) in [
+            "=ISSPILL(\"A2\")",
+            "=SPILLSOURCE(\"A3\")",
+            "=ISSPILL(\"A1\")",
+            "=SPILLSOURCE(\"A1\")",
+        ]
+        .iter()
+        .enumerate()
+        {
+            core.set_cell_from_input(CellRef::new(1, row), formula)
+                .unwrap();
+        }
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "TRUE");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "A1");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 2)), "FALSE");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 3)), "");
+    }
+
+    #[test]
+    fn test_filter_spills_matching_rows_in_two_dimensions() {
+        let mut core = Document::new();
+        for (row, (key, value)) in [("1", "\"a\""), ("5", "\"b\""), ("7", "\"c\"")]
+            .into_iter()
+            .enumerate()
+        {
+            core.set_cell_from_input(CellRef::new(0, row), key).unwrap();
+            core.set_cell_from_input(CellRef::new(1, row), value)
+                .unwrap();
+        }
+        core.set_cell_from_input(CellRef::new(3, 0), "=FILTER(A1:B3, |x| x > 2)")
+            .unwrap();
+
+        assert_eq!(core.get_cell_display(&CellRef::new(3, 0)), "5");
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 0)), "b");
+        assert_eq!(core.get_cell_display(&CellRef::new(3, 1)), "7");
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 1)), "c");
+        assert_eq!(
+            core.spill_sources
+                .get(&CellRef::new(4, 1))
+                .map(|s| s.clone()),
+            Some(CellRef::new(3, 0))
+        );
+
+        // A blocking value in the second column is a spill conflict too.
+        core.set_cell_from_input(CellRef::new(4, 1), "x").unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(3, 0)), "#SPILL!");
+    }
+
+    #[test]
+    fn test_array_literal_spills_as_matrix() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "={1, 2; 3, 4}")
+            .unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "1");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "2");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "3");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "4");
+    }
+
+    #[test]
+    fn test_nested_array_spills_rectangle_and_conflicts_anywhere_in_it() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "=[[1, 2], [3, 4]]")
+            .unwrap();
+        for (col, row, display) in [(0, 0, "1"), (1, 0, "2"), (0, 1, "3"), (1, 1, "4")] {
+            assert_eq!(core.get_cell_display(&CellRef::new(col, row)), display);
+        }
+
+        // A value in any cell of the rectangle blocks the whole spill
+        for (col, row) in [(1, 0), (0, 1), (1, 1)] {
+            let blocker = CellRef::new(col, row);
+            core.set_cell_from_input(blocker.clone(), "x").unwrap();
+            assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "#SPILL!");
+            assert!(core.spill_sources.is_empty());
+            core.clear_cell(&blocker);
+            assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "1");
+            assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "4");
+        }
+
+        // A smaller result releases the rest of the old footprint
+        core.set_cell_from_input(CellRef::new(0, 0), "=[[5], [6]]")
+            .unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "5");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "6");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "");
+        assert_eq!(core.spill_sources.len(), 1);
+    }
+
+    #[test]
+    fn test_header_rows_excluded_from_whole_column_ranges() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "100").unwrap();
+        core.set_cell_from_input(CellRef::new(0, 1), "2").unwrap();
+        core.set_cell_from_input(CellRef::new(0, 2), "3").unwrap();
+        core.set_cell_from_input(CellRef::new(1, 0), "=SUM(A:A)")
+            .unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "105");
+
+        core.set_header_rows(1);
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "5");
+        assert_eq!(core.column_profile(0).count, 2);
+
+        // Rows added past the old extent are picked up.
+        core.set_cell_from_input(CellRef::new(0, 5), "10").unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "15");
+
+        // A whole-column range over its own column is circular.
+        assert!(
+            core.set_cell_from_input(CellRef::new(0, 7), "=SUM(A:A)")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_errortype_classifies_error_cells() {
+        let mut core = Document::new();
+        // A1: #ERR, A2: #SPILL! (blocked by A3), A4: #REF!, A5: #INF!, A6: #NAN!,
+        // A8: #DIV/0!
+        core.set_cell_from_input(CellRef::new(0, 0), "=no_such_fn()")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(0, 2), "1").unwrap();
+        core.set_cell_from_input(CellRef::new(0, 1), "=SPILL(1..=3)")
+            .unwrap();
+        // (what deleting a referenced row leaves behind)
+        core.set_cell_from_input(CellRef::new(0, 3), "\"=B1 + #REF!\"")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(0, 4), "=1e308 * 10.0")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(0, 5), "=sqrt(-1.0)")
+            .unwrap();
+        // C1 and C2 reference each other (loaded cycles bypass input checks)
+        core.grid
+            .insert(CellRef::new(2, 0), Cell::new_script("C2 + 1"));
+        core.grid
+            .insert(CellRef::new(2, 1), Cell::new_script("C1 + 1"));
+        core.rebuild_dependents();
+        // A7 holds an ordinary value
+        core.set_cell_from_input(CellRef::new(0, 6), "42").unwrap();
+        core.set_cell_from_input(CellRef::new(0, 7), "=1 / 0")
+            .unwrap();
+
+        for row in 0..8 {
+            let _ = core.get_cell_display(&CellRef::new(0, row));
+        }
+        let _ = core.get_cell_display(&CellRef::new(2, 0));
+
+        for (cell, expected) in [
+            ("A1", "1"),
+            ("A2", "3"),
+            ("A4", "4"),
+            ("A5", "5"),
+            ("A6", "6"),
+            ("A8", "8"),
+        ] {
+            core.set_cell_from_input(CellRef::new(3, 0), &format!("=ERRORTYPE(\"{}\")", cell))
+                .unwrap();
+            assert_eq!(
+                core.get_cell_display(&CellRef::new(3, 0)),
+                expected,
+                "{}",
+                cell
+            );
+        }
+
+        // ERRORTYPE depends on its target, so it can't look into a cycle.
+        assert!(matches!(
+            core.set_cell_from_input(CellRef::new(3, 0), "=ERRORTYPE(\"C1\")"),
+            Err(GridlineError::CircularDependency { .. })
+        ));
+
+        core.set_cell_from_input(CellRef::new(3, 0), "=ERRORTYPE(\"A7\")")
+            .unwrap();
+        assert!(
+            core.get_cell_display(&CellRef::new(3, 0))
+                .starts_with("#ERR"),
+        );
+        // Fixing a cell clears its recorded error.
+        core.set_cell_from_input(CellRef::new(0, 4), "5").unwrap();
+        assert!(core.error_cells.get(&CellRef::new(0, 4)).is_none());
+    }
+
+    #[test]
+    fn test_errortype_evaluates_its_target_first() {
+        let mut core = Document::new();
+        // Entered before the target, and displayed without displaying it.
+        core.set_cell_from_input(CellRef::new(1, 0), "=ERRORTYPE(\"A1\")")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(0, 0), "=no_such_fn()")
+            .unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "1");
+
+        // Fixing the target marks the ERRORTYPE cell dirty.
+        core.set_cell_from_input(CellRef::new(0, 0), "=1 + 1")
+            .unwrap();
+        assert!(
+            core.get_cell_display(&CellRef::new(1, 0))
+                .starts_with("#ERR")
+        );
+    }
+
+    #[test]
+    fn test_set_column_formula_past_undo_limit_is_one_step() {
+        let mut core = Document::new();
+        let rows = super::super::state::MAX_UNDO_STACK + 50;
+        core.set_cell_from_input(CellRef::new(0, rows - 1), "1")
+            .unwrap();
+        assert_eq!(core.set_column_formula(1, "=A1 * 2").unwrap(), rows);
+
+        core.undo().unwrap();
+        assert_eq!(core.grid.len(), 1);
+        assert_eq!(core.get_cell_display(&CellRef::new(0, rows - 1)), "1");
+    }
+
+    #[test]
+    fn test_set_column_formula_fills_rows_and_follows_inserts() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "\"A\"")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(1, 0), "\"B\"")
+            .unwrap();
+        for row in 1..=5 {
+            core.set_cell_from_input(CellRef::new(0, row), &row.to_string())
+                .unwrap();
+            core.set_cell_from_input(CellRef::new(1, row), &(row * 10).to_string())
+                .unwrap();
+        }
+        core.set_header_rows(1);
+        let undo_depth = core.undo_stack.len();
+
+        assert_eq!(core.set_column_formula(2, "=A2 + B2").unwrap(), 5);
+        assert_eq!(core.undo_stack.len(), undo_depth + 1);
+        for row in 1..=5 {
+            let cell = core.grid.get(&CellRef::new(2, row)).unwrap().clone();
+            assert_eq!(
+                cell.contents,
+                CellType::Script(format!("A{} + B{}", row + 1, row + 1))
+            );
+            assert_eq!(
+                core.get_cell_display(&CellRef::new(2, row)),
+                (row * 11).to_string()
+            );
+        }
+        assert!(core.grid.get(&CellRef::new(2, 0)).is_none());
+
+        // A row inserted inside the data gets the formula for its position.
+        core.insert_row(3);
+        let cell = core.grid.get(&CellRef::new(2, 3)).unwrap().clone();
+        assert_eq!(cell.contents, CellType::Script("A4 + B4".to_string()));
+        assert_eq!(core.column_formula(2).as_deref(), Some("=A2 + B2"));
+
+        // Inserting above the data moves the template along.
+        core.insert_row(0);
+        assert!(core.grid.get(&CellRef::new(2, 0)).is_none());
+        assert_eq!(core.column_formula(2).as_deref(), Some("=A3 + B3"));
+
+        core.undo().unwrap();
+        core.undo().unwrap();
+        core.undo().unwrap();
+        assert!(core.grid.get(&CellRef::new(2, 1)).is_none());
+    }
+
+    #[test]
+    fn test_clear_range_is_one_undo_step() {
+        let mut core = Document::new();
+        for row in 0..3 {
+            for col in 0..3 {
+                core.set_cell_from_input(CellRef::new(col, row), &(row * 3 + col).to_string())
+                    .unwrap();
+            }
+        }
+        core.set_cell_from_input(CellRef::new(4, 0), "=SUM(A1:C3)")
+            .unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 0)), "36");
+        let undo_depth = core.undo_stack.len();
+
+        assert_eq!(
+            core.clear_range(&CellRef::new(2, 2), &CellRef::new(0, 0)),
+            9
+        );
+        assert_eq!(core.undo_stack.len(), undo_depth + 1);
+        assert!(core.grid.get(&CellRef::new(1, 1)).is_none());
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 0)), "0");
+
+        core.undo().unwrap();
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 1)), "4");
+        assert_eq!(core.get_cell_display(&CellRef::new(4, 0)), "36");
+        assert_eq!(
+            core.clear_range(&CellRef::new(5, 5), &CellRef::new(6, 6)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_row_relative_references_survive_row_reordering() {
+        let mut doc = Document::new();
+        let rows = [("\"b\"", "30"), ("\"a\"", "10"), ("\"c\"", "20")];
+        for (row, (name, value)) in rows.iter().enumerate() {
+            doc.set_cell_from_input(CellRef::new(0, row), name).unwrap();
+            doc.set_cell_from_input(CellRef::new(1, row), value)
+                .unwrap();
+            doc.set_cell_from_input(CellRef::new(2, row), "=@row.B * 2")
+                .unwrap();
+        }
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "60");
+        assert_eq!(
+            doc.precedents_of(&CellRef::new(2, 1)),
+            vec![CellRef::new(1, 1)]
+        );
+
+        // Reorder the rows by name, carrying each row's inputs verbatim.
+        let inputs: Vec<Vec<String>> = (0..3)
+            .map(|row| {
+                (0..3)
+                    .map(|col| {
+                        doc.grid
+                            .get(&CellRef::new(col, row))
+                            .map(|c| c.to_input_string())
+                            .unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
+        for (row, source) in [1, 0, 2].into_iter().enumerate() {
+            for (col, input) in inputs[source].iter().enumerate() {
+                doc.set_cell_from_input(CellRef::new(col, row), input)
+                    .unwrap();
+            }
+        }
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "a");
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "20");
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 1)), "60");
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 2)), "40");
+
+        // Edits to the row's value propagate through the same-row reference.
+        doc.set_cell_from_input(CellRef::new(1, 2), "7").unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 2)), "14");
+
+        // Deleting a row above moves the formula without rewriting it.
+        doc.delete_row(0);
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "60");
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 1)), "14");
+        doc.insert_column(0);
+        assert_eq!(
+            doc.grid.get(&CellRef::new(3, 0)).unwrap().to_input_string(),
+            "=@row.C * 2"
+        );
+        assert_eq!(doc.get_cell_display(&CellRef::new(3, 0)), "60");
+    }
+
+    #[test]
+    fn test_sum_and_avg_with_mixed_arguments() {
+        let mut doc = Document::new();
+        for (row, value) in ["1", "2", "3"].iter().enumerate() {
+            doc.set_cell_from_input(CellRef::new(0, row), value)
+                .unwrap();
+        }
+        doc.set_cell_from_input(CellRef::new(1, 0), "4").unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(2, 0), "=SUM(A1:A3, 10, B1)")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(2, 1), "=AVG(A1:A3, B1, SUM(A1, 1))")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(2, 2), "=SUM(10, A2:A3)")
+            .unwrap();
+
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "20");
+        // (1 + 2 + 3 + 4 + 2) / 5
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 1)), "2.40");
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 2)), "15");
+        assert_eq!(
+            doc.precedents_of(&CellRef::new(2, 0)),
+            vec![
+                CellRef::new(0, 0),
+                CellRef::new(1, 0),
+                CellRef::new(0, 1),
+                CellRef::new(0, 2)
+            ]
+        );
+
+        // Every part is tracked as a dependency.
+        doc.set_cell_from_input(CellRef::new(1, 0), "5").unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 2), "6").unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "24");
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 2)), "18");
+    }
+
+    #[test]
+    fn test_paste_conflicts_reports_occupied_targets() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(1, 1), "5").unwrap(); // B2
+        doc.set_cell_from_input(CellRef::new(2, 2), "\"x\"")
+            .unwrap(); // C3
+        doc.set_cell_from_input(CellRef::new(4, 0), "={1; 2; 3}")
+            .unwrap(); // E1 spilling to E2:E3
+        doc.set_cell_from_input(CellRef::new(0, 5), "9").unwrap(); // A6, outside
+        doc.get_cell_display(&CellRef::new(4, 0));
+
+        // B2:E2, then C3 and F3 (blank fields skip B3, D3, E3), then B4:D4
+        let grid = crate::storage::parse_tsv("a\tb\tc\td\n\tq\t\t\te\nr\ts\tt");
+        let conflicts = doc.paste_conflicts(&CellRef::new(1, 1), &grid);
+        assert_eq!(
+            conflicts,
+            vec![CellRef::new(1, 1), CellRef::new(4, 1), CellRef::new(2, 2)]
+        );
+
+        // Pasting onto the spilled range counts its outputs as occupied.
+        let grid = crate::storage::parse_tsv("1\n2\n3");
+        assert_eq!(
+            doc.paste_conflicts(&CellRef::new(4, 0), &grid),
+            vec![CellRef::new(4, 0), CellRef::new(4, 1), CellRef::new(4, 2)]
+        );
+        assert!(doc.paste_conflicts(&CellRef::new(9, 9), &grid).is_empty());
+    }
+
+    #[test]
+    fn test_convert_text_to_numbers_skips_genuine_text() {
+        let mut doc = Document::new();
+        for (row, text) in ["1", "2", "x"].iter().enumerate() {
+            doc.grid.insert(CellRef::new(1, row), Cell::new_text(text));
+        }
+        doc.set_cell_from_input(CellRef::new(2, 0), "=SUM(B1:B3)")
+            .unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "0");
+
+        let converted = doc.convert_text_to_numbers(&CellRef::new(1, 0), &CellRef::new(1, 99));
+        assert_eq!(converted, 2);
+        let contents = |doc: &Document, row| {
+            doc.grid
+                .get(&CellRef::new(1, row))
+                .unwrap()
+                .contents
+                .clone()
+        };
+        assert!(matches!(contents(&doc, 0), CellType::Number(n) if n == 1.0));
+        assert!(matches!(contents(&doc, 1), CellType::Number(n) if n == 2.0));
+        assert!(matches!(contents(&doc, 2), CellType::Text(ref s) if s == "x"));
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "3");
+
+        // One undo restores all the text.
+        doc.undo().unwrap();
+        assert!(matches!(contents(&doc, 0), CellType::Text(ref s) if s == "1"));
+        assert!(matches!(contents(&doc, 1), CellType::Text(ref s) if s == "2"));
+        assert_eq!(
+            doc.convert_text_to_numbers(&CellRef::new(0, 0), &CellRef::new(0, 9)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_recalc_range_only_refreshes_selected_formulas() {
+        let mut core = Document::new();
+        core.custom_functions = Some("fn rate() { 1 }".to_string());
+        core.set_cell_from_input(CellRef::new(0, 0), "=rate()")
+            .unwrap(); // A1
+        core.set_cell_from_input(CellRef::new(1, 0), "=rate() * 10")
+            .unwrap(); // B1
+        core.set_cell_from_input(CellRef::new(0, 1), "=A1 + 100")
+            .unwrap(); // A2
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "1");
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "10");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "101");
+
+        // Change the function behind the document's back: caches stay stale.
+        core.custom_functions = Some("fn rate() { 2 }".to_string());
+        assert_eq!(
+            core.recalc_range(&CellRef::new(0, 0), &CellRef::new(0, 0)),
+            1
+        );
+
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "2");
+        // Dependents of the selection follow it...
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "102");
+        // ...but unselected formulas keep their cached value until touched.
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "10");
+        core.recalc_range(&CellRef::new(1, 0), &CellRef::new(1, 0));
+        assert_eq!(core.get_cell_display(&CellRef::new(1, 0)), "20");
+    }
+
+    #[test]
+    fn test_eager_eval_policy_updates_dependents_without_display() {
+        let cached =
+            |core: &Document, cell: CellRef| core.grid.get(&cell).unwrap().cached_value.clone();
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "1").unwrap(); // A1
+        core.set_cell_from_input(CellRef::new(1, 0), "=A1 * 2")
+            .unwrap(); // B1
+        assert_eq!(cached(&core, CellRef::new(1, 0)), None);
+
+        core.set_eval_policy(crate::document::EvalPolicy::Eager);
+        assert_eq!(cached(&core, CellRef::new(1, 0)).as_deref(), Some("2"));
+        core.set_cell_from_input(CellRef::new(0, 0), "5").unwrap();
+        assert_eq!(cached(&core, CellRef::new(1, 0)).as_deref(), Some("10"));
+        core.undo().unwrap();
+        assert_eq!(cached(&core, CellRef::new(1, 0)).as_deref(), Some("2"));
+
+        core.set_eval_policy(crate::document::EvalPolicy::Lazy);
+        core.set_cell_from_input(CellRef::new(0, 0), "7").unwrap();
+        assert_eq!(cached(&core, CellRef::new(1, 0)), None);
+    }
+
+    #[test]
+    fn test_pending_recompute_count_tracks_dirty_dependents() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "1").unwrap(); // A1
+        for row in 0..3 {
+            core.set_cell_from_input(CellRef::new(1, row), &format!("=A1 * {}", row + 1))
+                .unwrap(); // B1:B3
+        }
+        for row in 0..3 {
+            core.get_cell_display(&CellRef::new(1, row));
+        }
+        assert_eq!(core.pending_recompute_count(), 0);
+
+        core.set_cell_from_input(CellRef::new(0, 0), "2").unwrap();
+        assert_eq!(core.pending_recompute_count(), 3);
+        for row in 0..3 {
+            core.get_cell_display(&CellRef::new(1, row));
+        }
+        assert_eq!(core.pending_recompute_count(), 0);
+    }
+
+    #[test]
+    fn test_evaluate_formula_at_uses_context_without_storing() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 4), "10").unwrap(); // A5
+        let context = CellRef::new(1, 4); // B5
+
+        assert_eq!(core.evaluate_formula_at("=ROW()", &context).unwrap(), "5");
+        assert_eq!(core.evaluate_formula_at("COL()", &context).unwrap(), "2");
+        assert_eq!(
+            core.evaluate_formula_at("=@row.A * 2", &context).unwrap(),
+            "20"
+        );
+        assert!(core.evaluate_formula_at("=nope(", &context).is_err());
+
+        assert!(core.grid.get(&context).is_none());
+        assert_eq!(core.grid.len(), 1);
+        assert!(!core.value_cache.contains_key(&context));
+    }
+
+    #[test]
+    fn test_freeze_dependents_on_delete_keeps_last_value() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "21").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 0), "=A1*2")
+            .unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(2, 0), "=B1+1")
+            .unwrap(); // C1
+        assert_eq!(doc.get_cell_display(&CellRef::new(1, 0)), "42");
+
+        doc.set_freeze_dependents_on_delete(true);
+        doc.clear_cell(&CellRef::new(0, 0));
+        let b1 = doc.grid.get(&CellRef::new(1, 0)).unwrap().contents.clone();
+        assert!(matches!(b1, CellType::Number(n) if n == 42.0));
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "43");
+
+        // Clearing and freezing undo together
+        doc.undo().unwrap();
+        let b1 = doc.grid.get(&CellRef::new(1, 0)).unwrap().contents.clone();
+        assert!(matches!(b1, CellType::Script(ref f) if f == "A1*2"));
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "21");
+
+        // Deleting the precedent's row freezes too, instead of #REF!
+        doc.set_cell_from_input(CellRef::new(0, 1), "=A1+1")
+            .unwrap(); // A2
+        doc.delete_row(0);
+        let a1 = doc.grid.get(&CellRef::new(0, 0)).unwrap().contents.clone();
+        assert!(matches!(a1, CellType::Number(n) if n == 22.0));
+        doc.undo().unwrap();
+        let a2 = doc.grid.get(&CellRef::new(0, 1)).unwrap().contents.clone();
+        assert!(matches!(a2, CellType::Script(ref f) if f == "A1+1"));
+    }
+
+    #[test]
+    fn test_division_by_zero_shows_div0() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "=1/0")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(0, 1), "=1/0.0")
+            .unwrap();
+        // B1 is blank, so it divides by zero too
+        core.set_cell_from_input(CellRef::new(0, 2), "=10 / B1")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(0, 3), "=7.0 / 2")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(0, 4), "=1e308 * 10.0")
+            .unwrap();
+
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 0)), "#DIV/0!");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 1)), "#DIV/0!");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 2)), "#DIV/0!");
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 3)), "3.50");
+        // Overflow is still an infinity, not a division by zero
+        assert_eq!(core.get_cell_display(&CellRef::new(0, 4)), "#INF!");
+    }
+
+    #[test]
+    fn test_fill_range_with_generator_is_row_major() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "old").unwrap();
+        let filled = doc
+            .fill_range_with_generator(&CellRef::new(1, 1), &CellRef::new(0, 0), "|i| i")
+            .unwrap();
+        assert_eq!(filled, 4);
+        for (col, row, expected) in [(0, 0, "0"), (1, 0, "1"), (0, 1, "2"), (1, 1, "3")] {
+            assert_eq!(doc.get_cell_display(&CellRef::new(col, row)), expected);
+        }
+        let a1 = doc.grid.get(&CellRef::new(0, 0)).unwrap().contents.clone();
+        assert!(matches!(a1, CellType::Number(n) if n == 0.0));
+
+        // The fill undoes in one step
+        doc.undo().unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "old");
+        assert_eq!(doc.get_cell_display(&CellRef::new(1, 1)), "");
+
+        // Even past the undo stack limit, and without losing earlier history
+        let rows = super::super::state::MAX_UNDO_STACK + 50;
+        doc.fill_range_with_generator(&CellRef::new(2, 0), &CellRef::new(3, rows - 1), "|i| i")
+            .unwrap();
+        doc.undo().unwrap();
+        assert_eq!(doc.grid.len(), 1);
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "old");
+
+        // A failing index aborts the fill without writing anything
+        let err = doc
+            .fill_range_with_generator(
+                &CellRef::new(0, 0),
+                &CellRef::new(0, 3),
+                r#"|i| if i == 2 { throw "boom" } else { i }"#,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            GridlineError::GeneratorFailed { index: 2, .. }
+        ));
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "old");
+        assert!(matches!(
+            doc.fill_range_with_generator(&CellRef::new(0, 0), &CellRef::new(0, 0), "42"),
+            Err(GridlineError::NotAClosure(_))
+        ));
+    }
+
+    #[test]
+    fn test_unbounded_recursion_hits_call_depth_limit() {
+        let mut core = Document::new();
+        core.custom_functions = Some("fn down(n) { down(n + 1) }".to_string());
+        core.set_max_call_depth(16);
+        assert_eq!(core.max_call_depth(), 16);
+        core.set_cell_from_input(CellRef::new(0, 0), "=down(1)")
+            .unwrap(); // A1
+        assert_eq!(
+            core.get_cell_display(&CellRef::new(0, 0)),
+            "#ERR: recursion limit (16 calls) exceeded"
+        );
+    }
+
+    #[test]
+    fn test_iferror_shows_fallback_for_failed_formula() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "5").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(2, 0), "=IFERROR(A1/B1, 0)")
+            .unwrap(); // C1
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "0");
+        doc.set_cell_from_input(CellRef::new(1, 0), "2").unwrap(); // B1
+        assert_eq!(doc.get_cell_display(&CellRef::new(2, 0)), "2.50");
+    }
+
+    #[test]
+    fn test_cycle_path_at_follows_indirect_cycle() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "=B1 + 1")
+            .unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 0), "=C1 + 1")
+            .unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(3, 0), "=A1 * 2")
+            .unwrap(); // D1
+        assert!(doc.cycle_path_at(&CellRef::new(0, 0)).is_none());
+        // Edits can't close a cycle, but a loaded sheet can contain one
+        doc.grid
+            .insert(CellRef::new(2, 0), Cell::new_script("A1 + 1")); // C1
+
+        let path = doc.cycle_path_at(&CellRef::new(0, 0)).unwrap();
+        let names: Vec<String> = path.iter().map(ToString::to_string).collect();
+        assert_eq!(names, ["A1", "B1", "C1", "A1"]);
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "#CYCLE!");
+        assert_eq!(
+            doc.cycle_status_at(&CellRef::new(1, 0)).unwrap(),
+            "#CYCLE! B1 → C1 → A1 → B1"
+        );
+        // A formula depending on the cycle shows how it leads there
+        assert_eq!(
+            doc.cycle_status_at(&CellRef::new(3, 0)).unwrap(),
+            "#CYCLE! D1 → A1 → B1 → C1 → A1"
+        );
+        assert!(doc.cycle_status_at(&CellRef::new(4, 0)).is_none());
+    }
 }
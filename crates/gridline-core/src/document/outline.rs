@@ -0,0 +1,223 @@
+//! Row groups (outline): spans of rows that can be collapsed like Vim folds.
+//! A collapsed group keeps its first row visible and hides the rest, so the
+//! group can be reopened from that row. Groups may nest but not partly overlap.
+
+use super::Document;
+use super::merge::shift_span;
+use crate::error::{GridlineError, Result};
+use gridline_engine::engine::ShiftOperation;
+
+/// Rows `start..=end` as shown to users (1-based), e.g. `3-5`.
+fn group_name(group: (usize, usize)) -> String {
+    format!("{}-{}", group.0 + 1, group.1 + 1)
+}
+
+/// Rows hidden while the collapsed `groups` are closed, as sorted,
+/// non-overlapping inclusive ranges.
+fn hidden_row_ranges<'a>(
+    groups: impl IntoIterator<Item = &'a (usize, usize)>,
+) -> Vec<(usize, usize)> {
+    let mut hidden: Vec<(usize, usize)> = groups
+        .into_iter()
+        .map(|&(start, end)| (start + 1, end))
+        .collect();
+    hidden.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(hidden.len());
+    for (start, end) in hidden {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+impl Document {
+    /// Group the rows between `a` and `b` (inclusive). Grouping a single row
+    /// does nothing; a group partly overlapping an existing one (or equal to
+    /// it) is an error, while nesting inside or around one is fine.
+    pub fn group_rows(&mut self, a: usize, b: usize) -> Result<()> {
+        let group = (a.min(b), a.max(b));
+        if group.0 == group.1 {
+            return Ok(());
+        }
+        let nested = |other: &(usize, usize)| {
+            group != *other
+                && ((group.0 >= other.0 && group.1 <= other.1)
+                    || (other.0 >= group.0 && other.1 <= group.1))
+        };
+        let disjoint = |other: &(usize, usize)| group.1 < other.0 || other.1 < group.0;
+        if let Some(existing) = self
+            .row_groups
+            .iter()
+            .find(|other| !disjoint(other) && !nested(other))
+        {
+            return Err(GridlineError::RowGroupOverlap(group_name(*existing)));
+        }
+        let old_layout = self.layout();
+        self.row_groups.push(group);
+        self.row_groups.sort_unstable();
+        self.push_undo_with_layout(Vec::new(), old_layout);
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Remove the innermost group containing `row`, returning it.
+    pub fn ungroup_rows(&mut self, row: usize) -> Option<(usize, usize)> {
+        let group = self.row_group_at(row)?;
+        let old_layout = self.layout();
+        self.row_groups.retain(|other| *other != group);
+        self.collapsed_row_groups.remove(&group);
+        self.push_undo_with_layout(Vec::new(), old_layout);
+        self.modified = true;
+        Some(group)
+    }
+
+    /// The innermost group (start, end) containing `row`, if any.
+    pub fn row_group_at(&self, row: usize) -> Option<(usize, usize)> {
+        self.row_groups
+            .iter()
+            .filter(|(start, end)| (*start..=*end).contains(&row))
+            .min_by_key(|(start, end)| end - start)
+            .copied()
+    }
+
+    /// Collapse or expand the innermost group containing `row`. Returns
+    /// whether it is now collapsed, or `None` if `row` isn't grouped.
+    pub fn toggle_row_group(&mut self, row: usize) -> Option<bool> {
+        let group = self.row_group_at(row)?;
+        let collapsed = if self.collapsed_row_groups.remove(&group) {
+            false
+        } else {
+            self.collapsed_row_groups.insert(group);
+            true
+        };
+        self.modified = true;
+        Some(collapsed)
+    }
+
+    /// Rows hidden by collapsed groups, as sorted inclusive ranges.
+    pub fn collapsed_row_ranges(&self) -> Vec<(usize, usize)> {
+        hidden_row_ranges(&self.collapsed_row_groups)
+    }
+
+    /// Whether `row` is hidden inside a collapsed group.
+    pub fn is_row_collapsed(&self, row: usize) -> bool {
+        self.collapsed_row_groups
+            .iter()
+            .any(|&(start, end)| row > start && row <= end)
+    }
+
+    /// The row shown in place of `row`: `row` itself when visible, otherwise
+    /// the first row of the collapsed group hiding it.
+    pub fn visible_row(&self, row: usize) -> usize {
+        let mut row = row;
+        while let Some(&(start, _)) = self
+            .collapsed_row_groups
+            .iter()
+            .filter(|&&(start, end)| row > start && row <= end)
+            .min_by_key(|(start, _)| *start)
+        {
+            row = start;
+        }
+        row
+    }
+
+    /// Keep row groups in step with a row insert or delete. A group grows or
+    /// shrinks when the change falls inside it, and is dropped once it spans
+    /// a single row.
+    pub(crate) fn shift_row_groups(&mut self, op: ShiftOperation) {
+        let (at, insert) = match op {
+            ShiftOperation::InsertRow(at) => (at, true),
+            ShiftOperation::DeleteRow(at) => (at, false),
+            ShiftOperation::InsertColumn(_) | ShiftOperation::DeleteColumn(_) => return,
+        };
+        let shift = |(start, end): (usize, usize)| {
+            shift_span(start, end, at, insert).filter(|(start, end)| start < end)
+        };
+        let groups = std::mem::take(&mut self.row_groups);
+        self.row_groups = groups.into_iter().filter_map(shift).collect();
+        // Deleting rows can shrink a nested group onto its parent
+        self.row_groups.dedup();
+        let collapsed = std::mem::take(&mut self.collapsed_row_groups);
+        self.collapsed_row_groups = collapsed.into_iter().filter_map(shift).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapsed_groups_hide_all_but_first_row() {
+        let mut doc = Document::new();
+        doc.group_rows(1, 4).unwrap(); // rows 2-5
+        doc.group_rows(2, 3).unwrap(); // rows 3-4, nested
+        doc.group_rows(7, 9).unwrap(); // rows 8-10
+        assert!(doc.group_rows(3, 6).is_err());
+
+        assert_eq!(doc.toggle_row_group(2), Some(true));
+        assert_eq!(doc.collapsed_row_ranges(), vec![(3, 3)]);
+        assert_eq!(doc.toggle_row_group(1), Some(true));
+        assert_eq!(doc.toggle_row_group(8), Some(true));
+        assert_eq!(doc.collapsed_row_ranges(), vec![(2, 4), (8, 9)]);
+        assert!(!doc.is_row_collapsed(1));
+        assert!(doc.is_row_collapsed(3));
+        assert_eq!(doc.visible_row(3), 1);
+        assert_eq!(doc.visible_row(5), 5);
+        assert_eq!(doc.toggle_row_group(5), None);
+
+        // Expanding the outer group still leaves the inner one closed
+        assert_eq!(doc.toggle_row_group(1), Some(false));
+        assert_eq!(doc.collapsed_row_ranges(), vec![(3, 3), (8, 9)]);
+        assert_eq!(doc.ungroup_rows(3), Some((2, 3)));
+        assert_eq!(doc.collapsed_row_ranges(), vec![(8, 9)]);
+    }
+
+    #[test]
+    fn test_row_groups_follow_inserts_and_deletes() {
+        let mut doc = Document::new();
+        doc.group_rows(2, 4).unwrap();
+        doc.toggle_row_group(2);
+
+        doc.insert_row(0);
+        assert_eq!(doc.row_groups, vec![(3, 5)]);
+        doc.insert_row(4);
+        assert_eq!(doc.row_groups, vec![(3, 6)]);
+        doc.delete_row(3);
+        assert_eq!(doc.row_groups, vec![(3, 5)]);
+        assert_eq!(doc.collapsed_row_ranges(), vec![(4, 5)]);
+        doc.delete_row(7);
+        assert_eq!(doc.row_groups, vec![(3, 5)]);
+
+        // A group shrunk to one row is dropped
+        doc.delete_row(4);
+        doc.delete_row(4);
+        assert!(doc.row_groups.is_empty());
+        assert!(doc.collapsed_row_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_row_groups_round_trip_through_undo() {
+        let mut doc = Document::new();
+        doc.group_rows(3, 5).unwrap();
+        doc.toggle_row_group(3);
+
+        // An insert's undo puts the shifted group back
+        doc.insert_row(0);
+        assert_eq!(doc.row_groups, vec![(4, 6)]);
+        doc.undo().unwrap();
+        assert_eq!(doc.row_groups, vec![(3, 5)]);
+        assert!(doc.collapsed_row_groups.contains(&(3, 5)));
+
+        assert_eq!(doc.ungroup_rows(4), Some((3, 5)));
+        doc.undo().unwrap();
+        assert_eq!(doc.row_groups, vec![(3, 5)]);
+        assert!(doc.collapsed_row_groups.contains(&(3, 5)));
+
+        doc.undo().unwrap();
+        assert!(doc.row_groups.is_empty());
+        doc.redo().unwrap();
+        assert_eq!(doc.row_groups, vec![(3, 5)]);
+    }
+}
@@ -0,0 +1,265 @@
+//! Column profiling: quick summary statistics for a column's used range.
+//!
+//! Numbers (including formula results) are summarized with min/max/mean, while
+//! text values are counted separately so mixed columns still profile cleanly.
+
+use super::Document;
+use gridline_engine::engine::{CellRef, CellType, format_number};
+use std::collections::HashMap;
+
+/// How many of the most frequent values a profile lists.
+const TOP_VALUES: usize = 5;
+
+/// Summary of the non-blank values in one column.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColumnProfile {
+    /// Column index (0-based)
+    pub col: usize,
+    /// Number of non-blank cells
+    pub count: usize,
+    /// Number of distinct values (numbers and text counted together)
+    pub distinct: usize,
+    /// Number of numeric values
+    pub numeric_count: usize,
+    /// Smallest numeric value, if any
+    pub min: Option<f64>,
+    /// Largest numeric value, if any
+    pub max: Option<f64>,
+    /// Mean of the numeric values, if any
+    pub mean: Option<f64>,
+    /// Most frequent numeric values (as displayed) with their counts
+    pub top_numbers: Vec<(String, usize)>,
+    /// Most frequent text values with their counts
+    pub top_text: Vec<(String, usize)>,
+}
+
+/// Count, sum and average of a selected range, for a status bar readout.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SelectionStats {
+    /// Number of non-blank cells (numbers and text)
+    pub count: usize,
+    /// Number of numeric values
+    pub numeric_count: usize,
+    /// Sum of the numeric values
+    pub sum: f64,
+    /// Mean of the numeric values, if any
+    pub average: Option<f64>,
+    /// Smallest numeric value, if any
+    pub min: Option<f64>,
+    /// Largest numeric value, if any
+    pub max: Option<f64>,
+}
+
+enum ProfileValue {
+    Number(f64),
+    Text(String),
+}
+
+impl Document {
+    /// Profile column `col` over the rows it uses (including spilled values),
+    /// skipping any header rows.
+    pub fn column_profile(&mut self, col: usize) -> ColumnProfile {
+        let mut profile = ColumnProfile {
+            col,
+            ..ColumnProfile::default()
+        };
+        let max_row = self
+            .grid
+            .iter()
+            .map(|entry| entry.key().clone())
+            .chain(self.spill_sources.iter().map(|entry| entry.key().clone()))
+            .filter(|cell| cell.col == col)
+            .map(|cell| cell.row)
+            .max();
+        let Some(max_row) = max_row else {
+            return profile;
+        };
+
+        let mut sum = 0.0;
+        let mut numbers: HashMap<String, usize> = HashMap::new();
+        let mut text: HashMap<String, usize> = HashMap::new();
+        for row in self.header_rows..=max_row {
+            let Some(value) = self.profile_value(&CellRef::new(col, row)) else {
+                continue;
+            };
+            profile.count += 1;
+            match value {
+                ProfileValue::Number(n) => {
+                    profile.numeric_count += 1;
+                    sum += n;
+                    profile.min = Some(profile.min.map_or(n, |m| m.min(n)));
+                    profile.max = Some(profile.max.map_or(n, |m| m.max(n)));
+                    *numbers.entry(format_number(n)).or_default() += 1;
+                }
+                ProfileValue::Text(s) => *text.entry(s).or_default() += 1,
+            }
+        }
+
+        if profile.numeric_count > 0 {
+            profile.mean = Some(sum / profile.numeric_count as f64);
+        }
+        profile.distinct =
+            numbers.len() + text.keys().filter(|s| !numbers.contains_key(*s)).count();
+        profile.top_numbers = top_frequencies(numbers);
+        profile.top_text = top_frequencies(text);
+        profile
+    }
+
+    /// Summarize the non-blank cells in the rectangle spanned by `top_left`
+    /// and `bottom_right` (including spilled values). Text counts towards
+    /// `count` but is left out of the sum and average.
+    pub fn selection_stats(
+        &mut self,
+        top_left: &CellRef,
+        bottom_right: &CellRef,
+    ) -> SelectionStats {
+        let (min_col, max_col) = (
+            top_left.col.min(bottom_right.col),
+            top_left.col.max(bottom_right.col),
+        );
+        let (min_row, max_row) = (
+            top_left.row.min(bottom_right.row),
+            top_left.row.max(bottom_right.row),
+        );
+        let mut cells: Vec<CellRef> = self
+            .grid
+            .iter()
+            .map(|entry| entry.key().clone())
+            .chain(self.spill_sources.iter().map(|entry| entry.key().clone()))
+            .filter(|cell| {
+                (min_col..=max_col).contains(&cell.col) && (min_row..=max_row).contains(&cell.row)
+            })
+            .collect();
+        cells.sort_by_key(|cell| (cell.row, cell.col));
+        cells.dedup();
+
+        let mut stats = SelectionStats::default();
+        for cell in cells {
+            let Some(value) = self.profile_value(&cell) else {
+                continue;
+            };
+            stats.count += 1;
+            if let ProfileValue::Number(n) = value {
+                stats.numeric_count += 1;
+                stats.sum += n;
+                stats.min = Some(stats.min.map_or(n, |m| m.min(n)));
+                stats.max = Some(stats.max.map_or(n, |m| m.max(n)));
+            }
+        }
+        if stats.numeric_count > 0 {
+            stats.average = Some(stats.sum / stats.numeric_count as f64);
+        }
+        stats
+    }
+
+    /// Evaluated value of a cell for profiling, or `None` if it is blank.
+    fn profile_value(&mut self, cell_ref: &CellRef) -> Option<ProfileValue> {
+        let is_formula_or_spill = self.spill_sources.contains_key(cell_ref)
+            || matches!(
+                self.grid.get(cell_ref).map(|c| c.contents.clone()),
+                Some(CellType::Script(_))
+            );
+        if !is_formula_or_spill {
+            return match self.grid.get(cell_ref)?.contents.clone() {
+                CellType::Number(n) => Some(ProfileValue::Number(n)),
+                CellType::Text(s) => Some(ProfileValue::Text(s)),
+                CellType::Empty | CellType::Script(_) => None,
+            };
+        }
+
+        let display = self.get_cell_display(cell_ref);
+        if let Some(value) = self.value_cache.get(cell_ref) {
+            if let Ok(n) = value.as_float() {
+                return Some(ProfileValue::Number(n));
+            }
+            if let Ok(n) = value.as_int() {
+                return Some(ProfileValue::Number(n as f64));
+            }
+            if value.is_unit() {
+                return None;
+            }
+        }
+        if display.is_empty() {
+            None
+        } else {
+            Some(ProfileValue::Text(display))
+        }
+    }
+}
+
+/// The most frequent entries, highest count first (ties broken alphabetically).
+fn top_frequencies(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut entries: Vec<(String, usize)> = counts.into_iter().collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries.truncate(TOP_VALUES);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_profile_mixed_column() {
+        let mut doc = Document::new();
+        for (row, input) in [
+            "\"Name\"", "3", "1", "3", "", "\"n/a\"", "=B1 * 2", "\"n/a\"",
+        ]
+        .iter()
+        .enumerate()
+        {
+            doc.set_cell_from_input(CellRef::new(0, row), input)
+                .unwrap();
+        }
+        doc.set_cell_from_input(CellRef::new(1, 0), "4").unwrap();
+
+        let profile = doc.column_profile(0);
+        assert_eq!(profile.col, 0);
+        assert_eq!(profile.count, 7);
+        assert_eq!(profile.numeric_count, 4);
+        // Distinct: Name, n/a, 3, 1, 8
+        assert_eq!(profile.distinct, 5);
+        assert_eq!(profile.min, Some(1.0));
+        assert_eq!(profile.max, Some(8.0));
+        assert_eq!(profile.mean, Some(15.0 / 4.0));
+        assert_eq!(profile.top_numbers[0], ("3".to_string(), 2));
+        assert_eq!(
+            profile.top_text,
+            vec![("n/a".to_string(), 2), ("Name".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_column_profile_empty_column() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "1").unwrap();
+        let profile = doc.column_profile(3);
+        assert_eq!(profile.count, 0);
+        assert_eq!(profile.mean, None);
+        assert!(profile.top_numbers.is_empty());
+    }
+
+    #[test]
+    fn test_selection_stats_mixed_selection() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "2").unwrap();
+        doc.set_cell_from_input(CellRef::new(1, 0), "\"label\"")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 1), "=A1 * 3")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(1, 2), "-1").unwrap();
+        // Outside the selection
+        doc.set_cell_from_input(CellRef::new(2, 0), "100").unwrap();
+
+        let stats = doc.selection_stats(&CellRef::new(1, 2), &CellRef::new(0, 0));
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.numeric_count, 3);
+        assert_eq!(stats.sum, 7.0);
+        assert_eq!(stats.average, Some(7.0 / 3.0));
+        assert_eq!(stats.min, Some(-1.0));
+        assert_eq!(stats.max, Some(6.0));
+
+        let empty = doc.selection_stats(&CellRef::new(5, 5), &CellRef::new(6, 6));
+        assert_eq!(empty, SelectionStats::default());
+    }
+}
@@ -7,7 +7,8 @@ use super::{Document, UndoAction};
 use crate::error::{GridlineError, Result};
 use gridline_engine::builtins::ScriptModifications;
 use gridline_engine::engine::{
-    CellType, create_script_engine_with_functions, detect_cycle, eval_with_functions_script,
+    CellType, create_engine_with_functions_and_cache, create_script_engine_with_functions,
+    detect_cycle, eval_with_functions_script, set_max_call_depth,
 };
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
@@ -124,6 +125,9 @@ impl Document {
     /// - Context variables (CURSOR_ROW, CURSOR_COL, HAS_SELECTION, SEL_R1, etc.)
     ///
     /// All modifications are collected into a single batch undo entry.
+    /// A sandboxed document (see [`Document::set_sandboxed`]) runs scripts
+    /// with the read builtins only, so write builtins are not found, and
+    /// without module imports.
     pub fn execute_script(
         &mut self,
         script: &str,
@@ -132,17 +136,30 @@ impl Document {
         // Create modifications tracker
         let modifications: ScriptModifications = Arc::new(Mutex::new(HashMap::new()));
 
-        // Create script engine with write builtins
-        let (engine, custom_ast, compile_error) = create_script_engine_with_functions(
-            self.grid.clone(),
-            self.value_cache.clone(),
-            modifications.clone(),
-            self.custom_functions.as_deref(),
-        );
+        // Create script engine with write builtins, unless sandboxed
+        let (engine, custom_ast, compile_error) = if self.sandboxed {
+            create_engine_with_functions_and_cache(
+                self.grid.clone(),
+                self.value_cache.clone(),
+                self.custom_functions.as_deref(),
+            )
+        } else {
+            create_script_engine_with_functions(
+                self.grid.clone(),
+                self.value_cache.clone(),
+                modifications.clone(),
+                self.custom_functions.as_deref(),
+            )
+        };
 
         if let Some(err) = compile_error {
             return Err(GridlineError::RhaiCompile(err));
         }
+        let mut engine = engine;
+        set_max_call_depth(&mut engine, self.max_call_depth);
+        if self.headless || self.sandboxed {
+            super::state::disable_module_imports(&mut engine);
+        }
 
         // Build the full script with context declarations
         let context_decls = context.to_rhai_declarations();
@@ -175,10 +192,10 @@ impl Document {
         })?;
         for (cell_ref, (_old_cell, new_cell)) in mods.iter() {
             if matches!(new_cell, Some(cell) if matches!(cell.contents, CellType::Script(_)))
-                && detect_cycle(cell_ref, &self.grid).is_some()
+                && let Some(cycle) = detect_cycle(cell_ref, &self.grid)
             {
                 self.rollback_script_modifications(&mods);
-                return Err(GridlineError::CircularDependency);
+                return Err(GridlineError::circular(&cycle));
             }
         }
 
@@ -220,6 +237,7 @@ impl Document {
             }
         }
 
+        self.apply_eval_policy();
         Ok(ScriptResult {
             cells_modified,
             return_value,
@@ -406,8 +424,72 @@ mod tests {
         let ctx = ScriptContext::new(0, 0);
 
         let result = doc.execute_script(r#"SET_CELL(0, 0, "=A1")"#, &ctx);
-        assert!(matches!(result, Err(GridlineError::CircularDependency)));
+        assert!(matches!(
+            result,
+            Err(GridlineError::CircularDependency { .. })
+        ));
         assert!(doc.grid.get(&CellRef::new(0, 0)).is_none());
         assert!(doc.undo_stack.is_empty());
     }
+
+    #[test]
+    fn test_sandboxed_document_omits_write_builtins() {
+        let mut doc = Document::new();
+        doc.set_sandboxed(true);
+        assert!(doc.is_sandboxed());
+        let ctx = ScriptContext::new(0, 0);
+
+        let err = doc
+            .execute_script("SET_CELL(0, 0, 42)", &ctx)
+            .unwrap_err()
+            .to_string();
+        assert!(err.to_lowercase().contains("function not found"), "{err}");
+        assert!(doc.grid.get(&CellRef::new(0, 0)).is_none());
+
+        // Formulas never see write builtins
+        doc.set_cell_from_input(CellRef::new(1, 0), "=SET_CELL(0, 0, 1)")
+            .unwrap();
+        let display = doc.get_cell_display(&CellRef::new(1, 0));
+        assert!(display.starts_with("#"), "{display}");
+        assert!(doc.grid.get(&CellRef::new(0, 0)).is_none());
+
+        // Read builtins still work
+        let result = doc.execute_script("1 + 2", &ctx).unwrap();
+        assert_eq!(result.return_value.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_sandboxed_document_blocks_formula_imports() {
+        let dir = std::env::temp_dir().join(format!(
+            "gridline_sandbox_{}_{:?}",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(std::path::PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_dir_all(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(dir.clone());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("module.rhai"), "fn answer() { 42 }").unwrap();
+        let module = dir.join("module").display().to_string().replace('\\', "/");
+        let formula = format!("=import \"{}\" as m; m::answer()", module);
+
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), &formula)
+            .unwrap();
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "42");
+
+        doc.set_sandboxed(true);
+        let display = doc.get_cell_display(&CellRef::new(0, 0));
+        assert!(display.starts_with("#ERR"), "{display}");
+        let ctx = ScriptContext::new(0, 0);
+        let script = format!("import \"{}\" as m; m::answer()", module);
+        assert!(doc.execute_script(&script, &ctx).is_err());
+
+        doc.set_sandboxed(false);
+        assert_eq!(doc.get_cell_display(&CellRef::new(0, 0)), "42");
+    }
 }
@@ -1,14 +1,76 @@
-use crate::error::Result;
+use super::align::AlignedRange;
+use super::column_formula::ColumnFormula;
+use super::notify::CellChange;
+use crate::error::{GridlineError, Result};
 use gridline_engine::engine::{
-    AST, Cell, CellRef, Grid, ValueCache, create_engine_with_functions_and_cache,
+    AST, Cell, CellRef, CellType, DEFAULT_MAX_CALL_DEPTH, ErrorMap, Grid, NumberLocale, SpillMap,
+    ValueCache, count_cell_references, create_engine_with_functions_and_cache,
+    expand_column_ranges, expand_row_references, extract_dependencies, has_column_ranges,
+    has_row_references, register_error_builtins, register_spill_builtins, set_blanks_are_zero,
+    set_max_call_depth,
 };
 use rhai::Engine;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::mpsc::Sender;
 
 /// Maximum number of undo entries to keep
 pub(crate) const MAX_UNDO_STACK: usize = 100;
 
+/// Size limits for formulas written to a document, whether entered through
+/// [`Document::set_cell_from_input`], pasted, imported or loaded from a file.
+///
+/// These bound the cost of preprocessing and dependency extraction when a
+/// document accepts untrusted input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormulaLimits {
+    /// Maximum formula length in characters (excluding the leading `=`)
+    pub max_len: usize,
+    /// Maximum number of cell references (a range `A1:B5` counts as two)
+    pub max_refs: usize,
+}
+
+impl Default for FormulaLimits {
+    fn default() -> Self {
+        FormulaLimits {
+            max_len: 8192,
+            max_refs: 1024,
+        }
+    }
+}
+
+impl FormulaLimits {
+    /// Check a formula (without the leading `=`) against these limits.
+    pub fn check(&self, formula: &str) -> Result<()> {
+        let len = formula.chars().count();
+        if len > self.max_len {
+            return Err(GridlineError::FormulaTooLong {
+                len,
+                max: self.max_len,
+            });
+        }
+        let count = count_cell_references(formula);
+        if count > self.max_refs {
+            return Err(GridlineError::TooManyReferences {
+                count,
+                max: self.max_refs,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// When formulas are evaluated (see [`Document::set_eval_policy`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvalPolicy {
+    /// Evaluate formulas when first displayed, suiting big interactive sheets
+    #[default]
+    Lazy,
+    /// Evaluate every dirty formula after each edit, so cached values are
+    /// always current (e.g. for servers reading values without displaying)
+    Eager,
+}
+
 /// Represents an undoable action for a single cell
 #[derive(Clone)]
 pub struct UndoAction {
@@ -24,6 +86,22 @@ pub enum UndoEntry {
     Single(UndoAction),
     /// A batch of modifications from a script execution
     Batch(Vec<UndoAction>),
+    /// Cell modifications (possibly none) that also changed the layout,
+    /// such as a merge or a row insert shifting merged regions
+    Layout {
+        actions: Vec<UndoAction>,
+        old_layout: Layout,
+        new_layout: Layout,
+    },
+}
+
+/// Sheet layout kept outside the grid, restored by undo along with cells.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Layout {
+    pub merged_regions: Vec<(CellRef, CellRef)>,
+    pub cell_alignments: Vec<AlignedRange>,
+    pub row_groups: Vec<(usize, usize)>,
+    pub collapsed_row_groups: HashSet<(usize, usize)>,
 }
 
 /// UI-agnostic document state for the spreadsheet.
@@ -42,10 +120,60 @@ pub struct Document {
     pub custom_functions: Option<String>,
     /// Compiled custom functions AST
     pub custom_ast: Option<AST>,
+    /// Lambdas defined in the sheet (see [`Document::define_lambda`])
+    pub(crate) lambdas: BTreeMap<String, String>,
+    /// Rhai source of `lambdas`, kept at the end of `custom_functions`
+    pub(crate) lambda_script: String,
+    /// File and line declaring each custom function, for go-to-definition
+    pub(crate) function_sources: HashMap<String, (PathBuf, usize)>,
     /// Reverse dependency map: cell -> cells that depend on it
     pub dependents: HashMap<CellRef, HashSet<CellRef>>,
-    /// Maps spill cell positions to their source cell
-    pub spill_sources: HashMap<CellRef, CellRef>,
+    /// Maps spill cell positions to their source cell (shared with engine builtins)
+    pub spill_sources: SpillMap,
+    /// Cells whose last display was an error, with that display (shared with engine builtins)
+    pub error_cells: ErrorMap,
+    /// Columns hidden from display (data and references are unaffected)
+    pub hidden_cols: HashSet<usize>,
+    /// Merged display regions as (top-left, bottom-right); see [`Document::merge_cells`]
+    pub merged_regions: Vec<(CellRef, CellRef)>,
+    /// Row groups as inclusive (start, end) rows, sorted; see [`Document::group_rows`]
+    pub row_groups: Vec<(usize, usize)>,
+    /// Row groups currently collapsed (see [`Document::toggle_row_group`])
+    pub collapsed_row_groups: HashSet<(usize, usize)>,
+    /// Per-cell alignment overrides (see [`Document::set_alignment`])
+    pub cell_alignments: Vec<AlignedRange>,
+    /// Sheet metadata (title, author, created, ...) saved in the .grd header
+    pub(crate) metadata: BTreeMap<String, String>,
+    /// Grouping/decimal separators recognized when parsing typed numbers
+    pub number_locale: NumberLocale,
+    /// Maximum formula length and reference count accepted from input
+    pub formula_limits: FormulaLimits,
+    /// Whether blank cells count as 0 in formulas (see [`Document::set_blanks_are_zero`])
+    pub(crate) blanks_are_zero: bool,
+    /// Bound on nested function calls (see [`Document::set_max_call_depth`])
+    pub(crate) max_call_depth: usize,
+    /// Leading header rows skipped by whole-column ranges and profiling
+    /// (see [`Document::set_header_rows`])
+    pub(crate) header_rows: usize,
+    /// Row-relative formulas filled down whole columns (see [`Document::set_column_formula`])
+    pub(crate) column_formulas: BTreeMap<usize, ColumnFormula>,
+    /// Whether scripts run without write builtins (see [`Document::set_sandboxed`])
+    pub(crate) sandboxed: bool,
+    /// When formulas are evaluated (see [`Document::set_eval_policy`])
+    pub(crate) eval_policy: EvalPolicy,
+    /// Whether filesystem access is refused (see [`Document::new_headless`])
+    pub(crate) headless: bool,
+    /// Whether deleting a precedent freezes its dependents
+    /// (see [`Document::set_freeze_dependents_on_delete`])
+    pub(crate) freeze_dependents_on_delete: bool,
+    /// Formula cells recomputed on every recalc tick (see [`Document::recalc_volatile`])
+    pub volatile_cells: HashSet<CellRef>,
+    /// Formulas calling `VOLATILE(...)`, kept up to date by `rebuild_dependents`
+    pub(crate) volatile_formulas: HashSet<CellRef>,
+    /// Receiver of change events (see [`Document::subscribe`])
+    pub(crate) change_subscriber: Option<Sender<CellChange>>,
+    /// Displays last sent to the subscriber, diffed to find changes
+    pub(crate) published_displays: HashMap<CellRef, String>,
     /// Shared value cache for computed cell values (accessible by engine builtins).
     /// Used for both scalar formula results and array formula spill values.
     /// DashMap is internally Arc-based, clones are cheap.
@@ -63,8 +191,12 @@ impl Document {
     pub fn new() -> Self {
         let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
         let value_cache = ValueCache::default();
-        let (engine, _, _) =
+        let spill_sources = SpillMap::default();
+        let (mut engine, _, _) =
             create_engine_with_functions_and_cache(grid.clone(), value_cache.clone(), None);
+        let error_cells = ErrorMap::default();
+        register_spill_builtins(&mut engine, spill_sources.clone());
+        register_error_builtins(&mut engine, error_cells.clone());
 
         Document {
             grid,
@@ -74,17 +206,73 @@ impl Document {
             functions_files: Vec::new(),
             custom_functions: None,
             custom_ast: None,
+            lambdas: BTreeMap::new(),
+            lambda_script: String::new(),
+            function_sources: HashMap::new(),
             dependents: HashMap::new(),
-            spill_sources: HashMap::new(),
+            spill_sources,
+            error_cells,
+            hidden_cols: HashSet::new(),
+            merged_regions: Vec::new(),
+            row_groups: Vec::new(),
+            collapsed_row_groups: HashSet::new(),
+            cell_alignments: Vec::new(),
+            metadata: BTreeMap::new(),
+            number_locale: NumberLocale::default(),
+            formula_limits: FormulaLimits::default(),
+            blanks_are_zero: true,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            header_rows: 0,
+            sandboxed: false,
+            eval_policy: EvalPolicy::default(),
+            headless: false,
+            freeze_dependents_on_delete: false,
+            column_formulas: BTreeMap::new(),
+            volatile_cells: HashSet::new(),
+            volatile_formulas: HashSet::new(),
+            change_subscriber: None,
+            published_displays: HashMap::new(),
             value_cache,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
         }
     }
 
+    /// Create a document that never touches the filesystem, for embedding in
+    /// sandboxed hosts.
+    ///
+    /// No default or custom functions are loaded, formulas and scripts cannot
+    /// `import` Rhai modules from disk, and every method that reads or writes
+    /// a path (loading, saving, importing, exporting) fails with
+    /// [`GridlineError::Headless`].
+    pub fn new_headless() -> Self {
+        let mut doc = Self::new();
+        doc.headless = true;
+        disable_module_imports(&mut doc.engine);
+        doc
+    }
+
+    /// Whether the document was created with [`Document::new_headless`].
+    pub fn is_headless(&self) -> bool {
+        self.headless
+    }
+
     /// Create a new document and load a file if provided.
     pub fn with_file(path: Option<PathBuf>, functions_files: Vec<PathBuf>) -> Result<Self> {
+        Self::with_file_sandboxed(path, functions_files, false)
+    }
+
+    /// Like [`Document::with_file`], but sandboxed (see
+    /// [`Document::set_sandboxed`]) before anything is loaded or evaluated.
+    pub fn with_file_sandboxed(
+        path: Option<PathBuf>,
+        functions_files: Vec<PathBuf>,
+        sandboxed: bool,
+    ) -> Result<Self> {
         let mut core = Self::new();
+        if sandboxed {
+            core.set_sandboxed(true);
+        }
 
         // Load custom functions if specified.
         for func_path in &functions_files {
@@ -102,13 +290,28 @@ impl Document {
         Ok(core)
     }
 
+    /// Check `cell`, if it is a formula, against `formula_limits`.
+    pub(crate) fn check_formula_limits(&self, cell: &Cell) -> Result<()> {
+        match &cell.contents {
+            CellType::Script(formula) => self.formula_limits.check(formula),
+            _ => Ok(()),
+        }
+    }
+
     /// Rebuild the reverse dependency map from the grid.
     /// Call this after cells are added, removed, or their formulas change.
     pub(crate) fn rebuild_dependents(&mut self) {
+        self.refresh_contextual_dependencies();
         self.dependents.clear();
+        self.volatile_formulas.clear();
         for entry in self.grid.iter() {
             let cell_ref = entry.key();
             let cell = entry.value();
+            if let CellType::Script(formula) = &cell.contents
+                && super::volatile::calls_volatile(formula)
+            {
+                self.volatile_formulas.insert(cell_ref.clone());
+            }
             for dep in &cell.depends_on {
                 self.dependents
                     .entry(dep.clone())
@@ -117,6 +320,233 @@ impl Document {
             }
         }
     }
+
+    /// Sheet metadata such as `title`, `author` and `created`.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Set a metadata entry; an empty value removes the key.
+    ///
+    /// Keys must be non-empty and may not contain whitespace or `:`.
+    pub fn set_metadata(&mut self, key: &str, value: &str) -> Result<()> {
+        let key = key.trim();
+        if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c == ':') {
+            return Err(GridlineError::InvalidMetadataKey(key.to_string()));
+        }
+        // Values are stored on a single line in the .grd header.
+        let value = value.trim().replace(['\r', '\n'], " ");
+        let changed = if value.is_empty() {
+            self.metadata.remove(key).is_some()
+        } else {
+            self.metadata.insert(key.to_string(), value.clone()) != Some(value)
+        };
+        if changed {
+            self.modified = true;
+        }
+        Ok(())
+    }
+
+    /// Cells directly referenced by the formula at `cell_ref`, sorted by row then column.
+    pub fn precedents_of(&self, cell_ref: &CellRef) -> Vec<CellRef> {
+        let mut precedents = match self.grid.get(cell_ref).map(|c| c.contents.clone()) {
+            Some(CellType::Script(script)) => self.formula_dependencies(cell_ref, &script),
+            _ => Vec::new(),
+        };
+        sort_row_major(&mut precedents);
+        precedents.dedup();
+        precedents
+    }
+
+    /// Cells whose formulas directly reference `cell_ref`, sorted by row then column.
+    pub fn dependents_of(&self, cell_ref: &CellRef) -> Vec<CellRef> {
+        let mut dependents: Vec<CellRef> = self
+            .dependents
+            .get(cell_ref)
+            .map(|deps| deps.iter().cloned().collect())
+            .unwrap_or_default();
+        sort_row_major(&mut dependents);
+        dependents
+    }
+
+    /// Whether blank cells count as 0 in formulas.
+    pub fn blanks_are_zero(&self) -> bool {
+        self.blanks_are_zero
+    }
+
+    /// Install a freshly built formula engine, re-applying document-level
+    /// engine state (spill and error builtins, the blanks rule, the call
+    /// depth limit and, when headless or sandboxed, disabled imports).
+    pub(crate) fn install_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        register_spill_builtins(&mut self.engine, self.spill_sources.clone());
+        register_error_builtins(&mut self.engine, self.error_cells.clone());
+        set_blanks_are_zero(&mut self.engine, self.blanks_are_zero);
+        set_max_call_depth(&mut self.engine, self.max_call_depth);
+        if self.headless || self.sandboxed {
+            disable_module_imports(&mut self.engine);
+        }
+    }
+
+    /// Choose whether blank cells act as 0 in arithmetic and range builtins
+    /// (the default) or make the formula error, to catch missing data.
+    /// Recalculates every formula under the new rule.
+    pub fn set_blanks_are_zero(&mut self, blanks_are_zero: bool) {
+        self.blanks_are_zero = blanks_are_zero;
+        set_blanks_are_zero(&mut self.engine, blanks_are_zero);
+        self.value_cache.clear();
+        self.spill_sources.clear();
+        self.error_cells.clear();
+        self.invalidate_script_cache();
+        self.evaluate_all_cells();
+    }
+
+    /// Maximum depth of nested function calls in formulas and scripts.
+    pub fn max_call_depth(&self) -> usize {
+        self.max_call_depth
+    }
+
+    /// Bound how deeply function calls may nest (64 by default). A custom
+    /// function recursing past it shows a recursion-limit `#ERR` instead of
+    /// overflowing the stack; very large bounds give that protection up.
+    /// Recalculates every formula under the new bound.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+        set_max_call_depth(&mut self.engine, depth);
+        self.value_cache.clear();
+        self.spill_sources.clear();
+        self.error_cells.clear();
+        self.invalidate_script_cache();
+        self.evaluate_all_cells();
+    }
+
+    /// Whether the document is sandboxed.
+    pub fn is_sandboxed(&self) -> bool {
+        self.sandboxed
+    }
+
+    /// Sandbox the document for untrusted files. Formula engines never
+    /// register write or I/O builtins; when sandboxed, scripts (`:call`,
+    /// `:rhai`) get the same read-only builtins, and neither formulas nor
+    /// scripts can `import` Rhai modules, so a hostile sheet can't touch the
+    /// filesystem or silently rewrite cells. Recalculates every formula.
+    pub fn set_sandboxed(&mut self, sandboxed: bool) {
+        self.sandboxed = sandboxed;
+        // Rebuild the engine so the module resolver matches the new mode
+        let (engine, custom_ast, _) = create_engine_with_functions_and_cache(
+            self.grid.clone(),
+            self.value_cache.clone(),
+            self.custom_functions.as_deref(),
+        );
+        self.install_engine(engine);
+        self.custom_ast = custom_ast;
+        self.refresh_after_functions_change();
+    }
+
+    /// When formulas are evaluated.
+    pub fn eval_policy(&self) -> EvalPolicy {
+        self.eval_policy
+    }
+
+    /// Choose lazy evaluation on display (the default) or eager evaluation
+    /// after every edit. Switching to eager evaluates everything now.
+    pub fn set_eval_policy(&mut self, policy: EvalPolicy) {
+        self.eval_policy = policy;
+        self.apply_eval_policy();
+    }
+
+    /// Whether deleting a referenced cell freezes the formulas using it.
+    pub fn freezes_dependents_on_delete(&self) -> bool {
+        self.freeze_dependents_on_delete
+    }
+
+    /// When on, clearing a cell or deleting its row/column first replaces
+    /// formulas that reference it with their last computed value (as
+    /// [`Document::freeze_cell`] would), instead of letting them recompute
+    /// to 0 or `#REF!`. The freeze is part of the same undo step.
+    pub fn set_freeze_dependents_on_delete(&mut self, freeze: bool) {
+        self.freeze_dependents_on_delete = freeze;
+    }
+
+    /// Number of leading header rows excluded from whole-column ranges.
+    pub fn header_rows(&self) -> usize {
+        self.header_rows
+    }
+
+    /// Treat the first `rows` rows as headers: whole-column ranges like
+    /// `SUM(A:A)` start below them and column profiles skip them.
+    /// Recalculates every formula under the new bounds.
+    pub fn set_header_rows(&mut self, rows: usize) {
+        self.header_rows = rows;
+        self.rebuild_dependents();
+        self.value_cache.clear();
+        self.spill_sources.clear();
+        self.error_cells.clear();
+        self.invalidate_script_cache();
+        self.evaluate_all_cells();
+    }
+
+    /// Rewrite whole-column ranges in `script` to span from below the header
+    /// rows down to the last used row.
+    pub(crate) fn expand_column_ranges(&self, script: &str) -> String {
+        if !has_column_ranges(script) {
+            return script.to_string();
+        }
+        let last_row = self.used_extent().map_or(0, |(_, row)| row);
+        expand_column_ranges(script, self.header_rows, last_row.max(self.header_rows))
+    }
+
+    /// Cells referenced by `script` when it sits at `cell_ref`, with
+    /// whole-column ranges and same-row references resolved.
+    pub(crate) fn formula_dependencies(&self, cell_ref: &CellRef, script: &str) -> Vec<CellRef> {
+        let script = expand_row_references(script, cell_ref.row);
+        extract_dependencies(&self.expand_column_ranges(&script))
+    }
+
+    /// Recompute dependencies of formulas using whole-column ranges or
+    /// same-row references, which follow the used range or the formula's own
+    /// row rather than the formula text alone.
+    pub(crate) fn refresh_contextual_dependencies(&mut self) {
+        let targets: Vec<(CellRef, String)> = self
+            .grid
+            .iter()
+            .filter_map(|entry| match &entry.contents {
+                CellType::Script(script)
+                    if has_column_ranges(script) || has_row_references(script) =>
+                {
+                    Some((entry.key().clone(), script.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        for (cell_ref, script) in targets {
+            let depends_on = self.formula_dependencies(&cell_ref, &script);
+            if let Some(mut cell) = self.grid.get_mut(&cell_ref) {
+                cell.depends_on = depends_on;
+            }
+        }
+    }
+
+    /// Highest occupied `(col, row)`, counting spill outputs, or `None` if the sheet is empty.
+    pub fn used_extent(&self) -> Option<(usize, usize)> {
+        self.grid
+            .iter()
+            .map(|entry| entry.key().clone())
+            .chain(self.spill_sources.iter().map(|entry| entry.key().clone()))
+            .fold(None, |extent, cell| {
+                let (col, row) = extent.unwrap_or((0, 0));
+                Some((col.max(cell.col), row.max(cell.row)))
+            })
+    }
+}
+
+/// Make `import` statements fail instead of resolving modules from disk.
+pub(crate) fn disable_module_imports(engine: &mut Engine) {
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+}
+
+fn sort_row_major(cells: &mut [CellRef]) {
+    cells.sort_by(|a, b| a.row.cmp(&b.row).then(a.col.cmp(&b.col)));
 }
 
 impl Default for Document {
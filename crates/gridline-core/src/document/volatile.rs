@@ -0,0 +1,186 @@
+//! Volatile cells: formulas recomputed on every recalc tick.
+//!
+//! Formulas are normally cached until something they reference changes, so a
+//! `NOW()` clock would stay frozen. Cells marked volatile, and formulas
+//! wrapped in `VOLATILE(...)`, are dirtied on each tick instead.
+
+use super::Document;
+use super::merge::shift_span;
+use gridline_engine::engine::{CellRef, CellType, ShiftOperation, blank_string_literals};
+
+/// Marker call making a formula volatile, e.g. `=VOLATILE(NOW())`.
+const VOLATILE_CALL: &str = "VOLATILE(";
+
+/// Whether `formula` calls `VOLATILE(...)` outside its string literals.
+pub(crate) fn calls_volatile(formula: &str) -> bool {
+    if !formula.contains(VOLATILE_CALL) {
+        return false;
+    }
+    let blanked = blank_string_literals(formula);
+    blanked
+        .match_indices(VOLATILE_CALL)
+        .any(|(i, _)| !blanked[..i].ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+impl Document {
+    /// Mark (or unmark) `cell_ref` as volatile. Returns whether the marking changed.
+    pub fn set_volatile(&mut self, cell_ref: &CellRef, volatile: bool) -> bool {
+        if volatile {
+            self.volatile_cells.insert(cell_ref.clone())
+        } else {
+            self.volatile_cells.remove(cell_ref)
+        }
+    }
+
+    /// Whether `cell_ref` is recomputed on every recalc tick.
+    pub fn is_volatile(&self, cell_ref: &CellRef) -> bool {
+        self.volatile_cells.contains(cell_ref) || self.volatile_formulas.contains(cell_ref)
+    }
+
+    /// Whether any cell needs recomputing on recalc ticks. Cheap enough to
+    /// call on every UI tick: only the tracked cells are looked at.
+    pub fn has_volatile_cells(&self) -> bool {
+        !self.volatile_formulas.is_empty()
+            || self
+                .volatile_cells
+                .iter()
+                .any(|cell_ref| self.is_formula(cell_ref))
+    }
+
+    /// Recalc tick: mark every volatile formula and its dependents dirty so
+    /// they re-evaluate when next displayed. Returns the volatile cells.
+    pub fn recalc_volatile(&mut self) -> Vec<CellRef> {
+        let cells = self.volatile_formula_cells();
+        for cell_ref in &cells {
+            if let Some(mut cell) = self.grid.get_mut(cell_ref) {
+                cell.dirty = true;
+                cell.cached_value = None;
+            }
+            self.value_cache.remove(cell_ref);
+            self.clear_spill_from(cell_ref);
+            self.mark_dependents_dirty(cell_ref);
+        }
+        cells
+    }
+
+    /// Formula cells that are volatile, sorted by row then column.
+    fn volatile_formula_cells(&self) -> Vec<CellRef> {
+        let mut cells: Vec<CellRef> = self
+            .volatile_formulas
+            .iter()
+            .chain(
+                self.volatile_cells
+                    .iter()
+                    .filter(|cell_ref| !self.volatile_formulas.contains(*cell_ref)),
+            )
+            .filter(|cell_ref| self.is_formula(cell_ref))
+            .cloned()
+            .collect();
+        cells.sort_by_key(|cell| (cell.row, cell.col));
+        cells
+    }
+
+    fn is_formula(&self, cell_ref: &CellRef) -> bool {
+        self.grid
+            .get(cell_ref)
+            .is_some_and(|cell| matches!(cell.contents, CellType::Script(_)))
+    }
+
+    /// Keep volatile marks on their cells across a row/column insert or
+    /// delete; marks on deleted cells are dropped.
+    pub(crate) fn shift_volatile_cells(&mut self, op: ShiftOperation) {
+        let cells = std::mem::take(&mut self.volatile_cells);
+        self.volatile_cells = cells
+            .into_iter()
+            .filter_map(|cell| match op {
+                ShiftOperation::InsertRow(at) | ShiftOperation::DeleteRow(at) => {
+                    let insert = matches!(op, ShiftOperation::InsertRow(_));
+                    let (row, _) = shift_span(cell.row, cell.row, at, insert)?;
+                    Some(CellRef::new(cell.col, row))
+                }
+                ShiftOperation::InsertColumn(at) | ShiftOperation::DeleteColumn(at) => {
+                    let insert = matches!(op, ShiftOperation::InsertColumn(_));
+                    let (col, _) = shift_span(cell.col, cell.col, at, insert)?;
+                    Some(CellRef::new(col, cell.row))
+                }
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recalc_volatile_dirties_only_volatile_cells_and_dependents() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "=NOW()")
+            .unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(1, 0), "=TODAY()")
+            .unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(2, 0), "=VOLATILE(1 + 1)")
+            .unwrap(); // C1
+        doc.set_cell_from_input(CellRef::new(3, 0), "=C1 * 2")
+            .unwrap(); // D1
+        doc.set_cell_from_input(CellRef::new(4, 0), "7").unwrap(); // E1, not a formula
+        assert!(doc.set_volatile(&CellRef::new(0, 0), true));
+        assert!(doc.set_volatile(&CellRef::new(4, 0), true));
+        for col in 0..4 {
+            doc.get_cell_display(&CellRef::new(col, 0));
+        }
+        let dirty = |doc: &Document, col| doc.grid.get(&CellRef::new(col, 0)).unwrap().dirty;
+        assert!((0..4).all(|col| !dirty(&doc, col)));
+
+        let ticked = doc.recalc_volatile();
+        assert_eq!(ticked, vec![CellRef::new(0, 0), CellRef::new(2, 0)]);
+        assert!(dirty(&doc, 0));
+        assert!(!dirty(&doc, 1));
+        assert!(dirty(&doc, 2));
+        assert!(dirty(&doc, 3));
+        assert_eq!(doc.get_cell_display(&CellRef::new(3, 0)), "4");
+        assert!(doc.is_volatile(&CellRef::new(2, 0)));
+
+        assert!(doc.set_volatile(&CellRef::new(0, 0), false));
+        doc.get_cell_display(&CellRef::new(0, 0));
+        doc.recalc_volatile();
+        assert!(!dirty(&doc, 0));
+    }
+
+    #[test]
+    fn test_volatile_tracking_follows_edits_and_shifts() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "=LEN(\"VOLATILE(\")")
+            .unwrap();
+        assert!(!doc.has_volatile_cells());
+
+        doc.set_cell_from_input(CellRef::new(0, 1), "=VOLATILE(NOW())")
+            .unwrap(); // A2
+        doc.set_cell_from_input(CellRef::new(1, 1), "=NOW()")
+            .unwrap(); // B2
+        doc.set_volatile(&CellRef::new(1, 1), true);
+        doc.insert_row(0);
+        assert_eq!(
+            doc.recalc_volatile(),
+            vec![CellRef::new(0, 2), CellRef::new(1, 2)]
+        );
+
+        doc.delete_row(2);
+        assert!(doc.volatile_cells.is_empty());
+        assert!(!doc.has_volatile_cells());
+    }
+
+    #[test]
+    fn test_volatile_marks_move_up_once_per_deleted_row() {
+        let mut doc = Document::new();
+        doc.set_volatile(&CellRef::new(0, 1), true); // A2
+        doc.set_volatile(&CellRef::new(0, 5), true); // A6
+        doc.delete_row(0);
+        assert_eq!(
+            doc.volatile_cells,
+            [CellRef::new(0, 0), CellRef::new(0, 4)]
+                .into_iter()
+                .collect()
+        );
+    }
+}
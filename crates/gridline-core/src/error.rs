@@ -2,6 +2,7 @@
 
 use thiserror::Error;
 
+use gridline_engine::engine::CellRef;
 use rhai::EvalAltResult;
 
 /// Errors that can occur in the Gridline application
@@ -13,12 +14,25 @@ pub enum GridlineError {
     #[error("Parse error at line {line}: {message}")]
     Parse { line: usize, message: String },
 
-    #[error("Circular dependency detected")]
-    CircularDependency,
+    #[error(
+        "Circular dependency detected: {} (remove reference to {reference} from {cell})",
+        .path.join(" -> ")
+    )]
+    CircularDependency {
+        /// The cycle as cell names, starting and ending at the edited cell
+        path: Vec<String>,
+        /// The edited cell whose new formula closed the cycle
+        cell: String,
+        /// The reference in `cell` to remove to break the cycle
+        reference: String,
+    },
 
     #[error("No file path set")]
     NoFilePath,
 
+    #[error("Filesystem access is disabled for this document")]
+    Headless,
+
     #[error("No functions file loaded")]
     NoFunctionsLoaded,
 
@@ -31,6 +45,27 @@ pub enum GridlineError {
     #[error("Nothing to redo")]
     NothingToRedo,
 
+    #[error("Formula too long: {len} characters (max {max})")]
+    FormulaTooLong { len: usize, max: usize },
+
+    #[error("Formula has too many cell references: {count} (max {max})")]
+    TooManyReferences { count: usize, max: usize },
+
+    #[error("Invalid metadata key: {0}")]
+    InvalidMetadataKey(String),
+
+    #[error("Cell {0} is empty")]
+    EmptyCell(String),
+
+    #[error("Target cell {0} is not empty")]
+    TargetOccupied(String),
+
+    #[error("Overlaps merged cells {0}")]
+    MergeOverlap(String),
+
+    #[error("Overlaps row group {0}")]
+    RowGroupOverlap(String),
+
     #[error("Rhai error: {0}")]
     Rhai(
         #[from]
@@ -40,6 +75,34 @@ pub enum GridlineError {
 
     #[error("Rhai compile error: {0}")]
     RhaiCompile(String),
+
+    #[error("Expected a closure such as |i| i * i, got {0}")]
+    NotAClosure(String),
+
+    #[error("Invalid function name: {0}")]
+    InvalidFunctionName(String),
+
+    #[error("{0} is a built-in function")]
+    BuiltinFunctionName(String),
+
+    #[error("Generator failed at index {index}: {message}")]
+    GeneratorFailed { index: usize, message: String },
+}
+
+impl GridlineError {
+    /// Build a [`GridlineError::CircularDependency`] from a cycle path as returned by
+    /// `detect_cycle`. The edited cell is the newest edge, so the suggestion is to drop
+    /// its reference to the next cell on the cycle.
+    pub(crate) fn circular(cycle: &[CellRef]) -> Self {
+        let path: Vec<String> = cycle.iter().map(ToString::to_string).collect();
+        let cell = path.first().cloned().unwrap_or_default();
+        let reference = path.get(1).cloned().unwrap_or_else(|| cell.clone());
+        GridlineError::CircularDependency {
+            path,
+            cell,
+            reference,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, GridlineError>;
@@ -4,7 +4,11 @@ pub mod document;
 pub mod error;
 pub mod storage;
 
-pub use document::{Document, ScriptContext, UndoAction, UndoEntry};
+pub use document::{
+    Align, AlignedRange, AuditIssue, AuditWarning, CellChange, ColumnProfile, Definition, Document,
+    EvalPolicy, FormulaLimits, Layout, ScriptContext, SelectionStats, UndoAction, UndoEntry,
+    normalize_formula_input,
+};
 pub use error::{GridlineError, Result};
 
 pub use gridline_engine::engine::CellRef;
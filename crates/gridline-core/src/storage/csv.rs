@@ -9,8 +9,50 @@ use std::path::Path;
 const MAX_CSV_FILE_BYTES: u64 = 16 * 1024 * 1024; // 16 MiB
 const MAX_IMPORTED_CSV_CELLS: usize = 100_000;
 
+/// Which part of a CSV file to import.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsvImportOptions {
+    /// 0-based field indexes to keep, in the order they are placed; `None`
+    /// keeps every field
+    pub columns: Option<Vec<usize>>,
+    /// Number of leading lines to skip
+    pub skip: usize,
+    /// Maximum number of lines to import after skipping
+    pub limit: Option<usize>,
+}
+
+/// How to lay out an exported CSV file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsvExportOptions {
+    /// Start the file with a UTF-8 byte order mark
+    pub bom: bool,
+    /// End lines with `\r\n` instead of `\n`
+    pub crlf: bool,
+}
+
+impl CsvExportOptions {
+    /// BOM and CRLF line endings, as Excel on Windows expects.
+    pub fn excel() -> Self {
+        CsvExportOptions {
+            bom: true,
+            crlf: true,
+        }
+    }
+}
+
 /// Parse a CSV file into cells, starting at the given offset
 pub fn parse_csv(path: &Path, start_col: usize, start_row: usize) -> Result<Vec<(CellRef, Cell)>> {
+    parse_csv_with_options(path, start_col, start_row, &CsvImportOptions::default())
+}
+
+/// Parse the rows and columns of a CSV file selected by `options` into
+/// cells, placed contiguously from the given offset.
+pub fn parse_csv_with_options(
+    path: &Path,
+    start_col: usize,
+    start_row: usize,
+    options: &CsvImportOptions,
+) -> Result<Vec<(CellRef, Cell)>> {
     let meta = std::fs::metadata(path)?;
     if meta.len() > MAX_CSV_FILE_BYTES {
         return Err(GridlineError::Io(std::io::Error::new(
@@ -27,18 +69,30 @@ pub fn parse_csv(path: &Path, start_col: usize, start_row: usize) -> Result<Vec<
     let reader = BufReader::new(file);
     let mut cells = Vec::new();
 
-    for (row_idx, line_res) in reader.lines().enumerate() {
+    let lines = reader
+        .lines()
+        .enumerate()
+        .skip(options.skip)
+        .take(options.limit.unwrap_or(usize::MAX));
+    for (row_idx, (line_idx, line_res)) in lines.enumerate() {
         let line = line_res?;
         let row = start_row
             .checked_add(row_idx)
             .ok_or_else(|| GridlineError::Parse {
-                line: row_idx + 1,
+                line: line_idx + 1,
                 message: "CSV row index overflow from import offset".to_string(),
             })?;
         let fields = parse_csv_line(&line).map_err(|message| GridlineError::Parse {
-            line: row_idx + 1,
+            line: line_idx + 1,
             message: message.to_string(),
         })?;
+        let fields: Vec<String> = match &options.columns {
+            Some(columns) => columns
+                .iter()
+                .map(|&idx| fields.get(idx).cloned().unwrap_or_default())
+                .collect(),
+            None => fields,
+        };
         for (col_idx, field) in fields.into_iter().enumerate() {
             if field.is_empty() {
                 continue;
@@ -46,7 +100,7 @@ pub fn parse_csv(path: &Path, start_col: usize, start_row: usize) -> Result<Vec<
             let col = start_col
                 .checked_add(col_idx)
                 .ok_or_else(|| GridlineError::Parse {
-                    line: row_idx + 1,
+                    line: line_idx + 1,
                     message: "CSV column index overflow from import offset".to_string(),
                 })?;
             let cell_ref = CellRef::new(col, row);
@@ -54,7 +108,7 @@ pub fn parse_csv(path: &Path, start_col: usize, start_row: usize) -> Result<Vec<
             cells.push((cell_ref, cell));
             if cells.len() > MAX_IMPORTED_CSV_CELLS {
                 return Err(GridlineError::Parse {
-                    line: row_idx + 1,
+                    line: line_idx + 1,
                     message: format!(
                         "Too many CSV cells: {} (max {})",
                         cells.len(),
@@ -68,6 +122,29 @@ pub fn parse_csv(path: &Path, start_col: usize, start_row: usize) -> Result<Vec<
     Ok(cells)
 }
 
+/// Split tab-separated clipboard text into rows of fields.
+///
+/// Line endings are normalized, and trailing empty lines and trailing empty
+/// fields on each row are dropped so stray tabs/newlines from other apps do
+/// not produce spurious cells.
+pub fn parse_tsv(text: &str) -> Vec<Vec<String>> {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut rows: Vec<Vec<String>> = text
+        .split('\n')
+        .map(|line| {
+            let mut fields: Vec<String> = line.split('\t').map(str::to_string).collect();
+            while fields.last().is_some_and(|f| f.trim().is_empty()) {
+                fields.pop();
+            }
+            fields
+        })
+        .collect();
+    while rows.last().is_some_and(|row| row.is_empty()) {
+        rows.pop();
+    }
+    rows
+}
+
 /// Parse a single CSV line, handling quoted fields
 pub(crate) fn parse_csv_line(line: &str) -> std::result::Result<Vec<String>, &'static str> {
     let mut fields = Vec::new();
@@ -158,6 +235,17 @@ pub fn write_csv(
     path: &Path,
     doc: &mut Document,
     range: Option<((usize, usize), (usize, usize))>,
+) -> Result<()> {
+    write_csv_with_options(path, doc, range, &CsvExportOptions::default())
+}
+
+/// Export grid data to CSV using evaluated display values, with the byte
+/// order mark and line endings chosen by `options`.
+pub fn write_csv_with_options(
+    path: &Path,
+    doc: &mut Document,
+    range: Option<((usize, usize), (usize, usize))>,
+    options: &CsvExportOptions,
 ) -> Result<()> {
     let (min_row, min_col, max_row, max_col) = if let Some(((c1, r1), (c2, r2))) = range {
         (r1, c1, r2, c2)
@@ -193,6 +281,10 @@ pub fn write_csv(
     };
 
     let mut file = std::fs::File::create(path)?;
+    if options.bom {
+        file.write_all("\u{feff}".as_bytes())?;
+    }
+    let line_end = if options.crlf { "\r\n" } else { "\n" };
 
     for row in min_row..=max_row {
         let mut row_fields = Vec::new();
@@ -201,7 +293,7 @@ pub fn write_csv(
             let value = doc.get_cell_display(&cell_ref);
             row_fields.push(escape_csv_field(&value));
         }
-        writeln!(file, "{}", row_fields.join(","))?;
+        write!(file, "{}{}", row_fields.join(","), line_end)?;
     }
 
     Ok(())
@@ -603,4 +695,98 @@ mod tests {
             other => panic!("expected parse error, got {other:?}"),
         }
     }
+
+    #[test]
+    fn test_parse_csv_with_options_selects_columns_and_rows() {
+        let input_path = std::env::temp_dir().join(format!(
+            "gridline_parse_csv_subset_{}_{:?}.csv",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(std::path::PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(input_path.clone());
+        std::fs::write(&input_path, "h1,h2,h3,h4\n1,2,3,4\n5,6,7,8\n9,10,11,12\n").unwrap();
+
+        let options = CsvImportOptions {
+            columns: Some(vec![3, 1, 9]),
+            skip: 1,
+            limit: Some(2),
+        };
+        let cells = parse_csv_with_options(&input_path, 2, 5, &options).unwrap();
+        let placed: Vec<(CellRef, String)> = cells
+            .into_iter()
+            .map(|(cell_ref, cell)| (cell_ref, cell.to_input_string()))
+            .collect();
+        assert_eq!(
+            placed,
+            vec![
+                (CellRef::new(2, 5), "4".to_string()),
+                (CellRef::new(3, 5), "2".to_string()),
+                (CellRef::new(2, 6), "8".to_string()),
+                (CellRef::new(3, 6), "6".to_string()),
+            ]
+        );
+
+        // Skipping past the end imports nothing
+        let options = CsvImportOptions {
+            skip: 10,
+            ..CsvImportOptions::default()
+        };
+        assert!(
+            parse_csv_with_options(&input_path, 0, 0, &options)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_parse_tsv_trims_trailing_empty_fields_and_lines() {
+        assert_eq!(
+            parse_tsv("a\tb\t\t\r\nc\t\n\t\n\n"),
+            vec![vec!["a", "b"], vec!["c"]]
+        );
+        // Interior blanks are kept so columns stay aligned
+        assert_eq!(
+            parse_tsv("\tx\n\ny"),
+            vec![vec!["", "x"], vec![], vec!["y"]]
+        );
+        assert!(parse_tsv("\t\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_export_csv_excel_options_add_bom_and_crlf() {
+        let mut core = Document::new();
+        core.set_cell_from_input(CellRef::new(0, 0), "café")
+            .unwrap();
+        core.set_cell_from_input(CellRef::new(1, 0), "1").unwrap();
+        core.set_cell_from_input(CellRef::new(0, 1), "2").unwrap();
+
+        let output_path = std::env::temp_dir().join(format!(
+            "gridline_export_excel_{}_{:?}.csv",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(std::path::PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(output_path.clone());
+
+        write_csv_with_options(&output_path, &mut core, None, &CsvExportOptions::excel()).unwrap();
+        let bytes = std::fs::read(&output_path).unwrap();
+        assert!(bytes.starts_with(&[0xEF, 0xBB, 0xBF]));
+        assert_eq!(&bytes[3..], "café,1\r\n2,\r\n".as_bytes());
+
+        // The default stays LF without a BOM
+        write_csv(&output_path, &mut core, None).unwrap();
+        let bytes = std::fs::read(&output_path).unwrap();
+        assert_eq!(bytes, "café,1\n2,\n".as_bytes());
+    }
 }
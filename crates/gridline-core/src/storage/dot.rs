@@ -0,0 +1,80 @@
+//! Graphviz DOT export of the cell dependency graph
+
+use crate::document::Document;
+use gridline_engine::engine::{CellRef, error_type_code};
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Write the dependency graph to a DOT file
+pub fn write_dependency_dot(path: &Path, doc: &mut Document) -> std::io::Result<()> {
+    std::fs::write(path, dependency_dot(doc))
+}
+
+/// Render the dependency graph as DOT: one node per cell taking part in a
+/// dependency, labeled by its A1 reference, and an edge from each precedent
+/// to each dependent. Cells showing an error are filled red.
+pub(crate) fn dependency_dot(doc: &mut Document) -> String {
+    let key = |cell: &CellRef| (cell.row, cell.col);
+    let mut edges: BTreeSet<((usize, usize), (usize, usize))> = BTreeSet::new();
+    for (precedent, dependents) in &doc.dependents {
+        for dependent in dependents {
+            edges.insert((key(precedent), key(dependent)));
+        }
+    }
+    let nodes: BTreeSet<(usize, usize)> = edges.iter().flat_map(|&(a, b)| [a, b]).collect();
+    let name = |(row, col): (usize, usize)| CellRef::new(col, row).to_string();
+
+    let mut out = String::from("digraph dependencies {\n    rankdir=LR;\n    node [shape=box];\n");
+    for &node in &nodes {
+        let display = doc.get_cell_display(&CellRef::new(node.1, node.0));
+        let style = if error_type_code(&display).is_some() {
+            " style=filled fillcolor=\"#f4cccc\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "    {} [label=\"{}\"{}];",
+            name(node),
+            name(node),
+            style
+        );
+    }
+    for &(from, to) in &edges {
+        let _ = writeln!(out, "    {} -> {};", name(from), name(to));
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dependency_dot;
+    use crate::document::Document;
+    use gridline_engine::engine::CellRef;
+
+    #[test]
+    fn dependency_dot_lists_chain_edges_and_marks_errors() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "1").unwrap(); // A1
+        doc.set_cell_from_input(CellRef::new(0, 1), "=A1 + 1")
+            .unwrap(); // A2
+        doc.set_cell_from_input(CellRef::new(0, 2), "=A2 * 2")
+            .unwrap(); // A3
+        doc.set_cell_from_input(CellRef::new(1, 0), "=A3 / 0 + nope()")
+            .unwrap(); // B1
+        doc.set_cell_from_input(CellRef::new(2, 0), "5").unwrap(); // C1, unconnected
+
+        let dot = dependency_dot(&mut doc);
+        assert!(dot.starts_with("digraph dependencies {"));
+        assert!(dot.contains("    A1 -> A2;\n"));
+        assert!(dot.contains("    A2 -> A3;\n"));
+        assert!(dot.contains("    A3 -> B1;\n"));
+        assert!(!dot.contains("A2 -> A1"));
+        assert!(!dot.contains("C1"));
+        assert!(dot.contains("    A1 [label=\"A1\"];\n"));
+        assert!(dot.contains("    B1 [label=\"B1\" style=filled"));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+}
@@ -1,6 +1,6 @@
 //! Markdown export functionality
 
-use crate::document::Document;
+use crate::document::{Align, Document};
 use gridline_engine::engine::CellRef;
 use gridline_engine::plot::{PLOT_PREFIX, PlotData, PlotKind, PlotSpec, parse_plot_spec};
 use std::io::Write;
@@ -8,13 +8,31 @@ use std::path::Path;
 
 /// Write the grid to a markdown file
 pub fn write_markdown(path: &Path, doc: &mut Document) -> std::io::Result<()> {
+    write_markdown_range(path, doc, None)
+}
+
+/// Write the grid to a markdown file, limited to `range` (inclusive corners as
+/// `((col, row), (col, row))`) when given.
+pub fn write_markdown_range(
+    path: &Path,
+    doc: &mut Document,
+    range: Option<((usize, usize), (usize, usize))>,
+) -> std::io::Result<()> {
     // Find grid bounds (from populated cells + spilled values)
-    let (min_row, min_col, max_row, max_col) = find_grid_bounds(doc);
+    let (min_row, min_col, max_row, max_col) = match range {
+        Some(((c1, r1), (c2, r2))) => (r1, c1, r2, c2),
+        None => find_grid_bounds(doc),
+    };
+    let title = doc
+        .metadata()
+        .get("title")
+        .map(|t| escape_markdown(t))
+        .unwrap_or_else(|| "Sheet".to_string());
 
     if min_row > max_row {
         // Empty grid
         let mut file = std::fs::File::create(path)?;
-        writeln!(file, "# Sheet")?;
+        writeln!(file, "# {}", title)?;
         writeln!(file)?;
         writeln!(file, "*Empty spreadsheet*")?;
         return Ok(());
@@ -24,7 +42,7 @@ pub fn write_markdown(path: &Path, doc: &mut Document) -> std::io::Result<()> {
     let mut plots: Vec<PlotSpec> = Vec::new();
 
     // Write header
-    writeln!(file, "# Sheet")?;
+    writeln!(file, "# {}", title)?;
     writeln!(file)?;
 
     // Write markdown table header with column letters
@@ -34,10 +52,21 @@ pub fn write_markdown(path: &Path, doc: &mut Document) -> std::io::Result<()> {
     }
     writeln!(file)?;
 
-    // Write separator row
+    // Write separator row; a column whose aligned cells all agree takes
+    // that alignment (markdown tables can't align single cells)
     write!(file, "|---|")?;
-    for _ in min_col..=max_col {
-        write!(file, "---|")?;
+    for col in min_col..=max_col {
+        let mut aligns =
+            (min_row..=max_row).filter_map(|row| doc.alignment(&CellRef::new(col, row)));
+        let separator = match aligns.next() {
+            Some(first) if aligns.all(|a| a == first) => match first {
+                Align::Left => ":---",
+                Align::Center => ":---:",
+                Align::Right => "---:",
+            },
+            _ => "---",
+        };
+        write!(file, "{}|", separator)?;
     }
     writeln!(file)?;
 
@@ -308,7 +337,8 @@ fn render_scatter_chart<W: Write>(w: &mut W, data: &PlotData) -> std::io::Result
 #[cfg(test)]
 mod tests {
     use super::write_markdown;
-    use crate::document::Document;
+    use crate::document::{Align, Document};
+    use gridline_engine::engine::CellRef;
     use std::fs;
     use std::path::PathBuf;
 
@@ -343,4 +373,70 @@ mod tests {
         let normalize = |text: String| text.replace("\r\n", "\n");
         assert_eq!(normalize(actual), normalize(expected));
     }
+
+    #[test]
+    fn markdown_export_uses_metadata_title() {
+        let output_path = std::env::temp_dir().join(format!(
+            "gridline_title_export_{}_{}_{:?}.md",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(output_path.clone());
+
+        let mut doc = Document::new();
+        doc.set_metadata("title", "Budget").unwrap();
+        write_markdown(&output_path, &mut doc).unwrap();
+
+        let actual = fs::read_to_string(&output_path).unwrap();
+        assert!(actual.starts_with("# Budget\n"));
+    }
+
+    #[test]
+    fn markdown_export_aligns_columns() {
+        let output_path = std::env::temp_dir().join(format!(
+            "gridline_align_export_{}_{}_{:?}.md",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+            std::thread::current().id(),
+        ));
+        struct Cleanup(PathBuf);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                let _ = fs::remove_file(&self.0);
+            }
+        }
+        let _cleanup = Cleanup(output_path.clone());
+
+        let mut doc = Document::new();
+        for (col, row) in [(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1)] {
+            doc.set_cell_from_input(CellRef::new(col, row), "1")
+                .unwrap();
+        }
+        doc.set_alignment(
+            &CellRef::new(0, 0),
+            &CellRef::new(0, 1),
+            Some(Align::Center),
+        );
+        doc.set_alignment(&CellRef::new(1, 0), &CellRef::new(1, 0), Some(Align::Right));
+        // Mixed alignments in a column fall back to the default
+        doc.set_alignment(&CellRef::new(2, 0), &CellRef::new(2, 0), Some(Align::Left));
+        doc.set_alignment(&CellRef::new(2, 1), &CellRef::new(2, 1), Some(Align::Right));
+        write_markdown(&output_path, &mut doc).unwrap();
+
+        let actual = fs::read_to_string(&output_path).unwrap();
+        assert!(actual.contains("\n|---|:---:|---:|---|\n"), "{actual}");
+    }
 }
@@ -1,11 +1,47 @@
-//! Storage module for .grd file format and CSV/Markdown import/export.
+//! Storage module for .grd file format, CSV/Markdown import/export and DOT export.
 
 pub(crate) mod csv;
+mod dot;
 mod md;
 mod parser;
 mod writer;
 
-pub use csv::{parse_csv, write_csv};
-pub use md::write_markdown;
-pub use parser::parse_grd;
-pub use writer::write_grd;
+use crate::document::AlignedRange;
+use gridline_engine::engine::CellRef;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+pub use csv::{
+    CsvExportOptions, CsvImportOptions, parse_csv, parse_csv_with_options, parse_tsv, write_csv,
+    write_csv_with_options,
+};
+pub use dot::write_dependency_dot;
+pub use md::{write_markdown, write_markdown_range};
+pub use parser::{parse_grd, parse_grd_with_progress, parse_grd_with_settings};
+pub use writer::{write_grd, write_grd_with_settings};
+
+/// Whether `path` names a gzip-compressed sheet (`.grd.gz`).
+pub(crate) fn is_gzip_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.to_ascii_lowercase().ends_with(".grd.gz"))
+}
+
+/// Sheet-level settings stored in `@name: value` directive lines of a .grd file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SheetSettings {
+    /// Free-form document metadata such as title, author and created date
+    pub metadata: BTreeMap<String, String>,
+    /// Columns hidden from display (their data and references are unaffected)
+    pub hidden_cols: HashSet<usize>,
+    /// Merged display regions as (top-left, bottom-right)
+    pub merged_regions: Vec<(CellRef, CellRef)>,
+    /// Row groups as inclusive (start, end) rows
+    pub row_groups: Vec<(usize, usize)>,
+    /// Row groups saved collapsed
+    pub collapsed_row_groups: HashSet<(usize, usize)>,
+    /// Alignment rectangles, later ones taking precedence
+    pub alignments: Vec<AlignedRange>,
+    /// Named lambdas (name to `|args| body`) defined in the sheet
+    pub lambdas: BTreeMap<String, String>,
+}
@@ -1,8 +1,12 @@
 //! Parser for .grd file format
 
+use super::{SheetSettings, is_gzip_path};
+use crate::document::Align;
 use crate::error::{GridlineError, Result};
+use flate2::read::GzDecoder;
 use gridline_engine::engine::{Cell, CellRef, Grid};
 use std::fs;
+use std::io::{BufRead, BufReader, Read};
 use std::path::Path;
 
 const MAX_GRD_FILE_BYTES: u64 = 16 * 1024 * 1024; // 16 MiB
@@ -11,46 +15,138 @@ const MAX_GRD_CELLS: usize = 100_000;
 
 /// Parse a .grd file and return a Grid
 pub fn parse_grd(path: &Path) -> Result<Grid> {
+    parse_grd_with_settings(path).map(|(grid, _)| grid)
+}
+
+/// How many lines are parsed between progress callback invocations.
+const PROGRESS_INTERVAL: usize = 1_000;
+
+/// Parse a .grd file and return its Grid along with sheet-level settings
+pub fn parse_grd_with_settings(path: &Path) -> Result<(Grid, SheetSettings)> {
+    parse_grd_with_progress(path, |_| {})
+}
+
+/// Parse a .grd file, reporting the number of lines parsed so far to `progress`.
+///
+/// The callback fires every [`PROGRESS_INTERVAL`] lines and once more at the
+/// end with the total, so counts are strictly increasing. Lines are streamed
+/// from disk and cells inserted into the grid as they are read.
+/// Paths ending in `.grd.gz` are decompressed transparently.
+pub fn parse_grd_with_progress(
+    path: &Path,
+    mut progress: impl FnMut(usize),
+) -> Result<(Grid, SheetSettings)> {
     let meta = fs::metadata(path)?;
     if meta.len() > MAX_GRD_FILE_BYTES {
-        return Err(GridlineError::Io(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!(
-                "Refusing to read {}: .grd file too large ({} bytes, max {})",
-                path.display(),
-                meta.len(),
-                MAX_GRD_FILE_BYTES
-            ),
-        )));
+        return Err(too_large(path, meta.len()));
     }
-    let content = fs::read_to_string(path)?;
-    parse_grd_content(&content)
+    let file = fs::File::open(path)?;
+    // Compressed sheets are capped on their decompressed size as well.
+    let reader: Box<dyn BufRead> = if is_gzip_path(path) {
+        Box::new(BufReader::new(
+            GzDecoder::new(file).take(MAX_GRD_FILE_BYTES + 1),
+        ))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    let mut parser = GrdParser::new();
+    let mut bytes: u64 = 0;
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line?;
+        bytes += line.len() as u64 + 1;
+        if bytes > MAX_GRD_FILE_BYTES {
+            return Err(too_large(path, bytes));
+        }
+        parser.parse_line(line_num + 1, &line)?;
+        parser.report(line_num + 1, &mut progress);
+    }
+    Ok(parser.finish(&mut progress))
+}
+
+fn too_large(path: &Path, len: u64) -> GridlineError {
+    GridlineError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!(
+            "Refusing to read {}: .grd file too large ({} bytes, max {})",
+            path.display(),
+            len,
+            MAX_GRD_FILE_BYTES
+        ),
+    ))
 }
 
 /// Parse .grd content from a string
+#[cfg(test)]
 pub fn parse_grd_content(content: &str) -> Result<Grid> {
-    let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
-    let mut parsed_cells = 0usize;
+    parse_grd_content_with_settings(content).map(|(grid, _)| grid)
+}
+
+/// Parse .grd content from a string, including `@name: value` directives
+#[cfg(test)]
+pub fn parse_grd_content_with_settings(content: &str) -> Result<(Grid, SheetSettings)> {
+    parse_grd_content_with_progress(content, |_| {})
+}
 
+/// Parse .grd content from a string, reporting lines parsed to `progress`.
+#[cfg(test)]
+pub fn parse_grd_content_with_progress(
+    content: &str,
+    mut progress: impl FnMut(usize),
+) -> Result<(Grid, SheetSettings)> {
+    let mut parser = GrdParser::new();
     for (line_num, line) in content.lines().enumerate() {
-        if line_num + 1 > MAX_GRD_LINES {
+        parser.parse_line(line_num + 1, line)?;
+        parser.report(line_num + 1, &mut progress);
+    }
+    Ok(parser.finish(&mut progress))
+}
+
+/// Incremental .grd parser: cells go straight into the grid as each line is read.
+struct GrdParser {
+    grid: Grid,
+    settings: SheetSettings,
+    parsed_cells: usize,
+    lines: usize,
+    last_reported: usize,
+}
+
+impl GrdParser {
+    fn new() -> Self {
+        Self {
+            grid: std::sync::Arc::new(dashmap::DashMap::new()),
+            settings: SheetSettings::default(),
+            parsed_cells: 0,
+            lines: 0,
+            last_reported: 0,
+        }
+    }
+
+    /// Parse one line (`line_num` is 1-based).
+    fn parse_line(&mut self, line_num: usize, line: &str) -> Result<()> {
+        if line_num > MAX_GRD_LINES {
             return Err(GridlineError::Parse {
-                line: line_num + 1,
+                line: line_num,
                 message: format!("Too many lines in .grd file (max {})", MAX_GRD_LINES),
             });
         }
+        self.lines = line_num;
 
         let line = line.trim();
 
         // Skip empty lines and comments
         if line.is_empty() || line.starts_with('#') {
-            continue;
+            return Ok(());
+        }
+
+        // Sheet-level directive: "@name: value"
+        if let Some(directive) = line.strip_prefix('@') {
+            return parse_directive(directive, line_num, &mut self.settings);
         }
 
         // Parse "CELLREF: VALUE" format
         let Some((cell_ref_str, value_str)) = line.split_once(':') else {
             return Err(GridlineError::Parse {
-                line: line_num + 1,
+                line: line_num,
                 message: "Expected 'CELLREF: VALUE' format".to_string(),
             });
         };
@@ -59,23 +155,150 @@ pub fn parse_grd_content(content: &str) -> Result<Grid> {
         let value_str = value_str.trim();
 
         let cell_ref = CellRef::from_str(cell_ref_str).ok_or_else(|| GridlineError::Parse {
-            line: line_num + 1,
+            line: line_num,
             message: format!("Invalid cell reference: {}", cell_ref_str),
         })?;
 
-        parsed_cells += 1;
-        if parsed_cells > MAX_GRD_CELLS {
+        self.parsed_cells += 1;
+        if self.parsed_cells > MAX_GRD_CELLS {
             return Err(GridlineError::Parse {
-                line: line_num + 1,
+                line: line_num,
                 message: format!("Too many cells in .grd file (max {})", MAX_GRD_CELLS),
             });
         }
 
-        let cell = parse_cell_value(value_str, line_num + 1)?;
-        grid.insert(cell_ref, cell);
+        let cell = parse_cell_value(value_str, line_num)?;
+        self.grid.insert(cell_ref, cell);
+        Ok(())
+    }
+
+    fn report(&mut self, lines: usize, progress: &mut impl FnMut(usize)) {
+        if lines.is_multiple_of(PROGRESS_INTERVAL) {
+            self.last_reported = lines;
+            progress(lines);
+        }
+    }
+
+    fn finish(self, progress: &mut impl FnMut(usize)) -> (Grid, SheetSettings) {
+        if self.lines > self.last_reported {
+            progress(self.lines);
+        }
+        (self.grid, self.settings)
+    }
+}
+
+/// Parse a directive line (without the leading '@') into sheet settings
+fn parse_directive(directive: &str, line_num: usize, settings: &mut SheetSettings) -> Result<()> {
+    let Some((name, value)) = directive.split_once(':') else {
+        return Err(GridlineError::Parse {
+            line: line_num,
+            message: "Expected '@NAME: VALUE' format".to_string(),
+        });
+    };
+
+    let name = name.trim();
+    if let Some(key) = name.strip_prefix("meta ") {
+        let value = value.trim();
+        if !(value.len() >= 2 && value.starts_with('"') && value.ends_with('"')) {
+            return Err(GridlineError::Parse {
+                line: line_num,
+                message: "Metadata values must be quoted".to_string(),
+            });
+        }
+        let value = unescape_grd_text(&value[1..value.len() - 1]);
+        settings.metadata.insert(key.trim().to_string(), value);
+        return Ok(());
+    }
+
+    if let Some(lambda_name) = name.strip_prefix("lambda ") {
+        settings
+            .lambdas
+            .insert(lambda_name.trim().to_string(), value.trim().to_string());
+        return Ok(());
     }
 
-    Ok(grid)
+    if let Some(align_name) = name.strip_prefix("align ") {
+        let align = match align_name.trim() {
+            "clear" => None,
+            name => Some(Align::parse(name).ok_or_else(|| GridlineError::Parse {
+                line: line_num,
+                message: format!("Invalid alignment: {}", name),
+            })?),
+        };
+        for range in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (a, b) = range.split_once(':').unwrap_or((range, range));
+            let (top_left, bottom_right) = CellRef::from_str(a)
+                .zip(CellRef::from_str(b))
+                .filter(|(a, b)| a.col <= b.col && a.row <= b.row)
+                .ok_or_else(|| GridlineError::Parse {
+                    line: line_num,
+                    message: format!("Invalid alignment range: {}", range),
+                })?;
+            settings.alignments.push((top_left, bottom_right, align));
+        }
+        return Ok(());
+    }
+
+    match name {
+        "hidden_cols" => {
+            for letters in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let col = CellRef::from_str(&format!("{}1", letters))
+                    .filter(|_| letters.chars().all(|c| c.is_ascii_alphabetic()))
+                    .map(|cell_ref| cell_ref.col)
+                    .ok_or_else(|| GridlineError::Parse {
+                        line: line_num,
+                        message: format!("Invalid column: {}", letters),
+                    })?;
+                settings.hidden_cols.insert(col);
+            }
+            Ok(())
+        }
+        "merged" => {
+            for range in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let region = range
+                    .split_once(':')
+                    .and_then(|(a, b)| Some((CellRef::from_str(a)?, CellRef::from_str(b)?)))
+                    .filter(|(a, b)| a.col <= b.col && a.row <= b.row && a != b)
+                    .ok_or_else(|| GridlineError::Parse {
+                        line: line_num,
+                        message: format!("Invalid merged range: {}", range),
+                    })?;
+                settings.merged_regions.push(region);
+            }
+            Ok(())
+        }
+        "row_groups" => {
+            for entry in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let (span, collapsed) = match entry.strip_suffix("collapsed") {
+                    Some(span) => (span.trim_end(), true),
+                    None => (entry, false),
+                };
+                let group = span
+                    .split_once('-')
+                    .and_then(|(a, b)| {
+                        Some((
+                            a.trim().parse::<usize>().ok()?,
+                            b.trim().parse::<usize>().ok()?,
+                        ))
+                    })
+                    .filter(|&(start, end)| start >= 1 && start < end)
+                    .map(|(start, end)| (start - 1, end - 1))
+                    .ok_or_else(|| GridlineError::Parse {
+                        line: line_num,
+                        message: format!("Invalid row group: {}", entry),
+                    })?;
+                settings.row_groups.push(group);
+                if collapsed {
+                    settings.collapsed_row_groups.insert(group);
+                }
+            }
+            Ok(())
+        }
+        other => Err(GridlineError::Parse {
+            line: line_num,
+            message: format!("Unknown directive: @{}", other),
+        }),
+    }
 }
 
 /// Parse a cell value string into a Cell
@@ -103,6 +326,14 @@ fn parse_cell_value(value: &str, line_num: usize) -> Result<Cell> {
         return Ok(Cell::new_number(n));
     }
 
+    // Percent written as typed, e.g. "50%"
+    if let Some(n) = value
+        .strip_suffix('%')
+        .and_then(|digits| digits.trim_end().parse::<f64>().ok())
+    {
+        return Ok(Cell::new_percent(n / 100.0));
+    }
+
     Err(GridlineError::Parse {
         line: line_num,
         message: format!("Invalid value: {}. Use quotes for text.", value),
@@ -269,4 +500,72 @@ B1: 100
             other => panic!("expected parse error, got {other:?}"),
         }
     }
+
+    #[test]
+    fn test_parse_hidden_cols_directive() {
+        let content = "@hidden_cols: B, AA\nA1: 1";
+        let (grid, settings) = parse_grd_content_with_settings(content).unwrap();
+        assert_eq!(grid.len(), 1);
+        assert!(settings.hidden_cols.contains(&1));
+        assert!(settings.hidden_cols.contains(&26));
+        assert_eq!(settings.hidden_cols.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_directive() {
+        assert!(parse_grd_content("@bogus: 1").is_err());
+        assert!(parse_grd_content("@hidden_cols: B2").is_err());
+        assert!(parse_grd_content("@merged: A1").is_err());
+        assert!(parse_grd_content("@merged: C3:A1").is_err());
+    }
+
+    #[test]
+    fn test_parse_meta_directives() {
+        let content = "@meta title: \"My \\\"Sheet\\\"\"\n@meta custom-key: \"kept\"\nA1: 1";
+        let (_, settings) = parse_grd_content_with_settings(content).unwrap();
+        assert_eq!(
+            settings.metadata.get("title").map(String::as_str),
+            Some("My \"Sheet\"")
+        );
+        assert_eq!(
+            settings.metadata.get("custom-key").map(String::as_str),
+            Some("kept")
+        );
+        assert!(parse_grd_content("@meta title: unquoted").is_err());
+    }
+
+    #[test]
+    fn test_parse_progress_counts_increase_monotonically() {
+        let content: String = (1..=2_500)
+            .map(|row| format!("A{}: {}\n", row, row))
+            .collect();
+        let mut counts = Vec::new();
+        let (grid, _) =
+            parse_grd_content_with_progress(&content, |lines| counts.push(lines)).unwrap();
+
+        assert_eq!(grid.len(), 2_500);
+        assert_eq!(counts, vec![1_000, 2_000, 2_500]);
+        assert!(counts.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_parse_grd_with_progress_reports_from_file() {
+        let path = std::env::temp_dir().join(format!(
+            "gridline_parse_grd_progress_{}_{:?}.grd",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        let content: String = (1..=1_200)
+            .map(|row| format!("B{}: {}\n", row, row))
+            .collect();
+        std::fs::write(&path, content).unwrap();
+
+        let mut counts = Vec::new();
+        let result = parse_grd_with_progress(&path, |lines| counts.push(lines));
+        let _ = std::fs::remove_file(&path);
+
+        let (grid, _) = result.unwrap();
+        assert_eq!(grid.len(), 1_200);
+        assert_eq!(counts, vec![1_000, 1_200]);
+    }
 }
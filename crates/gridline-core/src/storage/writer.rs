@@ -1,21 +1,101 @@
 //! Writer for .grd file format
 
+use super::{SheetSettings, is_gzip_path};
+use crate::document::Align;
 use crate::error::Result;
-use gridline_engine::engine::{CellType, Grid};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use gridline_engine::engine::{CellFormat, CellRef, CellType, Grid};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 
 /// Write a Grid to a .grd file
 pub fn write_grd(path: &Path, grid: &Grid) -> Result<()> {
-    let content = write_grd_content(grid);
-    fs::write(path, content)?;
+    write_grd_with_settings(path, grid, &SheetSettings::default())
+}
+
+/// Write a Grid and its sheet-level settings to a .grd file
+///
+/// Paths ending in `.grd.gz` are gzip-compressed.
+pub fn write_grd_with_settings(path: &Path, grid: &Grid, settings: &SheetSettings) -> Result<()> {
+    let content = write_grd_content_with_settings(grid, settings);
+    if is_gzip_path(path) {
+        let mut encoder = GzEncoder::new(fs::File::create(path)?, Compression::default());
+        encoder.write_all(content.as_bytes())?;
+        encoder.finish()?;
+    } else {
+        fs::write(path, content)?;
+    }
     Ok(())
 }
 
 /// Write a Grid to a .grd format string
+#[cfg(test)]
 pub fn write_grd_content(grid: &Grid) -> String {
+    write_grd_content_with_settings(grid, &SheetSettings::default())
+}
+
+/// Write a Grid and its sheet-level settings to a .grd format string
+pub fn write_grd_content_with_settings(grid: &Grid, settings: &SheetSettings) -> String {
     let mut lines = vec!["# Gridline Spreadsheet".to_string()];
 
+    for (key, value) in &settings.metadata {
+        lines.push(format!("@meta {}: \"{}\"", key, escape_grd_text(value)));
+    }
+
+    if !settings.hidden_cols.is_empty() {
+        let mut cols: Vec<usize> = settings.hidden_cols.iter().copied().collect();
+        cols.sort_unstable();
+        let letters: Vec<String> = cols.into_iter().map(CellRef::col_to_letters).collect();
+        lines.push(format!("@hidden_cols: {}", letters.join(", ")));
+    }
+
+    if !settings.merged_regions.is_empty() {
+        let regions: Vec<String> = settings
+            .merged_regions
+            .iter()
+            .map(|(top_left, bottom_right)| format!("{}:{}", top_left, bottom_right))
+            .collect();
+        lines.push(format!("@merged: {}", regions.join(", ")));
+    }
+
+    if !settings.row_groups.is_empty() {
+        let groups: Vec<String> = settings
+            .row_groups
+            .iter()
+            .map(|group| {
+                let suffix = if settings.collapsed_row_groups.contains(group) {
+                    " collapsed"
+                } else {
+                    ""
+                };
+                format!("{}-{}{}", group.0 + 1, group.1 + 1, suffix)
+            })
+            .collect();
+        lines.push(format!("@row_groups: {}", groups.join(", ")));
+    }
+
+    for (name, lambda) in &settings.lambdas {
+        lines.push(format!("@lambda {}: {}", name, lambda));
+    }
+
+    // Order matters (later rectangles win), so only neighbours are joined
+    for group in settings.alignments.chunk_by(|a, b| a.2 == b.2) {
+        let ranges: Vec<String> = group
+            .iter()
+            .map(|(top_left, bottom_right, _)| {
+                if top_left == bottom_right {
+                    top_left.to_string()
+                } else {
+                    format!("{}:{}", top_left, bottom_right)
+                }
+            })
+            .collect();
+        let name = group[0].2.map_or("clear", Align::name);
+        lines.push(format!("@align {}: {}", name, ranges.join(", ")));
+    }
+
     // Collect and sort cells by position for consistent output
     let mut cells: Vec<_> = grid.iter().collect();
     cells.sort_by(|a, b| {
@@ -30,6 +110,8 @@ pub fn write_grd_content(grid: &Grid) -> String {
 
         let value_str = match &cell.contents {
             CellType::Empty => continue, // Skip empty cells
+            // Percent cells are written as typed (`50%`) so the format survives a reload.
+            CellType::Number(_) if cell.format == CellFormat::Percent => cell.to_input_string(),
             CellType::Number(n) => n.to_string(),
             CellType::Text(s) => format!("\"{}\"", escape_grd_text(s)),
             CellType::Script(s) => format!("={}", s),
@@ -105,4 +187,144 @@ mod tests {
         assert!(lines[2].starts_with("B1"));
         assert!(lines[3].starts_with("B2"));
     }
+
+    #[test]
+    fn test_write_hidden_cols_directive() {
+        let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(1.0));
+        let mut settings = SheetSettings::default();
+        settings.hidden_cols.insert(3);
+        settings.hidden_cols.insert(1);
+        let content = write_grd_content_with_settings(&grid, &settings);
+        assert!(content.contains("@hidden_cols: B, D\n"));
+    }
+
+    #[test]
+    fn test_merged_directive_round_trips() {
+        let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_text("Report"));
+        let mut settings = SheetSettings::default();
+        settings
+            .merged_regions
+            .push((CellRef::new(0, 0), CellRef::new(2, 0)));
+        settings
+            .merged_regions
+            .push((CellRef::new(1, 3), CellRef::new(2, 5)));
+        let content = write_grd_content_with_settings(&grid, &settings);
+        assert!(content.contains("@merged: A1:C1, B4:C6\n"));
+
+        let (_, parsed) =
+            crate::storage::parser::parse_grd_content_with_settings(&content).unwrap();
+        assert_eq!(parsed, settings);
+    }
+
+    #[test]
+    fn test_row_groups_directive_round_trips() {
+        let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
+        let settings = SheetSettings {
+            row_groups: vec![(1, 4), (7, 9)],
+            collapsed_row_groups: [(7, 9)].into_iter().collect(),
+            ..SheetSettings::default()
+        };
+        let content = write_grd_content_with_settings(&grid, &settings);
+        assert!(content.contains("@row_groups: 2-5, 8-10 collapsed\n"));
+
+        let (_, parsed) =
+            crate::storage::parser::parse_grd_content_with_settings(&content).unwrap();
+        assert_eq!(parsed, settings);
+        assert!(
+            crate::storage::parser::parse_grd_content_with_settings("@row_groups: 3-3").is_err()
+        );
+    }
+
+    #[test]
+    fn test_align_directive_round_trips() {
+        let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_text("Total"));
+        let settings = SheetSettings {
+            alignments: vec![
+                (CellRef::new(0, 0), CellRef::new(0, 0), Some(Align::Center)),
+                (CellRef::new(1, 0), CellRef::new(1, 9), Some(Align::Center)),
+                (CellRef::new(2, 3), CellRef::new(2, 3), Some(Align::Right)),
+                (CellRef::new(1, 2), CellRef::new(1, 2), None),
+            ],
+            ..SheetSettings::default()
+        };
+        let content = write_grd_content_with_settings(&grid, &settings);
+        assert!(content.contains("@align center: A1, B1:B10\n@align right: C4\n"));
+        assert!(content.contains("@align clear: B3\n"));
+
+        let (_, parsed) =
+            crate::storage::parser::parse_grd_content_with_settings(&content).unwrap();
+        assert_eq!(parsed, settings);
+        assert!(crate::storage::parser::parse_grd_content("@align middle: A1").is_err());
+    }
+
+    #[test]
+    fn test_write_percent_round_trips() {
+        let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::from_input("12.5%"));
+        let content = write_grd_content(&grid);
+        assert!(content.contains("A1: 12.5%"));
+
+        let parsed = crate::storage::parser::parse_grd_content(&content).unwrap();
+        let cell = parsed.get(&CellRef::new(0, 0)).unwrap();
+        assert_eq!(cell.format, CellFormat::Percent);
+        assert!(matches!(cell.contents, CellType::Number(n) if n == 0.125));
+    }
+
+    #[test]
+    fn test_absolute_references_round_trip() {
+        let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
+        grid.insert(CellRef::new(1, 0), Cell::from_input("=$A$1 + A$2 + $A3"));
+        let content = write_grd_content(&grid);
+        assert!(content.contains("B1: =$A$1 + A$2 + $A3"));
+
+        let parsed = crate::storage::parser::parse_grd_content(&content).unwrap();
+        let cell = parsed.get(&CellRef::new(1, 0)).unwrap();
+        assert!(matches!(&cell.contents, CellType::Script(s) if s == "$A$1 + A$2 + $A3"));
+    }
+
+    #[test]
+    fn test_grd_gz_round_trip_is_compressed() {
+        let dir = std::env::temp_dir();
+        let stem = format!(
+            "gridline_grd_gz_{}_{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        );
+        let gz_path = dir.join(format!("{stem}.grd.gz"));
+        let plain_path = dir.join(format!("{stem}.grd"));
+        struct Cleanup(Vec<std::path::PathBuf>);
+        impl Drop for Cleanup {
+            fn drop(&mut self) {
+                for path in &self.0 {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+        }
+        let _cleanup = Cleanup(vec![gz_path.clone(), plain_path.clone()]);
+
+        let grid: Grid = std::sync::Arc::new(dashmap::DashMap::new());
+        for row in 0..200 {
+            grid.insert(CellRef::new(0, row), Cell::new_text("repeated value"));
+            grid.insert(CellRef::new(1, row), Cell::new_script("A1 + 1"));
+        }
+        let mut settings = SheetSettings::default();
+        settings.hidden_cols.insert(1);
+        write_grd_with_settings(&gz_path, &grid, &settings).unwrap();
+        write_grd_with_settings(&plain_path, &grid, &settings).unwrap();
+
+        let gz_len = std::fs::metadata(&gz_path).unwrap().len();
+        let plain_len = std::fs::metadata(&plain_path).unwrap().len();
+        assert!(gz_len < plain_len, "{gz_len} >= {plain_len}");
+
+        let (parsed, parsed_settings) = crate::storage::parse_grd_with_settings(&gz_path).unwrap();
+        assert_eq!(parsed_settings, settings);
+        assert_eq!(parsed.len(), grid.len());
+        for entry in grid.iter() {
+            let cell = parsed.get(entry.key()).unwrap();
+            assert_eq!(cell.to_input_string(), entry.value().to_input_string());
+        }
+    }
 }
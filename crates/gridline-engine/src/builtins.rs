@@ -5,14 +5,19 @@
 //! - Built-ins rewrite to ALLCAPS Rhai function names (e.g. `SUM_RANGE`).
 //! - If you add a new built-in range function, update `RANGE_BUILTINS` and
 //!   register its implementation in `register_builtins`.
+//! - Functions taking two ranges (e.g. `AVERAGEIFS(B1:B5, A1:A5, ">2")`) go in
+//!   `PAIRED_RANGE_BUILTINS` and receive both ranges' coordinates.
 
-use crate::engine::{Cell, CellRef, CellType, Grid, ValueCache, parse_range, preprocess_script};
+use crate::engine::{
+    Cell, CellRef, CellType, DIV_ZERO, ErrorMap, Grid, SpillMap, ValueCache, error_type_code,
+    parse_range, preprocess_script,
+};
 use crate::plot::{PlotKind, PlotSpec, format_plot_spec};
 use rand::Rng;
 use regex::Regex;
 use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, NativeCallContext, Position};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex, OnceLock};
 
 pub struct RangeBuiltin {
@@ -68,15 +73,35 @@ pub const RANGE_BUILTINS: &[RangeBuiltin] = &[
         rhai_name: "VEC_RANGE",
         description: "Convert a range to an array",
     },
+    RangeBuiltin {
+        sheet_name: "FILTER",
+        rhai_name: "FILTER_RANGE",
+        description: "Rows of a range whose first-column value matches, as a 2D spill",
+    },
+    RangeBuiltin {
+        sheet_name: "AVERAGEIF",
+        rhai_name: "AVERAGEIF_RANGE",
+        description: "Average values where predicate or criterion matches",
+    },
+    RangeBuiltin {
+        sheet_name: "MAPRANGE",
+        rhai_name: "MAPRANGE_RANGE",
+        description: "Apply a function to each cell value, returning an array",
+    },
+    RangeBuiltin {
+        sheet_name: "REDUCE",
+        rhai_name: "REDUCE_RANGE",
+        description: "Fold cell values into a single value with a function",
+    },
     RangeBuiltin {
         sheet_name: "SUMIF",
         rhai_name: "SUMIF_RANGE",
-        description: "Sum values where predicate is true",
+        description: "Sum values where predicate or criterion matches",
     },
     RangeBuiltin {
         sheet_name: "COUNTIF",
         rhai_name: "COUNTIF_RANGE",
-        description: "Count cells where predicate is true",
+        description: "Count cells where predicate or criterion matches",
     },
     RangeBuiltin {
         sheet_name: "PRODUCT",
@@ -93,6 +118,36 @@ pub const RANGE_BUILTINS: &[RangeBuiltin] = &[
         rhai_name: "GEOMEAN_RANGE",
         description: "Geometric mean of numeric values in a cell range",
     },
+    RangeBuiltin {
+        sheet_name: "STDEV",
+        rhai_name: "STDEV_RANGE",
+        description: "Sample standard deviation of values in a cell range",
+    },
+    RangeBuiltin {
+        sheet_name: "STDEVP",
+        rhai_name: "STDEVP_RANGE",
+        description: "Population standard deviation of values in a cell range",
+    },
+    RangeBuiltin {
+        sheet_name: "VAR",
+        rhai_name: "VAR_RANGE",
+        description: "Sample variance of values in a cell range",
+    },
+    RangeBuiltin {
+        sheet_name: "VARP",
+        rhai_name: "VARP_RANGE",
+        description: "Population variance of values in a cell range",
+    },
+    RangeBuiltin {
+        sheet_name: "TRIMMEAN",
+        rhai_name: "TRIMMEAN_RANGE",
+        description: "Mean after discarding a fraction of the lowest and highest values",
+    },
+    RangeBuiltin {
+        sheet_name: "PERCENTRANK",
+        rhai_name: "PERCENTRANK_RANGE",
+        description: "Percentile rank (0 to 1) of a value within a cell range",
+    },
     RangeBuiltin {
         sheet_name: "CONCAT",
         rhai_name: "CONCAT_RANGE",
@@ -102,9 +157,12 @@ pub const RANGE_BUILTINS: &[RangeBuiltin] = &[
 
 /// Regex that matches built-in range calls like `SUM(A1:B5)`.
 ///
+/// Endpoints may carry `$` markers (`SUM($A$1:$A5)`), so reference shifting
+/// sees the range whole; evaluation strips the markers first.
+///
 /// Captures:
 /// - group 1: function name (e.g. `SUM`)
-/// - group 2: start cell ref (e.g. `A1`)
+/// - group 2: start cell ref (e.g. `A1` or `$A$1`)
 /// - group 3: end cell ref (e.g. `B5`)
 pub fn range_fn_re() -> &'static Regex {
     static RE: OnceLock<Regex> = OnceLock::new();
@@ -115,7 +173,7 @@ pub fn range_fn_re() -> &'static Regex {
             .collect::<Vec<_>>()
             .join("|");
         Regex::new(&format!(
-            r"\b({})\(([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)(\s*,[^)]*)?\)",
+            r"\b({})\((\$?[A-Za-z]+\$?[0-9]+):(\$?[A-Za-z]+\$?[0-9]+)(\s*,[^)]*)?\)",
             names
         ))
         .expect("built-in range regex must compile")
@@ -129,6 +187,129 @@ pub fn range_rhai_name(sheet_name: &str) -> Option<&'static str> {
         .map(|b| b.rhai_name)
 }
 
+/// Built-ins whose first two arguments are ranges, e.g. `AVERAGEIFS(B1:B5, A1:A5, ">2")`.
+pub const PAIRED_RANGE_BUILTINS: &[RangeBuiltin] = &[
+    RangeBuiltin {
+        sheet_name: "AVERAGEIFS",
+        rhai_name: "AVERAGEIFS_RANGES",
+        description: "Average one range where a paired range matches a predicate or criterion",
+    },
+    RangeBuiltin {
+        sheet_name: "WEIGHTEDMEDIAN",
+        rhai_name: "WEIGHTEDMEDIAN_RANGES",
+        description: "Median of a range weighted by a paired range",
+    },
+    RangeBuiltin {
+        sheet_name: "SLOPE",
+        rhai_name: "SLOPE_RANGES",
+        description: "Least-squares slope of known y values against known x values",
+    },
+    RangeBuiltin {
+        sheet_name: "INTERCEPT",
+        rhai_name: "INTERCEPT_RANGES",
+        description: "Least-squares intercept of known y values against known x values",
+    },
+];
+
+/// Regex that matches paired range calls like `AVERAGEIFS(B1:B5, A1:A5, ...)`.
+///
+/// Captures:
+/// - group 1: function name (e.g. `AVERAGEIFS`)
+/// - groups 2-3: first range start/end
+/// - groups 4-5: second range start/end
+/// - group 6: remaining arguments, including the leading comma
+pub fn paired_range_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        let names = PAIRED_RANGE_BUILTINS
+            .iter()
+            .map(|b| b.sheet_name)
+            .collect::<Vec<_>>()
+            .join("|");
+        Regex::new(&format!(
+            r"\b({})\(\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*,\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)(\s*,[^)]*)?\)",
+            names
+        ))
+        .expect("paired range regex must compile")
+    })
+}
+
+pub fn paired_range_rhai_name(sheet_name: &str) -> Option<&'static str> {
+    PAIRED_RANGE_BUILTINS
+        .iter()
+        .find(|b| b.sheet_name == sheet_name)
+        .map(|b| b.rhai_name)
+}
+
+/// Built-ins whose last argument is a range, e.g. `ZSCORE(@B1, A1:A100)`.
+pub const TRAILING_RANGE_BUILTINS: &[RangeBuiltin] = &[
+    RangeBuiltin {
+        sheet_name: "ZSCORE",
+        rhai_name: "ZSCORE_RANGE",
+        description: "Standard score of a value against a cell range",
+    },
+    RangeBuiltin {
+        sheet_name: "NETWORKDAYS",
+        rhai_name: "NETWORKDAYS_RANGE",
+        description: "Weekdays between two dates, skipping holidays in a range",
+    },
+    RangeBuiltin {
+        sheet_name: "WORKDAY",
+        rhai_name: "WORKDAY_RANGE",
+        description: "Date a number of weekdays away, skipping holidays in a range",
+    },
+];
+
+/// Regex that matches trailing range calls like `ZSCORE(@B1, A1:A100)`.
+///
+/// Captures:
+/// - group 1: function name (e.g. `ZSCORE`)
+/// - group 2: leading arguments (e.g. `@B1`)
+/// - group 3: range start (e.g. `A1`)
+/// - group 4: range end (e.g. `A100`)
+pub fn trailing_range_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        let names = TRAILING_RANGE_BUILTINS
+            .iter()
+            .map(|b| b.sheet_name)
+            .collect::<Vec<_>>()
+            .join("|");
+        Regex::new(&format!(
+            r"\b({})\((.+?),\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*\)",
+            names
+        ))
+        .expect("trailing range regex must compile")
+    })
+}
+
+pub fn trailing_range_rhai_name(sheet_name: &str) -> Option<&'static str> {
+    TRAILING_RANGE_BUILTINS
+        .iter()
+        .find(|b| b.sheet_name == sheet_name)
+        .map(|b| b.rhai_name)
+}
+
+/// Whether `name` (any case) is a built-in function, either as registered
+/// with the engine or as a spreadsheet name rewritten to one (e.g. `SUM`).
+///
+/// Registered names are read from this file's `register_fn` calls, so new
+/// built-ins are picked up without keeping a separate list.
+pub fn is_builtin_function(name: &str) -> bool {
+    static NAMES: OnceLock<HashSet<String>> = OnceLock::new();
+    let names = NAMES.get_or_init(|| {
+        let re = Regex::new(r#"register_fn\(\s*"([A-Za-z_][A-Za-z0-9_]*)""#)
+            .expect("register_fn regex must compile");
+        re.captures_iter(include_str!("builtins.rs"))
+            .map(|caps| caps[1].to_ascii_uppercase())
+            .collect()
+    });
+    let name = name.to_ascii_uppercase();
+    ["", "_RANGE", "_RANGES"]
+        .iter()
+        .any(|suffix| names.contains(&format!("{}{}", name, suffix)))
+}
+
 /// Regex for `LOOKUP(value_expr, search_start:search_end, return_start:return_end)`.
 ///
 /// Captures:
@@ -147,6 +328,97 @@ pub fn lookup_fn_re() -> &'static Regex {
     })
 }
 
+/// Regex for `VLOOKUP(value_expr, table_start:table_end, col_index[, exact])`.
+///
+/// The column index and match flag are simple expressions without commas or
+/// parentheses (e.g. `2`, `false` or `@E1`).
+///
+/// Captures:
+/// - group 1: value expression (e.g. `"apple"` or `@A1`)
+/// - group 2: table range start (e.g. `A1`)
+/// - group 3: table range end (e.g. `C10`)
+/// - group 4: 1-based column index expression
+/// - group 5: exact-match flag expression (optional)
+pub fn vlookup_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"\bVLOOKUP\((.+?),\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*,\s*([^,()]+?)(?:\s*,\s*([^,()]+?))?\s*\)"
+        )
+        .expect("VLOOKUP regex must compile")
+    })
+}
+
+/// Regex for `FORECAST(x_expr, ys_start:ys_end, xs_start:xs_end)`.
+///
+/// Captures:
+/// - group 1: x expression (e.g. `6` or `@A7`)
+/// - group 2: known y range start (e.g. `B1`)
+/// - group 3: known y range end (e.g. `B5`)
+/// - group 4: known x range start (e.g. `A1`)
+/// - group 5: known x range end (e.g. `A5`)
+pub fn forecast_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"\bFORECAST\((.+?),\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*,\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*\)"
+        )
+        .expect("FORECAST regex must compile")
+    })
+}
+
+/// Regex for `DSUM(data_start:data_end, field, criteria_start:criteria_end)`.
+///
+/// Captures:
+/// - group 1: data range start (e.g. `A1`)
+/// - group 2: data range end (e.g. `C10`)
+/// - group 3: field expression (e.g. `"Sales"` or `3`)
+/// - group 4: criteria range start (e.g. `E1`)
+/// - group 5: criteria range end (e.g. `E2`)
+pub fn dsum_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"\bDSUM\(\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*,(.+?),\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*\)"
+        )
+        .expect("DSUM regex must compile")
+    })
+}
+
+/// Regex for `COUNTIFS(range1, crit1, [range2, crit2, [range3, crit3]])`.
+///
+/// Criteria are string literals or simple expressions without commas or
+/// parentheses (e.g. `">5"` or `@E1`).
+///
+/// Captures, for pair `n` in 0..3 (later pairs are optional):
+/// - groups `3n+1`, `3n+2`: range start/end
+/// - group `3n+3`: criterion expression
+pub fn countifs_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        let pair = r#"([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*,\s*("[^"]*"|[^,()"]+?)"#;
+        Regex::new(&format!(
+            r"\bCOUNTIFS\(\s*{pair}(?:\s*,\s*{pair})?(?:\s*,\s*{pair})?\s*\)"
+        ))
+        .expect("COUNTIFS regex must compile")
+    })
+}
+
+/// Regex for `SUMPRODUCT(range1, [range2, [range3, [range4]]])`.
+///
+/// Captures, for range `n` in 0..4 (later ranges are optional):
+/// - groups `2n+1`, `2n+2`: range start/end
+pub fn sumproduct_fn_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        let range = r"([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)";
+        Regex::new(&format!(
+            r"\bSUMPRODUCT\(\s*{range}(?:\s*,\s*{range})?(?:\s*,\s*{range})?(?:\s*,\s*{range})?\s*\)"
+        ))
+        .expect("SUMPRODUCT regex must compile")
+    })
+}
+
 fn eval_script_cell(ctx: &NativeCallContext, script: &str) -> Option<f64> {
     // `script` is stored without the leading '='.
     let processed = preprocess_script(script);
@@ -161,10 +433,139 @@ fn eval_script_cell(ctx: &NativeCallContext, script: &str) -> Option<f64> {
     None
 }
 
+/// Parse user-entered text as a number, allowing surrounding whitespace and a
+/// leading sign. Non-finite spellings like "inf" and "NaN" are rejected.
+fn parse_number_text(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    text.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// Excel-style `ADDRESS`: reference text for a 1-based row and column, with
+/// `$` placement chosen by `abs_mode` (1 = `$A$1`, 2 = `A$1`, 3 = `$A1`, 4 = `A1`).
+fn address(row: i64, col: i64, abs_mode: i64) -> Result<String, Box<EvalAltResult>> {
+    let row = from_one_based(row, "row")? as usize;
+    let col = from_one_based(col, "col")? as usize;
+    let (abs_col, abs_row) = match abs_mode {
+        1 => (true, true),
+        2 => (false, true),
+        3 => (true, false),
+        4 => (false, false),
+        _ => return Err(invalid_arg("ADDRESS: abs_mode must be 1, 2, 3 or 4")),
+    };
+    Ok(format!(
+        "{}{}{}{}",
+        if abs_col { "$" } else { "" },
+        CellRef::col_to_letters(col),
+        if abs_row { "$" } else { "" },
+        row + 1
+    ))
+}
+
+/// Whether a value counts as empty: unit or the empty string.
+fn is_empty_value(value: &Dynamic) -> bool {
+    value.is_unit() || value.clone().into_string().is_ok_and(|s| s.is_empty())
+}
+
+/// Whether `value` is a failed result: a NaN or infinite number, or text that
+/// is an error display (`#ERR: ...`, `#CYCLE!`, ...).
+fn is_error_value(value: &Dynamic) -> bool {
+    if let Ok(n) = value.as_float() {
+        return !n.is_finite();
+    }
+    value
+        .clone()
+        .into_immutable_string()
+        .is_ok_and(|text| error_type_code(&text).is_some())
+}
+
+/// The first non-empty value, or the last value when all are empty.
+fn coalesce(values: Vec<Dynamic>) -> Dynamic {
+    let last = values.len() - 1;
+    values
+        .into_iter()
+        .enumerate()
+        .find(|(i, value)| *i == last || !is_empty_value(value))
+        .map(|(_, value)| value)
+        .unwrap_or(Dynamic::UNIT)
+}
+
+/// Whether a lookup key matches a cell value: text compares as text, numbers
+/// as numbers, and anything else by its string form.
+fn lookup_matches(value: &Dynamic, cell_val: &Dynamic) -> bool {
+    if value.is_string() && cell_val.is_string() {
+        value.clone().into_string().unwrap_or_default()
+            == cell_val.clone().into_string().unwrap_or_default()
+    } else if let (Some(a), Some(b)) = (dynamic_as_f64(value), dynamic_as_f64(cell_val)) {
+        a == b
+    } else {
+        value.to_string() == cell_val.to_string()
+    }
+}
+
+/// Order of a cell value relative to a lookup key, for approximate matches.
+/// Text compares with text and numbers with numbers; other pairs don't compare.
+fn lookup_order(cell_val: &Dynamic, value: &Dynamic) -> Option<std::cmp::Ordering> {
+    if value.is_string() && cell_val.is_string() {
+        let a = cell_val.clone().into_string().unwrap_or_default();
+        let b = value.clone().into_string().unwrap_or_default();
+        return Some(a.cmp(&b));
+    }
+    dynamic_as_f64(cell_val)?.partial_cmp(&dynamic_as_f64(value)?)
+}
+
 fn invalid_arg(message: &str) -> Box<EvalAltResult> {
     EvalAltResult::ErrorRuntime(message.into(), Position::NONE).into()
 }
 
+/// Override `/` so dividing by zero fails with the `#DIV/0!` marker instead of
+/// giving an infinity (floats) or Rhai's own error (integers). Integer division
+/// otherwise keeps Rhai's truncating semantics.
+fn register_division(engine: &mut Engine) {
+    // Registered operators only replace Rhai's built-in ones with fast operators off
+    engine.set_fast_operators(false);
+    let div_zero = || invalid_arg(DIV_ZERO);
+    engine.register_fn(
+        "/",
+        move |a: f64, b: f64| -> Result<f64, Box<EvalAltResult>> {
+            if b == 0.0 { Err(div_zero()) } else { Ok(a / b) }
+        },
+    );
+    engine.register_fn(
+        "/",
+        move |a: f64, b: i64| -> Result<f64, Box<EvalAltResult>> {
+            if b == 0 {
+                Err(div_zero())
+            } else {
+                Ok(a / b as f64)
+            }
+        },
+    );
+    engine.register_fn(
+        "/",
+        move |a: i64, b: f64| -> Result<f64, Box<EvalAltResult>> {
+            if b == 0.0 {
+                Err(div_zero())
+            } else {
+                Ok(a as f64 / b)
+            }
+        },
+    );
+    engine.register_fn(
+        "/",
+        move |a: i64, b: i64| -> Result<i64, Box<EvalAltResult>> {
+            if b == 0 {
+                return Err(div_zero());
+            }
+            a.checked_div(b)
+                .ok_or_else(|| invalid_arg(&format!("Division overflow: {} / {}", a, b)))
+        },
+    );
+}
+
 fn lock_script_modifications<'a>(
     modifications: &'a ScriptModifications,
 ) -> Result<ScriptModificationGuard<'a>, Box<EvalAltResult>> {
@@ -191,6 +592,17 @@ fn to_grid_index(value: i64, label: &str) -> Result<usize, Box<EvalAltResult>> {
     Ok(idx)
 }
 
+/// Convert a 1-based coordinate (as used by `CELL1` and friends) to the
+/// 0-based index the rest of the builtins expect.
+fn from_one_based(value: i64, label: &str) -> Result<i64, Box<EvalAltResult>> {
+    if value < 1 {
+        return Err(invalid_arg(&format!("{} must be >= 1", label)));
+    }
+    let idx = value - 1;
+    to_grid_index(idx, label)?;
+    Ok(idx)
+}
+
 fn normalize_range_coords(
     c1: i64,
     r1: i64,
@@ -234,6 +646,39 @@ fn to_decimal_places(value: i64) -> Result<usize, Box<EvalAltResult>> {
     Ok(places)
 }
 
+/// Round `n` to `decimals` places away from zero (`up`) or toward zero.
+/// Products like `1.1 * 10` that land a hair off a whole number are snapped
+/// to it first, so `ROUNDUP(1.1, 1)` stays 1.1.
+fn round_directed(n: f64, decimals: i64, up: bool) -> Result<f64, Box<EvalAltResult>> {
+    let factor = 10_f64.powi(to_decimal_places(decimals)? as i32);
+    let scaled = n * factor;
+    let nearest = scaled.round();
+    let scaled = if (scaled - nearest).abs() <= 1e-9 * nearest.abs().max(1.0) {
+        nearest
+    } else {
+        scaled
+    };
+    let rounded = if up {
+        scaled.abs().ceil().copysign(scaled)
+    } else {
+        scaled.trunc()
+    };
+    Ok(rounded / factor)
+}
+
+/// Spreadsheet `MOD`: the remainder takes the sign of the divisor.
+fn float_mod(a: f64, b: f64) -> Result<f64, Box<EvalAltResult>> {
+    if b == 0.0 {
+        return Err(invalid_arg("MOD: divisor must not be zero"));
+    }
+    let r = a % b;
+    Ok(if r != 0.0 && (r < 0.0) != (b < 0.0) {
+        r + b
+    } else {
+        r
+    })
+}
+
 fn fixed_decimal_string(n: f64, decimals: usize) -> String {
     if n.is_nan() {
         return "#NAN!".to_string();
@@ -265,818 +710,1462 @@ fn cell_value_or_zero(
     value_cache: &ValueCache,
     col: usize,
     row: usize,
-) -> f64 {
+) -> Result<f64, Box<EvalAltResult>> {
     let cell_ref = CellRef::new(col, row);
 
     // Check value cache first (for pre-computed formulas and spills)
     if let Some(cached_val) = value_cache.get(&cell_ref) {
         if let Ok(n) = cached_val.as_float() {
-            return n;
+            return Ok(n);
         }
         if let Ok(n) = cached_val.as_int() {
-            return n as f64;
+            return Ok(n as f64);
         }
-        return 0.0;
+        return Ok(0.0);
     }
 
     let Some(cell) = grid.get(&cell_ref) else {
-        return 0.0;
+        return blank_value(ctx, &cell_ref);
     };
 
     match &cell.contents {
-        CellType::Number(n) => *n,
-        CellType::Empty => 0.0,
-        CellType::Script(s) => eval_script_cell(ctx, s).unwrap_or(0.0),
-        _ => 0.0,
+        CellType::Number(n) => Ok(*n),
+        CellType::Empty => blank_value(ctx, &cell_ref),
+        CellType::Script(s) => Ok(eval_script_cell(ctx, s).unwrap_or(0.0)),
+        _ => Ok(0.0),
+    }
+}
+
+/// Whether blank cells count as 0 for this evaluation (see
+/// [`crate::engine::set_blanks_are_zero`]); defaults to true.
+fn blanks_are_zero(ctx: &NativeCallContext) -> bool {
+    ctx.tag().and_then(|tag| tag.as_bool().ok()).unwrap_or(true)
+}
+
+/// Numeric value of a genuinely empty cell: 0, or an error when blanks are not zero.
+fn blank_value(ctx: &NativeCallContext, cell_ref: &CellRef) -> Result<f64, Box<EvalAltResult>> {
+    if blanks_are_zero(ctx) {
+        Ok(0.0)
+    } else {
+        Err(invalid_arg(&format!(
+            "blank cell {} (blanks are not zero)",
+            cell_ref
+        )))
     }
 }
 
+/// Rows of a range (as arrays) whose first-column value satisfies `keep`.
+/// An empty result is the single marker `#CALC!` so the formula cell is not blank.
 #[allow(clippy::too_many_arguments)]
-fn make_plot_spec(
-    kind: PlotKind,
+fn filter_rows(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
     c1: i64,
     r1: i64,
     c2: i64,
     r2: i64,
-    title: Option<String>,
-    x_label: Option<String>,
-    y_label: Option<String>,
-) -> Result<String, Box<EvalAltResult>> {
+    mut keep: impl FnMut(&Dynamic) -> bool,
+) -> Result<rhai::Array, Box<EvalAltResult>> {
     let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-    let spec = PlotSpec {
-        kind,
-        r1: min_row,
-        c1: min_col,
-        r2: max_row,
-        c2: max_col,
-        title,
-        x_label,
-        y_label,
+    let mut rows = rhai::Array::new();
+    for row in min_row..=max_row {
+        let key = cell_dynamic_value(ctx, grid, value_cache, min_col, row);
+        if keep(&key) {
+            let values: rhai::Array = (min_col..=max_col)
+                .map(|col| cell_dynamic_value(ctx, grid, value_cache, col, row))
+                .collect();
+            rows.push(Dynamic::from_array(values));
+        }
+    }
+    if rows.is_empty() {
+        rows.push(Dynamic::from("#CALC!".to_string()));
+    }
+    Ok(rows)
+}
+
+/// Typed value at a cell (number/text/bool) as Dynamic, like `VALUE(col, row)`.
+fn cell_dynamic_value(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    col: usize,
+    row: usize,
+) -> Dynamic {
+    let cell_ref = CellRef::new(col, row);
+    if let Some(cached_val) = value_cache.get(&cell_ref) {
+        return cached_val.clone();
+    }
+    let Some(entry) = grid.get(&cell_ref) else {
+        return Dynamic::from("".to_string());
     };
-    Ok(format_plot_spec(&spec))
+    match &entry.contents {
+        CellType::Empty => Dynamic::from("".to_string()),
+        CellType::Number(n) => Dynamic::from(*n),
+        CellType::Text(s) => Dynamic::from(s.clone()),
+        CellType::Script(s) => {
+            let processed = preprocess_script(s);
+            ctx.engine()
+                .eval::<Dynamic>(&processed)
+                .unwrap_or(Dynamic::UNIT)
+        }
+    }
 }
 
-/// Register all built-in functions into the Rhai engine.
-pub fn register_builtins(engine: &mut Engine, grid: Grid, value_cache: ValueCache) {
-    // CELL(col, row): numeric value at cell (text/script -> NaN)
+/// Cell values of a range as an array (typed, like `VALUE`), row by row.
+///
+/// Respects range direction: `(0, 2, 0, 0)` yields `[A3, A2, A1]`.
+#[allow(clippy::too_many_arguments)]
+fn range_to_array(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    c1: i64,
+    r1: i64,
+    c2: i64,
+    r2: i64,
+) -> Result<rhai::Array, Box<EvalAltResult>> {
+    let c1_u = to_grid_index(c1, "c1")?;
+    let r1_u = to_grid_index(r1, "r1")?;
+    let c2_u = to_grid_index(c2, "c2")?;
+    let r2_u = to_grid_index(r2, "r2")?;
+
+    let width = c1_u.max(c2_u) - c1_u.min(c2_u) + 1;
+    let height = r1_u.max(r2_u) - r1_u.min(r2_u) + 1;
+    let Some(cells) = width.checked_mul(height) else {
+        return Err(invalid_arg("range is too large"));
+    };
+    if cells > MAX_RANGE_CELLS {
+        return Err(invalid_arg(&format!(
+            "range exceeds maximum size of {} cells",
+            MAX_RANGE_CELLS
+        )));
+    }
 
-    // Checks value cache first for pre-evaluated values
-    let grid_cell = grid.clone();
-    let cache_cell = value_cache.clone();
-    engine.register_fn(
-        "CELL",
-        move |ctx: NativeCallContext, col: i64, row: i64| -> Result<f64, Box<EvalAltResult>> {
-            let col = to_grid_index(col, "col")?;
-            let row = to_grid_index(row, "row")?;
-            let cell_ref = CellRef::new(col, row);
+    // Build col/row indices respecting direction
+    let rows: Vec<usize> = if r1_u <= r2_u {
+        (r1_u..=r2_u).collect()
+    } else {
+        (r2_u..=r1_u).rev().collect()
+    };
+    let cols: Vec<usize> = if c1_u <= c2_u {
+        (c1_u..=c2_u).collect()
+    } else {
+        (c2_u..=c1_u).rev().collect()
+    };
 
-            // Check value cache first (for pre-computed formulas and spills)
-            if let Some(cached_val) = cache_cell.get(&cell_ref) {
-                if let Ok(n) = cached_val.as_float() {
-                    return Ok(n);
-                }
-                if let Ok(n) = cached_val.as_int() {
-                    return Ok(n as f64);
-                }
-                return Ok(f64::NAN);
-            }
+    let mut result = rhai::Array::new();
+    for row in &rows {
+        for col in &cols {
+            result.push(cell_dynamic_value(ctx, grid, value_cache, *col, *row));
+        }
+    }
+    Ok(result)
+}
 
-            if let Some(entry) = grid_cell.get(&cell_ref) {
-                match &entry.contents {
-                    CellType::Number(n) => Ok(*n),
-                    CellType::Empty => Ok(0.0),
-                    CellType::Script(s) => {
-                        // Fallback: try to evaluate (works for built-in-only scripts)
-                        Ok(eval_script_cell(&ctx, s).unwrap_or(f64::NAN))
-                    }
-                    _ => Ok(f64::NAN),
-                }
-            } else {
-                Ok(0.0)
-            }
-        },
-    );
+/// Numeric values of a range in row-major order (non-numeric cells count as 0).
+#[allow(clippy::too_many_arguments)]
+fn range_values_or_zero(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    c1: i64,
+    r1: i64,
+    c2: i64,
+    r2: i64,
+) -> Result<Vec<f64>, Box<EvalAltResult>> {
+    let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+    let mut values = Vec::new();
+    for row in min_row..=max_row {
+        for col in min_col..=max_col {
+            values.push(cell_value_or_zero(ctx, grid, value_cache, col, row)?);
+        }
+    }
+    Ok(values)
+}
 
-    // VALUE(col, row): typed value at cell (number/text/bool) as Dynamic.
+/// Matching positions `(col, row, paired_col, paired_row)` in two same-shaped ranges.
+type PairedPosition = (usize, usize, usize, usize);
 
-    // - Empty cells => "" (so things like `len(@A1)` behave intuitively)
-    // - Script cells => use cached value from value_cache, fall back to eval
-    // Checks value cache first for pre-evaluated values
-    let grid_value = grid.clone();
-    let cache_value = value_cache.clone();
-    engine.register_fn(
-        "VALUE",
-        move |ctx: NativeCallContext, col: i64, row: i64| -> Result<Dynamic, Box<EvalAltResult>> {
-            let col = to_grid_index(col, "col")?;
-            let row = to_grid_index(row, "row")?;
-            let cell_ref = CellRef::new(col, row);
+/// Positions pairing each cell of the first range with the same offset in the second.
+#[allow(clippy::too_many_arguments)]
+fn paired_positions(
+    name: &str,
+    ac1: i64,
+    ar1: i64,
+    ac2: i64,
+    ar2: i64,
+    cc1: i64,
+    cr1: i64,
+    cc2: i64,
+    cr2: i64,
+) -> Result<Vec<PairedPosition>, Box<EvalAltResult>> {
+    let (a_min_row, a_max_row, a_min_col, a_max_col) = normalize_range_coords(ac1, ar1, ac2, ar2)?;
+    let (c_min_row, c_max_row, c_min_col, c_max_col) = normalize_range_coords(cc1, cr1, cc2, cr2)?;
+    if a_max_row - a_min_row != c_max_row - c_min_row
+        || a_max_col - a_min_col != c_max_col - c_min_col
+    {
+        return Err(invalid_arg(&format!(
+            "{}: ranges must have the same size",
+            name
+        )));
+    }
+    let mut positions = Vec::new();
+    for dr in 0..=a_max_row - a_min_row {
+        for dc in 0..=a_max_col - a_min_col {
+            positions.push((
+                a_min_col + dc,
+                a_min_row + dr,
+                c_min_col + dc,
+                c_min_row + dr,
+            ));
+        }
+    }
+    Ok(positions)
+}
 
-            // Check value cache first (for pre-computed formulas and array spills)
-            if let Some(cached_val) = cache_value.get(&cell_ref) {
-                return Ok(cached_val.clone());
-            }
+/// Conditional average, erroring like a division by zero when nothing matched.
+fn conditional_average(name: &str, sum: f64, count: usize) -> Result<f64, Box<EvalAltResult>> {
+    if count == 0 {
+        return Err(invalid_arg(&format!(
+            "{}: no cells match the criteria (division by zero)",
+            name
+        )));
+    }
+    Ok(sum / count as f64)
+}
 
-            let Some(entry) = grid_value.get(&cell_ref) else {
-                return Ok(Dynamic::from("".to_string()));
-            };
+fn dynamic_as_f64(value: &Dynamic) -> Option<f64> {
+    if let Ok(n) = value.as_float() {
+        return Some(n);
+    }
+    value.as_int().ok().map(|n| n as f64)
+}
 
-            let result = match &entry.contents {
-                CellType::Empty => Dynamic::from("".to_string()),
-                CellType::Number(n) => Dynamic::from(*n),
-                CellType::Text(s) => Dynamic::from(s.clone()),
-                CellType::Script(s) => {
-                    // Fallback: try to evaluate (works for built-in-only scripts)
-                    let processed = preprocess_script(s);
-                    ctx.engine()
-                        .eval::<Dynamic>(&processed)
-                        .unwrap_or(Dynamic::UNIT)
-                }
-            };
+/// Comparison used by a spreadsheet-style criterion string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CriterionOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
 
-            Ok(result)
-        },
-    );
+/// Spreadsheet-style criterion such as `">5"`, `"<>done"`, or `"app*"`.
+///
+/// Text equality supports `*` (any run) and `?` (one char) wildcards,
+/// matched case-insensitively; `~*`, `~?` and `~~` match literally.
+#[derive(Debug)]
+struct Criterion {
+    op: CriterionOp,
+    number: Option<f64>,
+    text: String,
+    pattern: Regex,
+}
 
-    // SUM_RANGE(c1, r1, c2, r2)
+impl Criterion {
+    fn parse(criterion: &str) -> Result<Criterion, Box<EvalAltResult>> {
+        let (op, operand) = [
+            (">=", CriterionOp::Ge),
+            ("<=", CriterionOp::Le),
+            ("<>", CriterionOp::Ne),
+            (">", CriterionOp::Gt),
+            ("<", CriterionOp::Lt),
+            ("=", CriterionOp::Eq),
+        ]
+        .iter()
+        .find_map(|(prefix, op)| criterion.strip_prefix(prefix).map(|rest| (*op, rest)))
+        .unwrap_or((CriterionOp::Eq, criterion));
+
+        let pattern = Regex::new(&format!("(?is)^{}$", glob_to_regex(operand)))
+            .map_err(|e| invalid_arg(&format!("invalid criterion '{}': {}", criterion, e)))?;
+        Ok(Criterion {
+            op,
+            number: operand.trim().parse::<f64>().ok(),
+            text: operand.to_lowercase(),
+            pattern,
+        })
+    }
 
-    let grid_sum = grid.clone();
-    let cache_sum = value_cache.clone();
-    engine.register_fn(
-        "SUM_RANGE",
-        move |ctx: NativeCallContext,
-              c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64|
-              -> Result<f64, Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut sum = 0.0;
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    sum += cell_value_or_zero(&ctx, &grid_sum, &cache_sum, col, row);
+    fn matches(&self, value: &Dynamic) -> bool {
+        let ordering = match (self.number, dynamic_as_f64(value)) {
+            (Some(target), Some(n)) => n.partial_cmp(&target),
+            (Some(_), None) => None,
+            (None, _) => {
+                let text = if value.is_string() {
+                    value.clone().into_string().unwrap_or_default()
+                } else if value.is_unit() {
+                    String::new()
+                } else {
+                    value.to_string()
+                };
+                match self.op {
+                    CriterionOp::Eq => return self.pattern.is_match(&text),
+                    CriterionOp::Ne => return !self.pattern.is_match(&text),
+                    _ => Some(text.to_lowercase().cmp(&self.text)),
                 }
             }
-            Ok(sum)
-        },
-    );
+        };
+        let Some(ordering) = ordering else {
+            // Numbers never equal text, so only "<>" matches a type mismatch.
+            return self.op == CriterionOp::Ne;
+        };
+        match self.op {
+            CriterionOp::Eq => ordering.is_eq(),
+            CriterionOp::Ne => ordering.is_ne(),
+            CriterionOp::Lt => ordering.is_lt(),
+            CriterionOp::Le => ordering.is_le(),
+            CriterionOp::Gt => ordering.is_gt(),
+            CriterionOp::Ge => ordering.is_ge(),
+        }
+    }
+}
 
-    // AVG_RANGE(c1, r1, c2, r2)
+/// Translate a `*`/`?` glob into a regex body, escaping everything else.
+fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::with_capacity(glob.len() * 2);
+    let mut chars = glob.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            '~' => match chars.next() {
+                Some(next) => out.push_str(&regex::escape(&next.to_string())),
+                None => out.push_str(&regex::escape("~")),
+            },
+            _ => out.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    out
+}
 
-    let grid_avg = grid.clone();
-    let cache_avg = value_cache.clone();
-    engine.register_fn(
-        "AVG_RANGE",
-        move |ctx: NativeCallContext,
-              c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64|
-              -> Result<f64, Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut sum = 0.0;
-            let mut count = 0;
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    sum += cell_value_or_zero(&ctx, &grid_avg, &cache_avg, col, row);
-                    count += 1;
-                }
-            }
-            Ok(if count > 0 { sum / count as f64 } else { 0.0 })
-        },
-    );
+fn pct_change(old: f64, new: f64) -> Result<f64, Box<EvalAltResult>> {
+    if old == 0.0 {
+        return Err(invalid_arg("PCTCHANGE: old value must be non-zero"));
+    }
+    Ok((new - old) / old)
+}
 
-    // COUNT_RANGE(c1, r1, c2, r2): count non-empty
+/// Limit `x` to `[lo, hi]`, rejecting an empty interval.
+fn clamp_value(x: f64, lo: f64, hi: f64) -> Result<f64, Box<EvalAltResult>> {
+    if lo.is_nan() || hi.is_nan() || lo > hi {
+        return Err(invalid_arg("CLAMP: lo must be <= hi"));
+    }
+    Ok(x.clamp(lo, hi))
+}
 
-    let grid_count = grid.clone();
-    let cache_count = value_cache.clone();
-    engine.register_fn(
-        "COUNT_RANGE",
-        move |_ctx: NativeCallContext,
-              c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64|
-              -> Result<f64, Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut count = 0;
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    let cell_ref = CellRef::new(col, row);
-                    if cache_count.contains_key(&cell_ref) {
-                        count += 1;
-                        continue;
-                    }
-                    if let Some(cell) = grid_count.get(&cell_ref)
-                        && !matches!(cell.contents, CellType::Empty)
-                    {
-                        count += 1;
-                    }
-                }
-            }
-            Ok(count as f64)
-        },
-    );
+/// Largest operand accepted by the BIT* builtins (Excel's 2^48 limit).
+const MAX_BIT_OPERAND: i64 = (1 << 48) - 1;
 
-    // MIN_RANGE(c1, r1, c2, r2)
+/// Truncate `value` to an integer and check it is in `[0, 2^48)`.
+fn bit_operand(name: &str, value: f64) -> Result<i64, Box<EvalAltResult>> {
+    let n = value.trunc();
+    if !n.is_finite() || n < 0.0 || n > MAX_BIT_OPERAND as f64 {
+        return Err(invalid_arg(&format!(
+            "{}: operands must be integers between 0 and 2^48 - 1",
+            name
+        )));
+    }
+    Ok(n as i64)
+}
 
-    let grid_min = grid.clone();
-    let cache_min = value_cache.clone();
-    engine.register_fn(
-        "MIN_RANGE",
-        move |ctx: NativeCallContext,
-              c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64|
-              -> Result<f64, Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut min_val = f64::INFINITY;
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    let val = cell_value_or_zero(&ctx, &grid_min, &cache_min, col, row);
-                    if val < min_val {
-                        min_val = val;
-                    }
-                }
-            }
-            Ok(if min_val == f64::INFINITY {
-                0.0
-            } else {
-                min_val
-            })
-        },
-    );
+type BitOp = fn(i64, i64) -> i64;
 
-    // MAX_RANGE(c1, r1, c2, r2)
+fn bitwise(name: &str, a: f64, b: f64, op: BitOp) -> Result<i64, Box<EvalAltResult>> {
+    Ok(op(bit_operand(name, a)?, bit_operand(name, b)?))
+}
 
-    let grid_max = grid.clone();
-    let cache_max = value_cache.clone();
-    engine.register_fn(
-        "MAX_RANGE",
-        move |ctx: NativeCallContext,
-              c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64|
-              -> Result<f64, Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut max_val = f64::NEG_INFINITY;
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    let val = cell_value_or_zero(&ctx, &grid_max, &cache_max, col, row);
-                    if val > max_val {
-                        max_val = val;
-                    }
-                }
-            }
-            Ok(if max_val == f64::NEG_INFINITY {
-                0.0
-            } else {
-                max_val
-            })
-        },
-    );
+/// Shift `n` left by `bits` (right when `bits` is negative); the result must stay below 2^48.
+fn bit_shift(name: &str, n: f64, bits: f64) -> Result<i64, Box<EvalAltResult>> {
+    let n = bit_operand(name, n)?;
+    let bits = bits.trunc();
+    if !bits.is_finite() || bits.abs() > 53.0 {
+        return Err(invalid_arg(&format!(
+            "{}: shift amount must be between -53 and 53",
+            name
+        )));
+    }
+    let bits = bits as i64;
+    let shifted = if bits >= 0 {
+        n.checked_shl(bits as u32).filter(|v| v >> bits == n)
+    } else {
+        Some(n >> -bits)
+    };
+    match shifted {
+        Some(v) if v <= MAX_BIT_OPERAND => Ok(v),
+        _ => Err(invalid_arg(&format!("{}: result exceeds 2^48 - 1", name))),
+    }
+}
 
-    // Plot specs (rendered by the TUI)
-    engine.register_fn(
-        "BARCHART_RANGE",
-        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(PlotKind::Bar, c1, r1, c2, r2, None, None, None)
-        },
-    );
-    engine.register_fn(
-        "BARCHART_RANGE",
-        move |c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64,
-              title: String|
-              -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(PlotKind::Bar, c1, r1, c2, r2, Some(title), None, None)
-        },
-    );
-    engine.register_fn(
-        "BARCHART_RANGE",
-        move |c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64,
-              title: String,
-              x: String,
-              y: String|
-              -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(PlotKind::Bar, c1, r1, c2, r2, Some(title), Some(x), Some(y))
-        },
-    );
+/// Digits of a non-negative integer in base 2 or 16 (floats are truncated),
+/// left-padded with zeros to `width` digits when given.
+fn to_base(
+    name: &str,
+    n: f64,
+    radix: u32,
+    width: Option<i64>,
+) -> Result<String, Box<EvalAltResult>> {
+    let n = n.trunc();
+    if !n.is_finite() || n < 0.0 || n > i64::MAX as f64 {
+        return Err(invalid_arg(&format!(
+            "{}: value must be a non-negative integer",
+            name
+        )));
+    }
+    let digits = match radix {
+        2 => format!("{:b}", n as u64),
+        _ => format!("{:X}", n as u64),
+    };
+    match width {
+        None => Ok(digits),
+        Some(width) if (digits.len() as i64..=64).contains(&width) => {
+            Ok(format!("{:0>width$}", digits, width = width as usize))
+        }
+        Some(width) => Err(invalid_arg(&format!(
+            "{}: width {} cannot hold {} digit(s) (max 64)",
+            name,
+            width,
+            digits.len()
+        ))),
+    }
+}
 
-    engine.register_fn(
-        "LINECHART_RANGE",
-        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(PlotKind::Line, c1, r1, c2, r2, None, None, None)
-        },
-    );
-    engine.register_fn(
-        "LINECHART_RANGE",
-        move |c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64,
-              title: String|
-              -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(PlotKind::Line, c1, r1, c2, r2, Some(title), None, None)
-        },
-    );
-    engine.register_fn(
-        "LINECHART_RANGE",
-        move |c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64,
-              title: String,
-              x: String,
-              y: String|
-              -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(
-                PlotKind::Line,
-                c1,
-                r1,
-                c2,
-                r2,
-                Some(title),
-                Some(x),
-                Some(y),
-            )
-        },
-    );
+/// Parse base 2 or 16 digits (case-insensitive, no sign or prefix).
+fn from_base(name: &str, text: &str, radix: u32) -> Result<i64, Box<EvalAltResult>> {
+    let text = text.trim();
+    if text.is_empty() || !text.chars().all(|c| c.is_digit(radix)) {
+        return Err(invalid_arg(&format!("{}: invalid digits '{}'", name, text)));
+    }
+    i64::from_str_radix(text, radix)
+        .map_err(|_| invalid_arg(&format!("{}: '{}' is too large", name, text)))
+}
 
-    engine.register_fn(
-        "SCATTER_RANGE",
-        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(PlotKind::Scatter, c1, r1, c2, r2, None, None, None)
-        },
-    );
-    engine.register_fn(
-        "SCATTER_RANGE",
-        move |c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64,
-              title: String|
-              -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(PlotKind::Scatter, c1, r1, c2, r2, Some(title), None, None)
-        },
-    );
-    engine.register_fn(
-        "SCATTER_RANGE",
-        move |c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64,
-              title: String,
-              x: String,
-              y: String|
-              -> Result<String, Box<EvalAltResult>> {
-            make_plot_spec(
-                PlotKind::Scatter,
-                c1,
-                r1,
-                c2,
-                r2,
-                Some(title),
-                Some(x),
-                Some(y),
-            )
-        },
-    );
+/// Largest n for which n! fits in an f64.
+const MAX_FACT_ARG: i64 = 170;
 
-    // PARSE_CELL("A1"): returns [col, row] (0-indexed)
-    engine.register_fn(
-        "PARSE_CELL",
-        |cell_str: &str| -> Result<rhai::Array, Box<EvalAltResult>> {
-            let Some(cell_ref) = CellRef::from_str(cell_str) else {
-                return Err(invalid_arg(&format!(
-                    "Invalid cell reference: {}",
-                    cell_str
-                )));
-            };
-            Ok(vec![
-                Dynamic::from(cell_ref.col as i64),
-                Dynamic::from(cell_ref.row as i64),
-            ])
-        },
-    );
+fn check_choose_args(name: &str, n: i64, k: i64) -> Result<(), Box<EvalAltResult>> {
+    if n < 0 || k < 0 {
+        return Err(invalid_arg(&format!("{}: n and k must be >= 0", name)));
+    }
+    if k > n {
+        return Err(invalid_arg(&format!("{}: k must be <= n", name)));
+    }
+    Ok(())
+}
 
-    // FORMAT_CELL(col, row): returns "A1" (0-indexed)
+/// `n` as an integer, or an error naming `name`'s `label` argument if `n`
+/// has a fractional part (or is not finite).
+fn whole_number_arg(name: &str, label: &str, n: f64) -> Result<i64, Box<EvalAltResult>> {
+    if n.is_finite() && n.fract() == 0.0 {
+        Ok(n as i64)
+    } else {
+        Err(invalid_arg(&format!(
+            "{}: {} must be a whole number, got {}",
+            name, label, n
+        )))
+    }
+}
 
-    engine.register_fn(
-        "FORMAT_CELL",
-        |col: i64, row: i64| -> Result<String, Box<EvalAltResult>> {
-            let col = to_usize(col, "col")?;
-            let row = to_usize(row, "row")?;
-            Ok(CellRef::new(col, row).to_string())
-        },
-    );
+/// Signature shared by COMBIN and PERMUT.
+type ChooseFn = fn(i64, i64) -> Result<f64, Box<EvalAltResult>>;
+
+/// n choose k, using the multiplicative formula to avoid computing factorials.
+fn combin(n: i64, k: i64) -> Result<f64, Box<EvalAltResult>> {
+    check_choose_args("COMBIN", n, k)?;
+    let k = k.min(n - k);
+    let mut result = 1.0;
+    for i in 1..=k {
+        result = result * (n - k + i) as f64 / i as f64;
+        // The product only grows, so stop once it overflows rather than
+        // looping up to k times
+        if result.is_infinite() {
+            return Err(choose_too_large("COMBIN"));
+        }
+    }
+    Ok(result.round())
+}
 
-    // PARSE_RANGE("A1:B4"): returns [c1, r1, c2, r2] (0-indexed, col/row)
+/// Number of ordered selections of k items from n: n! / (n - k)!.
+fn permut(n: i64, k: i64) -> Result<f64, Box<EvalAltResult>> {
+    check_choose_args("PERMUT", n, k)?;
+    let mut result = 1.0;
+    for i in n - k + 1..=n {
+        result *= i as f64;
+        if result.is_infinite() {
+            return Err(choose_too_large("PERMUT"));
+        }
+    }
+    Ok(result)
+}
 
-    engine.register_fn(
-        "PARSE_RANGE",
-        |range: &str| -> Result<rhai::Array, Box<EvalAltResult>> {
-            let Some((c1, r1, c2, r2)) = parse_range(range) else {
-                return Err(invalid_arg(&format!("Invalid range reference: {}", range)));
-            };
-            Ok(vec![
-                Dynamic::from(c1 as i64),
-                Dynamic::from(r1 as i64),
-                Dynamic::from(c2 as i64),
-                Dynamic::from(r2 as i64),
-            ])
-        },
-    );
+fn choose_too_large(name: &str) -> Box<EvalAltResult> {
+    invalid_arg(&format!("{}: result is too large", name))
+}
 
-    // FORMAT_RANGE(c1, r1, c2, r2): returns "A1:B4" (0-indexed)
+fn fact(n: i64) -> Result<f64, Box<EvalAltResult>> {
+    if n < 0 {
+        return Err(invalid_arg("FACT: n must be >= 0"));
+    }
+    if n > MAX_FACT_ARG {
+        return Err(invalid_arg(&format!("FACT: n must be <= {}", MAX_FACT_ARG)));
+    }
+    Ok((1..=n).fold(1.0, |acc, i| acc * i as f64))
+}
 
-    engine.register_fn(
-        "FORMAT_RANGE",
-        |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
-            let c1 = to_usize(c1, "c1")?;
-            let r1 = to_usize(r1, "r1")?;
-            let c2 = to_usize(c2, "c2")?;
-            let r2 = to_usize(r2, "r2")?;
-            Ok(format!("{}:{}", CellRef::new(c1, r1), CellRef::new(c2, r2)))
-        },
-    );
+/// Characters left as-is by ENCODEURL: RFC 3986 unreserved characters.
+const URL_ENCODE_SET: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+fn url_decode(text: &str) -> Result<String, Box<EvalAltResult>> {
+    let bytes = text.as_bytes();
+    for (i, b) in bytes.iter().enumerate() {
+        if *b == b'%'
+            && !(bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+                && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit))
+        {
+            return Err(invalid_arg(
+                "URLDECODE: '%' must be followed by two hex digits",
+            ));
+        }
+    }
+    percent_encoding::percent_decode_str(text)
+        .decode_utf8()
+        .map(|s| s.into_owned())
+        .map_err(|_| invalid_arg("URLDECODE: decoded bytes are not valid UTF-8"))
+}
 
-    // SPILL(x): converts ranges to arrays, identity for arrays.
-    // Arrays automatically spill down when returned from a formula.
-    // Function form: SPILL(0..=10) or SPILL(arr)
-    engine.register_fn("SPILL", |arr: rhai::Array| -> rhai::Array { arr });
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard (padded) base64 encoding of UTF-8 text.
+fn base64_encode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
 
-    engine.register_fn("SPILL", |range: std::ops::Range<i64>| -> rhai::Array {
-        range.map(Dynamic::from).collect()
-    });
-    engine.register_fn(
-        "SPILL",
-        |range: std::ops::RangeInclusive<i64>| -> rhai::Array {
-            range.map(Dynamic::from).collect()
-        },
-    );
+/// Decode standard (padded) base64 into UTF-8 text, rejecting malformed input.
+fn base64_decode(text: &str) -> Result<String, Box<EvalAltResult>> {
+    let invalid = || invalid_arg("BASE64DECODE: invalid base64 input");
+    let input = text.trim().as_bytes();
+    if !input.len().is_multiple_of(4) {
+        return Err(invalid());
+    }
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    for (idx, chunk) in input.chunks(4).enumerate() {
+        let last = idx == input.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|b| **b == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return Err(invalid());
+        }
+        let mut n = 0u32;
+        for &b in &chunk[..4 - padding] {
+            let v = BASE64_ALPHABET
+                .iter()
+                .position(|c| *c == b)
+                .ok_or_else(invalid)?;
+            n = n << 6 | v as u32;
+        }
+        n <<= 6 * padding as u32;
+        out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+    String::from_utf8(out)
+        .map_err(|_| invalid_arg("BASE64DECODE: decoded bytes are not valid UTF-8"))
+}
 
-    // Method form: (0..=10).SPILL() or arr.SPILL()
-    engine.register_fn("SPILL", |arr: &mut rhai::Array| -> rhai::Array {
-        arr.clone()
-    });
-    engine.register_fn("SPILL", |range: &mut std::ops::Range<i64>| -> rhai::Array {
-        range.clone().map(Dynamic::from).collect()
-    });
-    engine.register_fn(
-        "SPILL",
-        |range: &mut std::ops::RangeInclusive<i64>| -> rhai::Array {
-            range.clone().map(Dynamic::from).collect()
-        },
-    );
+/// Number of compiled patterns kept by [`cached_regex`].
+const REGEX_CACHE_SIZE: usize = 32;
+/// Compiled-size limit for user patterns, so a hostile pattern can't eat memory.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// Compile `pattern` for the REGEX* builtins, reusing recently used patterns
+/// (least recently used first out) so a column of formulas compiles it once.
+fn cached_regex(name: &str, pattern: &str) -> Result<Regex, Box<EvalAltResult>> {
+    static CACHE: OnceLock<Mutex<std::collections::VecDeque<(String, Regex)>>> = OnceLock::new();
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(pos) = cache.iter().position(|(p, _)| p == pattern) {
+        let entry = cache.remove(pos).expect("position is in bounds");
+        let re = entry.1.clone();
+        cache.push_front(entry);
+        return Ok(re);
+    }
+    let re = regex::RegexBuilder::new(pattern)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| invalid_arg(&format!("{}: invalid pattern: {}", name, e)))?;
+    cache.push_front((pattern.to_string(), re.clone()));
+    cache.truncate(REGEX_CACHE_SIZE);
+    Ok(re)
+}
 
-    // VEC_RANGE(c1, r1, c2, r2): returns array of cell values
+fn cagr(begin: f64, end: f64, periods: f64) -> Result<f64, Box<EvalAltResult>> {
+    if begin == 0.0 {
+        return Err(invalid_arg("CAGR: begin value must be non-zero"));
+    }
+    if periods == 0.0 {
+        return Err(invalid_arg("CAGR: periods must be non-zero"));
+    }
+    let ratio = end / begin;
+    if ratio <= 0.0 {
+        return Err(invalid_arg("CAGR: end/begin ratio must be positive"));
+    }
+    Ok(ratio.powf(1.0 / periods) - 1.0)
+}
 
-    // Checks spill map first for spilled array values
-    // Respects range direction: VEC(A3:A1) returns [A3, A2, A1]
-    let grid_vec = grid.clone();
-    let cache_vec = value_cache.clone();
-    engine.register_fn(
-        "VEC_RANGE",
-        move |ctx: NativeCallContext,
-              c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64|
-              -> Result<rhai::Array, Box<EvalAltResult>> {
-            let c1_u = to_grid_index(c1, "c1")?;
-            let r1_u = to_grid_index(r1, "r1")?;
-            let c2_u = to_grid_index(c2, "c2")?;
-            let r2_u = to_grid_index(r2, "r2")?;
-
-            let width = c1_u.max(c2_u) - c1_u.min(c2_u) + 1;
-            let height = r1_u.max(r2_u) - r1_u.min(r2_u) + 1;
-            let Some(cells) = width.checked_mul(height) else {
-                return Err(invalid_arg("range is too large"));
-            };
-            if cells > MAX_RANGE_CELLS {
-                return Err(invalid_arg(&format!(
-                    "range exceeds maximum size of {} cells",
-                    MAX_RANGE_CELLS
-                )));
-            }
+/// Day zero of serial dates (spreadsheet convention: 1 = 1899-12-31, 45658 = 2025-01-01).
+fn serial_epoch() -> chrono::NaiveDate {
+    chrono::NaiveDate::from_ymd_opt(1899, 12, 30).expect("serial epoch is a valid date")
+}
 
-            // Build col/row indices respecting direction
-            let rows: Vec<usize> = if r1_u <= r2_u {
-                (r1_u..=r2_u).collect()
-            } else {
-                (r2_u..=r1_u).rev().collect()
-            };
-            let cols: Vec<usize> = if c1_u <= c2_u {
-                (c1_u..=c2_u).collect()
-            } else {
-                (c2_u..=c1_u).rev().collect()
-            };
+/// Convert a date to its serial day number.
+fn date_to_serial(date: chrono::NaiveDate) -> i64 {
+    (date - serial_epoch()).num_days()
+}
 
-            let mut result = rhai::Array::new();
-            for row in &rows {
-                for col in &cols {
-                    let cell_ref = CellRef::new(*col, *row);
-
-                    // Check value cache first
-                    let val = if let Some(cached_val) = cache_vec.get(&cell_ref) {
-                        cached_val.clone()
-                    } else if let Some(entry) = grid_vec.get(&cell_ref) {
-                        match &entry.contents {
-                            CellType::Empty => Dynamic::from("".to_string()),
-                            CellType::Number(n) => Dynamic::from(*n),
-                            CellType::Text(s) => Dynamic::from(s.clone()),
-                            CellType::Script(s) => {
-                                // Fallback: try to evaluate (works for built-in-only scripts)
-                                let processed = preprocess_script(s);
-                                ctx.engine()
-                                    .eval::<Dynamic>(&processed)
-                                    .unwrap_or(Dynamic::UNIT)
-                            }
-                        }
-                    } else {
-                        Dynamic::from("".to_string())
-                    };
+/// Convert a serial number (days, with the fraction as time of day) to a datetime.
+fn serial_to_datetime(serial: f64) -> Option<chrono::NaiveDateTime> {
+    if !serial.is_finite() {
+        return None;
+    }
+    let days = serial.floor();
+    let seconds = ((serial - days) * 86_400.0).round() as i64;
+    let midnight = serial_epoch()
+        .checked_add_signed(chrono::Duration::try_days(days as i64)?)?
+        .and_hms_opt(0, 0, 0)?;
+    midnight.checked_add_signed(chrono::Duration::try_seconds(seconds)?)
+}
 
-                    result.push(val);
-                }
-            }
-            Ok(result)
-        },
-    );
+/// A date argument: a `YYYY-MM-DD` string (a time part is ignored) or a serial day number.
+fn date_arg(name: &str, value: &Dynamic) -> Result<chrono::NaiveDate, Box<EvalAltResult>> {
+    if let Some(serial) = dynamic_as_f64(value) {
+        return serial_to_datetime(serial)
+            .map(|dt| dt.date())
+            .ok_or_else(|| invalid_arg(&format!("{}: invalid date serial {}", name, serial)));
+    }
+    let text = value.to_string();
+    let text = text.trim();
+    chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .or_else(|_| {
+            chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").map(|dt| dt.date())
+        })
+        .map_err(|_| invalid_arg(&format!("{}: invalid date '{}'", name, text)))
+}
 
-    // POW(base, exp): exponentiation
-    // Rhai doesn't have built-in pow for floats, so we register it here
-    // Handle all type combinations since cell values can be int or float
-    engine.register_fn("POW", |base: f64, exp: f64| -> f64 { base.powf(exp) });
-    engine.register_fn("POW", |base: f64, exp: i64| -> f64 {
-        base.powf(exp as f64)
-    });
-    engine.register_fn("POW", |base: i64, exp: f64| -> f64 {
-        (base as f64).powf(exp)
-    });
-    engine.register_fn("POW", |base: i64, exp: i64| -> f64 {
-        (base as f64).powf(exp as f64)
-    });
+/// WORKDAY's day count, which must be a whole number.
+fn whole_days_arg(value: &Dynamic) -> Result<i64, Box<EvalAltResult>> {
+    match dynamic_as_f64(value) {
+        Some(n) if n.is_finite() && n.fract() == 0.0 => Ok(n as i64),
+        _ => Err(invalid_arg(&format!(
+            "WORKDAY: days must be a whole number, got '{}'",
+            value
+        ))),
+    }
+}
 
-    // SQRT(x): square root
-    engine.register_fn("SQRT", |x: f64| -> f64 { x.sqrt() });
-    engine.register_fn("SQRT", |x: i64| -> f64 { (x as f64).sqrt() });
+fn is_business_day(date: chrono::NaiveDate, holidays: &HashSet<chrono::NaiveDate>) -> bool {
+    use chrono::{Datelike, Weekday};
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !holidays.contains(&date)
+}
 
-    // RAND(): random float in [0.0, 1.0)
-    engine.register_fn("RAND", || -> f64 { rand::thread_rng().r#gen() });
+/// Business days from `start` to `end` inclusive, negative when `end` is earlier.
+fn network_days(
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+    holidays: &HashSet<chrono::NaiveDate>,
+) -> i64 {
+    let (from, to, sign) = if start <= end {
+        (start, end, 1)
+    } else {
+        (end, start, -1)
+    };
+    let count = from
+        .iter_days()
+        .take_while(|date| *date <= to)
+        .filter(|date| is_business_day(*date, holidays))
+        .count() as i64;
+    sign * count
+}
 
-    // RANDINT(min, max): random integer in [min, max] inclusive
-    engine.register_fn(
-        "RANDINT",
-        |min: i64, max: i64| -> Result<i64, Box<EvalAltResult>> {
-            if min > max {
-                return Err(invalid_arg("RANDINT min must be <= max"));
+/// The date `days` business days after (or before, if negative) `start`.
+fn work_day(
+    start: chrono::NaiveDate,
+    days: i64,
+    holidays: &HashSet<chrono::NaiveDate>,
+) -> Result<chrono::NaiveDate, Box<EvalAltResult>> {
+    let step = if days < 0 { -1 } else { 1 };
+    let mut date = start;
+    let mut remaining = days.unsigned_abs();
+    while remaining > 0 {
+        date = date
+            .checked_add_signed(chrono::Duration::days(step))
+            .ok_or_else(|| invalid_arg("WORKDAY: date out of range"))?;
+        if is_business_day(date, holidays) {
+            remaining -= 1;
+        }
+    }
+    Ok(date)
+}
+
+/// Holiday dates listed in a range; blank cells are skipped.
+#[allow(clippy::too_many_arguments)]
+fn range_dates(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    name: &str,
+    c1: i64,
+    r1: i64,
+    c2: i64,
+    r2: i64,
+) -> Result<HashSet<chrono::NaiveDate>, Box<EvalAltResult>> {
+    let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+    let mut dates = HashSet::new();
+    for row in min_row..=max_row {
+        for col in min_col..=max_col {
+            let value = cell_dynamic_value(ctx, grid, value_cache, col, row);
+            if !is_empty_value(&value) {
+                dates.insert(date_arg(name, &value)?);
             }
-            Ok(rand::thread_rng().r#gen_range(min..=max))
-        },
-    );
+        }
+    }
+    Ok(dates)
+}
 
-    // FIXED(n, decimals): format with a fixed number of decimal places.
-    engine.register_fn(
-        "FIXED",
-        |n: f64, decimals: i64| -> Result<String, Box<EvalAltResult>> {
-            let decimals = to_decimal_places(decimals)?;
-            Ok(fixed_decimal_string(n, decimals))
-        },
-    );
-    engine.register_fn(
-        "FIXED",
-        |n: i64, decimals: i64| -> Result<String, Box<EvalAltResult>> {
-            let decimals = to_decimal_places(decimals)?;
-            Ok(fixed_decimal_string(n as f64, decimals))
-        },
-    );
+/// Format a datetime with a spreadsheet-style pattern such as `yyyy-mm-dd hh:mm:ss`.
+///
+/// Tokens: `yy`/`yyyy`, `m`/`mm`/`mmm`/`mmmm` (month), `d`/`dd`/`ddd`/`dddd`,
+/// `h`/`hh`, `s`/`ss`. `m`/`mm` after an hour or before a seconds token means
+/// minutes. Any other character is copied literally.
+fn format_date_pattern(dt: &chrono::NaiveDateTime, pattern: &str) -> String {
+    use chrono::{Datelike, Timelike};
+
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens: Vec<(char, usize)> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].to_ascii_lowercase();
+        let mut len = 1;
+        if matches!(c, 'y' | 'm' | 'd' | 'h' | 's') {
+            while i + len < chars.len() && chars[i + len].to_ascii_lowercase() == c {
+                len += 1;
+            }
+            tokens.push((c, len));
+        } else {
+            tokens.push((chars[i], 0));
+        }
+        i += len;
+    }
 
-    // MONEY(n, symbol[, decimals]): format as currency (no separators).
-    // Examples:
-    //   MONEY(15.0424, "£")    -> "£15.04"
-    //   MONEY(-2, "$", 0)      -> "-$2"
-    engine.register_fn("MONEY", |n: f64, symbol: &str| -> String {
-        money_string(n, symbol, 2)
-    });
-    engine.register_fn("MONEY", |n: i64, symbol: &str| -> String {
-        money_string(n as f64, symbol, 2)
-    });
-    engine.register_fn(
-        "MONEY",
-        |n: f64, symbol: &str, decimals: i64| -> Result<String, Box<EvalAltResult>> {
-            let decimals = to_decimal_places(decimals)?;
-            Ok(money_string(n, symbol, decimals))
-        },
-    );
-    engine.register_fn(
-        "MONEY",
-        |n: i64, symbol: &str, decimals: i64| -> Result<String, Box<EvalAltResult>> {
-            let decimals = to_decimal_places(decimals)?;
-            Ok(money_string(n as f64, symbol, decimals))
-        },
-    );
+    let is_field = |t: &(char, usize)| t.1 > 0;
+    let mut out = String::new();
+    for (idx, &(c, len)) in tokens.iter().enumerate() {
+        if len == 0 {
+            out.push(c);
+            continue;
+        }
+        let prev = tokens[..idx].iter().rev().find(|t| is_field(t));
+        let next = tokens[idx + 1..].iter().find(|t| is_field(t));
+        let is_minute = c == 'm'
+            && len <= 2
+            && (prev.is_some_and(|t| t.0 == 'h') || next.is_some_and(|t| t.0 == 's'));
+        let pad = |n: u32| {
+            if len >= 2 {
+                format!("{:02}", n)
+            } else {
+                n.to_string()
+            }
+        };
+        match c {
+            'y' if len <= 2 => out.push_str(&format!("{:02}", dt.year().rem_euclid(100))),
+            'y' => out.push_str(&format!("{:04}", dt.year())),
+            'm' if is_minute => out.push_str(&pad(dt.minute())),
+            'm' if len == 3 => out.push_str(&dt.format("%b").to_string()),
+            'm' if len >= 4 => out.push_str(&dt.format("%B").to_string()),
+            'm' => out.push_str(&pad(dt.month())),
+            'd' if len == 3 => out.push_str(&dt.format("%a").to_string()),
+            'd' if len >= 4 => out.push_str(&dt.format("%A").to_string()),
+            'd' => out.push_str(&pad(dt.day())),
+            'h' => out.push_str(&pad(dt.hour())),
+            's' => out.push_str(&pad(dt.second())),
+            _ => {}
+        }
+    }
+    out
+}
 
-    // TODAY(): current date as string
-    engine.register_fn("TODAY", || -> String {
-        chrono::Local::now().format("%Y-%m-%d").to_string()
-    });
+/// Format a number with a spreadsheet-style code such as `#,##0.00` or `0.0%`.
+///
+/// The code is a run of `0` (always shown) and `#` (shown if significant)
+/// digits, with an optional `,` for thousands grouping and one `.` decimal
+/// point. Text around the run is copied literally, and each `%` in it
+/// multiplies the value by 100.
+fn format_number_pattern(n: f64, pattern: &str) -> Result<String, Box<EvalAltResult>> {
+    let unsupported = || invalid_arg(&format!("TEXT: unsupported format '{}'", pattern));
+    if pattern.contains([';', '"']) {
+        return Err(unsupported());
+    }
+    let is_code = |c: char| matches!(c, '0' | '#' | ',' | '.');
+    let start = pattern.find(['0', '#', '.']).ok_or_else(unsupported)?;
+    let end = pattern[start..]
+        .find(|c: char| !is_code(c))
+        .map_or(pattern.len(), |i| start + i);
+    let (prefix, code, suffix) = (&pattern[..start], &pattern[start..end], &pattern[end..]);
+    if suffix.contains(['0', '#']) {
+        return Err(unsupported());
+    }
+    let (int_code, dec_code) = code.split_once('.').unwrap_or((code, ""));
+    if dec_code.contains(['.', ',']) {
+        return Err(unsupported());
+    }
+    let grouped = int_code.contains(',');
+    let min_int = int_code.matches('0').count();
+    let min_dec = dec_code.matches('0').count();
+    let max_dec = dec_code.len();
 
-    // NOW(): current date and time as string
-    engine.register_fn("NOW", || -> String {
-        chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
-    });
+    if !n.is_finite() {
+        return Ok(fixed_decimal_string(n, 0));
+    }
+    let percents = prefix.matches('%').count() + suffix.matches('%').count();
+    let value = n * 100f64.powi(percents as i32);
+    // Round half away from zero, as spreadsheets do, before printing.
+    let scale = 10f64.powi(max_dec as i32);
+    let rounded = format!("{:.*}", max_dec, (value.abs() * scale).round() / scale);
+    let (int_digits, frac_digits) = rounded.split_once('.').unwrap_or((&rounded, ""));
+    let mut frac = frac_digits.to_string();
+    while frac.len() > min_dec && frac.ends_with('0') {
+        frac.pop();
+    }
+    let int_digits = int_digits.trim_start_matches('0');
+    let mut int_part = format!("{:0>width$}", int_digits, width = min_int);
+    if grouped {
+        let digits = std::mem::take(&mut int_part);
+        for (i, d) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                int_part.push(',');
+            }
+            int_part.push(d);
+        }
+    }
+    let is_zero = rounded.chars().all(|c| c == '0' || c == '.');
+    let sign = if value < 0.0 && !is_zero { "-" } else { "" };
+    let point = if frac.is_empty() { "" } else { "." };
+    Ok(format!("{sign}{prefix}{int_part}{point}{frac}{suffix}"))
+}
 
-    // DATEDIFF(a, b): signed difference in seconds between two date/datetime strings
-    engine.register_fn(
-        "DATEDIFF",
-        |a: &str, b: &str| -> Result<i64, Box<EvalAltResult>> {
-            let parse = |s: &str| -> Result<chrono::NaiveDateTime, Box<EvalAltResult>> {
-                chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
-                    .or_else(|_| {
-                        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
-                            .map(|d| d.and_hms_opt(0, 0, 0).unwrap())
-                    })
-                    .map_err(|e| {
-                        Box::new(EvalAltResult::ErrorRuntime(
-                            format!("DATEDIFF: invalid date '{}': {}", s, e).into(),
-                            rhai::Position::NONE,
-                        ))
-                    })
-            };
-            let da = parse(a)?;
-            let db = parse(b)?;
-            Ok((da - db).num_seconds())
-        },
-    );
+/// `TEXT(value, pattern)`: number codes (containing `0` or `#`) format the
+/// value as a number, date tokens format it as a serial date/time.
+fn text_format(value: f64, pattern: &str) -> Result<String, Box<EvalAltResult>> {
+    if pattern.contains(['0', '#']) {
+        return format_number_pattern(value, pattern);
+    }
+    if !pattern.contains(['y', 'Y', 'm', 'M', 'd', 'D', 'h', 'H', 's', 'S']) {
+        return Err(invalid_arg(&format!(
+            "TEXT: unsupported format '{}'",
+            pattern
+        )));
+    }
+    let dt = serial_to_datetime(value)
+        .ok_or_else(|| invalid_arg(&format!("TEXT: invalid serial date {}", value)))?;
+    Ok(format_date_pattern(&dt, pattern))
+}
 
-    // IF(cond, then_val, else_val): conditional expression
-    engine.register_fn(
-        "IF",
-        |cond: bool, then_val: Dynamic, else_val: Dynamic| -> Dynamic {
-            if cond { then_val } else { else_val }
-        },
-    );
+/// Mean after discarding `fraction` of the values, split evenly between both tails.
+///
+/// Like spreadsheet TRIMMEAN, the trimmed count is rounded down to an even number.
+fn trim_mean(mut values: Vec<f64>, fraction: f64) -> Result<f64, Box<EvalAltResult>> {
+    if !(0.0..1.0).contains(&fraction) {
+        return Err(invalid_arg("TRIMMEAN: fraction must be in [0, 1)"));
+    }
+    if values.is_empty() {
+        return Ok(0.0);
+    }
+    values.sort_by(f64::total_cmp);
+    let per_side = (values.len() as f64 * fraction).floor() as usize / 2;
+    let kept = &values[per_side..values.len() - per_side];
+    Ok(kept.iter().sum::<f64>() / kept.len() as f64)
+}
 
-    // ISNUMBER(val): true if value is numeric
-    engine.register_fn("ISNUMBER", |val: Dynamic| -> bool {
-        val.is_float() || val.is_int()
-    });
+/// Value at which half of the total weight lies on either side.
+///
+/// When the cumulative weight lands exactly on the midpoint, the two
+/// neighbouring values are averaged (matching the unweighted median).
+fn weighted_median(mut pairs: Vec<(f64, f64)>) -> Result<f64, Box<EvalAltResult>> {
+    if pairs.iter().any(|&(_, w)| w < 0.0 || !w.is_finite()) {
+        return Err(invalid_arg(
+            "WEIGHTEDMEDIAN: weights must be non-negative numbers",
+        ));
+    }
+    pairs.retain(|&(_, w)| w > 0.0);
+    let total: f64 = pairs.iter().map(|&(_, w)| w).sum();
+    if total == 0.0 {
+        return Err(invalid_arg("WEIGHTEDMEDIAN: total weight must be positive"));
+    }
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let half = total / 2.0;
+    let mut cumulative = 0.0;
+    for (i, &(value, weight)) in pairs.iter().enumerate() {
+        cumulative += weight;
+        if (cumulative - half).abs() <= f64::EPSILON * total {
+            return Ok(pairs
+                .get(i + 1)
+                .map_or(value, |next| (value + next.0) / 2.0));
+        }
+        if cumulative > half {
+            return Ok(value);
+        }
+    }
+    Ok(pairs[pairs.len() - 1].0)
+}
 
-    // ISTEXT(val): true if value is a string
-    engine.register_fn("ISTEXT", |val: Dynamic| -> bool { val.is_string() });
+/// Numeric values of a range in row-major order, skipping blank and text cells.
+#[allow(clippy::too_many_arguments)]
+fn range_numbers(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    c1: i64,
+    r1: i64,
+    c2: i64,
+    r2: i64,
+) -> Result<Vec<f64>, Box<EvalAltResult>> {
+    let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+    let mut values = Vec::new();
+    for row in min_row..=max_row {
+        for col in min_col..=max_col {
+            let value = cell_dynamic_value(ctx, grid, value_cache, col, row);
+            if let Some(n) = dynamic_as_f64(&value) {
+                values.push(n);
+            }
+        }
+    }
+    Ok(values)
+}
 
-    // ISEMPTY(val): true if value is empty string or unit
-    engine.register_fn("ISEMPTY", |val: Dynamic| -> bool {
-        if val.is_unit() {
-            return true;
+/// `(x, y)` points pairing each known y with the known x at the same offset.
+#[allow(clippy::too_many_arguments)]
+fn regression_points(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    name: &str,
+    yc1: i64,
+    yr1: i64,
+    yc2: i64,
+    yr2: i64,
+    xc1: i64,
+    xr1: i64,
+    xc2: i64,
+    xr2: i64,
+) -> Result<Vec<(f64, f64)>, Box<EvalAltResult>> {
+    paired_positions(name, yc1, yr1, yc2, yr2, xc1, xr1, xc2, xr2)?
+        .into_iter()
+        .map(|(yc, yr, xc, xr)| {
+            Ok((
+                cell_value_or_zero(ctx, grid, value_cache, xc, xr)?,
+                cell_value_or_zero(ctx, grid, value_cache, yc, yr)?,
+            ))
+        })
+        .collect()
+}
+
+/// Text of a header or criteria cell, as compared against field names.
+/// Whole numbers read as typed (`2024`, not `2024.0`).
+fn dynamic_label(value: &Dynamic) -> String {
+    if value.is_string() {
+        value.clone().into_string().unwrap_or_default()
+    } else if value.is_unit() {
+        String::new()
+    } else if let Ok(n) = value.as_float() {
+        n.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Column of `field` in a database range whose first row is `header`:
+/// a header name (case-insensitive) or a 1-based column number.
+fn database_field_column(
+    name: &str,
+    header: &[(usize, String)],
+    field: &Dynamic,
+) -> Result<usize, Box<EvalAltResult>> {
+    if let Some(n) = dynamic_as_f64(field) {
+        let index = n as usize;
+        if n.fract() != 0.0 || index < 1 || index > header.len() {
+            return Err(invalid_arg(&format!(
+                "{}: field {} is outside the database's {} columns",
+                name,
+                n,
+                header.len()
+            )));
         }
-        if let Ok(s) = val.into_string() {
-            return s.is_empty();
+        return Ok(header[index - 1].0);
+    }
+    let wanted = dynamic_label(field);
+    let wanted = wanted.trim();
+    header
+        .iter()
+        .find(|(_, label)| label.trim().eq_ignore_ascii_case(wanted))
+        .map(|(col, _)| *col)
+        .ok_or_else(|| invalid_arg(&format!("{}: no field named '{}'", name, wanted)))
+}
+
+/// `(column, label)` for each column of a database range's header row.
+fn database_header(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    (min_row, min_col, max_col): (usize, usize, usize),
+) -> Vec<(usize, String)> {
+    (min_col..=max_col)
+        .map(|col| {
+            let label = cell_dynamic_value(ctx, grid, value_cache, col, min_row);
+            (col, dynamic_label(&label))
+        })
+        .collect()
+}
+
+/// Rows of a database range (excluding its header) matching a criteria block.
+///
+/// The criteria block's first row names fields; each row below it is a set of
+/// criteria that must all hold (blank cells are ignored), and a record matches
+/// if any criteria row does.
+fn database_matching_rows(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    name: &str,
+    header: &[(usize, String)],
+    (min_row, max_row): (usize, usize),
+    (kc1, kr1, kc2, kr2): (i64, i64, i64, i64),
+) -> Result<Vec<usize>, Box<EvalAltResult>> {
+    let (crit_min_row, crit_max_row, crit_min_col, crit_max_col) =
+        normalize_range_coords(kc1, kr1, kc2, kr2)?;
+    let mut criteria_rows = Vec::new();
+    for row in crit_min_row + 1..=crit_max_row {
+        let mut criteria = Vec::new();
+        for col in crit_min_col..=crit_max_col {
+            let value = cell_dynamic_value(ctx, grid, value_cache, col, row);
+            let text = dynamic_label(&value);
+            if text.is_empty() {
+                continue;
+            }
+            let field = cell_dynamic_value(ctx, grid, value_cache, col, crit_min_row);
+            let data_col = database_field_column(name, header, &field)?;
+            criteria.push((data_col, Criterion::parse(&text)?));
         }
-        false
-    });
+        criteria_rows.push(criteria);
+    }
 
-    // ROUND(n, decimals): round to N decimal places
-    engine.register_fn(
-        "ROUND",
-        |n: f64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
-            let decimals = to_decimal_places(decimals)?;
-            let factor = 10_f64.powi(decimals as i32);
-            Ok((n * factor).round() / factor)
-        },
-    );
-    engine.register_fn(
-        "ROUND",
-        |n: i64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
-            let decimals = to_decimal_places(decimals)?;
-            let factor = 10_f64.powi(decimals as i32);
-            Ok((n as f64 * factor).round() / factor)
-        },
-    );
+    let mut rows = Vec::new();
+    for row in min_row + 1..=max_row {
+        let matched = criteria_rows.iter().any(|criteria| {
+            criteria.iter().all(|(col, criterion)| {
+                criterion.matches(&cell_dynamic_value(ctx, grid, value_cache, *col, row))
+            })
+        });
+        if matched {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
 
-    // ABS(n): absolute value
-    engine.register_fn("ABS", |n: f64| -> f64 { n.abs() });
-    engine.register_fn("ABS", |n: i64| -> i64 { n.abs() });
+/// A criteria range `(c1, r1, c2, r2)` and its criterion string, as passed to COUNTIFS.
+type CriteriaPair<'a> = ((i64, i64, i64, i64), &'a str);
 
-    // FLOOR(x): round down toward negative infinity
-    engine.register_fn("FLOOR", |x: f64| -> f64 { x.floor() });
-    engine.register_fn("FLOOR", |x: i64| -> f64 { x as f64 });
+/// Count positions where every range's cell matches its criterion. All ranges
+/// must have the same size; cells are paired by their offset in the range.
+fn count_ifs(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    pairs: &[CriteriaPair],
+) -> Result<i64, Box<EvalAltResult>> {
+    let ((fc1, fr1, fc2, fr2), _) = pairs[0];
+    let mut columns = Vec::new();
+    for &((c1, r1, c2, r2), criterion) in pairs {
+        let positions = paired_positions("COUNTIFS", fc1, fr1, fc2, fr2, c1, r1, c2, r2)?;
+        columns.push((positions, Criterion::parse(criterion)?));
+    }
+    let total = columns[0].0.len();
+    let count = (0..total)
+        .filter(|&i| {
+            columns.iter().all(|(positions, criterion)| {
+                let (_, _, col, row) = positions[i];
+                criterion.matches(&cell_dynamic_value(ctx, grid, value_cache, col, row))
+            })
+        })
+        .count();
+    Ok(count as i64)
+}
 
-    // CEIL(x): round up toward positive infinity
-    engine.register_fn("CEIL", |x: f64| -> f64 { x.ceil() });
-    engine.register_fn("CEIL", |x: i64| -> f64 { x as f64 });
+/// Sum over positions of the product of every range's cell at that position.
+/// All ranges must have the same size; non-numeric cells count as 0.
+fn sum_product(
+    ctx: &NativeCallContext,
+    grid: &Grid,
+    value_cache: &ValueCache,
+    ranges: &[(i64, i64, i64, i64)],
+) -> Result<f64, Box<EvalAltResult>> {
+    let (fc1, fr1, fc2, fr2) = ranges[0];
+    let mut columns = Vec::new();
+    for &(c1, r1, c2, r2) in ranges {
+        columns.push(paired_positions(
+            "SUMPRODUCT",
+            fc1,
+            fr1,
+            fc2,
+            fr2,
+            c1,
+            r1,
+            c2,
+            r2,
+        )?);
+    }
+    Ok((0..columns[0].len())
+        .map(|i| {
+            columns
+                .iter()
+                .map(|positions| {
+                    let (_, _, col, row) = positions[i];
+                    dynamic_as_f64(&cell_dynamic_value(ctx, grid, value_cache, col, row))
+                        .unwrap_or(0.0)
+                })
+                .product::<f64>()
+        })
+        .sum())
+}
 
-    // PI(): ratio of circumference to diameter
-    engine.register_fn("PI", || -> f64 { std::f64::consts::PI });
+/// Least-squares `(slope, intercept)` of the line `y = slope * x + intercept`.
+fn linear_fit(name: &str, points: &[(f64, f64)]) -> Result<(f64, f64), Box<EvalAltResult>> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    if sxx == 0.0 {
+        return Err(invalid_arg(&format!(
+            "{}: known x values have zero variance",
+            name
+        )));
+    }
+    let slope = sxy / sxx;
+    Ok((slope, mean_y - slope * mean_x))
+}
 
-    // E(): Euler's number
-    engine.register_fn("E", || -> f64 { std::f64::consts::E });
+/// Mean and sample standard deviation (n - 1 denominator); `None` for fewer than two values.
+fn mean_and_sample_stdev(values: &[f64]) -> Option<(f64, f64)> {
+    if values.len() < 2 {
+        return None;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Some((mean, variance.sqrt()))
+}
 
-    // LN(x): natural logarithm
-    engine.register_fn("LN", |x: f64| -> f64 { x.ln() });
-    engine.register_fn("LN", |x: i64| -> f64 { (x as f64).ln() });
+/// Variance of `values`: the sample variance (n - 1 denominator), which
+/// needs at least two values, or the population variance (n denominator).
+fn variance(name: &str, values: &[f64], sample: bool) -> Result<f64, Box<EvalAltResult>> {
+    let min_len = if sample { 2 } else { 1 };
+    if values.len() < min_len {
+        return Err(invalid_arg(&format!(
+            "{}: range needs at least {} numeric value{}",
+            name,
+            min_len,
+            if sample { "s" } else { "" }
+        )));
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let squares = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+    Ok(squares / if sample { n - 1.0 } else { n })
+}
 
-    // LOG(x): base-10 logarithm; LOG(x, base): arbitrary base
-    engine.register_fn("LOG", |x: f64| -> f64 { x.log10() });
-    engine.register_fn("LOG", |x: i64| -> f64 { (x as f64).log10() });
-    engine.register_fn("LOG", |x: f64, base: f64| -> f64 { x.log(base) });
-    engine.register_fn("LOG", |x: i64, base: i64| -> f64 {
-        (x as f64).log(base as f64)
+/// Inclusive percentile rank of `x` in `values`, from 0 (minimum) to 1 (maximum).
+///
+/// Values between two data points are interpolated linearly, like spreadsheet
+/// PERCENTRANK.INC; values outside the data range are an error.
+fn percent_rank(mut values: Vec<f64>, x: f64) -> Result<f64, Box<EvalAltResult>> {
+    if values.is_empty() {
+        return Err(invalid_arg("PERCENTRANK: range has no numeric values"));
+    }
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    if x < values[0] || x > values[n - 1] {
+        return Err(invalid_arg("PERCENTRANK: value is outside the range"));
+    }
+    if n == 1 {
+        return Ok(1.0);
+    }
+    let below = values.partition_point(|&v| v < x);
+    if values[below] == x {
+        return Ok(below as f64 / (n - 1) as f64);
+    }
+    let (lo, hi) = (values[below - 1], values[below]);
+    Ok(((below - 1) as f64 + (x - lo) / (hi - lo)) / (n - 1) as f64)
+}
+
+/// Standard score `(x - mean) / stdev` using the sample standard deviation.
+fn z_score(x: f64, values: &[f64]) -> Result<f64, Box<EvalAltResult>> {
+    let Some((mean, stdev)) = mean_and_sample_stdev(values) else {
+        return Err(invalid_arg(
+            "ZSCORE: range needs at least two numeric values",
+        ));
+    };
+    if stdev == 0.0 {
+        return Err(invalid_arg("ZSCORE: standard deviation is zero"));
+    }
+    Ok((x - mean) / stdev)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn make_plot_spec(
+    kind: PlotKind,
+    c1: i64,
+    r1: i64,
+    c2: i64,
+    r2: i64,
+    title: Option<String>,
+    x_label: Option<String>,
+    y_label: Option<String>,
+) -> Result<String, Box<EvalAltResult>> {
+    let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+    let spec = PlotSpec {
+        kind,
+        r1: min_row,
+        c1: min_col,
+        r2: max_row,
+        c2: max_col,
+        title,
+        x_label,
+        y_label,
+    };
+    Ok(format_plot_spec(&spec))
+}
+
+/// Register all built-in functions into the Rhai engine.
+pub fn register_builtins(engine: &mut Engine, grid: Grid, value_cache: ValueCache) {
+    register_division(engine);
+
+    // CELL(col, row): numeric value at cell (text/script -> NaN)
+
+    // Checks value cache first for pre-evaluated values
+    let grid_cell = grid.clone();
+    let cache_cell = value_cache.clone();
+    engine.register_fn(
+        "CELL",
+        move |ctx: NativeCallContext, col: i64, row: i64| -> Result<f64, Box<EvalAltResult>> {
+            let col = to_grid_index(col, "col")?;
+            let row = to_grid_index(row, "row")?;
+            let cell_ref = CellRef::new(col, row);
+
+            // Check value cache first (for pre-computed formulas and spills)
+            if let Some(cached_val) = cache_cell.get(&cell_ref) {
+                if let Ok(n) = cached_val.as_float() {
+                    return Ok(n);
+                }
+                if let Ok(n) = cached_val.as_int() {
+                    return Ok(n as f64);
+                }
+                return Ok(f64::NAN);
+            }
+
+            if let Some(entry) = grid_cell.get(&cell_ref) {
+                match &entry.contents {
+                    CellType::Number(n) => Ok(*n),
+                    CellType::Empty => blank_value(&ctx, &cell_ref),
+                    CellType::Script(s) => {
+                        // Fallback: try to evaluate (works for built-in-only scripts)
+                        Ok(eval_script_cell(&ctx, s).unwrap_or(f64::NAN))
+                    }
+                    _ => Ok(f64::NAN),
+                }
+            } else {
+                blank_value(&ctx, &cell_ref)
+            }
+        },
+    );
+
+    // CELL1(col, row): same as CELL, but with 1-based coordinates
+    // (CELL1(1, 1) is A1, whereas CELL(0, 0) is A1).
+    engine.register_fn(
+        "CELL1",
+        |ctx: NativeCallContext, col: i64, row: i64| -> Result<f64, Box<EvalAltResult>> {
+            let col = from_one_based(col, "col")?;
+            let row = from_one_based(row, "row")?;
+            ctx.call_fn("CELL", (col, row))
+        },
+    );
+
+    // VALUE(col, row): typed value at cell (number/text/bool) as Dynamic.
+
+    // - Empty cells => "" (so things like `len(@A1)` behave intuitively)
+    // - Script cells => use cached value from value_cache, fall back to eval
+    // Checks value cache first for pre-evaluated values
+    let grid_value = grid.clone();
+    let cache_value = value_cache.clone();
+    engine.register_fn(
+        "VALUE",
+        move |ctx: NativeCallContext, col: i64, row: i64| -> Result<Dynamic, Box<EvalAltResult>> {
+            let col = to_grid_index(col, "col")?;
+            let row = to_grid_index(row, "row")?;
+            let cell_ref = CellRef::new(col, row);
+
+            // Check value cache first (for pre-computed formulas and array spills)
+            if let Some(cached_val) = cache_value.get(&cell_ref) {
+                return Ok(cached_val.clone());
+            }
+
+            let Some(entry) = grid_value.get(&cell_ref) else {
+                return Ok(Dynamic::from("".to_string()));
+            };
+
+            let result = match &entry.contents {
+                CellType::Empty => Dynamic::from("".to_string()),
+                CellType::Number(n) => Dynamic::from(*n),
+                CellType::Text(s) => Dynamic::from(s.clone()),
+                CellType::Script(s) => {
+                    // Fallback: try to evaluate (works for built-in-only scripts)
+                    let processed = preprocess_script(s);
+                    ctx.engine()
+                        .eval::<Dynamic>(&processed)
+                        .unwrap_or(Dynamic::UNIT)
+                }
+            };
+
+            Ok(result)
+        },
+    );
+
+    // ISFORMULA("A1"): true if the cell holds a formula (spill outputs do not)
+    let grid_formula = grid.clone();
+    engine.register_fn(
+        "ISFORMULA",
+        move |cell_str: &str| -> Result<bool, Box<EvalAltResult>> {
+            let cell_ref = CellRef::from_str(cell_str)
+                .ok_or_else(|| invalid_arg(&format!("Invalid cell reference: {}", cell_str)))?;
+            Ok(grid_formula
+                .get(&cell_ref)
+                .is_some_and(|cell| matches!(cell.contents, CellType::Script(_))))
+        },
+    );
+
+    // ISREF(text): true if text is a valid A1-style cell reference
+    engine.register_fn("ISREF", |text: &str| -> bool {
+        CellRef::from_str(text.trim()).is_some()
     });
-    engine.register_fn("LOG", |x: f64, base: i64| -> f64 { x.log(base as f64) });
-    engine.register_fn("LOG", |x: i64, base: f64| -> f64 { (x as f64).log(base) });
 
-    // SUMIF(c1, r1, c2, r2, predicate): sum values where predicate returns true
-    let grid_sumif = grid.clone();
-    let cache_sumif = value_cache.clone();
+    // SUM_RANGE(c1, r1, c2, r2)
+
+    let grid_sum = grid.clone();
+    let cache_sum = value_cache.clone();
     engine.register_fn(
-        "SUMIF_RANGE",
+        "SUM_RANGE",
         move |ctx: NativeCallContext,
               c1: i64,
               r1: i64,
               c2: i64,
-              r2: i64,
-              pred: FnPtr|
+              r2: i64|
               -> Result<f64, Box<EvalAltResult>> {
             let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
             let mut sum = 0.0;
             for row in min_row..=max_row {
                 for col in min_col..=max_col {
-                    let _cell_ref = CellRef::new(col, row);
-                    let val = cell_value_or_zero(&ctx, &grid_sumif, &cache_sumif, col, row);
-                    let pred_result: bool = pred.call_within_context(&ctx, (val,)).unwrap_or(false);
-                    if pred_result {
-                        sum += val;
-                    }
+                    sum += cell_value_or_zero(&ctx, &grid_sum, &cache_sum, col, row)?;
                 }
             }
             Ok(sum)
         },
     );
 
-    // COUNTIF(c1, r1, c2, r2, predicate): count cells where predicate returns true
-    let grid_countif = grid.clone();
-    let cache_countif = value_cache.clone();
+    // AVG_RANGE(c1, r1, c2, r2)
+
+    let grid_avg = grid.clone();
+    let cache_avg = value_cache.clone();
     engine.register_fn(
-        "COUNTIF_RANGE",
+        "AVG_RANGE",
         move |ctx: NativeCallContext,
               c1: i64,
               r1: i64,
               c2: i64,
-              r2: i64,
-              pred: FnPtr|
-              -> Result<i64, Box<EvalAltResult>> {
+              r2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
             let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut sum = 0.0;
             let mut count = 0;
             for row in min_row..=max_row {
                 for col in min_col..=max_col {
-                    let _cell_ref = CellRef::new(col, row);
-                    let val = cell_value_or_zero(&ctx, &grid_countif, &cache_countif, col, row);
-                    let pred_result: bool = pred.call_within_context(&ctx, (val,)).unwrap_or(false);
-                    if pred_result {
-                        count += 1;
-                    }
+                    sum += cell_value_or_zero(&ctx, &grid_avg, &cache_avg, col, row)?;
+                    count += 1;
                 }
             }
-            Ok(count)
+            Ok(if count > 0 { sum / count as f64 } else { 0.0 })
         },
     );
 
-    // PRODUCT_RANGE(c1, r1, c2, r2): product of numeric values in range
-    let grid_product = grid.clone();
-    let cache_product = value_cache.clone();
+    // COUNT_RANGE(c1, r1, c2, r2): count non-empty
+
+    let grid_count = grid.clone();
+    let cache_count = value_cache.clone();
     engine.register_fn(
-        "PRODUCT_RANGE",
-        move |ctx: NativeCallContext,
+        "COUNT_RANGE",
+        move |_ctx: NativeCallContext,
               c1: i64,
               r1: i64,
               c2: i64,
               r2: i64|
               -> Result<f64, Box<EvalAltResult>> {
             let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut product = 1.0;
+            let mut count = 0;
             for row in min_row..=max_row {
                 for col in min_col..=max_col {
-                    product *= cell_value_or_zero(&ctx, &grid_product, &cache_product, col, row);
+                    let cell_ref = CellRef::new(col, row);
+                    if cache_count.contains_key(&cell_ref) {
+                        count += 1;
+                        continue;
+                    }
+                    if let Some(cell) = grid_count.get(&cell_ref)
+                        && !matches!(cell.contents, CellType::Empty)
+                    {
+                        count += 1;
+                    }
                 }
             }
-            Ok(product)
+            Ok(count as f64)
         },
     );
 
-    // MEDIAN_RANGE(c1, r1, c2, r2): median of numeric values in range
-    let grid_median = grid.clone();
-    let cache_median = value_cache.clone();
+    // MIN_RANGE(c1, r1, c2, r2)
+
+    let grid_min = grid.clone();
+    let cache_min = value_cache.clone();
     engine.register_fn(
-        "MEDIAN_RANGE",
+        "MIN_RANGE",
         move |ctx: NativeCallContext,
               c1: i64,
               r1: i64,
@@ -1084,36 +2173,29 @@ pub fn register_builtins(engine: &mut Engine, grid: Grid, value_cache: ValueCach
               r2: i64|
               -> Result<f64, Box<EvalAltResult>> {
             let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut values = Vec::new();
+            let mut min_val = f64::INFINITY;
             for row in min_row..=max_row {
                 for col in min_col..=max_col {
-                    values.push(cell_value_or_zero(
-                        &ctx,
-                        &grid_median,
-                        &cache_median,
-                        col,
-                        row,
-                    ));
+                    let val = cell_value_or_zero(&ctx, &grid_min, &cache_min, col, row)?;
+                    if val < min_val {
+                        min_val = val;
+                    }
                 }
             }
-            if values.is_empty() {
-                return Ok(0.0);
-            }
-            values.sort_by(f64::total_cmp);
-            let n = values.len();
-            if n % 2 == 1 {
-                Ok(values[n / 2])
+            Ok(if min_val == f64::INFINITY {
+                0.0
             } else {
-                Ok((values[n / 2 - 1] + values[n / 2]) / 2.0)
-            }
+                min_val
+            })
         },
     );
 
-    // GEOMEAN_RANGE(c1, r1, c2, r2): geometric mean using exp(avg(ln(values)))
-    let grid_geomean = grid.clone();
-    let cache_geomean = value_cache.clone();
+    // MAX_RANGE(c1, r1, c2, r2)
+
+    let grid_max = grid.clone();
+    let cache_max = value_cache.clone();
     engine.register_fn(
-        "GEOMEAN_RANGE",
+        "MAX_RANGE",
         move |ctx: NativeCallContext,
               c1: i64,
               r1: i64,
@@ -1121,1234 +2203,4216 @@ pub fn register_builtins(engine: &mut Engine, grid: Grid, value_cache: ValueCach
               r2: i64|
               -> Result<f64, Box<EvalAltResult>> {
             let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut ln_sum = 0.0;
-            let mut count = 0usize;
+            let mut max_val = f64::NEG_INFINITY;
             for row in min_row..=max_row {
                 for col in min_col..=max_col {
-                    let val = cell_value_or_zero(&ctx, &grid_geomean, &cache_geomean, col, row);
-                    if val <= 0.0 {
-                        return Err(invalid_arg("GEOMEAN: all values must be positive"));
+                    let val = cell_value_or_zero(&ctx, &grid_max, &cache_max, col, row)?;
+                    if val > max_val {
+                        max_val = val;
                     }
-                    ln_sum += val.ln();
-                    count += 1;
                 }
             }
-            if count == 0 {
-                return Ok(0.0);
-            }
-            Ok((ln_sum / count as f64).exp())
+            Ok(if max_val == f64::NEG_INFINITY {
+                0.0
+            } else {
+                max_val
+            })
         },
     );
 
-    // CONCAT_RANGE(c1, r1, c2, r2): concatenate cell values; optional separator
-    let grid_concat = grid.clone();
-    let cache_concat = value_cache.clone();
+    // Plot specs (rendered by the TUI)
     engine.register_fn(
-        "CONCAT_RANGE",
-        move |ctx: NativeCallContext,
-              c1: i64,
+        "BARCHART_RANGE",
+        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
+            make_plot_spec(PlotKind::Bar, c1, r1, c2, r2, None, None, None)
+        },
+    );
+    engine.register_fn(
+        "BARCHART_RANGE",
+        move |c1: i64,
               r1: i64,
               c2: i64,
-              r2: i64|
+              r2: i64,
+              title: String|
               -> Result<String, Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut parts = Vec::new();
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    let cell_ref = CellRef::new(col, row);
-                    if let Some(cached_val) = cache_concat.get(&cell_ref) {
-                        parts.push(cached_val.to_string());
-                        continue;
-                    }
-                    if let Some(cell) = grid_concat.get(&cell_ref) {
-                        match &cell.contents {
-                            CellType::Empty => {}
-                            CellType::Number(n) => parts.push(n.to_string()),
-                            CellType::Text(s) => parts.push(s.clone()),
-                            CellType::Script(s) => {
-                                let processed = preprocess_script(s);
-                                if let Ok(val) = ctx.engine().eval::<Dynamic>(&processed) {
-                                    parts.push(val.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(parts.join(""))
+            make_plot_spec(PlotKind::Bar, c1, r1, c2, r2, Some(title), None, None)
+        },
+    );
+    engine.register_fn(
+        "BARCHART_RANGE",
+        move |c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              title: String,
+              x: String,
+              y: String|
+              -> Result<String, Box<EvalAltResult>> {
+            make_plot_spec(PlotKind::Bar, c1, r1, c2, r2, Some(title), Some(x), Some(y))
         },
     );
 
-    let grid_concat_sep = grid.clone();
-    let cache_concat_sep = value_cache.clone();
     engine.register_fn(
-        "CONCAT_RANGE",
-        move |ctx: NativeCallContext,
-              c1: i64,
+        "LINECHART_RANGE",
+        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
+            make_plot_spec(PlotKind::Line, c1, r1, c2, r2, None, None, None)
+        },
+    );
+    engine.register_fn(
+        "LINECHART_RANGE",
+        move |c1: i64,
               r1: i64,
               c2: i64,
               r2: i64,
-              sep: &str|
+              title: String|
               -> Result<String, Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
-            let mut parts = Vec::new();
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    let cell_ref = CellRef::new(col, row);
-                    if let Some(cached_val) = cache_concat_sep.get(&cell_ref) {
-                        parts.push(cached_val.to_string());
-                        continue;
-                    }
-                    if let Some(cell) = grid_concat_sep.get(&cell_ref) {
-                        match &cell.contents {
-                            CellType::Empty => {}
-                            CellType::Number(n) => parts.push(n.to_string()),
-                            CellType::Text(s) => parts.push(s.clone()),
-                            CellType::Script(s) => {
-                                let processed = preprocess_script(s);
-                                if let Ok(val) = ctx.engine().eval::<Dynamic>(&processed) {
-                                    parts.push(val.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(parts.join(sep))
+            make_plot_spec(PlotKind::Line, c1, r1, c2, r2, Some(title), None, None)
+        },
+    );
+    engine.register_fn(
+        "LINECHART_RANGE",
+        move |c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              title: String,
+              x: String,
+              y: String|
+              -> Result<String, Box<EvalAltResult>> {
+            make_plot_spec(
+                PlotKind::Line,
+                c1,
+                r1,
+                c2,
+                r2,
+                Some(title),
+                Some(x),
+                Some(y),
+            )
         },
     );
 
-    // LOOKUP_IMPL(value, sc1, sr1, sc2, sr2, rc1, rr1, rc2, rr2):
-    // Search for value in search range, return corresponding cell from return range.
-    let grid_lookup = grid.clone();
-    let cache_lookup = value_cache.clone();
     engine.register_fn(
-        "LOOKUP_IMPL",
-        move |ctx: NativeCallContext,
-              value: Dynamic,
-              sc1: i64,
-              sr1: i64,
-              sc2: i64,
-              sr2: i64,
-              rc1: i64,
-              rr1: i64,
-              rc2: i64,
-              rr2: i64|
-              -> Result<Dynamic, Box<EvalAltResult>> {
-            let (s_min_row, s_max_row, s_min_col, s_max_col) =
-                normalize_range_coords(sc1, sr1, sc2, sr2)?;
-            let (r_min_row, r_max_row, r_min_col, r_max_col) =
-                normalize_range_coords(rc1, rr1, rc2, rr2)?;
+        "SCATTER_RANGE",
+        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
+            make_plot_spec(PlotKind::Scatter, c1, r1, c2, r2, None, None, None)
+        },
+    );
+    engine.register_fn(
+        "SCATTER_RANGE",
+        move |c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              title: String|
+              -> Result<String, Box<EvalAltResult>> {
+            make_plot_spec(PlotKind::Scatter, c1, r1, c2, r2, Some(title), None, None)
+        },
+    );
+    engine.register_fn(
+        "SCATTER_RANGE",
+        move |c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              title: String,
+              x: String,
+              y: String|
+              -> Result<String, Box<EvalAltResult>> {
+            make_plot_spec(
+                PlotKind::Scatter,
+                c1,
+                r1,
+                c2,
+                r2,
+                Some(title),
+                Some(x),
+                Some(y),
+            )
+        },
+    );
 
-            // Collect search range coordinates
-            let mut search_coords = Vec::new();
-            for row in s_min_row..=s_max_row {
-                for col in s_min_col..=s_max_col {
-                    search_coords.push((col, row));
-                }
-            }
-            // Collect return range coordinates
-            let mut return_coords = Vec::new();
-            for row in r_min_row..=r_max_row {
-                for col in r_min_col..=r_max_col {
-                    return_coords.push((col, row));
-                }
-            }
+    // PARSE_CELL("A1"): returns [col, row] (0-indexed)
+    engine.register_fn(
+        "PARSE_CELL",
+        |cell_str: &str| -> Result<rhai::Array, Box<EvalAltResult>> {
+            let Some(cell_ref) = CellRef::from_str(cell_str) else {
+                return Err(invalid_arg(&format!(
+                    "Invalid cell reference: {}",
+                    cell_str
+                )));
+            };
+            Ok(vec![
+                Dynamic::from(cell_ref.col as i64),
+                Dynamic::from(cell_ref.row as i64),
+            ])
+        },
+    );
+
+    // FORMAT_CELL(col, row): returns "A1" (0-indexed)
+
+    engine.register_fn(
+        "FORMAT_CELL",
+        |col: i64, row: i64| -> Result<String, Box<EvalAltResult>> {
+            let col = to_usize(col, "col")?;
+            let row = to_usize(row, "row")?;
+            Ok(CellRef::new(col, row).to_string())
+        },
+    );
+
+    // ADDRESS(row, col, [abs_mode]): returns "$A$1", "A$1", "$A1" or "A1" (1-indexed, row/col)
+    engine.register_fn("ADDRESS", |row: i64, col: i64| address(row, col, 1));
+    engine.register_fn("ADDRESS", address);
+
+    // PARSE_RANGE("A1:B4"): returns [c1, r1, c2, r2] (0-indexed, col/row)
+
+    engine.register_fn(
+        "PARSE_RANGE",
+        |range: &str| -> Result<rhai::Array, Box<EvalAltResult>> {
+            let Some((c1, r1, c2, r2)) = parse_range(range) else {
+                return Err(invalid_arg(&format!("Invalid range reference: {}", range)));
+            };
+            Ok(vec![
+                Dynamic::from(c1 as i64),
+                Dynamic::from(r1 as i64),
+                Dynamic::from(c2 as i64),
+                Dynamic::from(r2 as i64),
+            ])
+        },
+    );
+
+    // FORMAT_RANGE(c1, r1, c2, r2): returns "A1:B4" (0-indexed)
+
+    engine.register_fn(
+        "FORMAT_RANGE",
+        |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<String, Box<EvalAltResult>> {
+            let c1 = to_usize(c1, "c1")?;
+            let r1 = to_usize(r1, "r1")?;
+            let c2 = to_usize(c2, "c2")?;
+            let r2 = to_usize(r2, "r2")?;
+            Ok(format!("{}:{}", CellRef::new(c1, r1), CellRef::new(c2, r2)))
+        },
+    );
+
+    // SPILL(x): converts ranges to arrays, identity for arrays.
+    // Arrays automatically spill down when returned from a formula.
+    // Function form: SPILL(0..=10) or SPILL(arr)
+    engine.register_fn("SPILL", |arr: rhai::Array| -> rhai::Array { arr });
+
+    engine.register_fn("SPILL", |range: std::ops::Range<i64>| -> rhai::Array {
+        range.map(Dynamic::from).collect()
+    });
+    engine.register_fn(
+        "SPILL",
+        |range: std::ops::RangeInclusive<i64>| -> rhai::Array {
+            range.map(Dynamic::from).collect()
+        },
+    );
+
+    // Method form: (0..=10).SPILL() or arr.SPILL()
+    engine.register_fn("SPILL", |arr: &mut rhai::Array| -> rhai::Array {
+        arr.clone()
+    });
+    engine.register_fn("SPILL", |range: &mut std::ops::Range<i64>| -> rhai::Array {
+        range.clone().map(Dynamic::from).collect()
+    });
+    engine.register_fn(
+        "SPILL",
+        |range: &mut std::ops::RangeInclusive<i64>| -> rhai::Array {
+            range.clone().map(Dynamic::from).collect()
+        },
+    );
+
+    // VEC_RANGE(c1, r1, c2, r2): returns array of cell values
+
+    // Checks spill map first for spilled array values
+    // Respects range direction: VEC(A3:A1) returns [A3, A2, A1]
+    let grid_vec = grid.clone();
+    let cache_vec = value_cache.clone();
+    engine.register_fn(
+        "VEC_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<rhai::Array, Box<EvalAltResult>> {
+            range_to_array(&ctx, &grid_vec, &cache_vec, c1, r1, c2, r2)
+        },
+    );
+
+    // FILTER(c1, r1, c2, r2, predicate): rows whose first-column value passes, spilled as 2D
+    let grid_filter = grid.clone();
+    let cache_filter = value_cache.clone();
+    engine.register_fn(
+        "FILTER_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              pred: FnPtr|
+              -> Result<rhai::Array, Box<EvalAltResult>> {
+            filter_rows(&ctx, &grid_filter, &cache_filter, c1, r1, c2, r2, |key| {
+                pred.call_within_context(&ctx, (key.clone(),))
+                    .unwrap_or(false)
+            })
+        },
+    );
+
+    // FILTER(c1, r1, c2, r2, criterion): rows whose first-column value matches ">5" or "app*"
+    let grid_filter_crit = grid.clone();
+    let cache_filter_crit = value_cache.clone();
+    engine.register_fn(
+        "FILTER_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              criterion: &str|
+              -> Result<rhai::Array, Box<EvalAltResult>> {
+            let criterion = Criterion::parse(criterion)?;
+            filter_rows(
+                &ctx,
+                &grid_filter_crit,
+                &cache_filter_crit,
+                c1,
+                r1,
+                c2,
+                r2,
+                |key| criterion.matches(key),
+            )
+        },
+    );
+
+    // AVERAGEIF(c1, r1, c2, r2, predicate): average values where predicate returns true
+    let grid_avgif = grid.clone();
+    let cache_avgif = value_cache.clone();
+    engine.register_fn(
+        "AVERAGEIF_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              pred: FnPtr|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let (mut sum, mut count) = (0.0, 0);
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let val = cell_value_or_zero(&ctx, &grid_avgif, &cache_avgif, col, row)?;
+                    let pred_result: bool = pred.call_within_context(&ctx, (val,)).unwrap_or(false);
+                    if pred_result {
+                        sum += val;
+                        count += 1;
+                    }
+                }
+            }
+            conditional_average("AVERAGEIF", sum, count)
+        },
+    );
+
+    // AVERAGEIF(c1, r1, c2, r2, criterion): average numeric values matching a criterion
+    let grid_avgif_crit = grid.clone();
+    let cache_avgif_crit = value_cache.clone();
+    engine.register_fn(
+        "AVERAGEIF_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              criterion: &str|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let criterion = Criterion::parse(criterion)?;
+            let (mut sum, mut count) = (0.0, 0);
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let val =
+                        cell_dynamic_value(&ctx, &grid_avgif_crit, &cache_avgif_crit, col, row);
+                    if criterion.matches(&val)
+                        && let Some(n) = dynamic_as_f64(&val)
+                    {
+                        sum += n;
+                        count += 1;
+                    }
+                }
+            }
+            conditional_average("AVERAGEIF", sum, count)
+        },
+    );
+
+    // AVERAGEIFS(avg_range, crit_range, predicate): average avg cells whose paired crit cell passes
+    let grid_avgifs = grid.clone();
+    let cache_avgifs = value_cache.clone();
+    engine.register_fn(
+        "AVERAGEIFS_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64,
+              cc1: i64,
+              cr1: i64,
+              cc2: i64,
+              cr2: i64,
+              pred: FnPtr|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (mut sum, mut count) = (0.0, 0);
+            for (ac, ar, cc, cr) in
+                paired_positions("AVERAGEIFS", ac1, ar1, ac2, ar2, cc1, cr1, cc2, cr2)?
+            {
+                let key = cell_value_or_zero(&ctx, &grid_avgifs, &cache_avgifs, cc, cr)?;
+                let pred_result: bool = pred.call_within_context(&ctx, (key,)).unwrap_or(false);
+                if pred_result {
+                    sum += cell_value_or_zero(&ctx, &grid_avgifs, &cache_avgifs, ac, ar)?;
+                    count += 1;
+                }
+            }
+            conditional_average("AVERAGEIFS", sum, count)
+        },
+    );
+
+    // AVERAGEIFS(avg_range, crit_range, criterion): criterion string form, e.g. "app*" or ">5"
+    let grid_avgifs_crit = grid.clone();
+    let cache_avgifs_crit = value_cache.clone();
+    engine.register_fn(
+        "AVERAGEIFS_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64,
+              cc1: i64,
+              cr1: i64,
+              cc2: i64,
+              cr2: i64,
+              criterion: &str|
+              -> Result<f64, Box<EvalAltResult>> {
+            let criterion = Criterion::parse(criterion)?;
+            let (mut sum, mut count) = (0.0, 0);
+            for (ac, ar, cc, cr) in
+                paired_positions("AVERAGEIFS", ac1, ar1, ac2, ar2, cc1, cr1, cc2, cr2)?
+            {
+                let key = cell_dynamic_value(&ctx, &grid_avgifs_crit, &cache_avgifs_crit, cc, cr);
+                if !criterion.matches(&key) {
+                    continue;
+                }
+                let val = cell_dynamic_value(&ctx, &grid_avgifs_crit, &cache_avgifs_crit, ac, ar);
+                if let Some(n) = dynamic_as_f64(&val) {
+                    sum += n;
+                    count += 1;
+                }
+            }
+            conditional_average("AVERAGEIFS", sum, count)
+        },
+    );
+
+    // MAPRANGE(c1, r1, c2, r2, f): apply f to each cell value, returning a spillable array
+    let grid_maprange = grid.clone();
+    let cache_maprange = value_cache.clone();
+    engine.register_fn(
+        "MAPRANGE_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              f: FnPtr|
+              -> Result<rhai::Array, Box<EvalAltResult>> {
+            range_to_array(&ctx, &grid_maprange, &cache_maprange, c1, r1, c2, r2)?
+                .into_iter()
+                .map(|val| f.call_within_context(&ctx, (val,)))
+                .collect()
+        },
+    );
+
+    // REDUCE(c1, r1, c2, r2, init, f): fold cell values with f(acc, x), starting from init
+    let grid_reduce = grid.clone();
+    let cache_reduce = value_cache.clone();
+    engine.register_fn(
+        "REDUCE_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              init: Dynamic,
+              f: FnPtr|
+              -> Result<Dynamic, Box<EvalAltResult>> {
+            range_to_array(&ctx, &grid_reduce, &cache_reduce, c1, r1, c2, r2)?
+                .into_iter()
+                .try_fold(init, |acc, val| f.call_within_context(&ctx, (acc, val)))
+        },
+    );
+
+    // POW(base, exp): exponentiation
+    // Rhai doesn't have built-in pow for floats, so we register it here
+    // Handle all type combinations since cell values can be int or float
+    engine.register_fn("POW", |base: f64, exp: f64| -> f64 { base.powf(exp) });
+    engine.register_fn("POW", |base: f64, exp: i64| -> f64 {
+        base.powf(exp as f64)
+    });
+    engine.register_fn("POW", |base: i64, exp: f64| -> f64 {
+        (base as f64).powf(exp)
+    });
+    engine.register_fn("POW", |base: i64, exp: i64| -> f64 {
+        (base as f64).powf(exp as f64)
+    });
+
+    // SQRT(x): square root
+    engine.register_fn("SQRT", |x: f64| -> f64 { x.sqrt() });
+    engine.register_fn("SQRT", |x: i64| -> f64 { (x as f64).sqrt() });
+
+    // RAND(): random float in [0.0, 1.0)
+    engine.register_fn("RAND", || -> f64 { rand::thread_rng().r#gen() });
+
+    // RANDINT(min, max): random integer in [min, max] inclusive
+    engine.register_fn(
+        "RANDINT",
+        |min: i64, max: i64| -> Result<i64, Box<EvalAltResult>> {
+            if min > max {
+                return Err(invalid_arg("RANDINT min must be <= max"));
+            }
+            Ok(rand::thread_rng().r#gen_range(min..=max))
+        },
+    );
+
+    // FIXED(n, decimals): format with a fixed number of decimal places.
+    engine.register_fn(
+        "FIXED",
+        |n: f64, decimals: i64| -> Result<String, Box<EvalAltResult>> {
+            let decimals = to_decimal_places(decimals)?;
+            Ok(fixed_decimal_string(n, decimals))
+        },
+    );
+    engine.register_fn(
+        "FIXED",
+        |n: i64, decimals: i64| -> Result<String, Box<EvalAltResult>> {
+            let decimals = to_decimal_places(decimals)?;
+            Ok(fixed_decimal_string(n as f64, decimals))
+        },
+    );
+
+    // MONEY(n, symbol[, decimals]): format as currency (no separators).
+    // Examples:
+    //   MONEY(15.0424, "£")    -> "£15.04"
+    //   MONEY(-2, "$", 0)      -> "-$2"
+    engine.register_fn("MONEY", |n: f64, symbol: &str| -> String {
+        money_string(n, symbol, 2)
+    });
+    engine.register_fn("MONEY", |n: i64, symbol: &str| -> String {
+        money_string(n as f64, symbol, 2)
+    });
+    engine.register_fn(
+        "MONEY",
+        |n: f64, symbol: &str, decimals: i64| -> Result<String, Box<EvalAltResult>> {
+            let decimals = to_decimal_places(decimals)?;
+            Ok(money_string(n, symbol, decimals))
+        },
+    );
+    engine.register_fn(
+        "MONEY",
+        |n: i64, symbol: &str, decimals: i64| -> Result<String, Box<EvalAltResult>> {
+            let decimals = to_decimal_places(decimals)?;
+            Ok(money_string(n as f64, symbol, decimals))
+        },
+    );
+
+    // TODAY(): current date as string
+    engine.register_fn("TODAY", || -> String {
+        chrono::Local::now().format("%Y-%m-%d").to_string()
+    });
+
+    // NOW(): current date and time as string
+    engine.register_fn("NOW", || -> String {
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+    });
+
+    // VOLATILE(x): returns x; the document recomputes such formulas on every recalc tick
+    engine.register_fn("VOLATILE", |value: Dynamic| -> Dynamic { value });
+
+    // DATEDIFF(a, b): signed difference in seconds between two date/datetime strings
+    engine.register_fn(
+        "DATEDIFF",
+        |a: &str, b: &str| -> Result<i64, Box<EvalAltResult>> {
+            let parse = |s: &str| -> Result<chrono::NaiveDateTime, Box<EvalAltResult>> {
+                chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+                    .or_else(|_| {
+                        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                            .map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+                    })
+                    .map_err(|e| {
+                        Box::new(EvalAltResult::ErrorRuntime(
+                            format!("DATEDIFF: invalid date '{}': {}", s, e).into(),
+                            rhai::Position::NONE,
+                        ))
+                    })
+            };
+            let da = parse(a)?;
+            let db = parse(b)?;
+            Ok((da - db).num_seconds())
+        },
+    );
+
+    // DATEVALUE(text): serial day number for a "YYYY-MM-DD" date (time part ignored)
+    engine.register_fn(
+        "DATEVALUE",
+        |text: &str| -> Result<i64, Box<EvalAltResult>> {
+            let text = text.trim();
+            let date = chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .or_else(|_| {
+                    chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+                        .map(|dt| dt.date())
+                })
+                .map_err(|e| invalid_arg(&format!("DATEVALUE: invalid date '{}': {}", text, e)))?;
+            Ok(date_to_serial(date))
+        },
+    );
+
+    // NETWORKDAYS(start, end): weekdays from start to end inclusive (dates as
+    // "YYYY-MM-DD" text or serials); NETWORKDAYS(start, end, H1:H5) also skips holidays
+    engine.register_fn(
+        "NETWORKDAYS",
+        |start: Dynamic, end: Dynamic| -> Result<i64, Box<EvalAltResult>> {
+            let start = date_arg("NETWORKDAYS", &start)?;
+            let end = date_arg("NETWORKDAYS", &end)?;
+            Ok(network_days(start, end, &HashSet::new()))
+        },
+    );
+
+    // WORKDAY(start, days): "YYYY-MM-DD" date `days` weekdays after start (before
+    // if negative); WORKDAY(start, days, H1:H5) also skips holidays
+    engine.register_fn(
+        "WORKDAY",
+        |start: Dynamic, days: Dynamic| -> Result<String, Box<EvalAltResult>> {
+            let start = date_arg("WORKDAY", &start)?;
+            let days = whole_days_arg(&days)?;
+            Ok(work_day(start, days, &HashSet::new())?
+                .format("%Y-%m-%d")
+                .to_string())
+        },
+    );
+
+    // TIMEVALUE(text): fraction of a day for "HH:MM[:SS]" (date part ignored)
+    engine.register_fn(
+        "TIMEVALUE",
+        |text: &str| -> Result<f64, Box<EvalAltResult>> {
+            let text = text.trim();
+            let time_part = text.rsplit(' ').next().unwrap_or(text);
+            let time = chrono::NaiveTime::parse_from_str(time_part, "%H:%M:%S")
+                .or_else(|_| chrono::NaiveTime::parse_from_str(time_part, "%H:%M"))
+                .map_err(|e| invalid_arg(&format!("TIMEVALUE: invalid time '{}': {}", text, e)))?;
+            use chrono::Timelike;
+            Ok(time.num_seconds_from_midnight() as f64 / 86_400.0)
+        },
+    );
+
+    // TONUMBER(text[, default]) / N(text[, default]): parse text as a number
+    // (surrounding whitespace and a leading +/- allowed); numbers pass through.
+    // Without a default, unparseable text is an error.
+    for name in ["TONUMBER", "N"] {
+        engine.register_fn(name, move |text: &str| -> Result<f64, Box<EvalAltResult>> {
+            parse_number_text(text)
+                .ok_or_else(|| invalid_arg(&format!("{}: not a number: '{}'", name, text)))
+        });
+        engine.register_fn(name, |text: &str, default: f64| -> f64 {
+            parse_number_text(text).unwrap_or(default)
+        });
+        engine.register_fn(name, |text: &str, default: i64| -> f64 {
+            parse_number_text(text).unwrap_or(default as f64)
+        });
+        engine.register_fn(name, |n: f64| -> f64 { n });
+        engine.register_fn(name, |n: i64| -> f64 { n as f64 });
+    }
+
+    // TODAY_SERIAL(): today's date as a serial day number
+    engine.register_fn("TODAY_SERIAL", || -> i64 {
+        date_to_serial(chrono::Local::now().date_naive())
+    });
+
+    // TEXT(value, pattern): format a number, e.g. TEXT(1234.5, "#,##0.00"), or a
+    // serial date/time, e.g. TEXT(45658, "yyyy-mm-dd")
+    engine.register_fn("TEXT", text_format);
+    engine.register_fn(
+        "TEXT",
+        |value: i64, pattern: &str| -> Result<String, Box<EvalAltResult>> {
+            text_format(value as f64, pattern)
+        },
+    );
+
+    // ENCODEURL(text) / URLDECODE(text): percent-encode/decode UTF-8 text
+    engine.register_fn("ENCODEURL", |text: &str| -> String {
+        percent_encoding::utf8_percent_encode(text, URL_ENCODE_SET).to_string()
+    });
+    engine.register_fn("URLDECODE", url_decode);
+
+    // BASE64ENCODE(text) / BASE64DECODE(text): standard base64 of UTF-8 text
+    engine.register_fn("BASE64ENCODE", |text: &str| -> String {
+        base64_encode(text)
+    });
+    engine.register_fn("BASE64DECODE", base64_decode);
+
+    // REGEXMATCH(text, pattern): whether the pattern matches anywhere in text
+    engine.register_fn(
+        "REGEXMATCH",
+        |text: &str, pattern: &str| -> Result<bool, Box<EvalAltResult>> {
+            Ok(cached_regex("REGEXMATCH", pattern)?.is_match(text))
+        },
+    );
+
+    // REGEXEXTRACT(text, pattern): first capture group of the first match (the
+    // whole match if the pattern has no groups); errors if nothing matches
+    engine.register_fn(
+        "REGEXEXTRACT",
+        |text: &str, pattern: &str| -> Result<String, Box<EvalAltResult>> {
+            let re = cached_regex("REGEXEXTRACT", pattern)?;
+            let caps = re
+                .captures(text)
+                .ok_or_else(|| invalid_arg("REGEXEXTRACT: no match"))?;
+            let m = caps.get(1).or_else(|| caps.get(0));
+            Ok(m.map(|m| m.as_str().to_string()).unwrap_or_default())
+        },
+    );
+
+    // REGEXREPLACE(text, pattern, replacement): replace every match; `$1`
+    // (or `${name}`) in the replacement refers to capture groups
+    engine.register_fn(
+        "REGEXREPLACE",
+        |text: &str, pattern: &str, replacement: &str| -> Result<String, Box<EvalAltResult>> {
+            let re = cached_regex("REGEXREPLACE", pattern)?;
+            Ok(re.replace_all(text, replacement).into_owned())
+        },
+    );
+
+    // IF(cond, then_val, else_val): conditional expression
+    engine.register_fn(
+        "IF",
+        |cond: bool, then_val: Dynamic, else_val: Dynamic| -> Dynamic {
+            if cond { then_val } else { else_val }
+        },
+    );
+
+    // ISNUMBER(val): true if value is numeric
+    engine.register_fn("ISNUMBER", |val: Dynamic| -> bool {
+        val.is_float() || val.is_int()
+    });
+
+    // ISTEXT(val): true if value is a string
+    engine.register_fn("ISTEXT", |val: Dynamic| -> bool { val.is_string() });
+
+    // ISEMPTY(val): true if value is empty string or unit
+    engine.register_fn("ISEMPTY", |val: Dynamic| -> bool { is_empty_value(&val) });
+
+    // COALESCE(a, b, ...): first argument that is not empty (as ISEMPTY), else the last (up to 5)
+    engine.register_fn("COALESCE", |a: Dynamic| a);
+    engine.register_fn("COALESCE", |a: Dynamic, b: Dynamic| coalesce(vec![a, b]));
+    engine.register_fn("COALESCE", |a: Dynamic, b: Dynamic, c: Dynamic| {
+        coalesce(vec![a, b, c])
+    });
+    engine.register_fn(
+        "COALESCE",
+        |a: Dynamic, b: Dynamic, c: Dynamic, d: Dynamic| coalesce(vec![a, b, c, d]),
+    );
+    engine.register_fn(
+        "COALESCE",
+        |a: Dynamic, b: Dynamic, c: Dynamic, d: Dynamic, e: Dynamic| coalesce(vec![a, b, c, d, e]),
+    );
+
+    // IFERROR(value, fallback): fallback when value raises an error, is NaN/Inf or is an
+    // error display such as #CYCLE!. The preprocessor passes value as a closure so errors
+    // raised while computing it can be caught; a plain value is only checked.
+    engine.register_fn(
+        "IFERROR",
+        |ctx: NativeCallContext, value: FnPtr, fallback: Dynamic| -> Dynamic {
+            match value.call_within_context::<Dynamic>(&ctx, ()) {
+                Ok(value) if !is_error_value(&value) => value,
+                _ => fallback,
+            }
+        },
+    );
+    engine.register_fn("IFERROR", |value: Dynamic, fallback: Dynamic| {
+        if is_error_value(&value) {
+            fallback
+        } else {
+            value
+        }
+    });
+
+    // ROUND(n, decimals): round to N decimal places
+    engine.register_fn(
+        "ROUND",
+        |n: f64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            let decimals = to_decimal_places(decimals)?;
+            let factor = 10_f64.powi(decimals as i32);
+            Ok((n * factor).round() / factor)
+        },
+    );
+    engine.register_fn(
+        "ROUND",
+        |n: i64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            let decimals = to_decimal_places(decimals)?;
+            let factor = 10_f64.powi(decimals as i32);
+            Ok((n as f64 * factor).round() / factor)
+        },
+    );
+
+    // ROUNDUP(n, decimals) / ROUNDDOWN(n, decimals): round away from / toward zero
+    engine.register_fn(
+        "ROUNDUP",
+        |n: f64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            round_directed(n, decimals, true)
+        },
+    );
+    engine.register_fn(
+        "ROUNDUP",
+        |n: i64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            round_directed(n as f64, decimals, true)
+        },
+    );
+    engine.register_fn(
+        "ROUNDDOWN",
+        |n: f64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            round_directed(n, decimals, false)
+        },
+    );
+    engine.register_fn(
+        "ROUNDDOWN",
+        |n: i64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            round_directed(n as f64, decimals, false)
+        },
+    );
+
+    // TRUNC(n) / TRUNC(n, decimals): drop digits past N decimal places (default 0)
+    engine.register_fn("TRUNC", |n: f64| -> f64 { n.trunc() });
+    engine.register_fn("TRUNC", |n: i64| -> f64 { n as f64 });
+    engine.register_fn(
+        "TRUNC",
+        |n: f64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            round_directed(n, decimals, false)
+        },
+    );
+    engine.register_fn(
+        "TRUNC",
+        |n: i64, decimals: i64| -> Result<f64, Box<EvalAltResult>> {
+            round_directed(n as f64, decimals, false)
+        },
+    );
+
+    // MOD(a, b): remainder with the sign of the divisor, e.g. MOD(-7, 3) is 2
+    engine.register_fn("MOD", |a: i64, b: i64| -> Result<i64, Box<EvalAltResult>> {
+        if b == 0 {
+            return Err(invalid_arg("MOD: divisor must not be zero"));
+        }
+        // i64::MIN % -1 overflows, but the remainder is 0
+        let r = a.checked_rem(b).unwrap_or(0);
+        Ok(if r != 0 && (r < 0) != (b < 0) {
+            r + b
+        } else {
+            r
+        })
+    });
+    engine.register_fn("MOD", |a: f64, b: f64| float_mod(a, b));
+    engine.register_fn("MOD", |a: f64, b: i64| float_mod(a, b as f64));
+    engine.register_fn("MOD", |a: i64, b: f64| float_mod(a as f64, b));
+
+    // ABS(n): absolute value
+    engine.register_fn("ABS", |n: f64| -> f64 { n.abs() });
+    engine.register_fn("ABS", |n: i64| -> i64 { n.abs() });
+
+    // FLOOR(x): round down toward negative infinity
+    engine.register_fn("FLOOR", |x: f64| -> f64 { x.floor() });
+    engine.register_fn("FLOOR", |x: i64| -> f64 { x as f64 });
+
+    // CEIL(x): round up toward positive infinity
+    engine.register_fn("CEIL", |x: f64| -> f64 { x.ceil() });
+    engine.register_fn("CEIL", |x: i64| -> f64 { x as f64 });
+
+    // PI(): ratio of circumference to diameter
+    engine.register_fn("PI", || -> f64 { std::f64::consts::PI });
+
+    // E(): Euler's number
+    engine.register_fn("E", || -> f64 { std::f64::consts::E });
+
+    // LN(x): natural logarithm
+    engine.register_fn("LN", |x: f64| -> f64 { x.ln() });
+    engine.register_fn("LN", |x: i64| -> f64 { (x as f64).ln() });
+
+    // LOG(x): base-10 logarithm; LOG(x, base): arbitrary base
+    engine.register_fn("LOG", |x: f64| -> f64 { x.log10() });
+    engine.register_fn("LOG", |x: i64| -> f64 { (x as f64).log10() });
+    engine.register_fn("LOG", |x: f64, base: f64| -> f64 { x.log(base) });
+    engine.register_fn("LOG", |x: i64, base: i64| -> f64 {
+        (x as f64).log(base as f64)
+    });
+    engine.register_fn("LOG", |x: f64, base: i64| -> f64 { x.log(base as f64) });
+    engine.register_fn("LOG", |x: i64, base: f64| -> f64 { (x as f64).log(base) });
+
+    // PCTCHANGE(old, new): relative change (new - old) / old
+    engine.register_fn("PCTCHANGE", |old: f64, new: f64| pct_change(old, new));
+    engine.register_fn("PCTCHANGE", |old: f64, new: i64| {
+        pct_change(old, new as f64)
+    });
+    engine.register_fn("PCTCHANGE", |old: i64, new: f64| {
+        pct_change(old as f64, new)
+    });
+    engine.register_fn("PCTCHANGE", |old: i64, new: i64| {
+        pct_change(old as f64, new as f64)
+    });
+
+    // CAGR(begin, end, periods): compound annual growth rate (end/begin)^(1/periods) - 1
+    engine.register_fn("CAGR", |b: f64, e: f64, p: f64| cagr(b, e, p));
+    engine.register_fn("CAGR", |b: f64, e: f64, p: i64| cagr(b, e, p as f64));
+    engine.register_fn("CAGR", |b: f64, e: i64, p: f64| cagr(b, e as f64, p));
+    engine.register_fn("CAGR", |b: f64, e: i64, p: i64| cagr(b, e as f64, p as f64));
+    engine.register_fn("CAGR", |b: i64, e: f64, p: f64| cagr(b as f64, e, p));
+    engine.register_fn("CAGR", |b: i64, e: f64, p: i64| cagr(b as f64, e, p as f64));
+    engine.register_fn("CAGR", |b: i64, e: i64, p: f64| cagr(b as f64, e as f64, p));
+    engine.register_fn("CAGR", |b: i64, e: i64, p: i64| {
+        cagr(b as f64, e as f64, p as f64)
+    });
+
+    // MIN(a, b) / MAX(a, b): scalar forms; MIN(A1:A3) is rewritten to MIN_RANGE
+    engine.register_fn("MIN", |a: i64, b: i64| -> i64 { a.min(b) });
+    engine.register_fn("MIN", |a: f64, b: f64| -> f64 { a.min(b) });
+    engine.register_fn("MIN", |a: f64, b: i64| -> f64 { a.min(b as f64) });
+    engine.register_fn("MIN", |a: i64, b: f64| -> f64 { (a as f64).min(b) });
+    engine.register_fn("MAX", |a: i64, b: i64| -> i64 { a.max(b) });
+    engine.register_fn("MAX", |a: f64, b: f64| -> f64 { a.max(b) });
+    engine.register_fn("MAX", |a: f64, b: i64| -> f64 { a.max(b as f64) });
+    engine.register_fn("MAX", |a: i64, b: f64| -> f64 { (a as f64).max(b) });
+
+    // CLAMP(x, lo, hi): x limited to [lo, hi]
+    engine.register_fn(
+        "CLAMP",
+        |x: i64, lo: i64, hi: i64| -> Result<i64, Box<EvalAltResult>> {
+            if lo > hi {
+                return Err(invalid_arg("CLAMP: lo must be <= hi"));
+            }
+            Ok(x.clamp(lo, hi))
+        },
+    );
+    engine.register_fn("CLAMP", |x: f64, lo: f64, hi: f64| clamp_value(x, lo, hi));
+    engine.register_fn("CLAMP", |x: f64, lo: f64, hi: i64| {
+        clamp_value(x, lo, hi as f64)
+    });
+    engine.register_fn("CLAMP", |x: f64, lo: i64, hi: f64| {
+        clamp_value(x, lo as f64, hi)
+    });
+    engine.register_fn("CLAMP", |x: f64, lo: i64, hi: i64| {
+        clamp_value(x, lo as f64, hi as f64)
+    });
+    engine.register_fn("CLAMP", |x: i64, lo: f64, hi: f64| {
+        clamp_value(x as f64, lo, hi)
+    });
+    engine.register_fn("CLAMP", |x: i64, lo: f64, hi: i64| {
+        clamp_value(x as f64, lo, hi as f64)
+    });
+    engine.register_fn("CLAMP", |x: i64, lo: i64, hi: f64| {
+        clamp_value(x as f64, lo as f64, hi)
+    });
+
+    // COMBIN(n, k): n choose k; PERMUT(n, k): ordered selections; FACT(n): n!
+    // Cell values arrive as floats, so those overloads take whole numbers only
+    let choose_fns: [(&str, ChooseFn); 2] = [("COMBIN", combin), ("PERMUT", permut)];
+    for (name, choose) in choose_fns {
+        engine.register_fn(name, choose);
+        engine.register_fn(name, move |n: f64, k: f64| {
+            choose(
+                whole_number_arg(name, "n", n)?,
+                whole_number_arg(name, "k", k)?,
+            )
+        });
+        engine.register_fn(name, move |n: f64, k: i64| {
+            choose(whole_number_arg(name, "n", n)?, k)
+        });
+        engine.register_fn(name, move |n: i64, k: f64| {
+            choose(n, whole_number_arg(name, "k", k)?)
+        });
+    }
+    engine.register_fn("FACT", fact);
+    engine.register_fn("FACT", |n: f64| fact(whole_number_arg("FACT", "n", n)?));
+
+    // BITAND(a, b) / BITOR(a, b) / BITXOR(a, b): bitwise ops on integers in [0, 2^48)
+    let bit_ops: [(&str, BitOp); 3] = [
+        ("BITAND", |a, b| a & b),
+        ("BITOR", |a, b| a | b),
+        ("BITXOR", |a, b| a ^ b),
+    ];
+    for (name, op) in bit_ops {
+        engine.register_fn(name, move |a: i64, b: i64| {
+            bitwise(name, a as f64, b as f64, op)
+        });
+        engine.register_fn(name, move |a: f64, b: f64| bitwise(name, a, b, op));
+        engine.register_fn(name, move |a: f64, b: i64| bitwise(name, a, b as f64, op));
+        engine.register_fn(name, move |a: i64, b: f64| bitwise(name, a as f64, b, op));
+    }
+
+    // BITLSHIFT(n, bits) / BITRSHIFT(n, bits): shift n by bits (negative bits shift the other way)
+    for (name, sign) in [("BITLSHIFT", 1.0), ("BITRSHIFT", -1.0)] {
+        engine.register_fn(name, move |n: i64, bits: i64| {
+            bit_shift(name, n as f64, sign * bits as f64)
+        });
+        engine.register_fn(name, move |n: f64, bits: f64| {
+            bit_shift(name, n, sign * bits)
+        });
+        engine.register_fn(name, move |n: f64, bits: i64| {
+            bit_shift(name, n, sign * bits as f64)
+        });
+        engine.register_fn(name, move |n: i64, bits: f64| {
+            bit_shift(name, n as f64, sign * bits)
+        });
+    }
+
+    // DEC2HEX(n, [width]) / DEC2BIN(n, [width]): "FF" / "1010"; HEX2DEC(text) / BIN2DEC(text)
+    for (name, radix) in [("DEC2HEX", 16), ("DEC2BIN", 2)] {
+        engine.register_fn(name, move |n: i64| to_base(name, n as f64, radix, None));
+        engine.register_fn(name, move |n: f64| to_base(name, n, radix, None));
+        engine.register_fn(name, move |n: i64, width: i64| {
+            to_base(name, n as f64, radix, Some(width))
+        });
+        engine.register_fn(name, move |n: f64, width: i64| {
+            to_base(name, n, radix, Some(width))
+        });
+    }
+    for (name, radix) in [("HEX2DEC", 16), ("BIN2DEC", 2)] {
+        engine.register_fn(name, move |text: &str| from_base(name, text, radix));
+    }
+
+    // SUMIF(c1, r1, c2, r2, predicate): sum values where predicate returns true
+    let grid_sumif = grid.clone();
+    let cache_sumif = value_cache.clone();
+    engine.register_fn(
+        "SUMIF_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              pred: FnPtr|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut sum = 0.0;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let _cell_ref = CellRef::new(col, row);
+                    let val = cell_value_or_zero(&ctx, &grid_sumif, &cache_sumif, col, row)?;
+                    let pred_result: bool = pred.call_within_context(&ctx, (val,)).unwrap_or(false);
+                    if pred_result {
+                        sum += val;
+                    }
+                }
+            }
+            Ok(sum)
+        },
+    );
+
+    // COUNTIF(c1, r1, c2, r2, predicate): count cells where predicate returns true
+    let grid_countif = grid.clone();
+    let cache_countif = value_cache.clone();
+    engine.register_fn(
+        "COUNTIF_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              pred: FnPtr|
+              -> Result<i64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut count = 0;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let _cell_ref = CellRef::new(col, row);
+                    let val = cell_value_or_zero(&ctx, &grid_countif, &cache_countif, col, row)?;
+                    let pred_result: bool = pred.call_within_context(&ctx, (val,)).unwrap_or(false);
+                    if pred_result {
+                        count += 1;
+                    }
+                }
+            }
+            Ok(count)
+        },
+    );
+
+    // SUMIF(c1, r1, c2, r2, criterion): sum values matching a criterion like ">5" or "app*"
+    let grid_sumif_crit = grid.clone();
+    let cache_sumif_crit = value_cache.clone();
+    engine.register_fn(
+        "SUMIF_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              criterion: &str|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let criterion = Criterion::parse(criterion)?;
+            let mut sum = 0.0;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let val =
+                        cell_dynamic_value(&ctx, &grid_sumif_crit, &cache_sumif_crit, col, row);
+                    if criterion.matches(&val)
+                        && let Some(n) = dynamic_as_f64(&val)
+                    {
+                        sum += n;
+                    }
+                }
+            }
+            Ok(sum)
+        },
+    );
+
+    // COUNTIF(c1, r1, c2, r2, criterion): count cells matching a criterion like ">5" or "app*"
+    let grid_countif_crit = grid.clone();
+    let cache_countif_crit = value_cache.clone();
+    engine.register_fn(
+        "COUNTIF_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              criterion: &str|
+              -> Result<i64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let criterion = Criterion::parse(criterion)?;
+            let mut count = 0;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let val =
+                        cell_dynamic_value(&ctx, &grid_countif_crit, &cache_countif_crit, col, row);
+                    if criterion.matches(&val) {
+                        count += 1;
+                    }
+                }
+            }
+            Ok(count)
+        },
+    );
+
+    // COUNTIFS(range1, crit1, ...): count positions where every criterion holds, up to 3 pairs
+    let grid_countifs = grid.clone();
+    let cache_countifs = value_cache.clone();
+    engine.register_fn(
+        "COUNTIFS_RANGES",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              crit: &str|
+              -> Result<i64, Box<EvalAltResult>> {
+            count_ifs(
+                &ctx,
+                &grid_countifs,
+                &cache_countifs,
+                &[((c1, r1, c2, r2), crit)],
+            )
+        },
+    );
+    let grid_countifs2 = grid.clone();
+    let cache_countifs2 = value_cache.clone();
+    engine.register_fn(
+        "COUNTIFS_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64,
+              a_crit: &str,
+              bc1: i64,
+              br1: i64,
+              bc2: i64,
+              br2: i64,
+              b_crit: &str|
+              -> Result<i64, Box<EvalAltResult>> {
+            count_ifs(
+                &ctx,
+                &grid_countifs2,
+                &cache_countifs2,
+                &[
+                    ((ac1, ar1, ac2, ar2), a_crit),
+                    ((bc1, br1, bc2, br2), b_crit),
+                ],
+            )
+        },
+    );
+    let grid_countifs3 = grid.clone();
+    let cache_countifs3 = value_cache.clone();
+    engine.register_fn(
+        "COUNTIFS_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64,
+              a_crit: &str,
+              bc1: i64,
+              br1: i64,
+              bc2: i64,
+              br2: i64,
+              b_crit: &str,
+              cc1: i64,
+              cr1: i64,
+              cc2: i64,
+              cr2: i64,
+              c_crit: &str|
+              -> Result<i64, Box<EvalAltResult>> {
+            count_ifs(
+                &ctx,
+                &grid_countifs3,
+                &cache_countifs3,
+                &[
+                    ((ac1, ar1, ac2, ar2), a_crit),
+                    ((bc1, br1, bc2, br2), b_crit),
+                    ((cc1, cr1, cc2, cr2), c_crit),
+                ],
+            )
+        },
+    );
+
+    // SUMPRODUCT(range1, ...): multiply up to 4 same-sized ranges element-wise and sum
+    let grid_sumproduct = grid.clone();
+    let cache_sumproduct = value_cache.clone();
+    engine.register_fn(
+        "SUMPRODUCT_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            sum_product(
+                &ctx,
+                &grid_sumproduct,
+                &cache_sumproduct,
+                &[(ac1, ar1, ac2, ar2)],
+            )
+        },
+    );
+    let grid_sumproduct2 = grid.clone();
+    let cache_sumproduct2 = value_cache.clone();
+    engine.register_fn(
+        "SUMPRODUCT_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64,
+              bc1: i64,
+              br1: i64,
+              bc2: i64,
+              br2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            sum_product(
+                &ctx,
+                &grid_sumproduct2,
+                &cache_sumproduct2,
+                &[(ac1, ar1, ac2, ar2), (bc1, br1, bc2, br2)],
+            )
+        },
+    );
+    let grid_sumproduct3 = grid.clone();
+    let cache_sumproduct3 = value_cache.clone();
+    engine.register_fn(
+        "SUMPRODUCT_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64,
+              bc1: i64,
+              br1: i64,
+              bc2: i64,
+              br2: i64,
+              cc1: i64,
+              cr1: i64,
+              cc2: i64,
+              cr2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            sum_product(
+                &ctx,
+                &grid_sumproduct3,
+                &cache_sumproduct3,
+                &[
+                    (ac1, ar1, ac2, ar2),
+                    (bc1, br1, bc2, br2),
+                    (cc1, cr1, cc2, cr2),
+                ],
+            )
+        },
+    );
+    let grid_sumproduct4 = grid.clone();
+    let cache_sumproduct4 = value_cache.clone();
+    engine.register_fn(
+        "SUMPRODUCT_RANGES",
+        move |ctx: NativeCallContext,
+              ac1: i64,
+              ar1: i64,
+              ac2: i64,
+              ar2: i64,
+              bc1: i64,
+              br1: i64,
+              bc2: i64,
+              br2: i64,
+              cc1: i64,
+              cr1: i64,
+              cc2: i64,
+              cr2: i64,
+              dc1: i64,
+              dr1: i64,
+              dc2: i64,
+              dr2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            sum_product(
+                &ctx,
+                &grid_sumproduct4,
+                &cache_sumproduct4,
+                &[
+                    (ac1, ar1, ac2, ar2),
+                    (bc1, br1, bc2, br2),
+                    (cc1, cr1, cc2, cr2),
+                    (dc1, dr1, dc2, dr2),
+                ],
+            )
+        },
+    );
+
+    // PRODUCT_RANGE(c1, r1, c2, r2): product of numeric values in range
+    let grid_product = grid.clone();
+    let cache_product = value_cache.clone();
+    engine.register_fn(
+        "PRODUCT_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut product = 1.0;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    product *= cell_value_or_zero(&ctx, &grid_product, &cache_product, col, row)?;
+                }
+            }
+            Ok(product)
+        },
+    );
+
+    // MEDIAN_RANGE(c1, r1, c2, r2): median of numeric values in range
+    let grid_median = grid.clone();
+    let cache_median = value_cache.clone();
+    engine.register_fn(
+        "MEDIAN_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut values = Vec::new();
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    values.push(cell_value_or_zero(
+                        &ctx,
+                        &grid_median,
+                        &cache_median,
+                        col,
+                        row,
+                    )?);
+                }
+            }
+            if values.is_empty() {
+                return Ok(0.0);
+            }
+            values.sort_by(f64::total_cmp);
+            let n = values.len();
+            if n % 2 == 1 {
+                Ok(values[n / 2])
+            } else {
+                Ok((values[n / 2 - 1] + values[n / 2]) / 2.0)
+            }
+        },
+    );
+
+    // GEOMEAN_RANGE(c1, r1, c2, r2): geometric mean using exp(avg(ln(values)))
+    let grid_geomean = grid.clone();
+    let cache_geomean = value_cache.clone();
+    engine.register_fn(
+        "GEOMEAN_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut ln_sum = 0.0;
+            let mut count = 0usize;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let val = cell_value_or_zero(&ctx, &grid_geomean, &cache_geomean, col, row)?;
+                    if val <= 0.0 {
+                        return Err(invalid_arg("GEOMEAN: all values must be positive"));
+                    }
+                    ln_sum += val.ln();
+                    count += 1;
+                }
+            }
+            if count == 0 {
+                return Ok(0.0);
+            }
+            Ok((ln_sum / count as f64).exp())
+        },
+    );
+
+    // STDEV/VAR (sample) and STDEVP/VARP (population) over a range
+    for (name, rhai_name, sample, root) in [
+        ("STDEV", "STDEV_RANGE", true, true),
+        ("STDEVP", "STDEVP_RANGE", false, true),
+        ("VAR", "VAR_RANGE", true, false),
+        ("VARP", "VARP_RANGE", false, false),
+    ] {
+        let grid_spread = grid.clone();
+        let cache_spread = value_cache.clone();
+        engine.register_fn(
+            rhai_name,
+            move |ctx: NativeCallContext,
+                  c1: i64,
+                  r1: i64,
+                  c2: i64,
+                  r2: i64|
+                  -> Result<f64, Box<EvalAltResult>> {
+                let values =
+                    range_values_or_zero(&ctx, &grid_spread, &cache_spread, c1, r1, c2, r2)?;
+                let variance = variance(name, &values, sample)?;
+                Ok(if root { variance.sqrt() } else { variance })
+            },
+        );
+    }
+
+    // TRIMMEAN(c1, r1, c2, r2, fraction): mean after trimming `fraction` of values from the tails
+    let grid_trimmean = grid.clone();
+    let cache_trimmean = value_cache.clone();
+    engine.register_fn(
+        "TRIMMEAN_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              fraction: f64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let values =
+                range_values_or_zero(&ctx, &grid_trimmean, &cache_trimmean, c1, r1, c2, r2)?;
+            trim_mean(values, fraction)
+        },
+    );
+    let grid_trimmean_int = grid.clone();
+    let cache_trimmean_int = value_cache.clone();
+    engine.register_fn(
+        "TRIMMEAN_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              fraction: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let values = range_values_or_zero(
+                &ctx,
+                &grid_trimmean_int,
+                &cache_trimmean_int,
+                c1,
+                r1,
+                c2,
+                r2,
+            )?;
+            trim_mean(values, fraction as f64)
+        },
+    );
+
+    // WEIGHTEDMEDIAN(values_range, weights_range): median where each value counts by its weight
+    let grid_wmedian = grid.clone();
+    let cache_wmedian = value_cache.clone();
+    engine.register_fn(
+        "WEIGHTEDMEDIAN_RANGES",
+        move |ctx: NativeCallContext,
+              vc1: i64,
+              vr1: i64,
+              vc2: i64,
+              vr2: i64,
+              wc1: i64,
+              wr1: i64,
+              wc2: i64,
+              wr2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let pairs = paired_positions("WEIGHTEDMEDIAN", vc1, vr1, vc2, vr2, wc1, wr1, wc2, wr2)?
+                .into_iter()
+                .map(|(vc, vr, wc, wr)| {
+                    Ok((
+                        cell_value_or_zero(&ctx, &grid_wmedian, &cache_wmedian, vc, vr)?,
+                        cell_value_or_zero(&ctx, &grid_wmedian, &cache_wmedian, wc, wr)?,
+                    ))
+                })
+                .collect::<Result<_, Box<EvalAltResult>>>()?;
+            weighted_median(pairs)
+        },
+    );
+
+    // SLOPE(ys_range, xs_range) / INTERCEPT(ys_range, xs_range): least-squares line fit
+    for (name, rhai_name, pick) in [
+        (
+            "SLOPE",
+            "SLOPE_RANGES",
+            (|(slope, _)| slope) as fn((f64, f64)) -> f64,
+        ),
+        ("INTERCEPT", "INTERCEPT_RANGES", |(_, intercept)| intercept),
+    ] {
+        let grid_fit = grid.clone();
+        let cache_fit = value_cache.clone();
+        engine.register_fn(
+            rhai_name,
+            move |ctx: NativeCallContext,
+                  yc1: i64,
+                  yr1: i64,
+                  yc2: i64,
+                  yr2: i64,
+                  xc1: i64,
+                  xr1: i64,
+                  xc2: i64,
+                  xr2: i64|
+                  -> Result<f64, Box<EvalAltResult>> {
+                let points = regression_points(
+                    &ctx, &grid_fit, &cache_fit, name, yc1, yr1, yc2, yr2, xc1, xr1, xc2, xr2,
+                )?;
+                linear_fit(name, &points).map(pick)
+            },
+        );
+    }
+
+    // FORECAST(x, ys_range, xs_range): y predicted at x by the least-squares line
+    let grid_forecast = grid.clone();
+    let cache_forecast = value_cache.clone();
+    engine.register_fn(
+        "FORECAST_RANGES",
+        move |ctx: NativeCallContext,
+              x: Dynamic,
+              yc1: i64,
+              yr1: i64,
+              yc2: i64,
+              yr2: i64,
+              xc1: i64,
+              xr1: i64,
+              xc2: i64,
+              xr2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let Some(x) = dynamic_as_f64(&x) else {
+                return Err(invalid_arg("FORECAST: x must be a number"));
+            };
+            let points = regression_points(
+                &ctx,
+                &grid_forecast,
+                &cache_forecast,
+                "FORECAST",
+                yc1,
+                yr1,
+                yc2,
+                yr2,
+                xc1,
+                xr1,
+                xc2,
+                xr2,
+            )?;
+            let (slope, intercept) = linear_fit("FORECAST", &points)?;
+            Ok(slope * x + intercept)
+        },
+    );
+
+    // DSUM(database, field, criteria): sum a field over records matching a criteria block
+    let grid_dsum = grid.clone();
+    let cache_dsum = value_cache.clone();
+    engine.register_fn(
+        "DSUM_RANGES",
+        move |ctx: NativeCallContext,
+              dc1: i64,
+              dr1: i64,
+              dc2: i64,
+              dr2: i64,
+              field: Dynamic,
+              kc1: i64,
+              kr1: i64,
+              kc2: i64,
+              kr2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(dc1, dr1, dc2, dr2)?;
+            let header =
+                database_header(&ctx, &grid_dsum, &cache_dsum, (min_row, min_col, max_col));
+            let col = database_field_column("DSUM", &header, &field)?;
+            let rows = database_matching_rows(
+                &ctx,
+                &grid_dsum,
+                &cache_dsum,
+                "DSUM",
+                &header,
+                (min_row, max_row),
+                (kc1, kr1, kc2, kr2),
+            )?;
+            Ok(rows
+                .into_iter()
+                .filter_map(|row| {
+                    dynamic_as_f64(&cell_dynamic_value(&ctx, &grid_dsum, &cache_dsum, col, row))
+                })
+                .sum())
+        },
+    );
+
+    // PERCENTRANK(c1, r1, c2, r2, x): percentile rank of x among the range's numbers
+    let grid_prank = grid.clone();
+    let cache_prank = value_cache.clone();
+    engine.register_fn(
+        "PERCENTRANK_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              x: f64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let values = range_numbers(&ctx, &grid_prank, &cache_prank, c1, r1, c2, r2)?;
+            percent_rank(values, x)
+        },
+    );
+    let grid_prank_int = grid.clone();
+    let cache_prank_int = value_cache.clone();
+    engine.register_fn(
+        "PERCENTRANK_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              x: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let values = range_numbers(&ctx, &grid_prank_int, &cache_prank_int, c1, r1, c2, r2)?;
+            percent_rank(values, x as f64)
+        },
+    );
+
+    // ZSCORE(x, c1, r1, c2, r2): (x - mean) / sample stdev of the range's numbers
+    let grid_zscore = grid.clone();
+    let cache_zscore = value_cache.clone();
+    engine.register_fn(
+        "ZSCORE_RANGE",
+        move |ctx: NativeCallContext,
+              x: f64,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let values = range_numbers(&ctx, &grid_zscore, &cache_zscore, c1, r1, c2, r2)?;
+            z_score(x, &values)
+        },
+    );
+    let grid_zscore_int = grid.clone();
+    let cache_zscore_int = value_cache.clone();
+    engine.register_fn(
+        "ZSCORE_RANGE",
+        move |ctx: NativeCallContext,
+              x: i64,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<f64, Box<EvalAltResult>> {
+            let values = range_numbers(&ctx, &grid_zscore_int, &cache_zscore_int, c1, r1, c2, r2)?;
+            z_score(x as f64, &values)
+        },
+    );
+
+    let grid_networkdays = grid.clone();
+    let cache_networkdays = value_cache.clone();
+    engine.register_fn(
+        "NETWORKDAYS_RANGE",
+        move |ctx: NativeCallContext,
+              start: Dynamic,
+              end: Dynamic,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<i64, Box<EvalAltResult>> {
+            let start = date_arg("NETWORKDAYS", &start)?;
+            let end = date_arg("NETWORKDAYS", &end)?;
+            let holidays = range_dates(
+                &ctx,
+                &grid_networkdays,
+                &cache_networkdays,
+                "NETWORKDAYS",
+                c1,
+                r1,
+                c2,
+                r2,
+            )?;
+            Ok(network_days(start, end, &holidays))
+        },
+    );
+    let grid_workday = grid.clone();
+    let cache_workday = value_cache.clone();
+    engine.register_fn(
+        "WORKDAY_RANGE",
+        move |ctx: NativeCallContext,
+              start: Dynamic,
+              days: Dynamic,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<String, Box<EvalAltResult>> {
+            let start = date_arg("WORKDAY", &start)?;
+            let days = whole_days_arg(&days)?;
+            let holidays = range_dates(
+                &ctx,
+                &grid_workday,
+                &cache_workday,
+                "WORKDAY",
+                c1,
+                r1,
+                c2,
+                r2,
+            )?;
+            Ok(work_day(start, days, &holidays)?
+                .format("%Y-%m-%d")
+                .to_string())
+        },
+    );
+
+    // CONCAT_RANGE(c1, r1, c2, r2): concatenate cell values; optional separator
+    let grid_concat = grid.clone();
+    let cache_concat = value_cache.clone();
+    engine.register_fn(
+        "CONCAT_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64|
+              -> Result<String, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut parts = Vec::new();
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let cell_ref = CellRef::new(col, row);
+                    if let Some(cached_val) = cache_concat.get(&cell_ref) {
+                        parts.push(cached_val.to_string());
+                        continue;
+                    }
+                    if let Some(cell) = grid_concat.get(&cell_ref) {
+                        match &cell.contents {
+                            CellType::Empty => {}
+                            CellType::Number(n) => parts.push(n.to_string()),
+                            CellType::Text(s) => parts.push(s.clone()),
+                            CellType::Script(s) => {
+                                let processed = preprocess_script(s);
+                                if let Ok(val) = ctx.engine().eval::<Dynamic>(&processed) {
+                                    parts.push(val.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(parts.join(""))
+        },
+    );
+
+    let grid_concat_sep = grid.clone();
+    let cache_concat_sep = value_cache.clone();
+    engine.register_fn(
+        "CONCAT_RANGE",
+        move |ctx: NativeCallContext,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              sep: &str|
+              -> Result<String, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let mut parts = Vec::new();
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let cell_ref = CellRef::new(col, row);
+                    if let Some(cached_val) = cache_concat_sep.get(&cell_ref) {
+                        parts.push(cached_val.to_string());
+                        continue;
+                    }
+                    if let Some(cell) = grid_concat_sep.get(&cell_ref) {
+                        match &cell.contents {
+                            CellType::Empty => {}
+                            CellType::Number(n) => parts.push(n.to_string()),
+                            CellType::Text(s) => parts.push(s.clone()),
+                            CellType::Script(s) => {
+                                let processed = preprocess_script(s);
+                                if let Ok(val) = ctx.engine().eval::<Dynamic>(&processed) {
+                                    parts.push(val.to_string());
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(parts.join(sep))
+        },
+    );
+
+    // LOOKUP_IMPL(value, sc1, sr1, sc2, sr2, rc1, rr1, rc2, rr2):
+    // Search for value in search range, return corresponding cell from return range.
+    let grid_lookup = grid.clone();
+    let cache_lookup = value_cache.clone();
+    engine.register_fn(
+        "LOOKUP_IMPL",
+        move |ctx: NativeCallContext,
+              value: Dynamic,
+              sc1: i64,
+              sr1: i64,
+              sc2: i64,
+              sr2: i64,
+              rc1: i64,
+              rr1: i64,
+              rc2: i64,
+              rr2: i64|
+              -> Result<Dynamic, Box<EvalAltResult>> {
+            let (s_min_row, s_max_row, s_min_col, s_max_col) =
+                normalize_range_coords(sc1, sr1, sc2, sr2)?;
+            let (r_min_row, r_max_row, r_min_col, r_max_col) =
+                normalize_range_coords(rc1, rr1, rc2, rr2)?;
+
+            // Collect search range coordinates
+            let mut search_coords = Vec::new();
+            for row in s_min_row..=s_max_row {
+                for col in s_min_col..=s_max_col {
+                    search_coords.push((col, row));
+                }
+            }
+            // Collect return range coordinates
+            let mut return_coords = Vec::new();
+            for row in r_min_row..=r_max_row {
+                for col in r_min_col..=r_max_col {
+                    return_coords.push((col, row));
+                }
+            }
+
+            if search_coords.len() != return_coords.len() {
+                return Err(invalid_arg(
+                    "LOOKUP: search and return ranges must have the same size",
+                ));
+            }
+
+            // Helper: get Dynamic value at (col, row)
+            let get_dynamic = |col: usize, row: usize| -> Dynamic {
+                let cell_ref = CellRef::new(col, row);
+                if let Some(cached_val) = cache_lookup.get(&cell_ref) {
+                    return cached_val.clone();
+                }
+                let Some(entry) = grid_lookup.get(&cell_ref) else {
+                    return Dynamic::from("".to_string());
+                };
+                match &entry.contents {
+                    CellType::Empty => Dynamic::from("".to_string()),
+                    CellType::Number(n) => Dynamic::from(*n),
+                    CellType::Text(s) => Dynamic::from(s.clone()),
+                    CellType::Script(s) => {
+                        let processed = preprocess_script(s);
+                        ctx.engine()
+                            .eval::<Dynamic>(&processed)
+                            .unwrap_or(Dynamic::UNIT)
+                    }
+                }
+            };
+
+            // Search for matching value
+            for (i, &(col, row)) in search_coords.iter().enumerate() {
+                let cell_val = get_dynamic(col, row);
+                if lookup_matches(&value, &cell_val) {
+                    let (rcol, rrow) = return_coords[i];
+                    return Ok(get_dynamic(rcol, rrow));
+                }
+            }
+
+            Err(invalid_arg("LOOKUP: value not found"))
+        },
+    );
+
+    // VLOOKUP_IMPL(value, c1, r1, c2, r2, col_index, exact):
+    // Search the first column of the table for value and return the cell
+    // col_index columns across (1 is the first column). Without an exact
+    // match, the first column must be sorted ascending and the last row not
+    // exceeding value is used.
+    let grid_vlookup = grid.clone();
+    let cache_vlookup = value_cache.clone();
+    engine.register_fn(
+        "VLOOKUP_IMPL",
+        move |ctx: NativeCallContext,
+              value: Dynamic,
+              c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              col_index: Dynamic,
+              exact: Dynamic|
+              -> Result<Dynamic, Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+            let col_index = match dynamic_as_f64(&col_index) {
+                Some(n) if n.is_finite() && n.fract() == 0.0 => n as i64,
+                _ => {
+                    return Err(invalid_arg(&format!(
+                        "VLOOKUP: col_index must be a whole number, got '{}'",
+                        col_index
+                    )));
+                }
+            };
+            let exact = match (exact.as_bool(), dynamic_as_f64(&exact)) {
+                (Ok(flag), _) => flag,
+                (_, Some(n)) => n != 0.0,
+                _ => {
+                    return Err(invalid_arg(&format!(
+                        "VLOOKUP: exact must be true/false or a number, got '{}'",
+                        exact
+                    )));
+                }
+            };
+            let width = max_col - min_col + 1;
+            let offset = from_one_based(col_index, "VLOOKUP: col_index")? as usize;
+            if offset >= width {
+                return Err(invalid_arg(&format!(
+                    "VLOOKUP: col_index {} exceeds table width {}",
+                    col_index, width
+                )));
+            }
+
+            let key =
+                |row: usize| cell_dynamic_value(&ctx, &grid_vlookup, &cache_vlookup, min_col, row);
+            let found = if exact {
+                (min_row..=max_row).find(|&row| lookup_matches(&value, &key(row)))
+            } else {
+                let mut found = None;
+                for row in min_row..=max_row {
+                    match lookup_order(&key(row), &value) {
+                        Some(std::cmp::Ordering::Greater) => break,
+                        Some(_) => found = Some(row),
+                        None => {}
+                    }
+                }
+                found
+            };
+
+            match found {
+                Some(row) => Ok(cell_dynamic_value(
+                    &ctx,
+                    &grid_vlookup,
+                    &cache_vlookup,
+                    min_col + offset,
+                    row,
+                )),
+                None => Err(invalid_arg("VLOOKUP: value not found")),
+            }
+        },
+    );
+}
+
+/// Register spill introspection builtins backed by the document's spill map.
+/// These read spill state directly rather than through cell references, so a
+/// formula using them is not recomputed when the spill it inspects changes.
+pub fn register_spill_builtins(engine: &mut Engine, spill_sources: SpillMap) {
+    fn parse_cell_arg(cell_str: &str) -> Result<CellRef, Box<EvalAltResult>> {
+        CellRef::from_str(cell_str)
+            .ok_or_else(|| invalid_arg(&format!("Invalid cell reference: {}", cell_str)))
+    }
+
+    // ISSPILL("A2"): true if the cell holds a value spilled from an array formula
+    let spills = spill_sources.clone();
+    engine.register_fn(
+        "ISSPILL",
+        move |cell_str: &str| -> Result<bool, Box<EvalAltResult>> {
+            Ok(spills.contains_key(&parse_cell_arg(cell_str)?))
+        },
+    );
+
+    // SPILLSOURCE("A2"): the formula cell a spilled value came from ("A1"), or ""
+    engine.register_fn(
+        "SPILLSOURCE",
+        move |cell_str: &str| -> Result<String, Box<EvalAltResult>> {
+            let cell_ref = parse_cell_arg(cell_str)?;
+            Ok(spill_sources
+                .get(&cell_ref)
+                .map(|source| source.to_string())
+                .unwrap_or_default())
+        },
+    );
+}
+
+/// Register builtins that read the document's error displays (see [`ErrorMap`]).
+pub fn register_error_builtins(engine: &mut Engine, errors: ErrorMap) {
+    // ERRORTYPE("A1"): code of the error shown in a cell (1=#ERR, 2=#CYCLE!,
+    // 3=#SPILL!, 4=#REF!, 5=#INF!, 6=#NAN!, 7=#CALC!, 8=#DIV/0!); errors if it shows a value
+    engine.register_fn(
+        "ERRORTYPE",
+        move |cell_str: &str| -> Result<i64, Box<EvalAltResult>> {
+            let cell_ref = CellRef::from_str(cell_str)
+                .ok_or_else(|| invalid_arg(&format!("Invalid cell reference: {}", cell_str)))?;
+            errors
+                .get(&cell_ref)
+                .and_then(|display| error_type_code(&display))
+                .ok_or_else(|| invalid_arg(&format!("ERRORTYPE: {} is not an error", cell_str)))
+        },
+    );
+}
+
+/// Tracks cell modifications made by script builtins.
+/// Maps CellRef -> (old_cell, new_cell) to support undo.
+type ScriptModificationMap = HashMap<CellRef, (Option<Cell>, Option<Cell>)>;
+type ScriptModificationGuard<'a> = std::sync::MutexGuard<'a, ScriptModificationMap>;
+pub type ScriptModifications = Arc<Mutex<ScriptModificationMap>>;
+
+/// Register script-only write builtins for script execution.
+/// These are NOT available in cell formulas, only when running scripts via :call or :rhai.
+pub fn register_script_builtins(
+    engine: &mut Engine,
+    grid: Grid,
+    modifications: ScriptModifications,
+) {
+    // SET_CELL(col, row, value) - Set cell by column/row indices
+    let grid_set = grid.clone();
+    let mods_set = modifications.clone();
+    engine.register_fn(
+        "SET_CELL",
+        move |col: i64, row: i64, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let col = to_grid_index(col, "col")?;
+            let row = to_grid_index(row, "row")?;
+            let cell_ref = CellRef::new(col, row);
+            let new_cell = dynamic_to_cell(value);
+            let mut mods = lock_script_modifications(&mods_set)?;
+
+            let old_cell = grid_set.get(&cell_ref).map(|r| r.clone());
+            grid_set.insert(cell_ref.clone(), new_cell.clone());
+
+            mods.entry(cell_ref)
+                .and_modify(|(_, nc)| *nc = Some(new_cell.clone()))
+                .or_insert((old_cell, Some(new_cell)));
+
+            Ok(())
+        },
+    );
+
+    // SET_CELL("A1", value) - Set cell by A1 notation
+    let grid_set_a1 = grid.clone();
+    let mods_set_a1 = modifications.clone();
+    engine.register_fn(
+        "SET_CELL",
+        move |cell_str: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let Some(cell_ref) = CellRef::from_str(cell_str) else {
+                return Err(invalid_arg(&format!(
+                    "Invalid cell reference: {}",
+                    cell_str
+                )));
+            };
+            let mut mods = lock_script_modifications(&mods_set_a1)?;
+            let old_cell = grid_set_a1.get(&cell_ref).map(|r| r.clone());
+
+            let new_cell = dynamic_to_cell(value);
+            grid_set_a1.insert(cell_ref.clone(), new_cell.clone());
+
+            mods.entry(cell_ref)
+                .and_modify(|(_, nc)| *nc = Some(new_cell.clone()))
+                .or_insert((old_cell, Some(new_cell)));
+
+            Ok(())
+        },
+    );
+
+    // CLEAR_CELL(col, row) - Clear cell by column/row indices
+    let grid_clear = grid.clone();
+    let mods_clear = modifications.clone();
+    engine.register_fn(
+        "CLEAR_CELL",
+        move |col: i64, row: i64| -> Result<(), Box<EvalAltResult>> {
+            let col = to_grid_index(col, "col")?;
+            let row = to_grid_index(row, "row")?;
+            let cell_ref = CellRef::new(col, row);
+            let mut mods = lock_script_modifications(&mods_clear)?;
+            let old_cell = grid_clear.get(&cell_ref).map(|r| r.clone());
+            grid_clear.remove(&cell_ref);
+
+            mods.entry(cell_ref)
+                .and_modify(|(_, nc)| *nc = None)
+                .or_insert((old_cell, None));
+
+            Ok(())
+        },
+    );
+
+    // CLEAR_CELL("A1") - Clear cell by A1 notation
+    let grid_clear_a1 = grid.clone();
+    let mods_clear_a1 = modifications.clone();
+    engine.register_fn(
+        "CLEAR_CELL",
+        move |cell_str: &str| -> Result<(), Box<EvalAltResult>> {
+            let Some(cell_ref) = CellRef::from_str(cell_str) else {
+                return Err(invalid_arg(&format!(
+                    "Invalid cell reference: {}",
+                    cell_str
+                )));
+            };
+            let mut mods = lock_script_modifications(&mods_clear_a1)?;
+            let old_cell = grid_clear_a1.get(&cell_ref).map(|r| r.clone());
+            grid_clear_a1.remove(&cell_ref);
+
+            mods.entry(cell_ref)
+                .and_modify(|(_, nc)| *nc = None)
+                .or_insert((old_cell, None));
+
+            Ok(())
+        },
+    );
+
+    // SET_CELL1(col, row, value) - Like SET_CELL, but 1-based (SET_CELL1(1, 1, v) sets A1)
+    engine.register_fn(
+        "SET_CELL1",
+        |ctx: NativeCallContext,
+         col: i64,
+         row: i64,
+         value: Dynamic|
+         -> Result<(), Box<EvalAltResult>> {
+            let col = from_one_based(col, "col")?;
+            let row = from_one_based(row, "row")?;
+            ctx.call_fn("SET_CELL", (col, row, value))
+        },
+    );
+
+    // CLEAR_CELL1(col, row) - Like CLEAR_CELL, but 1-based (CLEAR_CELL1(1, 1) clears A1)
+    engine.register_fn(
+        "CLEAR_CELL1",
+        |ctx: NativeCallContext, col: i64, row: i64| -> Result<(), Box<EvalAltResult>> {
+            let col = from_one_based(col, "col")?;
+            let row = from_one_based(row, "row")?;
+            ctx.call_fn("CLEAR_CELL", (col, row))
+        },
+    );
+
+    // SET_RANGE(c1, r1, c2, r2, value) - Fill range with value
+    let grid_set_range = grid.clone();
+    let mods_set_range = modifications.clone();
+    engine.register_fn(
+        "SET_RANGE",
+        move |c1: i64,
+              r1: i64,
+              c2: i64,
+              r2: i64,
+              value: Dynamic|
+              -> Result<(), Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+
+            let new_cell = dynamic_to_cell(value);
+
+            let mut mods = lock_script_modifications(&mods_set_range)?;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let cell_ref = CellRef::new(col, row);
+                    let old_cell = grid_set_range.get(&cell_ref).map(|r| r.clone());
+                    grid_set_range.insert(cell_ref.clone(), new_cell.clone());
+
+                    mods.entry(cell_ref)
+                        .and_modify(|(_, nc)| *nc = Some(new_cell.clone()))
+                        .or_insert((old_cell, Some(new_cell.clone())));
+                }
+            }
+
+            Ok(())
+        },
+    );
+
+    // CLEAR_RANGE(c1, r1, c2, r2) - Clear a range of cells
+    let grid_clear_range = grid.clone();
+    let mods_clear_range = modifications.clone();
+    engine.register_fn(
+        "CLEAR_RANGE",
+        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<(), Box<EvalAltResult>> {
+            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+
+            let mut mods = lock_script_modifications(&mods_clear_range)?;
+            for row in min_row..=max_row {
+                for col in min_col..=max_col {
+                    let cell_ref = CellRef::new(col, row);
+                    let old_cell = grid_clear_range.get(&cell_ref).map(|r| r.clone());
+                    grid_clear_range.remove(&cell_ref);
+
+                    mods.entry(cell_ref)
+                        .and_modify(|(_, nc)| *nc = None)
+                        .or_insert((old_cell, None));
+                }
+            }
+
+            Ok(())
+        },
+    );
+}
+
+/// Convert a Rhai Dynamic value to a Cell.
+fn dynamic_to_cell(value: Dynamic) -> Cell {
+    if value.is_unit() {
+        return Cell::new_empty();
+    }
+    if let Ok(s) = value.clone().into_string() {
+        // Check if it's a formula
+        if let Some(formula) = s.strip_prefix('=') {
+            return Cell::new_script(formula);
+        }
+        return Cell::new_text(&s);
+    }
+    if let Ok(n) = value.as_float() {
+        return Cell::new_number(n);
+    }
+    if let Ok(n) = value.as_int() {
+        return Cell::new_number(n as f64);
+    }
+    if let Ok(b) = value.as_bool() {
+        return Cell::new_text(if b { "TRUE" } else { "FALSE" });
+    }
+    // Fallback: convert to string
+    Cell::new_text(&value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Cell, Grid};
+    use dashmap::DashMap;
+
+    #[test]
+    fn test_range_rhai_name_mapping() {
+        assert_eq!(range_rhai_name("SUM"), Some("SUM_RANGE"));
+        assert_eq!(range_rhai_name("AVG"), Some("AVG_RANGE"));
+        assert_eq!(range_rhai_name("NOPE"), None);
+    }
+
+    #[test]
+    fn test_range_regex_matches_uppercase_only() {
+        let re = range_fn_re();
+        assert!(re.is_match("SUM(A1:B2)"));
+        assert!(!re.is_match("sum(A1:B2)"));
+    }
+
+    #[test]
+    fn test_sum_range_uses_script_values() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(1.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_script("A1 + 1"));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: f64 = engine.eval("SUM_RANGE(0, 0, 0, 1)").unwrap();
+        assert_eq!(result, 3.0);
+    }
+
+    #[test]
+    fn test_sum_range_prefers_value_cache() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_script("unknown_func()"));
+
+        let value_cache = ValueCache::default();
+        value_cache.insert(CellRef::new(0, 0), Dynamic::from(5.0_f64));
+
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: f64 = engine.eval("SUM_RANGE(0, 0, 0, 0)").unwrap();
+        assert_eq!(result, 5.0);
+    }
+
+    #[test]
+    fn test_plot_spec_builtins_return_tagged_string() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let s: String = engine.eval("BARCHART_RANGE(0, 0, 0, 9)").unwrap();
+        assert!(s.starts_with(crate::plot::PLOT_PREFIX));
+    }
+
+    #[test]
+    fn test_vec_range_returns_array() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // Test basic VEC returns array
+        let result: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 2)").unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].clone().cast::<f64>(), 10.0);
+        assert_eq!(result[1].clone().cast::<f64>(), 20.0);
+        assert_eq!(result[2].clone().cast::<f64>(), 30.0);
+    }
+
+    #[test]
+    fn test_parse_cell_and_format_cell() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: rhai::Array = engine.eval("PARSE_CELL(\"A1\")").unwrap();
+        assert_eq!(result[0].clone().cast::<i64>(), 0);
+        assert_eq!(result[1].clone().cast::<i64>(), 0);
+
+        let result: rhai::Array = engine.eval("PARSE_CELL(\"B4\")").unwrap();
+        assert_eq!(result[0].clone().cast::<i64>(), 1);
+        assert_eq!(result[1].clone().cast::<i64>(), 3);
+
+        let result: String = engine.eval("FORMAT_CELL(1, 3)").unwrap();
+        assert_eq!(result, "B4");
+    }
+
+    #[test]
+    fn test_parse_range_and_format_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: rhai::Array = engine.eval("PARSE_RANGE(\"A1:B4\")").unwrap();
+        assert_eq!(result[0].clone().cast::<i64>(), 0);
+        assert_eq!(result[1].clone().cast::<i64>(), 0);
+        assert_eq!(result[2].clone().cast::<i64>(), 1);
+        assert_eq!(result[3].clone().cast::<i64>(), 3);
+
+        let result: String = engine.eval("FORMAT_RANGE(0, 0, 1, 3)").unwrap();
+        assert_eq!(result, "A1:B4");
+    }
+
+    #[test]
+    fn test_parse_cell_invalid_reference() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: Result<rhai::Array, _> = engine.eval("PARSE_CELL(\"A0\")");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_range_invalid_reference() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: Result<rhai::Array, _> = engine.eval("PARSE_RANGE(\"A1\")");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_format_cell_rejects_negative_indices() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: Result<String, _> = engine.eval("FORMAT_CELL(-1, 0)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_format_range_rejects_negative_indices() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: Result<String, _> = engine.eval("FORMAT_RANGE(-1, 0, 2, 2)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_vec_range_with_map() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // Test VEC with map transformation
+        let result: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 1).map(|x| x * 2)").unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].clone().cast::<f64>(), 20.0);
+        assert_eq!(result[1].clone().cast::<f64>(), 40.0);
+    }
+
+    #[test]
+    fn test_vec_range_with_filter() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(5.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(15.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(25.0));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // Test VEC with filter
+        let result: rhai::Array = engine
+            .eval("VEC_RANGE(0, 0, 0, 2).filter(|x| x > 10)")
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].clone().cast::<f64>(), 15.0);
+        assert_eq!(result[1].clone().cast::<f64>(), 25.0);
+    }
+
+    #[test]
+    fn test_vec_range_reads_from_value_cache() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+
+        let value_cache = ValueCache::default();
+        value_cache.insert(CellRef::new(0, 1), Dynamic::from(20.0_f64));
+        value_cache.insert(CellRef::new(0, 2), Dynamic::from(30.0_f64));
+
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 2)").unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].clone().cast::<f64>(), 10.0);
+        assert_eq!(result[1].clone().cast::<f64>(), 20.0);
+        assert_eq!(result[2].clone().cast::<f64>(), 30.0);
+    }
+
+    #[test]
+    fn test_spill_array_identity() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // SPILL on array returns the same array
+        let result: rhai::Array = engine.eval("SPILL(VEC_RANGE(0, 0, 0, 2))").unwrap();
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].clone().cast::<f64>(), 10.0);
+        assert_eq!(result[1].clone().cast::<f64>(), 20.0);
+        assert_eq!(result[2].clone().cast::<f64>(), 30.0);
+    }
+
+    #[test]
+    fn test_spill_exclusive_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // SPILL on exclusive range (0..5) converts to array [0,1,2,3,4]
+        let result: rhai::Array = engine.eval("SPILL(0..5)").unwrap();
+        assert_eq!(result.len(), 5);
+        assert_eq!(result[0].clone().cast::<i64>(), 0);
+        assert_eq!(result[4].clone().cast::<i64>(), 4);
+    }
+
+    #[test]
+    fn test_spill_inclusive_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // SPILL on inclusive range (0..=5) converts to array [0,1,2,3,4,5]
+        let result: rhai::Array = engine.eval("SPILL(0..=5)").unwrap();
+        assert_eq!(result.len(), 6);
+        assert_eq!(result[0].clone().cast::<i64>(), 0);
+        assert_eq!(result[5].clone().cast::<i64>(), 5);
+    }
+
+    #[test]
+    fn test_spill_method_form() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // Method form: (0..=3).SPILL()
+        let result: rhai::Array = engine.eval("(0..=3).SPILL()").unwrap();
+        assert_eq!(result.len(), 4);
+        assert_eq!(result[0].clone().cast::<i64>(), 0);
+        assert_eq!(result[3].clone().cast::<i64>(), 3);
+    }
+
+    #[test]
+    fn test_vec_range_respects_reverse_direction() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // Forward direction: A1:A3 = [10, 20, 30]
+        let forward: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 2)").unwrap();
+        assert_eq!(forward.len(), 3);
+        assert_eq!(forward[0].clone().cast::<f64>(), 10.0);
+        assert_eq!(forward[2].clone().cast::<f64>(), 30.0);
+
+        // Reverse direction: A3:A1 = [30, 20, 10]
+        let reverse: rhai::Array = engine.eval("VEC_RANGE(0, 2, 0, 0)").unwrap();
+        assert_eq!(reverse.len(), 3);
+        assert_eq!(reverse[0].clone().cast::<f64>(), 30.0);
+        assert_eq!(reverse[2].clone().cast::<f64>(), 10.0);
+    }
+
+    #[test]
+    fn test_rand_returns_value_in_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // RAND() should return a value in [0.0, 1.0)
+        for _ in 0..100 {
+            let result: f64 = engine.eval("RAND()").unwrap();
+            assert!((0.0..1.0).contains(&result));
+        }
+    }
+
+    #[test]
+    fn test_randint_returns_value_in_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // RANDINT(1, 6) should return a value in [1, 6]
+        for _ in 0..100 {
+            let result: i64 = engine.eval("RANDINT(1, 6)").unwrap();
+            assert!((1..=6).contains(&result));
+        }
+    }
+
+    #[test]
+    fn test_randint_rejects_invalid_bounds() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: Result<i64, _> = engine.eval("RANDINT(6, 1)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sum_range_rejects_negative_indices() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        let result: Result<f64, _> = engine.eval("SUM_RANGE(-1, 0, 0, 0)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sumif_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
+        grid.insert(CellRef::new(0, 3), Cell::new_number(5.0));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // Sum values > 10: 20 + 30 = 50
+        let result: f64 = engine.eval("SUMIF_RANGE(0, 0, 0, 3, |x| x > 10)").unwrap();
+        assert_eq!(result, 50.0);
+
+        // Sum values <= 10: 10 + 5 = 15
+        let result: f64 = engine.eval("SUMIF_RANGE(0, 0, 0, 3, |x| x <= 10)").unwrap();
+        assert_eq!(result, 15.0);
+    }
+
+    #[test]
+    fn test_countif_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
+        grid.insert(CellRef::new(0, 3), Cell::new_number(5.0));
+
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
+
+        // Count values > 10: 20, 30 = 2
+        let result: i64 = engine
+            .eval("COUNTIF_RANGE(0, 0, 0, 3, |x| x > 10)")
+            .unwrap();
+        assert_eq!(result, 2);
+
+        // Count values >= 10: 10, 20, 30 = 3
+        let result: i64 = engine
+            .eval("COUNTIF_RANGE(0, 0, 0, 3, |x| x >= 10)")
+            .unwrap();
+        assert_eq!(result, 3);
+    }
 
-            if search_coords.len() != return_coords.len() {
-                return Err(invalid_arg(
-                    "LOOKUP: search and return ranges must have the same size",
-                ));
-            }
+    #[test]
+    fn test_countif_range_col_row_order() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(3.0));
+        grid.insert(CellRef::new(1, 0), Cell::new_number(4.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(1, 1), Cell::new_number(30.0));
 
-            // Helper: get Dynamic value at (col, row)
-            let get_dynamic = |col: usize, row: usize| -> Dynamic {
-                let cell_ref = CellRef::new(col, row);
-                if let Some(cached_val) = cache_lookup.get(&cell_ref) {
-                    return cached_val.clone();
-                }
-                let Some(entry) = grid_lookup.get(&cell_ref) else {
-                    return Dynamic::from("".to_string());
-                };
-                match &entry.contents {
-                    CellType::Empty => Dynamic::from("".to_string()),
-                    CellType::Number(n) => Dynamic::from(*n),
-                    CellType::Text(s) => Dynamic::from(s.clone()),
-                    CellType::Script(s) => {
-                        let processed = preprocess_script(s);
-                        ctx.engine()
-                            .eval::<Dynamic>(&processed)
-                            .unwrap_or(Dynamic::UNIT)
-                    }
-                }
-            };
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
 
-            // Search for matching value
-            for (i, &(col, row)) in search_coords.iter().enumerate() {
-                let cell_val = get_dynamic(col, row);
-                // Compare using string representation for cross-type matching
-                let matches = if value.is_string() && cell_val.is_string() {
-                    value.clone().into_string().unwrap_or_default()
-                        == cell_val.clone().into_string().unwrap_or_default()
-                } else if let (Ok(a), Ok(b)) = (value.as_float(), cell_val.as_float()) {
-                    a == b
-                } else if let (Ok(a), Ok(b)) = (value.as_int(), cell_val.as_int()) {
-                    a == b
-                } else {
-                    value.to_string() == cell_val.to_string()
-                };
+        let result: i64 = engine
+            .eval("COUNTIF_RANGE(0, 1, 1, 1, |x| x >= 20)")
+            .unwrap();
+        assert_eq!(result, 2);
+    }
 
-                if matches {
-                    let (rcol, rrow) = return_coords[i];
-                    return Ok(get_dynamic(rcol, rrow));
-                }
-            }
+    #[test]
+    fn test_sumif_range_col_row_order() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(3.0));
+        grid.insert(CellRef::new(1, 0), Cell::new_number(4.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(1, 1), Cell::new_number(30.0));
 
-            Err(invalid_arg("LOOKUP: value not found"))
-        },
-    );
-}
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
 
-/// Tracks cell modifications made by script builtins.
-/// Maps CellRef -> (old_cell, new_cell) to support undo.
-type ScriptModificationMap = HashMap<CellRef, (Option<Cell>, Option<Cell>)>;
-type ScriptModificationGuard<'a> = std::sync::MutexGuard<'a, ScriptModificationMap>;
-pub type ScriptModifications = Arc<Mutex<ScriptModificationMap>>;
+        let result: f64 = engine.eval("SUMIF_RANGE(0, 1, 1, 1, |x| x >= 20)").unwrap();
+        assert_eq!(result, 50.0);
+    }
 
-/// Register script-only write builtins for script execution.
-/// These are NOT available in cell formulas, only when running scripts via :call or :rhai.
-pub fn register_script_builtins(
-    engine: &mut Engine,
-    grid: Grid,
-    modifications: ScriptModifications,
-) {
-    // SET_CELL(col, row, value) - Set cell by column/row indices
-    let grid_set = grid.clone();
-    let mods_set = modifications.clone();
-    engine.register_fn(
-        "SET_CELL",
-        move |col: i64, row: i64, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
-            let col = to_grid_index(col, "col")?;
-            let row = to_grid_index(row, "row")?;
-            let cell_ref = CellRef::new(col, row);
-            let new_cell = dynamic_to_cell(value);
-            let mut mods = lock_script_modifications(&mods_set)?;
+    #[test]
+    fn test_script_builtins_set_cell() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let value_cache = ValueCache::default();
+        let modifications: ScriptModifications =
+            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
 
-            let old_cell = grid_set.get(&cell_ref).map(|r| r.clone());
-            grid_set.insert(cell_ref.clone(), new_cell.clone());
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid.clone(), value_cache);
+        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
 
-            mods.entry(cell_ref)
-                .and_modify(|(_, nc)| *nc = Some(new_cell.clone()))
-                .or_insert((old_cell, Some(new_cell)));
+        // Set a cell using col/row
+        let _: () = engine.eval("SET_CELL(0, 0, 42)").unwrap();
+        {
+            // Drop the DashMap guard before the next SET_CELL writes to the grid.
+            let cell = grid.get(&CellRef::new(0, 0)).unwrap();
+            assert!(matches!(cell.contents, CellType::Number(n) if (n - 42.0).abs() < 0.001));
+        }
 
-            Ok(())
-        },
-    );
+        // Set a cell using A1 notation
+        let _: () = engine.eval(r#"SET_CELL("B2", "hello")"#).unwrap();
+        let cell = grid.get(&CellRef::new(1, 1)).unwrap();
+        assert!(matches!(&cell.contents, CellType::Text(s) if s == "hello"));
 
-    // SET_CELL("A1", value) - Set cell by A1 notation
-    let grid_set_a1 = grid.clone();
-    let mods_set_a1 = modifications.clone();
-    engine.register_fn(
-        "SET_CELL",
-        move |cell_str: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
-            let Some(cell_ref) = CellRef::from_str(cell_str) else {
-                return Err(invalid_arg(&format!(
-                    "Invalid cell reference: {}",
-                    cell_str
-                )));
-            };
-            let mut mods = lock_script_modifications(&mods_set_a1)?;
-            let old_cell = grid_set_a1.get(&cell_ref).map(|r| r.clone());
+        // Check modifications were tracked
+        let mods = modifications.lock().unwrap();
+        assert_eq!(mods.len(), 2);
+    }
 
-            let new_cell = dynamic_to_cell(value);
-            grid_set_a1.insert(cell_ref.clone(), new_cell.clone());
+    #[test]
+    fn test_script_builtins_set_cell_rejects_invalid_ref() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let value_cache = ValueCache::default();
+        let modifications: ScriptModifications =
+            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
 
-            mods.entry(cell_ref)
-                .and_modify(|(_, nc)| *nc = Some(new_cell.clone()))
-                .or_insert((old_cell, Some(new_cell)));
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid.clone(), value_cache);
+        register_script_builtins(&mut engine, grid, modifications);
 
-            Ok(())
-        },
-    );
+        let bad_a1: Result<(), _> = engine.eval(r#"SET_CELL("NOPE", 1)"#);
+        assert!(bad_a1.is_err());
 
-    // CLEAR_CELL(col, row) - Clear cell by column/row indices
-    let grid_clear = grid.clone();
-    let mods_clear = modifications.clone();
-    engine.register_fn(
-        "CLEAR_CELL",
-        move |col: i64, row: i64| -> Result<(), Box<EvalAltResult>> {
-            let col = to_grid_index(col, "col")?;
-            let row = to_grid_index(row, "row")?;
-            let cell_ref = CellRef::new(col, row);
-            let mut mods = lock_script_modifications(&mods_clear)?;
-            let old_cell = grid_clear.get(&cell_ref).map(|r| r.clone());
-            grid_clear.remove(&cell_ref);
+        let bad_coord: Result<(), _> = engine.eval("SET_CELL(-1, 0, 1)");
+        assert!(bad_coord.is_err());
+    }
 
-            mods.entry(cell_ref)
-                .and_modify(|(_, nc)| *nc = None)
-                .or_insert((old_cell, None));
+    #[test]
+    fn test_cell_engine_has_no_write_builtins() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let (engine, _, _) = crate::engine::create_engine_with_functions_and_cache(
+            grid.clone(),
+            ValueCache::default(),
+            None,
+        );
+
+        for formula in [
+            "SET_CELL(0, 0, 1)",
+            r#"SET_CELL("A1", 1)"#,
+            "CLEAR_CELL(0, 0)",
+            "SET_RANGE(0, 0, [[1]])",
+            "CLEAR_RANGE(0, 0, 1, 1)",
+        ] {
+            let err = engine.eval::<Dynamic>(formula).unwrap_err();
+            assert!(
+                matches!(*err, EvalAltResult::ErrorFunctionNotFound(..)),
+                "{formula} should not be callable from a cell: {err}"
+            );
+        }
+        assert!(grid.is_empty());
+    }
 
-            Ok(())
-        },
-    );
+    #[test]
+    fn test_script_builtins_clear_cell() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(1, 1), Cell::new_text("hello"));
 
-    // CLEAR_CELL("A1") - Clear cell by A1 notation
-    let grid_clear_a1 = grid.clone();
-    let mods_clear_a1 = modifications.clone();
-    engine.register_fn(
-        "CLEAR_CELL",
-        move |cell_str: &str| -> Result<(), Box<EvalAltResult>> {
-            let Some(cell_ref) = CellRef::from_str(cell_str) else {
-                return Err(invalid_arg(&format!(
-                    "Invalid cell reference: {}",
-                    cell_str
-                )));
-            };
-            let mut mods = lock_script_modifications(&mods_clear_a1)?;
-            let old_cell = grid_clear_a1.get(&cell_ref).map(|r| r.clone());
-            grid_clear_a1.remove(&cell_ref);
+        let value_cache = ValueCache::default();
+        let modifications: ScriptModifications =
+            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
 
-            mods.entry(cell_ref)
-                .and_modify(|(_, nc)| *nc = None)
-                .or_insert((old_cell, None));
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid.clone(), value_cache);
+        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
 
-            Ok(())
-        },
-    );
+        // Clear by col/row
+        let _: () = engine.eval("CLEAR_CELL(0, 0)").unwrap();
+        assert!(grid.get(&CellRef::new(0, 0)).is_none());
 
-    // SET_RANGE(c1, r1, c2, r2, value) - Fill range with value
-    let grid_set_range = grid.clone();
-    let mods_set_range = modifications.clone();
-    engine.register_fn(
-        "SET_RANGE",
-        move |c1: i64,
-              r1: i64,
-              c2: i64,
-              r2: i64,
-              value: Dynamic|
-              -> Result<(), Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+        // Clear by A1 notation
+        let _: () = engine.eval(r#"CLEAR_CELL("B2")"#).unwrap();
+        assert!(grid.get(&CellRef::new(1, 1)).is_none());
+    }
 
-            let new_cell = dynamic_to_cell(value);
+    #[test]
+    fn test_script_builtins_set_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let value_cache = ValueCache::default();
+        let modifications: ScriptModifications =
+            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
 
-            let mut mods = lock_script_modifications(&mods_set_range)?;
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    let cell_ref = CellRef::new(col, row);
-                    let old_cell = grid_set_range.get(&cell_ref).map(|r| r.clone());
-                    grid_set_range.insert(cell_ref.clone(), new_cell.clone());
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid.clone(), value_cache);
+        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
+
+        // Fill a 3x2 range with value 99
+        let _: () = engine.eval("SET_RANGE(0, 0, 1, 2, 99)").unwrap();
+
+        // Verify all 6 cells were set
+        for row in 0..=2 {
+            for col in 0..=1 {
+                let cell = grid.get(&CellRef::new(col, row)).unwrap();
+                assert!(matches!(cell.contents, CellType::Number(n) if (n - 99.0).abs() < 0.001));
+            }
+        }
+    }
 
-                    mods.entry(cell_ref)
-                        .and_modify(|(_, nc)| *nc = Some(new_cell.clone()))
-                        .or_insert((old_cell, Some(new_cell.clone())));
-                }
+    #[test]
+    fn test_script_builtins_clear_range() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        // Fill a 2x2 grid
+        for row in 0..=1 {
+            for col in 0..=1 {
+                grid.insert(
+                    CellRef::new(col, row),
+                    Cell::new_number(row as f64 + col as f64),
+                );
             }
+        }
 
-            Ok(())
-        },
-    );
+        let value_cache = ValueCache::default();
+        let modifications: ScriptModifications =
+            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
 
-    // CLEAR_RANGE(c1, r1, c2, r2) - Clear a range of cells
-    let grid_clear_range = grid.clone();
-    let mods_clear_range = modifications.clone();
-    engine.register_fn(
-        "CLEAR_RANGE",
-        move |c1: i64, r1: i64, c2: i64, r2: i64| -> Result<(), Box<EvalAltResult>> {
-            let (min_row, max_row, min_col, max_col) = normalize_range_coords(c1, r1, c2, r2)?;
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid.clone(), value_cache);
+        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
 
-            let mut mods = lock_script_modifications(&mods_clear_range)?;
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    let cell_ref = CellRef::new(col, row);
-                    let old_cell = grid_clear_range.get(&cell_ref).map(|r| r.clone());
-                    grid_clear_range.remove(&cell_ref);
+        // Clear the range
+        let _: () = engine.eval("CLEAR_RANGE(0, 0, 1, 1)").unwrap();
 
-                    mods.entry(cell_ref)
-                        .and_modify(|(_, nc)| *nc = None)
-                        .or_insert((old_cell, None));
-                }
+        // Verify all cells were cleared
+        for row in 0..=1 {
+            for col in 0..=1 {
+                assert!(grid.get(&CellRef::new(col, row)).is_none());
             }
+        }
+    }
 
-            Ok(())
-        },
-    );
-}
+    #[test]
+    fn test_script_builtins_set_cell_formula() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let value_cache = ValueCache::default();
+        let modifications: ScriptModifications =
+            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
 
-/// Convert a Rhai Dynamic value to a Cell.
-fn dynamic_to_cell(value: Dynamic) -> Cell {
-    if value.is_unit() {
-        return Cell::new_empty();
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid.clone(), value_cache);
+        register_script_builtins(&mut engine, grid.clone(), modifications);
+
+        // Set a formula cell
+        let _: () = engine.eval(r#"SET_CELL(0, 0, "=A2+B2")"#).unwrap();
+        let cell = grid.get(&CellRef::new(0, 0)).unwrap();
+        assert!(matches!(&cell.contents, CellType::Script(s) if s == "A2+B2"));
     }
-    if let Ok(s) = value.clone().into_string() {
-        // Check if it's a formula
-        if let Some(formula) = s.strip_prefix('=') {
-            return Cell::new_script(formula);
-        }
-        return Cell::new_text(&s);
+
+    // --- Tests for new builtins ---
+
+    fn make_engine() -> Engine {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let value_cache = ValueCache::default();
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, value_cache);
+        engine
     }
-    if let Ok(n) = value.as_float() {
-        return Cell::new_number(n);
+
+    fn make_engine_with_grid(grid: Grid) -> Engine {
+        let value_cache = ValueCache::default();
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, value_cache);
+        engine
     }
-    if let Ok(n) = value.as_int() {
-        return Cell::new_number(n as f64);
+
+    #[test]
+    fn test_floor() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<f64>("FLOOR(2.7)").unwrap(), 2.0);
+        assert_eq!(engine.eval::<f64>("FLOOR(-2.3)").unwrap(), -3.0);
+        assert_eq!(engine.eval::<f64>("FLOOR(5)").unwrap(), 5.0);
     }
-    if let Ok(b) = value.as_bool() {
-        return Cell::new_text(if b { "TRUE" } else { "FALSE" });
+
+    #[test]
+    fn test_roundup_and_rounddown() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<f64>("ROUNDUP(2.341, 2)").unwrap(), 2.35);
+        assert_eq!(engine.eval::<f64>("ROUNDUP(-2.341, 2)").unwrap(), -2.35);
+        assert_eq!(engine.eval::<f64>("ROUNDUP(1.1, 1)").unwrap(), 1.1);
+        assert_eq!(engine.eval::<f64>("ROUNDUP(-3, 0)").unwrap(), -3.0);
+        assert_eq!(engine.eval::<f64>("ROUNDDOWN(2.349, 2)").unwrap(), 2.34);
+        assert_eq!(engine.eval::<f64>("ROUNDDOWN(-2.349, 2)").unwrap(), -2.34);
+        assert_eq!(engine.eval::<f64>("ROUNDDOWN(-7, 1)").unwrap(), -7.0);
+        assert!(engine.eval::<f64>("ROUNDUP(1.5, -1)").is_err());
+        assert!(engine.eval::<f64>("ROUNDDOWN(1.5, 13)").is_err());
     }
-    // Fallback: convert to string
-    Cell::new_text(&value.to_string())
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::engine::{Cell, Grid};
-    use dashmap::DashMap;
+    #[test]
+    fn test_trunc() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<f64>("TRUNC(2.9)").unwrap(), 2.0);
+        assert_eq!(engine.eval::<f64>("TRUNC(-2.9)").unwrap(), -2.0);
+        assert_eq!(engine.eval::<f64>("TRUNC(-4)").unwrap(), -4.0);
+        assert_eq!(engine.eval::<f64>("TRUNC(-8.768, 1)").unwrap(), -8.7);
+        assert_eq!(engine.eval::<f64>("TRUNC(5, 2)").unwrap(), 5.0);
+    }
 
     #[test]
-    fn test_range_rhai_name_mapping() {
-        assert_eq!(range_rhai_name("SUM"), Some("SUM_RANGE"));
-        assert_eq!(range_rhai_name("AVG"), Some("AVG_RANGE"));
-        assert_eq!(range_rhai_name("NOPE"), None);
+    fn test_mod_takes_sign_of_divisor() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<i64>("MOD(7, 3)").unwrap(), 1);
+        assert_eq!(engine.eval::<i64>("MOD(-7, 3)").unwrap(), 2);
+        assert_eq!(engine.eval::<i64>("MOD(7, -3)").unwrap(), -2);
+        assert_eq!(engine.eval::<i64>("MOD(-7, -3)").unwrap(), -1);
+        assert_eq!(engine.eval::<i64>("MOD(-6, 3)").unwrap(), 0);
+        assert_eq!(engine.eval::<f64>("MOD(-7.5, 2)").unwrap(), 0.5);
+        assert_eq!(engine.eval::<f64>("MOD(7.5, -2.0)").unwrap(), -0.5);
+        assert_eq!(engine.eval::<f64>("MOD(5, 2.5)").unwrap(), 0.0);
+        assert!(engine.eval::<i64>("MOD(5, 0)").is_err());
+        assert!(engine.eval::<f64>("MOD(-5.5, 0.0)").is_err());
     }
 
     #[test]
-    fn test_range_regex_matches_uppercase_only() {
-        let re = range_fn_re();
-        assert!(re.is_match("SUM(A1:B2)"));
-        assert!(!re.is_match("sum(A1:B2)"));
+    fn test_ceil() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<f64>("CEIL(2.3)").unwrap(), 3.0);
+        assert_eq!(engine.eval::<f64>("CEIL(-2.7)").unwrap(), -2.0);
+        assert_eq!(engine.eval::<f64>("CEIL(5)").unwrap(), 5.0);
     }
 
     #[test]
-    fn test_sum_range_uses_script_values() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(1.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_script("A1 + 1"));
+    fn test_pi_and_e() {
+        let engine = make_engine();
+        let pi: f64 = engine.eval("PI()").unwrap();
+        assert!((pi - std::f64::consts::PI).abs() < 1e-10);
+        let e: f64 = engine.eval("E()").unwrap();
+        assert!((e - std::f64::consts::E).abs() < 1e-10);
+    }
 
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+    #[test]
+    fn test_ln() {
+        let engine = make_engine();
+        let result: f64 = engine.eval("LN(1.0)").unwrap();
+        assert!((result - 0.0).abs() < 1e-10);
+        let result: f64 = engine.eval("LN(E())").unwrap();
+        assert!((result - 1.0).abs() < 1e-10);
+    }
 
-        let result: f64 = engine.eval("SUM_RANGE(0, 0, 0, 1)").unwrap();
-        assert_eq!(result, 3.0);
+    #[test]
+    fn test_log_base10() {
+        let engine = make_engine();
+        let result: f64 = engine.eval("LOG(100.0)").unwrap();
+        assert!((result - 2.0).abs() < 1e-10);
+        let result: f64 = engine.eval("LOG(1000)").unwrap();
+        assert!((result - 3.0).abs() < 1e-10);
     }
 
     #[test]
-    fn test_sum_range_prefers_value_cache() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_script("unknown_func()"));
+    fn test_log_custom_base() {
+        let engine = make_engine();
+        let result: f64 = engine.eval("LOG(8.0, 2.0)").unwrap();
+        assert!((result - 3.0).abs() < 1e-10);
+        let result: f64 = engine.eval("LOG(27, 3)").unwrap();
+        assert!((result - 3.0).abs() < 1e-10);
+    }
 
-        let value_cache = ValueCache::default();
-        value_cache.insert(CellRef::new(0, 0), Dynamic::from(5.0_f64));
+    #[test]
+    fn test_datediff_same_date() {
+        let engine = make_engine();
+        let result: i64 = engine
+            .eval(r#"DATEDIFF("2025-01-15", "2025-01-15")"#)
+            .unwrap();
+        assert_eq!(result, 0);
+    }
 
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid, value_cache);
+    #[test]
+    fn test_datediff_days() {
+        let engine = make_engine();
+        let result: i64 = engine
+            .eval(r#"DATEDIFF("2025-01-15", "2025-01-01")"#)
+            .unwrap();
+        assert_eq!(result, 14 * 86400);
+    }
 
-        let result: f64 = engine.eval("SUM_RANGE(0, 0, 0, 0)").unwrap();
-        assert_eq!(result, 5.0);
+    #[test]
+    fn test_datediff_negative() {
+        let engine = make_engine();
+        let result: i64 = engine
+            .eval(r#"DATEDIFF("2025-01-01", "2025-01-15")"#)
+            .unwrap();
+        assert_eq!(result, -14 * 86400);
     }
 
     #[test]
-    fn test_plot_spec_builtins_return_tagged_string() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+    fn test_datediff_datetime() {
+        let engine = make_engine();
+        let result: i64 = engine
+            .eval(r#"DATEDIFF("2025-01-01 01:00:00", "2025-01-01 00:00:00")"#)
+            .unwrap();
+        assert_eq!(result, 3600);
+    }
 
-        let s: String = engine.eval("BARCHART_RANGE(0, 0, 0, 9)").unwrap();
-        assert!(s.starts_with(crate::plot::PLOT_PREFIX));
+    #[test]
+    fn test_datediff_invalid() {
+        let engine = make_engine();
+        let result: Result<i64, _> = engine.eval(r#"DATEDIFF("not-a-date", "2025-01-01")"#);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_vec_range_returns_array() {
+    fn test_median_odd() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
-
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+        grid.insert(CellRef::new(0, 0), Cell::new_number(5.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(1.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(3.0));
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine.eval("MEDIAN_RANGE(0, 0, 0, 2)").unwrap();
+        assert_eq!(result, 3.0);
+    }
 
-        // Test basic VEC returns array
-        let result: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 2)").unwrap();
-        assert_eq!(result.len(), 3);
-        assert_eq!(result[0].clone().cast::<f64>(), 10.0);
-        assert_eq!(result[1].clone().cast::<f64>(), 20.0);
-        assert_eq!(result[2].clone().cast::<f64>(), 30.0);
+    #[test]
+    fn test_median_even() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(1.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(2.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(3.0));
+        grid.insert(CellRef::new(0, 3), Cell::new_number(4.0));
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine.eval("MEDIAN_RANGE(0, 0, 0, 3)").unwrap();
+        assert_eq!(result, 2.5);
     }
 
     #[test]
-    fn test_parse_cell_and_format_cell() {
+    fn test_stdev_and_var_sample() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        let result: rhai::Array = engine.eval("PARSE_CELL(\"A1\")").unwrap();
-        assert_eq!(result[0].clone().cast::<i64>(), 0);
-        assert_eq!(result[1].clone().cast::<i64>(), 0);
-
-        let result: rhai::Array = engine.eval("PARSE_CELL(\"B4\")").unwrap();
-        assert_eq!(result[0].clone().cast::<i64>(), 1);
-        assert_eq!(result[1].clone().cast::<i64>(), 3);
-
-        let result: String = engine.eval("FORMAT_CELL(1, 3)").unwrap();
-        assert_eq!(result, "B4");
+        for (row, n) in [2.0, 4.0, 4.0, 6.0].into_iter().enumerate() {
+            grid.insert(CellRef::new(0, row), Cell::new_number(n));
+        }
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine.eval("VAR_RANGE(0, 0, 0, 3)").unwrap();
+        assert!((result - 8.0 / 3.0).abs() < 1e-12);
+        let result: f64 = engine.eval("STDEV_RANGE(0, 0, 0, 3)").unwrap();
+        assert!((result - (8.0f64 / 3.0).sqrt()).abs() < 1e-12);
+        // The sheet names are rewritten to the range built-ins
+        let result: f64 = engine.eval(&preprocess_script("VAR(A1:A4)")).unwrap();
+        assert!((result - 8.0 / 3.0).abs() < 1e-12);
+        // A sample needs at least two values
+        let result: Result<f64, _> = engine.eval("STDEV_RANGE(0, 0, 0, 0)");
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_parse_range_and_format_range() {
+    fn test_stdevp_and_varp_population() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        let result: rhai::Array = engine.eval("PARSE_RANGE(\"A1:B4\")").unwrap();
-        assert_eq!(result[0].clone().cast::<i64>(), 0);
-        assert_eq!(result[1].clone().cast::<i64>(), 0);
-        assert_eq!(result[2].clone().cast::<i64>(), 1);
-        assert_eq!(result[3].clone().cast::<i64>(), 3);
-
-        let result: String = engine.eval("FORMAT_RANGE(0, 0, 1, 3)").unwrap();
-        assert_eq!(result, "A1:B4");
+        for (row, n) in [2.0, 4.0, 4.0, 6.0].into_iter().enumerate() {
+            grid.insert(CellRef::new(0, row), Cell::new_number(n));
+        }
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine.eval("VARP_RANGE(0, 0, 0, 3)").unwrap();
+        assert_eq!(result, 2.0);
+        let result: f64 = engine.eval(&preprocess_script("STDEVP(A1:A4)")).unwrap();
+        assert_eq!(result, 2.0f64.sqrt());
+        let result: f64 = engine.eval("VARP_RANGE(0, 0, 0, 0)").unwrap();
+        assert_eq!(result, 0.0);
     }
 
     #[test]
-    fn test_parse_cell_invalid_reference() {
+    fn test_geomean() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        let result: Result<rhai::Array, _> = engine.eval("PARSE_CELL(\"A0\")");
-        assert!(result.is_err());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(2.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(8.0));
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine.eval("GEOMEAN_RANGE(0, 0, 0, 1)").unwrap();
+        assert!((result - 4.0).abs() < 1e-10);
     }
 
     #[test]
-    fn test_parse_range_invalid_reference() {
+    fn test_geomean_rejects_non_positive() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        let result: Result<rhai::Array, _> = engine.eval("PARSE_RANGE(\"A1\")");
+        grid.insert(CellRef::new(0, 0), Cell::new_number(2.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(-1.0));
+        let engine = make_engine_with_grid(grid);
+        let result: Result<f64, _> = engine.eval("GEOMEAN_RANGE(0, 0, 0, 1)");
         assert!(result.is_err());
     }
 
     #[test]
-    fn test_format_cell_rejects_negative_indices() {
+    fn test_concat_no_separator() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        let result: Result<String, _> = engine.eval("FORMAT_CELL(-1, 0)");
-        assert!(result.is_err());
+        grid.insert(CellRef::new(0, 0), Cell::new_text("hello"));
+        grid.insert(CellRef::new(0, 1), Cell::new_text("world"));
+        let engine = make_engine_with_grid(grid);
+        let result: String = engine.eval("CONCAT_RANGE(0, 0, 0, 1)").unwrap();
+        assert_eq!(result, "helloworld");
     }
 
     #[test]
-    fn test_format_range_rejects_negative_indices() {
+    fn test_concat_with_separator() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        let result: Result<String, _> = engine.eval("FORMAT_RANGE(-1, 0, 2, 2)");
-        assert!(result.is_err());
+        grid.insert(CellRef::new(0, 0), Cell::new_text("hello"));
+        grid.insert(CellRef::new(0, 1), Cell::new_text("world"));
+        let engine = make_engine_with_grid(grid);
+        let result: String = engine.eval(r#"CONCAT_RANGE(0, 0, 0, 1, ", ")"#).unwrap();
+        assert_eq!(result, "hello, world");
     }
 
     #[test]
-    fn test_vec_range_with_map() {
+    fn test_concat_skips_empty() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
-
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        // Test VEC with map transformation
-        let result: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 1).map(|x| x * 2)").unwrap();
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].clone().cast::<f64>(), 20.0);
-        assert_eq!(result[1].clone().cast::<f64>(), 40.0);
+        grid.insert(CellRef::new(0, 0), Cell::new_text("a"));
+        // row 1 is empty
+        grid.insert(CellRef::new(0, 2), Cell::new_text("b"));
+        let engine = make_engine_with_grid(grid);
+        let result: String = engine.eval(r#"CONCAT_RANGE(0, 0, 0, 2, "-")"#).unwrap();
+        assert_eq!(result, "a-b");
     }
 
     #[test]
-    fn test_vec_range_with_filter() {
+    fn test_lookup_string_match() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(5.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(15.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(25.0));
-
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        // Test VEC with filter
-        let result: rhai::Array = engine
-            .eval("VEC_RANGE(0, 0, 0, 2).filter(|x| x > 10)")
+        grid.insert(CellRef::new(0, 0), Cell::new_text("alice"));
+        grid.insert(CellRef::new(0, 1), Cell::new_text("bob"));
+        grid.insert(CellRef::new(0, 2), Cell::new_text("carol"));
+        grid.insert(CellRef::new(1, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(1, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(1, 2), Cell::new_number(30.0));
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine
+            .eval(r#"LOOKUP_IMPL("bob", 0, 0, 0, 2, 1, 0, 1, 2)"#)
             .unwrap();
-        assert_eq!(result.len(), 2);
-        assert_eq!(result[0].clone().cast::<f64>(), 15.0);
-        assert_eq!(result[1].clone().cast::<f64>(), 25.0);
+        assert_eq!(result, 20.0);
     }
 
     #[test]
-    fn test_vec_range_reads_from_value_cache() {
+    fn test_lookup_numeric_match() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
-
-        let value_cache = ValueCache::default();
-        value_cache.insert(CellRef::new(0, 1), Dynamic::from(20.0_f64));
-        value_cache.insert(CellRef::new(0, 2), Dynamic::from(30.0_f64));
-
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid, value_cache);
-
-        let result: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 2)").unwrap();
-        assert_eq!(result.len(), 3);
-        assert_eq!(result[0].clone().cast::<f64>(), 10.0);
-        assert_eq!(result[1].clone().cast::<f64>(), 20.0);
-        assert_eq!(result[2].clone().cast::<f64>(), 30.0);
+        grid.insert(CellRef::new(0, 0), Cell::new_number(100.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(200.0));
+        grid.insert(CellRef::new(1, 0), Cell::new_text("first"));
+        grid.insert(CellRef::new(1, 1), Cell::new_text("second"));
+        let engine = make_engine_with_grid(grid);
+        let result: String = engine
+            .eval(r#"LOOKUP_IMPL(200.0, 0, 0, 0, 1, 1, 0, 1, 1)"#)
+            .unwrap();
+        assert_eq!(result, "second");
     }
 
     #[test]
-    fn test_spill_array_identity() {
+    fn test_lookup_not_found() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
-
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        // SPILL on array returns the same array
-        let result: rhai::Array = engine.eval("SPILL(VEC_RANGE(0, 0, 0, 2))").unwrap();
-
-        assert_eq!(result.len(), 3);
-        assert_eq!(result[0].clone().cast::<f64>(), 10.0);
-        assert_eq!(result[1].clone().cast::<f64>(), 20.0);
-        assert_eq!(result[2].clone().cast::<f64>(), 30.0);
+        grid.insert(CellRef::new(0, 0), Cell::new_text("alice"));
+        grid.insert(CellRef::new(1, 0), Cell::new_number(10.0));
+        let engine = make_engine_with_grid(grid);
+        let result: Result<Dynamic, _> =
+            engine.eval(r#"LOOKUP_IMPL("missing", 0, 0, 0, 0, 1, 0, 1, 0)"#);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_spill_exclusive_range() {
+    fn test_lookup_mismatched_ranges() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        // SPILL on exclusive range (0..5) converts to array [0,1,2,3,4]
-        let result: rhai::Array = engine.eval("SPILL(0..5)").unwrap();
-        assert_eq!(result.len(), 5);
-        assert_eq!(result[0].clone().cast::<i64>(), 0);
-        assert_eq!(result[4].clone().cast::<i64>(), 4);
+        let engine = make_engine_with_grid(grid);
+        // search range: 3 cells, return range: 2 cells
+        let result: Result<Dynamic, _> = engine.eval(r#"LOOKUP_IMPL("x", 0, 0, 0, 2, 1, 0, 1, 1)"#);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_spill_inclusive_range() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        // SPILL on inclusive range (0..=5) converts to array [0,1,2,3,4,5]
-        let result: rhai::Array = engine.eval("SPILL(0..=5)").unwrap();
-        assert_eq!(result.len(), 6);
-        assert_eq!(result[0].clone().cast::<i64>(), 0);
-        assert_eq!(result[5].clone().cast::<i64>(), 5);
+    fn test_isnumber() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<bool>("ISNUMBER(42.0)").unwrap(), true);
+        assert_eq!(engine.eval::<bool>("ISNUMBER(42)").unwrap(), true);
+        assert_eq!(engine.eval::<bool>(r#"ISNUMBER("hello")"#).unwrap(), false);
     }
 
     #[test]
-    fn test_spill_method_form() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+    fn test_istext() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<bool>(r#"ISTEXT("hello")"#).unwrap(), true);
+        assert_eq!(engine.eval::<bool>("ISTEXT(42)").unwrap(), false);
+        assert_eq!(engine.eval::<bool>("ISTEXT(3.14)").unwrap(), false);
+    }
 
-        // Method form: (0..=3).SPILL()
-        let result: rhai::Array = engine.eval("(0..=3).SPILL()").unwrap();
-        assert_eq!(result.len(), 4);
-        assert_eq!(result[0].clone().cast::<i64>(), 0);
-        assert_eq!(result[3].clone().cast::<i64>(), 3);
+    #[test]
+    fn test_isempty() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<bool>(r#"ISEMPTY("")"#).unwrap(), true);
+        assert_eq!(engine.eval::<bool>("ISEMPTY(())").unwrap(), true);
+        assert_eq!(engine.eval::<bool>(r#"ISEMPTY("hello")"#).unwrap(), false);
+        assert_eq!(engine.eval::<bool>("ISEMPTY(0)").unwrap(), false);
     }
 
     #[test]
-    fn test_vec_range_respects_reverse_direction() {
+    fn test_coalesce_returns_first_non_empty() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
-
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        // Forward direction: A1:A3 = [10, 20, 30]
-        let forward: rhai::Array = engine.eval("VEC_RANGE(0, 0, 0, 2)").unwrap();
-        assert_eq!(forward.len(), 3);
-        assert_eq!(forward[0].clone().cast::<f64>(), 10.0);
-        assert_eq!(forward[2].clone().cast::<f64>(), 30.0);
+        grid.insert(CellRef::new(1, 0), Cell::new_text("backup"));
+        let engine = make_engine_with_grid(grid);
+        // A1 is blank, so B1 is used
+        let script = preprocess_script(r#"COALESCE(@A1, @B1, "default")"#);
+        assert_eq!(engine.eval::<String>(&script).unwrap(), "backup");
+        assert_eq!(engine.eval::<i64>(r#"COALESCE("", 0, 5)"#).unwrap(), 0);
+        // All empty: the last argument is the fallback
+        let script = preprocess_script(r#"COALESCE(@A1, @C1, "", "default")"#);
+        assert_eq!(engine.eval::<String>(&script).unwrap(), "default");
+        assert_eq!(engine.eval::<String>(r#"COALESCE("", "")"#).unwrap(), "");
+    }
 
-        // Reverse direction: A3:A1 = [30, 20, 10]
-        let reverse: rhai::Array = engine.eval("VEC_RANGE(0, 2, 0, 0)").unwrap();
-        assert_eq!(reverse.len(), 3);
-        assert_eq!(reverse[0].clone().cast::<f64>(), 30.0);
-        assert_eq!(reverse[2].clone().cast::<f64>(), 10.0);
+    #[test]
+    fn test_product() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(2.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(3.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(4.0));
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine.eval("PRODUCT_RANGE(0, 0, 0, 2)").unwrap();
+        assert_eq!(result, 24.0);
     }
 
     #[test]
-    fn test_rand_returns_value_in_range() {
+    fn test_product_with_zero() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+        grid.insert(CellRef::new(0, 0), Cell::new_number(5.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(0.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(3.0));
+        let engine = make_engine_with_grid(grid);
+        let result: f64 = engine.eval("PRODUCT_RANGE(0, 0, 0, 2)").unwrap();
+        assert_eq!(result, 0.0);
+    }
 
-        // RAND() should return a value in [0.0, 1.0)
-        for _ in 0..100 {
-            let result: f64 = engine.eval("RAND()").unwrap();
-            assert!((0.0..1.0).contains(&result));
-        }
+    #[test]
+    fn test_pctchange() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<f64>("PCTCHANGE(100, 150)").unwrap(), 0.5);
+        assert_eq!(engine.eval::<f64>("PCTCHANGE(200.0, 150)").unwrap(), -0.25);
+        assert!(engine.eval::<f64>("PCTCHANGE(0, 10)").is_err());
     }
 
     #[test]
-    fn test_randint_returns_value_in_range() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+    fn test_cagr() {
+        let engine = make_engine();
+        let result: f64 = engine.eval("CAGR(100, 200, 10)").unwrap();
+        assert!((result - 0.071773).abs() < 1e-6);
+        let result: f64 = engine.eval("CAGR(100.0, 121, 2)").unwrap();
+        assert!((result - 0.1).abs() < 1e-10);
+        assert!(engine.eval::<f64>("CAGR(0, 200, 10)").is_err());
+        assert!(engine.eval::<f64>("CAGR(100, -50, 10)").is_err());
+        assert!(engine.eval::<f64>("CAGR(100, 200, 0)").is_err());
+    }
 
-        // RANDINT(1, 6) should return a value in [1, 6]
-        for _ in 0..100 {
-            let result: i64 = engine.eval("RANDINT(1, 6)").unwrap();
-            assert!((1..=6).contains(&result));
+    #[test]
+    fn test_countif_wildcard_criteria() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        for (row, text) in ["apple", "Application", "cat", "bat", "at", "a.b", "axb"]
+            .iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_text(text));
         }
+        let engine = make_engine_with_grid(grid);
+
+        let result: i64 = engine.eval(r#"COUNTIF_RANGE(0, 0, 0, 6, "app*")"#).unwrap();
+        assert_eq!(result, 2);
+        let result: i64 = engine.eval(r#"COUNTIF_RANGE(0, 0, 0, 6, "?at")"#).unwrap();
+        assert_eq!(result, 2);
+        // Regex metacharacters in the literal parts match themselves only.
+        let result: i64 = engine.eval(r#"COUNTIF_RANGE(0, 0, 0, 6, "a.b")"#).unwrap();
+        assert_eq!(result, 1);
+        let result: i64 = engine.eval(r#"COUNTIF_RANGE(0, 0, 0, 6, "<>a*")"#).unwrap();
+        assert_eq!(result, 2);
     }
 
     #[test]
-    fn test_randint_rejects_invalid_bounds() {
+    fn test_sumif_comparison_criteria() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_text("n/a"));
+        grid.insert(CellRef::new(0, 3), Cell::new_number(5.0));
+        let engine = make_engine_with_grid(grid);
 
-        let result: Result<i64, _> = engine.eval("RANDINT(6, 1)");
-        assert!(result.is_err());
+        let result: f64 = engine.eval(r#"SUMIF_RANGE(0, 0, 0, 3, ">=10")"#).unwrap();
+        assert_eq!(result, 30.0);
+        let result: i64 = engine.eval(r#"COUNTIF_RANGE(0, 0, 0, 3, "5")"#).unwrap();
+        assert_eq!(result, 1);
     }
 
     #[test]
-    fn test_sum_range_rejects_negative_indices() {
+    fn test_maprange_doubles_range() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+        grid.insert(CellRef::new(0, 0), Cell::new_number(1.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(2.0));
+        grid.insert(CellRef::new(0, 2), Cell::new_number(3.5));
+        let engine = make_engine_with_grid(grid);
 
-        let result: Result<f64, _> = engine.eval("SUM_RANGE(-1, 0, 0, 0)");
-        assert!(result.is_err());
+        let script = preprocess_script("MAPRANGE(A1:A3, |x| x * 2)");
+        assert_eq!(script, "MAPRANGE_RANGE(0, 0, 0, 2, |x| x * 2)");
+        let result: rhai::Array = engine.eval(&script).unwrap();
+        let values: Vec<f64> = result.into_iter().map(|v| v.as_float().unwrap()).collect();
+        assert_eq!(values, vec![2.0, 4.0, 7.0]);
     }
 
     #[test]
-    fn test_sumif_range() {
+    fn test_reduce_sums_range() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
         grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
         grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
-        grid.insert(CellRef::new(0, 3), Cell::new_number(5.0));
-
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+        grid.insert(CellRef::new(0, 2), Cell::new_number(12.5));
+        let engine = make_engine_with_grid(grid);
 
-        // Sum values > 10: 20 + 30 = 50
-        let result: f64 = engine.eval("SUMIF_RANGE(0, 0, 0, 3, |x| x > 10)").unwrap();
-        assert_eq!(result, 50.0);
+        let script = preprocess_script("REDUCE(A1:A3, 0, |acc, x| acc + x)");
+        let result: f64 = engine.eval(&script).unwrap();
+        assert_eq!(result, 42.5);
 
-        // Sum values <= 10: 10 + 5 = 15
-        let result: f64 = engine.eval("SUMIF_RANGE(0, 0, 0, 3, |x| x <= 10)").unwrap();
-        assert_eq!(result, 15.0);
+        // Errors raised by the callback are surfaced rather than swallowed.
+        assert!(
+            engine
+                .eval::<Dynamic>(r#"REDUCE_RANGE(0, 0, 0, 2, 0, |acc, x| NO_SUCH_FN(acc, x))"#)
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_countif_range() {
+    fn test_averageif_matching_subset() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
         grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
         grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
         grid.insert(CellRef::new(0, 2), Cell::new_number(30.0));
         grid.insert(CellRef::new(0, 3), Cell::new_number(5.0));
+        let engine = make_engine_with_grid(grid);
 
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
-
-        // Count values > 10: 20, 30 = 2
-        let result: i64 = engine
-            .eval("COUNTIF_RANGE(0, 0, 0, 3, |x| x > 10)")
+        let result: f64 = engine
+            .eval(&preprocess_script("AVERAGEIF(A1:A4, |x| x >= 10)"))
             .unwrap();
-        assert_eq!(result, 2);
-
-        // Count values >= 10: 10, 20, 30 = 3
-        let result: i64 = engine
-            .eval("COUNTIF_RANGE(0, 0, 0, 3, |x| x >= 10)")
+        assert_eq!(result, 20.0);
+        let result: f64 = engine
+            .eval(&preprocess_script(r#"AVERAGEIF(A1:A4, ">10")"#))
             .unwrap();
-        assert_eq!(result, 3);
+        assert_eq!(result, 25.0);
     }
 
     #[test]
-    fn test_countif_range_col_row_order() {
+    fn test_averageifs_uses_criteria_range() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(3.0));
-        grid.insert(CellRef::new(1, 0), Cell::new_number(4.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
-        grid.insert(CellRef::new(1, 1), Cell::new_number(30.0));
+        for (row, (fruit, qty)) in [("apple", 4.0), ("pear", 10.0), ("apricot", 8.0)]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_text(fruit));
+            grid.insert(CellRef::new(1, row), Cell::new_number(qty));
+        }
+        let engine = make_engine_with_grid(grid);
 
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+        let script = preprocess_script(r#"AVERAGEIFS(B1:B3, A1:A3, "ap*")"#);
+        assert_eq!(
+            script,
+            r#"AVERAGEIFS_RANGES(1, 0, 1, 2, 0, 0, 0, 2, "ap*")"#
+        );
+        assert_eq!(engine.eval::<f64>(&script).unwrap(), 6.0);
 
-        let result: i64 = engine
-            .eval("COUNTIF_RANGE(0, 1, 1, 1, |x| x >= 20)")
+        let result: f64 = engine
+            .eval(&preprocess_script("AVERAGEIFS(A1:A3, B1:B3, |q| q > 5)"))
             .unwrap();
-        assert_eq!(result, 2);
+        // Text cells in the average range count as zero for predicate form.
+        assert_eq!(result, 0.0);
+
+        let mismatched = engine.eval::<f64>(&preprocess_script(r#"AVERAGEIFS(B1:B3, A1:A2, "x")"#));
+        assert!(mismatched.is_err());
     }
 
     #[test]
-    fn test_sumif_range_col_row_order() {
+    fn test_averageif_no_match_errors() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(3.0));
-        grid.insert(CellRef::new(1, 0), Cell::new_number(4.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(20.0));
-        grid.insert(CellRef::new(1, 1), Cell::new_number(30.0));
-
-        let mut engine = Engine::new();
-        let value_cache = ValueCache::default();
-        register_builtins(&mut engine, grid, value_cache);
+        grid.insert(CellRef::new(0, 0), Cell::new_number(1.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_number(2.0));
+        let engine = make_engine_with_grid(grid);
 
-        let result: f64 = engine.eval("SUMIF_RANGE(0, 1, 1, 1, |x| x >= 20)").unwrap();
-        assert_eq!(result, 50.0);
+        let err = engine
+            .eval::<f64>(&preprocess_script(r#"AVERAGEIF(A1:A2, ">100")"#))
+            .unwrap_err();
+        assert!(err.to_string().contains("division by zero"));
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script("AVERAGEIF(A1:A2, |x| x < 0)"))
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_script_builtins_set_cell() {
+    fn test_trimmean_discards_outliers() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let value_cache = ValueCache::default();
-        let modifications: ScriptModifications =
-            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
-
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid.clone(), value_cache);
-        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
-
-        // Set a cell using col/row
-        let _: () = engine.eval("SET_CELL(0, 0, 42)").unwrap();
-        let cell = grid.get(&CellRef::new(0, 0)).unwrap();
-        assert!(matches!(cell.contents, CellType::Number(n) if (n - 42.0).abs() < 0.001));
-
-        // Set a cell using A1 notation
-        let _: () = engine.eval(r#"SET_CELL("B2", "hello")"#).unwrap();
-        let cell = grid.get(&CellRef::new(1, 1)).unwrap();
-        assert!(matches!(&cell.contents, CellType::Text(s) if s == "hello"));
+        for (row, v) in [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 1000.0]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_number(v));
+        }
+        let engine = make_engine_with_grid(grid);
 
-        // Check modifications were tracked
-        let mods = modifications.lock().unwrap();
-        assert_eq!(mods.len(), 2);
+        // 20% of 10 values: drop the lowest and highest one
+        let result: f64 = engine
+            .eval(&preprocess_script("TRIMMEAN(A1:A10, 0.2)"))
+            .unwrap();
+        assert_eq!(result, 5.5);
+        // 10% rounds down to an even count of zero, so nothing is trimmed
+        let result: f64 = engine.eval("TRIMMEAN_RANGE(0, 0, 0, 9, 0.1)").unwrap();
+        assert_eq!(result, 104.5);
+        assert!(
+            engine
+                .eval::<f64>("TRIMMEAN_RANGE(0, 0, 0, 9, 1.0)")
+                .is_err()
+        );
+        assert!(
+            engine
+                .eval::<f64>("TRIMMEAN_RANGE(0, 0, 0, 9, -0.1)")
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_script_builtins_set_cell_rejects_invalid_ref() {
+    fn test_weightedmedian() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let value_cache = ValueCache::default();
-        let modifications: ScriptModifications =
-            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
-
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid.clone(), value_cache);
-        register_script_builtins(&mut engine, grid, modifications);
+        for (row, (v, w)) in [(1.0, 1.0), (2.0, 1.0), (3.0, 1.0), (4.0, 5.0)]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_number(v));
+            grid.insert(CellRef::new(1, row), Cell::new_number(w));
+        }
+        let engine = make_engine_with_grid(grid);
 
-        let bad_a1: Result<(), _> = engine.eval(r#"SET_CELL("NOPE", 1)"#);
-        assert!(bad_a1.is_err());
+        let result: f64 = engine
+            .eval(&preprocess_script("WEIGHTEDMEDIAN(A1:A4, B1:B4)"))
+            .unwrap();
+        assert_eq!(result, 4.0);
+        // Equal weights behave like the plain median
+        let result: f64 = engine
+            .eval(&preprocess_script("WEIGHTEDMEDIAN(A1:A2, B1:B2)"))
+            .unwrap();
+        assert_eq!(result, 1.5);
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script("WEIGHTEDMEDIAN(A1:A4, B1:B3)"))
+                .is_err()
+        );
+    }
 
-        let bad_coord: Result<(), _> = engine.eval("SET_CELL(-1, 0, 1)");
-        assert!(bad_coord.is_err());
+    #[test]
+    fn test_datevalue_round_trips_through_text() {
+        let engine = make_engine();
+        assert_eq!(
+            engine.eval::<i64>(r#"DATEVALUE("2025-01-01")"#).unwrap(),
+            45658
+        );
+        assert_eq!(
+            engine
+                .eval::<String>(r#"TEXT(DATEVALUE("2024-02-28"), "yyyy-mm-dd")"#)
+                .unwrap(),
+            "2024-02-28"
+        );
+        assert!(engine.eval::<i64>(r#"DATEVALUE("not-a-date")"#).is_err());
     }
 
     #[test]
-    fn test_script_builtins_clear_cell() {
+    fn test_serial_date_arithmetic() {
+        let engine = make_engine();
+        // Adding 1 advances the day, across month and leap-day boundaries
+        assert_eq!(
+            engine
+                .eval::<String>(r#"TEXT(DATEVALUE("2024-02-28") + 1, "yyyy-mm-dd")"#)
+                .unwrap(),
+            "2024-02-29"
+        );
+        assert_eq!(
+            engine
+                .eval::<String>(r#"TEXT(DATEVALUE("2025-01-01") + 30, "dd/mm/yy")"#)
+                .unwrap(),
+            "31/01/25"
+        );
+        assert_eq!(engine.eval::<f64>(r#"TIMEVALUE("18:00")"#).unwrap(), 0.75);
+        assert_eq!(
+            engine
+                .eval::<String>(
+                    r#"TEXT(DATEVALUE("2025-03-09") + TIMEVALUE("13:05:09"), "ddd d mmm yyyy hh:mm:ss")"#
+                )
+                .unwrap(),
+            "Sun 9 Mar 2025 13:05:09"
+        );
+        let today: i64 = engine.eval("TODAY_SERIAL()").unwrap();
+        let today_text: String = engine.eval("TODAY()").unwrap();
+        assert_eq!(
+            engine
+                .eval::<String>(&format!(r#"TEXT({}, "yyyy-mm-dd")"#, today))
+                .unwrap(),
+            today_text
+        );
+    }
+
+    fn grading_grid() -> Grid {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(10.0));
-        grid.insert(CellRef::new(1, 1), Cell::new_text("hello"));
+        for (row, v) in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_number(v));
+        }
+        grid.insert(CellRef::new(1, 0), Cell::new_number(9.0));
+        grid.insert(CellRef::new(2, 0), Cell::new_text("n/a"));
+        grid
+    }
 
-        let value_cache = ValueCache::default();
-        let modifications: ScriptModifications =
-            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
+    #[test]
+    fn test_percentrank() {
+        let engine = make_engine_with_grid(grading_grid());
 
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid.clone(), value_cache);
-        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
+        let result: f64 = engine
+            .eval(&preprocess_script("PERCENTRANK(A1:A8, 4)"))
+            .unwrap();
+        assert!((result - 1.0 / 7.0).abs() < 1e-12);
+        let result: f64 = engine
+            .eval(&preprocess_script("PERCENTRANK(A1:A8, @B1)"))
+            .unwrap();
+        assert_eq!(result, 1.0);
+        // 6 sits halfway between the 6th (5) and 7th (7) sorted values
+        let result: f64 = engine
+            .eval(&preprocess_script("PERCENTRANK(A1:A8, 6.0)"))
+            .unwrap();
+        assert!((result - 5.5 / 7.0).abs() < 1e-12);
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script("PERCENTRANK(A1:A8, 10)"))
+                .is_err()
+        );
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script("PERCENTRANK(C1:C3, 1)"))
+                .is_err()
+        );
+    }
 
-        // Clear by col/row
-        let _: () = engine.eval("CLEAR_CELL(0, 0)").unwrap();
-        assert!(grid.get(&CellRef::new(0, 0)).is_none());
+    #[test]
+    fn test_zscore() {
+        let engine = make_engine_with_grid(grading_grid());
 
-        // Clear by A1 notation
-        let _: () = engine.eval(r#"CLEAR_CELL("B2")"#).unwrap();
-        assert!(grid.get(&CellRef::new(1, 1)).is_none());
+        // mean 5, sample stdev sqrt(32 / 7)
+        let stdev = (32.0f64 / 7.0).sqrt();
+        let result: f64 = engine
+            .eval(&preprocess_script("ZSCORE(@B1, A1:A8)"))
+            .unwrap();
+        assert!((result - 4.0 / stdev).abs() < 1e-12);
+        let result: f64 = engine.eval(&preprocess_script("ZSCORE(2, A1:A8)")).unwrap();
+        assert!((result + 3.0 / stdev).abs() < 1e-12);
+        // Zero stdev and ranges without numbers are errors
+        let err = engine
+            .eval::<f64>(&preprocess_script("ZSCORE(4, A2:A4)"))
+            .unwrap_err();
+        assert!(err.to_string().contains("standard deviation is zero"));
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script("ZSCORE(1, C1:C3)"))
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_script_builtins_set_range() {
+    fn test_scalar_min_max_and_clamp() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let value_cache = ValueCache::default();
-        let modifications: ScriptModifications =
-            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
-
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid.clone(), value_cache);
-        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
+        for (row, v) in [5.0, 1.0, 3.0].into_iter().enumerate() {
+            grid.insert(CellRef::new(0, row), Cell::new_number(v));
+        }
+        let engine = make_engine_with_grid(grid);
 
-        // Fill a 3x2 range with value 99
-        let _: () = engine.eval("SET_RANGE(0, 0, 1, 2, 99)").unwrap();
+        let result: i64 = engine.eval(&preprocess_script("MIN(2, 3)")).unwrap();
+        assert_eq!(result, 2);
+        let result: f64 = engine.eval(&preprocess_script("MAX(2, 3.5)")).unwrap();
+        assert_eq!(result, 3.5);
+        let result: i64 = engine
+            .eval(&preprocess_script("CLAMP(150, 0, 100)"))
+            .unwrap();
+        assert_eq!(result, 100);
+        let result: f64 = engine
+            .eval(&preprocess_script("CLAMP(@A1, 0, 2.5)"))
+            .unwrap();
+        assert_eq!(result, 2.5);
+        assert!(engine.eval::<i64>("CLAMP(1, 5, 0)").is_err());
 
-        // Verify all 6 cells were set
-        for row in 0..=2 {
-            for col in 0..=1 {
-                let cell = grid.get(&CellRef::new(col, row)).unwrap();
-                assert!(matches!(cell.contents, CellType::Number(n) if (n - 99.0).abs() < 0.001));
-            }
-        }
+        // Range forms are still rewritten to the range builtins
+        let result: f64 = engine.eval(&preprocess_script("MIN(A1:A3)")).unwrap();
+        assert_eq!(result, 1.0);
+        let result: f64 = engine
+            .eval(&preprocess_script("MAX(A1:A3) + MIN(A1, 2)"))
+            .unwrap();
+        assert_eq!(result, 7.0);
     }
 
     #[test]
-    fn test_script_builtins_clear_range() {
+    fn test_cell1_is_one_based_cell() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        // Fill a 2x2 grid
-        for row in 0..=1 {
-            for col in 0..=1 {
-                grid.insert(
-                    CellRef::new(col, row),
-                    Cell::new_number(row as f64 + col as f64),
-                );
-            }
-        }
-
-        let value_cache = ValueCache::default();
-        let modifications: ScriptModifications =
-            std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
-
+        grid.insert(CellRef::new(0, 0), Cell::new_number(7.0));
+        grid.insert(CellRef::new(2, 1), Cell::new_number(3.5));
         let mut engine = Engine::new();
-        register_builtins(&mut engine, grid.clone(), value_cache);
-        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
-
-        // Clear the range
-        let _: () = engine.eval("CLEAR_RANGE(0, 0, 1, 1)").unwrap();
-
-        // Verify all cells were cleared
-        for row in 0..=1 {
-            for col in 0..=1 {
-                assert!(grid.get(&CellRef::new(col, row)).is_none());
-            }
-        }
+        register_builtins(&mut engine, grid, ValueCache::default());
+
+        let one: f64 = engine.eval("CELL1(1, 1)").unwrap();
+        let zero: f64 = engine.eval("CELL(0, 0)").unwrap();
+        assert_eq!(one, zero);
+        assert_eq!(one, 7.0);
+        let c2: f64 = engine.eval("CELL1(3, 2)").unwrap();
+        assert_eq!(c2, 3.5);
+
+        assert!(engine.eval::<f64>("CELL1(0, 1)").is_err());
+        assert!(engine.eval::<f64>("CELL1(1, -4)").is_err());
     }
 
     #[test]
-    fn test_script_builtins_set_cell_formula() {
+    fn test_script_builtins_set_and_clear_cell1() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let value_cache = ValueCache::default();
         let modifications: ScriptModifications =
             std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
 
         let mut engine = Engine::new();
-        register_builtins(&mut engine, grid.clone(), value_cache);
-        register_script_builtins(&mut engine, grid.clone(), modifications);
-
-        // Set a formula cell
-        let _: () = engine.eval(r#"SET_CELL(0, 0, "=A2+B2")"#).unwrap();
-        let cell = grid.get(&CellRef::new(0, 0)).unwrap();
-        assert!(matches!(&cell.contents, CellType::Script(s) if s == "A2+B2"));
-    }
+        register_builtins(&mut engine, grid.clone(), ValueCache::default());
+        register_script_builtins(&mut engine, grid.clone(), modifications.clone());
 
-    // --- Tests for new builtins ---
+        let _: () = engine.eval("SET_CELL1(1, 1, 42)").unwrap();
+        {
+            let cell = grid.get(&CellRef::new(0, 0)).unwrap();
+            assert!(matches!(cell.contents, CellType::Number(n) if (n - 42.0).abs() < 0.001));
+        }
 
-    fn make_engine() -> Engine {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let value_cache = ValueCache::default();
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid, value_cache);
-        engine
-    }
+        let _: () = engine.eval("CLEAR_CELL1(1, 1)").unwrap();
+        assert!(grid.get(&CellRef::new(0, 0)).is_none());
+        assert_eq!(modifications.lock().unwrap().len(), 1);
 
-    fn make_engine_with_grid(grid: Grid) -> Engine {
-        let value_cache = ValueCache::default();
-        let mut engine = Engine::new();
-        register_builtins(&mut engine, grid, value_cache);
-        engine
+        assert!(engine.eval::<()>("SET_CELL1(0, 1, 5)").is_err());
+        assert!(engine.eval::<()>("CLEAR_CELL1(1, 0)").is_err());
     }
 
     #[test]
-    fn test_floor() {
-        let engine = make_engine();
-        assert_eq!(engine.eval::<f64>("FLOOR(2.7)").unwrap(), 2.0);
-        assert_eq!(engine.eval::<f64>("FLOOR(-2.3)").unwrap(), -3.0);
-        assert_eq!(engine.eval::<f64>("FLOOR(5)").unwrap(), 5.0);
+    fn test_bitwise_builtins() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, ValueCache::default());
+
+        assert_eq!(engine.eval::<i64>("BITAND(6, 3)").unwrap(), 2);
+        assert_eq!(engine.eval::<i64>("BITOR(6, 3)").unwrap(), 7);
+        assert_eq!(engine.eval::<i64>("BITXOR(6, 3)").unwrap(), 5);
+        assert_eq!(engine.eval::<i64>("BITLSHIFT(1, 4)").unwrap(), 16);
+        assert_eq!(engine.eval::<i64>("BITRSHIFT(16, 2)").unwrap(), 4);
+        assert_eq!(engine.eval::<i64>("BITRSHIFT(16, -1)").unwrap(), 32);
+        // Float operands are truncated.
+        assert_eq!(engine.eval::<i64>("BITAND(6.9, 3.2)").unwrap(), 2);
+
+        assert!(engine.eval::<i64>("BITAND(-1, 3)").is_err());
+        assert!(engine.eval::<i64>("BITOR(281474976710656, 1)").is_err());
+        assert!(engine.eval::<i64>("BITLSHIFT(1, 48)").is_err());
+        assert!(engine.eval::<i64>("BITLSHIFT(1, 100)").is_err());
     }
 
     #[test]
-    fn test_ceil() {
-        let engine = make_engine();
-        assert_eq!(engine.eval::<f64>("CEIL(2.3)").unwrap(), 3.0);
-        assert_eq!(engine.eval::<f64>("CEIL(-2.7)").unwrap(), -2.0);
-        assert_eq!(engine.eval::<f64>("CEIL(5)").unwrap(), 5.0);
+    fn test_combinatorics_builtins() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, ValueCache::default());
+
+        assert_eq!(engine.eval::<f64>("COMBIN(5, 2)").unwrap(), 10.0);
+        assert_eq!(engine.eval::<f64>("COMBIN(5, 0)").unwrap(), 1.0);
+        assert_eq!(
+            engine.eval::<f64>("COMBIN(60, 30)").unwrap(),
+            118264581564861424.0
+        );
+        assert_eq!(engine.eval::<f64>("PERMUT(5, 2)").unwrap(), 20.0);
+        assert_eq!(engine.eval::<f64>("PERMUT(5, 0)").unwrap(), 1.0);
+        assert_eq!(engine.eval::<f64>("FACT(0)").unwrap(), 1.0);
+        assert_eq!(engine.eval::<f64>("FACT(5)").unwrap(), 120.0);
+
+        assert!(engine.eval::<f64>("COMBIN(2, 5)").is_err());
+        assert!(engine.eval::<f64>("PERMUT(5, -1)").is_err());
+        assert!(engine.eval::<f64>("FACT(-1)").is_err());
+        assert!(engine.eval::<f64>("FACT(171)").is_err());
+
+        // Whole-number floats (cell values) work; fractions don't
+        assert_eq!(engine.eval::<f64>("COMBIN(5.0, 2)").unwrap(), 10.0);
+        assert_eq!(engine.eval::<f64>("COMBIN(5, 2.0)").unwrap(), 10.0);
+        assert_eq!(engine.eval::<f64>("PERMUT(5.0, 2.0)").unwrap(), 20.0);
+        assert_eq!(engine.eval::<f64>("FACT(5.0)").unwrap(), 120.0);
+        assert!(engine.eval::<f64>("COMBIN(5.5, 2)").is_err());
+        assert!(engine.eval::<f64>("PERMUT(5, 1.5)").is_err());
+        assert!(engine.eval::<f64>("FACT(2.5)").is_err());
+        assert!(engine.eval::<f64>("FACT(-1.0)").is_err());
     }
 
     #[test]
-    fn test_pi_and_e() {
+    fn test_combinatorics_reject_huge_results_quickly() {
         let engine = make_engine();
-        let pi: f64 = engine.eval("PI()").unwrap();
-        assert!((pi - std::f64::consts::PI).abs() < 1e-10);
-        let e: f64 = engine.eval("E()").unwrap();
-        assert!((e - std::f64::consts::E).abs() < 1e-10);
+        let start = std::time::Instant::now();
+        for script in [
+            "COMBIN(1e15, 5e14)",
+            "PERMUT(1e15, 1e15)",
+            "COMBIN(1000000000, 500000000)",
+        ] {
+            let err = engine.eval::<f64>(script).unwrap_err().to_string();
+            assert!(err.contains("too large"), "{script}: {err}");
+        }
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
     }
 
     #[test]
-    fn test_ln() {
-        let engine = make_engine();
-        let result: f64 = engine.eval("LN(1.0)").unwrap();
-        assert!((result - 0.0).abs() < 1e-10);
-        let result: f64 = engine.eval("LN(E())").unwrap();
-        assert!((result - 1.0).abs() < 1e-10);
+    fn test_url_and_base64_builtins() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, ValueCache::default());
+
+        let encoded: String = engine.eval(r#"ENCODEURL("a b&c=d/é")"#).unwrap();
+        assert_eq!(encoded, "a%20b%26c%3Dd%2F%C3%A9");
+        let decoded: String = engine.eval(r#"URLDECODE(ENCODEURL("a b&c=d/é"))"#).unwrap();
+        assert_eq!(decoded, "a b&c=d/é");
+        assert!(engine.eval::<String>(r#"URLDECODE("100%")"#).is_err());
+        assert!(engine.eval::<String>(r#"URLDECODE("%FF")"#).is_err());
+
+        let b64: String = engine.eval(r#"BASE64ENCODE("hello, world")"#).unwrap();
+        assert_eq!(b64, "aGVsbG8sIHdvcmxk");
+        for (text, expected) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v")] {
+            let script = format!(r#"BASE64ENCODE("{}")"#, text);
+            assert_eq!(engine.eval::<String>(&script).unwrap(), expected);
+            let script = format!(r#"BASE64DECODE("{}")"#, expected);
+            assert_eq!(engine.eval::<String>(&script).unwrap(), text);
+        }
+        let round: String = engine
+            .eval(r#"BASE64DECODE(BASE64ENCODE("naïve ✓"))"#)
+            .unwrap();
+        assert_eq!(round, "naïve ✓");
+        assert!(engine.eval::<String>(r#"BASE64DECODE("abc")"#).is_err());
+        assert!(engine.eval::<String>(r#"BASE64DECODE("ab=c")"#).is_err());
+        assert!(engine.eval::<String>(r#"BASE64DECODE("a!==")"#).is_err());
     }
 
     #[test]
-    fn test_log_base10() {
-        let engine = make_engine();
-        let result: f64 = engine.eval("LOG(100.0)").unwrap();
-        assert!((result - 2.0).abs() < 1e-10);
-        let result: f64 = engine.eval("LOG(1000)").unwrap();
-        assert!((result - 3.0).abs() < 1e-10);
-    }
+    fn test_filter_range_returns_matching_rows() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        for (row, (name, qty)) in [("apple", 3.0), ("pear", 12.0), ("plum", 20.0)]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_text(name));
+            grid.insert(CellRef::new(1, row), Cell::new_number(qty));
+        }
 
-    #[test]
-    fn test_log_custom_base() {
-        let engine = make_engine();
-        let result: f64 = engine.eval("LOG(8.0, 2.0)").unwrap();
-        assert!((result - 3.0).abs() < 1e-10);
-        let result: f64 = engine.eval("LOG(27, 3)").unwrap();
-        assert!((result - 3.0).abs() < 1e-10);
-    }
+        let mut engine = Engine::new();
+        let value_cache = ValueCache::default();
+        register_builtins(&mut engine, grid, value_cache);
 
-    #[test]
-    fn test_datediff_same_date() {
-        let engine = make_engine();
-        let result: i64 = engine
-            .eval(r#"DATEDIFF("2025-01-15", "2025-01-15")"#)
+        let rows: rhai::Array = engine
+            .eval(r#"FILTER_RANGE(0, 0, 1, 2, |name| name.starts_with("p"))"#)
             .unwrap();
-        assert_eq!(result, 0);
-    }
+        let rows: Vec<(String, f64)> = rows
+            .into_iter()
+            .map(|row| {
+                let row = row.into_array().unwrap();
+                (
+                    row[0].clone().into_string().unwrap(),
+                    row[1].as_float().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("pear".to_string(), 12.0), ("plum".to_string(), 20.0)]
+        );
 
-    #[test]
-    fn test_datediff_days() {
-        let engine = make_engine();
-        let result: i64 = engine
-            .eval(r#"DATEDIFF("2025-01-15", "2025-01-01")"#)
-            .unwrap();
-        assert_eq!(result, 14 * 86400);
-    }
+        let rows: rhai::Array = engine.eval(r#"FILTER_RANGE(0, 0, 1, 2, "a*")"#).unwrap();
+        assert_eq!(rows.len(), 1);
 
-    #[test]
-    fn test_datediff_negative() {
-        let engine = make_engine();
-        let result: i64 = engine
-            .eval(r#"DATEDIFF("2025-01-01", "2025-01-15")"#)
+        let none: rhai::Array = engine
+            .eval(r#"FILTER_RANGE(0, 0, 1, 2, |name| name == "fig")"#)
             .unwrap();
-        assert_eq!(result, -14 * 86400);
+        assert_eq!(none.len(), 1);
+        assert_eq!(none[0].clone().into_string().unwrap(), "#CALC!");
     }
 
     #[test]
-    fn test_datediff_datetime() {
+    fn test_tonumber_parses_text() {
         let engine = make_engine();
-        let result: i64 = engine
-            .eval(r#"DATEDIFF("2025-01-01 01:00:00", "2025-01-01 00:00:00")"#)
-            .unwrap();
-        assert_eq!(result, 3600);
+        assert_eq!(engine.eval::<f64>(r#"TONUMBER("42")"#).unwrap(), 42.0);
+        assert_eq!(engine.eval::<f64>(r#"TONUMBER(" 2.5 ")"#).unwrap(), 2.5);
+        assert_eq!(engine.eval::<f64>(r#"N("-7")"#).unwrap(), -7.0);
+        assert_eq!(engine.eval::<f64>(r#"N("+0.5")"#).unwrap(), 0.5);
+        assert_eq!(engine.eval::<f64>("TONUMBER(5)").unwrap(), 5.0);
+        assert!(engine.eval::<f64>(r#"TONUMBER("abc")"#).is_err());
+        assert!(engine.eval::<f64>(r#"TONUMBER("inf")"#).is_err());
+        assert_eq!(engine.eval::<f64>(r#"TONUMBER("abc", 0)"#).unwrap(), 0.0);
+        assert_eq!(engine.eval::<f64>(r#"N("abc", -1.5)"#).unwrap(), -1.5);
+        assert_eq!(engine.eval::<f64>(r#"N("12", 0)"#).unwrap(), 12.0);
     }
 
     #[test]
-    fn test_datediff_invalid() {
+    fn test_regex_builtins() {
         let engine = make_engine();
-        let result: Result<i64, _> = engine.eval(r#"DATEDIFF("not-a-date", "2025-01-01")"#);
-        assert!(result.is_err());
+        assert!(
+            engine
+                .eval::<bool>(r#"REGEXMATCH("order-1234", "\\d{4}$")"#)
+                .unwrap()
+        );
+        assert!(
+            !engine
+                .eval::<bool>(r#"REGEXMATCH("order", "\\d")"#)
+                .unwrap()
+        );
+        assert_eq!(
+            engine
+                .eval::<String>(r#"REGEXEXTRACT("ann@example.com", "@(\\w+)\\.")"#)
+                .unwrap(),
+            "example"
+        );
+        assert_eq!(
+            engine
+                .eval::<String>(r#"REGEXEXTRACT("ab12cd", "\\d+")"#)
+                .unwrap(),
+            "12"
+        );
+        assert!(
+            engine
+                .eval::<String>(r#"REGEXEXTRACT("abc", "\\d")"#)
+                .is_err()
+        );
+        assert_eq!(
+            engine
+                .eval::<String>(r#"REGEXREPLACE("2025-01-31", "(\\d+)-(\\d+)-(\\d+)", "$3/$2/$1")"#)
+                .unwrap(),
+            "31/01/2025"
+        );
+        assert!(engine.eval::<bool>(r#"REGEXMATCH("x", "(")"#).is_err());
     }
 
     #[test]
-    fn test_median_odd() {
+    fn test_isformula_and_isref() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(5.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(1.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(3.0));
+        grid.insert(CellRef::new(0, 0), Cell::new_script("1 + 1"));
+        grid.insert(CellRef::new(1, 0), Cell::new_number(5.0));
         let engine = make_engine_with_grid(grid);
-        let result: f64 = engine.eval("MEDIAN_RANGE(0, 0, 0, 2)").unwrap();
-        assert_eq!(result, 3.0);
+
+        assert!(engine.eval::<bool>(r#"ISFORMULA("A1")"#).unwrap());
+        assert!(!engine.eval::<bool>(r#"ISFORMULA("B1")"#).unwrap());
+        assert!(!engine.eval::<bool>(r#"ISFORMULA("C9")"#).unwrap());
+        assert!(engine.eval::<bool>(r#"ISFORMULA("nope")"#).is_err());
+
+        assert!(engine.eval::<bool>(r#"ISREF("A1")"#).unwrap());
+        assert!(engine.eval::<bool>(r#"ISREF("zz100")"#).unwrap());
+        assert!(!engine.eval::<bool>(r#"ISREF("A0")"#).unwrap());
+        assert!(!engine.eval::<bool>(r#"ISREF("1A")"#).unwrap());
+        assert!(!engine.eval::<bool>(r#"ISREF("A1:B2")"#).unwrap());
     }
 
     #[test]
-    fn test_median_even() {
+    fn test_slope_intercept_forecast() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(1.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(2.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(3.0));
-        grid.insert(CellRef::new(0, 3), Cell::new_number(4.0));
+        // y = 2x + 1
+        for (row, x) in [1.0, 2.0, 3.0, 4.0, 5.0].into_iter().enumerate() {
+            grid.insert(CellRef::new(0, row), Cell::new_number(x));
+            grid.insert(CellRef::new(1, row), Cell::new_number(2.0 * x + 1.0));
+            grid.insert(CellRef::new(2, row), Cell::new_number(7.0));
+        }
         let engine = make_engine_with_grid(grid);
-        let result: f64 = engine.eval("MEDIAN_RANGE(0, 0, 0, 3)").unwrap();
-        assert_eq!(result, 2.5);
+
+        let slope: f64 = engine
+            .eval(&preprocess_script("SLOPE(B1:B5, A1:A5)"))
+            .unwrap();
+        assert_eq!(slope, 2.0);
+        let intercept: f64 = engine
+            .eval(&preprocess_script("INTERCEPT(B1:B5, A1:A5)"))
+            .unwrap();
+        assert_eq!(intercept, 1.0);
+        let forecast: f64 = engine
+            .eval(&preprocess_script("FORECAST(10, B1:B5, A1:A5)"))
+            .unwrap();
+        assert_eq!(forecast, 21.0);
+        let forecast: f64 = engine
+            .eval(&preprocess_script("FORECAST(A2 + 0.5, B1:B5, A1:A5)"))
+            .unwrap();
+        assert_eq!(forecast, 6.0);
+
+        // Size mismatch and constant x values are errors
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script("SLOPE(B1:B5, A1:A4)"))
+                .is_err()
+        );
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script("FORECAST(1, B1:B5, C1:C5)"))
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_geomean() {
+    fn test_dsum_with_criteria_block() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(2.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(8.0));
+        let rows: [(&str, &str, f64); 5] = [
+            ("Region", "Product", 0.0),
+            ("East", "Apples", 100.0),
+            ("West", "Apples", 50.0),
+            ("east", "Pears", 25.0),
+            ("North", "Pears", 10.0),
+        ];
+        for (row, (region, product, sales)) in rows.into_iter().enumerate() {
+            grid.insert(CellRef::new(0, row), Cell::new_text(region));
+            grid.insert(CellRef::new(1, row), Cell::new_text(product));
+            if row > 0 {
+                grid.insert(CellRef::new(2, row), Cell::new_number(sales));
+            }
+        }
+        grid.insert(CellRef::new(2, 0), Cell::new_text("Sales"));
+        // Criteria block: Region = East
+        grid.insert(CellRef::new(4, 0), Cell::new_text("Region"));
+        grid.insert(CellRef::new(4, 1), Cell::new_text("East"));
         let engine = make_engine_with_grid(grid);
-        let result: f64 = engine.eval("GEOMEAN_RANGE(0, 0, 0, 1)").unwrap();
-        assert!((result - 4.0).abs() < 1e-10);
+
+        let total: f64 = engine
+            .eval(&preprocess_script(r#"DSUM(A1:C5, "Sales", E1:E2)"#))
+            .unwrap();
+        assert_eq!(total, 125.0);
+        // Field by 1-based column number
+        let total: f64 = engine
+            .eval(&preprocess_script("DSUM(A1:C5, 3, E1:E2)"))
+            .unwrap();
+        assert_eq!(total, 125.0);
+
+        assert!(
+            engine
+                .eval::<f64>(&preprocess_script(r#"DSUM(A1:C5, "Profit", E1:E2)"#))
+                .is_err()
+        );
     }
 
     #[test]
-    fn test_geomean_rejects_non_positive() {
+    fn test_database_matches_whole_number_headers_and_criteria() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(2.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(-1.0));
+        grid.insert(CellRef::new(0, 0), Cell::new_text("Year"));
+        grid.insert(CellRef::new(1, 0), Cell::new_number(2024.0));
+        for (row, (year, amount)) in [(2023.0, 5.0), (2024.0, 7.0)].into_iter().enumerate() {
+            grid.insert(CellRef::new(0, row + 1), Cell::new_number(year));
+            grid.insert(CellRef::new(1, row + 1), Cell::new_number(amount));
+        }
+        grid.insert(CellRef::new(3, 0), Cell::new_text("Year"));
+        grid.insert(CellRef::new(3, 1), Cell::new_number(2024.0));
         let engine = make_engine_with_grid(grid);
-        let result: Result<f64, _> = engine.eval("GEOMEAN_RANGE(0, 0, 0, 1)");
-        assert!(result.is_err());
+
+        let total: f64 = engine
+            .eval(&preprocess_script(r#"DSUM(A1:B3, "2024", D1:D2)"#))
+            .unwrap();
+        assert_eq!(total, 7.0);
     }
 
     #[test]
-    fn test_concat_no_separator() {
+    fn test_address_abs_modes() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_text("hello"));
-        grid.insert(CellRef::new(0, 1), Cell::new_text("world"));
-        let engine = make_engine_with_grid(grid);
-        let result: String = engine.eval("CONCAT_RANGE(0, 0, 0, 1)").unwrap();
-        assert_eq!(result, "helloworld");
+        let mut engine = Engine::new();
+        register_builtins(&mut engine, grid, ValueCache::default());
+
+        for (mode, expected) in [(1, "$A$1"), (2, "A$1"), (3, "$A1"), (4, "A1")] {
+            let result: String = engine.eval(&format!("ADDRESS(1, 1, {})", mode)).unwrap();
+            assert_eq!(result, expected);
+        }
+        let result: String = engine.eval("ADDRESS(12, 28)").unwrap();
+        assert_eq!(result, "$AB$12");
+
+        for bad in [
+            "ADDRESS(1, 1, 5)",
+            "ADDRESS(1, 1, 0)",
+            "ADDRESS(0, 1)",
+            "ADDRESS(1, -2)",
+        ] {
+            assert!(engine.eval::<String>(bad).is_err(), "{bad}");
+        }
     }
 
     #[test]
-    fn test_concat_with_separator() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_text("hello"));
-        grid.insert(CellRef::new(0, 1), Cell::new_text("world"));
-        let engine = make_engine_with_grid(grid);
-        let result: String = engine.eval(r#"CONCAT_RANGE(0, 0, 0, 1, ", ")"#).unwrap();
-        assert_eq!(result, "hello, world");
+    fn test_text_number_formats() {
+        let engine = make_engine_with_grid(std::sync::Arc::new(DashMap::new()));
+        let text = |expr: &str| engine.eval::<String>(expr).unwrap();
+
+        assert_eq!(text(r##"TEXT(1234.5, "#,##0.00")"##), "1,234.50");
+        assert_eq!(text(r##"TEXT(1234567, "#,##0")"##), "1,234,567");
+        assert_eq!(text(r##"TEXT(-1234.5, "$#,##0")"##), "-$1,235");
+        assert_eq!(text(r##"TEXT(0.5, "#.##")"##), ".5");
+        assert_eq!(text(r#"TEXT(7, "000")"#), "007");
+        assert_eq!(text(r#"TEXT(0.256, "0.0%")"#), "25.6%");
+        assert_eq!(text(r#"TEXT(1, "0%")"#), "100%");
+        assert_eq!(text(r#"TEXT(45658, "yyyy-mm-dd")"#), "2025-01-01");
+
+        for bad in [
+            r#"TEXT(1, "abc")"#,
+            r#"TEXT(1, "0;-0")"#,
+            r#"TEXT(1, "0.0.0")"#,
+            r#"TEXT(1, "0.0E+0")"#,
+        ] {
+            let err = engine.eval::<String>(bad).unwrap_err().to_string();
+            assert!(err.contains("unsupported format"), "{bad}: {err}");
+        }
     }
 
     #[test]
-    fn test_concat_skips_empty() {
+    fn test_countifs_two_pairs_and_size_mismatch() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_text("a"));
-        // row 1 is empty
-        grid.insert(CellRef::new(0, 2), Cell::new_text("b"));
+        for (row, (fruit, qty)) in [
+            ("apple", 5.0),
+            ("apricot", 1.0),
+            ("banana", 8.0),
+            ("avocado", 9.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_text(fruit));
+            grid.insert(CellRef::new(1, row), Cell::new_number(qty));
+        }
         let engine = make_engine_with_grid(grid);
-        let result: String = engine.eval(r#"CONCAT_RANGE(0, 0, 0, 2, "-")"#).unwrap();
-        assert_eq!(result, "a-b");
+        let script = preprocess_script(r#"COUNTIFS(A1:A4, "a*", B1:B4, ">2")"#);
+        assert_eq!(
+            script,
+            r#"COUNTIFS_RANGES(0, 0, 0, 3, "a*", 1, 0, 1, 3, ">2")"#
+        );
+        assert_eq!(engine.eval::<i64>(&script).unwrap(), 2);
+        assert_eq!(
+            engine
+                .eval::<i64>(&preprocess_script(r#"COUNTIFS(A1:A4, "a*")"#))
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            engine
+                .eval::<i64>(&preprocess_script(
+                    r#"COUNTIFS(A1:A4, "a*", B1:B4, ">2", B1:B4, "<9")"#
+                ))
+                .unwrap(),
+            1
+        );
+        let mismatched =
+            engine.eval::<i64>(&preprocess_script(r#"COUNTIFS(A1:A4, "a*", B1:B3, ">2")"#));
+        assert!(
+            mismatched
+                .unwrap_err()
+                .to_string()
+                .contains("COUNTIFS: ranges must have the same size")
+        );
     }
 
     #[test]
-    fn test_lookup_string_match() {
+    fn test_sumproduct_three_ranges_and_size_mismatch() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_text("alice"));
-        grid.insert(CellRef::new(0, 1), Cell::new_text("bob"));
-        grid.insert(CellRef::new(0, 2), Cell::new_text("carol"));
-        grid.insert(CellRef::new(1, 0), Cell::new_number(10.0));
-        grid.insert(CellRef::new(1, 1), Cell::new_number(20.0));
-        grid.insert(CellRef::new(1, 2), Cell::new_number(30.0));
+        for (row, (a, b, c)) in [(1.0, 2.0, 3.0), (4.0, 5.0, 6.0), (7.0, 8.0, 9.0)]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_number(a));
+            grid.insert(CellRef::new(1, row), Cell::new_number(b));
+            grid.insert(CellRef::new(2, row), Cell::new_number(c));
+        }
+        grid.insert(CellRef::new(3, 0), Cell::new_text("n/a"));
+        grid.insert(CellRef::new(3, 1), Cell::new_number(1.0));
+        grid.insert(CellRef::new(3, 2), Cell::new_number(1.0));
         let engine = make_engine_with_grid(grid);
-        let result: f64 = engine
-            .eval(r#"LOOKUP_IMPL("bob", 0, 0, 0, 2, 1, 0, 1, 2)"#)
-            .unwrap();
-        assert_eq!(result, 20.0);
+
+        let script = preprocess_script("SUMPRODUCT(A1:A3, B1:B3, C1:C3)");
+        assert_eq!(
+            script,
+            "SUMPRODUCT_RANGES(0, 0, 0, 2, 1, 0, 1, 2, 2, 0, 2, 2)"
+        );
+        // 1*2*3 + 4*5*6 + 7*8*9
+        assert_eq!(engine.eval::<f64>(&script).unwrap(), 630.0);
+        assert_eq!(
+            engine
+                .eval::<f64>(&preprocess_script("SUMPRODUCT(A1:A3, B1:B3)"))
+                .unwrap(),
+            78.0
+        );
+        // The text cell in D1 contributes 0
+        assert_eq!(
+            engine
+                .eval::<f64>(&preprocess_script("SUMPRODUCT(A1:A3, B1:B3, C1:C3, D1:D3)"))
+                .unwrap(),
+            624.0
+        );
+        let mismatched = engine.eval::<f64>(&preprocess_script("SUMPRODUCT(A1:A3, B1:B2)"));
+        assert!(
+            mismatched
+                .unwrap_err()
+                .to_string()
+                .contains("SUMPRODUCT: ranges must have the same size")
+        );
     }
 
     #[test]
-    fn test_lookup_numeric_match() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(100.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(200.0));
-        grid.insert(CellRef::new(1, 0), Cell::new_text("first"));
-        grid.insert(CellRef::new(1, 1), Cell::new_text("second"));
-        let engine = make_engine_with_grid(grid);
-        let result: String = engine
-            .eval(r#"LOOKUP_IMPL(200.0, 0, 0, 0, 1, 1, 0, 1, 1)"#)
-            .unwrap();
-        assert_eq!(result, "second");
+    fn test_base_conversion_builtins() {
+        let engine = make_engine();
+        assert_eq!(engine.eval::<String>("DEC2HEX(255)").unwrap(), "FF");
+        assert_eq!(engine.eval::<String>("DEC2HEX(255, 4)").unwrap(), "00FF");
+        assert_eq!(engine.eval::<String>("DEC2BIN(10)").unwrap(), "1010");
+        assert_eq!(
+            engine.eval::<String>("DEC2BIN(5.7, 8)").unwrap(),
+            "00000101"
+        );
+        assert_eq!(engine.eval::<i64>(r#"HEX2DEC("FF")"#).unwrap(), 255);
+        assert_eq!(engine.eval::<i64>(r#"HEX2DEC("00ff")"#).unwrap(), 255);
+        assert_eq!(engine.eval::<i64>(r#"BIN2DEC("1010")"#).unwrap(), 10);
+
+        assert!(engine.eval::<String>("DEC2HEX(-1)").is_err());
+        assert!(engine.eval::<String>("DEC2HEX(255, 1)").is_err());
+        assert!(engine.eval::<i64>(r#"HEX2DEC("FG")"#).is_err());
+        assert!(engine.eval::<i64>(r#"BIN2DEC("102")"#).is_err());
+        assert!(engine.eval::<i64>(r#"BIN2DEC("-1")"#).is_err());
     }
 
     #[test]
-    fn test_lookup_not_found() {
+    fn test_networkdays_and_workday() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_text("alice"));
-        grid.insert(CellRef::new(1, 0), Cell::new_number(10.0));
+        grid.insert(CellRef::new(0, 0), Cell::new_text("2025-01-13")); // A1: holiday (Mon)
         let engine = make_engine_with_grid(grid);
-        let result: Result<Dynamic, _> =
-            engine.eval(r#"LOOKUP_IMPL("missing", 0, 0, 0, 0, 1, 0, 1, 0)"#);
-        assert!(result.is_err());
+
+        // Monday to Friday of one week
+        let days: i64 = engine
+            .eval(r#"NETWORKDAYS("2025-01-06", "2025-01-10")"#)
+            .unwrap();
+        assert_eq!(days, 5);
+        // Thursday to Tuesday skips the weekend; reversed dates count negative
+        let days: i64 = engine
+            .eval(r#"NETWORKDAYS("2025-01-09", "2025-01-14")"#)
+            .unwrap();
+        assert_eq!(days, 4);
+        let days: i64 = engine
+            .eval(r#"NETWORKDAYS("2025-01-14", "2025-01-09")"#)
+            .unwrap();
+        assert_eq!(days, -4);
+        let days: i64 = engine
+            .eval(&preprocess_script(
+                r#"NETWORKDAYS("2025-01-09", "2025-01-14", A1:A2)"#,
+            ))
+            .unwrap();
+        assert_eq!(days, 3);
+
+        // Friday + 1 business day is Monday, or Tuesday over the holiday
+        let date: String = engine.eval(r#"WORKDAY("2025-01-10", 1)"#).unwrap();
+        assert_eq!(date, "2025-01-13");
+        let date: String = engine
+            .eval(&preprocess_script(r#"WORKDAY("2025-01-10", 1, A1:A1)"#))
+            .unwrap();
+        assert_eq!(date, "2025-01-14");
+        let date: String = engine.eval(r#"WORKDAY(45670, -5)"#).unwrap(); // 2025-01-13
+        assert_eq!(date, "2025-01-06");
+
+        let err = engine
+            .eval::<i64>(r#"NETWORKDAYS("2025-13-01", "2025-01-10")"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid date"));
     }
 
     #[test]
-    fn test_lookup_mismatched_ranges() {
-        let grid: Grid = std::sync::Arc::new(DashMap::new());
-        let engine = make_engine_with_grid(grid);
-        // search range: 3 cells, return range: 2 cells
-        let result: Result<Dynamic, _> = engine.eval(r#"LOOKUP_IMPL("x", 0, 0, 0, 2, 1, 0, 1, 1)"#);
-        assert!(result.is_err());
+    fn test_division_by_zero_raises_div0_marker() {
+        let engine = make_engine();
+        let err = engine.eval::<i64>("1 / 0").unwrap_err();
+        assert!(err.to_string().contains("#DIV/0!"));
+        let err = engine.eval::<f64>("1 / 0.0").unwrap_err();
+        assert!(err.to_string().contains("#DIV/0!"));
+        // Integer division still truncates
+        assert_eq!(engine.eval::<i64>("7 / 2").unwrap(), 3);
+        assert_eq!(engine.eval::<f64>("7 / 2.0").unwrap(), 3.5);
     }
 
     #[test]
-    fn test_isnumber() {
-        let engine = make_engine();
-        assert_eq!(engine.eval::<bool>("ISNUMBER(42.0)").unwrap(), true);
-        assert_eq!(engine.eval::<bool>("ISNUMBER(42)").unwrap(), true);
-        assert_eq!(engine.eval::<bool>(r#"ISNUMBER("hello")"#).unwrap(), false);
+    fn test_is_builtin_function() {
+        assert!(is_builtin_function("SUM"));
+        assert!(is_builtin_function("averageifs"));
+        assert!(is_builtin_function("NETWORKDAYS"));
+        assert!(is_builtin_function("ABS"));
+        assert!(!is_builtin_function("PROFIT"));
     }
 
     #[test]
-    fn test_istext() {
-        let engine = make_engine();
-        assert_eq!(engine.eval::<bool>(r#"ISTEXT("hello")"#).unwrap(), true);
-        assert_eq!(engine.eval::<bool>("ISTEXT(42)").unwrap(), false);
-        assert_eq!(engine.eval::<bool>("ISTEXT(3.14)").unwrap(), false);
+    fn test_iferror_traps_errors_and_passes_values_through() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        grid.insert(CellRef::new(0, 0), Cell::new_number(5.0));
+        grid.insert(CellRef::new(0, 1), Cell::new_text("#CYCLE!"));
+        let engine = make_engine_with_grid(grid);
+        // B1 is empty, so the division fails
+        let script = preprocess_script("IFERROR(A1 / B1, 0)");
+        assert_eq!(engine.eval::<i64>(&script).unwrap(), 0);
+        let script = preprocess_script("IFERROR(A1 / 2, 0)");
+        assert_eq!(engine.eval::<f64>(&script).unwrap(), 2.5);
+        // Errors thrown by built-ins and error displays are trapped too
+        let script = preprocess_script(r#"IFERROR(DATEDIFF("soon", "2024-01-01"), "none")"#);
+        assert_eq!(engine.eval::<String>(&script).unwrap(), "none");
+        let script = preprocess_script(r#"IFERROR(@A2, "ok")"#);
+        assert_eq!(engine.eval::<String>(&script).unwrap(), "ok");
+        let script = preprocess_script("IFERROR(IFERROR(A1 / B1, 1 / 0.0), -1)");
+        assert_eq!(engine.eval::<i64>(&script).unwrap(), -1);
+        // A call spelled inside a string is left alone
+        let script = preprocess_script(r#"IFERROR("IFERROR(x, y)", 0)"#);
+        assert_eq!(engine.eval::<String>(&script).unwrap(), "IFERROR(x, y)");
+        assert_eq!(engine.eval::<f64>("IFERROR(1.5, 0)").unwrap(), 1.5);
     }
 
     #[test]
-    fn test_isempty() {
-        let engine = make_engine();
-        assert_eq!(engine.eval::<bool>(r#"ISEMPTY("")"#).unwrap(), true);
-        assert_eq!(engine.eval::<bool>("ISEMPTY(())").unwrap(), true);
-        assert_eq!(engine.eval::<bool>(r#"ISEMPTY("hello")"#).unwrap(), false);
-        assert_eq!(engine.eval::<bool>("ISEMPTY(0)").unwrap(), false);
+    fn test_vlookup_string_and_numeric_keys() {
+        let grid: Grid = std::sync::Arc::new(DashMap::new());
+        for (row, (name, price, qty)) in [("apple", 1.5, 10.0), ("banana", 0.25, 20.0)]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_text(name));
+            grid.insert(CellRef::new(1, row), Cell::new_number(price));
+            grid.insert(CellRef::new(2, row), Cell::new_number(qty));
+        }
+        let engine = make_engine_with_grid(grid);
+        let script = preprocess_script(r#"VLOOKUP("banana", A1:C2, 3, false)"#);
+        assert_eq!(script, r#"VLOOKUP_IMPL("banana", 0, 0, 2, 1, 3, false)"#);
+        assert_eq!(engine.eval::<f64>(&script).unwrap(), 20.0);
+        let script = preprocess_script(r#"VLOOKUP("apple", A1:C2, 2)"#);
+        assert_eq!(engine.eval::<f64>(&script).unwrap(), 1.5);
+        // Integer key matching a number in a one-column table
+        let script = preprocess_script("VLOOKUP(20, C1:C2, 1, true)");
+        assert_eq!(engine.eval::<f64>(&script).unwrap(), 20.0);
+        let script = preprocess_script(r#"VLOOKUP("cherry", A1:C2, 2, true)"#);
+        assert!(engine.eval::<Dynamic>(&script).is_err());
+        let err = engine
+            .eval::<Dynamic>(&preprocess_script(r#"VLOOKUP("apple", A1:C2, 4)"#))
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds table width 3"), "{err}");
     }
 
     #[test]
-    fn test_product() {
+    fn test_vlookup_approximate_match_uses_largest_key_not_above() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(2.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(3.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(4.0));
+        for (row, (limit, rate)) in [(0.0, "none"), (10000.0, "low"), (50000.0, "high")]
+            .into_iter()
+            .enumerate()
+        {
+            grid.insert(CellRef::new(0, row), Cell::new_number(limit));
+            grid.insert(CellRef::new(1, row), Cell::new_text(rate));
+        }
+        grid.insert(CellRef::new(3, 0), Cell::new_text("a"));
+        grid.insert(CellRef::new(3, 1), Cell::new_text("m"));
+        grid.insert(CellRef::new(4, 0), Cell::new_number(1.0));
+        grid.insert(CellRef::new(4, 1), Cell::new_number(2.0));
         let engine = make_engine_with_grid(grid);
-        let result: f64 = engine.eval("PRODUCT_RANGE(0, 0, 0, 2)").unwrap();
-        assert_eq!(result, 24.0);
+        let lookup = |script: &str| {
+            engine
+                .eval::<Dynamic>(&preprocess_script(script))
+                .map(|value| value.to_string())
+        };
+        assert_eq!(lookup("VLOOKUP(25000, A1:B3, 2, false)").unwrap(), "low");
+        assert_eq!(lookup("VLOOKUP(50000, A1:B3, 2, false)").unwrap(), "high");
+        assert_eq!(lookup("VLOOKUP(99999, A1:B3, 2, false)").unwrap(), "high");
+        assert!(lookup("VLOOKUP(-1, A1:B3, 2, false)").is_err());
+        // The exact flag still requires an exact key
+        assert!(lookup("VLOOKUP(25000, A1:B3, 2, true)").is_err());
+        assert_eq!(
+            lookup(r#"VLOOKUP("kiwi", D1:E2, 2, false)"#).unwrap(),
+            "1.0"
+        );
+        assert_eq!(lookup(r#"VLOOKUP("z", D1:E2, 2, false)"#).unwrap(), "2.0");
     }
 
     #[test]
-    fn test_product_with_zero() {
+    fn test_vlookup_takes_column_and_flag_from_cells() {
         let grid: Grid = std::sync::Arc::new(DashMap::new());
-        grid.insert(CellRef::new(0, 0), Cell::new_number(5.0));
-        grid.insert(CellRef::new(0, 1), Cell::new_number(0.0));
-        grid.insert(CellRef::new(0, 2), Cell::new_number(3.0));
+        grid.insert(CellRef::new(0, 0), Cell::new_text("a"));
+        grid.insert(CellRef::new(1, 0), Cell::new_text("first"));
+        grid.insert(CellRef::new(0, 1), Cell::new_text("b"));
+        grid.insert(CellRef::new(1, 1), Cell::new_text("second"));
+        grid.insert(CellRef::new(5, 0), Cell::new_number(2.0)); // F1
+        grid.insert(CellRef::new(6, 0), Cell::new_number(0.0)); // G1
+        grid.insert(CellRef::new(5, 1), Cell::new_number(1.5)); // F2
         let engine = make_engine_with_grid(grid);
-        let result: f64 = engine.eval("PRODUCT_RANGE(0, 0, 0, 2)").unwrap();
-        assert_eq!(result, 0.0);
+        let lookup = |script: &str| engine.eval::<Dynamic>(&preprocess_script(script));
+        let text = |script: &str| lookup(script).unwrap().to_string();
+        assert_eq!(text(r#"VLOOKUP("b", A1:B2, @F1, true)"#), "second");
+        assert_eq!(text(r#"VLOOKUP("b", A1:B2, F1)"#), "second");
+        // A zero flag means an approximate match
+        assert_eq!(text(r#"VLOOKUP("c", A1:B2, F1, G1)"#), "second");
+        assert_eq!(text(r#"VLOOKUP("a", A1:B2, 2, 1)"#), "first");
+        let err = lookup(r#"VLOOKUP("a", A1:B2, F2)"#).unwrap_err();
+        assert!(err.to_string().contains("whole number"), "{err}");
+        let err = lookup(r#"VLOOKUP("a", A1:B2, 2, "yes")"#).unwrap_err();
+        assert!(err.to_string().contains("true/false"), "{err}");
     }
 }
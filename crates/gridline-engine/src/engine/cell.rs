@@ -2,6 +2,8 @@
 //!
 //! This module provides the core data types for representing cells:
 //! - [`CellType`] - The type of content in a cell (empty, text, number, or formula)
+//! - [`NumberLocale`] - Grouping/decimal separators recognized on input
+//! - [`CellFormat`] - How a number cell's value is displayed
 //! - [`Cell`] - A cell with content, dependencies, and cached evaluation state
 //! - [`Grid`] - Thread-safe sparse storage for cells (backed by `DashMap`)
 //! - [`ValueCache`] - Thread-safe cache for computed values and array spills
@@ -22,7 +24,105 @@ pub enum CellType {
     Script(String),
 }
 
+/// Number format conventions accepted when parsing cell input.
+///
+/// With a grouping locale, input such as `1,234.56` is stored as the number
+/// `1234.56` instead of text. Plain numbers parse the same under every locale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NumberLocale {
+    /// Only plain numbers such as `1234.56` are recognized.
+    #[default]
+    Plain,
+    /// `,` groups thousands and `.` is the decimal point (`1,234.56`).
+    English,
+    /// `.` groups thousands and `,` is the decimal point (`1.234,56`).
+    European,
+}
+
+impl NumberLocale {
+    /// Parse a number using this locale's grouping and decimal separators.
+    ///
+    /// Grouped input must use groups of exactly three digits, so list-like
+    /// text such as `1,2,3` is not mistaken for a number. Under `European`,
+    /// a `.` always groups, so `1.234` is `1234` rather than `1.234`.
+    pub fn parse_number(self, input: &str) -> Option<f64> {
+        let plain = match self {
+            NumberLocale::European => !input.contains(['.', ',']),
+            NumberLocale::Plain | NumberLocale::English => true,
+        };
+        if plain && let Ok(n) = input.parse::<f64>() {
+            return Some(n);
+        }
+        let (group, decimal) = match self {
+            NumberLocale::Plain => return None,
+            NumberLocale::English => (',', '.'),
+            NumberLocale::European => ('.', ','),
+        };
+
+        let (sign, body) = match input.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", input.strip_prefix('+').unwrap_or(input)),
+        };
+        let (int_part, frac_part) = match body.split_once(decimal) {
+            Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+            None => (body, None),
+        };
+
+        let mut groups = int_part.split(group);
+        let first = groups.next()?;
+        let max_first = if int_part.contains(group) {
+            3
+        } else {
+            usize::MAX
+        };
+        if first.is_empty() || first.len() > max_first || !first.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let mut digits = first.to_string();
+        let mut grouped = false;
+        for g in groups {
+            if g.len() != 3 || !g.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            digits.push_str(g);
+            grouped = true;
+        }
+        if let Some(frac) = frac_part {
+            if frac.is_empty() || !frac.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            digits.push('.');
+            digits.push_str(frac);
+        } else if !grouped {
+            return None;
+        }
+
+        format!("{}{}", sign, digits).parse::<f64>().ok()
+    }
+}
+
+/// Display format for a number cell, inferred from how the value was typed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CellFormat {
+    /// Plain number display.
+    #[default]
+    General,
+    /// Typed as `50%`: stored as `0.5`, displayed and edited as `50%`.
+    Percent,
+}
+
+/// Render a fraction as the percentage digits a user would type (`0.025` -> `2.5`).
+pub fn percent_input_digits(n: f64) -> String {
+    // Round away binary noise such as 0.07 * 100 = 7.000000000000001.
+    let pct = (n * 100.0 * 1e9).round() / 1e9;
+    pct.to_string()
+}
+
 /// A cell in the spreadsheet grid.
+///
+/// Equality and hashing consider only what the cell holds (its contents and
+/// number format), never cached results, dirty flags or extracted dependencies.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Cell {
     pub contents: CellType,
@@ -31,6 +131,9 @@ pub struct Cell {
     /// Cached display string for script cells (not serialized).
     #[serde(skip)]
     pub cached_value: Option<String>,
+    /// Display format for number cells.
+    #[serde(default)]
+    pub format: CellFormat,
 }
 
 impl Cell {
@@ -40,6 +143,7 @@ impl Cell {
             depends_on: vec![],
             dirty: false,
             cached_value: None,
+            format: CellFormat::General,
         }
     }
 
@@ -49,6 +153,7 @@ impl Cell {
             depends_on: vec![],
             dirty: false,
             cached_value: None,
+            format: CellFormat::General,
         }
     }
 
@@ -58,6 +163,7 @@ impl Cell {
             depends_on: vec![],
             dirty: false,
             cached_value: None,
+            format: CellFormat::General,
         }
     }
 
@@ -69,6 +175,7 @@ impl Cell {
             contents: CellType::Script(script.to_string()),
             dirty: true,
             cached_value: None,
+            format: CellFormat::General,
         }
     }
 
@@ -77,8 +184,15 @@ impl Cell {
     /// - Starts with '=' -> Script (without the '=')
     /// - Quoted string -> Text (without quotes)
     /// - Valid number -> Number
+    /// - Number with a trailing `%` -> Number divided by 100, formatted as percent
     /// - Otherwise -> Text
     pub fn from_input(input: &str) -> Cell {
+        Cell::from_input_with_locale(input, NumberLocale::Plain)
+    }
+
+    /// Parse user input like [`Cell::from_input`], recognizing grouped numbers
+    /// (e.g. `1,234.56`) according to `locale`.
+    pub fn from_input_with_locale(input: &str, locale: NumberLocale) -> Cell {
         let trimmed = input.trim();
         if trimmed.is_empty() {
             return Cell::new_empty();
@@ -93,13 +207,28 @@ impl Cell {
             return Cell::new_text(text);
         }
 
-        if let Ok(n) = trimmed.parse::<f64>() {
+        if let Some(n) = locale.parse_number(trimmed) {
             return Cell::new_number(n);
         }
 
+        if let Some(n) = trimmed
+            .strip_suffix('%')
+            .and_then(|digits| locale.parse_number(digits.trim_end()))
+        {
+            return Cell::new_percent(n / 100.0);
+        }
+
         Cell::new_text(trimmed)
     }
 
+    /// Create a number cell displayed as a percentage (`0.5` shows as `50%`).
+    pub fn new_percent(n: f64) -> Cell {
+        Cell {
+            format: CellFormat::Percent,
+            ..Cell::new_number(n)
+        }
+    }
+
     /// Get a display string for the cell content (for editing).
     pub fn to_input_string(&self) -> String {
         match &self.contents {
@@ -107,12 +236,76 @@ impl Cell {
             // Always round-trip Text as a quoted string so UIs can fetch the
             // current input and write it back without changing the cell type.
             CellType::Text(s) => format!("\"{}\"", s),
+            CellType::Number(n) if self.format == CellFormat::Percent => {
+                format!("{}%", percent_input_digits(*n))
+            }
             CellType::Number(n) => n.to_string(),
             CellType::Script(s) => format!("={}", s),
         }
     }
 }
 
+impl PartialEq for CellType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CellType::Empty, CellType::Empty) => true,
+            (CellType::Text(a), CellType::Text(b)) | (CellType::Script(a), CellType::Script(b)) => {
+                a == b
+            }
+            // Bitwise so equality agrees with hashing (NaN == NaN, 0.0 != -0.0).
+            (CellType::Number(a), CellType::Number(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for CellType {}
+
+impl PartialEq for Cell {
+    fn eq(&self, other: &Self) -> bool {
+        self.contents == other.contents && self.format == other.format
+    }
+}
+
+impl Eq for Cell {}
+
+impl std::hash::Hash for Cell {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        state.write_u64(self.content_hash());
+    }
+}
+
+impl Cell {
+    /// Hash of the cell's contents and format, stable across runs and builds
+    /// (64-bit FNV-1a over a tagged encoding), so it can be stored or compared
+    /// between sessions. Cells that are `==` always have the same hash.
+    pub fn content_hash(&self) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let number_bytes;
+        let (tag, payload): (u8, &[u8]) = match &self.contents {
+            CellType::Empty => (0, &[]),
+            CellType::Text(s) => (1, s.as_bytes()),
+            CellType::Number(n) => {
+                number_bytes = n.to_bits().to_le_bytes();
+                (2, &number_bytes)
+            }
+            CellType::Script(s) => (3, s.as_bytes()),
+        };
+        let format = match self.format {
+            CellFormat::General => 0,
+            CellFormat::Percent => 1,
+        };
+        [tag, format]
+            .iter()
+            .chain(payload.iter())
+            .fold(FNV_OFFSET, |hash, &byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            })
+    }
+}
+
 /// Thread-safe sparse grid storage.
 /// Wrapped in Arc so clones share the same underlying data.
 pub type Grid = std::sync::Arc<DashMap<CellRef, Cell>>;
@@ -123,3 +316,12 @@ pub type Grid = std::sync::Arc<DashMap<CellRef, Cell>>;
 /// - Cell references to use pre-computed values instead of re-evaluating
 /// - Array formulas to store spill values for chaining
 pub type ValueCache = Arc<DashMap<CellRef, rhai::Dynamic>>;
+
+/// Thread-safe map from spilled output cells to the formula cell they spilled from.
+/// Shared with the engine so builtins can tell spill outputs from ordinary cells.
+pub type SpillMap = Arc<DashMap<CellRef, CellRef>>;
+
+/// Thread-safe map from cells currently displaying an error (`#ERR: ...`,
+/// `#CYCLE!`, `#SPILL!`, ...) to that display text.
+/// Shared with the engine so `ERRORTYPE` can inspect other cells' errors.
+pub type ErrorMap = Arc<DashMap<CellRef, String>>;
@@ -17,6 +17,15 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::sync::OnceLock;
 
+/// Which parts of a reference are pinned with `$` (absolute), e.g. the column
+/// in `$A1` and both parts in `$A$1`. Pinned parts don't move when rows or
+/// columns are inserted or deleted, or when a formula is copied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Absolute {
+    pub col: bool,
+    pub row: bool,
+}
+
 /// A reference to a cell by column and row indices (0-indexed).
 #[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CellRef {
@@ -55,6 +64,36 @@ impl CellRef {
         Some(CellRef::new(col, row))
     }
 
+    /// Parse a reference that may pin its column and/or row with `$`
+    /// (`$A$1`, `$A1`, `A$1` or plain `A1`).
+    pub fn parse_absolute(name: &str) -> Option<(CellRef, Absolute)> {
+        let (col, rest) = match name.strip_prefix('$') {
+            Some(rest) => (true, rest),
+            None => (false, name),
+        };
+        let split = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .filter(|&split| split > 0)?;
+        let (letters, numbers) = rest.split_at(split);
+        let (row, numbers) = match numbers.strip_prefix('$') {
+            Some(numbers) => (true, numbers),
+            None => (false, numbers),
+        };
+        let cell = Self::parse_a1(&format!("{}{}", letters, numbers))?;
+        Some((cell, Absolute { col, row }))
+    }
+
+    /// Format in A1 notation with `$` before each pinned part.
+    pub fn to_absolute_string(&self, absolute: Absolute) -> String {
+        format!(
+            "{}{}{}{}",
+            if absolute.col { "$" } else { "" },
+            CellRef::col_to_letters(self.col),
+            if absolute.row { "$" } else { "" },
+            self.row + 1
+        )
+    }
+
     /// Convert column index to spreadsheet-style letters (0 -> A, 25 -> Z, 26 -> AA).
     pub fn col_to_letters(col: usize) -> String {
         let mut result = String::new();
@@ -92,7 +131,7 @@ impl fmt::Display for CellRef {
 
 #[cfg(test)]
 mod tests {
-    use super::CellRef;
+    use super::{Absolute, CellRef};
 
     #[test]
     fn test_parse_a1_overflow_returns_none() {
@@ -100,6 +139,26 @@ mod tests {
         assert!(CellRef::from_str(&huge).is_none());
     }
 
+    #[test]
+    fn test_parse_absolute_round_trips_markers() {
+        for (text, col, row) in [
+            ("$A$1", true, true),
+            ("$B2", true, false),
+            ("C$3", false, true),
+            ("D4", false, false),
+        ] {
+            let (cell, absolute) = CellRef::parse_absolute(text).unwrap();
+            assert_eq!(absolute, Absolute { col, row });
+            assert_eq!(cell.to_absolute_string(absolute), text);
+        }
+        assert_eq!(
+            CellRef::parse_absolute("$A$1").unwrap().0,
+            CellRef::new(0, 0)
+        );
+        assert!(CellRef::parse_absolute("$$A1").is_none());
+        assert!(CellRef::parse_absolute("A1$").is_none());
+    }
+
     #[test]
     fn test_col_to_letters_handles_max_usize() {
         let letters = CellRef::col_to_letters(usize::MAX);
@@ -7,6 +7,12 @@
 //! Handles:
 //! - Simple cell references: `A1`, `B2`
 //! - Range references in functions: `SUM(A1:B5)`
+//! - Table lookups: `VLOOKUP(x, A1:C10, 2, false)`
+//! - Two-range functions: `LOOKUP(x, A1:A5, B1:B5)`, `FORECAST(x, B1:B5, A1:A5)`,
+//!   `DSUM(A1:C5, "Sales", E1:E2)`, `AVERAGEIFS(B1:B5, A1:A5, c)`
+//! - Multi-pair criteria: `COUNTIFS(A1:A5, ">2", B1:B5, "x")`
+//! - Several ranges: `SUMPRODUCT(A1:A5, B1:B5, C1:C5)`
+//! - Trailing-range functions: `ZSCORE(@B1, A1:A5)`
 //! - Ignores references inside string literals
 
 use regex::Regex;
@@ -18,76 +24,93 @@ const MAX_DEPENDENCY_RANGE_CELLS: usize = 1_000_000;
 
 /// Extract all cell references from a script as dependencies.
 pub fn extract_dependencies(script: &str) -> Vec<CellRef> {
+    let (cells, ranges) = extract_references(script);
     let mut deps = Vec::new();
+    for (start, end) in &ranges {
+        push_range_dependencies(&mut deps, start, end);
+    }
+    deps.extend(cells);
+    deps
+}
+
+/// Extract the single-cell references and the ranges (as corner pairs) a
+/// script refers to, without expanding the ranges.
+pub fn extract_references(script: &str) -> (Vec<CellRef>, Vec<(CellRef, CellRef)>) {
+    let mut deps = Vec::new();
+    let mut ranges = Vec::new();
+
+    // ERRORTYPE names its target cell in a string, which the literal
+    // stripping below would hide, so pick those targets up first.
+    for caps in errortype_target_re().captures_iter(script) {
+        if let Some(cell_ref) = CellRef::from_str(&caps[1]) {
+            deps.push(cell_ref);
+        }
+    }
+
+    // Ignore references inside string literals, and split multi-argument
+    // SUM/AVG calls so each range is seen whole.
+    let script = super::preprocess::expand_multi_arg_aggregates(&strip_string_literals(
+        &super::preprocess::strip_absolute_markers(script),
+    ));
 
-    // Ignore references inside string literals.
-    let script = strip_string_literals(script);
-
-    // Match LOOKUP(value, search_range, return_range) — two ranges
-    let lookup_re = crate::builtins::lookup_fn_re();
-    let script_without_lookups = lookup_re.replace_all(&script, "").to_string();
-
-    for caps in lookup_re.captures_iter(&script) {
-        // Extract both search range (groups 2-3) and return range (groups 4-5)
-        for (start_group, end_group) in [(2, 3), (4, 5)] {
-            if let (Some(start), Some(end)) = (
-                CellRef::from_str(&caps[start_group]),
-                CellRef::from_str(&caps[end_group]),
-            ) {
-                let min_row = start.row.min(end.row);
-                let max_row = start.row.max(end.row);
-                let min_col = start.col.min(end.col);
-                let max_col = start.col.max(end.col);
-
-                let row_count = max_row - min_row + 1;
-                let col_count = max_col - min_col + 1;
-                let Some(cell_count) = row_count.checked_mul(col_count) else {
+    // Match LOOKUP(value, search_range, return_range), paired-range functions
+    // like AVERAGEIFS(B1:B5, A1:A5, ...) and trailing-range functions like
+    // ZSCORE(x, A1:A5), along with the capture groups holding each range and
+    // what to leave behind (ZSCORE keeps its leading arguments for scanning)
+    let mut script_without_pairs = script.clone();
+    let two_ranges: &[(usize, usize)] = &[(2, 3), (4, 5)];
+    for (pair_re, range_groups, remainder) in [
+        (crate::builtins::lookup_fn_re(), two_ranges, ""),
+        (
+            crate::builtins::vlookup_fn_re(),
+            &[(2, 3)],
+            " ${1} ${4} ${5} ",
+        ),
+        (crate::builtins::forecast_fn_re(), two_ranges, " ${1} "),
+        (crate::builtins::dsum_fn_re(), &[(1, 2), (4, 5)], " ${3} "),
+        (
+            crate::builtins::countifs_fn_re(),
+            &[(1, 2), (4, 5), (7, 8)],
+            " ${3} ${6} ${9} ",
+        ),
+        (
+            crate::builtins::sumproduct_fn_re(),
+            &[(1, 2), (3, 4), (5, 6), (7, 8)],
+            "",
+        ),
+        (crate::builtins::paired_range_fn_re(), two_ranges, ""),
+        (crate::builtins::trailing_range_fn_re(), &[(3, 4)], " ${2} "),
+    ] {
+        for caps in pair_re.captures_iter(&script_without_pairs) {
+            for &(start_group, end_group) in range_groups {
+                // Optional groups (later COUNTIFS pairs) may not have matched
+                let (Some(start), Some(end)) = (caps.get(start_group), caps.get(end_group)) else {
                     continue;
                 };
-                if cell_count > MAX_DEPENDENCY_RANGE_CELLS {
-                    continue;
-                }
-
-                for row in min_row..=max_row {
-                    for col in min_col..=max_col {
-                        deps.push(CellRef::new(col, row));
-                    }
+                if let (Some(start), Some(end)) = (
+                    CellRef::from_str(start.as_str()),
+                    CellRef::from_str(end.as_str()),
+                ) {
+                    ranges.push((start, end));
                 }
             }
         }
+        script_without_pairs = pair_re
+            .replace_all(&script_without_pairs, remainder)
+            .to_string();
     }
 
     // Match range functions like SUM(A1:B5, ...)
     let range_re = crate::builtins::range_fn_re();
 
     // First, remove range function calls from the script to avoid double-counting
-    let script_without_ranges = range_re
-        .replace_all(&script_without_lookups, "")
-        .to_string();
+    let script_without_ranges = range_re.replace_all(&script_without_pairs, "").to_string();
 
     // Extract dependencies from ranges
-    for caps in range_re.captures_iter(&script_without_lookups) {
+    for caps in range_re.captures_iter(&script_without_pairs) {
         if let (Some(start), Some(end)) = (CellRef::from_str(&caps[2]), CellRef::from_str(&caps[3]))
         {
-            let min_row = start.row.min(end.row);
-            let max_row = start.row.max(end.row);
-            let min_col = start.col.min(end.col);
-            let max_col = start.col.max(end.col);
-
-            let row_count = max_row - min_row + 1;
-            let col_count = max_col - min_col + 1;
-            let Some(cell_count) = row_count.checked_mul(col_count) else {
-                continue;
-            };
-            if cell_count > MAX_DEPENDENCY_RANGE_CELLS {
-                continue;
-            }
-
-            for row in min_row..=max_row {
-                for col in min_col..=max_col {
-                    deps.push(CellRef::new(col, row));
-                }
-            }
+            ranges.push((start, end));
         }
     }
 
@@ -101,7 +124,39 @@ pub fn extract_dependencies(script: &str) -> Vec<CellRef> {
         }
     }
 
-    deps
+    (deps, ranges)
+}
+
+/// Count cell reference tokens (outside string literals) without expanding ranges.
+///
+/// `SUM(A1:B5) + C1` counts as three references. This is a cheap, linear pass
+/// suitable for rejecting pathological formulas before heavier preprocessing.
+pub fn count_cell_references(script: &str) -> usize {
+    let script = strip_string_literals(&super::preprocess::strip_absolute_markers(script));
+    cell_ref_re().find_iter(&script).count()
+}
+
+/// Push every cell of a range, skipping ranges too large to track.
+fn push_range_dependencies(deps: &mut Vec<CellRef>, start: &CellRef, end: &CellRef) {
+    let min_row = start.row.min(end.row);
+    let max_row = start.row.max(end.row);
+    let min_col = start.col.min(end.col);
+    let max_col = start.col.max(end.col);
+
+    let row_count = max_row - min_row + 1;
+    let col_count = max_col - min_col + 1;
+    let Some(cell_count) = row_count.checked_mul(col_count) else {
+        return;
+    };
+    if cell_count > MAX_DEPENDENCY_RANGE_CELLS {
+        return;
+    }
+
+    for row in min_row..=max_row {
+        for col in min_col..=max_col {
+            deps.push(CellRef::new(col, row));
+        }
+    }
 }
 
 fn cell_ref_re() -> &'static Regex {
@@ -112,6 +167,14 @@ fn cell_ref_re() -> &'static Regex {
     })
 }
 
+fn errortype_target_re() -> &'static Regex {
+    static ERRORTYPE_RE: OnceLock<Regex> = OnceLock::new();
+    ERRORTYPE_RE.get_or_init(|| {
+        Regex::new(r#"\bERRORTYPE\s*\(\s*"([A-Za-z]+[0-9]+)"\s*\)"#)
+            .expect("ERRORTYPE target regex must compile")
+    })
+}
+
 fn strip_string_literals(script: &str) -> String {
     let mut out = String::with_capacity(script.len());
     let mut in_string = false;
@@ -166,4 +229,72 @@ mod tests {
         let deps = extract_dependencies("SUM(A1:A1000001)+B2");
         assert_eq!(deps, vec![CellRef::new(1, 1)]);
     }
+
+    #[test]
+    fn test_extract_dependencies_paired_ranges() {
+        let deps = extract_dependencies(r#"AVERAGEIFS(B1:B2, A1:A2, ">1") + C1"#);
+        assert_eq!(
+            deps,
+            vec![
+                CellRef::new(1, 0),
+                CellRef::new(1, 1),
+                CellRef::new(0, 0),
+                CellRef::new(0, 1),
+                CellRef::new(2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_dependencies_countifs_pairs() {
+        let deps = extract_dependencies(r#"COUNTIFS(A1:A2, ">1", B1:B2, "x")"#);
+        assert_eq!(
+            deps,
+            vec![
+                CellRef::new(0, 0),
+                CellRef::new(0, 1),
+                CellRef::new(1, 0),
+                CellRef::new(1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_dependencies_trailing_range_keeps_value_ref() {
+        let deps = extract_dependencies("ZSCORE(@C1, A1:A2)");
+        assert_eq!(
+            deps,
+            vec![CellRef::new(0, 0), CellRef::new(0, 1), CellRef::new(2, 0)]
+        );
+    }
+
+    #[test]
+    fn test_count_cell_references_ignores_strings() {
+        assert_eq!(count_cell_references(r#"SUM(A1:B5) + C1 + "D4""#), 3);
+        assert_eq!(count_cell_references("1 + 2"), 0);
+    }
+
+    #[test]
+    fn test_forecast_dependencies() {
+        let deps = extract_dependencies("FORECAST(D1, B1:B2, A1:A2)");
+        for cell in ["D1", "B1", "B2", "A1", "A2"] {
+            assert!(deps.contains(&CellRef::from_str(cell).unwrap()), "{cell}");
+        }
+        assert_eq!(deps.len(), 5);
+    }
+
+    #[test]
+    fn test_vlookup_dependencies_cover_whole_table() {
+        let deps = extract_dependencies("VLOOKUP(@E1, A1:B2, F1, false)");
+        for cell in ["E1", "A1", "B1", "A2", "B2", "F1"] {
+            assert!(deps.contains(&CellRef::from_str(cell).unwrap()), "{cell}");
+        }
+        assert_eq!(deps.len(), 6);
+    }
+
+    #[test]
+    fn test_errortype_depends_on_named_cell() {
+        let deps = extract_dependencies(r#"ERRORTYPE("A7") + LEN("B2")"#);
+        assert_eq!(deps, vec![CellRef::from_str("A7").unwrap()]);
+    }
 }
@@ -6,27 +6,67 @@
 
 use rhai::{Engine, EvalAltResult};
 
-use super::{AST, Dynamic, Grid, ValueCache};
+use super::{AST, Dynamic, ErrorMap, Grid, SpillMap, ValueCache};
 use crate::builtins::ScriptModifications;
 
 const MAX_SCRIPT_OPERATIONS: u64 = 5_000_000;
-const MAX_CALL_LEVELS: usize = 64;
 const MAX_EXPR_DEPTH: usize = 64;
 const MAX_FUNCTION_EXPR_DEPTH: usize = 32;
 const MAX_ARRAY_SIZE: usize = 100_000;
 const MAX_STRING_SIZE: usize = 1_000_000;
 const MAX_VARIABLES: usize = 10_000;
 
+/// Default bound on nested function calls (see [`set_max_call_depth`]).
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 64;
+
 fn configure_engine_limits(engine: &mut Engine) {
     engine
         .set_max_operations(MAX_SCRIPT_OPERATIONS)
-        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_call_levels(DEFAULT_MAX_CALL_DEPTH)
         .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_FUNCTION_EXPR_DEPTH)
         .set_max_array_size(MAX_ARRAY_SIZE)
         .set_max_string_size(MAX_STRING_SIZE)
         .set_max_variables(MAX_VARIABLES);
 }
 
+/// Choose whether blank cells read as 0 in arithmetic and range builtins (the
+/// default) or raise an error, so missing data is caught instead of summed as 0.
+/// The setting lives in the engine's default tag and applies to every evaluation.
+pub fn set_blanks_are_zero(engine: &mut Engine, blanks_are_zero: bool) {
+    engine.set_default_tag(blanks_are_zero);
+}
+
+/// Bound how deeply function calls may nest, so runaway recursion in a custom
+/// function fails with a recursion-limit error (see [`is_recursion_limit`])
+/// instead of exhausting the stack.
+pub fn set_max_call_depth(engine: &mut Engine, depth: usize) {
+    engine.set_max_call_levels(depth);
+}
+
+/// Whether an evaluation error is the call-depth limit being hit, possibly
+/// reported from inside nested function calls.
+pub fn is_recursion_limit(err: &EvalAltResult) -> bool {
+    match err {
+        EvalAltResult::ErrorStackOverflow(_) => true,
+        EvalAltResult::ErrorInFunctionCall(_, _, inner, _)
+        | EvalAltResult::ErrorInModule(_, inner, _) => is_recursion_limit(inner),
+        _ => false,
+    }
+}
+
+/// Register `ISSPILL` and `SPILLSOURCE`, which read the document's spill map.
+/// The map is owned by the document rather than the engine, so it is attached
+/// separately once the engine has been created.
+pub fn register_spill_builtins(engine: &mut Engine, spill_sources: SpillMap) {
+    crate::builtins::register_spill_builtins(engine, spill_sources);
+}
+
+/// Register `ERRORTYPE`, which reads the document's map of error displays.
+/// Like the spill map, it is owned by the document and attached separately.
+pub fn register_error_builtins(engine: &mut Engine, errors: ErrorMap) {
+    crate::builtins::register_error_builtins(engine, errors);
+}
+
 /// Create a Rhai engine with built-ins registered.
 pub fn create_engine(grid: Grid) -> Engine {
     let value_cache = ValueCache::default();
@@ -34,6 +74,8 @@ pub fn create_engine(grid: Grid) -> Engine {
 }
 
 /// Create a Rhai engine with built-ins registered and shared value cache.
+/// Cell formulas are evaluated here, so write builtins (`SET_CELL`, `CLEAR_CELL`, ...)
+/// are deliberately not registered; they only exist on the script engine.
 pub fn create_engine_with_cache(grid: Grid, value_cache: ValueCache) -> Engine {
     let mut engine = Engine::new();
     configure_engine_limits(&mut engine);
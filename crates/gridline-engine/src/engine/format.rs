@@ -23,6 +23,38 @@ pub fn format_dynamic(value: &Dynamic) -> String {
     }
 }
 
+/// Display of a formula that divided by zero. Division raises an evaluation
+/// error carrying this marker, which the document shows in place of `#ERR`.
+pub const DIV_ZERO: &str = "#DIV/0!";
+
+/// Error displays and their `ERRORTYPE` codes, matched by prefix.
+const ERROR_CODES: &[(&str, i64)] = &[
+    ("#ERR", 1),
+    ("#CYCLE!", 2),
+    ("#SPILL", 3),
+    ("#REF!", 4),
+    ("#INF!", 5),
+    ("#NAN!", 6),
+    ("#CALC!", 7),
+    (DIV_ZERO, 8),
+];
+
+/// Classify a cell display as an error, returning its `ERRORTYPE` code
+/// (1 = `#ERR`, 2 = `#CYCLE!`, 3 = `#SPILL!`, 4 = `#REF!`, 5 = `#INF!`,
+/// 6 = `#NAN!`, 7 = `#CALC!`, 8 = `#DIV/0!`), or `None` for ordinary values.
+///
+/// Formulas broken by a row/column deletion are kept as text like
+/// `=A1 + #REF!`, so those count as `#REF!` too.
+pub fn error_type_code(display: &str) -> Option<i64> {
+    if display.starts_with('=') && display.contains("#REF!") {
+        return Some(4);
+    }
+    ERROR_CODES
+        .iter()
+        .find(|(prefix, _)| display.starts_with(prefix))
+        .map(|&(_, code)| code)
+}
+
 /// Format a number for display.
 pub fn format_number(n: f64) -> String {
     if n.is_nan() {
@@ -35,3 +67,13 @@ pub fn format_number(n: f64) -> String {
         format!("{:.2}", n)
     }
 }
+
+/// Format a fraction as a percentage for display (`0.5` -> `50%`).
+pub fn format_percent(n: f64) -> String {
+    if !n.is_finite() {
+        return format_number(n);
+    }
+    // Round first so 0.07 (7.000000000000001 after scaling) shows as a whole percent.
+    let pct = (n * 100.0 * 1e9).round() / 1e9;
+    format!("{}%", format_number(pct))
+}
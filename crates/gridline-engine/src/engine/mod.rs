@@ -19,19 +19,26 @@ mod eval;
 mod format;
 mod preprocess;
 
-pub use cell::{Cell, CellType, Grid, ValueCache};
-pub use cell_ref::CellRef;
+pub use cell::{
+    Cell, CellFormat, CellType, ErrorMap, Grid, NumberLocale, SpillMap, ValueCache,
+    percent_input_digits,
+};
+pub use cell_ref::{Absolute, CellRef};
 pub use cycle::detect_cycle;
-pub use deps::{extract_dependencies, parse_range};
+pub use deps::{count_cell_references, extract_dependencies, extract_references, parse_range};
 pub use eval::{
-    create_engine, create_engine_with_cache, create_engine_with_functions,
+    DEFAULT_MAX_CALL_DEPTH, create_engine, create_engine_with_cache, create_engine_with_functions,
     create_engine_with_functions_and_cache, create_script_engine,
     create_script_engine_with_functions, eval_with_functions, eval_with_functions_script,
+    is_recursion_limit, register_error_builtins, register_spill_builtins, set_blanks_are_zero,
+    set_max_call_depth,
 };
-pub use format::{format_dynamic, format_number};
+pub use format::{DIV_ZERO, error_type_code, format_dynamic, format_number, format_percent};
 pub use preprocess::{
-    ShiftOperation, offset_formula_references, preprocess_script, preprocess_script_with_context,
-    shift_formula_references,
+    ShiftOperation, blank_string_literals, expand_column_ranges, expand_row_references,
+    has_column_ranges, has_row_references, offset_formula_references, preprocess_script,
+    preprocess_script_with_context, rename_cell_reference, shift_formula_references,
+    strip_absolute_markers, transpose_formula_references,
 };
 
 pub use rhai::{AST, Dynamic};
@@ -5,7 +5,10 @@
 //!
 //! - **Preprocessing**: Converting `A1` → `CELL(0, 0)` and `@A1` → `VALUE(0, 0)`
 //! - **Range functions**: Converting `SUM(A1:B5)` → `SUM_RANGE(0, 0, 1, 4)` (col/row)
+//! - **Array literals**: Converting `{1, 2; 3, 4}` → `[[1, 2], [3, 4]]` so they spill
 //! - **Reference shifting**: Adjusting references when rows/columns are inserted/deleted
+//! - **Absolute references**: `$A$1`, `$A1` and `A$1` pin a column and/or row
+//!   against shifting and copying; the `$` markers are dropped before evaluation
 
 use regex::Regex;
 use std::sync::OnceLock;
@@ -37,11 +40,77 @@ fn value_ref_re() -> &'static Regex {
     })
 }
 
+/// Like `cell_ref_re`, but also matching `$` markers (`$A$1`, `$A1`, `A$1`).
+fn absolute_ref_re() -> &'static Regex {
+    static ABSOLUTE_RE: OnceLock<Regex> = OnceLock::new();
+    ABSOLUTE_RE.get_or_init(|| {
+        Regex::new(r"\$?\b[A-Za-z]+\$?[0-9]+\b")
+            .expect("absolute cell reference regex must compile")
+    })
+}
+
+/// Like `value_ref_re`, but also matching `$` markers (`@$A$1`).
+fn absolute_value_ref_re() -> &'static Regex {
+    static ABSOLUTE_VALUE_RE: OnceLock<Regex> = OnceLock::new();
+    ABSOLUTE_VALUE_RE.get_or_init(|| {
+        Regex::new(r"@(\$?[A-Za-z]+\$?[0-9]+)\b")
+            .expect("absolute value reference regex must compile")
+    })
+}
+
+/// Drop the `$` markers from absolute references outside string literals,
+/// leaving plain A1 references (`$A$1` becomes `A1`). Pinning only matters
+/// when formulas are rewritten, not when they are evaluated.
+pub fn strip_absolute_markers(script: &str) -> String {
+    if !script.contains('$') {
+        return script.to_string();
+    }
+    map_outside_strings(script, |seg| {
+        absolute_ref_re()
+            .replace_all(seg, |caps: &regex::Captures| caps[0].replace('$', ""))
+            .to_string()
+    })
+}
+
 fn row_context_re() -> &'static Regex {
     static ROW_RE: OnceLock<Regex> = OnceLock::new();
     ROW_RE.get_or_init(|| Regex::new(r"\bROW\(\s*\)").expect("ROW() regex must compile"))
 }
 
+fn row_relative_re() -> &'static Regex {
+    static ROW_RELATIVE_RE: OnceLock<Regex> = OnceLock::new();
+    ROW_RELATIVE_RE.get_or_init(|| {
+        Regex::new(r"@row\.([A-Za-z]+)\b").expect("row-relative reference regex must compile")
+    })
+}
+
+/// Whether `script` uses same-row references like `@row.B`.
+pub fn has_row_references(script: &str) -> bool {
+    row_relative_re().is_match(script)
+}
+
+/// Rewrite same-row references like `@row.B` to column B of `row` (0-based).
+/// They bind to the row the formula is in, so the relationship survives the
+/// formula's row being moved.
+pub fn expand_row_references(script: &str, row: usize) -> String {
+    if !has_row_references(script) {
+        return script.to_string();
+    }
+    let row_literal = (row as u128 + 1).to_string();
+    row_relative_re()
+        .replace_all(script, |caps: &regex::Captures| {
+            format!("{}{}", caps[1].to_ascii_uppercase(), row_literal)
+        })
+        .to_string()
+}
+
+fn column_range_re() -> &'static Regex {
+    static COLUMN_RANGE_RE: OnceLock<Regex> = OnceLock::new();
+    COLUMN_RANGE_RE.get_or_init(|| {
+        Regex::new(r"\b([A-Z]+):([A-Z]+)\b").expect("column range regex must compile")
+    })
+}
+
 fn col_context_re() -> &'static Regex {
     static COL_RE: OnceLock<Regex> = OnceLock::new();
     COL_RE.get_or_init(|| Regex::new(r"\bCOL\(\s*\)").expect("COL() regex must compile"))
@@ -77,7 +146,10 @@ pub fn shift_formula_references(formula: &str, op: ShiftOperation) -> String {
             let idx = replacements.len();
             replacements.push(format!(
                 "{}({}:{}{})",
-                func_name, new_start, new_end, rest_args
+                func_name,
+                new_start,
+                new_end,
+                shift_cell_refs_outside_strings(rest_args, op)
             ));
             format!("@@@{}@@@", idx)
         })
@@ -127,7 +199,10 @@ pub fn offset_formula_references(formula: &str, delta_col: isize, delta_row: isi
             } else {
                 replacements.push(format!(
                     "{}({}:{}{})",
-                    func_name, new_start, new_end, rest_args
+                    func_name,
+                    new_start,
+                    new_end,
+                    offset_cell_refs_outside_strings(rest_args, delta_col, delta_row)
                 ));
             }
             format!("@@@{}@@@", idx)
@@ -147,51 +222,233 @@ pub fn offset_formula_references(formula: &str, delta_col: isize, delta_row: isi
     restored
 }
 
+/// Rewrite a formula moved from cell `from` to cell `to` by a transposing
+/// paste, swapping the row and column offset of each reference from the cell.
+///
+/// Rules:
+/// - copied from B1 to A2, `A1` (one column left) becomes `A1` (one row up)
+/// - range endpoints are mapped individually: `SUM(A1:C1)` becomes `SUM(A1:A3)`
+///   when pasted from A2 to A2
+/// - a part pinned with `$` stays put and keeps its marker: `$A$1` is unchanged
+/// - refs that move out of bounds become `#REF!`
+/// - references inside string literals are untouched
+pub fn transpose_formula_references(formula: &str, from: &CellRef, to: &CellRef) -> String {
+    let cell_re = absolute_ref_re();
+    map_outside_strings(formula, |seg| {
+        cell_re
+            .replace_all(seg, |caps: &regex::Captures| {
+                let Some((cr, absolute)) = CellRef::parse_absolute(&caps[0]) else {
+                    return caps[0].to_string();
+                };
+                let new_col = if absolute.col {
+                    cr.col as i128
+                } else {
+                    to.col as i128 + cr.row as i128 - from.row as i128
+                };
+                let new_row = if absolute.row {
+                    cr.row as i128
+                } else {
+                    to.row as i128 + cr.col as i128 - from.col as i128
+                };
+                if new_col < 0
+                    || new_row < 0
+                    || new_col > usize::MAX as i128
+                    || new_row > usize::MAX as i128
+                {
+                    return "#REF!".to_string();
+                }
+                CellRef::new(new_col as usize, new_row as usize).to_absolute_string(absolute)
+            })
+            .replace("@#REF!", "#REF!")
+    })
+}
+
+/// Rewrite references to the single cell `from` so they point at `to`.
+/// Used when a cell is moved so formulas follow it.
+///
+/// Rules:
+/// - `A1` and `@A1` become `C3` and `@C3` when moving A1 to C3
+/// - pinned references follow too, keeping their markers: `$A$1` becomes `$C$3`
+/// - range endpoints (`A1:B5`) are left alone; only the one cell moved
+/// - references inside string literals are untouched
+pub fn rename_cell_reference(formula: &str, from: &CellRef, to: &CellRef) -> String {
+    let cell_re = absolute_ref_re();
+    map_outside_strings(formula, |seg| {
+        cell_re
+            .replace_all(seg, |caps: &regex::Captures| {
+                let whole = caps.get(0).expect("match has group 0");
+                let in_range =
+                    seg[..whole.start()].ends_with(':') || seg[whole.end()..].starts_with(':');
+                match CellRef::parse_absolute(whole.as_str()) {
+                    Some((cr, absolute)) if !in_range && cr == *from => {
+                        to.to_absolute_string(absolute)
+                    }
+                    _ => whole.as_str().to_string(),
+                }
+            })
+            .to_string()
+    })
+}
+
+/// Whether `script` may contain a whole-column range such as `A:A`
+/// (a cheap check before computing bounds for [`expand_column_ranges`]).
+pub fn has_column_ranges(script: &str) -> bool {
+    column_range_re().is_match(script)
+}
+
+/// Expand whole-column ranges like `A:A` or `B:D` into ordinary ranges
+/// covering rows `first_row..=last_row` (0-based), e.g. `A2:A10`.
+///
+/// The caller picks the bounds (typically below any header rows and down to
+/// the last used row), so the result can go through dependency extraction and
+/// [`preprocess_script`] like any other range. Column letters must be
+/// uppercase so map literals such as `#{a:b}` are left alone.
+pub fn expand_column_ranges(script: &str, first_row: usize, last_row: usize) -> String {
+    if !has_column_ranges(script) {
+        return script.to_string();
+    }
+    let re = column_range_re();
+    map_outside_strings(script, |segment| {
+        re.replace_all(segment, |caps: &regex::Captures| {
+            match (
+                CellRef::from_str(&format!("{}1", &caps[1])),
+                CellRef::from_str(&format!("{}1", &caps[2])),
+            ) {
+                (Some(start), Some(end)) => format!(
+                    "{}:{}",
+                    CellRef::new(start.col, first_row),
+                    CellRef::new(end.col, last_row)
+                ),
+                _ => caps[0].to_string(),
+            }
+        })
+        .to_string()
+    })
+}
+
+/// Apply `f` to each part of `script` outside string literals.
+fn map_outside_strings(script: &str, f: impl Fn(&str) -> String) -> String {
+    map_string_parts(script, f, str::to_string)
+}
+
+/// `script` with the contents of string literals replaced by spaces. The
+/// quotes stay and byte offsets are unchanged, so positions found in the
+/// result can be used to slice `script`.
+pub fn blank_string_literals(script: &str) -> String {
+    map_string_parts(script, str::to_string, |literal| {
+        literal
+            .char_indices()
+            .map(|(i, c)| {
+                if i == 0 || (c == '"' && i + 1 == literal.len()) {
+                    c.to_string()
+                } else {
+                    " ".repeat(c.len_utf8())
+                }
+            })
+            .collect()
+    })
+}
+
+/// Apply `outside` to each part of `script` outside string literals and
+/// `literal` to each string literal, quotes included.
+fn map_string_parts(
+    script: &str,
+    outside: impl Fn(&str) -> String,
+    literal: impl Fn(&str) -> String,
+) -> String {
+    let bytes = script.as_bytes();
+    let mut out = String::new();
+    let mut seg_start = 0;
+    let mut in_string = false;
+    let mut backslashes = 0usize;
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if in_string {
+            if b == b'\\' {
+                backslashes += 1;
+                continue;
+            }
+            if b == b'"' && backslashes.is_multiple_of(2) {
+                out.push_str(&literal(&script[seg_start..=i]));
+                in_string = false;
+                seg_start = i + 1;
+            }
+            backslashes = 0;
+        } else if b == b'"' {
+            out.push_str(&outside(&script[seg_start..i]));
+            in_string = true;
+            seg_start = i;
+            backslashes = 0;
+        }
+    }
+
+    if seg_start < script.len() {
+        if in_string {
+            out.push_str(&literal(&script[seg_start..]));
+        } else {
+            out.push_str(&outside(&script[seg_start..]));
+        }
+    }
+    out
+}
+
+/// Shift one reference for `op`. A part pinned with `$` is left alone, so
+/// `$A$1` keeps pointing at A1 whatever is inserted or deleted.
 fn shift_single_ref(cell_ref_str: &str, op: ShiftOperation) -> String {
-    let Some(cr) = CellRef::from_str(cell_ref_str) else {
+    let Some((cr, absolute)) = CellRef::parse_absolute(cell_ref_str) else {
         return cell_ref_str.to_string();
     };
+    let moved = |col, row| CellRef::new(col, row).to_absolute_string(absolute);
 
     match op {
+        ShiftOperation::InsertRow(_) | ShiftOperation::DeleteRow(_) if absolute.row => {
+            cell_ref_str.to_string()
+        }
+        ShiftOperation::InsertColumn(_) | ShiftOperation::DeleteColumn(_) if absolute.col => {
+            cell_ref_str.to_string()
+        }
         ShiftOperation::InsertRow(at_row) => {
             if cr.row >= at_row {
-                CellRef::new(cr.col, cr.row + 1).to_string()
+                moved(cr.col, cr.row + 1)
             } else {
-                cr.to_string()
+                moved(cr.col, cr.row)
             }
         }
         ShiftOperation::DeleteRow(at_row) => {
             if cr.row == at_row {
                 "#REF!".to_string()
             } else if cr.row > at_row {
-                CellRef::new(cr.col, cr.row - 1).to_string()
+                moved(cr.col, cr.row - 1)
             } else {
-                cr.to_string()
+                moved(cr.col, cr.row)
             }
         }
         ShiftOperation::InsertColumn(at_col) => {
             if cr.col >= at_col {
-                CellRef::new(cr.col + 1, cr.row).to_string()
+                moved(cr.col + 1, cr.row)
             } else {
-                cr.to_string()
+                moved(cr.col, cr.row)
             }
         }
         ShiftOperation::DeleteColumn(at_col) => {
             if cr.col == at_col {
                 "#REF!".to_string()
             } else if cr.col > at_col {
-                CellRef::new(cr.col - 1, cr.row).to_string()
+                moved(cr.col - 1, cr.row)
             } else {
-                cr.to_string()
+                moved(cr.col, cr.row)
             }
         }
     }
 }
 
+/// Offset one reference, leaving any part pinned with `$` in place.
 fn offset_single_ref(cell_ref_str: &str, delta_col: isize, delta_row: isize) -> String {
-    let Some(cr) = CellRef::from_str(cell_ref_str) else {
+    let Some((cr, absolute)) = CellRef::parse_absolute(cell_ref_str) else {
         return cell_ref_str.to_string();
     };
+    let delta_col = if absolute.col { 0 } else { delta_col };
+    let delta_row = if absolute.row { 0 } else { delta_row };
 
     let new_col = cr.col as i128 + delta_col as i128;
     let new_row = cr.row as i128 + delta_row as i128;
@@ -199,20 +456,30 @@ fn offset_single_ref(cell_ref_str: &str, delta_col: isize, delta_row: isize) ->
         return "#REF!".to_string();
     }
 
-    CellRef::new(new_col as usize, new_row as usize).to_string()
+    CellRef::new(new_col as usize, new_row as usize).to_absolute_string(absolute)
 }
 
 fn shift_cell_refs_outside_strings(script: &str, op: ShiftOperation) -> String {
-    let cell_re = cell_ref_re();
-    let value_re = value_ref_re();
+    let cell_re = absolute_ref_re();
+    let value_re = absolute_value_ref_re();
 
     let shift_cells = |seg: &str| {
-        // First handle @-prefixed refs using placeholders to avoid double-shifting.
+        // Same-row references only move with column inserts/deletes.
+        let seg = row_relative_re().replace_all(seg, |caps: &regex::Captures| match op {
+            ShiftOperation::InsertColumn(_) | ShiftOperation::DeleteColumn(_) => {
+                match CellRef::from_str(&shift_single_ref(&format!("{}1", &caps[1]), op)) {
+                    Some(cr) => format!("@row.{}", CellRef::col_to_letters(cr.col)),
+                    None => "#REF!".to_string(),
+                }
+            }
+            _ => caps[0].to_string(),
+        });
+
+        // Then handle @-prefixed refs using placeholders to avoid double-shifting.
         let mut value_refs: Vec<String> = Vec::new();
         let seg = value_re
-            .replace_all(seg, |caps: &regex::Captures| {
-                let cell_ref = format!("{}{}", &caps[1], &caps[2]);
-                let shifted = shift_single_ref(&cell_ref, op);
+            .replace_all(&seg, |caps: &regex::Captures| {
+                let shifted = shift_single_ref(&caps[1], op);
                 let idx = value_refs.len();
                 if shifted == "#REF!" {
                     value_refs.push(shifted);
@@ -226,8 +493,7 @@ fn shift_cell_refs_outside_strings(script: &str, op: ShiftOperation) -> String {
         // Then handle regular refs
         let shifted = cell_re
             .replace_all(&seg, |caps: &regex::Captures| {
-                let cell_ref = format!("{}{}", &caps[1], &caps[2]);
-                shift_single_ref(&cell_ref, op)
+                shift_single_ref(&caps[0], op)
             })
             .to_string();
 
@@ -292,15 +558,14 @@ fn shift_cell_refs_outside_strings(script: &str, op: ShiftOperation) -> String {
 }
 
 fn offset_cell_refs_outside_strings(script: &str, delta_col: isize, delta_row: isize) -> String {
-    let cell_re = cell_ref_re();
-    let value_re = value_ref_re();
+    let cell_re = absolute_ref_re();
+    let value_re = absolute_value_ref_re();
 
     let offset_cells = |seg: &str| {
         let mut value_refs: Vec<String> = Vec::new();
         let seg = value_re
             .replace_all(seg, |caps: &regex::Captures| {
-                let cell_ref = format!("{}{}", &caps[1], &caps[2]);
-                let shifted = offset_single_ref(&cell_ref, delta_col, delta_row);
+                let shifted = offset_single_ref(&caps[1], delta_col, delta_row);
                 let idx = value_refs.len();
                 if shifted == "#REF!" {
                     value_refs.push(shifted);
@@ -313,8 +578,7 @@ fn offset_cell_refs_outside_strings(script: &str, delta_col: isize, delta_row: i
 
         let shifted = cell_re
             .replace_all(&seg, |caps: &regex::Captures| {
-                let cell_ref = format!("{}{}", &caps[1], &caps[2]);
-                offset_single_ref(&cell_ref, delta_col, delta_row)
+                offset_single_ref(&caps[0], delta_col, delta_row)
             })
             .to_string();
 
@@ -389,13 +653,15 @@ pub fn preprocess_script(script: &str) -> String {
 /// When context is provided, ROW() and COL() are replaced with 1-based row/col values (row/col ordering is unchanged).
 // NOTE: builtin coordinate order is col/row.
 pub fn preprocess_script_with_context(script: &str, context: Option<&CellRef>) -> String {
+    let script = &strip_absolute_markers(script);
     // First, replace ROW() and COL() if context is provided
     let script = if let Some(cell_ref) = context {
         let row_re = row_context_re();
         let col_re = col_context_re();
         let row_literal = (cell_ref.row as u128 + 1).to_string();
         let col_literal = (cell_ref.col as u128 + 1).to_string();
-        let script = row_re.replace_all(script, row_literal).to_string();
+        let script = expand_row_references(script, cell_ref.row);
+        let script = row_re.replace_all(&script, row_literal).to_string();
         col_re.replace_all(&script, col_literal).to_string()
     } else {
         script.to_string()
@@ -405,6 +671,10 @@ pub fn preprocess_script_with_context(script: &str, context: Option<&CellRef>) -
 }
 
 fn preprocess_script_inner(script: &str) -> String {
+    let script = &delay_iferror_values(&expand_multi_arg_aggregates(&translate_array_literals(
+        script,
+    )));
+
     // Preprocess LOOKUP(value, search_range, return_range) before standard range functions.
     // Converts: LOOKUP(expr, A1:A5, B1:B5) → LOOKUP_IMPL(expr, 0, 0, 0, 4, 1, 0, 1, 4)
     let script = crate::builtins::lookup_fn_re()
@@ -431,6 +701,181 @@ fn preprocess_script_inner(script: &str) -> String {
         })
         .to_string();
 
+    // VLOOKUP(value, table, col_index[, exact]) → VLOOKUP_IMPL(value, 0, 0, 2, 9, col_index, exact)
+    // The match is exact unless the flag says otherwise.
+    let script = crate::builtins::vlookup_fn_re()
+        .replace_all(&script, |caps: &regex::Captures| {
+            if let (Some(start), Some(end)) =
+                (CellRef::from_str(&caps[2]), CellRef::from_str(&caps[3]))
+            {
+                format!(
+                    "VLOOKUP_IMPL({}, {}, {}, {}, {}, {}, {})",
+                    &caps[1],
+                    start.col,
+                    start.row,
+                    end.col,
+                    end.row,
+                    &caps[4],
+                    caps.get(5).map_or("true", |exact| exact.as_str())
+                )
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .to_string();
+
+    // FORECAST(x, ys_range, xs_range) → FORECAST_RANGES(x, 1, 0, 1, 4, 0, 0, 0, 4)
+    let script = crate::builtins::forecast_fn_re()
+        .replace_all(&script, |caps: &regex::Captures| {
+            if let (Some(ys), Some(ye), Some(xs), Some(xe)) = (
+                CellRef::from_str(&caps[2]),
+                CellRef::from_str(&caps[3]),
+                CellRef::from_str(&caps[4]),
+                CellRef::from_str(&caps[5]),
+            ) {
+                format!(
+                    "FORECAST_RANGES({}, {}, {}, {}, {}, {}, {}, {}, {})",
+                    &caps[1], ys.col, ys.row, ye.col, ye.row, xs.col, xs.row, xe.col, xe.row
+                )
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .to_string();
+
+    // DSUM(A1:C5, "Sales", E1:E2) → DSUM_RANGES(0, 0, 2, 4, "Sales", 4, 0, 4, 1)
+    let script = crate::builtins::dsum_fn_re()
+        .replace_all(&script, |caps: &regex::Captures| {
+            if let (Some(ds), Some(de), Some(ks), Some(ke)) = (
+                CellRef::from_str(&caps[1]),
+                CellRef::from_str(&caps[2]),
+                CellRef::from_str(&caps[4]),
+                CellRef::from_str(&caps[5]),
+            ) {
+                format!(
+                    "DSUM_RANGES({}, {}, {}, {}, {}, {}, {}, {}, {})",
+                    ds.col,
+                    ds.row,
+                    de.col,
+                    de.row,
+                    caps[3].trim(),
+                    ks.col,
+                    ks.row,
+                    ke.col,
+                    ke.row
+                )
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .to_string();
+
+    // COUNTIFS(A1:A5, ">2", B1:B5, "x") → COUNTIFS_RANGES(0, 0, 0, 4, ">2", 1, 0, 1, 4, "x")
+    let script = crate::builtins::countifs_fn_re()
+        .replace_all(&script, |caps: &regex::Captures| {
+            let mut args = Vec::new();
+            for pair in 0..3 {
+                let (Some(start), Some(end), Some(crit)) = (
+                    caps.get(3 * pair + 1),
+                    caps.get(3 * pair + 2),
+                    caps.get(3 * pair + 3),
+                ) else {
+                    break;
+                };
+                let (Some(start), Some(end)) = (
+                    CellRef::from_str(start.as_str()),
+                    CellRef::from_str(end.as_str()),
+                ) else {
+                    return caps[0].to_string();
+                };
+                args.push(format!(
+                    "{}, {}, {}, {}, {}",
+                    start.col,
+                    start.row,
+                    end.col,
+                    end.row,
+                    crit.as_str().trim()
+                ));
+            }
+            format!("COUNTIFS_RANGES({})", args.join(", "))
+        })
+        .to_string();
+
+    // SUMPRODUCT(A1:A5, B1:B5) → SUMPRODUCT_RANGES(0, 0, 0, 4, 1, 0, 1, 4)
+    let script = crate::builtins::sumproduct_fn_re()
+        .replace_all(&script, |caps: &regex::Captures| {
+            let mut args = Vec::new();
+            for range in 0..4 {
+                let (Some(start), Some(end)) = (caps.get(2 * range + 1), caps.get(2 * range + 2))
+                else {
+                    break;
+                };
+                let (Some(start), Some(end)) = (
+                    CellRef::from_str(start.as_str()),
+                    CellRef::from_str(end.as_str()),
+                ) else {
+                    return caps[0].to_string();
+                };
+                args.push(format!(
+                    "{}, {}, {}, {}",
+                    start.col, start.row, end.col, end.row
+                ));
+            }
+            format!("SUMPRODUCT_RANGES({})", args.join(", "))
+        })
+        .to_string();
+
+    // Paired-range functions: AVERAGEIFS(B1:B5, A1:A5, c) → AVERAGEIFS_RANGES(1, 0, 1, 4, 0, 0, 0, 4, c)
+    let script = crate::builtins::paired_range_fn_re()
+        .replace_all(&script, |caps: &regex::Captures| {
+            let rest_args = caps.get(6).map(|m| m.as_str()).unwrap_or("");
+            let Some(rhai_name) = crate::builtins::paired_range_rhai_name(&caps[1]) else {
+                return caps[0].to_string();
+            };
+            if let (Some(s1), Some(e1), Some(s2), Some(e2)) = (
+                CellRef::from_str(&caps[2]),
+                CellRef::from_str(&caps[3]),
+                CellRef::from_str(&caps[4]),
+                CellRef::from_str(&caps[5]),
+            ) {
+                format!(
+                    "{}({}, {}, {}, {}, {}, {}, {}, {}{})",
+                    rhai_name,
+                    s1.col,
+                    s1.row,
+                    e1.col,
+                    e1.row,
+                    s2.col,
+                    s2.row,
+                    e2.col,
+                    e2.row,
+                    rest_args
+                )
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .to_string();
+
+    // Trailing-range functions: ZSCORE(@B1, A1:A5) → ZSCORE_RANGE(@B1, 0, 0, 0, 4)
+    let script = crate::builtins::trailing_range_fn_re()
+        .replace_all(&script, |caps: &regex::Captures| {
+            let Some(rhai_name) = crate::builtins::trailing_range_rhai_name(&caps[1]) else {
+                return caps[0].to_string();
+            };
+            if let (Some(start), Some(end)) =
+                (CellRef::from_str(&caps[3]), CellRef::from_str(&caps[4]))
+            {
+                format!(
+                    "{}({}, {}, {}, {}, {})",
+                    rhai_name, &caps[2], start.col, start.row, end.col, end.row
+                )
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .to_string();
+
     let with_ranges = crate::builtins::range_fn_re()
         .replace_all(&script, |caps: &regex::Captures| {
             let start_ref = &caps[2];
@@ -457,6 +902,295 @@ fn preprocess_script_inner(script: &str) -> String {
     replace_cell_refs_outside_strings(&with_ranges)
 }
 
+fn lone_range_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^\s*([A-Za-z]+[0-9]+):([A-Za-z]+[0-9]+)\s*$")
+            .expect("lone range regex must compile")
+    })
+}
+
+/// Rewrite `SUM`/`AVG` calls with several arguments into sums of their parts,
+/// so ranges, cell references and other expressions can be mixed:
+/// `SUM(A1:A3, 10, B1)` becomes `(SUM(A1:A3) + (10) + (B1))`, and
+/// `AVG(A1:A3, 10)` divides that sum by the combined count, `/ 4.0`.
+/// Single-argument calls (the nested ones included) are left as they are.
+pub(crate) fn expand_multi_arg_aggregates(script: &str) -> String {
+    if !script.contains("SUM(") && !script.contains("AVG(") {
+        return script.to_string();
+    }
+    let blanked = blank_string_literals(script);
+    let mut out = String::with_capacity(script.len());
+    let mut copied = 0;
+    let mut calls: Vec<usize> = blanked
+        .match_indices("SUM(")
+        .chain(blanked.match_indices("AVG("))
+        .map(|(i, _)| i)
+        .collect();
+    calls.sort_unstable();
+    for i in calls {
+        if i < copied || !at_word_start(&blanked, i) {
+            continue;
+        }
+        let Some(close) = matching_paren(&blanked, i + 3) else {
+            continue;
+        };
+        let args = split_top_level_args(&script[i + 4..close]);
+        if args.len() > 1 && args.iter().all(|arg| !arg.trim().is_empty()) {
+            out.push_str(&script[copied..i]);
+            out.push_str(&render_aggregate(&script[i..i + 3], &args));
+            copied = close + 1;
+        }
+    }
+    out.push_str(&script[copied..]);
+    out
+}
+
+/// Index of the `)` closing the `(` at `open` in `blanked`, a script with
+/// its string literals blanked by [`blank_string_literals`].
+fn matching_paren(blanked: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (idx, b) in blanked.bytes().enumerate().skip(open) {
+        match b {
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Wrap the first argument of each two-argument `IFERROR` call in a closure,
+/// `IFERROR(A1 / B1, 0)` becoming `IFERROR(|| A1 / B1, 0)`, so the built-in
+/// can catch an error raised while evaluating it (Rhai evaluates arguments
+/// before the call). Nested calls are wrapped too.
+fn delay_iferror_values(script: &str) -> String {
+    const CALL: &str = "IFERROR(";
+    if !script.contains(CALL) {
+        return script.to_string();
+    }
+    let blanked = blank_string_literals(script);
+    let mut out = String::with_capacity(script.len() + 4);
+    let mut copied = 0;
+    for (i, _) in blanked.match_indices(CALL) {
+        // Calls nested in one already wrapped are handled by the recursion
+        if i < copied || !at_word_start(&blanked, i) {
+            continue;
+        }
+        let open = i + CALL.len() - 1;
+        if let Some(close) = matching_paren(&blanked, open)
+            && let [value, fallback] = split_top_level_args(&script[open + 1..close])[..]
+            && !value.trim().is_empty()
+        {
+            out.push_str(&script[copied..i]);
+            out.push_str(&format!(
+                "IFERROR(|| {}, {})",
+                delay_iferror_values(value.trim()),
+                delay_iferror_values(fallback.trim())
+            ));
+            copied = close + 1;
+        }
+    }
+    out.push_str(&script[copied..]);
+    out
+}
+
+/// Whether `text[i..]` starts a word rather than continuing an identifier.
+fn at_word_start(text: &str, i: usize) -> bool {
+    let bytes = text.as_bytes();
+    i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_')
+}
+
+/// Split call arguments on commas outside brackets and string literals.
+fn split_top_level_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (idx, b) in blank_string_literals(args).bytes().enumerate() {
+        match b {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            b',' if depth == 0 => {
+                parts.push(&args[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&args[start..]);
+    parts
+}
+
+/// The sum (or average) of mixed `SUM`/`AVG` arguments as one expression.
+fn render_aggregate(name: &str, args: &[&str]) -> String {
+    let mut count = 0usize;
+    let terms: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            if let Some(caps) = lone_range_re().captures(arg)
+                && let (Some(start), Some(end)) =
+                    (CellRef::from_str(&caps[1]), CellRef::from_str(&caps[2]))
+            {
+                let cols = start.col.abs_diff(end.col) + 1;
+                let rows = start.row.abs_diff(end.row) + 1;
+                count = count.saturating_add(cols.saturating_mul(rows));
+                format!("SUM({}:{})", &caps[1], &caps[2])
+            } else {
+                count += 1;
+                format!("({})", expand_multi_arg_aggregates(arg.trim()))
+            }
+        })
+        .collect();
+    let sum = terms.join(" + ");
+    if name == "AVG" {
+        format!("(({}) / {}.0)", sum, count)
+    } else {
+        format!("({})", sum)
+    }
+}
+
+/// Translate `{...}` array constants into Rhai arrays: `,` separates columns
+/// and `;` rows. A single row `{1, 2}` becomes `[[1, 2]]` (spills right), a
+/// single column `{1; 2}` becomes `[1, 2]` (spills down) and anything larger
+/// an array of rows.
+///
+/// Braces are only treated as a literal where a value can start (formula
+/// start or after `(`, `,`, `[` or an operator), so blocks such as
+/// `if x { a } else { b }`, closure bodies and `#{...}` maps are untouched,
+/// as is anything containing an empty element or a `let`/`const` statement.
+fn translate_array_literals(script: &str) -> String {
+    if !script.contains('{') {
+        return script.to_string();
+    }
+    let chars: Vec<char> = script.chars().collect();
+    let mut out = String::with_capacity(script.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            i += 1;
+            continue;
+        }
+        if c == '"' {
+            in_string = true;
+        } else if c == '{'
+            && literal_can_start(&out)
+            && let Some((rows, end)) = parse_array_literal(&chars, i + 1)
+        {
+            out.push_str(&render_array_literal(rows));
+            i = end + 1;
+            continue;
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+/// Whether a `{` following `preceding` is in value position.
+fn literal_can_start(preceding: &str) -> bool {
+    let trimmed = preceding.trim_end();
+    if trimmed.ends_with("=>") {
+        return false;
+    }
+    match trimmed.chars().last() {
+        None => true,
+        Some(c) => "(,[=+-*/%<>!&^:?".contains(c),
+    }
+}
+
+/// Split the body of an array literal starting at `start` (just past `{`) into
+/// rows of translated element expressions. Returns the rows and the index of
+/// the closing `}`, or `None` if this is not a well-formed literal.
+fn parse_array_literal(chars: &[char], start: usize) -> Option<(Vec<Vec<String>>, usize)> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut element = String::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    fn finish_element(element: &mut String, row: &mut Vec<String>) -> Option<()> {
+        let text = element.trim();
+        let is_statement = ["let ", "const "].iter().any(|kw| text.starts_with(kw));
+        if text.is_empty() || is_statement {
+            return None;
+        }
+        row.push(translate_array_literals(text));
+        element.clear();
+        Some(())
+    }
+
+    for (j, &c) in chars.iter().enumerate().skip(start) {
+        if in_string {
+            element.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                element.push(c);
+            }
+            '(' | '[' | '{' => {
+                depth += 1;
+                element.push(c);
+            }
+            ')' | ']' => {
+                depth = depth.checked_sub(1)?;
+                element.push(c);
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                element.push(c);
+            }
+            '}' => {
+                finish_element(&mut element, &mut row)?;
+                rows.push(row);
+                return Some((rows, j));
+            }
+            ',' if depth == 0 => finish_element(&mut element, &mut row)?,
+            ';' if depth == 0 => {
+                finish_element(&mut element, &mut row)?;
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => element.push(c),
+        }
+    }
+    None
+}
+
+fn render_array_literal(rows: Vec<Vec<String>>) -> String {
+    let join = |row: &[String]| format!("[{}]", row.join(", "));
+    if rows.len() > 1 && rows.iter().all(|row| row.len() == 1) {
+        let column: Vec<String> = rows.into_iter().flatten().collect();
+        return join(&column);
+    }
+    let rows: Vec<String> = rows.iter().map(|row| join(row)).collect();
+    format!("[{}]", rows.join(", "))
+}
+
 fn replace_cell_refs_outside_strings(script: &str) -> String {
     let cell_re = cell_ref_re();
     let value_re = value_ref_re();
@@ -558,6 +1292,40 @@ mod tests {
         assert_eq!(shifted, "VEC(B1:B10) + C1");
     }
 
+    #[test]
+    fn test_shift_formula_references_skips_pinned_parts() {
+        let formula = "$A$1 + $A2 + A$3 + @$B$4 + B5";
+        let shifted = shift_formula_references(formula, ShiftOperation::InsertRow(0));
+        assert_eq!(shifted, "$A$1 + $A3 + A$3 + @$B$4 + B6");
+        let shifted = shift_formula_references(formula, ShiftOperation::InsertColumn(0));
+        assert_eq!(shifted, "$A$1 + $A2 + B$3 + @$B$4 + C5");
+        // A pinned row survives deleting it; the unpinned ref does not
+        let shifted = shift_formula_references("A$1 + A1", ShiftOperation::DeleteRow(0));
+        assert_eq!(shifted, "A$1 + #REF!");
+    }
+
+    #[test]
+    fn test_offset_formula_references_keeps_pinned_parts() {
+        let shifted = offset_formula_references("$A$1 + $A1 + A$1 + A1", 1, 1);
+        assert_eq!(shifted, "$A$1 + $A2 + B$1 + B2");
+    }
+
+    #[test]
+    fn test_absolute_references_evaluate_like_plain_ones() {
+        assert_eq!(
+            strip_absolute_markers(r#"$A$1 + A$2 + "$B$3""#),
+            r#"A1 + A2 + "$B$3""#
+        );
+        assert_eq!(
+            preprocess_script("$A$1 + @A$2"),
+            preprocess_script("A1 + @A2")
+        );
+        assert_eq!(
+            preprocess_script("SUM($A$1:$A$3)"),
+            preprocess_script("SUM(A1:A3)")
+        );
+    }
+
     #[test]
     fn test_offset_formula_references_positive_delta() {
         let formula = "SUM(A1:B2) + @C3 + D4";
@@ -572,6 +1340,25 @@ mod tests {
         assert_eq!(shifted, "#REF! + @A2");
     }
 
+    #[test]
+    fn test_transpose_formula_references_swaps_offsets() {
+        // B1 -> A2: a reference one column left becomes one row up
+        let from = CellRef::new(1, 0);
+        let to = CellRef::new(0, 1);
+        assert_eq!(
+            transpose_formula_references("A1 * 2 + @C1", &from, &to),
+            "A1 * 2 + @A3"
+        );
+        assert_eq!(
+            transpose_formula_references("SUM(A1:C1) & \"B1\"", &from, &to),
+            "SUM(A1:A3) & \"B1\""
+        );
+        // Two columns left of C5 becomes two rows above A1
+        let shifted =
+            transpose_formula_references("A5 + @A5", &CellRef::new(2, 4), &CellRef::new(0, 0));
+        assert_eq!(shifted, "#REF! + #REF!");
+    }
+
     #[test]
     fn test_offset_formula_references_handles_large_columns() {
         let large_col = CellRef::col_to_letters(isize::MAX as usize + 1);
@@ -590,4 +1377,123 @@ mod tests {
             format!("{}+{}", usize::MAX as u128 + 1, usize::MAX as u128 + 1)
         );
     }
+
+    #[test]
+    fn test_preprocess_scalar_min_is_not_a_range_call() {
+        assert_eq!(preprocess_script("MIN(2, 3)"), "MIN(2, 3)");
+        assert_eq!(preprocess_script("MAX(A1, 3)"), "MAX(CELL(0, 0), 3)");
+        assert_eq!(preprocess_script("MIN(A1:A3)"), "MIN_RANGE(0, 0, 0, 2)");
+    }
+
+    #[test]
+    fn test_rename_cell_reference() {
+        let from = CellRef::new(0, 0);
+        let to = CellRef::new(2, 2);
+        assert_eq!(
+            rename_cell_reference("A1 + @A1 + A10", &from, &to),
+            "C3 + @C3 + A10"
+        );
+        assert_eq!(
+            rename_cell_reference("SUM(A1:B2) + a1", &from, &to),
+            "SUM(A1:B2) + C3"
+        );
+        assert_eq!(
+            rename_cell_reference(r#"A1 & "A1""#, &from, &to),
+            r#"C3 & "A1""#
+        );
+    }
+
+    #[test]
+    fn test_pinned_references_follow_rename_transpose_and_range_shifts() {
+        assert_eq!(
+            rename_cell_reference(
+                "$A$1 + A$1 + $A1 + A1 + @$A$1",
+                &CellRef::new(0, 0),
+                &CellRef::new(3, 3)
+            ),
+            "$D$4 + D$4 + $D4 + D4 + @$D$4"
+        );
+        // B1 -> A2: pinned parts stay, relative parts are transposed
+        assert_eq!(
+            transpose_formula_references(
+                "$A$1 + A$1 + $C1",
+                &CellRef::new(1, 0),
+                &CellRef::new(0, 1)
+            ),
+            "$A$1 + A$1 + $C3"
+        );
+        assert_eq!(
+            shift_formula_references("SUM($A1:$A5)", ShiftOperation::DeleteRow(0)),
+            "#REF!"
+        );
+        assert_eq!(
+            shift_formula_references("SUM($A2:$A5) + SUM(A$1:A$3)", ShiftOperation::DeleteRow(0)),
+            "SUM($A1:$A4) + SUM(A$1:A$3)"
+        );
+    }
+
+    #[test]
+    fn test_expand_column_ranges() {
+        assert_eq!(expand_column_ranges("SUM(A:A)", 1, 9), "SUM(A2:A10)");
+        assert_eq!(
+            expand_column_ranges("SUMIF(B:B, \"A:A\", C:D)", 0, 4),
+            "SUMIF(B1:B5, \"A:A\", C1:D5)"
+        );
+        assert_eq!(expand_column_ranges("#{a:b}", 0, 4), "#{a:b}");
+        assert_eq!(expand_column_ranges("SUM(A1:A3)", 0, 4), "SUM(A1:A3)");
+    }
+
+    #[test]
+    fn test_row_references_expand_to_context_row_and_follow_columns() {
+        let context = CellRef::new(4, 6);
+        assert_eq!(expand_row_references("@row.B + @row.aa", 6), "B7 + AA7");
+        assert_eq!(
+            preprocess_script_with_context("@row.B", Some(&context)),
+            preprocess_script("B7")
+        );
+        assert_eq!(expand_row_references("@row.B2", 0), "@row.B2");
+        assert_eq!(
+            shift_formula_references("@row.B + B1", ShiftOperation::InsertRow(0)),
+            "@row.B + B2"
+        );
+        assert_eq!(
+            shift_formula_references("@row.B + A1", ShiftOperation::InsertColumn(1)),
+            "@row.C + A1"
+        );
+        assert_eq!(
+            shift_formula_references("@row.B", ShiftOperation::DeleteColumn(1)),
+            "#REF!"
+        );
+    }
+
+    #[test]
+    fn test_expand_multi_arg_aggregates() {
+        assert_eq!(
+            expand_multi_arg_aggregates("SUM(A1:A3, 10, B1)"),
+            "(SUM(A1:A3) + (10) + (B1))"
+        );
+        assert_eq!(
+            expand_multi_arg_aggregates("AVG(A1:B2, 2) * 2"),
+            "((SUM(A1:B2) + (2)) / 5.0) * 2"
+        );
+        // Nested calls and strings with commas stay within their argument
+        assert_eq!(
+            expand_multi_arg_aggregates(r#"SUM(MAX(A1:A2), SUM(B1, 1), LEN("a,b"))"#),
+            r#"((MAX(A1:A2)) + (((B1) + (1))) + (LEN("a,b")))"#
+        );
+        // Single-argument calls and similarly named functions are untouched
+        assert_eq!(expand_multi_arg_aggregates("SUM(A1:A3)"), "SUM(A1:A3)");
+        assert_eq!(
+            expand_multi_arg_aggregates("DSUM(A1, 2) + \"SUM(1, 2)\""),
+            "DSUM(A1, 2) + \"SUM(1, 2)\""
+        );
+        assert_eq!(
+            shift_formula_references("SUM(A1:A3, B1)", ShiftOperation::InsertColumn(0)),
+            "SUM(B1:B3, C1)"
+        );
+        assert_eq!(
+            offset_formula_references("SUM(A1:A3, B1)", 0, 1),
+            "SUM(A2:A4, B2)"
+        );
+    }
 }
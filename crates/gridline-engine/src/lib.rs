@@ -334,4 +334,125 @@ mod tests {
         assert!(error.is_some());
         assert!(error.unwrap().contains("Error"));
     }
+
+    #[test]
+    fn test_from_input_grouped_numbers_follow_locale() {
+        let en = Cell::from_input_with_locale("1,234.56", NumberLocale::English);
+        assert!(matches!(en.contents, CellType::Number(n) if n == 1234.56));
+        let eu = Cell::from_input_with_locale("1.234,56", NumberLocale::European);
+        assert!(matches!(eu.contents, CellType::Number(n) if n == 1234.56));
+        let neg = Cell::from_input_with_locale("-12,345,678", NumberLocale::English);
+        assert!(matches!(neg.contents, CellType::Number(n) if n == -12345678.0));
+
+        // Without a locale, grouped input stays text.
+        let plain = Cell::from_input("1,234.56");
+        assert!(matches!(plain.contents, CellType::Text(ref s) if s == "1,234.56"));
+        // The stored value round-trips as a clean float.
+        assert_eq!(en.to_input_string(), "1234.56");
+    }
+
+    #[test]
+    fn test_from_input_grouped_numbers_reject_list_like_text() {
+        for input in ["1,2,3", "12,34", "1,234,", ",234", "1,234.5.6", "a,234"] {
+            let cell = Cell::from_input_with_locale(input, NumberLocale::English);
+            assert!(
+                matches!(cell.contents, CellType::Text(_)),
+                "{} should stay text",
+                input
+            );
+        }
+        let cell = Cell::from_input_with_locale("1.2.3", NumberLocale::European);
+        assert!(matches!(cell.contents, CellType::Text(_)));
+    }
+
+    #[test]
+    fn test_european_locale_dot_groups_thousands() {
+        let parse = |input| NumberLocale::European.parse_number(input);
+        assert_eq!(parse("1.234"), Some(1234.0));
+        assert_eq!(parse("1234,5"), Some(1234.5));
+        assert_eq!(parse("1234"), Some(1234.0));
+        assert_eq!(parse("1.5"), None);
+        assert_eq!(NumberLocale::English.parse_number("1234.5"), Some(1234.5));
+    }
+
+    #[test]
+    fn test_from_input_percent() {
+        let half = Cell::from_input("50%");
+        assert!(matches!(half.contents, CellType::Number(n) if n == 0.5));
+        assert_eq!(half.format, CellFormat::Percent);
+        assert_eq!(half.to_input_string(), "50%");
+
+        let small = Cell::from_input("2.5%");
+        assert!(matches!(small.contents, CellType::Number(n) if (n - 0.025).abs() < 1e-12));
+        assert_eq!(small.to_input_string(), "2.5%");
+        assert_eq!(Cell::from_input("7%").to_input_string(), "7%");
+
+        let eu = Cell::from_input_with_locale("1.234,5%", NumberLocale::European);
+        assert!(matches!(eu.contents, CellType::Number(n) if (n - 12.345).abs() < 1e-12));
+
+        assert_eq!(format_percent(0.5), "50%");
+        assert_eq!(format_percent(0.07), "7%");
+        // A bare "%" or text before it is not a number.
+        assert!(matches!(Cell::from_input("%").contents, CellType::Text(_)));
+        assert!(matches!(
+            Cell::from_input("abc%").contents,
+            CellType::Text(_)
+        ));
+        assert_eq!(Cell::from_input("5").format, CellFormat::General);
+    }
+
+    #[test]
+    fn test_cell_equality_ignores_cached_state() {
+        let fresh = Cell::new_script("A1 + 1");
+        let mut evaluated = Cell::new_script("A1 + 1");
+        evaluated.dirty = false;
+        evaluated.cached_value = Some("2".to_string());
+        evaluated.depends_on.clear();
+        assert_eq!(fresh, evaluated);
+        assert_eq!(fresh.content_hash(), evaluated.content_hash());
+
+        let different = [
+            Cell::new_script("A1 + 2"),
+            Cell::new_text("A1 + 1"),
+            Cell::new_number(0.5),
+            Cell::new_empty(),
+        ];
+        for other in &different {
+            assert_ne!(&fresh, other);
+            assert_ne!(fresh.content_hash(), other.content_hash());
+        }
+        // Same value typed as a percent is a different cell.
+        assert_ne!(Cell::new_number(0.5), Cell::new_percent(0.5));
+        assert_eq!(Cell::new_number(f64::NAN), Cell::new_number(f64::NAN));
+        // The hash is stable across runs, so it may be persisted.
+        assert_eq!(Cell::new_empty().content_hash(), 0x0832_8807_b4eb_6fed);
+    }
+
+    #[test]
+    fn test_preprocess_array_literals() {
+        // A row spills right, a column spills down, a matrix does both.
+        assert_eq!(preprocess_script("{1, 2, 3}"), "[[1, 2, 3]]");
+        assert_eq!(preprocess_script("{1;2;3}"), "[1, 2, 3]");
+        assert_eq!(preprocess_script("{1, 2; 3, 4}"), "[[1, 2], [3, 4]]");
+        assert_eq!(
+            preprocess_script(r#"SPILL({A1; "x;y"; MAX(1, 2)})"#),
+            r#"SPILL([CELL(0, 0), "x;y", MAX(1, 2)])"#
+        );
+    }
+
+    #[test]
+    fn test_preprocess_leaves_blocks_and_maps_alone() {
+        for script in [
+            r#"if A1 > 1 { "big" } else { "small" }"#,
+            "OUTPUT(VEC(A1:A3), |v| { v.sort(); v })",
+            "#{a: 1, b: 2}",
+            "{ let x = 1; x + 1 }",
+            "{}",
+        ] {
+            assert!(
+                !preprocess_script(script).contains('['),
+                "{script} was treated as an array literal"
+            );
+        }
+    }
 }
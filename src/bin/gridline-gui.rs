@@ -71,6 +71,10 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "Gridline",
         options,
-        Box::new(|_cc| Ok(Box::new(gui::GridlineGuiApp::new(doc)))),
+        Box::new(|_cc| {
+            Ok(Box::new(
+                gui::GridlineGuiApp::new(doc).with_settings_file(gui::settings::settings_path()),
+            ))
+        }),
     )
 }
@@ -53,6 +53,12 @@ pub enum Action {
 
     /// Save the document.
     Save,
+
+    /// Switch between dark and light themes.
+    ToggleTheme,
+
+    /// Switch column headers between letters and numbers.
+    ToggleHeaders,
     // Future actions:
     // EnterCommandMode,
     // ExecuteCommand(String),
@@ -69,6 +75,12 @@ pub fn apply_action(app: &mut GuiApp, state: &mut GuiState, action: Action) {
         }
 
         Action::BeginEdit => {
+            // Edits of a merged cell go to the region's top-left cell.
+            let anchor = app.doc.merge_anchor(&app.selected);
+            if anchor != app.selected {
+                app.set_selected(anchor, false);
+                state.ensure_selected_visible(&app.selected);
+            }
             state.editing = true;
             state.request_focus_formula = true;
         }
@@ -153,5 +165,13 @@ pub fn apply_action(app: &mut GuiApp, state: &mut GuiState, action: Action) {
         Action::Save => {
             let _ = app.save();
         }
+
+        Action::ToggleTheme => {
+            state.theme = state.theme.toggled();
+        }
+
+        Action::ToggleHeaders => {
+            state.numeric_headers = !state.numeric_headers;
+        }
     }
 }
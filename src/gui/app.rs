@@ -1,7 +1,8 @@
 //! Core application state and business logic (UI-agnostic).
 
+use gridline_core::storage::parse_tsv;
 use gridline_core::{CellRef, Document};
-use gridline_engine::engine::Cell;
+use gridline_engine::engine::{Cell, format_number, offset_formula_references};
 
 struct InternalClipboard {
     text: String,
@@ -10,6 +11,18 @@ struct InternalClipboard {
     cells: Vec<(usize, usize, Cell)>,
 }
 
+/// A paste held back for confirmation because it would overwrite data.
+pub struct PendingPaste {
+    /// Clipboard text to paste once confirmed
+    pub text: String,
+    /// Top-left cell of the paste
+    pub base: CellRef,
+    /// Fields as they will land, row by row from `base`
+    pub grid: Vec<Vec<String>>,
+    /// Non-empty cells the paste would overwrite
+    pub conflicts: Vec<CellRef>,
+}
+
 /// Core application state - contains spreadsheet data and business logic.
 /// This is independent of the UI framework and can be tested in isolation.
 pub struct GuiApp {
@@ -20,6 +33,8 @@ pub struct GuiApp {
     pub edit_buffer: String,
     pub edit_dirty: bool,
     pub status: String,
+    /// Paste awaiting confirmation in the preview
+    pub pending_paste: Option<PendingPaste>,
     internal_clipboard: Option<InternalClipboard>,
 }
 
@@ -34,6 +49,7 @@ impl GuiApp {
             edit_buffer: String::new(),
             edit_dirty: false,
             status: String::new(),
+            pending_paste: None,
             internal_clipboard: None,
         };
         app.sync_edit_buffer();
@@ -83,6 +99,27 @@ impl GuiApp {
         }
     }
 
+    /// Count/sum/average of a multi-cell selection for the status bar.
+    pub fn selection_stats_label(&mut self) -> Option<String> {
+        let (c1, r1, c2, r2) = self.selection_bounds();
+        if r1 == r2 && c1 == c2 {
+            return None;
+        }
+        let stats = self
+            .doc
+            .selection_stats(&CellRef::new(c1, r1), &CellRef::new(c2, r2));
+        let average = stats
+            .average
+            .map(format_number)
+            .unwrap_or_else(|| "-".to_string());
+        Some(format!(
+            "Count: {}  |  Sum: {}  |  Average: {}",
+            stats.count,
+            format_number(stats.sum),
+            average
+        ))
+    }
+
     /// Check if a cell is within the current selection.
     pub fn in_selection(&self, cell: &CellRef) -> bool {
         let (c1, r1, c2, r2) = self.selection_bounds();
@@ -104,7 +141,17 @@ impl GuiApp {
     /// Move selection by relative offset (dx columns, dy rows).
     pub fn move_selection(&mut self, dx: isize, dy: isize, extend_selection: bool) {
         let r = self.selected.row as isize + dy;
-        let c = self.selected.col as isize + dx;
+        let mut c = self.selected.col as isize;
+        for _ in 0..dx.unsigned_abs() {
+            let mut next = c + dx.signum();
+            while next >= 0 && self.doc.is_column_hidden(next as usize) {
+                next += dx.signum();
+            }
+            if next < 0 {
+                break;
+            }
+            c = next;
+        }
         self.set_selected(
             CellRef::new(c.max(0) as usize, r.max(0) as usize),
             extend_selection,
@@ -112,10 +159,19 @@ impl GuiApp {
     }
 
     /// Set cell value from user input string.
+    ///
+    /// With a multi-cell selection the input is entered into every selected cell
+    /// (array entry), with references offset per position as if pasted from the
+    /// selected cell.
     pub fn set_cell_from_input(&mut self, input: &str) -> Result<(), String> {
-        match self.doc.set_cell_from_input(self.selected.clone(), input) {
+        let (c1, r1, c2, r2) = self.selection_bounds();
+        if c1 != c2 || r1 != r2 {
+            return self.fill_selection_from_input(input);
+        }
+        let target = self.doc.merge_anchor(&self.selected);
+        match self.doc.set_cell_from_input(target.clone(), input) {
             Ok(()) => {
-                self.status = format!("Updated {}", self.selected);
+                self.status = format!("Updated {}", target);
                 self.edit_dirty = false;
                 Ok(())
             }
@@ -126,38 +182,127 @@ impl GuiApp {
         }
     }
 
-    /// Clear all cells in current selection.
-    pub fn clear_selection(&mut self) {
+    /// Enter `input` into every selected cell as one undo step.
+    fn fill_selection_from_input(&mut self, input: &str) -> Result<(), String> {
         let (c1, r1, c2, r2) = self.selection_bounds();
-        for r in r1..=r2 {
-            for c in c1..=c2 {
-                self.doc.clear_cell(&CellRef::new(c, r));
+        // The formula was typed for the selected cell; re-anchor it at the top-left.
+        let input = match input.trim().strip_prefix('=') {
+            Some(formula) => format!(
+                "={}",
+                offset_formula_references(
+                    formula,
+                    c1 as isize - self.selected.col as isize,
+                    r1 as isize - self.selected.row as isize,
+                )
+            ),
+            None => input.to_string(),
+        };
+        match self
+            .doc
+            .fill_range_from_input(&CellRef::new(c1, r1), &CellRef::new(c2, r2), &input)
+        {
+            Ok(count) => {
+                self.status = format!("Filled {} cells in {}", count, self.selection_label());
+                self.edit_dirty = false;
+                self.sync_edit_buffer();
+                Ok(())
+            }
+            Err(e) => {
+                self.status = format!("Error: {}", e);
+                Err(e.to_string())
             }
         }
+    }
+
+    /// Clear all cells in current selection.
+    pub fn clear_selection(&mut self) {
+        let (c1, r1, c2, r2) = self.selection_bounds();
+        self.doc
+            .clear_range(&CellRef::new(c1, r1), &CellRef::new(c2, r2));
         self.sync_edit_buffer();
         self.status = format!("Cleared {}", self.selection_label());
     }
 
     /// Parse clipboard text into a 2D grid (handles tab/newline delimiters).
     pub fn parse_clipboard_grid(s: &str) -> Vec<Vec<String>> {
-        let s = s.replace("\r\n", "\n").replace('\r', "\n");
-        let mut lines: Vec<&str> = s.split('\n').collect();
-        while lines.last().is_some_and(|l| l.is_empty()) {
-            lines.pop();
+        parse_tsv(s)
+    }
+
+    /// Paste clipboard data into selection, first holding it in
+    /// `pending_paste` for confirmation if it would overwrite non-empty cells.
+    /// Returns the number of cells pasted (0 while awaiting confirmation).
+    pub fn request_paste(&mut self, s: String) -> Result<usize, String> {
+        let (c1, r1, c2, r2) = self.selection_bounds();
+        let base = CellRef::new(c1, r1);
+        let grid = Self::parse_clipboard_grid(&s);
+        let (sel_cols, sel_rows) = (c2 - c1 + 1, r2 - r1 + 1);
+
+        let internal_size = self
+            .internal_clipboard
+            .as_ref()
+            .filter(|clip| clip.text == s)
+            .map(|clip| {
+                let width = clip.cells.iter().map(|c| c.0 + 1).max().unwrap_or(1);
+                let height = clip.cells.iter().map(|c| c.1 + 1).max().unwrap_or(1);
+                (width, height)
+            });
+        let (grid, conflicts) = if let Some((width, height)) = internal_size {
+            // Tiled like `paste_cells_tiled`; blank fields clear their targets too.
+            let cols = width * (sel_cols / width).max(1);
+            let rows = height * (sel_rows / height).max(1);
+            let tiled: Vec<Vec<String>> = (0..rows)
+                .map(|r| {
+                    (0..cols)
+                        .map(|c| {
+                            grid.get(r % height)
+                                .and_then(|row| row.get(c % width))
+                                .cloned()
+                                .unwrap_or_default()
+                        })
+                        .collect()
+                })
+                .collect();
+            let whole_block = vec![vec!["*".to_string(); cols]; rows];
+            let conflicts = self.doc.paste_conflicts(&base, &whole_block);
+            (tiled, conflicts)
+        } else if grid.len() == 1 && grid[0].len() == 1 && (sel_rows > 1 || sel_cols > 1) {
+            let repeated = vec![vec![grid[0][0].clone(); sel_cols]; sel_rows];
+            let conflicts = self.doc.paste_conflicts(&base, &repeated);
+            (repeated, conflicts)
+        } else {
+            let conflicts = self.doc.paste_conflicts(&base, &grid);
+            (grid, conflicts)
+        };
+
+        if conflicts.is_empty() {
+            return self.paste_from_clipboard(s);
         }
-        if lines.is_empty() {
-            return Vec::new();
+        self.status = format!(
+            "Paste would overwrite {} cell(s): confirm or cancel",
+            conflicts.len()
+        );
+        self.pending_paste = Some(PendingPaste {
+            text: s,
+            base,
+            grid,
+            conflicts,
+        });
+        Ok(0)
+    }
+
+    /// Carry out the paste awaiting confirmation.
+    pub fn confirm_paste(&mut self) -> Result<usize, String> {
+        match self.pending_paste.take() {
+            Some(pending) => self.paste_from_clipboard(pending.text),
+            None => Ok(0),
+        }
+    }
+
+    /// Drop the paste awaiting confirmation.
+    pub fn cancel_paste(&mut self) {
+        if self.pending_paste.take().is_some() {
+            self.status = "Paste cancelled".to_string();
         }
-        lines
-            .iter()
-            .map(|line| {
-                if line.contains('\t') {
-                    line.split('\t').map(|c| c.to_string()).collect()
-                } else {
-                    vec![line.to_string()]
-                }
-            })
-            .collect()
     }
 
     /// Paste clipboard data into selection.
@@ -175,10 +320,17 @@ impl GuiApp {
         if let Some(clip) = self.internal_clipboard.as_ref()
             && clip.text == s
         {
-            match self
-                .doc
-                .paste_cells(c1, r1, clip.source_col, clip.source_row, &clip.cells)
-            {
+            // Tile the copied block across a larger selection.
+            let clip_width = grid.iter().map(Vec::len).max().unwrap_or(1);
+            let clip_height = grid.len();
+            match self.doc.paste_cells_tiled(
+                ((c1, r1), (c2, r2)),
+                clip.source_col,
+                clip.source_row,
+                clip_width,
+                clip_height,
+                &clip.cells,
+            ) {
                 Ok(pasted) => {
                     self.sync_edit_buffer();
                     self.status =
@@ -192,7 +344,7 @@ impl GuiApp {
             }
         } else {
             let single_value = grid.len() == 1 && grid[0].len() == 1;
-            let cells = if single_value && (sel_rows > 1 || sel_cols > 1) {
+            let result = if single_value && (sel_rows > 1 || sel_cols > 1) {
                 let value = Cell::from_input(&grid[0][0]);
                 let mut repeated = Vec::with_capacity(sel_rows * sel_cols);
                 for dr in 0..sel_rows {
@@ -200,18 +352,13 @@ impl GuiApp {
                         repeated.push((dc, dr, value.clone()));
                     }
                 }
-                repeated
+                self.doc.paste_cells(c1, r1, c1, r1, &repeated)
             } else {
-                let mut parsed = Vec::new();
-                for (dr, row) in grid.iter().enumerate() {
-                    for (dc, value) in row.iter().enumerate() {
-                        parsed.push((dc, dr, Cell::from_input(value)));
-                    }
-                }
-                parsed
+                // Blank fields are skipped so existing cells are not cleared
+                self.doc.paste_tsv(c1, r1, &s)
             };
 
-            match self.doc.paste_cells(c1, r1, c1, r1, &cells) {
+            match result {
                 Ok(pasted) => {
                     self.sync_edit_buffer();
                     self.status =
@@ -361,6 +508,48 @@ mod tests {
         assert_eq!(app.selection_label(), "C2:E4");
     }
 
+    #[test]
+    fn test_request_paste_waits_for_confirmation_over_data() {
+        let mut app = GuiApp::new(Document::new());
+        // Empty targets paste straight away
+        assert_eq!(app.request_paste("1\t2".to_string()), Ok(2));
+        assert!(app.pending_paste.is_none());
+
+        app.set_selected(CellRef::new(0, 1), false); // A2
+        app.doc
+            .set_cell_from_input(CellRef::new(1, 1), "5")
+            .unwrap(); // B2
+        assert_eq!(app.request_paste("7\t8".to_string()), Ok(0));
+        let pending = app.pending_paste.as_ref().unwrap();
+        assert_eq!(pending.conflicts, vec![CellRef::new(1, 1)]);
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 1)), "5");
+
+        app.cancel_paste();
+        assert!(app.pending_paste.is_none());
+        assert_eq!(app.cell_input_string(&CellRef::new(0, 1)), "");
+
+        app.request_paste("7\t8".to_string()).unwrap();
+        assert_eq!(app.confirm_paste(), Ok(2));
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 1)), "8");
+    }
+
+    #[test]
+    fn test_selection_stats_label_skips_text() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(0, 0), "4").unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 1), "\"x\"")
+            .unwrap();
+        doc.set_cell_from_input(CellRef::new(0, 2), "2").unwrap();
+
+        let mut app = GuiApp::new(doc);
+        assert_eq!(app.selection_stats_label(), None);
+        app.set_selected(CellRef::new(0, 2), true);
+        assert_eq!(
+            app.selection_stats_label().as_deref(),
+            Some("Count: 3  |  Sum: 6  |  Average: 3")
+        );
+    }
+
     #[test]
     fn test_internal_copy_paste_shifts_formula_references() {
         let mut doc = Document::new();
@@ -408,4 +597,48 @@ mod tests {
         assert_eq!(app.cell_input_string(&CellRef::new(0, 1)), "7");
         assert_eq!(app.cell_input_string(&CellRef::new(1, 1)), "7");
     }
+
+    #[test]
+    fn test_external_paste_with_trailing_tabs_keeps_adjacent_data() {
+        let mut doc = Document::new();
+        doc.set_cell_from_input(CellRef::new(2, 0), "keep").unwrap(); // C1
+        doc.set_cell_from_input(CellRef::new(1, 1), "also").unwrap(); // B2
+        let mut app = GuiApp::new(doc);
+
+        let pasted = app
+            .paste_from_clipboard("1\t2\t\t\n3\t\t\n\n".to_string())
+            .unwrap();
+
+        assert_eq!(pasted, 3);
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 0)), "2");
+        assert_eq!(app.cell_input_string(&CellRef::new(2, 0)), "\"keep\"");
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 1)), "\"also\"");
+    }
+
+    #[test]
+    fn test_commit_with_selection_fills_each_cell() {
+        let doc = Document::new();
+        let mut app = GuiApp::new(doc);
+        app.selection_anchor = CellRef::new(1, 0); // B1
+        app.selection_end = CellRef::new(1, 2); // B3
+        app.selected = CellRef::new(1, 0);
+
+        app.set_cell_from_input("=A1*2").unwrap();
+
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 0)), "=A1*2");
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 1)), "=A2*2");
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 2)), "=A3*2");
+        app.doc.undo().unwrap();
+        assert_eq!(app.cell_input_string(&CellRef::new(1, 2)), "");
+    }
+
+    #[test]
+    fn test_multiline_formula_commits_as_single_line() {
+        let doc = Document::new();
+        let mut app = GuiApp::new(doc);
+
+        app.set_cell_from_input("=POW(2, 3)\n  + 1").unwrap();
+
+        assert_eq!(app.cell_input_string(&CellRef::new(0, 0)), "=POW(2, 3) + 1");
+    }
 }
@@ -122,6 +122,16 @@ pub fn handle_keyboard_input(ctx: &egui::Context) -> Option<Action> {
         return Some(Action::InsertRow);
     }
 
+    // Toggle dark/light theme: Ctrl+Shift+T
+    if cmd_pressed && shift_pressed && input.key_pressed(Key::T) {
+        return Some(Action::ToggleTheme);
+    }
+
+    // Toggle letter/numeric column headers: Ctrl+Shift+H
+    if cmd_pressed && shift_pressed && input.key_pressed(Key::H) {
+        return Some(Action::ToggleHeaders);
+    }
+
     // Save: Ctrl+S
     if cmd_pressed && input.key_pressed(Key::S) {
         return Some(Action::Save);
@@ -6,6 +6,7 @@
 //! - actions.rs: Action types and dispatch
 //! - input.rs: Keyboard input handling
 //! - clipboard.rs: Clipboard abstraction
+//! - settings.rs: Persisted preferences (theme)
 //! - ui.rs: egui rendering
 //! - main.rs: Entry point and window setup
 
@@ -13,18 +14,24 @@ pub mod actions;
 pub mod app;
 pub mod clipboard;
 pub mod input;
+pub mod settings;
 pub mod state;
 pub mod ui;
 
 use eframe::egui;
 use gridline_core::{CellRef, Document};
+use std::path::PathBuf;
 
 use self::actions::{Action, apply_action};
 use self::app::GuiApp;
 use self::clipboard::{ClipboardProvider, SystemClipboard};
 use self::input::handle_keyboard_input;
+use self::settings::GuiSettings;
 use self::state::GuiState;
-use self::ui::{CellRenderer, apply_theme, draw_central_grid, draw_status_bar, draw_top_panel};
+use self::ui::{
+    CellRenderer, apply_theme, draw_central_grid, draw_paste_preview, draw_status_bar,
+    draw_top_panel,
+};
 
 fn selection_cell_count(app: &GuiApp) -> usize {
     let (c1, r1, c2, r2) = app.selection_bounds();
@@ -67,6 +74,8 @@ pub struct GridlineGuiApp {
     renderer: CellRenderer,
     clipboard: SystemClipboard,
     formula_id: egui::Id,
+    /// Where GUI preferences are persisted; `None` keeps them in memory only.
+    settings_path: Option<PathBuf>,
 }
 
 impl GridlineGuiApp {
@@ -77,8 +86,18 @@ impl GridlineGuiApp {
             renderer: CellRenderer::new(),
             clipboard: SystemClipboard,
             formula_id: egui::Id::new("gridline_formula_edit"),
+            settings_path: None,
         }
     }
+
+    /// Load preferences from `path` and save later changes back to it.
+    pub fn with_settings_file(mut self, path: Option<PathBuf>) -> Self {
+        if let Some(ref p) = path {
+            self.state.theme = GuiSettings::load(p).theme;
+        }
+        self.settings_path = path;
+        self
+    }
 }
 
 impl GridlineGuiApp {
@@ -92,12 +111,13 @@ impl GridlineGuiApp {
             }
             Action::Paste(text) => {
                 // If text is provided (from egui paste event), use it directly
+                // Pastes over existing data wait for confirmation in the preview.
                 if !text.is_empty() {
-                    let _ = self.app.paste_from_clipboard(text);
+                    let _ = self.app.request_paste(text);
                 } else {
                     // Otherwise try to read from system clipboard
                     if let Some(clipboard_text) = self.clipboard.get_text() {
-                        let _ = self.app.paste_from_clipboard(clipboard_text);
+                        let _ = self.app.request_paste(clipboard_text);
                     } else {
                         self.app.status = "✗ Paste failed: clipboard empty".to_string();
                     }
@@ -159,6 +179,20 @@ impl GridlineGuiApp {
                 self.app.insert_column(c1);
                 self.app.status = format!("✓ Inserted column at {}", CellRef::col_to_letters(c1));
             }
+            Action::ToggleTheme => {
+                self.state.theme = self.state.theme.toggled();
+                let settings = GuiSettings {
+                    theme: self.state.theme,
+                };
+                let saved = match &self.settings_path {
+                    Some(path) => settings.save(path),
+                    None => Ok(()),
+                };
+                self.app.status = match saved {
+                    Ok(()) => format!("✓ Theme: {}", self.state.theme.label()),
+                    Err(e) => format!("✗ Theme not saved: {}", e),
+                };
+            }
             _ => {
                 apply_action(&mut self.app, &mut self.state, action);
             }
@@ -260,7 +294,7 @@ mod tests {
 impl eframe::App for GridlineGuiApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Apply theme
-        apply_theme(ctx);
+        apply_theme(ctx, self.state.theme);
 
         // Handle Ctrl+W to close
         let ctrl_w = ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::W));
@@ -275,13 +309,13 @@ impl eframe::App for GridlineGuiApp {
 
         // Bottom panel: status bar
         egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
-            draw_status_bar(ui, &self.app, &self.state);
+            draw_status_bar(ui, &mut self.app, &self.state);
         });
 
         egui::CentralPanel::default().show(ctx, |ui| {
             // Handle other keyboard shortcuts in the grid area
             // Only when not actively editing
-            if !self.state.editing {
+            if !self.state.editing && self.app.pending_paste.is_none() {
                 if let Some(action) = handle_keyboard_input(ctx) {
                     self.handle_action(action);
                     ctx.request_repaint(); // Ensure GUI updates after action
@@ -292,6 +326,8 @@ impl eframe::App for GridlineGuiApp {
             draw_central_grid(ui, &mut self.app, &mut self.state, &self.renderer);
         });
 
+        draw_paste_preview(ctx, &mut self.app);
+
         // Handle focus management for formula bar
         if self.state.request_focus_formula {
             ctx.memory_mut(|m| m.request_focus(self.formula_id));
@@ -0,0 +1,108 @@
+//! Persisted GUI preferences (theme) stored in the config dir.
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Colour scheme for the GUI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    /// The other theme.
+    pub fn toggled(self) -> Self {
+        match self {
+            Theme::Dark => Theme::Light,
+            Theme::Light => Theme::Dark,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+}
+
+/// GUI preferences remembered across sessions.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuiSettings {
+    pub theme: Theme,
+}
+
+impl GuiSettings {
+    /// Parse settings from TOML text.
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// Serialize settings to TOML text.
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// Load settings from `path`, falling back to defaults if missing or invalid.
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| Self::from_toml(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write settings to `path`, creating the parent directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, self.to_toml()?).map_err(|e| e.to_string())
+    }
+}
+
+/// Location of `gui.toml` in the user's config dir.
+pub fn settings_path() -> Option<PathBuf> {
+    let proj = ProjectDirs::from("", "", "gridline")?;
+    let mut path = proj.config_dir().to_path_buf();
+    path.push("gui.toml");
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip_through_toml() {
+        let settings = GuiSettings {
+            theme: Theme::Light,
+        };
+        let text = settings.to_toml().unwrap();
+        assert_eq!(text.trim(), r#"theme = "light""#);
+        assert_eq!(GuiSettings::from_toml(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_theme_defaults_to_dark() {
+        assert_eq!(GuiSettings::from_toml("").unwrap().theme, Theme::Dark);
+        assert!(GuiSettings::from_toml(r#"theme = "sepia""#).is_err());
+    }
+
+    #[test]
+    fn settings_round_trip_through_file() {
+        let dir =
+            std::env::temp_dir().join(format!("gridline-gui-settings-{}", std::process::id()));
+        let path = dir.join("gui.toml");
+        let settings = GuiSettings {
+            theme: Theme::Light,
+        };
+        settings.save(&path).unwrap();
+        assert_eq!(GuiSettings::load(&path), settings);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,5 +1,6 @@
 //! UI-specific state (viewport, focus, layout).
 
+use crate::gui::settings::Theme;
 use gridline_core::CellRef;
 
 /// Viewport and UI layout state.
@@ -17,6 +18,15 @@ pub struct GuiState {
 
     /// True if we should request focus on the formula bar this frame.
     pub request_focus_formula: bool,
+
+    /// True if the formula bar is expanded into a multi-line editor.
+    pub formula_expanded: bool,
+
+    /// Active colour scheme.
+    pub theme: Theme,
+
+    /// True if column headers show 1, 2, 3 instead of A, B, C (display only).
+    pub numeric_headers: bool,
 }
 
 impl Default for GuiState {
@@ -28,11 +38,23 @@ impl Default for GuiState {
             viewport_cols: 12,
             editing: false,
             request_focus_formula: false,
+            formula_expanded: false,
+            theme: Theme::default(),
+            numeric_headers: false,
         }
     }
 }
 
 impl GuiState {
+    /// Header label for column `col`: its letters, or its 1-based number.
+    pub fn column_header(&self, col: usize) -> String {
+        if self.numeric_headers {
+            (col + 1).to_string()
+        } else {
+            CellRef::col_to_letters(col)
+        }
+    }
+
     pub fn new() -> Self {
         Self::default()
     }
@@ -1,9 +1,11 @@
 //! UI rendering and layout using egui.
 
 use crate::gui::app::GuiApp;
+use crate::gui::settings::Theme;
 use crate::gui::state::GuiState;
 use eframe::egui;
-use gridline_core::CellRef;
+use gridline_core::{Align, CellRef};
+use std::collections::HashMap;
 
 /// Cell rendering dimensions.
 pub struct CellRenderer {
@@ -23,17 +25,25 @@ impl CellRenderer {
 }
 
 /// Draw status bar with keyboard shortcuts and info (Excel/Word style for GUI).
-pub fn draw_status_bar(ui: &mut egui::Ui, app: &GuiApp, state: &GuiState) {
+pub fn draw_status_bar(ui: &mut egui::Ui, app: &mut GuiApp, state: &GuiState) {
     // Show any status message in the status bar instead of inline
     let status = if !app.status.is_empty() {
         app.status.clone()
+    } else if !state.editing
+        && let Some(stats) = app.selection_stats_label()
+    {
+        stats
+    } else if !state.editing
+        && let Some(cycle) = app.doc.cycle_status_at(&app.selection_end)
+    {
+        cycle
     } else {
         // Default shortcuts help (Excel/Word style)
         let editing = state.editing;
         if editing {
             "↵ Commit  |  Esc Cancel".to_string()
         } else {
-            "↵ Edit  |  Ctrl+S Save  |  Ctrl+Z Undo  |  Ctrl+Y Redo  |  Ctrl+C Copy  |  Ctrl+X Cut  |  Ctrl+V Paste  |  Del Clear".to_string()
+            "↵ Edit  |  Ctrl+S Save  |  Ctrl+Z Undo  |  Ctrl+Y Redo  |  Ctrl+C Copy  |  Ctrl+X Cut  |  Ctrl+V Paste  |  Del Clear  |  Ctrl+Shift+T Theme".to_string()
         }
     };
 
@@ -45,9 +55,82 @@ pub fn draw_status_bar(ui: &mut egui::Ui, app: &GuiApp, state: &GuiState) {
     );
 }
 
-/// Apply dark theme and style configuration (TUI-inspired).
-pub fn apply_theme(ctx: &egui::Context) {
-    ctx.set_visuals(egui::Visuals::dark());
+/// Most rows/columns of a pending paste shown in its preview.
+const PASTE_PREVIEW_ROWS: usize = 12;
+const PASTE_PREVIEW_COLS: usize = 6;
+
+/// Modal preview of a paste that would overwrite data, with confirm/cancel.
+/// Cells about to be overwritten are shown in red with their current input.
+pub fn draw_paste_preview(ctx: &egui::Context, app: &mut GuiApp) {
+    let Some(pending) = app.pending_paste.as_ref() else {
+        return;
+    };
+    let mut confirm = false;
+    let mut cancel = ctx.input(|i| i.key_pressed(egui::Key::Escape));
+
+    egui::Window::new("Paste preview")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} non-empty cell(s) will be overwritten",
+                pending.conflicts.len()
+            ));
+            ui.add_space(6.0);
+            egui::Grid::new("paste_preview_grid")
+                .striped(true)
+                .spacing([12.0, 4.0])
+                .show(ui, |ui| {
+                    for (dr, row) in pending.grid.iter().enumerate().take(PASTE_PREVIEW_ROWS) {
+                        for (dc, value) in row.iter().enumerate().take(PASTE_PREVIEW_COLS) {
+                            let target = CellRef::new(pending.base.col + dc, pending.base.row + dr);
+                            if pending.conflicts.contains(&target) {
+                                let text = format!(
+                                    "{}: {} → {}",
+                                    target,
+                                    app.cell_input_string(&target),
+                                    value
+                                );
+                                ui.label(
+                                    egui::RichText::new(text)
+                                        .monospace()
+                                        .color(egui::Color32::from_rgb(220, 80, 80)),
+                                );
+                            } else {
+                                ui.label(
+                                    egui::RichText::new(format!("{}: {}", target, value))
+                                        .monospace(),
+                                );
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            ui.add_space(6.0);
+            ui.horizontal(|ui| {
+                if ui.button("Paste").clicked() {
+                    confirm = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if confirm {
+        let _ = app.confirm_paste();
+    } else if cancel {
+        app.cancel_paste();
+    }
+}
+
+/// Apply the dark or light theme and style configuration (TUI-inspired).
+pub fn apply_theme(ctx: &egui::Context, theme: Theme) {
+    match theme {
+        Theme::Dark => ctx.set_visuals(egui::Visuals::dark()),
+        Theme::Light => ctx.set_visuals(egui::Visuals::light()),
+    }
 
     ctx.style_mut(|style| {
         // Spacing: minimal for compact spreadsheet feel
@@ -67,11 +150,14 @@ pub fn apply_theme(ctx: &egui::Context) {
             .insert(egui::TextStyle::Heading, egui::FontId::proportional(14.0));
 
         // Text colors for high contrast
-        let fg = egui::Color32::from_gray(235);
+        let (fg, strong) = match theme {
+            Theme::Dark => (egui::Color32::from_gray(235), egui::Color32::WHITE),
+            Theme::Light => (egui::Color32::from_gray(20), egui::Color32::BLACK),
+        };
         style.visuals.widgets.noninteractive.fg_stroke.color = fg;
         style.visuals.widgets.inactive.fg_stroke.color = fg;
-        style.visuals.widgets.hovered.fg_stroke.color = egui::Color32::WHITE;
-        style.visuals.widgets.active.fg_stroke.color = egui::Color32::WHITE;
+        style.visuals.widgets.hovered.fg_stroke.color = strong;
+        style.visuals.widgets.active.fg_stroke.color = strong;
         style.visuals.widgets.open.fg_stroke.color = fg;
 
         // Better contrast for selected items
@@ -95,6 +181,14 @@ pub fn draw_top_panel(
         // Cell reference label (compact)
         let cell_ref = app.selection_label();
         ui.label(egui::RichText::new(cell_ref).monospace().size(13.0));
+        if ui
+            .selectable_label(state.formula_expanded, "⤢")
+            .on_hover_text("Expand formula bar (Ctrl+Enter commits)")
+            .clicked()
+        {
+            state.formula_expanded = !state.formula_expanded;
+            state.request_focus_formula = state.editing;
+        }
         ui.separator();
 
         // Formula/value input - only show TextEdit when editing to avoid consuming keyboard shortcuts
@@ -104,8 +198,18 @@ pub fn draw_top_panel(
                 state.request_focus_formula = false;
             }
 
-            let resp = ui.add(
+            // The expanded editor uses Enter for new lines, so Ctrl+Enter commits there;
+            // take it before the TextEdit sees it. Lines are joined on commit.
+            let pressed_commit_expanded = state.formula_expanded
+                && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Enter));
+
+            let text_edit = if state.formula_expanded {
+                egui::TextEdit::multiline(&mut app.edit_buffer).desired_rows(4)
+            } else {
                 egui::TextEdit::singleline(&mut app.edit_buffer)
+            };
+            let resp = ui.add(
+                text_edit
                     .id(formula_id)
                     .hint_text("numbers, \"text\", or =formula")
                     .desired_width(f32::INFINITY)
@@ -116,8 +220,9 @@ pub fn draw_top_panel(
             }
 
             // Handle Enter/Escape in formula bar (only process if editing)
-            let pressed_enter =
-                ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter));
+            let pressed_enter = pressed_commit_expanded
+                || (!state.formula_expanded
+                    && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)));
             let pressed_escape =
                 ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape));
 
@@ -193,6 +298,15 @@ pub fn draw_central_grid(
     // Ensure selected cell is visible with updated viewport size
     state.ensure_selected_visible(&app.selected);
 
+    // Hidden columns keep their data but are not drawn
+    let columns: Vec<usize> = (state.viewport_col..)
+        .filter(|&col| !app.doc.is_column_hidden(col))
+        .take(state.viewport_cols)
+        .collect();
+
+    // Screen area covered by each visible merged region, keyed by its anchor
+    let mut merged_rects: HashMap<CellRef, egui::Rect> = HashMap::new();
+
     egui::ScrollArea::both()
         .auto_shrink([false, false])
         .show(ui, |ui| {
@@ -207,9 +321,8 @@ pub fn draw_central_grid(
                         [row_header_w, cell_h],
                         egui::Label::new(egui::RichText::new("").monospace()),
                     );
-                    for c in 0..state.viewport_cols {
-                        let col = state.viewport_col + c;
-                        let label = CellRef::col_to_letters(col);
+                    for &col in &columns {
+                        let label = state.column_header(col);
                         ui.add_sized(
                             [cell_w, cell_h],
                             egui::Label::new(
@@ -234,14 +347,26 @@ pub fn draw_central_grid(
                             ),
                         );
 
-                        for c in 0..state.viewport_cols {
-                            let col = state.viewport_col + c;
+                        for &col in &columns {
                             let cell_ref = CellRef::new(col, row);
-                            let display = app.cell_display(&cell_ref);
+                            let merged = app.doc.merged_region_at(&cell_ref);
+                            // Merged cells are blank; the anchor's value is painted over the region.
+                            let display = if merged.is_some() {
+                                String::new()
+                            } else {
+                                app.cell_display(&cell_ref)
+                            };
 
                             let is_selected = app.selected == cell_ref;
                             let is_in_range = app.in_selection(&cell_ref);
 
+                            // Aligned values are painted over an empty label below.
+                            let align = app.doc.alignment(&cell_ref).filter(|_| merged.is_none());
+                            let (display, aligned_display) = match align {
+                                Some(_) => (String::new(), display),
+                                None => (display, String::new()),
+                            };
+
                             // Format text with better visual feedback
                             let text = if is_selected {
                                 egui::RichText::new(display)
@@ -260,6 +385,41 @@ pub fn draw_central_grid(
                                 [cell_w, cell_h],
                                 egui::SelectableLabel::new(is_selected || is_in_range, text),
                             );
+                            if let Some(align) = align {
+                                let inset = egui::vec2(4.0, 0.0);
+                                let (pos, anchor) = match align {
+                                    Align::Left => {
+                                        (resp.rect.left_center() + inset, egui::Align2::LEFT_CENTER)
+                                    }
+                                    Align::Center => {
+                                        (resp.rect.center(), egui::Align2::CENTER_CENTER)
+                                    }
+                                    Align::Right => (
+                                        resp.rect.right_center() - inset,
+                                        egui::Align2::RIGHT_CENTER,
+                                    ),
+                                };
+                                let color = if is_selected {
+                                    egui::Color32::WHITE
+                                } else if is_in_range {
+                                    egui::Color32::from_rgb(230, 230, 230)
+                                } else {
+                                    ui.visuals().text_color()
+                                };
+                                ui.painter().with_clip_rect(resp.rect).text(
+                                    pos,
+                                    anchor,
+                                    aligned_display,
+                                    egui::FontId::monospace(12.0),
+                                    color,
+                                );
+                            }
+                            if let Some((anchor, _)) = merged {
+                                merged_rects
+                                    .entry(anchor)
+                                    .and_modify(|rect| *rect = rect.union(resp.rect))
+                                    .or_insert(resp.rect);
+                            }
                             if resp.clicked() {
                                 let extend = ui.input(|i| i.modifiers.shift);
                                 app.set_selected(cell_ref, extend);
@@ -268,5 +428,18 @@ pub fn draw_central_grid(
                         ui.end_row();
                     }
                 });
+
+            for (anchor, rect) in &merged_rects {
+                let display = app.cell_display(anchor);
+                let painter = ui.painter();
+                painter.rect_filled(*rect, 0.0, ui.visuals().extreme_bg_color);
+                painter.text(
+                    rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    display,
+                    egui::FontId::monospace(12.0),
+                    ui.visuals().strong_text_color(),
+                );
+            }
         });
 }
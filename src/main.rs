@@ -2,6 +2,7 @@
 
 use anyhow::{Context, Result};
 use gridline_core::{CellRef, Document};
+use gridline_engine::engine::error_type_code;
 use std::env;
 use std::path::PathBuf;
 use std::process::ExitCode;
@@ -60,11 +61,7 @@ fn run_command_mode(
     let result = doc.get_cell_display(&cell_ref);
 
     // Check for errors (for exit code)
-    let is_error = result.starts_with("#ERR")
-        || result.starts_with("#CYCLE")
-        || result.starts_with("#SPILL")
-        || result.starts_with("#INF")
-        || result.starts_with("#NAN");
+    let is_error = error_type_code(&result).is_some();
 
     // Output handling
     if let Some(output_path) = output_file {
@@ -83,7 +80,10 @@ fn run_command_mode(
 /// Print command result to stdout, handling array/spill results
 fn print_command_result(result: &str, cell_ref: &CellRef, doc: &mut Document) {
     // Check if this is a spill source (array result)
-    let has_spill = doc.spill_sources.values().any(|src| src == cell_ref);
+    let has_spill = doc
+        .spill_sources
+        .iter()
+        .any(|entry| entry.value() == cell_ref);
 
     if has_spill {
         // Print array elements one per line
@@ -95,8 +95,9 @@ fn print_command_result(result: &str, cell_ref: &CellRef, doc: &mut Document) {
         loop {
             let spill_ref = CellRef::new(cell_ref.col, row);
 
-            if let Some(src) = doc.spill_sources.get(&spill_ref) {
-                if src == cell_ref {
+            let source = doc.spill_sources.get(&spill_ref).map(|src| src.clone());
+            if let Some(src) = source {
+                if src == *cell_ref {
                     println!("{}", doc.get_cell_display(&spill_ref));
                     row += 1;
                 } else {
@@ -123,6 +124,9 @@ fn print_usage() {
     eprintln!("  -f, --functions <FILE>    Load custom Rhai functions (can be repeated)");
     eprintln!("  --no-default-functions    Do not auto-load default.rhai from config dir");
     eprintln!("  -o, --output <FILE>       Export to markdown file (non-interactive)");
+    eprintln!(
+        "  --sandbox                 Run :call/:rhai scripts without write builtins or imports"
+    );
     eprintln!("  --keymap <name>           Select keybindings (default: vim)");
     eprintln!("  --keymap-file <path>      Load keybindings from TOML file");
     eprintln!("  -h, --help                Print help");
@@ -138,6 +142,7 @@ fn try_main() -> Result<ExitCode> {
     let mut keymap_file: Option<PathBuf> = None;
     let mut command_formula: Option<String> = None;
     let mut no_default_functions: bool = false;
+    let mut sandbox: bool = false;
 
     let mut i = 1;
     while i < args.len() {
@@ -165,6 +170,9 @@ fn try_main() -> Result<ExitCode> {
             "--no-default-functions" => {
                 no_default_functions = true;
             }
+            "--sandbox" => {
+                sandbox = true;
+            }
             "-o" | "--output" => {
                 i += 1;
                 if i >= args.len() {
@@ -265,7 +273,7 @@ fn try_main() -> Result<ExitCode> {
             eprintln!("Warning: {}", warning);
         }
 
-        let mut app = match tui::App::with_file(file_path, all_functions, keymap) {
+        let mut app = match tui::App::with_file(file_path, all_functions, keymap, sandbox) {
             Ok(app) => app,
             Err(e) => return Err(e).context("failed to initialize TUI"),
         };
@@ -276,7 +284,7 @@ fn try_main() -> Result<ExitCode> {
 
     #[cfg(not(feature = "tui"))]
     {
-        let _ = (keymap_name, keymap_file);
+        let _ = (keymap_name, keymap_file, sandbox);
         eprintln!("Error: interactive mode requires the 'tui' feature");
         eprintln!("Hint: cargo run --features tui");
         return Ok(ExitCode::from(1));
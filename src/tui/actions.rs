@@ -1,6 +1,6 @@
 use crossterm::event::{self, KeyCode, KeyModifiers};
 
-use super::app::{App, Mode};
+use super::app::{App, Mode, Relation};
 use super::keymap::Action;
 
 fn modifiers_only_include(modifiers: KeyModifiers, allowed: KeyModifiers) -> bool {
@@ -90,10 +90,39 @@ pub enum ApplyResult {
 ///
 /// Returns `ApplyResult::Quit` if the application should exit.
 pub fn apply_action(app: &mut App, action: Action, _key: event::KeyEvent) -> ApplyResult {
+    // Any action outside the edit/commit-right cycle ends a data-entry row.
+    if !matches!(
+        action,
+        Action::EnterEdit
+            | Action::InsertAtStart
+            | Action::ChangeCell
+            | Action::CommitEditRight
+            | Action::CommitEditNextRow
+    ) {
+        app.entry_start_col = None;
+    }
+
+    // While picking a range for an edit, Enter inserts it and Esc abandons it.
+    if app.range_pick.is_some() && app.mode == Mode::Visual {
+        match action {
+            Action::EnterEdit => {
+                app.finish_range_pick(true);
+                return ApplyResult::Continue;
+            }
+            Action::Cancel | Action::ExitVisual => {
+                app.finish_range_pick(false);
+                return ApplyResult::Continue;
+            }
+            _ => {}
+        }
+    }
+
     match action {
         Action::Cancel => match app.mode {
             Mode::Edit => {
                 app.mode = Mode::Normal;
+                app.selection_anchor = None;
+                app.multiline_edit = false;
                 app.edit_buffer.clear();
                 app.edit_cursor = 0;
             }
@@ -116,9 +145,16 @@ pub fn apply_action(app: &mut App, action: Action, _key: event::KeyEvent) -> App
             app.move_cursor(0, 1);
         }
         Action::CommitEditRight => {
+            app.entry_start_col.get_or_insert(app.cursor_col);
             app.commit_edit();
             app.move_cursor(1, 0);
         }
+        Action::CommitEditNextRow => {
+            let start_col = app.entry_start_col.take().unwrap_or(app.cursor_col);
+            app.commit_edit();
+            app.cursor_col = start_col;
+            app.move_cursor(0, 1);
+        }
         Action::CommitEditLeft => {
             app.commit_edit();
             app.move_cursor(-1, 0);
@@ -128,6 +164,7 @@ pub fn apply_action(app: &mut App, action: Action, _key: event::KeyEvent) -> App
             app.command_buffer.clear();
             app.command_cursor = 0;
         }
+        Action::CommandPalette => app.open_command_palette(),
         Action::ExecuteCommand => {
             if app.execute_command() {
                 return ApplyResult::Quit;
@@ -142,6 +179,8 @@ pub fn apply_action(app: &mut App, action: Action, _key: event::KeyEvent) -> App
         Action::ExitVisual => app.exit_visual_mode(),
         Action::Yank => app.yank(),
         Action::Paste => app.paste(),
+        Action::PasteTranspose => app.paste_transposed(),
+        Action::RepeatLastChange => app.repeat_last_change(),
         Action::Undo => app.undo(),
         Action::Redo => app.redo(),
         Action::ClearCell => app.clear_current_cell(),
@@ -154,6 +193,7 @@ pub fn apply_action(app: &mut App, action: Action, _key: event::KeyEvent) -> App
         Action::OpenPlot => app.open_plot_modal_at_cursor(),
         Action::FreezeCell => app.freeze_current_cell(),
         Action::FreezeAll => app.freeze_all_cells(),
+        Action::ToggleRowGroup => app.toggle_row_group(),
         Action::OpenRowBelowEdit => {
             app.move_cursor(0, 1);
             app.insert_row();
@@ -197,6 +237,11 @@ pub fn apply_action(app: &mut App, action: Action, _key: event::KeyEvent) -> App
         }
         Action::SearchNext => app.search_next(),
         Action::SearchPrev => app.search_prev(),
+        Action::JumpPrecedent => app.jump_to_relation(Relation::Precedent),
+        Action::JumpDependent => app.jump_to_relation(Relation::Dependent),
+        Action::SwitchPane => app.switch_pane(),
+        Action::PickRange => app.start_range_pick(),
+        Action::GotoDefinition => app.goto_definition(),
     }
     ApplyResult::Continue
 }
@@ -347,4 +392,117 @@ mod tests {
         );
         assert_eq!(app.cursor_col, 0);
     }
+
+    #[test]
+    fn apply_action_commit_from_selection_fills_each_cell() {
+        let mut app = App::new();
+        app.cursor_col = 1;
+        app.cursor_row = 2;
+        let key = KeyEvent::new(KeyCode::Enter, KeyModifiers::empty());
+
+        apply_action(&mut app, Action::EnterVisual, key);
+        apply_action(&mut app, Action::Move(0, -2), key);
+        apply_action(&mut app, Action::EnterEdit, key);
+        assert!(matches!(app.mode, Mode::Edit));
+        assert_eq!((app.cursor_col, app.cursor_row), (1, 0));
+
+        app.edit_buffer = "=A1*2".to_string();
+        apply_action(&mut app, Action::CommitEdit, key);
+
+        assert!(matches!(app.mode, Mode::Normal));
+        assert!(app.selection_anchor.is_none());
+        for row in 0..3 {
+            let cell = app.core.grid.get(&CellRef::new(1, row)).unwrap();
+            assert_eq!(cell.to_input_string(), format!("=A{}*2", row + 1));
+        }
+        app.core.undo().unwrap();
+        assert_eq!(app.core.get_cell_display(&CellRef::new(1, 1)), "");
+    }
+
+    #[test]
+    fn apply_action_commit_next_row_returns_to_entry_start_column() {
+        let mut app = App::new();
+        app.cursor_col = 1;
+        let key = KeyEvent::new(KeyCode::Tab, KeyModifiers::empty());
+
+        for value in ["a", "b"] {
+            apply_action(&mut app, Action::EnterEdit, key);
+            app.edit_buffer = value.to_string();
+            apply_action(&mut app, Action::CommitEditRight, key);
+        }
+        assert_eq!((app.cursor_col, app.cursor_row), (3, 0));
+
+        apply_action(&mut app, Action::EnterEdit, key);
+        app.edit_buffer = "c".to_string();
+        apply_action(&mut app, Action::CommitEditNextRow, key);
+        assert_eq!((app.cursor_col, app.cursor_row), (1, 1));
+        assert_eq!(app.core.get_cell_display(&CellRef::new(3, 0)), "c");
+
+        // Moving by hand starts a fresh row from wherever the cursor lands.
+        apply_action(&mut app, Action::EnterEdit, key);
+        apply_action(&mut app, Action::CommitEditRight, key);
+        apply_action(&mut app, Action::Move(1, 0), key);
+        apply_action(&mut app, Action::EnterEdit, key);
+        apply_action(&mut app, Action::CommitEditNextRow, key);
+        assert_eq!((app.cursor_col, app.cursor_row), (3, 2));
+        assert!(app.entry_start_col.is_none());
+    }
+
+    #[test]
+    fn apply_action_pick_range_inserts_at_edit_cursor() {
+        let mut app = App::new();
+        app.cursor_col = 3;
+        app.cursor_row = 0;
+        let key = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+
+        apply_action(&mut app, Action::EnterEdit, key);
+        app.edit_buffer = "=SUM() * 2".to_string();
+        app.edit_cursor = "=SUM(".len();
+        apply_action(&mut app, Action::PickRange, key);
+        assert!(app.mode == Mode::Visual);
+
+        // Select A2:B4 starting from the edited cell.
+        app.cursor_col = 0;
+        app.cursor_row = 1;
+        app.selection_anchor = Some((1, 3));
+        apply_action(&mut app, Action::EnterEdit, key);
+
+        assert!(app.mode == Mode::Edit);
+        assert_eq!(app.edit_buffer, "=SUM(A2:B4) * 2");
+        assert_eq!(app.edit_cursor, "=SUM(A2:B4".len());
+        assert_eq!((app.cursor_col, app.cursor_row), (3, 0));
+        assert!(app.selection_anchor.is_none());
+
+        // A single cell is inserted as a plain reference; Esc inserts nothing.
+        apply_action(&mut app, Action::PickRange, key);
+        app.cursor_row = 2;
+        app.selection_anchor = Some((3, 2));
+        apply_action(&mut app, Action::EnterEdit, key);
+        assert_eq!(app.edit_buffer, "=SUM(A2:B4D3) * 2");
+        apply_action(&mut app, Action::PickRange, key);
+        apply_action(&mut app, Action::ExitVisual, key);
+        assert_eq!(app.edit_buffer, "=SUM(A2:B4D3) * 2");
+        assert!(app.mode == Mode::Edit);
+    }
+
+    #[test]
+    fn apply_action_col_width_adjusts_current_column_and_clamps() {
+        let mut app = App::new();
+        app.cursor_col = 2;
+        let start = app.get_column_width(2);
+        let key = KeyEvent::new(KeyCode::Char('+'), KeyModifiers::empty());
+
+        apply_action(&mut app, Action::IncColWidth, key);
+        assert_eq!(app.get_column_width(2), start + 2);
+        assert_eq!(app.get_column_width(1), start);
+
+        for _ in 0..100 {
+            apply_action(&mut app, Action::IncColWidth, key);
+        }
+        assert_eq!(app.get_column_width(2), 50);
+        for _ in 0..100 {
+            apply_action(&mut app, Action::DecColWidth, key);
+        }
+        assert_eq!(app.get_column_width(2), 4);
+    }
 }
@@ -5,14 +5,31 @@
 //! The app operates in different [`Mode`]s (Normal, Edit, Command, Visual) similar
 //! to Vim's modal editing.
 
-use gridline_core::{Document, Result, ScriptContext};
-use gridline_engine::engine::{Cell, CellRef};
+use gridline_core::storage::{CsvExportOptions, CsvImportOptions};
+use gridline_core::{
+    Align, AuditWarning, ColumnProfile, Definition, Document, Result, ScriptContext,
+};
+use gridline_engine::engine::{Cell, CellRef, NumberLocale, parse_range};
 use gridline_engine::plot::{PlotSpec, parse_plot_spec};
 use regex::Regex;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use super::commands::{self, CommandPalette};
 use super::keymap::Keymap;
+use super::ui::GRID_COLUMN_SPACING;
+
+/// Navigation bounds never grow past the engine's coordinate limit.
+const MAX_GRID_EXTENT: usize = 1_000_000;
+
+/// Last-seen modification time of each loaded functions file (`None` if unreadable).
+pub type FunctionsMtimes = HashMap<PathBuf, Option<SystemTime>>;
+
+/// Whether any functions file was modified, added or removed since `stored` was taken.
+pub fn functions_changed(stored: &FunctionsMtimes, current: &FunctionsMtimes) -> bool {
+    stored != current
+}
 
 /// Clipboard contents for yank/paste
 #[derive(Clone)]
@@ -50,6 +67,67 @@ pub enum Mode {
     Visual,
 }
 
+/// A change that `.` re-applies at the cursor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LastChange {
+    /// Commit this input to the cell (or selection)
+    Edit(String),
+    Clear,
+    Paste,
+    PasteTranspose,
+    InsertRow,
+    DeleteRow,
+    InsertColumn,
+    DeleteColumn,
+}
+
+/// Direction of a dependency-graph jump (`[d` / `]d`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    /// Cells the current formula references.
+    Precedent,
+    /// Cells whose formulas reference the current cell.
+    Dependent,
+}
+
+impl Relation {
+    fn label(self) -> &'static str {
+        match self {
+            Relation::Precedent => "precedent",
+            Relation::Dependent => "dependent",
+        }
+    }
+}
+
+/// In-progress precedent/dependent traversal started from `origin`.
+#[derive(Clone)]
+pub struct RelationNav {
+    pub origin: CellRef,
+    pub relation: Relation,
+    pub targets: Vec<CellRef>,
+    pub index: usize,
+}
+
+/// Cursor and scroll position of one pane of a split view (`:split`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PaneView {
+    pub cursor_col: usize,
+    pub cursor_row: usize,
+    pub viewport_col: usize,
+    pub viewport_row: usize,
+}
+
+/// An edit suspended while a range is picked in Visual mode (`pick_range`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RangePick {
+    pub cursor_col: usize,
+    pub cursor_row: usize,
+    pub edit_buffer: String,
+    pub edit_cursor: usize,
+    pub multiline_edit: bool,
+    pub selection_anchor: Option<(usize, usize)>,
+}
+
 /// Main application state container.
 ///
 /// Holds all state for the spreadsheet application including:
@@ -103,6 +181,40 @@ pub struct App {
     /// Plot modal state (when open)
     pub plot_modal: Option<PlotSpec>,
 
+    /// Multi-line edit (`:edit`): Enter inserts a newline, Esc commits
+    pub multiline_edit: bool,
+
+    /// Column where a row of Tab-committed entries began; a
+    /// `commit_edit_next_row` commit returns here one row down
+    pub entry_start_col: Option<usize>,
+
+    /// Edit waiting for a range picked in Visual mode to be inserted at its cursor
+    pub range_pick: Option<RangePick>,
+
+    /// Auto-reload functions files when they change on disk (`:set watch on`)
+    pub watch_functions: bool,
+    /// Let text wider than its column spill into empty cells to the right
+    pub overflow: bool,
+    /// Whether column headers show 1, 2, 3 instead of A, B, C (display only)
+    pub numeric_headers: bool,
+    /// Functions file mtimes as of the last load/reload, used by the watcher
+    pub functions_mtimes: FunctionsMtimes,
+
+    /// The unfocused pane while the view is split (`:split`). The focused
+    /// pane always lives in the cursor/viewport fields above.
+    pub split: Option<PaneView>,
+    /// Whether the focused pane is the bottom one of the split
+    pub split_focus_bottom: bool,
+
+    /// Column profile modal (`:profile`), when open
+    pub profile_modal: Option<ColumnProfile>,
+
+    /// Formula audit warnings modal (`:audit`), when open
+    pub audit_modal: Option<Vec<AuditWarning>>,
+
+    /// Command palette (`Ctrl+P`), when open
+    pub command_palette: Option<CommandPalette>,
+
     /// Help modal state
     pub help_modal: bool,
     /// Help modal vertical scroll offset (line index)
@@ -130,6 +242,8 @@ pub struct App {
     pub pending_c: bool,
     /// Pending 'z' key for Vim zf/zF commands
     pub pending_z: bool,
+    /// Pending '[' or ']' key for Vim [d/]d commands
+    pub pending_bracket: Option<char>,
 
     /// Search: compiled regex pattern
     pub search_pattern: Option<Regex>,
@@ -137,6 +251,12 @@ pub struct App {
     pub search_matches: Vec<CellRef>,
     /// Search: current match index
     pub search_index: usize,
+
+    /// Active precedent/dependent traversal
+    pub relation_nav: Option<RelationNav>,
+
+    /// Last change, repeated by `.`
+    pub last_change: Option<LastChange>,
 }
 
 impl App {
@@ -165,6 +285,18 @@ impl App {
             system_clipboard: arboard::Clipboard::new().ok(),
             column_widths: HashMap::new(),
             plot_modal: None,
+            multiline_edit: false,
+            entry_start_col: None,
+            range_pick: None,
+            watch_functions: false,
+            overflow: false,
+            numeric_headers: false,
+            functions_mtimes: HashMap::new(),
+            split: None,
+            split_focus_bottom: false,
+            profile_modal: None,
+            audit_modal: None,
+            command_palette: None,
             help_modal: false,
             help_scroll: 0,
             keymap: Keymap::Vim,
@@ -175,9 +307,12 @@ impl App {
             pending_y: false,
             pending_c: false,
             pending_z: false,
+            pending_bracket: None,
             search_pattern: None,
             search_matches: Vec::new(),
             search_index: 0,
+            relation_nav: None,
+            last_change: None,
         }
     }
 
@@ -185,6 +320,107 @@ impl App {
         self.plot_modal = None;
     }
 
+    /// Profile a column and show the summary in a modal.
+    pub fn open_profile_modal(&mut self, col: usize) {
+        let profile = self.core.column_profile(col);
+        if profile.count == 0 {
+            self.status_message = format!("Column {} is empty", CellRef::col_to_letters(col));
+            return;
+        }
+        self.profile_modal = Some(profile);
+    }
+
+    /// Cursor and viewport of the focused pane.
+    pub fn pane_view(&self) -> PaneView {
+        PaneView {
+            cursor_col: self.cursor_col,
+            cursor_row: self.cursor_row,
+            viewport_col: self.viewport_col,
+            viewport_row: self.viewport_row,
+        }
+    }
+
+    /// Replace the focused pane's cursor and viewport.
+    pub fn set_pane_view(&mut self, view: PaneView) {
+        self.cursor_col = view.cursor_col;
+        self.cursor_row = view.cursor_row;
+        self.viewport_col = view.viewport_col;
+        self.viewport_row = view.viewport_row;
+    }
+
+    /// Split the grid into two panes over the same sheet, or close the split.
+    /// A new split starts with both panes showing the current view.
+    pub fn toggle_split(&mut self) {
+        if self.split.take().is_some() {
+            self.split_focus_bottom = false;
+            self.status_message = "Split closed".to_string();
+        } else {
+            self.split = Some(self.pane_view());
+            self.status_message = "Split view (C-w switches pane)".to_string();
+        }
+    }
+
+    /// Move focus to the other pane of a split, swapping in its cursor and scroll.
+    pub fn switch_pane(&mut self) {
+        let Some(other) = self.split else {
+            return;
+        };
+        self.split = Some(self.pane_view());
+        self.set_pane_view(other);
+        self.split_focus_bottom = !self.split_focus_bottom;
+        self.update_viewport();
+    }
+
+    pub fn close_profile_modal(&mut self) {
+        self.profile_modal = None;
+    }
+
+    /// Audit every formula and list any warnings in a modal.
+    pub fn open_audit_modal(&mut self) {
+        let warnings = self.core.audit();
+        if warnings.is_empty() {
+            self.status_message = "Audit: no warnings".to_string();
+            return;
+        }
+        self.audit_modal = Some(warnings);
+    }
+
+    pub fn close_audit_modal(&mut self) {
+        self.audit_modal = None;
+    }
+
+    pub fn open_command_palette(&mut self) {
+        self.mode = Mode::Normal;
+        self.command_palette = Some(CommandPalette::default());
+    }
+
+    pub fn close_command_palette(&mut self) {
+        self.command_palette = None;
+    }
+
+    /// Run the highlighted palette command. Commands that take arguments
+    /// open the `:` prompt pre-filled with the name instead. Returns true
+    /// if the app should quit.
+    pub fn run_palette_selection(&mut self) -> bool {
+        let Some(spec) = self
+            .command_palette
+            .take()
+            .and_then(|palette| palette.selection())
+        else {
+            return false;
+        };
+        self.mode = Mode::Command;
+        if spec.takes_args() {
+            self.command_buffer = format!("{} ", spec.name);
+            self.command_cursor = self.command_buffer.len();
+            self.status_message = spec.usage();
+            false
+        } else {
+            self.command_buffer = spec.name.to_string();
+            self.execute_command()
+        }
+    }
+
     pub fn close_help_modal(&mut self) {
         self.help_modal = false;
     }
@@ -226,10 +462,13 @@ impl App {
         path: Option<PathBuf>,
         functions_files: Vec<PathBuf>,
         keymap: Keymap,
+        sandboxed: bool,
     ) -> Result<Self> {
         let mut app = Self::new();
         app.keymap = keymap;
-        app.core = Document::with_file(path, functions_files)?;
+        app.core = Document::with_file_sandboxed(path, functions_files, sandboxed)?;
+        app.functions_mtimes = app.current_functions_mtimes();
+        app.fit_bounds_to_data();
         Ok(app)
     }
 
@@ -238,6 +477,8 @@ impl App {
         let mut app = Self::new();
         app.core = core;
         app.keymap = keymap;
+        app.functions_mtimes = app.current_functions_mtimes();
+        app.fit_bounds_to_data();
         app
     }
 
@@ -247,6 +488,7 @@ impl App {
             Ok(p) => self.status_message = format!("Loaded functions from {}", p.display()),
             Err(e) => self.status_message = format!("Error: {}", e),
         }
+        self.functions_mtimes = self.current_functions_mtimes();
     }
 
     /// Reload all custom functions from the loaded files
@@ -255,6 +497,36 @@ impl App {
             Ok(count) => self.status_message = format!("Reloaded {} function file(s)", count),
             Err(e) => self.status_message = format!("Error: {}", e),
         }
+        self.functions_mtimes = self.current_functions_mtimes();
+    }
+
+    /// Read the modification time of every loaded functions file.
+    pub fn current_functions_mtimes(&self) -> FunctionsMtimes {
+        self.core
+            .functions_files
+            .iter()
+            .map(|path| {
+                let mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+                (path.clone(), mtime)
+            })
+            .collect()
+    }
+
+    /// When watching is enabled, reload functions if any file changed on disk.
+    /// Returns true if a reload was attempted.
+    pub fn check_functions_changed(&mut self) -> bool {
+        if !self.watch_functions || self.core.functions_files.is_empty() {
+            return false;
+        }
+        let current = self.current_functions_mtimes();
+        if !functions_changed(&self.functions_mtimes, &current) {
+            return false;
+        }
+        self.reload_functions();
+        if !self.status_message.starts_with("Error") {
+            self.status_message = format!("Functions changed; {}", self.status_message);
+        }
+        true
     }
 
     /// Get the current cell reference
@@ -264,12 +536,18 @@ impl App {
 
     /// Move cursor by delta, clamping to valid range
     pub fn move_cursor(&mut self, dx: i32, dy: i32) {
-        self.cursor_col = (self.cursor_col as i32 + dx)
-            .max(0)
-            .min(self.max_cols as i32 - 1) as usize;
-        self.cursor_row = (self.cursor_row as i32 + dy)
-            .max(0)
-            .min(self.max_rows as i32 - 1) as usize;
+        for _ in 0..dx.unsigned_abs() {
+            match self.next_visible_col(self.cursor_col, dx > 0) {
+                Some(col) => self.cursor_col = col,
+                None => break,
+            }
+        }
+        for _ in 0..dy.unsigned_abs() {
+            match self.next_visible_row(self.cursor_row, dy > 0) {
+                Some(row) => self.cursor_row = row,
+                None => break,
+            }
+        }
         self.update_viewport();
     }
 
@@ -278,21 +556,144 @@ impl App {
         // Horizontal scrolling
         if self.cursor_col < self.viewport_col {
             self.viewport_col = self.cursor_col;
-        } else if self.cursor_col >= self.viewport_col + self.visible_cols {
-            self.viewport_col = self.cursor_col - self.visible_cols + 1;
+        } else {
+            // Hidden columns take no space, so count only displayed ones.
+            while self.viewport_col < self.cursor_col
+                && (self.viewport_col..=self.cursor_col)
+                    .filter(|&col| !self.core.is_column_hidden(col))
+                    .count()
+                    > self.visible_cols
+            {
+                self.viewport_col += 1;
+            }
         }
 
-        // Vertical scrolling
+        // Vertical scrolling; rows inside collapsed groups take no space, and
+        // a cursor left on one moves to the group's visible first row.
+        self.cursor_row = self.core.visible_row(self.cursor_row);
         if self.cursor_row < self.viewport_row {
             self.viewport_row = self.cursor_row;
-        } else if self.cursor_row >= self.viewport_row + self.visible_rows {
-            self.viewport_row = self.cursor_row - self.visible_rows + 1;
+        } else {
+            while self.viewport_row < self.cursor_row
+                && (self.viewport_row..=self.cursor_row)
+                    .filter(|&row| !self.core.is_row_collapsed(row))
+                    .count()
+                    > self.visible_rows
+            {
+                self.viewport_row += 1;
+            }
+        }
+    }
+
+    /// Next non-hidden column left or right of `from`, if any.
+    fn next_visible_col(&self, from: usize, forward: bool) -> Option<usize> {
+        if forward {
+            (from + 1..self.max_cols).find(|&col| !self.core.is_column_hidden(col))
+        } else {
+            (0..from)
+                .rev()
+                .find(|&col| !self.core.is_column_hidden(col))
+        }
+    }
+
+    /// Next row above or below `from` not hidden in a collapsed group, if any.
+    fn next_visible_row(&self, from: usize, forward: bool) -> Option<usize> {
+        if forward {
+            (from + 1..self.max_rows).find(|&row| !self.core.is_row_collapsed(row))
+        } else {
+            (0..from)
+                .rev()
+                .find(|&row| !self.core.is_row_collapsed(row))
+        }
+    }
+
+    /// Rows currently drawn in the viewport, skipping collapsed ones.
+    pub fn displayed_rows(&self) -> Vec<usize> {
+        (self.viewport_row..self.max_rows)
+            .filter(|&row| !self.core.is_row_collapsed(row))
+            .take(self.visible_rows)
+            .collect()
+    }
+
+    /// Collapse or expand the row group at the cursor (`za`).
+    pub fn toggle_row_group(&mut self) {
+        let row = self.cursor_row;
+        let Some((start, end)) = self.core.row_group_at(row) else {
+            self.status_message = format!("No row group at row {}", row + 1);
+            return;
+        };
+        let verb = if self.core.toggle_row_group(row) == Some(true) {
+            "Collapsed"
+        } else {
+            "Expanded"
+        };
+        self.status_message = format!("{} rows {}-{}", verb, start + 1, end + 1);
+        self.update_viewport();
+    }
+
+    /// Columns currently drawn in the viewport, skipping hidden ones.
+    pub fn displayed_columns(&self) -> Vec<usize> {
+        (self.viewport_col..self.max_cols)
+            .filter(|&col| !self.core.is_column_hidden(col))
+            .take(self.visible_cols)
+            .collect()
+    }
+
+    /// Width the display of `(col, row)` may take when text overflow is on:
+    /// its own column widened by the empty displayed columns to its right
+    /// (and the gaps between them), up to `text_width`.
+    pub fn overflow_width(&mut self, col: usize, row: usize, text_width: usize) -> usize {
+        let own_width = self.get_column_width(col);
+        let mut width = own_width;
+        let following: Vec<usize> = self
+            .displayed_columns()
+            .into_iter()
+            .skip_while(|&c| c != col)
+            .skip(1)
+            .collect();
+        for next in following {
+            if width >= text_width
+                || !self
+                    .core
+                    .get_cell_display(&CellRef::new(next, row))
+                    .is_empty()
+            {
+                break;
+            }
+            width += GRID_COLUMN_SPACING as usize + self.get_column_width(next);
+        }
+        width.min(text_width).max(own_width)
+    }
+
+    /// Header label for column `col`: its letters, or its 1-based number
+    /// with `:set headers numeric`. Cell references are unaffected.
+    pub fn column_header(&self, col: usize) -> String {
+        if self.numeric_headers {
+            (col + 1).to_string()
+        } else {
+            CellRef::col_to_letters(col)
         }
     }
 
     /// Enter edit mode for current cell
     /// If `at_start` is true, cursor is placed at the beginning; otherwise at the end.
+    ///
+    /// From Visual mode the selection is kept and the edit starts at its top-left
+    /// cell; committing then fills the whole selection (array entry).
     pub fn enter_edit_mode_at(&mut self, at_start: bool) {
+        if self.mode == Mode::Visual
+            && let Some(((c1, r1), _)) = self.get_selection()
+        {
+            self.cursor_col = c1;
+            self.cursor_row = r1;
+            self.update_viewport();
+        } else {
+            self.selection_anchor = None;
+            // Edits of a merged cell go to the region's top-left cell.
+            let anchor = self.core.merge_anchor(&self.current_cell_ref());
+            self.cursor_col = anchor.col;
+            self.cursor_row = anchor.row;
+        }
         let cell_ref = self.current_cell_ref();
         self.edit_buffer = if let Some(cell) = self.core.grid.get(&cell_ref) {
             cell.to_input_string()
@@ -309,18 +710,44 @@ impl App {
     }
 
     /// Commit the current edit
+    ///
+    /// If the edit was started from a selection, the input is entered into every
+    /// selected cell with references offset per position, as one undo step.
     pub fn commit_edit(&mut self) {
-        let cell_ref = self.current_cell_ref();
-        if let Err(e) = self.core.set_cell_from_input(cell_ref, &self.edit_buffer) {
-            self.status_message = format!("Error: {}", e);
+        self.last_change = Some(LastChange::Edit(self.edit_buffer.clone()));
+        if let Some(((c1, r1), (c2, r2))) = self.get_selection() {
+            self.selection_anchor = None;
+            match self.core.fill_range_from_input(
+                &CellRef::new(c1, r1),
+                &CellRef::new(c2, r2),
+                &self.edit_buffer,
+            ) {
+                Ok(count) => self.status_message = format!("Filled {} cells", count),
+                Err(e) => self.status_message = format!("Error: {}", e),
+            }
         } else {
-            self.status_message.clear();
+            let cell_ref = self.current_cell_ref();
+            if let Err(e) = self.core.set_cell_from_input(cell_ref, &self.edit_buffer) {
+                self.status_message = format!("Error: {}", e);
+            } else {
+                self.status_message.clear();
+            }
         }
         self.mode = Mode::Normal;
+        self.multiline_edit = false;
         self.edit_buffer.clear();
         self.edit_cursor = 0;
     }
 
+    /// Open the current cell in the multi-line editor.
+    ///
+    /// Newlines are joined back into a single-line formula on commit.
+    pub fn enter_multiline_edit(&mut self) {
+        self.enter_edit_mode();
+        self.multiline_edit = true;
+        self.status_message = "-- EDIT (multi-line) -- Enter: new line, Esc: commit".to_string();
+    }
+
     /// Execute a search: compile pattern, find all matching cells, jump to first.
     pub fn execute_search(&mut self, pattern: &str) {
         if pattern.is_empty() {
@@ -414,16 +841,106 @@ impl App {
         }
     }
 
+    /// Jump to the next direct precedent or dependent of the current cell.
+    ///
+    /// Repeated jumps keep cycling through the targets of the cell the
+    /// traversal started from, as long as the cursor has not moved elsewhere.
+    pub fn jump_to_relation(&mut self, relation: Relation) {
+        let cursor = self.current_cell_ref();
+        let continuing = self.relation_nav.as_ref().is_some_and(|nav| {
+            nav.relation == relation && nav.targets.get(nav.index) == Some(&cursor)
+        });
+
+        if continuing {
+            if let Some(nav) = self.relation_nav.as_mut() {
+                nav.index = (nav.index + 1) % nav.targets.len();
+            }
+        } else {
+            let targets = match relation {
+                Relation::Precedent => self.core.precedents_of(&cursor),
+                Relation::Dependent => self.core.dependents_of(&cursor),
+            };
+            if targets.is_empty() {
+                self.relation_nav = None;
+                self.status_message = format!("{} has no {}s", cursor, relation.label());
+                return;
+            }
+            self.relation_nav = Some(RelationNav {
+                origin: cursor,
+                relation,
+                targets,
+                index: 0,
+            });
+        }
+
+        if let Some(nav) = self.relation_nav.as_ref() {
+            let target = nav.targets[nav.index].clone();
+            self.status_message = format!(
+                "{} {} of {} [{}/{}]",
+                target,
+                relation.label(),
+                nav.origin,
+                nav.index + 1,
+                nav.targets.len()
+            );
+            self.cursor_col = target.col.min(self.max_cols.saturating_sub(1));
+            self.cursor_row = target.row.min(self.max_rows.saturating_sub(1));
+            self.update_viewport();
+        }
+    }
+
+    /// Jump to the cell the current formula refers to, or report where the
+    /// custom function it calls is defined.
+    pub fn goto_definition(&mut self) {
+        let cursor = self.current_cell_ref();
+        match self.core.definition_at(&cursor) {
+            Some(Definition::Cell(target)) => {
+                self.cursor_col = target.col.min(self.max_cols.saturating_sub(1));
+                self.cursor_row = target.row.min(self.max_rows.saturating_sub(1));
+                self.update_viewport();
+                self.status_message = format!("{} (from {})", target, cursor);
+            }
+            Some(Definition::Function { name, path, line }) => {
+                self.status_message = format!("{} defined at {}:{}", name, path.display(), line);
+            }
+            None => self.status_message = format!("{} has no definition to go to", cursor),
+        }
+    }
+
     /// Clear the current cell
     pub fn clear_current_cell(&mut self) {
         let cell_ref = self.current_cell_ref();
         self.core.clear_cell(&cell_ref);
+        self.last_change = Some(LastChange::Clear);
+    }
+
+    /// Re-apply the last change at the cursor (Vim's `.`).
+    pub fn repeat_last_change(&mut self) {
+        let Some(change) = self.last_change.clone() else {
+            self.status_message = "No change to repeat".to_string();
+            return;
+        };
+        match change {
+            LastChange::Edit(input) => {
+                self.edit_buffer = input;
+                self.edit_cursor = self.edit_buffer.len();
+                self.commit_edit();
+            }
+            LastChange::Clear => self.clear_current_cell(),
+            LastChange::Paste => self.paste(),
+            LastChange::PasteTranspose => self.paste_transposed(),
+            LastChange::InsertRow => self.insert_row(),
+            LastChange::DeleteRow => self.delete_row(),
+            LastChange::InsertColumn => self.insert_column(),
+            LastChange::DeleteColumn => self.delete_column(),
+        }
     }
 
     /// Insert a row above the cursor position
     pub fn insert_row(&mut self) {
         let at_row = self.cursor_row;
         self.core.insert_row(at_row);
+        self.last_change = Some(LastChange::InsertRow);
         self.status_message = format!("Inserted row at {}", at_row + 1);
     }
 
@@ -431,6 +948,7 @@ impl App {
     pub fn delete_row(&mut self) {
         let at_row = self.cursor_row;
         self.core.delete_row(at_row);
+        self.last_change = Some(LastChange::DeleteRow);
         self.status_message = format!("Deleted row {}", at_row + 1);
     }
 
@@ -493,6 +1011,7 @@ impl App {
     pub fn insert_column(&mut self) {
         let at_col = self.cursor_col;
         self.core.insert_column(at_col);
+        self.last_change = Some(LastChange::InsertColumn);
         self.status_message = format!("Inserted column at {}", CellRef::col_to_letters(at_col));
 
         // Shift column widths (UI state)
@@ -515,6 +1034,7 @@ impl App {
     pub fn delete_column(&mut self) {
         let at_col = self.cursor_col;
         self.core.delete_column(at_col);
+        self.last_change = Some(LastChange::DeleteColumn);
         self.status_message = format!("Deleted column {}", CellRef::col_to_letters(at_col));
 
         // Shift column widths (UI state)
@@ -534,6 +1054,34 @@ impl App {
         }
     }
 
+    /// Remove empty rows or columns within the used range (`:compact rows|cols`)
+    pub fn compact(&mut self, columns: bool) {
+        let (removed, noun) = if columns {
+            (self.core.compact_columns(), "column")
+        } else {
+            (self.core.compact_rows(), "row")
+        };
+        if removed.is_empty() {
+            self.status_message = format!("No empty {}s to remove", noun);
+            return;
+        }
+
+        if columns {
+            // Shift column widths (UI state)
+            self.column_widths = self
+                .column_widths
+                .iter()
+                .filter(|(col, _)| removed.binary_search(col).is_err())
+                .map(|(&col, &width)| (col - removed.partition_point(|&r| r < col), width))
+                .collect();
+            self.cursor_col -= removed.partition_point(|&r| r < self.cursor_col);
+        } else {
+            self.cursor_row -= removed.partition_point(|&r| r < self.cursor_row);
+        }
+        self.update_viewport();
+        self.status_message = format!("Removed {} empty {}(s)", removed.len(), noun);
+    }
+
     /// Undo the last action
     pub fn undo(&mut self) {
         match self.core.undo() {
@@ -583,6 +1131,60 @@ impl App {
         self.status_message = "-- VISUAL LINE --".to_string();
     }
 
+    /// Suspend the current edit and select a range in Visual mode, starting at
+    /// the cell being edited; [`App::finish_range_pick`] returns to the edit.
+    pub fn start_range_pick(&mut self) {
+        if self.mode != Mode::Edit {
+            return;
+        }
+        self.range_pick = Some(RangePick {
+            cursor_col: self.cursor_col,
+            cursor_row: self.cursor_row,
+            edit_buffer: std::mem::take(&mut self.edit_buffer),
+            edit_cursor: self.edit_cursor,
+            multiline_edit: self.multiline_edit,
+            selection_anchor: self.selection_anchor,
+        });
+        self.selection_anchor = Some((self.cursor_col, self.cursor_row));
+        self.mode = Mode::Visual;
+        self.status_message = "Pick a range: Enter inserts it, Esc returns to the edit".to_string();
+    }
+
+    /// The range being picked, as it would be inserted ("B2" for one cell).
+    pub fn picked_range_string(&self) -> Option<String> {
+        let range = self.get_selection_range_string()?;
+        match range.split_once(':') {
+            Some((start, end)) if start == end => Some(start.to_string()),
+            _ => Some(range),
+        }
+    }
+
+    /// Return to the suspended edit, inserting the picked range at the edit
+    /// cursor if `insert` is set.
+    pub fn finish_range_pick(&mut self, insert: bool) {
+        let Some(pick) = self.range_pick.take() else {
+            return;
+        };
+        let range = if insert {
+            self.picked_range_string()
+        } else {
+            None
+        };
+        self.cursor_col = pick.cursor_col;
+        self.cursor_row = pick.cursor_row;
+        self.update_viewport();
+        self.edit_buffer = pick.edit_buffer;
+        self.edit_cursor = pick.edit_cursor;
+        self.multiline_edit = pick.multiline_edit;
+        self.selection_anchor = pick.selection_anchor;
+        if let Some(range) = range {
+            self.edit_buffer.insert_str(self.edit_cursor, &range);
+            self.edit_cursor += range.len();
+        }
+        self.mode = Mode::Edit;
+        self.status_message.clear();
+    }
+
     /// Exit visual mode
     pub fn exit_visual_mode(&mut self) {
         self.selection_anchor = None;
@@ -721,15 +1323,41 @@ impl App {
             self.status_message = "Nothing to paste".to_string();
             return;
         };
+        self.last_change = Some(LastChange::Paste);
 
         let base_row = self.cursor_row;
         let base_col = self.cursor_col;
-        let _clip_width = clipboard.width; // Reserved for horizontal paste-repeat
+        let clip_width = clipboard.width;
         let clip_height = clipboard.height;
         let source_col = clipboard.source_col;
         let source_row = clipboard.source_row;
         let cells = clipboard.cells.clone();
 
+        // With a selection, anchor at its top-left and tile the clipboard to fill it.
+        if let Some(selection) = self.get_selection() {
+            let ((sel_col, sel_row), _) = selection;
+            let result = self.core.paste_cells_tiled(
+                selection,
+                source_col,
+                source_row,
+                clip_width,
+                clip_height,
+                &cells,
+            );
+            self.exit_visual_mode();
+            match result {
+                Ok(pasted) => {
+                    self.cursor_col = sel_col;
+                    self.cursor_row = sel_row;
+                    self.fit_bounds_to_data();
+                    self.update_viewport();
+                    self.status_message = format!("Pasted {} cells into selection", pasted);
+                }
+                Err(e) => self.status_message = format!("Paste failed: {}", e),
+            }
+            return;
+        }
+
         let mut total_pasted = 0;
         for i in 0..count {
             // Offset each paste by clipboard dimensions (paste downward)
@@ -746,6 +1374,7 @@ impl App {
             }
         }
 
+        self.fit_bounds_to_data();
         if count > 1 {
             self.status_message = format!("Pasted {} cells ({} times)", total_pasted, count);
         } else {
@@ -753,6 +1382,36 @@ impl App {
         }
     }
 
+    /// Paste the clipboard with rows and columns swapped, at the cursor or the
+    /// selection's top-left.
+    pub fn paste_transposed(&mut self) {
+        let Some(clipboard) = &self.clipboard else {
+            self.status_message = "Nothing to paste".to_string();
+            return;
+        };
+        self.last_change = Some(LastChange::PasteTranspose);
+        let (source_col, source_row) = (clipboard.source_col, clipboard.source_row);
+        let cells = clipboard.cells.clone();
+        let (base_col, base_row) = match self.get_selection() {
+            Some((top_left, _)) => top_left,
+            None => (self.cursor_col, self.cursor_row),
+        };
+        self.exit_visual_mode();
+        match self
+            .core
+            .paste_cells_transposed(base_col, base_row, source_col, source_row, &cells)
+        {
+            Ok(pasted) => {
+                self.cursor_col = base_col;
+                self.cursor_row = base_row;
+                self.fit_bounds_to_data();
+                self.update_viewport();
+                self.status_message = format!("Pasted {} cells transposed", pasted);
+            }
+            Err(e) => self.status_message = format!("Paste failed: {}", e),
+        }
+    }
+
     /// Freeze the formula/spill value at the current cursor into a concrete value.
     pub fn freeze_current_cell(&mut self) {
         let cell_ref = self.current_cell_ref();
@@ -774,6 +1433,14 @@ impl App {
         }
     }
 
+    /// Grow `max_cols`/`max_rows` so every occupied cell can be navigated to.
+    pub fn fit_bounds_to_data(&mut self) {
+        if let Some((col, row)) = self.core.used_extent() {
+            self.max_cols = self.max_cols.max((col + 1).min(MAX_GRID_EXTENT));
+            self.max_rows = self.max_rows.max((row + 1).min(MAX_GRID_EXTENT));
+        }
+    }
+
     /// Get width for a specific column
     pub fn get_column_width(&self, col: usize) -> usize {
         *self.column_widths.get(&col).unwrap_or(&self.col_width)
@@ -900,7 +1567,12 @@ impl App {
             return false;
         }
 
-        match command {
+        let Some(spec) = commands::lookup(command) else {
+            self.status_message = format!("Unknown command: {}", command);
+            return false;
+        };
+
+        match spec.name {
             "q" => {
                 if self.core.modified {
                     self.status_message =
@@ -913,7 +1585,7 @@ impl App {
             "q!" => {
                 return true;
             }
-            "w" | "save" => {
+            "w" => {
                 if let Some(path) = args {
                     self.save_file_as(path);
                 } else {
@@ -937,24 +1609,45 @@ impl App {
             "new!" => {
                 self.new_document();
             }
-            "e" | "open" | "load" => {
+            "e" => {
                 if let Some(path) = args {
                     match self.core.load_file(&PathBuf::from(path)) {
-                        Ok(()) => self.status_message = format!("Loaded {}", path),
+                        Ok(()) => {
+                            self.fit_bounds_to_data();
+                            self.status_message = format!("Loaded {}", path);
+                        }
                         Err(e) => self.status_message = format!("Error: {}", e),
                     }
                 } else {
                     self.status_message = "Usage: :e <path>".to_string();
                 }
             }
-            "goto" | "g" => {
+            "goto" => {
                 if let Some(cell_ref) = args {
                     self.goto_cell(cell_ref);
                 } else {
                     self.status_message = "Usage: :goto CELL (e.g., :goto A100)".to_string();
                 }
             }
-            "source" | "so" => {
+            "edit" => self.enter_multiline_edit(),
+            "move" => {
+                if let Some(target) = args.and_then(CellRef::from_str) {
+                    let from = self.current_cell_ref();
+                    match self.core.move_cell(&from, &target) {
+                        Ok(()) => {
+                            self.cursor_col = target.col;
+                            self.cursor_row = target.row;
+                            self.fit_bounds_to_data();
+                            self.update_viewport();
+                            self.status_message = format!("Moved {} to {}", from, target);
+                        }
+                        Err(e) => self.status_message = format!("Error: {}", e),
+                    }
+                } else {
+                    self.status_message = "Usage: :move CELL (e.g., :move C3)".to_string();
+                }
+            }
+            "source" => {
                 if let Some(path) = args {
                     self.load_functions(&PathBuf::from(path));
                 } else if !self.core.functions_files.is_empty() {
@@ -975,34 +1668,238 @@ impl App {
                         } else {
                             self.status_message = "Invalid width".to_string();
                         }
-                    } else {
-                        self.status_message = "Usage: :set colwidth <n>".to_string();
-                    }
-                } else {
-                    self.status_message = "Usage: :set colwidth <n>".to_string();
-                }
-            }
-            "colwidth" | "cw" => {
-                if let Some(args) = args {
-                    let parts: Vec<&str> = args.split_whitespace().collect();
-                    match parts.len() {
-                        1 => {
-                            // :colwidth 15 - set current column
-                            if let Ok(w) = parts[0].parse() {
-                                self.set_column_width(w);
-                                self.status_message = format!(
-                                    "Column width set to {}",
-                                    self.get_column_width(self.cursor_col)
-                                );
-                            } else {
-                                self.status_message = "Invalid width".to_string();
+                    } else if parts.len() == 2 && parts[0] == "locale" {
+                        let locale = match parts[1].to_ascii_lowercase().as_str() {
+                            "plain" => Some(NumberLocale::Plain),
+                            "en" => Some(NumberLocale::English),
+                            "eu" => Some(NumberLocale::European),
+                            _ => None,
+                        };
+                        if let Some(locale) = locale {
+                            self.core.number_locale = locale;
+                            self.status_message = format!("Number locale set to {}", parts[1]);
+                        } else {
+                            self.status_message =
+                                "Invalid locale (use plain, en or eu)".to_string();
+                        }
+                    } else if parts.len() == 2 && parts[0] == "blanks" {
+                        match parts[1] {
+                            "zero" => {
+                                self.core.set_blanks_are_zero(true);
+                                self.status_message = "Blank cells count as 0".to_string();
+                            }
+                            "error" => {
+                                self.core.set_blanks_are_zero(false);
+                                self.status_message =
+                                    "Blank cells in formulas are errors".to_string();
+                            }
+                            _ => {
+                                self.status_message = "Usage: :set blanks <zero|error>".to_string()
                             }
                         }
-                        2 => {
-                            // :colwidth A 15 - set specific column
-                            if let Some(col) = parse_column_letter(parts[0]) {
-                                if let Ok(w) = parts[1].parse::<usize>() {
-                                    let w = w.clamp(4, 50);
+                    } else if parts.len() == 2 && parts[0] == "freezedeps" {
+                        match parts[1] {
+                            "on" => {
+                                self.core.set_freeze_dependents_on_delete(true);
+                                self.status_message =
+                                    "Deleting a cell freezes formulas that use it".to_string();
+                            }
+                            "off" => {
+                                self.core.set_freeze_dependents_on_delete(false);
+                                self.status_message =
+                                    "Deleting a cell recomputes formulas that use it".to_string();
+                            }
+                            _ => {
+                                self.status_message = "Usage: :set freezedeps <on|off>".to_string()
+                            }
+                        }
+                    } else if parts.len() == 2 && parts[0] == "headerrows" {
+                        if let Ok(rows) = parts[1].parse::<usize>() {
+                            self.core.set_header_rows(rows);
+                            self.status_message =
+                                format!("{} header row(s) excluded from whole-column ranges", rows);
+                        } else {
+                            self.status_message = "Usage: :set headerrows <n>".to_string();
+                        }
+                    } else if (parts.len() == 2 || parts.len() == 3) && parts[0] == "volatile" {
+                        let on = match parts.get(2) {
+                            None | Some(&"on") => Some(true),
+                            Some(&"off") => Some(false),
+                            Some(_) => None,
+                        };
+                        match (CellRef::from_str(parts[1]), on) {
+                            (Some(cell_ref), Some(on)) => {
+                                self.core.set_volatile(&cell_ref, on);
+                                self.status_message = if on {
+                                    format!("{} recalculates every tick", cell_ref)
+                                } else {
+                                    format!("{} is no longer volatile", cell_ref)
+                                };
+                            }
+                            _ => {
+                                self.status_message =
+                                    "Usage: :set volatile <cell> [on|off]".to_string()
+                            }
+                        }
+                    } else if parts.len() == 2 && parts[0] == "overflow" {
+                        match parts[1] {
+                            "on" => {
+                                self.overflow = true;
+                                self.status_message =
+                                    "Long text overflows into empty cells".to_string();
+                            }
+                            "off" => {
+                                self.overflow = false;
+                                self.status_message = "Long text is clipped".to_string();
+                            }
+                            _ => self.status_message = "Usage: :set overflow <on|off>".to_string(),
+                        }
+                    } else if parts.len() == 2 && parts[0] == "headers" {
+                        match parts[1] {
+                            "numeric" => {
+                                self.numeric_headers = true;
+                                self.status_message = "Column headers: 1, 2, 3".to_string();
+                            }
+                            "letters" => {
+                                self.numeric_headers = false;
+                                self.status_message = "Column headers: A, B, C".to_string();
+                            }
+                            _ => {
+                                self.status_message =
+                                    "Usage: :set headers <letters|numeric>".to_string()
+                            }
+                        }
+                    } else if parts.len() == 2 && parts[0] == "watch" {
+                        match parts[1] {
+                            "on" => {
+                                self.watch_functions = true;
+                                self.functions_mtimes = self.current_functions_mtimes();
+                                self.status_message = "Watching functions files".to_string();
+                            }
+                            "off" => {
+                                self.watch_functions = false;
+                                self.status_message =
+                                    "Stopped watching functions files".to_string();
+                            }
+                            _ => self.status_message = "Usage: :set watch <on|off>".to_string(),
+                        }
+                    } else {
+                        self.status_message =
+                            "Usage: :set colwidth <n> | locale <plain|en|eu> | watch <on|off> | blanks <zero|error> | freezedeps <on|off> | headerrows <n> | overflow <on|off> | headers <letters|numeric> | volatile <cell> [on|off]"
+                            .to_string();
+                    }
+                } else {
+                    self.status_message =
+                        "Usage: :set colwidth <n> | locale <plain|en|eu> | watch <on|off> | blanks <zero|error> | freezedeps <on|off> | headerrows <n> | overflow <on|off> | headers <letters|numeric> | volatile <cell> [on|off]"
+                            .to_string();
+                }
+            }
+            "split" => self.toggle_split(),
+            "profile" => {
+                let col = match args {
+                    Some(arg) => parse_column_letter(arg),
+                    None => Some(self.cursor_col),
+                };
+                match col {
+                    Some(col) => self.open_profile_modal(col),
+                    None => self.status_message = "Usage: :profile [COL]".to_string(),
+                }
+            }
+            "audit" => self.open_audit_modal(),
+            "tonumber" => {
+                let column = |col| (CellRef::new(col, 0), CellRef::new(col, MAX_GRID_EXTENT));
+                let range = match args {
+                    Some(arg) => parse_column_letter(arg).map(column),
+                    None => Some(match self.get_selection() {
+                        Some(((c1, r1), (c2, r2))) => (CellRef::new(c1, r1), CellRef::new(c2, r2)),
+                        None => column(self.cursor_col),
+                    }),
+                };
+                match range {
+                    Some((top_left, bottom_right)) => {
+                        let converted = self.core.convert_text_to_numbers(&top_left, &bottom_right);
+                        self.status_message = format!("Converted {} cell(s) to numbers", converted);
+                    }
+                    None => self.status_message = "Usage: :tonumber [COL]".to_string(),
+                }
+            }
+            "colhide" => {
+                let col = match args {
+                    Some(arg) => parse_column_letter(arg),
+                    None => Some(self.cursor_col),
+                };
+                match col {
+                    Some(col) if col >= self.max_cols => {
+                        self.status_message = "Column out of range".to_string();
+                    }
+                    Some(col) => {
+                        let visible = (0..self.max_cols)
+                            .filter(|&c| !self.core.is_column_hidden(c))
+                            .count();
+                        if !self.core.is_column_hidden(col) && visible <= 1 {
+                            self.status_message = "Cannot hide the last visible column".to_string();
+                        } else {
+                            self.core.hide_column(col);
+                            if self.cursor_col == col
+                                && let Some(next) = self
+                                    .next_visible_col(col, true)
+                                    .or_else(|| self.next_visible_col(col, false))
+                            {
+                                self.cursor_col = next;
+                            }
+                            self.update_viewport();
+                            self.status_message =
+                                format!("Column {} hidden", CellRef::col_to_letters(col));
+                        }
+                    }
+                    None => {
+                        self.status_message = "Usage: :colhide [COL]".to_string();
+                    }
+                }
+            }
+            "colshow" => match args {
+                Some(arg) if arg.eq_ignore_ascii_case("all") => {
+                    let count = self.core.show_all_columns();
+                    self.status_message = format!("Showed {} hidden column(s)", count);
+                }
+                Some(arg) => {
+                    if let Some(col) = parse_column_letter(arg) {
+                        if self.core.show_column(col) {
+                            self.status_message =
+                                format!("Column {} shown", CellRef::col_to_letters(col));
+                        } else {
+                            self.status_message =
+                                format!("Column {} is not hidden", CellRef::col_to_letters(col));
+                        }
+                    } else {
+                        self.status_message = "Invalid column".to_string();
+                    }
+                }
+                None => {
+                    self.status_message = "Usage: :colshow COL|all".to_string();
+                }
+            },
+            "colwidth" => {
+                if let Some(args) = args {
+                    let parts: Vec<&str> = args.split_whitespace().collect();
+                    match parts.len() {
+                        1 => {
+                            // :colwidth 15 - set current column
+                            if let Ok(w) = parts[0].parse() {
+                                self.set_column_width(w);
+                                self.status_message = format!(
+                                    "Column width set to {}",
+                                    self.get_column_width(self.cursor_col)
+                                );
+                            } else {
+                                self.status_message = "Invalid width".to_string();
+                            }
+                        }
+                        2 => {
+                            // :colwidth A 15 - set specific column
+                            if let Some(col) = parse_column_letter(parts[0]) {
+                                if let Ok(w) = parts[1].parse::<usize>() {
+                                    let w = w.clamp(4, 50);
                                     self.column_widths.insert(col, w);
                                     self.status_message = format!(
                                         "Column {} width set to {}",
@@ -1024,42 +1921,179 @@ impl App {
                     self.status_message = "Usage: :colwidth [COL] WIDTH".to_string();
                 }
             }
-            "import" => {
+            "import" => match args.map(parse_import_args) {
+                Some(Ok((path, options))) => self.import_csv(&path, &options),
+                Some(Err(e)) => self.status_message = format!("Error: {}", e),
+                None => {
+                    self.status_message =
+                        "Usage: :import <file.csv> [--cols 1,3,5] [--skip N] [--limit N]"
+                            .to_string();
+                }
+            },
+            "export" => match args.map(parse_export_args) {
+                Some(Ok((path, options))) => self.export_csv(&path, &options),
+                Some(Err(e)) => self.status_message = format!("Error: {}", e),
+                None => {
+                    self.status_message =
+                        "Usage: :export <file.csv> [--excel | --bom --crlf]".to_string();
+                }
+            },
+            "graph" => {
                 if let Some(path) = args {
-                    self.import_csv(path);
+                    match self.core.write_dependency_dot(Path::new(path)) {
+                        Ok(()) => {
+                            self.status_message = format!("Wrote dependency graph to {}", path)
+                        }
+                        Err(e) => self.status_message = format!("Error: {}", e),
+                    }
                 } else {
-                    self.status_message = "Usage: :import <file.csv>".to_string();
+                    self.status_message = "Usage: :graph <file.dot>".to_string();
                 }
             }
-            "export" => {
-                if let Some(path) = args {
-                    self.export_csv(path);
+            "paste" => match args.map(str::trim) {
+                None | Some("") => self.paste(),
+                Some("transpose") => self.paste_transposed(),
+                Some(_) => self.status_message = "Usage: :paste [transpose]".to_string(),
+            },
+            "align" => {
+                let align = match args.map(str::trim) {
+                    Some("clear") => Ok(None),
+                    Some(name) => Align::parse(name).map(Some).ok_or(()),
+                    None => Err(()),
+                };
+                match align {
+                    Ok(align) => {
+                        let ((c1, r1), (c2, r2)) = self.get_selection().unwrap_or((
+                            (self.cursor_col, self.cursor_row),
+                            (self.cursor_col, self.cursor_row),
+                        ));
+                        let count = self.core.set_alignment(
+                            &CellRef::new(c1, r1),
+                            &CellRef::new(c2, r2),
+                            align,
+                        );
+                        self.status_message = match align {
+                            Some(align) => format!("Aligned {} cell(s) {}", count, align.name()),
+                            None => format!("Cleared alignment of {} cell(s)", count),
+                        };
+                    }
+                    Err(()) => {
+                        self.status_message = "Usage: :align <left|center|right|clear>".to_string()
+                    }
+                }
+            }
+            "recalc-selection" => {
+                let ((c1, r1), (c2, r2)) = self.get_selection().unwrap_or((
+                    (self.cursor_col, self.cursor_row),
+                    (self.cursor_col, self.cursor_row),
+                ));
+                let (top_left, bottom_right) = (CellRef::new(c1, r1), CellRef::new(c2, r2));
+                let count = self.core.recalc_range(&top_left, &bottom_right);
+                self.status_message = if top_left == bottom_right {
+                    format!("Recalculated {} formula(s) at {}", count, top_left)
                 } else {
-                    self.status_message = "Usage: :export <file.csv>".to_string();
+                    format!(
+                        "Recalculated {} formula(s) in {}:{}",
+                        count, top_left, bottom_right
+                    )
+                };
+            }
+            "merge" => {
+                let range = match args {
+                    Some(arg) => parse_range(arg)
+                        .map(|(c1, r1, c2, r2)| (CellRef::new(c1, r1), CellRef::new(c2, r2))),
+                    None => self
+                        .get_selection()
+                        .map(|((c1, r1), (c2, r2))| (CellRef::new(c1, r1), CellRef::new(c2, r2))),
+                };
+                match range {
+                    Some((top_left, bottom_right)) => {
+                        match self.core.merge_cells(&top_left, &bottom_right) {
+                            Ok(()) => {
+                                self.selection_anchor = None;
+                                self.status_message =
+                                    format!("Merged {}:{}", top_left, bottom_right);
+                            }
+                            Err(e) => self.status_message = format!("Error: {}", e),
+                        }
+                    }
+                    None => {
+                        self.status_message =
+                            "Usage: :merge <range> (or select cells first)".to_string()
+                    }
                 }
             }
-            "freeze" | "fr" => {
+            "group" => {
+                let rows = match args {
+                    Some(arg) => arg.split_once('-').and_then(|(a, b)| {
+                        let a = a.trim().parse::<usize>().ok().filter(|&n| n >= 1)?;
+                        let b = b.trim().parse::<usize>().ok().filter(|&n| n >= 1)?;
+                        Some((a - 1, b - 1))
+                    }),
+                    None => self.get_selection().map(|((_, r1), (_, r2))| (r1, r2)),
+                };
+                match rows {
+                    Some((start, end)) => match self.core.group_rows(start, end) {
+                        Ok(()) => {
+                            self.selection_anchor = None;
+                            self.status_message = format!(
+                                "Grouped rows {}-{} (za to collapse)",
+                                start.min(end) + 1,
+                                start.max(end) + 1
+                            );
+                        }
+                        Err(e) => self.status_message = format!("Error: {}", e),
+                    },
+                    None => {
+                        self.status_message =
+                            "Usage: :group <first>-<last> (or select rows first)".to_string()
+                    }
+                }
+            }
+            "ungroup" => {
+                let row = self.cursor_row;
+                self.status_message = match self.core.ungroup_rows(row) {
+                    Some((start, end)) => format!("Ungrouped rows {}-{}", start + 1, end + 1),
+                    None => format!("No row group at row {}", row + 1),
+                };
+            }
+            "unmerge" => {
+                let cell = self.current_cell_ref();
+                self.status_message = if self.core.unmerge_cells(&cell) {
+                    format!("Unmerged cells at {}", cell)
+                } else {
+                    format!("{} is not merged", cell)
+                };
+            }
+            "freeze" => {
                 self.freeze_current_cell();
             }
-            "freezeall" | "fa" => {
+            "freezeall" => {
                 self.freeze_all_cells();
             }
-            "ir" | "insertrow" => {
+            "ir" => {
                 self.insert_row();
             }
-            "dr" | "deleterow" => {
+            "dr" => {
                 self.delete_row();
             }
-            "ic" | "insertcol" => {
+            "ic" => {
                 self.insert_column();
             }
-            "dc" | "deletecol" => {
+            "dc" => {
                 self.delete_column();
             }
-            "help" | "h" => {
+            "compact" => match args {
+                Some("rows") => self.compact(false),
+                Some("cols") => self.compact(true),
+                _ => {
+                    self.status_message = "Usage: :compact rows|cols".to_string();
+                }
+            },
+            "help" => {
                 self.open_help_modal();
             }
-            "find" | "search" => {
+            "find" => {
                 if let Some(pattern) = args {
                     self.execute_search(pattern);
                 } else {
@@ -1074,6 +2108,51 @@ impl App {
                     self.status_message = "Usage: :call func_name(args)".to_string();
                 }
             }
+            "genfill" => {
+                // :genfill |i| ... - fill the selection (or cursor cell) from a closure
+                let Some(closure) = args else {
+                    self.status_message =
+                        "Usage: :genfill <closure>, e.g. :genfill |i| i * i".to_string();
+                    return false;
+                };
+                let ((c1, r1), (c2, r2)) = self.get_selection().unwrap_or((
+                    (self.cursor_col, self.cursor_row),
+                    (self.cursor_col, self.cursor_row),
+                ));
+                match self.core.fill_range_with_generator(
+                    &CellRef::new(c1, r1),
+                    &CellRef::new(c2, r2),
+                    closure,
+                ) {
+                    Ok(filled) => {
+                        self.selection_anchor = None;
+                        self.status_message = format!("Filled {} cell(s)", filled);
+                    }
+                    Err(e) => self.status_message = format!("Error: {}", e),
+                }
+            }
+            "deflambda" => {
+                // :deflambda name = |args| body - define a formula callable as NAME(args)
+                let Some(args) = args else {
+                    let names: Vec<&str> = self.core.lambdas().keys().map(String::as_str).collect();
+                    self.status_message = if names.is_empty() {
+                        "No lambdas defined".to_string()
+                    } else {
+                        format!("Lambdas: {}", names.join(", "))
+                    };
+                    return false;
+                };
+                let Some((name, lambda)) = args.split_once('=') else {
+                    self.status_message =
+                        "Usage: :deflambda <name> = |args| body, e.g. :deflambda profit = |rev, cost| rev - cost"
+                            .to_string();
+                    return false;
+                };
+                match self.core.define_lambda(name, lambda) {
+                    Ok(name) => self.status_message = format!("Defined {}", name),
+                    Err(e) => self.status_message = format!("Error: {}", e),
+                }
+            }
             "rhai" => {
                 // :rhai expression - Execute arbitrary Rhai expression
                 if let Some(expr) = args {
@@ -1140,16 +2219,28 @@ impl App {
     }
 
     /// Import CSV data starting at current cursor position
-    fn import_csv(&mut self, path: &str) {
-        match self.core.import_csv(path, self.cursor_col, self.cursor_row) {
-            Ok(count) => self.status_message = format!("Imported {} cells from {}", count, path),
+    fn import_csv(&mut self, path: &str, options: &CsvImportOptions) {
+        match self
+            .core
+            .import_csv_with_options(path, self.cursor_col, self.cursor_row, options)
+        {
+            Ok(count) => {
+                self.fit_bounds_to_data();
+                self.status_message = format!("Imported {} cells from {}", count, path);
+            }
             Err(e) => self.status_message = format!("Error: {}", e),
         }
     }
 
     /// Export grid to CSV file
-    fn export_csv(&mut self, path: &str) {
-        match self.core.export_csv(path, self.get_selection()) {
+    fn export_csv(&mut self, path: &str, options: &CsvExportOptions) {
+        let range = self
+            .selection_anchor
+            .map(|(col, row)| (CellRef::new(col, row), self.current_cell_ref()));
+        match self
+            .core
+            .export_csv_range_with_options(Path::new(path), range, options)
+        {
             Ok(()) => self.status_message = format!("Exported to {}", path),
             Err(e) => self.status_message = format!("Error: {}", e),
         }
@@ -1162,6 +2253,71 @@ impl Default for App {
     }
 }
 
+/// Split `:import` arguments into the file path and the `--cols 1,3,5`,
+/// `--skip N` and `--limit N` options. Columns are 1-based CSV field numbers.
+fn parse_import_args(args: &str) -> std::result::Result<(String, CsvImportOptions), String> {
+    let mut options = CsvImportOptions::default();
+    let mut path: Vec<&str> = Vec::new();
+    let mut tokens = args.split_whitespace();
+    while let Some(token) = tokens.next() {
+        let mut value = |flag: &str| {
+            tokens
+                .next()
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match token {
+            "--cols" => {
+                let list = value(token)?;
+                let columns = list
+                    .split(',')
+                    .map(|n| match n.trim().parse::<usize>() {
+                        Ok(n) if n > 0 => Ok(n - 1),
+                        _ => Err(format!("Invalid column number: {}", n.trim())),
+                    })
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                options.columns = Some(columns);
+            }
+            "--skip" | "--limit" => {
+                let raw = value(token)?;
+                let n = raw
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid {} value: {}", token, raw))?;
+                if token == "--skip" {
+                    options.skip = n;
+                } else {
+                    options.limit = Some(n);
+                }
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            part => path.push(part),
+        }
+    }
+    if path.is_empty() {
+        return Err("Usage: :import <file.csv> [--cols 1,3,5] [--skip N] [--limit N]".to_string());
+    }
+    Ok((path.join(" "), options))
+}
+
+/// Split `:export` arguments into the file path and the `--bom` and `--crlf`
+/// options; `--excel` sets both.
+fn parse_export_args(args: &str) -> std::result::Result<(String, CsvExportOptions), String> {
+    let mut options = CsvExportOptions::default();
+    let mut path: Vec<&str> = Vec::new();
+    for token in args.split_whitespace() {
+        match token {
+            "--excel" => options = CsvExportOptions::excel(),
+            "--bom" => options.bom = true,
+            "--crlf" => options.crlf = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            part => path.push(part),
+        }
+    }
+    if path.is_empty() {
+        return Err("Usage: :export <file.csv> [--excel | --bom --crlf]".to_string());
+    }
+    Ok((path.join(" "), options))
+}
+
 /// Parse column letter(s) to column index (e.g., "A" -> 0, "AA" -> 26)
 fn parse_column_letter(s: &str) -> Option<usize> {
     let s = s.trim().to_uppercase();
@@ -1214,6 +2370,62 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_tonumber_command_converts_column() {
+        let mut app = App::new();
+        for (row, text) in ["1", "2", "x"].iter().enumerate() {
+            app.core
+                .grid
+                .insert(CellRef::new(1, row), Cell::new_text(text));
+        }
+        app.command_buffer = "tonumber B".to_string();
+        app.execute_command();
+        assert_eq!(app.status_message, "Converted 2 cell(s) to numbers");
+        assert!(matches!(
+            app.core.grid.get(&CellRef::new(1, 1)).unwrap().contents,
+            CellType::Number(n) if n == 2.0
+        ));
+        assert!(matches!(
+            app.core.grid.get(&CellRef::new(1, 2)).unwrap().contents,
+            CellType::Text(_)
+        ));
+    }
+
+    #[test]
+    fn test_paste_offsets_formula_references() {
+        let mut app = App::new();
+        app.core
+            .set_cell_from_input(CellRef::new(1, 0), "=A1")
+            .unwrap();
+        app.cursor_col = 1;
+        app.cursor_row = 0;
+        app.yank();
+
+        app.cursor_row = 1;
+        app.paste();
+        let input = |app: &App, col, row| {
+            app.core
+                .grid
+                .get(&CellRef::new(col, row))
+                .map(|c| c.to_input_string())
+        };
+        assert_eq!(input(&app, 1, 1).as_deref(), Some("=A2"));
+
+        // A counted paste offsets each copy by its own row.
+        app.cursor_row = 2;
+        app.paste_with_count(2);
+        assert_eq!(input(&app, 1, 2).as_deref(), Some("=A3"));
+        assert_eq!(input(&app, 1, 3).as_deref(), Some("=A4"));
+
+        // Yanked rows keep references relative as well.
+        app.cursor_row = 3;
+        app.yank_row();
+        app.cursor_col = 0;
+        app.cursor_row = 6;
+        app.paste();
+        assert_eq!(input(&app, 1, 6).as_deref(), Some("=A7"));
+    }
+
     #[test]
     fn test_freeze_command_replaces_formula_at_cursor() {
         let mut app = App::new();
@@ -1328,4 +2540,525 @@ mod tests {
         assert_eq!(app.core.get_cell_display(&CellRef::new(0, 0)), "top");
         assert_eq!(app.core.get_cell_display(&CellRef::new(0, 1)), "second");
     }
+
+    #[test]
+    fn test_jump_to_relation_cycles_precedents_and_dependents() {
+        let mut app = App::new();
+        // A1 = 1, B1 = 2, C1 = A1 + B1, C2 = C1 * 2, D1 = C1 + 1
+        app.core
+            .set_cell_from_input(CellRef::new(0, 0), "1")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(1, 0), "2")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(2, 0), "=A1 + B1")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(2, 1), "=C1 * 2")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(3, 0), "=C1 + 1")
+            .unwrap();
+
+        assert_eq!(
+            app.core.precedents_of(&CellRef::new(2, 0)),
+            vec![CellRef::new(0, 0), CellRef::new(1, 0)]
+        );
+        assert_eq!(
+            app.core.dependents_of(&CellRef::new(2, 0)),
+            vec![CellRef::new(3, 0), CellRef::new(2, 1)]
+        );
+
+        app.cursor_col = 2;
+        app.cursor_row = 0;
+        app.jump_to_relation(Relation::Precedent);
+        assert_eq!(app.current_cell_ref(), CellRef::new(0, 0));
+        assert_eq!(app.status_message, "A1 precedent of C1 [1/2]");
+        app.jump_to_relation(Relation::Precedent);
+        assert_eq!(app.current_cell_ref(), CellRef::new(1, 0));
+        app.jump_to_relation(Relation::Precedent);
+        assert_eq!(app.current_cell_ref(), CellRef::new(0, 0));
+
+        app.cursor_col = 2;
+        app.cursor_row = 0;
+        app.jump_to_relation(Relation::Dependent);
+        assert_eq!(app.current_cell_ref(), CellRef::new(3, 0));
+        assert_eq!(app.status_message, "D1 dependent of C1 [1/2]");
+        app.jump_to_relation(Relation::Dependent);
+        assert_eq!(app.current_cell_ref(), CellRef::new(2, 1));
+    }
+
+    #[test]
+    fn test_jump_to_relation_without_targets_keeps_cursor() {
+        let mut app = App::new();
+        app.core
+            .set_cell_from_input(CellRef::new(1, 1), "5")
+            .unwrap();
+        app.cursor_col = 1;
+        app.cursor_row = 1;
+
+        app.jump_to_relation(Relation::Precedent);
+        assert_eq!(app.current_cell_ref(), CellRef::new(1, 1));
+        assert_eq!(app.status_message, "B2 has no precedents");
+
+        app.jump_to_relation(Relation::Dependent);
+        assert_eq!(app.status_message, "B2 has no dependents");
+        assert!(app.relation_nav.is_none());
+    }
+
+    #[test]
+    fn test_colhide_keeps_references_and_navigation_skips_column() {
+        let mut app = App::new();
+        app.core
+            .set_cell_from_input(CellRef::new(1, 0), "21")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(2, 0), "=B1 * 2")
+            .unwrap();
+
+        app.command_buffer = "colhide B".to_string();
+        app.execute_command();
+        assert!(app.core.is_column_hidden(1));
+        assert_eq!(app.status_message, "Column B hidden");
+        assert_eq!(app.core.get_cell_display(&CellRef::new(2, 0)), "42");
+
+        app.cursor_col = 0;
+        app.move_cursor(1, 0);
+        assert_eq!(app.cursor_col, 2);
+        app.move_cursor(-1, 0);
+        assert_eq!(app.cursor_col, 0);
+        assert!(!app.displayed_columns().contains(&1));
+
+        app.command_buffer = "colshow B".to_string();
+        app.execute_command();
+        assert!(!app.core.is_column_hidden(1));
+        app.move_cursor(1, 0);
+        assert_eq!(app.cursor_col, 1);
+    }
+
+    #[test]
+    fn test_compact_cols_shifts_cells_and_column_widths() {
+        let mut app = App::new();
+        app.core
+            .set_cell_from_input(CellRef::new(0, 0), "5")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(2, 0), "=A1 + 1")
+            .unwrap();
+        app.column_widths.insert(2, 20);
+        app.cursor_col = 2;
+
+        app.command_buffer = "compact cols".to_string();
+        app.execute_command();
+        assert_eq!(app.status_message, "Removed 1 empty column(s)");
+        assert_eq!(app.core.get_cell_display(&CellRef::new(1, 0)), "6");
+        assert_eq!(app.get_column_width(1), 20);
+        assert_eq!(app.cursor_col, 1);
+
+        app.command_buffer = "compact rows".to_string();
+        app.execute_command();
+        assert_eq!(app.status_message, "No empty rows to remove");
+    }
+
+    #[test]
+    fn test_import_beyond_bounds_grows_navigation() {
+        let path = std::env::temp_dir().join(format!("gridline-grow-{}.csv", std::process::id()));
+        let rows: Vec<String> = (0..50).map(|i| format!("{},x,y,z", i)).collect();
+        std::fs::write(&path, rows.join("\n")).unwrap();
+
+        let mut app = App::new();
+        app.cursor_col = 24; // Y
+        app.cursor_row = 980;
+        app.import_csv(path.to_str().unwrap(), &CsvImportOptions::default());
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(app.core.get_cell_display(&CellRef::new(24, 1029)), "49");
+        assert_eq!(app.core.get_cell_display(&CellRef::new(27, 1029)), "z");
+        assert_eq!(app.max_rows, 1030);
+        assert_eq!(app.max_cols, 28);
+
+        // The new last row and column are reachable
+        app.move_cursor(3, 100);
+        assert_eq!((app.cursor_col, app.cursor_row), (27, 1029));
+    }
+
+    #[test]
+    fn test_overflow_width_stops_at_occupied_neighbor() {
+        let mut app = App::new();
+        app.visible_cols = 6;
+        app.col_width = 10;
+        app.column_widths.insert(1, 4);
+        app.core
+            .set_cell_from_input(CellRef::new(0, 0), "\"a long heading here\"")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(3, 0), "\"x\"")
+            .unwrap();
+
+        // A (10) + gap + B (4) + gap + C (10) before D blocks it
+        assert_eq!(app.overflow_width(0, 0, 30), 26);
+        // Capped at the text's own width
+        assert_eq!(app.overflow_width(0, 0, 12), 12);
+        // A hidden column is skipped over
+        app.core.hide_column(1);
+        assert_eq!(app.overflow_width(0, 0, 30), 21);
+        // An occupied right-hand neighbour leaves the text clipped
+        app.core
+            .set_cell_from_input(CellRef::new(2, 0), "1")
+            .unwrap();
+        assert_eq!(app.overflow_width(0, 0, 30), 10);
+
+        app.command_buffer = "set overflow on".to_string();
+        app.execute_command();
+        assert!(app.overflow);
+    }
+
+    #[test]
+    fn test_import_command_with_column_and_row_options() {
+        let path =
+            std::env::temp_dir().join(format!("gridline-import-subset-{}.csv", std::process::id()));
+        std::fs::write(&path, "id,name,score\n1,a,10\n2,b,20\n3,c,30\n").unwrap();
+
+        let mut app = App::new();
+        app.command_buffer = format!(
+            "import {} --cols 3,1 --skip 1 --limit 2",
+            path.to_str().unwrap()
+        );
+        app.execute_command();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            app.status_message,
+            format!("Imported 4 cells from {}", path.display())
+        );
+        assert_eq!(app.core.get_cell_display(&CellRef::new(0, 0)), "10");
+        assert_eq!(app.core.get_cell_display(&CellRef::new(1, 0)), "1");
+        assert_eq!(app.core.get_cell_display(&CellRef::new(0, 1)), "20");
+        assert!(app.core.grid.get(&CellRef::new(0, 2)).is_none());
+        assert!(app.core.grid.get(&CellRef::new(2, 0)).is_none());
+
+        app.command_buffer = "import data.csv --cols 0".to_string();
+        app.execute_command();
+        assert_eq!(app.status_message, "Error: Invalid column number: 0");
+    }
+
+    #[test]
+    fn test_edit_command_opens_multiline_and_commit_joins_lines() {
+        let mut app = App::new();
+        app.core
+            .set_cell_from_input(CellRef::new(0, 0), "=1 + 2")
+            .unwrap();
+        app.mode = Mode::Command;
+        app.command_buffer = "edit".to_string();
+
+        app.execute_command();
+        assert!(matches!(app.mode, Mode::Edit));
+        assert!(app.multiline_edit);
+        assert_eq!(app.edit_buffer, "=1 + 2");
+
+        app.edit_buffer = "=POW(2, 3)\n  * 10\n".to_string();
+        app.commit_edit();
+
+        assert!(!app.multiline_edit);
+        let cell = app.core.grid.get(&CellRef::new(0, 0)).unwrap().clone();
+        assert_eq!(cell.to_input_string(), "=POW(2, 3) * 10");
+        assert_eq!(app.core.get_cell_display(&CellRef::new(0, 0)), "80");
+    }
+
+    #[test]
+    fn test_functions_changed_detects_mtime_differences() {
+        let path = PathBuf::from("default.rhai");
+        let t0 = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(100);
+        let t1 = t0 + std::time::Duration::from_secs(1);
+
+        let stored: FunctionsMtimes = [(path.clone(), Some(t0))].into_iter().collect();
+        let same = stored.clone();
+        assert!(!functions_changed(&stored, &same));
+
+        let touched: FunctionsMtimes = [(path.clone(), Some(t1))].into_iter().collect();
+        assert!(functions_changed(&stored, &touched));
+
+        let deleted: FunctionsMtimes = [(path.clone(), None)].into_iter().collect();
+        assert!(functions_changed(&stored, &deleted));
+
+        let added: FunctionsMtimes = [(path, Some(t0)), (PathBuf::from("extra.rhai"), Some(t0))]
+            .into_iter()
+            .collect();
+        assert!(functions_changed(&stored, &added));
+    }
+
+    #[test]
+    fn test_check_functions_changed_reloads_when_watching() {
+        let path = std::env::temp_dir().join(format!("gridline-watch-{}.rhai", std::process::id()));
+        std::fs::write(&path, "fn twice(x) { x * 2 }").unwrap();
+
+        let mut app = App::new();
+        app.load_functions(&path);
+        app.core
+            .set_cell_from_input(CellRef::new(0, 0), "=twice(3)")
+            .unwrap();
+
+        // Not watching: nothing happens even if the stored snapshot is stale.
+        app.functions_mtimes.clear();
+        assert!(!app.check_functions_changed());
+
+        app.command_buffer = "set watch on".to_string();
+        app.execute_command();
+        assert!(app.watch_functions);
+        assert!(!app.check_functions_changed());
+
+        std::fs::write(&path, "fn twice(x) { x * 20 }").unwrap();
+        // Force a difference regardless of filesystem mtime granularity.
+        app.functions_mtimes.insert(path.clone(), None);
+        assert!(app.check_functions_changed());
+        assert!(!app.check_functions_changed());
+        let _ = std::fs::remove_file(&path);
+
+        assert!(app.status_message.starts_with("Functions changed"));
+        let display = app.core.get_cell_display(&CellRef::new(0, 0));
+        assert_eq!(display.trim(), "60");
+    }
+
+    #[test]
+    fn test_profile_command_opens_modal() {
+        let mut app = App::new();
+        app.core
+            .set_cell_from_input(CellRef::new(1, 0), "2")
+            .unwrap();
+        app.core
+            .set_cell_from_input(CellRef::new(1, 1), "4")
+            .unwrap();
+
+        app.command_buffer = "profile B".to_string();
+        app.execute_command();
+        let profile = app.profile_modal.as_ref().unwrap();
+        assert_eq!(profile.col, 1);
+        assert_eq!(profile.mean, Some(3.0));
+        let lines = crate::tui::ui::profile_lines(profile);
+        assert!(lines.contains(&"Mean:       3".to_string()));
+
+        app.close_profile_modal();
+        app.command_buffer = "profile C".to_string();
+        app.execute_command();
+        assert!(app.profile_modal.is_none());
+        assert_eq!(app.status_message, "Column C is empty");
+    }
+
+    #[test]
+    fn test_split_inactive_pane_stays_fixed_while_active_scrolls() {
+        let mut app = App::new();
+        app.visible_rows = 10;
+        app.command_buffer = "split".to_string();
+        app.execute_command();
+        assert_eq!(app.split, Some(PaneView::default()));
+
+        app.move_cursor(0, 40);
+        assert_eq!(app.viewport_row, 31);
+        assert_eq!(app.split, Some(PaneView::default()));
+
+        app.switch_pane();
+        assert!(app.split_focus_bottom);
+        assert_eq!((app.cursor_row, app.viewport_row), (0, 0));
+        assert_eq!(
+            app.split.map(|pane| (pane.cursor_row, pane.viewport_row)),
+            Some((40, 31))
+        );
+
+        // Scrolling the bottom pane leaves the top one where it was.
+        app.move_cursor(3, 12);
+        assert_eq!(app.viewport_row, 3);
+        assert_eq!(
+            app.split.map(|pane| (pane.cursor_row, pane.viewport_row)),
+            Some((40, 31))
+        );
+
+        app.toggle_split();
+        assert!(app.split.is_none());
+        assert!(!app.split_focus_bottom);
+        assert_eq!((app.cursor_col, app.cursor_row), (3, 12));
+    }
+
+    #[test]
+    fn test_goto_definition_jumps_to_referenced_cell() {
+        let mut app = App::new();
+        app.core
+            .set_cell_from_input(CellRef::new(0, 0), "=SUM(C5:C9) * 2")
+            .unwrap();
+
+        app.goto_definition();
+        assert_eq!((app.cursor_col, app.cursor_row), (2, 4));
+
+        // C5 is empty: nothing to follow, cursor stays.
+        app.goto_definition();
+        assert_eq!((app.cursor_col, app.cursor_row), (2, 4));
+        assert!(app.status_message.contains("no definition"));
+    }
+
+    #[test]
+    fn test_command_aliases_resolve_through_registry() {
+        let mut app = App::new();
+        app.command_buffer = "g C5".to_string();
+        app.execute_command();
+        assert_eq!((app.cursor_col, app.cursor_row), (2, 4));
+
+        app.command_buffer = "bogus".to_string();
+        app.execute_command();
+        assert_eq!(app.status_message, "Unknown command: bogus");
+    }
+
+    #[test]
+    fn test_command_palette_runs_selection() {
+        let mut app = App::new();
+        app.open_command_palette();
+        for c in "split".chars() {
+            app.command_palette.as_mut().unwrap().push(c);
+        }
+        assert!(!app.run_palette_selection());
+        assert!(app.command_palette.is_none());
+        assert!(app.split.is_some());
+        assert!(app.mode == Mode::Normal);
+
+        // Commands with arguments open the prompt pre-filled instead
+        app.open_command_palette();
+        for c in "goto".chars() {
+            app.command_palette.as_mut().unwrap().push(c);
+        }
+        app.run_palette_selection();
+        assert!(app.mode == Mode::Command);
+        assert_eq!(app.command_buffer, "goto ");
+        assert_eq!(app.command_cursor, 5);
+    }
+
+    #[test]
+    fn test_edit_in_merged_region_goes_to_anchor() {
+        let mut app = App::new();
+        app.command_buffer = "merge A1:C2".to_string();
+        app.execute_command();
+        assert_eq!(app.core.merged_regions.len(), 1);
+
+        app.cursor_col = 2;
+        app.cursor_row = 1;
+        app.enter_edit_mode();
+        assert_eq!((app.cursor_col, app.cursor_row), (0, 0));
+        app.edit_buffer = "Header".to_string();
+        app.commit_edit();
+        assert_eq!(app.core.get_cell_display(&CellRef::new(0, 0)), "Header");
+        assert!(app.core.grid.get(&CellRef::new(2, 1)).is_none());
+
+        app.command_buffer = "unmerge".to_string();
+        app.execute_command();
+        assert!(app.core.merged_regions.is_empty());
+    }
+
+    #[test]
+    fn test_numeric_column_headers() {
+        let mut app = App::new();
+        assert_eq!(app.column_header(0), "A");
+        assert_eq!(app.column_header(27), "AB");
+
+        app.command_buffer = "set headers numeric".to_string();
+        app.execute_command();
+        assert_eq!(app.column_header(0), "1");
+        assert_eq!(app.column_header(27), "28");
+        // Display only: references are still A1-style
+        assert_eq!(app.current_cell_ref().to_string(), "A1");
+
+        app.command_buffer = "set headers letters".to_string();
+        app.execute_command();
+        assert_eq!(app.column_header(0), "A");
+    }
+
+    #[test]
+    fn test_align_command_applies_to_selection() {
+        let mut app = App::new();
+        app.selection_anchor = Some((0, 0));
+        app.cursor_col = 1;
+        app.cursor_row = 1;
+        app.command_buffer = "align center".to_string();
+        app.execute_command();
+        assert_eq!(app.core.alignment(&CellRef::new(1, 0)), Some(Align::Center));
+        assert_eq!(app.core.alignment(&CellRef::new(2, 0)), None);
+
+        app.selection_anchor = None;
+        app.command_buffer = "align clear".to_string();
+        app.execute_command();
+        assert_eq!(app.core.alignment(&CellRef::new(1, 1)), None);
+        assert_eq!(app.core.alignment(&CellRef::new(0, 0)), Some(Align::Center));
+
+        app.command_buffer = "align middle".to_string();
+        app.execute_command();
+        assert!(app.status_message.starts_with("Usage"));
+    }
+
+    #[test]
+    fn test_repeat_last_change_clears_and_edits_at_cursor() {
+        let mut app = App::new();
+        for col in 0..3 {
+            app.core
+                .set_cell_from_input(CellRef::new(col, 0), "1")
+                .unwrap();
+        }
+        app.repeat_last_change();
+        assert_eq!(app.status_message, "No change to repeat");
+
+        app.clear_current_cell(); // A1
+        app.cursor_col = 1;
+        app.repeat_last_change(); // B1
+        assert!(app.core.grid.get(&CellRef::new(0, 0)).is_none());
+        assert!(app.core.grid.get(&CellRef::new(1, 0)).is_none());
+        assert!(app.core.grid.get(&CellRef::new(2, 0)).is_some());
+
+        app.cursor_row = 1;
+        app.edit_buffer = "=A1 + 5".to_string();
+        app.commit_edit(); // B2
+        app.cursor_col = 2;
+        app.repeat_last_change(); // C2
+        assert_eq!(app.core.get_cell_display(&CellRef::new(2, 1)), "5");
+        assert_eq!(
+            app.core
+                .grid
+                .get(&CellRef::new(2, 1))
+                .unwrap()
+                .to_input_string(),
+            "=A1 + 5"
+        );
+    }
+
+    #[test]
+    fn test_collapsed_row_group_is_skipped_by_cursor() {
+        let mut app = App::new();
+        app.command_buffer = "group 2-4".to_string();
+        app.execute_command();
+        assert_eq!(app.core.row_groups, vec![(1, 3)]);
+
+        app.cursor_row = 2;
+        app.toggle_row_group();
+        assert_eq!(app.status_message, "Collapsed rows 2-4");
+        assert_eq!(app.cursor_row, 1);
+        app.move_cursor(0, 1);
+        assert_eq!(app.cursor_row, 4);
+        app.move_cursor(0, -1);
+        assert_eq!(app.cursor_row, 1);
+        assert_eq!(&app.displayed_rows()[..3], &[0, 1, 4]);
+
+        app.toggle_row_group();
+        assert_eq!(app.status_message, "Expanded rows 2-4");
+        app.move_cursor(0, 1);
+        assert_eq!(app.cursor_row, 2);
+    }
+
+    #[test]
+    fn test_export_args_parse_excel_options() {
+        let (path, options) = parse_export_args("out file.csv --excel").unwrap();
+        assert_eq!(path, "out file.csv");
+        assert_eq!(options, CsvExportOptions::excel());
+        let (_, options) = parse_export_args("out.csv --crlf").unwrap();
+        assert!(options.crlf && !options.bom);
+        assert_eq!(
+            parse_export_args("out.csv").unwrap().1,
+            CsvExportOptions::default()
+        );
+        assert!(parse_export_args("out.csv --utf16").is_err());
+    }
 }
@@ -0,0 +1,326 @@
+//! Registry of `:` commands, shared by command-line parsing and the
+//! command palette (`Ctrl+P`).
+
+/// A `:` command known to the TUI.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// Canonical name, as matched in `App::execute_command`
+    pub name: &'static str,
+    /// Alternative spellings that resolve to `name`
+    pub aliases: &'static [&'static str],
+    /// Argument hint shown after the name (empty if the command takes none)
+    pub args: &'static str,
+    /// One-line description
+    pub description: &'static str,
+}
+
+impl CommandSpec {
+    /// Whether the command takes (optional or required) arguments.
+    pub fn takes_args(&self) -> bool {
+        !self.args.is_empty()
+    }
+
+    /// Usage string, e.g. `:goto <cell>`.
+    pub fn usage(&self) -> String {
+        if self.takes_args() {
+            format!(":{} {}", self.name, self.args)
+        } else {
+            format!(":{}", self.name)
+        }
+    }
+}
+
+const fn command(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    args: &'static str,
+    description: &'static str,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        aliases,
+        args,
+        description,
+    }
+}
+
+/// Every `:` command, in the order they are listed in the palette.
+pub const COMMANDS: &[CommandSpec] = &[
+    command("w", &["save"], "[file]", "Save (optionally to a new path)"),
+    command("q", &[], "", "Quit (warns if unsaved)"),
+    command("q!", &[], "", "Force quit, discarding changes"),
+    command("wq", &[], "", "Save and quit"),
+    command("e", &["open", "load"], "<file>", "Open a file"),
+    command("new", &[], "", "New empty document"),
+    command("new!", &[], "", "New document, discarding changes"),
+    command("goto", &["g"], "<cell>", "Go to a cell"),
+    command("edit", &[], "", "Edit the cell in a multi-line box"),
+    command(
+        "move",
+        &["mv"],
+        "<cell>",
+        "Move the cell, updating references to it",
+    ),
+    command("find", &["search"], "<pattern>", "Search cells by regex"),
+    command("ir", &["insertrow"], "", "Insert a row above the cursor"),
+    command("dr", &["deleterow"], "", "Delete the current row"),
+    command(
+        "ic",
+        &["insertcol"],
+        "",
+        "Insert a column left of the cursor",
+    ),
+    command("dc", &["deletecol"], "", "Delete the current column"),
+    command(
+        "compact",
+        &[],
+        "rows|cols",
+        "Remove empty rows or columns within the used range",
+    ),
+    command("set", &[], "<option> <value>", "Change a setting"),
+    command("colwidth", &["cw"], "[col] <n>", "Set a column width"),
+    command("colhide", &[], "[col]", "Hide a column (data is kept)"),
+    command("colshow", &[], "<col|all>", "Show hidden column(s)"),
+    command("split", &[], "", "Toggle a split view of two regions"),
+    command(
+        "profile",
+        &[],
+        "[col]",
+        "Summary stats and top values for a column",
+    ),
+    command(
+        "audit",
+        &[],
+        "",
+        "List formulas referencing empty cells or #REF!",
+    ),
+    command(
+        "tonumber",
+        &[],
+        "[col]",
+        "Turn numeric-looking text into numbers",
+    ),
+    command("import", &[], "<csv>", "Import a CSV at the cursor"),
+    command(
+        "export",
+        &[],
+        "<csv> [--excel]",
+        "Export the grid or selection to CSV",
+    ),
+    command("graph", &[], "<dot>", "Export the dependency graph"),
+    command(
+        "recalc-selection",
+        &[],
+        "",
+        "Recalculate only the selected formulas",
+    ),
+    command(
+        "merge",
+        &[],
+        "[range]",
+        "Merge the selection or range into one display cell",
+    ),
+    command("unmerge", &[], "", "Unmerge the cells at the cursor"),
+    command(
+        "group",
+        &[],
+        "[first-last]",
+        "Group the selected rows so za can collapse them",
+    ),
+    command("ungroup", &[], "", "Remove the row group at the cursor"),
+    command(
+        "paste",
+        &[],
+        "[transpose]",
+        "Paste the clipboard, optionally transposed",
+    ),
+    command(
+        "align",
+        &[],
+        "<left|center|right|clear>",
+        "Align the selection or current cell",
+    ),
+    command(
+        "freeze",
+        &["fr"],
+        "",
+        "Freeze the formula or spill at the cursor",
+    ),
+    command("freezeall", &["fa"], "", "Freeze all formulas and spills"),
+    command(
+        "source",
+        &["so"],
+        "[file]",
+        "Load or reload a Rhai functions file",
+    ),
+    command("call", &[], "<func(args)>", "Call a Rhai function"),
+    command("rhai", &[], "<expr>", "Evaluate a Rhai expression"),
+    command(
+        "genfill",
+        &[],
+        "<closure>",
+        "Fill the selection with a closure of the cell index",
+    ),
+    command(
+        "deflambda",
+        &[],
+        "[name = |args| body]",
+        "Define a formula saved with the sheet",
+    ),
+    command("help", &["h"], "", "Show the help modal"),
+];
+
+/// Resolve a command name or alias to its registry entry.
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.name == name || spec.aliases.contains(&name))
+}
+
+/// Score `query` as a case-insensitive subsequence of `text`; `None` if it
+/// doesn't match. Matches at the start, on word boundaries and in runs score
+/// higher, gaps score lower.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for q in query.to_lowercase().chars() {
+        let found = (pos..text.len()).find(|&i| text[i] == q)?;
+        if found == 0 {
+            score += 10;
+        } else if !text[found - 1].is_alphanumeric() {
+            score += 5;
+        }
+        match last {
+            Some(prev) if found == prev + 1 => score += 5,
+            Some(prev) => score -= (found - prev - 1) as i64,
+            None => score -= found as i64,
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Commands matching `query`, best first. Names and aliases are preferred
+/// over descriptions; an empty query lists every command.
+pub fn fuzzy_filter(query: &str) -> Vec<&'static CommandSpec> {
+    let query = query.trim();
+    if query.is_empty() {
+        return COMMANDS.iter().collect();
+    }
+    let mut scored: Vec<(i64, &'static CommandSpec)> = COMMANDS
+        .iter()
+        .filter_map(|spec| {
+            let by_name = std::iter::once(spec.name)
+                .chain(spec.aliases.iter().copied())
+                .filter_map(|name| fuzzy_score(query, name))
+                .max()
+                .map(|s| s + 100);
+            let by_description = fuzzy_score(query, spec.description);
+            by_name.max(by_description).map(|s| (s, spec))
+        })
+        .collect();
+    // Stable sort keeps registry order among equal scores.
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(_, spec)| spec).collect()
+}
+
+/// State of the open command palette.
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+    pub query: String,
+    pub selected: usize,
+}
+
+impl CommandPalette {
+    /// Commands matching the current query.
+    pub fn matches(&self) -> Vec<&'static CommandSpec> {
+        fuzzy_filter(&self.query)
+    }
+
+    /// The highlighted command, if anything matches.
+    pub fn selection(&self) -> Option<&'static CommandSpec> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Move the highlight, wrapping around the match list.
+    pub fn move_selection(&mut self, delta: isize) {
+        let len = self.matches().len();
+        if len == 0 {
+            self.selected = 0;
+            return;
+        }
+        self.selected = (self.selected as isize + delta).rem_euclid(len as isize) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_contains_known_commands() {
+        for name in [
+            "w", "q", "q!", "wq", "e", "goto", "set", "import", "export", "profile", "tonumber",
+            "graph", "help",
+        ] {
+            assert_eq!(lookup(name).map(|spec| spec.name), Some(name));
+        }
+        assert_eq!(lookup("save").unwrap().name, "w");
+        assert_eq!(lookup("fa").unwrap().name, "freezeall");
+        assert!(lookup("nonsense").is_none());
+    }
+
+    #[test]
+    fn test_registry_names_are_unique() {
+        let mut names: Vec<&str> = COMMANDS
+            .iter()
+            .flat_map(|spec| std::iter::once(spec.name).chain(spec.aliases.iter().copied()))
+            .collect();
+        let total = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), total);
+    }
+
+    #[test]
+    fn test_fuzzy_filter_narrows_results() {
+        assert_eq!(fuzzy_filter("").len(), COMMANDS.len());
+
+        let results = fuzzy_filter("exp");
+        assert!(results.len() < COMMANDS.len());
+        assert_eq!(results[0].name, "export");
+
+        // Subsequence match on the name
+        assert_eq!(fuzzy_filter("frzal")[0].name, "freezeall");
+        // Matches descriptions too
+        assert!(fuzzy_filter("dependency").iter().any(|s| s.name == "graph"));
+        assert!(fuzzy_filter("zzzz").is_empty());
+    }
+
+    #[test]
+    fn test_palette_selection_wraps() {
+        let mut palette = CommandPalette::default();
+        palette.push('c');
+        palette.push('o');
+        palette.push('l');
+        let count = palette.matches().len();
+        assert!(count > 1);
+        palette.move_selection(-1);
+        assert_eq!(palette.selected, count - 1);
+        palette.move_selection(1);
+        assert_eq!(palette.selected, 0);
+    }
+}
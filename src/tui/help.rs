@@ -30,6 +30,11 @@ fn vim_help_text() -> Vec<String> {
         "  /              Open regex search prompt",
         "  n / N          Next/previous search match",
         "",
+        "Auditing",
+        "  [d             Cycle through cells this formula uses",
+        "  ]d             Cycle through cells that use this cell",
+        "  gd             Go to referenced cell / function source",
+        "",
         "Editing",
         "  i / a / Enter  Edit cell (cursor at end)",
         "  I              Edit cell (cursor at start)",
@@ -41,13 +46,16 @@ fn vim_help_text() -> Vec<String> {
         "Selection & Clipboard",
         "  v              Enter visual mode (range select)",
         "  V              Select entire row",
+        "  Enter / i      Edit selection: fill each cell (array entry)",
         "  y              Yank (copy) cell or selection",
         "  yy             Yank entire row",
-        "  p              Paste at cursor",
+        "  p              Paste at cursor (in visual: tile into selection)",
         "  zf / zF        Freeze cell / freeze all formulas",
+        "  za             Collapse/expand the row group at cursor",
         "  dd             Delete entire row",
         "",
         "Undo/Redo",
+        "  .              Repeat last change at cursor",
         "  u              Undo",
         "  Ctrl+r         Redo",
         "",
@@ -55,10 +63,12 @@ fn vim_help_text() -> Vec<String> {
         "  +              Increase column width",
         "  -              Decrease column width",
         "  P              Open plot modal (chart cells)",
+        "  Ctrl+w         Switch split pane (:split)",
         "  :help / :h     Show this help",
         "",
         "Command Mode",
         "  :              Enter command mode",
+        "  Ctrl+p         Command palette (fuzzy search commands)",
     ]
     .into_iter()
     .map(str::to_string)
@@ -89,13 +99,17 @@ fn emacs_help_text() -> Vec<String> {
         "",
         "Selection & Clipboard",
         "  C-SPC          Set mark (visual mode)",
+        "  Enter          Edit selection: fill each cell (in mark)",
         "  M-w            Copy (yank)",
-        "  C-y            Paste",
+        "  C-y            Paste (with mark: tile into selection)",
         "",
         "Other",
         "  M-x            Enter command mode",
+        "  M-X            Command palette (fuzzy search commands)",
         "  C-s            Save",
         "  M-p            Open plot modal",
+        "  M-= / M--      Widen / narrow column",
+        "  C-w            Switch split pane (:split)",
     ]
     .into_iter()
     .map(str::to_string)
@@ -122,6 +136,8 @@ pub fn get_commands_help() -> Vec<String> {
         "Navigation",
         "  :goto <cell>   Go to cell (e.g. :goto A100)",
         "  :g <cell>      Alias for :goto",
+        "  :edit          Edit cell in a multi-line box (Esc commits)",
+        "  :move <cell>   Move cell, updating references to it",
         "",
         "Search",
         "  /<pattern>     Regex search in command line",
@@ -138,23 +154,50 @@ pub fn get_commands_help() -> Vec<String> {
         "  :deleterow     Alias for :dr",
         "  :insertcol     Alias for :ic",
         "  :deletecol     Alias for :dc",
+        "  :compact rows|cols  Remove empty rows/cols within used range",
         "",
         "Display",
         "  :set colwidth <n>  Set default column width",
+        "  :set locale <plain|en|eu>  Accept 1,234.56 / 1.234,56 input",
+        "  :set watch <on|off>  Auto-reload functions files on change",
+        "  :set overflow <on|off>  Let long text spill into empty cells",
+        "  :set headers <letters|numeric>  Column headers as A, B, C or 1, 2, 3",
+        "  :set volatile <cell> [on|off]  Recompute a formula every second",
+        "  :set blanks <zero|error>  Blank cells act as 0 or make formulas error",
+        "  :set freezedeps <on|off>  Deleting a cell freezes formulas using it",
+        "  :set headerrows <n>  Skip header rows in A:A ranges and :profile",
         "  :colwidth <n>  Set current column width",
         "  :cw [col] <n>  Set column width (e.g. :cw A 15)",
+        "  :colhide [col] Hide column (data is kept)",
+        "  :colshow <col|all>  Show hidden column(s)",
+        "  :merge [range] Merge selection/range into one display cell",
+        "  :align <left|center|right|clear>  Align selection/cell",
+        "  :paste [transpose]  Paste (transposed: rows become columns)",
+        "  :unmerge       Unmerge the region at the cursor",
+        "  :group [a-b]   Group selected rows (or rows a-b); za collapses",
+        "  :ungroup       Remove the row group at the cursor",
+        "  :split         Toggle a split view of two regions",
+        "  :profile [col] Summary stats and top values for a column",
+        "  :tonumber [col]  Turn numeric-looking text into numbers",
+        "  :audit         List formulas referencing empty cells or #REF!",
         "",
         "Import/Export",
         "  :import <csv>  Import CSV at cursor position",
+        "    --cols 1,3 --skip N --limit N  Import only some columns/rows",
         "  :export <csv>  Export grid (or selection) to CSV",
+        "    --excel      Add a BOM and CRLF line endings (or --bom / --crlf)",
+        "  :graph <dot>   Export the dependency graph (Graphviz DOT)",
         "  :freeze / :fr  Freeze formula/spill at cursor",
         "  :freezeall / :fa  Freeze all formulas and spills",
+        "  :recalc-selection  Recalculate selected formulas only",
         "",
         "Functions & Scripts",
         "  :source <file> Load Rhai functions file",
         "  :so            Reload loaded function files",
         "  :call <expr>   Execute Rhai function",
         "  :rhai <expr>   Execute Rhai expression",
+        "  :genfill <fn>  Fill selection with fn(index), e.g. |i| i * i",
+        "  :deflambda profit = |rev, cost| rev - cost  Define PROFIT(A1, B1)",
         "",
         "Help",
         "  :help / :h     Show this help modal",
@@ -191,6 +234,10 @@ pub fn get_functions_help() -> Vec<String> {
         "  SQRT(x)        Square root",
         "  ABS(x)         Absolute value",
         "  ROUND(n, dec)  Round to N decimal places",
+        "  ROUNDUP(n, d)  Round away from zero",
+        "  ROUNDDOWN(n, d) Round toward zero",
+        "  TRUNC(n)       Drop the fractional part",
+        "  MOD(a, b)      Remainder with the sign of b",
         "  RAND()         Random float [0, 1)",
         "  RANDINT(a, b)  Random integer [a, b]",
         "",
@@ -274,13 +321,17 @@ fn action_label(action: &Action) -> &'static str {
         Action::CommitEditDown => "Commit edit, move down",
         Action::CommitEditRight => "Commit edit, move right",
         Action::CommitEditLeft => "Commit edit, move left",
+        Action::CommitEditNextRow => "Commit edit, next row at entry start",
         Action::EnterCommand => "Command mode",
+        Action::CommandPalette => "Command palette",
         Action::ExecuteCommand => "Execute command",
         Action::EnterVisual => "Visual mode",
         Action::SelectRow => "Select entire row",
         Action::ExitVisual => "Exit visual",
         Action::Yank => "Yank (copy)",
         Action::Paste => "Paste",
+        Action::PasteTranspose => "Paste transposed",
+        Action::RepeatLastChange => "Repeat last change",
         Action::Undo => "Undo",
         Action::Redo => "Redo",
         Action::ClearCell => "Clear cell",
@@ -288,6 +339,7 @@ fn action_label(action: &Action) -> &'static str {
         Action::OpenPlot => "Open plot modal",
         Action::FreezeCell => "Freeze current formula",
         Action::FreezeAll => "Freeze all formulas",
+        Action::ToggleRowGroup => "Collapse/expand row group",
         Action::HomeDataCol => "First data column in row",
         Action::EndDataCol => "Last data column in row",
         Action::OpenRowBelowEdit => "Open row below and edit",
@@ -311,5 +363,10 @@ fn action_label(action: &Action) -> &'static str {
         Action::SearchPrompt => "Search",
         Action::SearchNext => "Next match",
         Action::SearchPrev => "Previous match",
+        Action::JumpPrecedent => "Next precedent",
+        Action::JumpDependent => "Next dependent",
+        Action::SwitchPane => "Switch split pane",
+        Action::PickRange => "Pick a range to insert",
+        Action::GotoDefinition => "Go to definition",
     }
 }
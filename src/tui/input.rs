@@ -3,6 +3,7 @@ use crossterm::event::{
 };
 use ratatui::prelude::*;
 use std::io;
+use std::time::Duration;
 
 use super::actions::{ApplyResult, apply_action, handle_command_text, handle_edit_text};
 use super::app::{App, Mode};
@@ -15,6 +16,7 @@ fn clear_pending_vim_state(app: &mut App) {
     app.pending_y = false;
     app.pending_c = false;
     app.pending_z = false;
+    app.pending_bracket = None;
     app.pending_count = None;
 }
 
@@ -27,11 +29,19 @@ fn handle_mouse_event(app: &mut App, terminal_area: Rect, mouse: MouseEvent) {
     if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
         return;
     }
-    if app.plot_modal.is_some() || app.help_modal || app.mode != Mode::Normal {
+    if app.plot_modal.is_some()
+        || app.profile_modal.is_some()
+        || app.audit_modal.is_some()
+        || app.help_modal
+        || app.command_palette.is_some()
+        || app.mode != Mode::Normal
+    {
         return;
     }
 
     let [_formula_area, grid_area, _status_area] = ui::split_main_chunks(terminal_area);
+    // Clicks only land in the focused pane of a split.
+    let (grid_area, _) = ui::pane_areas(app, grid_area);
     if let Some((col, row)) = ui::grid_cell_at(app, grid_area, mouse.column, mouse.row) {
         app.cursor_col = col;
         app.cursor_row = row;
@@ -40,10 +50,32 @@ fn handle_mouse_event(app: &mut App, terminal_area: Rect, mouse: MouseEvent) {
     }
 }
 
+/// How often the run loop polls functions file mtimes when `:set watch on`.
+const FUNCTIONS_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often volatile cells (e.g. `=VOLATILE(NOW())`) are recomputed.
+const VOLATILE_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
 pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
     loop {
         terminal.draw(|f| ui::draw(f, app))?;
 
+        // While watching functions files, wake up periodically to check mtimes.
+        if app.watch_functions && !event::poll(FUNCTIONS_WATCH_INTERVAL)? {
+            app.check_functions_changed();
+            app.core.recalc_volatile();
+            continue;
+        }
+
+        // Keep volatile cells current by redrawing on a recalc tick.
+        if !app.watch_functions
+            && app.core.has_volatile_cells()
+            && !event::poll(VOLATILE_TICK_INTERVAL)?
+        {
+            app.core.recalc_volatile();
+            continue;
+        }
+
         match event::read()? {
             Event::Key(key) => {
                 // Only process key press events (Windows reports Press + Release)
@@ -65,6 +97,57 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Res
                     continue;
                 }
 
+                // Profile modal takes over input
+                if app.profile_modal.is_some() {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => {
+                            app.close_profile_modal();
+                        }
+                        KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.close_profile_modal();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                // Audit modal takes over input
+                if app.audit_modal.is_some() {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => {
+                            app.close_audit_modal();
+                        }
+                        KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.close_audit_modal();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                // Command palette takes over input
+                if let Some(palette) = app.command_palette.as_mut() {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Esc => app.close_command_palette(),
+                        KeyCode::Char('g') if ctrl => app.close_command_palette(),
+                        KeyCode::Enter => {
+                            let quit = app.run_palette_selection();
+                            if quit {
+                                return Ok(());
+                            }
+                        }
+                        KeyCode::Up => palette.move_selection(-1),
+                        KeyCode::Down | KeyCode::Tab => palette.move_selection(1),
+                        KeyCode::Char('p' | 'k') if ctrl => palette.move_selection(-1),
+                        KeyCode::Char('n' | 'j') if ctrl => palette.move_selection(1),
+                        KeyCode::Backspace => palette.pop(),
+                        KeyCode::Char(c) if !ctrl => palette.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+
                 // Help modal takes over input
                 if app.help_modal {
                     match key.code {
@@ -124,6 +207,30 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Res
 
                 // Handle Vim key sequences (gg, dd, yy) in Normal mode
                 if matches!(app.keymap, Keymap::Vim) && app.mode == Mode::Normal {
+                    // Handle '[d' / ']d' sequences (jump to precedent / dependent)
+                    if let Some(bracket) = app.pending_bracket.take() {
+                        if key.code == KeyCode::Char('d') && key.modifiers.is_empty() {
+                            let action = if bracket == '[' {
+                                Action::JumpPrecedent
+                            } else {
+                                Action::JumpDependent
+                            };
+                            if apply_action(app, action, key) == ApplyResult::Quit {
+                                return Ok(());
+                            }
+                            continue;
+                        }
+                        // Let non-d keys fall through for normal processing.
+                    } else if let KeyCode::Char(c @ ('[' | ']')) = key.code {
+                        app.pending_bracket = Some(c);
+                        app.pending_g = false;
+                        app.pending_d = false;
+                        app.pending_y = false;
+                        app.pending_c = false;
+                        app.pending_z = false;
+                        continue;
+                    }
+
                     // Handle 'gg' sequence (go to first cell)
                     if key.code == KeyCode::Char('g') && key.modifiers.is_empty() {
                         if app.pending_g {
@@ -142,7 +249,13 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Res
                         }
                     } else if app.pending_g {
                         app.pending_g = false;
-                        if apply_action(app, Action::OpenGotoPrompt, key) == ApplyResult::Quit {
+                        // 'gd' goes to definition; any other key opens the goto prompt
+                        let action = if key.code == KeyCode::Char('d') && key.modifiers.is_empty() {
+                            Action::GotoDefinition
+                        } else {
+                            Action::OpenGotoPrompt
+                        };
+                        if apply_action(app, action, key) == ApplyResult::Quit {
                             return Ok(());
                         }
                         continue;
@@ -210,7 +323,7 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Res
                         app.pending_c = false;
                     }
 
-                    // Handle 'zf' / 'zF' sequences (freeze current / freeze all)
+                    // Handle 'zf' / 'zF' / 'za' sequences (freeze current / freeze all / toggle row group)
                     if key.code == KeyCode::Char('z') && key.modifiers.is_empty() {
                         app.pending_z = true;
                         app.pending_g = false;
@@ -233,13 +346,28 @@ pub fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Res
                                 }
                                 continue;
                             }
+                            KeyCode::Char('a') => {
+                                if apply_action(app, Action::ToggleRowGroup, key)
+                                    == ApplyResult::Quit
+                                {
+                                    return Ok(());
+                                }
+                                continue;
+                            }
                             _ => {
-                                // Let non-zf keys fall through for normal processing.
+                                // Let other keys fall through for normal processing.
                             }
                         }
                     }
                 }
 
+                // Multi-line edit: Enter inserts a newline instead of committing
+                if app.mode == Mode::Edit && app.multiline_edit && key.code == KeyCode::Enter {
+                    app.edit_buffer.insert(app.edit_cursor, '\n');
+                    app.edit_cursor += 1;
+                    continue;
+                }
+
                 if let Some(action) = translate(&app.keymap, app.mode, key) {
                     // Apply pending count to movement and paste actions
                     let count = app.pending_count.take().unwrap_or(1);
@@ -305,6 +433,7 @@ mod tests {
         app.pending_y = true;
         app.pending_c = true;
         app.pending_z = true;
+        app.pending_bracket = Some('[');
         app.pending_count = Some(3);
 
         let terminal_area = Rect::new(0, 0, 80, 24);
@@ -320,6 +449,7 @@ mod tests {
         assert!(!app.pending_y);
         assert!(!app.pending_c);
         assert!(!app.pending_z);
+        assert!(app.pending_bracket.is_none());
         assert!(app.pending_count.is_none());
     }
 
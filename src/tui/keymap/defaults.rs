@@ -9,6 +9,9 @@ pub(crate) fn translate_vim(mode: Mode, key: KeyEvent) -> Option<Action> {
             KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                 Some(Action::Redo)
             }
+            KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::SwitchPane)
+            }
 
             KeyCode::Up | KeyCode::Char('k') => Some(Action::Move(0, -1)),
             KeyCode::Down | KeyCode::Char('j') => Some(Action::Move(0, 1)),
@@ -40,10 +43,14 @@ pub(crate) fn translate_vim(mode: Mode, key: KeyEvent) -> Option<Action> {
             KeyCode::Char('x') | KeyCode::Delete => Some(Action::ClearCell),
             KeyCode::Char('S') => Some(Action::ChangeCell),
             KeyCode::Char(':') => Some(Action::EnterCommand),
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::CommandPalette)
+            }
             KeyCode::Char('v') => Some(Action::EnterVisual),
             KeyCode::Char('V') => Some(Action::SelectRow),
             KeyCode::Char('y') => Some(Action::Yank),
             KeyCode::Char('p') => Some(Action::Paste),
+            KeyCode::Char('.') => Some(Action::RepeatLastChange),
             KeyCode::Char('P') => Some(Action::OpenPlot),
             KeyCode::Char('+') | KeyCode::Char('>') => Some(Action::IncColWidth),
             KeyCode::Char('-') | KeyCode::Char('<') => Some(Action::DecColWidth),
@@ -66,10 +73,15 @@ pub(crate) fn translate_vim(mode: Mode, key: KeyEvent) -> Option<Action> {
             KeyCode::PageUp => Some(Action::Page(-1)),
             KeyCode::PageDown => Some(Action::Page(1)),
             KeyCode::Char('y') => Some(Action::Yank),
+            KeyCode::Char('p') => Some(Action::Paste),
+            KeyCode::Enter | KeyCode::Char('i') => Some(Action::EnterEdit),
             _ => None,
         },
 
         Mode::Edit => match key.code {
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::PickRange)
+            }
             KeyCode::Esc => Some(Action::CommitEdit),
             KeyCode::Enter => Some(Action::CommitEditDown),
             KeyCode::Tab if key.modifiers.contains(KeyModifiers::SHIFT) => {
@@ -131,11 +143,15 @@ pub(crate) fn translate_emacs(mode: Mode, key: KeyEvent) -> Option<Action> {
 
             // Command prompt
             KeyCode::Char('x') if alt => Some(Action::EnterCommand),
+            KeyCode::Char('X') if alt => Some(Action::CommandPalette),
             KeyCode::Char(':') => None, // strict
 
             // Save
             KeyCode::Char('s') if ctrl => Some(Action::Save),
 
+            // Split view
+            KeyCode::Char('w') if ctrl => Some(Action::SwitchPane),
+
             // Copy / Paste
             KeyCode::Char('w') if alt => Some(Action::Yank),
             KeyCode::Char('y') if ctrl => Some(Action::Paste),
@@ -150,11 +166,16 @@ pub(crate) fn translate_emacs(mode: Mode, key: KeyEvent) -> Option<Action> {
             // Plot modal
             KeyCode::Char('p') if alt => Some(Action::OpenPlot),
 
+            // Column width
+            KeyCode::Char('=') | KeyCode::Char('+') if alt => Some(Action::IncColWidth),
+            KeyCode::Char('-') if alt => Some(Action::DecColWidth),
+
             // Go to last
             KeyCode::Char('>') if alt => Some(Action::GotoLast),
 
             // Goto prompt
             KeyCode::Char('g') if alt => Some(Action::OpenGotoPrompt),
+            KeyCode::Char('.') if alt => Some(Action::GotoDefinition),
 
             // Search
             KeyCode::Char('s') if alt => Some(Action::SearchPrompt),
@@ -182,14 +203,19 @@ pub(crate) fn translate_emacs(mode: Mode, key: KeyEvent) -> Option<Action> {
             KeyCode::PageUp => Some(Action::Page(-1)),
             KeyCode::PageDown => Some(Action::Page(1)),
 
-            // Copy selection
+            // Copy selection / paste (tiled) into it
             KeyCode::Char('w') if alt => Some(Action::Yank),
+            KeyCode::Char('y') if ctrl => Some(Action::Paste),
+
+            // Array entry across the selection
+            KeyCode::Enter => Some(Action::EnterEdit),
 
             _ => None,
         },
 
         Mode::Edit => match key.code {
             KeyCode::Char('g') if ctrl => Some(Action::CommitEdit),
+            KeyCode::Char('r') if ctrl => Some(Action::PickRange),
             KeyCode::Esc => Some(Action::CommitEdit),
             KeyCode::Enter => Some(Action::CommitEditDown),
             KeyCode::Tab if key.modifiers.contains(KeyModifiers::SHIFT) => {
@@ -239,4 +265,32 @@ mod tests {
             Some(Action::OpenRowAboveEdit)
         );
     }
+
+    #[test]
+    fn column_width_keys_only_apply_in_normal_mode() {
+        let plus = KeyEvent::new(KeyCode::Char('+'), KeyModifiers::SHIFT);
+        let minus = KeyEvent::new(KeyCode::Char('-'), KeyModifiers::empty());
+
+        assert_eq!(translate_vim(Mode::Normal, plus), Some(Action::IncColWidth));
+        assert_eq!(
+            translate_vim(Mode::Normal, minus),
+            Some(Action::DecColWidth)
+        );
+        for mode in [Mode::Visual, Mode::Edit, Mode::Command] {
+            assert_eq!(translate_vim(mode, plus), None);
+            assert_eq!(translate_vim(mode, minus), None);
+        }
+
+        let alt_eq = KeyEvent::new(KeyCode::Char('='), KeyModifiers::ALT);
+        let alt_minus = KeyEvent::new(KeyCode::Char('-'), KeyModifiers::ALT);
+        assert_eq!(
+            translate_emacs(Mode::Normal, alt_eq),
+            Some(Action::IncColWidth)
+        );
+        assert_eq!(
+            translate_emacs(Mode::Normal, alt_minus),
+            Some(Action::DecColWidth)
+        );
+        assert_eq!(translate_emacs(Mode::Edit, alt_minus), None);
+    }
 }
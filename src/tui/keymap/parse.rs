@@ -352,18 +352,23 @@ fn action_from_str(input: &str) -> Option<Action> {
         "commit_edit_down" => Some(Action::CommitEditDown),
         "commit_edit_right" => Some(Action::CommitEditRight),
         "commit_edit_left" => Some(Action::CommitEditLeft),
+        "commit_edit_next_row" => Some(Action::CommitEditNextRow),
         "enter_command" => Some(Action::EnterCommand),
+        "command_palette" => Some(Action::CommandPalette),
         "execute_command" => Some(Action::ExecuteCommand),
         "enter_visual" => Some(Action::EnterVisual),
         "exit_visual" => Some(Action::ExitVisual),
         "yank" => Some(Action::Yank),
         "paste" => Some(Action::Paste),
+        "paste_transpose" => Some(Action::PasteTranspose),
+        "repeat_last_change" => Some(Action::RepeatLastChange),
         "undo" => Some(Action::Undo),
         "redo" => Some(Action::Redo),
         "clear_cell" => Some(Action::ClearCell),
         "open_plot" => Some(Action::OpenPlot),
         "freeze_cell" => Some(Action::FreezeCell),
         "freeze_all" => Some(Action::FreezeAll),
+        "toggle_row_group" => Some(Action::ToggleRowGroup),
         "home_data_col" => Some(Action::HomeDataCol),
         "end_data_col" => Some(Action::EndDataCol),
         "open_row_below_edit" => Some(Action::OpenRowBelowEdit),
@@ -384,6 +389,11 @@ fn action_from_str(input: &str) -> Option<Action> {
         "search" | "search_prompt" => Some(Action::SearchPrompt),
         "search_next" => Some(Action::SearchNext),
         "search_prev" => Some(Action::SearchPrev),
+        "jump_precedent" => Some(Action::JumpPrecedent),
+        "jump_dependent" => Some(Action::JumpDependent),
+        "switch_pane" => Some(Action::SwitchPane),
+        "pick_range" => Some(Action::PickRange),
+        "goto_definition" => Some(Action::GotoDefinition),
         _ => None,
     }
 }
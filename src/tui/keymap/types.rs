@@ -163,8 +163,13 @@ pub enum Action {
     CommitEditRight,
     /// Commit the current edit and move left.
     CommitEditLeft,
+    /// Commit the current edit and move down to the column where a run of
+    /// `CommitEditRight` entries began (spreadsheet data-entry flow).
+    CommitEditNextRow,
     /// Enter Command mode (`:` prompt).
     EnterCommand,
+    /// Open the fuzzy-searchable command palette.
+    CommandPalette,
     /// Execute the command in the command buffer.
     ExecuteCommand,
     /// Enter Visual selection mode.
@@ -177,6 +182,10 @@ pub enum Action {
     Yank,
     /// Paste clipboard at cursor position.
     Paste,
+    /// Paste clipboard at cursor position with rows and columns swapped.
+    PasteTranspose,
+    /// Repeat the last change (edit, clear, paste, row/column insert or delete) at the cursor.
+    RepeatLastChange,
     /// Undo the last action.
     Undo,
     /// Redo the last undone action.
@@ -191,6 +200,8 @@ pub enum Action {
     FreezeCell,
     /// Freeze every formula/spill in the sheet to current values.
     FreezeAll,
+    /// Collapse or expand the row group at the cursor.
+    ToggleRowGroup,
     /// Jump to the first non-empty column in the current row (or A if none).
     HomeDataCol,
     /// Jump to the last non-empty column in the current row (or A if none).
@@ -227,4 +238,14 @@ pub enum Action {
     SearchNext,
     /// Jump to previous search match.
     SearchPrev,
+    /// Cycle the cursor through the current cell's direct precedents.
+    JumpPrecedent,
+    /// Cycle the cursor through the current cell's direct dependents.
+    JumpDependent,
+    /// Focus the other pane of a split view.
+    SwitchPane,
+    /// While editing, select a range in Visual mode and insert it at the cursor.
+    PickRange,
+    /// Jump to what the current formula refers to (a cell or custom function).
+    GotoDefinition,
 }
@@ -2,6 +2,7 @@
 
 mod actions;
 mod app;
+mod commands;
 mod help;
 mod input;
 mod keymap;
@@ -1,16 +1,19 @@
 //! UI rendering
 
 use super::app::{App, Mode};
+use super::commands::CommandPalette;
 use super::help::{get_about_help, get_commands_help, get_functions_help, get_help_text};
-use gridline_engine::engine::CellRef;
+use gridline_core::{AuditWarning, ColumnProfile};
+use gridline_engine::engine::{CellRef, format_number};
 use gridline_engine::plot::{PLOT_PREFIX, PlotData, PlotKind, PlotSpec, parse_plot_spec};
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Cell, Clear, Paragraph, Row, Table, Wrap},
 };
+use std::collections::HashMap;
 use textplots::{AxisBuilder, Chart, LabelBuilder, LabelFormat, LineStyle, Plot, Shape};
 
 pub(crate) const FORMULA_BAR_HEIGHT: u16 = 3;
@@ -31,6 +34,32 @@ pub(crate) fn split_main_chunks(area: Rect) -> [Rect; 3] {
     [chunks[0], chunks[1], chunks[2]]
 }
 
+/// Grid areas of the focused pane and, while split, the unfocused one.
+/// A split stacks the two panes top and bottom with equal heights.
+pub(crate) fn pane_areas(app: &App, grid_area: Rect) -> (Rect, Option<Rect>) {
+    if app.split.is_none() {
+        return (grid_area, None);
+    }
+    let halves = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(grid_area);
+    if app.split_focus_bottom {
+        (halves[1], Some(halves[0]))
+    } else {
+        (halves[0], Some(halves[1]))
+    }
+}
+
+/// Size the viewport to the cells that fit in a grid pane.
+fn set_visible_dims(app: &mut App, grid_area: Rect) {
+    let available_width = grid_area.width.saturating_sub(ROW_HEADER_WIDTH + 2) as usize;
+    let available_height = grid_area.height.saturating_sub(3) as usize; // header + borders
+
+    app.visible_cols = (available_width / (app.col_width + 1)).max(1);
+    app.visible_rows = available_height.max(1);
+}
+
 pub(crate) fn grid_cell_at(
     app: &App,
     grid_area: Rect,
@@ -72,13 +101,7 @@ pub(crate) fn grid_cell_at(
     }
 
     let rel_row = mouse_row.saturating_sub(inner_y.saturating_add(1)) as usize;
-    if rel_row >= app.visible_rows {
-        return None;
-    }
-    let row = app.viewport_row.saturating_add(rel_row);
-    if row >= app.max_rows {
-        return None;
-    }
+    let row = *app.displayed_rows().get(rel_row)?;
 
     let row_header_end = inner_x.saturating_add(ROW_HEADER_WIDTH);
     if mouse_col < row_header_end {
@@ -92,12 +115,7 @@ pub(crate) fn grid_cell_at(
     }
     x = first_spacing_end;
 
-    for offset in 0..app.visible_cols {
-        let col = app.viewport_col + offset;
-        if col >= app.max_cols {
-            break;
-        }
-
+    for col in app.displayed_columns() {
         let width = app.get_column_width(col) as u16;
         let cell_end = x.saturating_add(width);
         if mouse_col >= x && mouse_col < cell_end && mouse_col < inner_right {
@@ -124,25 +142,47 @@ pub fn draw(f: &mut Frame, app: &mut App) {
     let chunks = split_main_chunks(f.area());
 
     // Update visible dimensions based on actual size
-    let grid_area = chunks[1];
-    let available_width = grid_area.width.saturating_sub(ROW_HEADER_WIDTH + 2) as usize;
-    let available_height = grid_area.height.saturating_sub(3) as usize; // header + borders
-
-    app.visible_cols = (available_width / (app.col_width + 1)).max(1);
-    app.visible_rows = available_height.max(1);
+    let (focused_area, other_area) = pane_areas(app, chunks[1]);
+    set_visible_dims(app, focused_area);
     app.update_viewport();
 
     draw_formula_bar(f, app, chunks[0]);
-    draw_grid(f, app, chunks[1]);
+    draw_grid(f, app, focused_area, true);
+    if let (Some(area), Some(other)) = (other_area, app.split) {
+        // Draw the unfocused pane from its own (fixed) viewport.
+        let focused = app.pane_view();
+        let dims = (app.visible_cols, app.visible_rows);
+        app.set_pane_view(other);
+        set_visible_dims(app, area);
+        draw_grid(f, app, area, false);
+        app.set_pane_view(focused);
+        (app.visible_cols, app.visible_rows) = dims;
+    }
     draw_status_bar(f, app, chunks[2]);
 
+    if app.mode == Mode::Edit && app.multiline_edit {
+        draw_multiline_editor(f, app);
+    }
+
     if let Some(spec) = app.plot_modal.clone() {
         draw_plot_modal(f, app, &spec);
     }
 
+    if let Some(profile) = &app.profile_modal {
+        draw_profile_modal(f, profile);
+    }
+
+    if let Some(warnings) = &app.audit_modal {
+        draw_audit_modal(f, warnings);
+    }
+
     if app.help_modal {
         draw_help_modal(f, app);
     }
+
+    if let Some(palette) = &app.command_palette {
+        draw_command_palette(f, palette);
+    }
 }
 
 fn draw_formula_bar(f: &mut Frame, app: &App, area: Rect) {
@@ -153,12 +193,22 @@ fn draw_formula_bar(f: &mut Frame, app: &App, area: Rect) {
         Mode::Edit => {
             // Insert cursor marker at cursor position
             let (before, after) = app.edit_buffer.split_at(app.edit_cursor);
-            format!("{}: {}│{}", cell_name, before, after)
+            // Array entry: the edit will be written across the selection
+            let target = app.get_selection_range_string().unwrap_or(cell_name);
+            format!("{}: {}│{}", target, before, after)
         }
         Mode::Command => {
             let (before, after) = app.command_buffer.split_at(app.command_cursor);
             format!(":{}│{}", before, after)
         }
+        Mode::Visual if app.range_pick.is_some() => {
+            // Preview the suspended edit with the picked range inserted
+            let pick = app.range_pick.as_ref().expect("checked above");
+            let (before, after) = pick.edit_buffer.split_at(pick.edit_cursor);
+            let origin = CellRef::new(pick.cursor_col, pick.cursor_row);
+            let range = app.picked_range_string().unwrap_or_default();
+            format!("{}: {}{}│{}", origin, before, range, after)
+        }
         Mode::Visual => {
             if let Some(range) = app.get_selection_range_string() {
                 format!("{} ({})", cell_name, range)
@@ -196,15 +246,13 @@ fn draw_formula_bar(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
-fn draw_grid(f: &mut Frame, app: &mut App, area: Rect) {
+/// Draw one grid pane. Only the focused pane shows the cursor and selection.
+fn draw_grid(f: &mut Frame, app: &mut App, area: Rect, focused: bool) {
     // Build header row
     let mut header_cells = vec![Cell::from(" ")]; // Corner
-    for col in app.viewport_col..app.viewport_col + app.visible_cols {
-        if col >= app.max_cols {
-            break;
-        }
-        let col_name = CellRef::col_to_letters(col);
-        let style = if col == app.cursor_col {
+    for col in app.displayed_columns() {
+        let col_name = app.column_header(col);
+        let style = if focused && col == app.cursor_col {
             Style::default()
                 .fg(Color::Black)
                 .bg(Color::White)
@@ -216,17 +264,25 @@ fn draw_grid(f: &mut Frame, app: &mut App, area: Rect) {
     }
     let header = Row::new(header_cells).height(1);
 
+    // Left edge of each displayed column, for drawing overflowing text
+    let inner_x = area.x.saturating_add(1);
+    let inner_right = area.x.saturating_add(area.width.saturating_sub(1));
+    let mut col_starts = HashMap::new();
+    let mut x = inner_x + ROW_HEADER_WIDTH + GRID_COLUMN_SPACING;
+    for col in app.displayed_columns() {
+        col_starts.insert(col, x);
+        x = x.saturating_add(app.get_column_width(col) as u16 + GRID_COLUMN_SPACING);
+    }
+    let mut overflows = Vec::new();
+
     // Build data rows
+    let displayed_rows = app.displayed_rows();
     let mut rows = Vec::new();
-    for row in app.viewport_row..app.viewport_row + app.visible_rows {
-        if row >= app.max_rows {
-            break;
-        }
-
+    for (rel_row, &row) in displayed_rows.iter().enumerate() {
         let mut cells = Vec::new();
 
         // Row header
-        let row_style = if row == app.cursor_row {
+        let row_style = if focused && row == app.cursor_row {
             Style::default()
                 .fg(Color::Black)
                 .bg(Color::White)
@@ -234,27 +290,40 @@ fn draw_grid(f: &mut Frame, app: &mut App, area: Rect) {
         } else {
             Style::default().fg(Color::DarkGray)
         };
-        cells.push(Cell::from(format!("{}", row + 1)).style(row_style));
+        // A collapsed group's visible first row is marked with `+`
+        let collapsed = app
+            .core
+            .collapsed_row_groups
+            .iter()
+            .any(|&(start, _)| start == row);
+        let label = if collapsed {
+            format!("{}+", row + 1)
+        } else {
+            format!("{}", row + 1)
+        };
+        cells.push(Cell::from(label).style(row_style));
 
         // Data cells
-        for col in app.viewport_col..app.viewport_col + app.visible_cols {
-            if col >= app.max_cols {
-                break;
-            }
-
+        for col in app.displayed_columns() {
             let cell_ref = CellRef::new(col, row);
-            let display = app.core.get_cell_display(&cell_ref);
+            // Merged cells are blank here; the anchor's value is drawn over the region below.
+            let display = if app.core.merged_region_at(&cell_ref).is_some() {
+                String::new()
+            } else {
+                app.core.get_cell_display(&cell_ref)
+            };
             let display = if display.starts_with(PLOT_PREFIX) {
                 plot_placeholder(&display)
             } else {
                 display
             };
 
-            let is_cursor = row == app.cursor_row && col == app.cursor_col;
-            let is_selected = if let Some(((c1, r1), (c2, r2))) = app.get_selection() {
-                row >= r1 && row <= r2 && col >= c1 && col <= c2
-            } else {
-                false
+            let is_cursor = focused && row == app.cursor_row && col == app.cursor_col;
+            let is_selected = match app.get_selection() {
+                Some(((c1, r1), (c2, r2))) if focused => {
+                    row >= r1 && row <= r2 && col >= c1 && col <= c2
+                }
+                _ => false,
             };
 
             let style = if is_cursor {
@@ -270,6 +339,19 @@ fn draw_grid(f: &mut Frame, app: &mut App, area: Rect) {
                 Style::default()
             };
 
+            let text_width = display.chars().count();
+            if app.overflow && text_width > app.get_column_width(col) {
+                let width = app.overflow_width(col, row, text_width);
+                if width > app.get_column_width(col) {
+                    let y = area.y + 2 + rel_row as u16;
+                    overflows.push((col_starts[&col], y, width as u16, display.clone(), style));
+                }
+            }
+
+            let display = match app.core.alignment(&cell_ref) {
+                Some(align) => align.pad(&display, app.get_column_width(col)),
+                None => display,
+            };
             cells.push(Cell::from(display).style(style));
         }
 
@@ -278,19 +360,76 @@ fn draw_grid(f: &mut Frame, app: &mut App, area: Rect) {
 
     // Build column widths dynamically based on per-column settings
     let mut widths = vec![Constraint::Length(ROW_HEADER_WIDTH)]; // Row header
-    for col in app.viewport_col..app.viewport_col + app.visible_cols {
-        if col >= app.max_cols {
-            break;
-        }
+    for col in app.displayed_columns() {
         widths.push(Constraint::Length(app.get_column_width(col) as u16));
     }
 
     let table = Table::new(rows, widths)
         .header(header)
-        .block(Block::default().borders(Borders::ALL).title(" Gridline "))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Gridline ")
+                .border_style(if focused {
+                    Style::default()
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                }),
+        )
         .column_spacing(GRID_COLUMN_SPACING);
 
     f.render_widget(table, area);
+
+    // Merged regions show their anchor's value centered across the visible part.
+    for (top_left, bottom_right) in app.core.merged_regions.clone() {
+        let cols: Vec<usize> = app
+            .displayed_columns()
+            .into_iter()
+            .filter(|col| (top_left.col..=bottom_right.col).contains(col))
+            .collect();
+        // Displayed lines covered by the region (collapsed rows take none)
+        let lines: Vec<usize> = displayed_rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| (top_left.row..=bottom_right.row).contains(row))
+            .map(|(rel_row, _)| rel_row)
+            .collect();
+        let (Some(&first), Some(&last)) = (cols.first(), cols.last()) else {
+            continue;
+        };
+        let (Some(&top), Some(&bottom)) = (lines.first(), lines.last()) else {
+            continue;
+        };
+        let x = col_starts[&first];
+        let right = col_starts[&last].saturating_add(app.get_column_width(last) as u16);
+        let width = right.min(inner_right).saturating_sub(x);
+        let y = area.y + 2 + top as u16;
+        let height = ((bottom - top + 1) as u16).min((area.y + area.height).saturating_sub(y + 1));
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let display = app.core.get_cell_display(&top_left);
+        let style = if display.starts_with('#') {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        };
+        let mut lines = vec![Line::from(""); (height as usize - 1) / 2];
+        lines.push(Line::from(Span::styled(display, style)));
+        f.render_widget(
+            Paragraph::new(lines).alignment(Alignment::Center),
+            Rect::new(x, y, width, height),
+        );
+    }
+
+    // Overflowing text is drawn over the empty cells it spills into.
+    for (x, y, width, text, style) in overflows {
+        let width = width.min(inner_right.saturating_sub(x));
+        if width > 0 && y < area.y + area.height.saturating_sub(1) {
+            let rect = Rect::new(x, y, width, 1);
+            f.render_widget(Paragraph::new(Span::styled(text, style)), rect);
+        }
+    }
 }
 
 fn plot_placeholder(s: &str) -> String {
@@ -452,7 +591,7 @@ fn render_textplots(data: &PlotData, width: u32, height: u32) -> String {
     chart.frame()
 }
 
-fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
+fn draw_status_bar(f: &mut Frame, app: &mut App, area: Rect) {
     let file_info = if let Some(ref path) = app.core.file_path {
         let modified_indicator = if app.core.modified { " [+]" } else { "" };
         format!("{}{}", path.display(), modified_indicator)
@@ -461,16 +600,29 @@ fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     } else {
         "[New File]".to_string()
     };
+    let file_info = match app.core.pending_recompute_count() {
+        0 => file_info,
+        pending => format!("{}  |  {} pending", file_info, pending),
+    };
 
     let help = app.keymap.status_hint();
+    let cycle = app
+        .core
+        .cycle_status_at(&CellRef::new(app.cursor_col, app.cursor_row));
 
     let status = if !app.status_message.is_empty() {
         app.status_message.clone()
+    } else if let Some(stats) = selection_stats_line(app) {
+        format!("{}  |  {}", stats, file_info)
+    } else if let Some(cycle) = &cycle {
+        format!("{}  |  {}", cycle, file_info)
     } else {
         format!("{}  |  [{}]  |  {}", file_info, app.keymap.name(), help)
     };
 
-    let style = if app.status_message.starts_with("Error") {
+    let style = if app.status_message.starts_with("Error")
+        || (app.status_message.is_empty() && cycle.is_some())
+    {
         Style::default().fg(Color::Red)
     } else if !app.status_message.is_empty() {
         Style::default().fg(Color::Yellow)
@@ -483,6 +635,158 @@ fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+/// Count/sum/average of the visual selection, shown live in the status bar.
+fn selection_stats_line(app: &mut App) -> Option<String> {
+    if app.mode != Mode::Visual {
+        return None;
+    }
+    let ((c1, r1), (c2, r2)) = app.get_selection()?;
+    let stats = app
+        .core
+        .selection_stats(&CellRef::new(c1, r1), &CellRef::new(c2, r2));
+    let average = stats
+        .average
+        .map(format_number)
+        .unwrap_or_else(|| "-".to_string());
+    Some(format!(
+        "Count: {}  Sum: {}  Avg: {}",
+        stats.count,
+        format_number(stats.sum),
+        average
+    ))
+}
+
+fn draw_multiline_editor(f: &mut Frame, app: &App) {
+    let area = centered_rect(70, 40, f.area());
+    let (before, after) = app.edit_buffer.split_at(app.edit_cursor);
+    let text = format!("{}│{}", before, after);
+    let lines: Vec<Line> = text.split('\n').map(Line::from).collect();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Edit {} (Enter: new line, Esc: commit) ",
+            app.current_cell_ref()
+        ))
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().fg(Color::White).bg(Color::Black));
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+/// Text lines for the `:profile` modal.
+pub fn profile_lines(profile: &ColumnProfile) -> Vec<String> {
+    let stat = |v: Option<f64>| v.map(format_number).unwrap_or_else(|| "-".to_string());
+    let mut lines = vec![
+        format!("Non-blank:  {}", profile.count),
+        format!("Distinct:   {}", profile.distinct),
+        format!("Numbers:    {}", profile.numeric_count),
+        format!("Min:        {}", stat(profile.min)),
+        format!("Max:        {}", stat(profile.max)),
+        format!("Mean:       {}", stat(profile.mean)),
+    ];
+    for (title, values) in [
+        ("Top numbers", &profile.top_numbers),
+        ("Top text", &profile.top_text),
+    ] {
+        if values.is_empty() {
+            continue;
+        }
+        lines.push(String::new());
+        lines.push(title.to_string());
+        for (value, count) in values {
+            lines.push(format!("  {:>5} x {}", count, value));
+        }
+    }
+    lines
+}
+
+fn draw_profile_modal(f: &mut Frame, profile: &ColumnProfile) {
+    let area = centered_rect(50, 60, f.area());
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Profile: column {} (Esc to close) ",
+            CellRef::col_to_letters(profile.col)
+        ))
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().fg(Color::White).bg(Color::Black));
+    let lines: Vec<Line> = profile_lines(profile).into_iter().map(Line::from).collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn draw_audit_modal(f: &mut Frame, warnings: &[AuditWarning]) {
+    let area = centered_rect(60, 60, f.area());
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Audit: {} warning(s) (Esc to close) ",
+            warnings.len()
+        ))
+        .border_style(Style::default().fg(Color::Yellow))
+        .style(Style::default().fg(Color::White).bg(Color::Black));
+    let lines: Vec<Line> = warnings
+        .iter()
+        .map(|warning| Line::from(warning.to_string()))
+        .collect();
+
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn draw_command_palette(f: &mut Frame, palette: &CommandPalette) {
+    let area = centered_rect(60, 60, f.area());
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Commands (Enter to run, Esc to close) ")
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().fg(Color::White).bg(Color::Black));
+
+    let mut lines = vec![Line::from(format!("> {}│", palette.query)), Line::from("")];
+    let matches = palette.matches();
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "  No matching commands",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    // Keep the highlighted entry in view.
+    let visible = (area.height as usize).saturating_sub(4).max(1);
+    let skip = (palette.selected + 1).saturating_sub(visible);
+    for (i, spec) in matches.iter().enumerate().skip(skip).take(visible) {
+        let style = if i == palette.selected {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(Span::styled(
+            format!("  {:<28} {}", spec.usage(), spec.description),
+            style,
+        )));
+    }
+
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
 fn draw_help_modal(f: &mut Frame, app: &App) {
     let area = centered_rect(88, 88, f.area());
 
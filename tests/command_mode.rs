@@ -92,7 +92,7 @@ fn test_error_exit_code() {
 #[test]
 fn test_division_by_zero() {
     let (stdout, _, code) = run_command(&["-c", "1/0"]);
-    assert!(stdout.starts_with("#ERR"));
+    assert!(stdout.starts_with("#DIV/0!"));
     assert_eq!(code, 1);
 }
 